@@ -0,0 +1,13 @@
+use criterion::{Criterion, criterion_group, criterion_main};
+use rollback_ecs::bench_scenarios;
+
+/// Runs every scenario from `bench_scenarios` under its stable name.
+fn benchmark_scenarios(c: &mut Criterion) {
+    for scenario in bench_scenarios::all() {
+        let mut step = scenario.prepare();
+        c.bench_function(scenario.name(), |b| b.iter(&mut step));
+    }
+}
+
+criterion_group!(benches, benchmark_scenarios);
+criterion_main!(benches);
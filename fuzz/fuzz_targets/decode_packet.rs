@@ -0,0 +1,28 @@
+//! Feeds arbitrary bytes to the snapshot/delta decoder. Run with
+//! `cargo +nightly fuzz run decode_packet` from the repository root.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use rollback_ecs::storage::Storage;
+use rollback_ecs::wire::{apply_delta, apply_snapshot, Packet, PacketKind};
+
+fuzz_target!(|data: &[u8]| {
+    let Ok(packet) = Packet::decode(data) else {
+        return;
+    };
+
+    for section in packet.sections() {
+        let _ = section.decode_snapshot::<u64>();
+        let _ = section.decode_delta::<u64>();
+        let _ = section.decode_snapshot::<String>();
+        let _ = section.decode_delta::<Vec<u16>>();
+    }
+
+    // Decoding into a real storage must not panic either
+    let mut storage = Storage::<rollback_ecs::entity::Entity>::new();
+    let _ = match packet.kind {
+        PacketKind::Snapshot => apply_snapshot(&mut storage, &packet),
+        PacketKind::Delta => apply_delta(&mut storage, &packet),
+    };
+});
@@ -1,10 +1,18 @@
-use std::mem::MaybeUninit;
+use alloc::boxed::Box;
+use core::mem::MaybeUninit;
 
+/// Boundary every block starts on. `data` is laid out first, so the slots of a fully
+/// occupied block form one slice aligned for SIMD loads; `#[component(align(N))]` may ask
+/// for any `N` up to this.
+pub const BLOCK_DATA_ALIGN: usize = 32;
+
+#[repr(C, align(32))]
 pub struct Block<T> {
+    pub data: [MaybeUninit<T>; 128],
     pub presence_mask: u128,
     pub absence_mask: u128,
     pub changed_mask: u128,
-    pub data: [MaybeUninit<T>; 128],
+    pub added_mask: u128,
 }
 
 pub struct RollbackBlock<T> {
@@ -13,14 +21,77 @@ pub struct RollbackBlock<T> {
     pub data: [MaybeUninit<T>; 128],
 }
 
+impl<T> Default for Block<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl<T> Block<T> {
+    /// Whether `T` is zero-sized and needs no drop, like a tag. `data` then takes no
+    /// space, and snapshotting, restoring and dropping a block only touch its masks.
+    pub const MASKS_ONLY: bool = core::mem::size_of::<T>() == 0 && !core::mem::needs_drop::<T>();
+
     pub fn new() -> Self {
         Block {
+            data: core::array::from_fn(|_| core::mem::MaybeUninit::uninit()),
             presence_mask: 0,
             absence_mask: 0,
             changed_mask: 0,
-            data: std::array::from_fn(|_| std::mem::MaybeUninit::uninit()),
+            added_mask: 0,
+        }
+    }
+
+    /// The 128 slots as one contiguous slice, if every slot is occupied.
+    pub fn as_slice(&self) -> Option<&[T]> {
+        if self.presence_mask != u128::MAX {
+            return None;
+        }
+        // Every slot is initialized
+        Some(unsafe { core::slice::from_raw_parts(self.data.as_ptr().cast::<T>(), 128) })
+    }
+
+    /// Mutable `as_slice`. Writes through it are not change-tracked, see
+    /// `Storage::dense_block_mut`.
+    pub fn as_mut_slice(&mut self) -> Option<&mut [T]> {
+        if self.presence_mask != u128::MAX {
+            return None;
         }
+        // Every slot is initialized
+        Some(unsafe { core::slice::from_raw_parts_mut(self.data.as_mut_ptr().cast::<T>(), 128) })
+    }
+
+    /// Writes zeroes over every unoccupied slot so the pages backing the block are faulted
+    /// in now rather than on first use.
+    pub fn pre_touch(&mut self) {
+        let mut free = !self.presence_mask;
+
+        while free != 0 {
+            let i = free.trailing_zeros() as usize;
+            free &= !(1u128 << i);
+
+            // Unoccupied slots hold no value, so overwriting them is sound
+            unsafe { self.data[i].as_mut_ptr().write_bytes(0, 1) };
+        }
+    }
+
+    /// Returns a copy of the occupied slots and fullness masks, with no changes recorded.
+    pub fn clone_occupied(&self) -> Self
+    where
+        T: Clone,
+    {
+        let mut block = Block::new();
+        let mut m = if Self::MASKS_ONLY { 0 } else { self.presence_mask };
+
+        while m != 0 {
+            let i = m.trailing_zeros() as usize;
+            m &= !(1u128 << i);
+            block.data[i].write(unsafe { self.data[i].assume_init_ref() }.clone());
+        }
+
+        block.presence_mask = self.presence_mask;
+        block.absence_mask = self.absence_mask;
+        block
     }
 
     pub fn restore_from(&mut self, snapshot: &RollbackBlock<T>)
@@ -28,7 +99,7 @@ impl<T> Block<T> {
         T: Clone,
     {
         // First, drop any existing data that's present
-        let mut m = self.presence_mask;
+        let mut m = if Self::MASKS_ONLY { 0 } else { self.presence_mask };
 
         while m != 0 {
             let start = m.trailing_zeros();
@@ -53,9 +124,10 @@ impl<T> Block<T> {
         self.presence_mask = snapshot.updated_mask;
         self.absence_mask = snapshot.added_mask;
         self.changed_mask = 0; // Reset changed mask on restore
+        self.added_mask = 0;
 
-        // Copy sparse data from snapshot to self
-        let mut mask = snapshot.updated_mask;
+        // Copy sparse data from snapshot to self. Zero-sized slots have nothing to copy
+        let mut mask = if Self::MASKS_ONLY { 0 } else { snapshot.updated_mask };
 
         while mask != 0 {
             let start = mask.trailing_zeros();
@@ -82,9 +154,9 @@ impl<T> Block<T> {
     where
         T: Clone,
     {
-        let mut data: [MaybeUninit<T>; 128] = std::array::from_fn(|_| MaybeUninit::uninit());
+        let mut data: [MaybeUninit<T>; 128] = core::array::from_fn(|_| MaybeUninit::uninit());
 
-        let mut mask = self.presence_mask;
+        let mut mask = if Self::MASKS_ONLY { 0 } else { self.presence_mask };
 
         while mask != 0 {
             let start = mask.trailing_zeros();
@@ -130,7 +202,7 @@ impl<T> Block<Box<Block<T>>> {
 
 impl<T> Drop for Block<T> {
     fn drop(&mut self) {
-        let mut m = self.presence_mask;
+        let mut m = if Self::MASKS_ONLY { 0 } else { self.presence_mask };
 
         unsafe {
             let ptr = self.data.as_mut_ptr();
@@ -158,7 +230,7 @@ impl<T> Drop for Block<T> {
 
 impl<T> Drop for RollbackBlock<T> {
     fn drop(&mut self) {
-        let mut m = self.updated_mask;
+        let mut m = if Block::<T>::MASKS_ONLY { 0 } else { self.updated_mask };
 
         unsafe {
             let ptr = self.data.as_mut_ptr();
@@ -224,6 +296,28 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_zero_sized_blocks_hold_only_masks() {
+        #[derive(Clone)]
+        struct Marker;
+
+        const { assert!(Block::<Marker>::MASKS_ONLY) };
+        const { assert!(!Block::<u32>::MASKS_ONLY) };
+        assert_eq!(core::mem::size_of::<Block<Marker>>(), 64);
+        assert_eq!(core::mem::size_of::<RollbackBlock<Marker>>(), 32);
+
+        let mut block = Block::<Marker>::new();
+        block.data[3].write(Marker);
+        block.presence_mask = 0b1000;
+        block.absence_mask = 0b1000;
+        let snapshot = block.snapshot();
+
+        block.presence_mask = 0b1;
+        block.restore_from(&snapshot);
+        assert_eq!(block.presence_mask, 0b1000);
+        assert_eq!(block.clone_occupied().absence_mask, 0b1000);
+    }
+
     #[test]
     fn test_restore_from() {
         // Create a block with some data
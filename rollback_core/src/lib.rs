@@ -0,0 +1,25 @@
+//! The `no_std` core of `rollback_ecs`: ticks, the block layout of the storages with
+//! their rollback snapshots, the rollback window and delta encoding, and entity index
+//! ranges. It needs only `alloc` and no threads, so it builds for sim-only servers and
+//! consoles without a full standard library.
+//!
+//! This is narrower than a full simulation core. `Storage<T>`, `SparseStorage<T>` and the
+//! rollback history of the world stay in the std-only `rollback_ecs`: a storage is generic
+//! over `Component`, whose registration, hashing and wire hooks are tied to `World`, and
+//! its side tables use `std` hash maps. The pieces here are the parts of storage and
+//! rollback that don't depend on either, so on their own they can lay out, snapshot and
+//! delta-encode blocks but not store components or run a world. Moving the storages here
+//! first needs `Component` split from the world's registration.
+//!
+//! `rollback_ecs` re-exports every module here under the same path (`rollback_ecs::tick`,
+//! `rollback_ecs::block`, ...), and the rollback items from `rollback_ecs::rollback`, so
+//! code and `system!` output written against the full crate keep compiling.
+
+#![cfg_attr(not(test), no_std)]
+
+extern crate alloc;
+
+pub mod block;
+pub mod range;
+pub mod rollback;
+pub mod tick;
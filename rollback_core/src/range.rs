@@ -0,0 +1,95 @@
+//! Entity index ranges for scoping queries, e.g. one arena per range.
+//!
+//! A `Range = start..end` clause on a `system!` query restricts it to entities whose
+//! index lies in the range. The range is turned into masks at the same three levels as the
+//! storages' (16384-slot middle blocks, 128-slot inner blocks, slots), so blocks outside
+//! the range are skipped without being visited. The clause is evaluated at the start of
+//! every run, so it may read a value that changes between ticks.
+//!
+//! Entities are indexed in spawn order, so an arena populated in one batch occupies one
+//! contiguous range. Giving each arena its own systems scoped to its range lets one
+//! scheduler pass run several arenas whose systems never touch each other's entities.
+//!
+//! # Example
+//! ```ignore
+//! const ARENA_B: std::ops::Range<u32> = 16384..32768;
+//!
+//! system! {
+//!     ArenaBGravity {
+//!         query! {
+//!             fn fall(velocity: &mut ViewMut<Velocity>) Range = ARENA_B {
+//!                 velocity.y -= 1.0;
+//!             }
+//!         }
+//!     }
+//! }
+//! ```
+
+use core::ops::Range;
+
+/// Indices per middle block.
+const MIDDLE_SPAN: u64 = 128 * 128;
+/// Indices per inner block.
+const INNER_SPAN: u64 = 128;
+
+/// A half-open range of entity indices as block masks.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct IndexRange {
+    start: u64,
+    end: u64,
+}
+
+impl IndexRange {
+    pub fn new(start: u32, end: u32) -> Self {
+        IndexRange {
+            start: start as u64,
+            end: end.max(start) as u64,
+        }
+    }
+
+    pub fn contains(&self, index: u32) -> bool {
+        (self.start..self.end).contains(&(index as u64))
+    }
+
+    /// Middle blocks overlapping the range.
+    pub fn root_mask(&self) -> u128 {
+        self.span_mask(0, MIDDLE_SPAN)
+    }
+
+    /// Inner blocks of middle block `ri` overlapping the range.
+    pub fn middle_mask(&self, ri: u32) -> u128 {
+        self.span_mask(ri as u64 * MIDDLE_SPAN, INNER_SPAN)
+    }
+
+    /// Slots of inner block `(ri, mi)` inside the range.
+    pub fn inner_mask(&self, ri: u32, mi: u32) -> u128 {
+        self.span_mask(ri as u64 * MIDDLE_SPAN + mi as u64 * INNER_SPAN, 1)
+    }
+
+    /// Bits of a 128-wide level starting at index `base`, each covering `unit` indices,
+    /// that overlap the range.
+    fn span_mask(&self, base: u64, unit: u64) -> u128 {
+        if self.start == self.end || self.end <= base || self.start >= base + 128 * unit {
+            return 0;
+        }
+        let lo = self.start.saturating_sub(base) / unit;
+        let hi = (self.end - base).div_ceil(unit).min(128);
+
+        let below_hi = if hi == 128 {
+            u128::MAX
+        } else {
+            (1u128 << hi) - 1
+        };
+        below_hi & !((1u128 << lo) - 1)
+    }
+}
+
+impl From<Range<u32>> for IndexRange {
+    fn from(range: Range<u32>) -> Self {
+        IndexRange::new(range.start, range.end)
+    }
+}
+
+#[cfg(test)]
+#[path = "range.tests.rs"]
+mod tests;
@@ -0,0 +1,27 @@
+use super::*;
+
+#[test]
+fn test_masks_cover_only_the_range() {
+    let range = IndexRange::from(130..16390);
+
+    assert_eq!(range.root_mask(), 0b11);
+    assert_eq!(range.middle_mask(0), !0b1);
+    assert_eq!(range.middle_mask(1), 0b1);
+    assert_eq!(range.middle_mask(2), 0);
+    assert_eq!(range.inner_mask(0, 0), 0);
+    assert_eq!(range.inner_mask(0, 1), !0b11);
+    assert_eq!(range.inner_mask(0, 2), u128::MAX);
+    assert_eq!(range.inner_mask(1, 0), (1u128 << 6) - 1);
+
+    assert!(range.contains(130));
+    assert!(!range.contains(16390));
+}
+
+#[test]
+fn test_empty_range_masks_nothing() {
+    let range = IndexRange::new(500, 100);
+
+    assert_eq!(range.root_mask(), 0);
+    assert_eq!(range.inner_mask(0, 3), 0);
+    assert!(!range.contains(500));
+}
@@ -0,0 +1,167 @@
+//! The storage-independent parts of rollback: the window of ticks history still covers
+//! and the byte deltas storages may keep instead of whole previous values.
+//!
+//! # Example
+//! ```ignore
+//! let delta = encode_delta(&previous, &next);
+//! assert_eq!(apply_delta(&next, &delta), previous);
+//! ```
+
+use crate::tick::Tick;
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+
+/// Range of ticks the world can currently roll back to (inclusive on both ends).
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct RollbackWindow {
+    /// Oldest tick whose state is still reconstructible from retained history.
+    pub oldest: Tick,
+    /// The world's current tick.
+    pub newest: Tick,
+}
+
+impl RollbackWindow {
+    /// Returns true if `tick` can be restored from retained history.
+    pub fn contains(&self, tick: Tick) -> bool {
+        !tick.is_before(self.oldest)
+    }
+
+    /// Number of ticks of history retained.
+    pub fn depth(&self) -> u32 {
+        self.newest.diff(self.oldest).value().max(0) as u32
+    }
+}
+
+/// Components whose rollback snapshots can be stored as byte deltas, see
+/// `Storage::enable_delta_snapshots`.
+///
+/// A previous value is stored as its XOR with the next value recorded for the same slot,
+/// keeping only the runs of bytes that differ, and rebuilt from that value on rollback.
+/// Large components with a few fields changing per tick shrink to those fields.
+///
+/// # Safety
+/// The bytes of the type must be plain data: no padding, no pointers or references, and
+/// every bit pattern a valid value. `#[repr(C)]` structs of integers and floats without
+/// gaps qualify.
+///
+/// # Example
+/// ```ignore
+/// #[derive(Component, Clone, Copy, Default)]
+/// #[repr(C)]
+/// struct Skeleton {
+///     bones: [[i32; 4]; 64],
+/// }
+///
+/// unsafe impl DeltaCompressible for Skeleton {}
+///
+/// world.enable_delta_snapshots::<Skeleton>();
+/// ```
+pub unsafe trait DeltaCompressible: Copy + 'static {}
+
+macro_rules! delta_compressible {
+    ($($ty:ty),*) => {
+        $(unsafe impl DeltaCompressible for $ty {})*
+    };
+}
+
+delta_compressible!(u8, u16, u32, u64, u128, i8, i16, i32, i64, i128, f32, f64);
+
+unsafe impl<T: DeltaCompressible, const N: usize> DeltaCompressible for [T; N] {}
+
+fn bytes_of<T: DeltaCompressible>(value: &T) -> &[u8] {
+    // SAFETY: `DeltaCompressible` types have no padding, so every byte is initialized
+    unsafe { core::slice::from_raw_parts(value as *const T as *const u8, size_of::<T>()) }
+}
+
+fn push_varint(out: &mut Vec<u8>, mut value: usize) {
+    while value >= 0x80 {
+        out.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+fn read_varint(bytes: &[u8], at: &mut usize) -> usize {
+    let mut value = 0;
+    let mut shift = 0;
+    loop {
+        let byte = bytes[*at];
+        *at += 1;
+        value |= ((byte & 0x7F) as usize) << shift;
+        if byte & 0x80 == 0 {
+            return value;
+        }
+        shift += 7;
+    }
+}
+
+/// Encodes `value` as a delta against `base`: `(skip, len, xor bytes)` runs covering the
+/// bytes that differ. Equal values encode to nothing.
+pub fn encode_delta<T: DeltaCompressible>(value: &T, base: &T) -> Box<[u8]> {
+    let (value, base) = (bytes_of(value), bytes_of(base));
+    let mut out = Vec::new();
+    let mut at = 0;
+    while at < value.len() {
+        let Some(skip) = (at..value.len()).find(|&i| value[i] != base[i]) else {
+            break;
+        };
+        let end = (skip..value.len())
+            .find(|&i| value[i] == base[i])
+            .unwrap_or(value.len());
+        push_varint(&mut out, skip - at);
+        push_varint(&mut out, end - skip);
+        out.extend((skip..end).map(|i| value[i] ^ base[i]));
+        at = end;
+    }
+    out.into_boxed_slice()
+}
+
+/// Rebuilds the value `encode_delta(value, base)` was computed from.
+pub fn apply_delta<T: DeltaCompressible>(base: &T, delta: &[u8]) -> T {
+    let mut value = *base;
+    // SAFETY: as in `bytes_of`, and any bit pattern is a valid `T`
+    let bytes = unsafe {
+        core::slice::from_raw_parts_mut(&mut value as *mut T as *mut u8, size_of::<T>())
+    };
+    let (mut at, mut read) = (0, 0);
+    while read < delta.len() {
+        at += read_varint(delta, &mut read);
+        let len = read_varint(delta, &mut read);
+        for (byte, xor) in bytes[at..at + len].iter_mut().zip(&delta[read..read + len]) {
+            *byte ^= xor;
+        }
+        at += len;
+        read += len;
+    }
+    value
+}
+
+/// `encode_delta` and `apply_delta` for one component type, kept by storages with delta
+/// snapshots enabled.
+pub struct DeltaCodec<T> {
+    pub encode: fn(&T, &T) -> Box<[u8]>,
+    pub apply: fn(&T, &[u8]) -> T,
+}
+
+impl<T> Clone for DeltaCodec<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for DeltaCodec<T> {}
+
+impl<T: DeltaCompressible> DeltaCodec<T> {
+    pub fn new() -> Self {
+        DeltaCodec {
+            encode: encode_delta::<T>,
+            apply: apply_delta::<T>,
+        }
+    }
+}
+
+impl<T: DeltaCompressible> Default for DeltaCodec<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -1,13 +1,15 @@
-use std::fmt;
-use std::ops::{Add, Sub};
+use core::fmt;
+use core::ops::{Add, Sub};
 
 /// Absolute tick in modular 32-bit time.
 #[derive(Copy, Clone, Eq, PartialEq, Hash, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Tick(pub u32);
 
 /// Signed linear delta between two ticks.
 /// Range: -(2^31) ..= +(2^31 - 1)
 #[derive(Copy, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct TickDelta(pub i32);
 
 impl Tick {
@@ -38,11 +40,13 @@ impl Tick {
     }
 
     /// Add a tick delta with wrapping.
+    #[allow(clippy::should_implement_trait)]
     pub fn add(self, delta: TickDelta) -> Tick {
         Tick(self.0.wrapping_add(delta.0 as u32))
     }
 
     /// Subtract a tick delta with wrapping.
+    #[allow(clippy::should_implement_trait)]
     pub fn sub(self, delta: TickDelta) -> Tick {
         Tick(self.0.wrapping_sub(delta.0 as u32))
     }
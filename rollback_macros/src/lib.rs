@@ -1,16 +1,104 @@
 use proc_macro::TokenStream;
 use quote::{format_ident, quote};
 use std::collections::HashMap;
+use std::collections::hash_map::Entry;
 use syn::{
     braced,
     parse::{Parse, ParseStream},
     parse_macro_input, Block, DeriveInput, Ident, Result, Token, Type,
 };
 
+#[derive(Clone)]
 struct ViewArg {
     ident: Ident,
     ty: Type,
     is_mut: bool,
+    param: Option<ParamKind>,
+}
+
+/// Query parameters that are not per-entity component views.
+#[derive(Clone)]
+enum ParamKind {
+    /// `name: Mailbox<M, Target>` - sends messages to a later system. `target` is boxed so
+    /// the variant isn't twice the size of the others
+    Mailbox { msg: Type, target: Box<Type> },
+    /// `name: Inbox<M>` - receives messages addressed to this system
+    Inbox { msg: Type },
+    /// `name: EntityRng` - deterministic random stream for the current entity
+    EntityRng,
+    /// `name: Aggregate<T>` - read-only traversal of the whole storage of `T`
+    Aggregate { ty: Type },
+    /// `name: Effects<E>` - emits rollback-deduplicated side effects
+    Effects { effect: Type },
+    /// `name: Entity` - handle of the entity being visited
+    Entity,
+    /// `name: Res<T>` - shared access to the world resource `T`
+    Res { ty: Type },
+    /// `name: ResMut<T>` - exclusive, rollback-logged access to the world resource `T`
+    ResMut { ty: Type },
+    /// `name: RemovedEvents<T>` - components removed by `Remove = [T]` queries this tick
+    RemovedEvents { ty: Type },
+    /// `name: PlayerInput<I>` - every player's input of type `I` for the current tick
+    PlayerInput { ty: Type },
+    /// `name: ParentView<T>` - the `T` of the current entity's parent, passed as `View<T>`;
+    /// entities whose parent lacks `T` are skipped
+    ParentView { ty: Type },
+}
+
+fn parse_param_kind(ty: &Type) -> Option<ParamKind> {
+    let Type::Path(tp) = ty else {
+        return None;
+    };
+    let seg = tp.path.segments.last()?;
+    if seg.ident == "EntityRng" && seg.arguments.is_empty() {
+        return Some(ParamKind::EntityRng);
+    }
+    if seg.ident == "Entity" && seg.arguments.is_empty() {
+        return Some(ParamKind::Entity);
+    }
+    // `Rng` is shorthand for the shared, rolled back random stream
+    if seg.ident == "Rng" && seg.arguments.is_empty() {
+        let ty = syn::parse_quote!(::rollback_ecs::rng::RollbackRng);
+        return Some(ParamKind::ResMut { ty });
+    }
+    let syn::PathArguments::AngleBracketed(ab) = &seg.arguments else {
+        return None;
+    };
+    let mut types = ab.args.iter().filter_map(|a| match a {
+        syn::GenericArgument::Type(t) => Some(t.clone()),
+        _ => None,
+    });
+    if seg.ident == "Mailbox" {
+        let msg = types.next()?;
+        let target = Box::new(types.next()?);
+        Some(ParamKind::Mailbox { msg, target })
+    } else if seg.ident == "Inbox" {
+        let msg = types.next()?;
+        Some(ParamKind::Inbox { msg })
+    } else if seg.ident == "Aggregate" {
+        let ty = types.next()?;
+        Some(ParamKind::Aggregate { ty })
+    } else if seg.ident == "Effects" {
+        let effect = types.next()?;
+        Some(ParamKind::Effects { effect })
+    } else if seg.ident == "Res" {
+        let ty = types.next()?;
+        Some(ParamKind::Res { ty })
+    } else if seg.ident == "ResMut" {
+        let ty = types.next()?;
+        Some(ParamKind::ResMut { ty })
+    } else if seg.ident == "RemovedEvents" {
+        let ty = types.next()?;
+        Some(ParamKind::RemovedEvents { ty })
+    } else if seg.ident == "PlayerInput" {
+        let ty = types.next()?;
+        Some(ParamKind::PlayerInput { ty })
+    } else if seg.ident == "ParentView" {
+        let ty = types.next()?;
+        Some(ParamKind::ParentView { ty })
+    } else {
+        None
+    }
 }
 
 fn parse_view_args(input: ParseStream) -> Result<Vec<ViewArg>> {
@@ -20,6 +108,21 @@ fn parse_view_args(input: ParseStream) -> Result<Vec<ViewArg>> {
         input.parse::<Token![:]>()?;
         let ty_view: Type = input.parse()?;
 
+        if let Some(kind) = parse_param_kind(&ty_view) {
+            args.push(ViewArg {
+                ident,
+                ty: ty_view,
+                is_mut: false,
+                param: Some(kind),
+            });
+            if input.peek(Token![,]) {
+                input.parse::<Token![,]>()?;
+                continue;
+            } else {
+                break;
+            }
+        }
+
         // Check if it's &mut ViewMut<T> or just View<T>/ViewMut<T>
         let (ty_inner, is_mut): (Type, bool) = match ty_view {
             Type::Reference(ref tr) => {
@@ -73,6 +176,7 @@ fn parse_view_args(input: ParseStream) -> Result<Vec<ViewArg>> {
             ident,
             ty: ty_inner,
             is_mut,
+            param: None,
         });
         if input.peek(Token![,]) {
             input.parse::<Token![,]>()?;
@@ -83,6 +187,56 @@ fn parse_view_args(input: ParseStream) -> Result<Vec<ViewArg>> {
     Ok(args)
 }
 
+/// Run conditions of a system or pipeline group: `RunIf`, `InState`, `OnEnter`, `OnExit`.
+#[derive(Default)]
+struct RunConditions {
+    run_if: Option<Type>,
+    in_state: Option<syn::Expr>,
+    on_enter: Option<syn::Expr>,
+    on_exit: Option<syn::Expr>,
+}
+
+impl RunConditions {
+    /// Parses the value of clause `kw` if it is one of the condition clauses.
+    fn parse_clause(&mut self, kw: &Ident, input: ParseStream) -> Result<bool> {
+        let slot = if kw == "InState" {
+            &mut self.in_state
+        } else if kw == "OnEnter" {
+            &mut self.on_enter
+        } else if kw == "OnExit" {
+            &mut self.on_exit
+        } else if kw == "RunIf" {
+            input.parse::<Token![=]>()?;
+            self.run_if = Some(input.parse()?);
+            return Ok(true);
+        } else {
+            return Ok(false);
+        };
+        input.parse::<Token![=]>()?;
+        // The state is followed by the query body, which must not parse as a struct literal
+        *slot = Some(syn::Expr::parse_without_eager_brace(input)?);
+        Ok(true)
+    }
+
+    /// One boolean expression per condition, evaluated against `world`.
+    fn checks(&self) -> Vec<proc_macro2::TokenStream> {
+        let mut checks = Vec::new();
+        if let Some(condition) = &self.run_if {
+            checks.push(quote!(<#condition as ::rollback_ecs::scheduler::RunCondition>::should_run(world)));
+        }
+        if let Some(state) = &self.in_state {
+            checks.push(quote!(::rollback_ecs::state::in_state(world, &(#state))));
+        }
+        if let Some(state) = &self.on_enter {
+            checks.push(quote!(::rollback_ecs::state::on_enter(world, &(#state))));
+        }
+        if let Some(state) = &self.on_exit {
+            checks.push(quote!(::rollback_ecs::state::on_exit(world, &(#state))));
+        }
+        checks
+    }
+}
+
 fn parse_type_list_bracketed(input: ParseStream) -> Result<Vec<Type>> {
     let content;
     syn::bracketed!(content in input);
@@ -99,6 +253,37 @@ fn parse_type_list_bracketed(input: ParseStream) -> Result<Vec<Type>> {
     Ok(tys)
 }
 
+fn parse_type_groups_bracketed(input: ParseStream) -> Result<Vec<Vec<Type>>> {
+    let content;
+    syn::bracketed!(content in input);
+    let mut groups = Vec::new();
+    while !content.is_empty() {
+        groups.push(parse_type_list_bracketed(&content)?);
+        if content.peek(Token![,]) {
+            content.parse::<Token![,]>()?;
+        } else {
+            break;
+        }
+    }
+    Ok(groups)
+}
+
+fn parse_expr_list_bracketed(input: ParseStream) -> Result<Vec<syn::Expr>> {
+    let content;
+    syn::bracketed!(content in input);
+    let mut exprs = Vec::new();
+    while !content.is_empty() {
+        let expr: syn::Expr = content.parse()?;
+        exprs.push(expr);
+        if content.peek(Token![,]) {
+            content.parse::<Token![,]>()?;
+        } else {
+            break;
+        }
+    }
+    Ok(exprs)
+}
+
 struct SystemInput {
     stage_ident: Ident,
     fn_ident: Ident,
@@ -106,11 +291,20 @@ struct SystemInput {
     all_types: Vec<Type>,
     none_types: Vec<Type>,
     any_types: Vec<Type>,
+    or_groups: Vec<Vec<Type>>,
     changed_types: Vec<Type>,
+    changed_since: Option<syn::Expr>,
+    added_types: Vec<Type>,
+    was_removed_types: Vec<Type>,
     remove_types: Vec<Type>,
+    has_tags: Vec<syn::Expr>,
+    not_tags: Vec<syn::Expr>,
     parent: Option<Type>,
     after: Vec<Type>,
     before: Vec<Type>,
+    range: Option<syn::Expr>,
+    parallel: bool,
+    conditions: RunConditions,
     body: Block,
 }
 
@@ -143,11 +337,20 @@ impl Parse for SystemInput {
         let mut all_types = Vec::new();
         let mut none_types = Vec::new();
         let mut any_types = Vec::new();
+        let mut or_groups = Vec::new();
         let mut changed_types = Vec::new();
+        let mut changed_since = None;
+        let mut added_types = Vec::new();
+        let mut was_removed_types = Vec::new();
         let mut remove_types = Vec::new();
+        let mut has_tags = Vec::new();
+        let mut not_tags = Vec::new();
         let mut parent = None;
         let mut after = Vec::new();
         let mut before = Vec::new();
+        let mut range = None;
+        let mut parallel = false;
+        let mut conditions = RunConditions::default();
         while inner.peek(Ident) {
             let kw: Ident = inner.parse()?;
             if kw == "All" {
@@ -159,12 +362,30 @@ impl Parse for SystemInput {
             } else if kw == "Any" {
                 inner.parse::<Token![=]>()?;
                 any_types = parse_type_list_bracketed(&inner)?;
+            } else if kw == "Or" {
+                inner.parse::<Token![=]>()?;
+                or_groups = parse_type_groups_bracketed(&inner)?;
             } else if kw == "Changed" {
                 inner.parse::<Token![=]>()?;
                 changed_types = parse_type_list_bracketed(&inner)?;
+            } else if kw == "ChangedSince" {
+                inner.parse::<Token![=]>()?;
+                changed_since = Some(syn::Expr::parse_without_eager_brace(&inner)?);
+            } else if kw == "Added" {
+                inner.parse::<Token![=]>()?;
+                added_types = parse_type_list_bracketed(&inner)?;
+            } else if kw == "WasRemoved" {
+                inner.parse::<Token![=]>()?;
+                was_removed_types = parse_type_list_bracketed(&inner)?;
             } else if kw == "Remove" {
                 inner.parse::<Token![=]>()?;
                 remove_types = parse_type_list_bracketed(&inner)?;
+            } else if kw == "Has" {
+                inner.parse::<Token![=]>()?;
+                has_tags = parse_expr_list_bracketed(&inner)?;
+            } else if kw == "Not" {
+                inner.parse::<Token![=]>()?;
+                not_tags = parse_expr_list_bracketed(&inner)?;
             } else if kw == "Parent" {
                 inner.parse::<Token![=]>()?;
                 let parent_type: Type = inner.parse()?;
@@ -175,6 +396,14 @@ impl Parse for SystemInput {
             } else if kw == "Before" {
                 inner.parse::<Token![=]>()?;
                 before = parse_type_list_bracketed(&inner)?;
+            } else if kw == "Range" {
+                inner.parse::<Token![=]>()?;
+                // `Range = ARENA { ... }` must not parse as a struct literal
+                range = Some(syn::Expr::parse_without_eager_brace(&inner)?);
+            } else if kw == "Parallel" {
+                inner.parse::<Token![=]>()?;
+                parallel = inner.parse::<syn::LitBool>()?.value;
+            } else if conditions.parse_clause(&kw, &inner)? {
             } else {
                 break;
             }
@@ -187,11 +416,20 @@ impl Parse for SystemInput {
             all_types,
             none_types,
             any_types,
+            or_groups,
             changed_types,
+            changed_since,
+            added_types,
+            was_removed_types,
             remove_types,
+            has_tags,
+            not_tags,
             parent,
             after,
             before,
+            range,
+            parallel,
+            conditions,
             body,
         })
     }
@@ -203,17 +441,105 @@ pub fn system(input: TokenStream) -> TokenStream {
 
     let stage_ident = parsed.stage_ident;
     let fn_ident = parsed.fn_ident;
-    let view_args = parsed.view_args;
+    let query_args = parsed.view_args;
+    let view_args: Vec<ViewArg> = query_args
+        .iter()
+        .filter(|a| a.param.is_none())
+        .cloned()
+        .collect();
+    let param_args: Vec<ViewArg> = query_args
+        .iter()
+        .filter(|a| a.param.is_some())
+        .cloned()
+        .collect();
+
+    // EntityRng, Entity and ParentView are per entity, so they need an entity to iterate over
+    if view_args.is_empty() {
+        if let Some(arg) = param_args.iter().find(|a| {
+            matches!(
+                a.param,
+                Some(ParamKind::EntityRng | ParamKind::Entity | ParamKind::ParentView { .. })
+            )
+        }) {
+            let name = match arg.param {
+                Some(ParamKind::Entity) => "Entity",
+                Some(ParamKind::ParentView { .. }) => "ParentView",
+                _ => "EntityRng",
+            };
+            return syn::Error::new(
+                arg.ident.span(),
+                format!("{} requires at least one View or ViewMut parameter", name),
+            )
+            .to_compile_error()
+            .into();
+        }
+    }
+    // Query stages get a generated `count` method
+    if fn_ident == "count" {
+        return syn::Error::new(
+            fn_ident.span(),
+            "a query function cannot be named `count`, it would clash with the generated Stage::count()",
+        )
+        .to_compile_error()
+        .into();
+    }
     let all_types = parsed.all_types;
     let none_types = parsed.none_types;
     let any_types = parsed.any_types;
+    let or_groups = parsed.or_groups;
     let changed_types = parsed.changed_types;
+    let changed_since = parsed.changed_since;
+    if changed_types.is_empty() {
+        if let Some(expr) = &changed_since {
+            return syn::Error::new_spanned(expr, "ChangedSince requires Changed=[...]")
+                .to_compile_error()
+                .into();
+        }
+    }
+    let added_types = parsed.added_types;
+    let was_removed_types = parsed.was_removed_types;
     let remove_types = parsed.remove_types;
+    let has_tags = parsed.has_tags;
+    let not_tags = parsed.not_tags;
     let parent = parsed.parent;
     let after = parsed.after;
     let before = parsed.before;
+    let range = parsed.range;
+    let parallel = parsed.parallel;
+    let conditions = parsed.conditions;
     let body = parsed.body;
 
+    // Parallel blocks run concurrently, so only per-entity or read-only parameters are allowed
+    if parallel {
+        if view_args.is_empty() {
+            return syn::Error::new(
+                fn_ident.span(),
+                "Parallel = true requires at least one View or ViewMut parameter",
+            )
+            .to_compile_error()
+            .into();
+        }
+        if let Some(arg) = param_args.iter().find(|a| {
+            matches!(
+                a.param,
+                Some(
+                    ParamKind::Mailbox { .. }
+                        | ParamKind::Inbox { .. }
+                        | ParamKind::Effects { .. }
+                        | ParamKind::ResMut { .. }
+                        | ParamKind::RemovedEvents { .. }
+                )
+            )
+        }) {
+            return syn::Error::new(
+                arg.ident.span(),
+                "Parallel = true cannot be combined with Mailbox, Inbox, Effects, ResMut or RemovedEvents parameters",
+            )
+            .to_compile_error()
+            .into();
+        }
+    }
+
     let view_types: Vec<Type> = view_args.iter().map(|v| v.ty.clone()).collect();
 
     // Build unique storage set per type with mutability if any usage requires it
@@ -231,16 +557,17 @@ pub fn system(input: TokenStream) -> TokenStream {
     };
     let mut push_unique = |t: &Type| {
         let key = quote!(#t).to_string();
-        if !type_index.contains_key(&key) {
-            let idx = unique_types.len();
-            unique_types.push(t.clone());
-            unique_mut_flags.push(requires_mut(t));
-            type_index.insert(key, idx);
-        } else {
-            // upgrade to mutable if new usage requires mut
-            let idx = *type_index.get(&key).expect("Type index should exist in map since we checked contains_key");
-            if requires_mut(t) {
-                unique_mut_flags[idx] = true;
+        match type_index.entry(key) {
+            Entry::Vacant(entry) => {
+                entry.insert(unique_types.len());
+                unique_types.push(t.clone());
+                unique_mut_flags.push(requires_mut(t));
+            }
+            Entry::Occupied(entry) => {
+                // upgrade to mutable if new usage requires mut
+                if requires_mut(t) {
+                    unique_mut_flags[*entry.get()] = true;
+                }
             }
         }
     };
@@ -253,15 +580,44 @@ pub fn system(input: TokenStream) -> TokenStream {
     for t in &any_types {
         push_unique(t);
     }
+    for t in or_groups.iter().flatten() {
+        push_unique(t);
+    }
     for t in &changed_types {
         push_unique(t);
     }
+    for t in &added_types {
+        push_unique(t);
+    }
+    for t in &was_removed_types {
+        push_unique(t);
+    }
     for t in &remove_types {
         push_unique(t);
     }
     for t in &view_types {
         push_unique(t);
     }
+    // Has/Not tag clauses filter on the TagSet storage; reuse it if the query already names it
+    let tagset_type: Option<Type> = if has_tags.is_empty() && not_tags.is_empty() {
+        None
+    } else {
+        let existing = view_types
+            .iter()
+            .chain(all_types.iter())
+            .chain(none_types.iter())
+            .chain(any_types.iter())
+            .chain(changed_types.iter())
+            .chain(added_types.iter())
+            .find(|t| match t {
+                Type::Path(tp) => tp.path.segments.last().is_some_and(|s| s.ident == "TagSet"),
+                _ => false,
+            })
+            .cloned();
+        let t: Type = existing.unwrap_or_else(|| syn::parse_quote!(::rollback_ecs::tags::TagSet));
+        push_unique(&t);
+        Some(t)
+    };
     let unique_idents: Vec<Ident> = (0..unique_types.len())
         .map(|i| format_ident!("storage{}", i + 1))
         .collect();
@@ -273,21 +629,61 @@ pub fn system(input: TokenStream) -> TokenStream {
     };
 
     let view_storage_idents: Vec<Ident> = view_types.iter().map(resolve_storage_ident).collect();
-    let all_storage_idents: Vec<Ident> = all_types.iter().map(resolve_storage_ident).collect();
+    let mut all_storage_idents: Vec<Ident> = all_types.iter().map(resolve_storage_ident).collect();
+    let tagset_ident: Option<Ident> = tagset_type.as_ref().map(resolve_storage_ident);
+    // Has=[...] requires the TagSet component, so it participates in the All intersections
+    if !has_tags.is_empty() {
+        if let Some(ref ti) = tagset_ident {
+            all_storage_idents.push(ti.clone());
+        }
+    }
     let none_storage_idents: Vec<Ident> = none_types.iter().map(resolve_storage_ident).collect();
     let any_storage_idents: Vec<Ident> = any_types.iter().map(resolve_storage_ident).collect();
+    let or_storage_idents: Vec<Vec<Ident>> = or_groups
+        .iter()
+        .map(|group| group.iter().map(resolve_storage_ident).collect())
+        .collect();
     let changed_storage_idents: Vec<Ident> =
         changed_types.iter().map(resolve_storage_ident).collect();
+    let added_storage_idents: Vec<Ident> = added_types.iter().map(resolve_storage_ident).collect();
+    let was_removed_storage_idents: Vec<Ident> =
+        was_removed_types.iter().map(resolve_storage_ident).collect();
     let remove_storage_idents: Vec<Ident> =
         remove_types.iter().map(resolve_storage_ident).collect();
 
     // Deprecated per-type field idents; using unique storages instead
 
     // Generate the actual function definition (as an associated function, not a method)
-    let fn_inputs = view_args.iter().map(|va| {
+    let fn_inputs = query_args.iter().map(|va| {
         let vi = &va.ident;
         let ty = &va.ty;
-        if va.is_mut {
+        if let Some(ref kind) = va.param {
+            match kind {
+                ParamKind::Mailbox { msg, target } => {
+                    quote!(#vi: &::rollback_ecs::mailbox::Mailbox<#msg, #target>)
+                }
+                ParamKind::Inbox { msg } => quote!(#vi: &::rollback_ecs::mailbox::Inbox<#msg>),
+                ParamKind::EntityRng => quote!(#vi: &mut ::rollback_ecs::rng::EntityRng),
+                ParamKind::Aggregate { ty } => {
+                    quote!(#vi: &::rollback_ecs::view::Aggregate<#ty>)
+                }
+                ParamKind::Effects { effect } => {
+                    quote!(#vi: &::rollback_ecs::effects::Effects<#effect>)
+                }
+                ParamKind::Entity => quote!(#vi: ::rollback_ecs::entity::Entity),
+                ParamKind::Res { ty } => quote!(#vi: &::rollback_ecs::resource::Res<#ty>),
+                ParamKind::ResMut { ty } => {
+                    quote!(#vi: &mut ::rollback_ecs::resource::ResMut<#ty>)
+                }
+                ParamKind::RemovedEvents { ty } => {
+                    quote!(#vi: &::rollback_ecs::removal::RemovedEvents<#ty>)
+                }
+                ParamKind::PlayerInput { ty } => {
+                    quote!(#vi: &::rollback_ecs::input::PlayerInput<#ty>)
+                }
+                ParamKind::ParentView { ty } => quote!(#vi: ::rollback_ecs::view::View<#ty>),
+            }
+        } else if va.is_mut {
             quote!(#vi: &mut ::rollback_ecs::view::ViewMut<#ty>)
         } else {
             quote!(#vi: ::rollback_ecs::view::View<#ty>)
@@ -301,9 +697,9 @@ pub fn system(input: TokenStream) -> TokenStream {
     // Tuple type: one per unique type with mutability as required
     let unique_tuple_types = unique_types.iter().enumerate().map(|(i, t)| {
         if unique_mut_flags[i] {
-            quote!(&mut ::rollback_ecs::storage::Storage<#t>)
+            quote!(&mut <#t as ::rollback_ecs::component::Component>::Storage)
         } else {
-            quote!(&::rollback_ecs::storage::Storage<#t>)
+            quote!(&<#t as ::rollback_ecs::component::Component>::Storage)
         }
     });
     let _args_tuple_type = quote!( ( #( #unique_tuple_types ),* ) );
@@ -316,11 +712,11 @@ pub fn system(input: TokenStream) -> TokenStream {
     let outer_intersections = {
         let view_intersections = view_storage_idents
             .iter()
-            .map(|si| quote!( outer_mask &= #si.root.presence_mask; ));
+            .map(|si| quote!( outer_mask &= #si.root_mask(); ));
 
         let all_intersections = all_storage_idents
             .iter()
-            .map(|si| quote!( outer_mask &= #si.root.presence_mask; ));
+            .map(|si| quote!( outer_mask &= #si.root_mask(); ));
 
         quote! {
             #( #view_intersections )*
@@ -331,30 +727,24 @@ pub fn system(input: TokenStream) -> TokenStream {
     let outer_none = if none_types.is_empty() {
         quote!()
     } else {
-        // For None queries, use absence_mask at root level to skip entire middle blocks
-        // that are full of excluded components. If a root block's absence_mask bit is set,
-        // that means ALL entities in that middle block (16K entities) have the excluded component.
-        // We can use absence_mask directly without checking presence_mask first because:
-        // - If block doesn't exist, absence_mask bit won't be set
-        // - If block exists but isn't full, absence_mask bit won't be set
-        // - If block exists and is full, absence_mask bit will be set
+        // For None queries, use the root full mask to skip entire middle blocks that are
+        // full of excluded components: ALL entities in such a middle block (16K entities)
+        // have the excluded component.
         let per_none_outer = none_storage_idents.iter().map(|ni| {
             quote! {
-                // Use absence_mask directly - no need to check presence_mask first
-                // If the root block exists and the middle block is full, absence_mask will be set
-                none_outer |= #ni.root.absence_mask;
+                none_outer |= #ni.root_full_mask();
             }
         });
         quote! { let mut none_outer: u128 = 0; #(#per_none_outer)* outer_mask &= !none_outer; }
     };
     let middle_intersections_views = {
-        let view_intersections = view_storage_idents.iter().map(|si| {
-            quote!( middle_mask &= unsafe { #si.root.data[oi as usize].assume_init_ref().presence_mask }; )
-        });
+        let view_intersections = view_storage_idents
+            .iter()
+            .map(|si| quote!( middle_mask &= #si.middle_mask(oi); ));
 
-        let all_intersections = all_storage_idents.iter().map(|si| {
-            quote!( middle_mask &= unsafe { #si.root.data[oi as usize].assume_init_ref().presence_mask }; )
-        });
+        let all_intersections = all_storage_idents
+            .iter()
+            .map(|si| quote!( middle_mask &= #si.middle_mask(oi); ));
 
         quote! {
             #( #view_intersections )*
@@ -362,13 +752,13 @@ pub fn system(input: TokenStream) -> TokenStream {
         }
     };
     let inner_intersections_views = {
-        let view_intersections = view_storage_idents.iter().map(|si| {
-            quote!( inner_mask &= unsafe { #si.root.data[oi as usize].assume_init_ref().data[mi as usize].assume_init_ref().presence_mask }; )
-        });
+        let view_intersections = view_storage_idents
+            .iter()
+            .map(|si| quote!( inner_mask &= #si.inner_mask(oi, mi); ));
 
-        let all_intersections = all_storage_idents.iter().map(|si| {
-            quote!( inner_mask &= unsafe { #si.root.data[oi as usize].assume_init_ref().data[mi as usize].assume_init_ref().presence_mask }; )
-        });
+        let all_intersections = all_storage_idents
+            .iter()
+            .map(|si| quote!( inner_mask &= #si.inner_mask(oi, mi); ));
 
         quote! {
             #( #view_intersections )*
@@ -381,13 +771,7 @@ pub fn system(input: TokenStream) -> TokenStream {
     } else {
         let per_all_regular = all_storage_idents.iter().map(|ai| {
             quote! {
-                let rp = #ai.root.presence_mask;
-                let mut all_mid_single: u128 = u128::MAX;
-                if ((rp >> oi) & 1) != 0 {
-                    let ab = unsafe { #ai.root.data[oi as usize].assume_init_ref() };
-                    all_mid_single &= ab.presence_mask;
-                } else { all_mid_single &= 0; }
-                all_mid &= all_mid_single;
+                all_mid &= #ai.middle_mask(oi);
             }
         });
         quote! { let mut all_mid: u128 = u128::MAX; #(#per_all_regular)* middle_mask &= all_mid; }
@@ -396,22 +780,11 @@ pub fn system(input: TokenStream) -> TokenStream {
     let middle_none = if none_types.is_empty() {
         quote!()
     } else {
-        // For None queries, use absence_mask at middle level to skip entire inner blocks
-        // that are full of excluded components. If a middle block's absence_mask bit is set,
-        // that means ALL entities in that inner block have the excluded component.
-        // We can use absence_mask directly without checking presence_mask first because:
-        // - If block doesn't exist, absence_mask will be 0 (safe to read uninitialized)
-        // - If block exists but isn't full, absence_mask bit won't be set
-        // - If block exists and is full, absence_mask bit will be set
+        // For None queries, use the middle full mask to skip entire inner blocks
+        // (128 entities) where every entity has the excluded component.
         let per_none_mid = none_storage_idents.iter().map(|ni| {
             quote! {
-                // Use absence_mask to skip entire inner blocks (128 entities) that are full
-                // Optimized: check root presence_mask once, then access middle absence_mask
-                // This efficiently skips 128-entity inner blocks where excluded components are full
-                if ((#ni.root.presence_mask >> oi) & 1) != 0 {
-                    let nb = unsafe { #ni.root.data[oi as usize].assume_init_ref() };
-                    none_mid |= nb.absence_mask;
-                }
+                none_mid |= #ni.middle_full_mask(oi);
             }
         });
         quote! { let mut none_mid: u128 = 0; #(#per_none_mid)* middle_mask &= !none_mid; }
@@ -422,44 +795,89 @@ pub fn system(input: TokenStream) -> TokenStream {
     } else {
         let per_any = any_storage_idents.iter().map(|ai| {
             quote! {
-                let rp = #ai.root.presence_mask;
-                if ((rp >> oi) & 1) != 0 {
-                    let ab = unsafe { #ai.root.data[oi as usize].assume_init_ref() };
-                    any_mid |= ab.presence_mask;
-                }
+                any_mid |= #ai.middle_mask(oi);
             }
         });
         quote! { let mut any_mid: u128 = 0; #(#per_any)* middle_mask &= any_mid; }
     };
 
+    // Or=[[A, B], [C, D]]: a union of per-group intersections at every level
+    let or_level = |mask: Ident, acc: Ident, level: proc_macro2::TokenStream| {
+        if or_groups.is_empty() {
+            return quote!();
+        }
+        let per_group = or_storage_idents.iter().map(|idents| {
+            let per_type = idents.iter().map(|si| quote!( group &= #si.#level; ));
+            quote! {
+                {
+                    let mut group: u128 = u128::MAX;
+                    #(#per_type)*
+                    #acc |= group;
+                }
+            }
+        });
+        quote! { let mut #acc: u128 = 0; #(#per_group)* #mask &= #acc; }
+    };
+    let outer_or = or_level(
+        format_ident!("outer_mask"),
+        format_ident!("or_outer"),
+        quote!(root_mask()),
+    );
+    let middle_or = or_level(
+        format_ident!("middle_mask"),
+        format_ident!("or_mid"),
+        quote!(middle_mask(oi)),
+    );
+    let inner_or = or_level(
+        format_ident!("inner_mask"),
+        format_ident!("or_in"),
+        quote!(inner_mask(oi, mi)),
+    );
+
+    // ChangedSince = tick compares against the storages' change ticks instead of the masks
     let middle_changed = if changed_types.is_empty() {
         quote!()
     } else {
         let per_changed = changed_storage_idents.iter().map(|ci| {
-            quote! {
-                let rp = #ci.root.presence_mask;
-                if ((rp >> oi) & 1) != 0 {
-                    let cb = unsafe { #ci.root.data[oi as usize].assume_init_ref() };
-                    changed_mid |= cb.changed_mask;
-                }
+            if changed_since.is_some() {
+                quote! { changed_mid |= #ci.middle_changed_since(oi, changed_since); }
+            } else {
+                quote! { changed_mid |= #ci.middle_changed_mask(oi); }
             }
         });
         quote! { let mut changed_mid: u128 = 0; #(#per_changed)* middle_mask &= changed_mid; }
     };
 
+    // Added=[...] is Changed=[...] narrowed to slots that were empty before
+    let middle_added = if added_types.is_empty() {
+        quote!()
+    } else {
+        let per_added = added_storage_idents.iter().map(|ai| {
+            quote! {
+                added_mid |= #ai.middle_added_mask(oi);
+            }
+        });
+        quote! { let mut added_mid: u128 = 0; #(#per_added)* middle_mask &= added_mid; }
+    };
+
+    // WasRemoved=[...] matches slots whose component was removed in the previous tick
+    let middle_was_removed = if was_removed_types.is_empty() {
+        quote!()
+    } else {
+        let per_removed = was_removed_storage_idents.iter().map(|ri| {
+            quote! {
+                removed_mid |= #ri.middle_removed_mask(oi);
+            }
+        });
+        quote! { let mut removed_mid: u128 = 0; #(#per_removed)* middle_mask &= removed_mid; }
+    };
+
     let inner_all = if all_types.is_empty() {
         quote!()
     } else {
         let per_all_regular = all_storage_idents.iter().map(|ai| {
             quote! {
-                let ab = unsafe { #ai.root.data[oi as usize].assume_init_ref() };
-                let mp = ab.presence_mask;
-                let mut all_in_single: u128 = u128::MAX;
-                if ((mp >> mi) & 1) != 0 {
-                    let ib = unsafe { ab.data[mi as usize].assume_init_ref() };
-                    all_in_single &= ib.presence_mask;
-                } else { all_in_single &= 0; }
-                all_in &= all_in_single;
+                all_in &= #ai.inner_mask(oi, mi);
             }
         });
         quote! { let mut all_in: u128 = u128::MAX; #(#per_all_regular)* inner_mask &= all_in; }
@@ -468,26 +886,10 @@ pub fn system(input: TokenStream) -> TokenStream {
     let inner_none = if none_types.is_empty() {
         quote!()
     } else {
-        // For None queries, use absence_mask at inner level to filter out entities
-        // that have the excluded component. absence_mask tracks "currently occupied"
-        // which is exactly what we need for None queries.
-        // We can use absence_mask directly without checking presence_mask first because:
-        // - If block doesn't exist, absence_mask bit won't be set
-        // - If block exists but isn't full, absence_mask bit won't be set at inner level
-        // - If entity has component, absence_mask bit will be set
+        // For None queries, filter out entities that currently have the excluded component
         let per_none = none_storage_idents.iter().map(|ni| {
             quote! {
-                // Use absence_mask for performance - check if blocks exist first for safety
-                let rp = #ni.root.presence_mask;
-                if ((rp >> oi) & 1) != 0 {
-                    let nb = unsafe { #ni.root.data[oi as usize].assume_init_ref() };
-                    let mp = nb.presence_mask;
-                    if ((mp >> mi) & 1) != 0 {
-                        let ib = unsafe { nb.data[mi as usize].assume_init_ref() };
-                        // Use absence_mask to get entities that currently have the excluded component
-                        none_in |= ib.absence_mask;
-                    }
-                }
+                none_in |= #ni.inner_mask(oi, mi);
             }
         });
         quote! { let mut none_in: u128 = 0; #(#per_none)* inner_mask &= !none_in; }
@@ -498,15 +900,7 @@ pub fn system(input: TokenStream) -> TokenStream {
     } else {
         let per_any = any_storage_idents.iter().map(|ai| {
             quote! {
-                let rp = #ai.root.presence_mask;
-                if ((rp >> oi) & 1) != 0 {
-                    let ab = unsafe { #ai.root.data[oi as usize].assume_init_ref() };
-                    let mp = ab.presence_mask;
-                    if ((mp >> mi) & 1) != 0 {
-                        let ib = unsafe { ab.data[mi as usize].assume_init_ref() };
-                        any_in |= ib.presence_mask;
-                    }
-                }
+                any_in |= #ai.inner_mask(oi, mi);
             }
         });
         quote! {
@@ -520,22 +914,148 @@ pub fn system(input: TokenStream) -> TokenStream {
         quote!()
     } else {
         let per_changed = changed_storage_idents.iter().map(|ci| {
+            if changed_since.is_some() {
+                quote! { changed_in |= #ci.inner_changed_since(oi, mi, changed_since); }
+            } else {
+                quote! { changed_in |= #ci.inner_changed_mask(oi, mi); }
+            }
+        });
+        quote! { let mut changed_in: u128 = 0; #(#per_changed)* inner_mask &= changed_in; }
+    };
+
+    let inner_added = if added_types.is_empty() {
+        quote!()
+    } else {
+        let per_added = added_storage_idents.iter().map(|ai| {
             quote! {
-                let rp = #ci.root.presence_mask;
-                if ((rp >> oi) & 1) != 0 {
-                    let cb = unsafe { #ci.root.data[oi as usize].assume_init_ref() };
-                    let mp = cb.presence_mask;
-                    if ((mp >> mi) & 1) != 0 {
-                        let ib = unsafe { cb.data[mi as usize].assume_init_ref() };
-                        changed_in |= ib.changed_mask;
+                added_in |= #ai.inner_added_mask(oi, mi);
+            }
+        });
+        quote! { let mut added_in: u128 = 0; #(#per_added)* inner_mask &= added_in; }
+    };
+
+    let inner_was_removed = if was_removed_types.is_empty() {
+        quote!()
+    } else {
+        let per_removed = was_removed_storage_idents.iter().map(|ri| {
+            quote! {
+                removed_in |= #ri.inner_removed_mask(oi, mi);
+            }
+        });
+        quote! { let mut removed_in: u128 = 0; #(#per_removed)* inner_mask &= removed_in; }
+    };
+
+    // Tag masks are resolved once in `create`, where `tag("name")` looks up the world's tags
+    let (struct_fields_tags, create_fields_tags, tag_bits) = if tagset_ident.is_some() {
+        (
+            quote!( pub tag_has_bits: u64, pub tag_not_bits: u64, ),
+            quote! {
+                tag_has_bits: {
+                    #[allow(unused_variables)]
+                    let tag = |name: &str| world.tag(name);
+                    0u64 #( | ::rollback_ecs::tags::TagId::bit(#has_tags) )*
+                },
+                tag_not_bits: {
+                    #[allow(unused_variables)]
+                    let tag = |name: &str| world.tag(name);
+                    0u64 #( | ::rollback_ecs::tags::TagId::bit(#not_tags) )*
+                },
+            },
+            quote! {
+                let tag_has_bits: u64 = self.tag_has_bits;
+                let tag_not_bits: u64 = self.tag_not_bits;
+            },
+        )
+    } else {
+        (quote!(), quote!(), quote!())
+    };
+
+    // Range clause: evaluated once per run, then masks each level like a storage would
+    let (range_bits, outer_range, middle_range, inner_range) = if let Some(ref expr) = range {
+        (
+            quote! { let index_range = ::rollback_ecs::range::IndexRange::from(#expr); },
+            quote! { outer_mask &= index_range.root_mask(); },
+            quote! { middle_mask &= index_range.middle_mask(oi); },
+            quote! { inner_mask &= index_range.inner_mask(oi, mi); },
+        )
+    } else {
+        (quote!(), quote!(), quote!(), quote!())
+    };
+    let range_bits = match &changed_since {
+        Some(expr) => quote! {
+            #range_bits
+            let changed_since: ::rollback_ecs::tick::Tick = #expr;
+        },
+        None => range_bits,
+    };
+
+    let inner_tags = if let Some(ref ti) = tagset_ident {
+        quote! {
+            {
+                // Per-entity mask+bit check against the TagSet component
+                let mut tag_in: u128 = 0;
+                let tag_present: u128 = #ti.inner_mask(oi, mi);
+                let mut m = inner_mask & tag_present;
+                while m != 0 {
+                    let ii = m.trailing_zeros();
+                    let index = (oi as u32 * 128 * 128) + (mi as u32 * 128) + ii;
+                    if #ti.get(index).is_some_and(|tags| tags.matches(tag_has_bits, tag_not_bits)) {
+                        tag_in |= 1u128 << ii;
                     }
+                    m &= !(1u128 << ii);
                 }
+                // Entities without a TagSet carry no tags, so they only pass pure Not filters
+                if tag_has_bits == 0 {
+                    tag_in |= inner_mask & !tag_present;
+                }
+                inner_mask &= tag_in;
             }
-        });
-        quote! { let mut changed_in: u128 = 0; #(#per_changed)* inner_mask &= changed_in; }
+        }
+    } else {
+        quote!()
+    };
+
+    // ParentView<T> parameters drop entities whose parent lacks `T` before anything runs, so
+    // counts and Remove=[...] only see entities the function is called for
+    let parent_fields: Vec<Ident> = param_args
+        .iter()
+        .filter(|pa| matches!(pa.param, Some(ParamKind::ParentView { .. })))
+        .map(|pa| format_ident!("param_{}", pa.ident))
+        .collect();
+    let inner_parents = if parent_fields.is_empty() {
+        quote!()
+    } else {
+        quote! {
+            {
+                let mut joined: u128 = 0;
+                let mut m = inner_mask;
+                while m != 0 {
+                    let ii = m.trailing_zeros();
+                    let index = (oi as u32 * 128 * 128) + (mi as u32 * 128) + ii;
+                    if #( self.#parent_fields.get(index).is_some() )&&* {
+                        joined |= 1u128 << ii;
+                    }
+                    m &= !(1u128 << ii);
+                }
+                inner_mask &= joined;
+            }
+        }
     };
 
     // Generate function call with View/ViewMut arguments - call for EACH entity in the run
+    // Resources are borrowed through a guard per call, other params are passed as is
+    let param_ref = |pa: &ViewArg| {
+        let field = format_ident!("param_{}", pa.ident);
+        match pa.param {
+            Some(ParamKind::Res { .. }) => quote!(&self.#field.res()),
+            Some(ParamKind::ResMut { .. }) => quote!(&mut self.#field.res_mut()),
+            Some(ParamKind::PlayerInput { .. }) => quote!(&self.#field.view()),
+            _ => quote!(&self.#field),
+        }
+    };
+    let param_refs: Vec<proc_macro2::TokenStream> = param_args.iter().map(param_ref).collect();
+
+    let mut parallel_call = quote!();
     let call_views = if !view_args.is_empty() {
         // Create View/ViewMut construction for each argument
         let view_constructions = view_args.iter().enumerate().map(|(i, va)| {
@@ -556,24 +1076,46 @@ pub fn system(input: TokenStream) -> TokenStream {
             } else {
                 quote! {
                     let #arg_ident = {
-                        let root = unsafe { &#storage_ident.root };
-                        let middle = unsafe { root.data[oi as usize].assume_init_ref() };
-                        let inner = unsafe { middle.data[mi as usize].assume_init_ref() };
+                        let entity_index = ((oi as u32 * 128 * 128) + (mi as u32 * 128) + ii);
 
+                        // The query masks guarantee the component is present
                         ::rollback_ecs::view::View::new(
-                            unsafe { inner.data[ii as usize].assume_init_ref() }
+                            unsafe { #storage_ident.get(entity_index).unwrap_unchecked() }
                         )
                     };
                 }
             }
         });
 
-        let arg_idents: Vec<_> = view_args
+        let arg_idents: Vec<_> = query_args
             .iter()
-            .enumerate()
-            .map(|(_i, va)| {
+            .map(|va| {
                 let ident = &va.ident;
-                if va.is_mut {
+                if let Some(ParamKind::EntityRng) = va.param {
+                    let field = format_ident!("param_{}", ident);
+                    quote!(&mut self.#field.for_entity((oi as u32 * 128 * 128) + (mi as u32 * 128) + ii))
+                } else if let Some(ParamKind::Entity) = va.param {
+                    // Components stored without a live entity get a generation-0 handle
+                    let field = format_ident!("param_{}", ident);
+                    quote! {{
+                        let entity_index = (oi as u32 * 128 * 128) + (mi as u32 * 128) + ii;
+                        self.#field
+                            .get(entity_index)
+                            .copied()
+                            .unwrap_or(::rollback_ecs::entity::Entity::new(entity_index, 0))
+                    }}
+                } else if let Some(ParamKind::ParentView { .. }) = va.param {
+                    // The join already dropped entities whose parent lacks the component
+                    let field = format_ident!("param_{}", ident);
+                    quote! {{
+                        let entity_index = (oi as u32 * 128 * 128) + (mi as u32 * 128) + ii;
+                        ::rollback_ecs::view::View::new(unsafe {
+                            self.#field.get(entity_index).unwrap_unchecked()
+                        })
+                    }}
+                } else if va.param.is_some() {
+                    param_ref(va)
+                } else if va.is_mut {
                     quote!(&mut #ident)
                 } else {
                     quote!(#ident)
@@ -589,6 +1131,71 @@ pub fn system(input: TokenStream) -> TokenStream {
             }
         });
 
+        if parallel {
+            // Pointers are gathered on the calling thread; each task only sees its own slots
+            let slot_idents: Vec<Ident> = view_args
+                .iter()
+                .map(|va| format_ident!("slot_{}", va.ident))
+                .collect();
+            let slot_ptrs = view_args.iter().enumerate().map(|(i, va)| {
+                let storage_ident = &view_storage_idents[i];
+                if va.is_mut {
+                    let ty = &va.ty;
+                    quote!( (#storage_ident.slot_ptr(index), ::std::option::Option::<#ty>::None) )
+                } else {
+                    quote!( unsafe { #storage_ident.get(index).unwrap_unchecked() } as *const _ )
+                }
+            });
+            let marked_views = view_args.iter().zip(&slot_idents).map(|(va, slot)| {
+                let arg_ident = &va.ident;
+                if va.is_mut {
+                    quote! {
+                        let (ptr, previous) = #slot;
+                        let mut #arg_ident = ::rollback_ecs::view::ViewMut::deferred(unsafe { &mut **ptr }, previous);
+                    }
+                } else {
+                    quote!( let #arg_ident = ::rollback_ecs::view::View::new(unsafe { &**#slot }); )
+                }
+            });
+            // Tasks keep the value before their first write; record those in index order
+            let record_writes = view_args.iter().enumerate().filter(|(_, va)| va.is_mut).map(|(i, _)| {
+                let storage_ident = &view_storage_idents[i];
+                let k = syn::Index::from(i);
+                quote! {
+                    if let ::std::option::Option::Some(previous) = slots.0.#k.1.take() {
+                        #storage_ident.record_write(*index, previous);
+                    }
+                }
+            });
+
+            parallel_call = quote! {
+                let mut jobs: ::std::vec::Vec<::std::vec::Vec<_>> = blocks
+                    .iter()
+                    .map(|&(oi, mi, inner_mask)| {
+                        let mut block = ::std::vec::Vec::with_capacity(inner_mask.count_ones() as usize);
+                        let mut m = inner_mask;
+                        while m != 0 {
+                            let ii = m.trailing_zeros();
+                            let index = (oi * 128 * 128) + (mi * 128) + ii;
+                            block.push((index, ::rollback_ecs::par::Slots(( #( #slot_ptrs, )* ))));
+                            m &= !(1u128 << ii);
+                        }
+                        block
+                    })
+                    .collect();
+
+                ::rollback_ecs::par::for_each_block(&mut jobs, |index, ( #( #slot_idents, )* )| {
+                    let (oi, mi, ii) = (index >> 14, (index >> 7) & 0x7F, index & 0x7F);
+                    #( #marked_views )*
+                    #stage_ident::#fn_ident(#(#arg_idents),*);
+                });
+
+                for (index, slots) in jobs.iter_mut().flatten() {
+                    #( #record_writes )*
+                }
+            };
+        }
+
         quote! {
             for ii in start..(start + run) {
                 #( #view_constructions )*
@@ -597,72 +1204,14 @@ pub fn system(input: TokenStream) -> TokenStream {
             }
         }
     } else {
-        quote! { #stage_ident::#fn_ident(); }
+        quote! { #stage_ident::#fn_ident(#(#param_refs),*); }
     };
 
     let remove_components = if !remove_types.is_empty() {
-        let remove_logic = remove_types.iter().enumerate().map(|(i, t)| {
-            let ident = &remove_storage_idents[i];
-            let ty_str = quote!(#t).to_string();
-            if ty_str.ends_with("Entity") {
-                quote! {
-                    {
-                        let root = &mut #ident.root;
-                        if ((root.presence_mask >> oi) & 1) != 0 {
-                            let middle = unsafe { root.data[oi as usize].assume_init_mut() };
-                            if ((middle.presence_mask >> mi) & 1) != 0 {
-                                let inner = unsafe { middle.data[mi as usize].assume_init_mut() };
-                                let ptr = inner.data.as_ptr();
-                                // range_mask represents occupied entities from query
-                                // presence_mask is sufficient - absence_mask is kept in sync
-                                let mut mask = range_mask & inner.presence_mask;
-                                while mask != 0 {
-                                    let ii = mask.trailing_zeros();
-                                    unsafe { ptr.add(ii as usize).read().assume_init_drop(); }
-                                    mask &= !(1u128 << ii);
-                                }
-                                inner.presence_mask &= !range_mask;
-                                inner.absence_mask &= !range_mask;
-                                if inner.absence_mask != u128::MAX {
-                                    middle.absence_mask &= !(1u128 << mi);
-                                }
-                                if middle.absence_mask != u128::MAX {
-                                    root.absence_mask &= !(1u128 << oi);
-                                }
-                            }
-                        }
-                    }
-                }
-            } else {
-                quote! {
-                    {
-                        let root = &mut #ident.root;
-                        if ((root.presence_mask >> oi) & 1) != 0 {
-                            let middle = unsafe { root.data[oi as usize].assume_init_mut() };
-                            if ((middle.presence_mask >> mi) & 1) != 0 {
-                                let inner = unsafe { middle.data[mi as usize].assume_init_mut() };
-                                let ptr = inner.data.as_ptr();
-                                // range_mask represents occupied entities from query
-                                // presence_mask is sufficient - absence_mask is kept in sync
-                                let mut mask = range_mask & inner.presence_mask;
-                                while mask != 0 {
-                                    let ii = mask.trailing_zeros();
-                                    unsafe { ptr.add(ii as usize).read().assume_init_drop(); }
-                                    mask &= !(1u128 << ii);
-                                }
-                                inner.presence_mask &= !range_mask;
-                                inner.absence_mask &= !range_mask;
-                                if inner.absence_mask != u128::MAX {
-                                    middle.absence_mask &= !(1u128 << mi);
-                                }
-                                if middle.absence_mask != u128::MAX {
-                                    root.absence_mask &= !(1u128 << oi);
-                                }
-                            }
-                        }
-                    }
-                }
-            }
+        let remove_logic = remove_storage_idents.iter().enumerate().map(|(k, ident)| {
+            let queue = format_ident!("removal_{}", k);
+            // range_mask represents occupied entities from query
+            quote! { ::rollback_ecs::removal::discard_into(#ident, &self.#queue, oi, mi, range_mask); }
         });
         quote! { #(#remove_logic)* }
     } else {
@@ -672,9 +1221,84 @@ pub fn system(input: TokenStream) -> TokenStream {
     // Stage fields: one per unique type
     let struct_fields_unique = unique_types.iter().enumerate().map(|(i, t)| {
         let id = &unique_idents[i];
-        quote!( pub #id: std::rc::Rc<std::cell::UnsafeCell<::rollback_ecs::storage::Storage<#t>>> , )
+        quote!( pub #id: std::rc::Rc<std::cell::UnsafeCell<<#t as ::rollback_ecs::component::Component>::Storage>> , )
+    });
+
+    // Mailbox/Inbox handles live on the stage alongside the storages
+    let struct_fields_params = param_args.iter().map(|pa| {
+        let field = format_ident!("param_{}", pa.ident);
+        match pa.param.as_ref().expect("param_args only holds params") {
+            ParamKind::Mailbox { msg, target } => {
+                quote!( pub #field: ::rollback_ecs::mailbox::Mailbox<#msg, #target>, )
+            }
+            ParamKind::Inbox { msg } => quote!( pub #field: ::rollback_ecs::mailbox::Inbox<#msg>, ),
+            ParamKind::EntityRng => quote!( pub #field: ::rollback_ecs::rng::RngSource, ),
+            ParamKind::Aggregate { ty } => {
+                quote!( pub #field: ::rollback_ecs::view::Aggregate<#ty>, )
+            }
+            ParamKind::Effects { effect } => {
+                quote!( pub #field: ::rollback_ecs::effects::Effects<#effect>, )
+            }
+            ParamKind::Entity => {
+                quote!( pub #field: ::rollback_ecs::view::Aggregate<::rollback_ecs::entity::Entity>, )
+            }
+            ParamKind::Res { ty } | ParamKind::ResMut { ty } => {
+                quote!( pub #field: std::rc::Rc<::rollback_ecs::resource::ResourceCell<#ty>>, )
+            }
+            ParamKind::RemovedEvents { ty } => {
+                quote!( pub #field: ::rollback_ecs::removal::RemovedEvents<#ty>, )
+            }
+            ParamKind::PlayerInput { ty } => {
+                quote!( pub #field: std::rc::Rc<::rollback_ecs::input::InputBuffer<#ty>>, )
+            }
+            ParamKind::ParentView { ty } => {
+                quote!( pub #field: ::rollback_ecs::hierarchy::ParentView<#ty>, )
+            }
+        }
+    });
+    let create_fields_params = param_args.iter().map(|pa| {
+        let field = format_ident!("param_{}", pa.ident);
+        match pa.param.as_ref().expect("param_args only holds params") {
+            ParamKind::Mailbox { msg, target } => quote!( #field: world.mailbox_from::<#msg, #target, #stage_ident>() ),
+            ParamKind::Inbox { msg } => quote!( #field: world.inbox::<#msg, #stage_ident>() ),
+            ParamKind::EntityRng => quote!( #field: world.entity_rng::<#stage_ident>() ),
+            ParamKind::Aggregate { ty } => {
+                quote!( #field: ::rollback_ecs::view::Aggregate::new(world.get_storage::<#ty>()) )
+            }
+            ParamKind::Effects { effect } => {
+                quote!( #field: world.effects_from::<#effect, #stage_ident>() )
+            }
+            ParamKind::Entity => quote! {
+                #field: ::rollback_ecs::view::Aggregate::new(world.get_storage::<::rollback_ecs::entity::Entity>())
+            },
+            ParamKind::Res { ty } | ParamKind::ResMut { ty } => {
+                quote!( #field: world.resource_cell::<#ty>() )
+            }
+            ParamKind::RemovedEvents { ty } => {
+                quote!( #field: ::rollback_ecs::removal::RemovedEvents::new(world.removal_queue::<#ty>()) )
+            }
+            ParamKind::PlayerInput { ty } => quote!( #field: world.input_buffer::<#ty>() ),
+            ParamKind::ParentView { ty } => {
+                quote!( #field: ::rollback_ecs::hierarchy::ParentView::<#ty>::new(world) )
+            }
+        }
     });
 
+    // Every Remove=[T] records its removals into the `Removed<T>` queue
+    let removal_fields: Vec<Ident> = (0..remove_types.len())
+        .map(|k| format_ident!("removal_{}", k))
+        .collect();
+    let struct_fields_removals = remove_types.iter().zip(&removal_fields).map(|(t, field)| {
+        quote!( pub #field: std::rc::Rc<::rollback_ecs::removal::RemovalQueue<#t>>, )
+    });
+    let create_fields_removals = remove_types
+        .iter()
+        .zip(&removal_fields)
+        .map(|(t, field)| quote!( #field: world.removal_queue::<#t>() ));
+    let writes_removals = remove_types
+        .iter()
+        .map(|t| quote!( std::any::TypeId::of::<::rollback_ecs::removal::RemovalQueue<#t>>() ));
+
     // Build run args from unique storages (unsafe access)
 
     // Access once per unique type into locals - always mutably if any usage requires it
@@ -691,6 +1315,12 @@ pub fn system(input: TokenStream) -> TokenStream {
         })
         .collect();
 
+    // Counting never writes, so every storage is borrowed shared
+    let borrow_locals_shared: Vec<proc_macro2::TokenStream> = unique_idents
+        .iter()
+        .map(|id| quote!( let #id = unsafe { &*self.#id.get() }; ))
+        .collect();
+
     let create_fields_unique = unique_types.iter().enumerate().map(|(i, t)| {
         let id = &unique_idents[i];
         quote!( #id: world.get_storage::<#t>() )
@@ -711,14 +1341,98 @@ pub fn system(input: TokenStream) -> TokenStream {
         }
     });
 
+    // Aggregates read their whole storage; types the query already reads are declared once.
+    // ParentView<T> reads `T` at the parents' indices, so it is declared the same way
+    let mut aggregate_reads: Vec<Type> = Vec::new();
+    if !parent_fields.is_empty() {
+        aggregate_reads.push(syn::parse_quote!(::rollback_ecs::hierarchy::Parent));
+    }
+    for pa in &param_args {
+        if let Some(ParamKind::Aggregate { ty } | ParamKind::ParentView { ty }) = pa.param.as_ref() {
+            let key = quote!(#ty).to_string();
+            if aggregate_reads.iter().any(|t| quote!(#t).to_string() == key) {
+                continue;
+            }
+            match type_index.get(&key) {
+                Some(&i) if unique_mut_flags[i] => {
+                    return syn::Error::new(
+                        pa.ident.span(),
+                        "Aggregate<T> and ParentView<T> cannot be combined with a mutable view or Remove of the same T",
+                    )
+                    .to_compile_error()
+                    .into();
+                }
+                Some(_) => {}
+                None => aggregate_reads.push(ty.clone()),
+            }
+        }
+    }
+    // Entity handles are read from the entity storage, declared once
+    let entity_read = param_args
+        .iter()
+        .any(|pa| matches!(pa.param, Some(ParamKind::Entity | ParamKind::ParentView { .. })))
+        .then(|| quote!( std::any::TypeId::of::<::rollback_ecs::entity::Entity>() ));
+    // A mailbox is keyed by its Mailbox<M, Target> type and read by its target. Senders each
+    // own a lane, so they don't conflict with one another and may share a wavefront
+    let reads_params = param_args
+        .iter()
+        .filter_map(|pa| match pa.param.as_ref() {
+            Some(ParamKind::Inbox { msg }) => Some(
+                quote!( std::any::TypeId::of::<::rollback_ecs::mailbox::Mailbox<#msg, #stage_ident>>() ),
+            ),
+            // Resources are keyed by their cell, so `Res<T>` never conflicts with a component `T`
+            Some(ParamKind::Res { ty }) => Some(
+                quote!( std::any::TypeId::of::<::rollback_ecs::resource::ResourceCell<#ty>>() ),
+            ),
+            Some(ParamKind::PlayerInput { ty }) => Some(
+                quote!( std::any::TypeId::of::<::rollback_ecs::input::InputBuffer<#ty>>() ),
+            ),
+            // Readers of removal events run after every Remove=[T] system
+            Some(ParamKind::RemovedEvents { ty }) => Some(
+                quote!( std::any::TypeId::of::<::rollback_ecs::removal::RemovalQueue<#ty>>() ),
+            ),
+            _ => None,
+        })
+        .chain(entity_read);
+    let writes_params = param_args.iter().filter_map(|pa| match pa.param.as_ref() {
+        Some(ParamKind::ResMut { ty }) => Some(
+            quote!( std::any::TypeId::of::<::rollback_ecs::resource::ResourceCell<#ty>>() ),
+        ),
+        _ => None,
+    });
+    let reads_aggregates = aggregate_reads
+        .iter()
+        .map(|t| quote!( std::any::TypeId::of::<#t>() ));
+
+    // Senders must run before the system they address
+    let mut before = before;
+    for pa in &param_args {
+        if let Some(ParamKind::Mailbox { target, .. }) = pa.param.as_ref() {
+            let key = quote!(#target).to_string();
+            if !before.iter().any(|b| quote!(#b).to_string() == key) {
+                before.push(Type::clone(target));
+            }
+        }
+    }
+
     // Generate parent(), after(), and before() implementations if specified
-    // Default to SimulationGroup if no parent is specified
+    // Default to SimulationGroup if no parent is specified, or to CleanupGroup for systems
+    // reacting to removal events, so they see every removal the simulation made
+    let reacts_to_removals = param_args
+        .iter()
+        .any(|pa| matches!(pa.param, Some(ParamKind::RemovedEvents { .. })));
     let parent_impl = if let Some(ref parent_ty) = parent {
         quote! {
             fn parent(&self) -> ::std::option::Option<::std::any::TypeId> {
                 ::std::option::Option::Some(::std::any::TypeId::of::<#parent_ty>())
             }
         }
+    } else if reacts_to_removals {
+        quote! {
+            fn parent(&self) -> ::std::option::Option<::std::any::TypeId> {
+                ::std::option::Option::Some(::std::any::TypeId::of::<::rollback_ecs::scheduler::CleanupGroup>())
+            }
+        }
     } else {
         // Default to SimulationGroup
         quote! {
@@ -758,25 +1472,183 @@ pub fn system(input: TokenStream) -> TokenStream {
         quote!()
     };
 
+    // Conditions of the system's pipeline groups are added by the scheduler
+    let checks = conditions.checks();
+    let run_if_impl = if checks.is_empty() {
+        quote!()
+    } else {
+        quote! {
+            fn run_if(&self) -> ::std::option::Option<fn(&::rollback_ecs::world::World) -> bool> {
+                fn condition(world: &::rollback_ecs::world::World) -> bool {
+                    #( #checks )&&*
+                }
+                ::std::option::Option::Some(condition)
+            }
+        }
+    };
+
     // query_impl defined above with full implementation
 
-    let expanded = quote! {
-        pub struct #stage_ident { #( #struct_fields_unique )* }
-        impl #stage_ident {
-            #fn_definition
+    // Systems whose only parameters are mailboxes/inboxes (no views, no filters) are not
+    // entity queries: they run exactly once per tick
+    let is_param_only = view_args.is_empty()
+        && !param_args.is_empty()
+        && all_types.is_empty()
+        && none_types.is_empty()
+        && any_types.is_empty()
+        && or_groups.is_empty()
+        && changed_types.is_empty()
+        && added_types.is_empty()
+        && was_removed_types.is_empty()
+        && remove_types.is_empty()
+        && has_tags.is_empty()
+        && not_tags.is_empty()
+        && range.is_none();
+
+    let run_body = if is_param_only {
+        quote! {
+            #stage_ident::#fn_ident(#(#param_refs),*);
         }
-        impl ::rollback_ecs::scheduler::PipelineStage for #stage_ident {
-            fn type_id(&self) -> ::std::any::TypeId {
-                ::std::any::TypeId::of::<Self>()
+    } else if parallel {
+        // Same walk as below, but matched blocks are collected and run on the thread pool
+        let remove_blocks = remove_storage_idents.iter().enumerate().map(|(k, ident)| {
+            let queue = format_ident!("removal_{}", k);
+            quote! { ::rollback_ecs::removal::discard_into(#ident, &self.#queue, oi, mi, inner_mask); }
+        });
+        quote! {
+            use ::rollback_ecs::storage::ComponentStorage as _;
+
+            #( #borrow_locals )*
+
+            #tag_bits
+            #range_bits
+
+            let mut blocks: ::std::vec::Vec<(u32, u32, u128)> = ::std::vec::Vec::new();
+            let mut outer_mask: u128 = u128::MAX;
+            #outer_range
+            #outer_intersections
+            #outer_or
+            #outer_none
+            while outer_mask != 0 {
+                let oi = outer_mask.trailing_zeros();
+                let mut middle_mask: u128 = u128::MAX;
+                #middle_intersections_views
+                #middle_all
+                #middle_none
+                #middle_any
+                #middle_or
+                #middle_changed
+                #middle_added
+                #middle_was_removed
+                #middle_range
+                while middle_mask != 0 {
+                    let mi = middle_mask.trailing_zeros();
+                    let mut inner_mask: u128 = u128::MAX;
+                    #inner_intersections_views
+                    #inner_all
+                    #inner_none
+                    #inner_any
+                    #inner_or
+                    #inner_changed
+                    #inner_added
+                    #inner_was_removed
+                    #inner_range
+                    #inner_tags
+                    #inner_parents
+                    if inner_mask != 0 {
+                        blocks.push((oi, mi, inner_mask));
+                    }
+                    middle_mask &= !(1u128 << mi);
+                }
+                outer_mask &= !(1u128 << oi);
             }
 
-            fn run(&self) {
-                #( #borrow_locals )*
+            #parallel_call
+
+            for &(oi, mi, inner_mask) in &blocks {
+                #( #remove_blocks )*
+            }
+        }
+    } else {
+        quote! {
+            use ::rollback_ecs::storage::ComponentStorage as _;
+
+            #( #borrow_locals )*
+
+            #tag_bits
+            #range_bits
+
+            let mut outer_mask: u128 = u128::MAX;
+            #outer_range
+            #outer_intersections
+            #outer_or
+            // Apply outer_none AFTER intersections to filter out full middle blocks efficiently
+            // This skips entire 16k-entity middle blocks where excluded components are full
+            #outer_none
+            while outer_mask != 0 {
+                let oi = outer_mask.trailing_zeros();
+                let mut middle_mask: u128 = u128::MAX;
+                #middle_intersections_views
+                #middle_all
+                #middle_none
+                #middle_any
+                #middle_or
+                #middle_changed
+                #middle_added
+                #middle_was_removed
+                #middle_range
+                while middle_mask != 0 {
+                    let mi = middle_mask.trailing_zeros();
+                    let mut inner_mask: u128 = u128::MAX;
+                    #inner_intersections_views
+                    #inner_all
+                    #inner_none
+                    #inner_any
+                    #inner_or
+                    #inner_changed
+                    #inner_added
+                    #inner_was_removed
+                    #inner_range
+                    #inner_tags
+                    #inner_parents
+                    while inner_mask != 0 {
+                        let start = inner_mask.trailing_zeros();
+                        let run = (inner_mask >> start).trailing_ones();
+                        #call_views
+
+
+                        let range_mask = if run == 128 { u128::MAX } else { ((1u128 << run) - 1) << start };
+                        #remove_components
+                        inner_mask &= !range_mask;
+                    }
+                    middle_mask &= !(1u128 << mi);
+                }
+                outer_mask &= !(1u128 << oi);
+            }
+        }
+    };
+
+    // Same masks as `run`, counted a block at a time instead of visited per entity
+    let count_impl = if is_param_only {
+        quote!()
+    } else {
+        quote! {
+            /// Number of entities the query matches right now, computed from the storage
+            /// masks without running the system.
+            #[allow(unused_mut)]
+            pub fn count(&self) -> usize {
+                use ::rollback_ecs::storage::ComponentStorage as _;
+
+                #( #borrow_locals_shared )*
+
+                #tag_bits
+                #range_bits
 
+                let mut count: usize = 0;
                 let mut outer_mask: u128 = u128::MAX;
+                #outer_range
                 #outer_intersections
-                // Apply outer_none AFTER intersections to filter out full middle blocks efficiently
-                // This skips entire 16k-entity middle blocks where excluded components are full
+                #outer_or
                 #outer_none
                 while outer_mask != 0 {
                     let oi = outer_mask.trailing_zeros();
@@ -785,7 +1657,11 @@ pub fn system(input: TokenStream) -> TokenStream {
                     #middle_all
                     #middle_none
                     #middle_any
+                    #middle_or
                     #middle_changed
+                    #middle_added
+                    #middle_was_removed
+                    #middle_range
                     while middle_mask != 0 {
                         let mi = middle_mask.trailing_zeros();
                         let mut inner_mask: u128 = u128::MAX;
@@ -793,37 +1669,53 @@ pub fn system(input: TokenStream) -> TokenStream {
                         #inner_all
                         #inner_none
                         #inner_any
+                        #inner_or
                         #inner_changed
-                        while inner_mask != 0 {
-                            let start = inner_mask.trailing_zeros();
-                            let run = (inner_mask >> start).trailing_ones();
-                            #call_views
-
-
-                            let range_mask = if run == 128 { u128::MAX } else { ((1u128 << run) - 1) << start };
-                            #remove_components
-                            inner_mask &= !range_mask;
-                        }
+                        #inner_added
+                        #inner_was_removed
+                        #inner_range
+                        #inner_tags
+                        #inner_parents
+                        count += inner_mask.count_ones() as usize;
                         middle_mask &= !(1u128 << mi);
                     }
                     outer_mask &= !(1u128 << oi);
                 }
+                count
+            }
+        }
+    };
+
+    let expanded = quote! {
+        pub struct #stage_ident { #( #struct_fields_unique )* #( #struct_fields_params )* #( #struct_fields_removals )* #struct_fields_tags }
+        impl #stage_ident {
+            #fn_definition
+            #count_impl
+        }
+        impl ::rollback_ecs::scheduler::PipelineStage for #stage_ident {
+            fn type_id(&self) -> ::std::any::TypeId {
+                ::std::any::TypeId::of::<Self>()
+            }
+
+            fn run(&self) {
+                #run_body
             }
             fn create(world: &mut ::rollback_ecs::world::World) -> Self {
-                Self { #( #create_fields_unique ),* }
+                Self { #create_fields_tags #( #create_fields_unique, )* #( #create_fields_params, )* #( #create_fields_removals ),* }
             }
             fn reads(&self) -> &'static [std::any::TypeId] {
-                static READS: &[std::any::TypeId] = &[ #( #reads_unique ),* ];
+                static READS: &[std::any::TypeId] = &[ #( #reads_unique, )* #( #reads_params, )* #( #reads_aggregates ),* ];
                 READS
             }
             fn writes(&self) -> &'static [std::any::TypeId] {
-                static WRITES: &[std::any::TypeId] = &[ #( #writes_unique ),* ];
+                static WRITES: &[std::any::TypeId] = &[ #( #writes_unique, )* #( #writes_params, )* #( #writes_removals ),* ];
                 WRITES
             }
 
             #parent_impl
             #after_impl
             #before_impl
+            #run_if_impl
         }
 
         unsafe impl ::std::marker::Send for #stage_ident {}
@@ -833,11 +1725,159 @@ pub fn system(input: TokenStream) -> TokenStream {
     TokenStream::from(expanded)
 }
 
-#[proc_macro_derive(Component)]
+#[proc_macro_derive(Component, attributes(component))]
 pub fn component_derive(input: TokenStream) -> TokenStream {
     let ast = parse_macro_input!(input as DeriveInput);
     let name = &ast.ident;
 
+    // #[component(storage = "dense" | "sparse")] picks the storage backend and
+    // #[component(align(16 | 32))] declares the block alignment SIMD code relies on,
+    // #[component(track_fields)] generates setters that skip writes of unchanged values and
+    // #[component(rollback = "snapshot" | "ignore" | "reset")] picks the rollback policy
+    let mut storage = quote!(::rollback_ecs::storage::Storage<#name>);
+    let mut block_align = quote!();
+    let mut track_fields = false;
+    let mut rollback = quote!();
+    for attr in ast.attrs.iter().filter(|a| a.path().is_ident("component")) {
+        let result = attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("rollback") {
+                let value: syn::LitStr = meta.value()?.parse()?;
+                let policy = match value.value().as_str() {
+                    "snapshot" => quote!(Snapshot),
+                    "ignore" => quote!(Ignore),
+                    "reset" => quote!(Reset),
+                    _ => {
+                        return Err(syn::Error::new(
+                            value.span(),
+                            "unknown rollback policy, expected \"snapshot\", \"ignore\" or \"reset\"",
+                        ));
+                    }
+                };
+                rollback = quote! {
+                    const ROLLBACK: ::rollback_ecs::component::RollbackPolicy =
+                        ::rollback_ecs::component::RollbackPolicy::#policy;
+                };
+                return Ok(());
+            }
+            if meta.path.is_ident("track_fields") {
+                track_fields = true;
+                return Ok(());
+            }
+            if meta.path.is_ident("align") {
+                let content;
+                syn::parenthesized!(content in meta.input);
+                let value: syn::LitInt = content.parse()?;
+                let align: usize = value.base10_parse()?;
+                if align != 16 && align != 32 {
+                    return Err(syn::Error::new(
+                        value.span(),
+                        "unsupported alignment, expected 16 or 32",
+                    ));
+                }
+                block_align = quote! {
+                    const BLOCK_ALIGN: usize = #align;
+                };
+                return Ok(());
+            }
+            if !meta.path.is_ident("storage") {
+                return Err(meta.error(
+                    "unknown component attribute, expected `storage`, `align`, `track_fields` or `rollback`",
+                ));
+            }
+            let value: syn::LitStr = meta.value()?.parse()?;
+            storage = match value.value().as_str() {
+                "dense" => quote!(::rollback_ecs::storage::Storage<#name>),
+                "sparse" => quote!(::rollback_ecs::sparse::SparseStorage<#name>),
+                _ => {
+                    return Err(syn::Error::new(
+                        value.span(),
+                        "unknown storage backend, expected \"dense\" or \"sparse\"",
+                    ));
+                }
+            };
+            Ok(())
+        });
+        if let Err(err) = result {
+            return err.to_compile_error().into();
+        }
+    }
+
+    // #[component(weak)] on an `EntityWeak` field resets it when its target is destroyed, and
+    // #[component(target)] on an `Entity` field makes the component a relation
+    let mut weak_fields = Vec::new();
+    let mut target_fields = Vec::new();
+    if let syn::Data::Struct(data) = &ast.data {
+        for (i, field) in data.fields.iter().enumerate() {
+            for attr in field.attrs.iter().filter(|a| a.path().is_ident("component")) {
+                let result = attr.parse_nested_meta(|meta| {
+                    let fields = if meta.path.is_ident("weak") {
+                        &mut weak_fields
+                    } else if meta.path.is_ident("target") {
+                        &mut target_fields
+                    } else {
+                        return Err(meta.error(
+                            "unknown field attribute, expected `weak` or `target`",
+                        ));
+                    };
+                    fields.push(match &field.ident {
+                        Some(ident) => quote!(#ident),
+                        None => {
+                            let index = syn::Index::from(i);
+                            quote!(#index)
+                        }
+                    });
+                    Ok(())
+                });
+                if let Err(err) = result {
+                    return err.to_compile_error().into();
+                }
+            }
+        }
+    }
+
+    let weak_refs = if weak_fields.is_empty() {
+        quote!()
+    } else {
+        quote! {
+            const HAS_WEAK_REFS: bool = true;
+
+            fn visit_weak_refs(&self, f: &mut dyn FnMut(&::rollback_ecs::entity::EntityWeak)) {
+                #(f(&self.#weak_fields);)*
+            }
+
+            fn visit_weak_refs_mut(&mut self, f: &mut dyn FnMut(&mut ::rollback_ecs::entity::EntityWeak)) {
+                #(f(&mut self.#weak_fields);)*
+            }
+        }
+    };
+
+    let relation = match target_fields.as_slice() {
+        [] => quote!(),
+        [target] => {
+            storage = quote!(::rollback_ecs::relation::Relation<#name>);
+            quote! {
+                const IS_RELATION: bool = true;
+
+                fn relation_target(&self) -> Option<::rollback_ecs::entity::Entity> {
+                    Some(self.#target)
+                }
+            }
+        }
+        _ => {
+            let message = "a relation has a single #[component(target)] field";
+            return syn::Error::new(name.span(), message).to_compile_error().into();
+        }
+    };
+
+    let field_setters = if track_fields {
+        match field_setters(&ast) {
+            Ok(setters) => setters,
+            Err(err) => return err.to_compile_error().into(),
+        }
+    } else {
+        quote!()
+    };
+
     let cleanup_name = syn::Ident::new(&format!("{}CleanupSystem", name), name.span());
 
     let gen = quote! {
@@ -851,11 +1891,46 @@ pub fn component_derive(input: TokenStream) -> TokenStream {
         }
 
         impl ::rollback_ecs::component::Component for #name {
+            type Storage = #storage;
+
+            #block_align
+            #rollback
+            #weak_refs
+            #relation
+
+            fn stable_name() -> &'static str {
+                concat!(module_path!(), "::", stringify!(#name))
+            }
+
+            fn state_hasher() -> Option<fn(&Self, &mut ::rollback_ecs::statehash::StateHasher)> {
+                #[allow(unused_imports)]
+                use ::rollback_ecs::statehash::{ViaHashable, ViaOpaque};
+                (&::rollback_ecs::statehash::Probe::<Self>::new()).state_hasher()
+            }
+
+            fn wire_codec() -> Option<&'static dyn ::rollback_ecs::wire::SnapshotCodec<Self>> {
+                #[allow(unused_imports)]
+                use ::rollback_ecs::wire::{ViaSerializable, ViaUnserializable};
+                (&::rollback_ecs::statehash::Probe::<Self>::new()).wire_codec()
+            }
+
+            fn is_pod() -> bool {
+                #[allow(unused_imports)]
+                use ::rollback_ecs::component::{ViaClone, ViaCopy};
+                (&::rollback_ecs::statehash::Probe::<Self>::new()).is_pod()
+            }
+
+            ::rollback_ecs::__component_serde_fns!();
+
             fn cleanup_system(world: &mut ::rollback_ecs::world::World) -> Box<dyn ::rollback_ecs::scheduler::PipelineStage> {
                 Box::new(<#cleanup_name as ::rollback_ecs::scheduler::PipelineStage>::create(world))
             }
         }
 
+        ::rollback_ecs::inventory::submit! {
+            ::rollback_ecs::component::ComponentRegistration::of::<#name>()
+        }
+
         pub struct #cleanup_name(::rollback_ecs::system::ComponentCleanupSystem<#name>);
 
         impl ::rollback_ecs::scheduler::PipelineStage for #cleanup_name {
@@ -887,18 +1962,125 @@ pub fn component_derive(input: TokenStream) -> TokenStream {
 
         unsafe impl ::std::marker::Send for #cleanup_name {}
         unsafe impl ::std::marker::Sync for #cleanup_name {}
+
+        #field_setters
     };
 
     gen.into()
 }
 
+/// The `{Name}Fields` trait of a `#[component(track_fields)]` struct: one `set_{field}`
+/// per field, implemented for `ViewMut<{Name}>` on top of `ViewMut::set_field`.
+fn field_setters(ast: &DeriveInput) -> Result<proc_macro2::TokenStream> {
+    let name = &ast.ident;
+    let vis = &ast.vis;
+    let syn::Data::Struct(data) = &ast.data else {
+        return Err(syn::Error::new(
+            name.span(),
+            "#[component(track_fields)] is only supported on structs",
+        ));
+    };
+
+    let trait_name = format_ident!("{}Fields", name);
+    let mut signatures = Vec::new();
+    let mut bodies = Vec::new();
+    for (i, field) in data.fields.iter().enumerate() {
+        let (member, setter) = match &field.ident {
+            Some(ident) => (quote!(#ident), format_ident!("set_{}", ident)),
+            None => {
+                let index = syn::Index::from(i);
+                (quote!(#index), format_ident!("set_{}", i))
+            }
+        };
+        let ty = &field.ty;
+        let doc = format!(
+            "Sets `{}` if the value differs, marking the component changed only then.",
+            member
+        );
+        signatures.push(quote! {
+            #[doc = #doc]
+            fn #setter(&mut self, value: #ty) -> bool;
+        });
+        bodies.push(quote! {
+            fn #setter(&mut self, value: #ty) -> bool {
+                self.set_field(|c| &c.#member, |c| &mut c.#member, value)
+            }
+        });
+    }
+
+    let doc = format!("Change-tracking setters of `{}` components.", name);
+    Ok(quote! {
+        #[doc = #doc]
+        #vis trait #trait_name {
+            #(#signatures)*
+        }
+
+        impl<'a> #trait_name for ::rollback_ecs::view::ViewMut<'a, #name> {
+            #(#bodies)*
+        }
+    })
+}
+
+#[proc_macro_derive(Bundle, attributes(bundle))]
+pub fn bundle_derive(input: TokenStream) -> TokenStream {
+    let ast = parse_macro_input!(input as DeriveInput);
+    let name = &ast.ident;
+    let (impl_generics, ty_generics, where_clause) = ast.generics.split_for_impl();
+
+    let fields = match &ast.data {
+        syn::Data::Struct(data) => &data.fields,
+        _ => {
+            return syn::Error::new(name.span(), "Bundle can only be derived for structs")
+                .to_compile_error()
+                .into();
+        }
+    };
+
+    // Every field is a component, or a nested bundle marked #[bundle]
+    let writes = fields.iter().enumerate().map(|(i, field)| {
+        let member = match &field.ident {
+            Some(ident) => quote!(#ident),
+            None => {
+                let index = syn::Index::from(i);
+                quote!(#index)
+            }
+        };
+        if field.attrs.iter().any(|a| a.path().is_ident("bundle")) {
+            quote!(writer.set_bundle(&self.#member);)
+        } else {
+            quote!(writer.set(&self.#member);)
+        }
+    });
+
+    let gen = quote! {
+        impl #impl_generics ::rollback_ecs::bundle::Bundle for #name #ty_generics #where_clause {
+            fn write(&self, writer: &mut ::rollback_ecs::bundle::BundleWriter<'_>) {
+                #(#writes)*
+            }
+        }
+    };
+    gen.into()
+}
+
 #[proc_macro_derive(Tag)]
 pub fn tag_derive(input: TokenStream) -> TokenStream {
     let ast = parse_macro_input!(input as syn::DeriveInput);
     let name = &ast.ident;
-    let gen = quote! {
-        impl crate::component::Component for #name {}
+
+    // A tag is a marker component; keeping it field-free lets its storage hold only masks
+    let has_fields = match &ast.data {
+        syn::Data::Struct(data) => !data.fields.is_empty(),
+        _ => true,
     };
+    if has_fields {
+        let message = "a tag is a struct without fields, derive `Component` instead";
+        return syn::Error::new(name.span(), message).to_compile_error().into();
+    }
+
+    let mut gen = proc_macro2::TokenStream::from(component_derive(quote!(#ast).into()));
+    gen.extend(quote! {
+        impl ::rollback_ecs::component::Tag for #name {}
+    });
     gen.into()
 }
 
@@ -912,6 +2094,7 @@ struct PipelineGroupAttrs {
     after: Vec<Type>,
     before: Vec<Type>,
     parent: Option<Type>,
+    conditions: RunConditions,
 }
 
 impl Parse for PipelineGroupAttrs {
@@ -919,6 +2102,7 @@ impl Parse for PipelineGroupAttrs {
         let mut after = Vec::new();
         let mut before = Vec::new();
         let mut parent = None;
+        let mut conditions = RunConditions::default();
 
         while !input.is_empty() {
             let ident: Ident = input.parse()?;
@@ -935,6 +2119,7 @@ impl Parse for PipelineGroupAttrs {
                 input.parse::<Token![=]>()?;
                 let ty: Type = input.parse()?;
                 parent = Some(ty);
+            } else if conditions.parse_clause(&ident, input)? {
             } else {
                 return Err(input.error(format!("unknown attribute: {}", ident)));
             }
@@ -948,6 +2133,7 @@ impl Parse for PipelineGroupAttrs {
             after,
             before,
             parent,
+            conditions,
         })
     }
 }
@@ -963,6 +2149,7 @@ pub fn pipeline_group(args: TokenStream, input: TokenStream) -> TokenStream {
             after: Vec::new(),
             before: Vec::new(),
             parent: None,
+            conditions: RunConditions::default(),
         }
     } else {
         // Convert proc_macro::TokenStream to proc_macro2::TokenStream for parsing
@@ -1021,6 +2208,20 @@ pub fn pipeline_group(args: TokenStream, input: TokenStream) -> TokenStream {
         quote!()
     };
 
+    // Register the group with its run condition, which the scheduler applies to every
+    // system nested in it
+    let checks = attrs.conditions.checks();
+    let run_if = if checks.is_empty() {
+        quote!(::std::option::Option::None)
+    } else {
+        quote! {{
+            fn condition(world: &::rollback_ecs::world::World) -> bool {
+                #( #checks )&&*
+            }
+            ::std::option::Option::Some(condition as fn(&::rollback_ecs::world::World) -> bool)
+        }}
+    };
+
     // Remove the pipeline_group attribute so it doesn't appear in the output
     item.attrs
         .retain(|attr| !attr.path().is_ident("pipeline_group"));
@@ -1043,6 +2244,10 @@ pub fn pipeline_group(args: TokenStream, input: TokenStream) -> TokenStream {
             #after_impl
             #parent_impl
         }
+
+        ::rollback_ecs::inventory::submit! {
+            ::rollback_ecs::scheduler::GroupRegistration::of::<#name>(#run_if)
+        }
     };
 
     TokenStream::from(expanded)
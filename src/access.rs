@@ -0,0 +1,205 @@
+//! Safe, borrow-checked access to component storages from application code.
+//!
+//! `World::get_storage` hands out `Rc<UnsafeCell<_>>` handles for the system macros,
+//! which is why reading and writing storages by hand between ticks used to take
+//! `unsafe`. The world offers safe entry points instead:
+//!
+//! - `World::get` / `World::get_mut` read or write one component of a live entity,
+//!   checked against the entity's generation like `World::set`;
+//! - `World::storage` / `World::storage_mut` return a guard over a whole storage, borrowed
+//!   from the world like any other reference;
+//! - `World::storages` locks the world and returns a `StorageAccess`, which hands out
+//!   several `StorageRef` / `StorageMut` guards at once and checks them at runtime, like a
+//!   `RefCell` per component type: any number of readers or one writer.
+//!
+//! Writes through `StorageMut` go through `ComponentStorage::get_mut` / `set` and are
+//! change-tracked and rolled back like writes made by systems.
+//!
+//! # Example
+//! ```ignore
+//! if let Some(health) = world.get_mut::<Health>(player) {
+//!     health.value -= 10;
+//! }
+//!
+//! let access = world.storages();
+//! let mut positions = access.write::<Position>().unwrap();
+//! let velocities = access.read::<Velocity>().unwrap();
+//! velocities.visit(|index, v| positions.get_mut(index).x += v.x);
+//! ```
+
+use crate::component::{Component, MAX_COMPONENTS};
+use crate::world::World;
+use std::cell::{Cell, UnsafeCell};
+use std::fmt;
+use std::ops::{Deref, DerefMut};
+use std::rc::Rc;
+
+/// A storage that is already borrowed in a conflicting way.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct BorrowError {
+    pub component: &'static str,
+    /// Whether the storage is held by a writer, rather than by readers.
+    pub written: bool,
+}
+
+impl fmt::Display for BorrowError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let held = if self.written { "mutably" } else { "immutably" };
+        write!(
+            f,
+            "storage of {} is already borrowed {}",
+            self.component, held
+        )
+    }
+}
+
+impl std::error::Error for BorrowError {}
+
+/// Shared access to the storage of `T`.
+pub struct StorageRef<'w, T: Component> {
+    storage: &'w T::Storage,
+    flag: Option<&'w Cell<isize>>,
+}
+
+impl<'w, T: Component> StorageRef<'w, T> {
+    pub(crate) fn new(storage: &'w T::Storage) -> Self {
+        StorageRef {
+            storage,
+            flag: None,
+        }
+    }
+}
+
+impl<T: Component> Deref for StorageRef<'_, T> {
+    type Target = T::Storage;
+
+    fn deref(&self) -> &T::Storage {
+        self.storage
+    }
+}
+
+impl<T: Component> Drop for StorageRef<'_, T> {
+    fn drop(&mut self) {
+        if let Some(flag) = self.flag {
+            flag.set(flag.get() - 1);
+        }
+    }
+}
+
+/// Exclusive access to the storage of `T`.
+pub struct StorageMut<'w, T: Component> {
+    storage: &'w mut T::Storage,
+    flag: Option<&'w Cell<isize>>,
+}
+
+impl<'w, T: Component> StorageMut<'w, T> {
+    pub(crate) fn new(storage: &'w mut T::Storage) -> Self {
+        StorageMut {
+            storage,
+            flag: None,
+        }
+    }
+}
+
+impl<T: Component> Deref for StorageMut<'_, T> {
+    type Target = T::Storage;
+
+    fn deref(&self) -> &T::Storage {
+        self.storage
+    }
+}
+
+impl<T: Component> DerefMut for StorageMut<'_, T> {
+    fn deref_mut(&mut self) -> &mut T::Storage {
+        self.storage
+    }
+}
+
+impl<T: Component> Drop for StorageMut<'_, T> {
+    fn drop(&mut self) {
+        if let Some(flag) = self.flag {
+            flag.set(0);
+        }
+    }
+}
+
+/// Runtime-checked access to several storages of a locked world, see `World::storages`.
+///
+/// Each component type has a borrow flag: positive while readers hold it, `-1` while a
+/// writer does.
+pub struct StorageAccess<'w> {
+    world: &'w mut World,
+    flags: [Cell<isize>; MAX_COMPONENTS],
+}
+
+impl<'w> StorageAccess<'w> {
+    pub(crate) fn new(world: &'w mut World) -> Self {
+        StorageAccess {
+            world,
+            flags: std::array::from_fn(|_| Cell::new(0)),
+        }
+    }
+
+    fn cell<T: Component>(&self) -> Option<&Rc<UnsafeCell<T::Storage>>> {
+        self.world.storage_handle::<T>()
+    }
+
+    fn conflict<T: Component>(written: bool) -> BorrowError {
+        BorrowError {
+            component: std::any::type_name::<T>(),
+            written,
+        }
+    }
+
+    /// Shared access to the storage of `T`, or `Ok(None)` if the world has none.
+    pub fn try_read<T: Component>(&self) -> Result<Option<StorageRef<'_, T>>, BorrowError> {
+        let Some(cell) = self.cell::<T>() else {
+            return Ok(None);
+        };
+        let flag = &self.flags[T::type_index()];
+        if flag.get() < 0 {
+            return Err(Self::conflict::<T>(true));
+        }
+        flag.set(flag.get() + 1);
+
+        // The flag rules out a live `StorageMut`, and the world is locked by `&mut`
+        Ok(Some(StorageRef {
+            storage: unsafe { &*cell.get() },
+            flag: Some(flag),
+        }))
+    }
+
+    /// Exclusive access to the storage of `T`, or `Ok(None)` if the world has none.
+    pub fn try_write<T: Component>(&self) -> Result<Option<StorageMut<'_, T>>, BorrowError> {
+        let Some(cell) = self.cell::<T>() else {
+            return Ok(None);
+        };
+        let flag = &self.flags[T::type_index()];
+        if flag.get() != 0 {
+            return Err(Self::conflict::<T>(flag.get() < 0));
+        }
+        flag.set(-1);
+
+        // The flag rules out any other guard, and the world is locked by `&mut`
+        Ok(Some(StorageMut {
+            storage: unsafe { &mut *cell.get() },
+            flag: Some(flag),
+        }))
+    }
+
+    /// # Panics
+    /// Panics if the storage of `T` is borrowed mutably.
+    pub fn read<T: Component>(&self) -> Option<StorageRef<'_, T>> {
+        self.try_read::<T>().unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// # Panics
+    /// Panics if the storage of `T` is borrowed.
+    pub fn write<T: Component>(&self) -> Option<StorageMut<'_, T>> {
+        self.try_write::<T>().unwrap_or_else(|e| panic!("{}", e))
+    }
+}
+
+#[cfg(test)]
+#[path = "access.tests.rs"]
+mod tests;
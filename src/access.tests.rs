@@ -0,0 +1,78 @@
+use super::*;
+use crate::prelude::*;
+use crate::storage::ComponentStorage;
+
+#[derive(Component, Default, Clone, Debug, PartialEq)]
+struct Position {
+    x: i32,
+}
+
+#[derive(Component, Default, Clone, Debug, PartialEq)]
+struct Velocity {
+    x: i32,
+}
+
+fn world_with_mover() -> (World, Entity) {
+    let mut world = World::new();
+    world.get_storage::<Position>();
+    world.get_storage::<Velocity>();
+    world.build_scheduler();
+    let e = world.spawn();
+    world.set(e, &Position { x: 0 });
+    world.set(e, &Velocity { x: 3 });
+    (world, e)
+}
+
+#[test]
+fn test_get_mut_is_checked_and_rolled_back() {
+    let (mut world, e) = world_with_mover();
+    world.run();
+    let before = world.current_tick();
+
+    world.get_mut::<Position>(e).unwrap().x = 5;
+    assert_eq!(world.get::<Position>(e), Some(&Position { x: 5 }));
+    world.run();
+
+    // Stale handles and missing components read nothing
+    let stale = Entity::new(e.index(), e.generation() + 1);
+    assert!(world.get::<Position>(stale).is_none());
+    assert!(world.get_mut::<Position>(stale).is_none());
+    world.storage_mut::<Velocity>().remove(e.index());
+    assert!(world.get_mut::<Velocity>(e).is_none());
+
+    world.rollback(Tick::new(before.value() - 1));
+    assert_eq!(world.get::<Position>(e), Some(&Position { x: 0 }));
+}
+
+#[test]
+fn test_storage_access_shares_readers_and_excludes_writers() {
+    let (mut world, e) = world_with_mover();
+    let access = world.storages();
+
+    let mut positions = access.write::<Position>().unwrap();
+    let velocities = access.read::<Velocity>().unwrap();
+    let again = access.read::<Velocity>().unwrap();
+    velocities.visit(|index, v| positions.get_mut(index).x += v.x);
+
+    let err = access.try_read::<Position>().err().unwrap();
+    assert!(err.written);
+    assert!(access.try_write::<Velocity>().is_err());
+    drop((velocities, again));
+    assert!(access.try_write::<Velocity>().unwrap().is_some());
+
+    drop(positions);
+    assert_eq!(
+        access.read::<Position>().unwrap().get(e.index()).unwrap().x,
+        3
+    );
+    assert!(access.read::<crate::tags::TagSet>().is_none());
+}
+
+#[test]
+#[should_panic(expected = "already borrowed mutably")]
+fn test_double_write_panics() {
+    let (mut world, _) = world_with_mover();
+    let access = world.storages();
+    let _first = access.write::<Position>();
+    let _second = access.write::<Position>();
+}
@@ -0,0 +1,110 @@
+//! Determinism audit: per-system write-set hashes for comparing parallel and sequential
+//! execution.
+//!
+//! Rollback netcode needs every tick to compute the same state on every peer, whether the
+//! scheduler runs a wavefront on the thread pool or one system after the other. Queries
+//! visit entities in strictly ascending index order (the `system!` loops, `World::query`
+//! and each block of a `Parallel = true` system alike), and systems in one wavefront never
+//! write what another reads, so a system that depends on nothing but its inputs produces
+//! the same writes either way. Systems that read shared state behind the scheduler's back,
+//! iterate a `HashMap` or accumulate floats across `Parallel = true` blocks don't.
+//!
+//! `World::set_determinism_audit(true)` makes the scheduler hash the component storages in
+//! every system's write set right after the system returns, and the world keep one
+//! `TickAudit` per simulated tick. Running a twin world with `run_sequential()` on the same
+//! inputs and comparing the audits with `TickAudit::diff` names the systems whose writes
+//! differ, in wavefront order: the first one is usually the culprit, and later ones (the
+//! cleanup systems of its components among them) only inherited its output. Systems a run
+//! condition skipped have no hash. A system in a loop group folds every iteration into its
+//! hash.
+//!
+//! Hashing costs a full `StateHash` pass over the written storages per system and tick, so
+//! the audit is meant for development builds and tests.
+//!
+//! # Example
+//! ```ignore
+//! parallel.set_determinism_audit(true);
+//! sequential.set_determinism_audit(true);
+//! for _ in 0..100 {
+//!     parallel.run();
+//!     sequential.run_sequential();
+//! }
+//! for (ours, theirs) in parallel.take_audits().iter().zip(&sequential.take_audits()) {
+//!     assert!(ours.diff(theirs).is_empty(), "tick {:?}: {:?}", ours.tick, ours.diff(theirs));
+//! }
+//! ```
+
+use crate::rollback::StorageLike;
+use crate::statehash::StateHasher;
+use crate::tick::Tick;
+use std::hash::Hash;
+use std::sync::Mutex;
+
+/// Write-set hashes of the systems of one scheduler during the current tick, indexed like
+/// its systems.
+pub struct SystemAudit {
+    /// Storages in each system's write set.
+    write_sets: Vec<Vec<*const dyn StorageLike>>,
+    /// Hash of each system's writes, `None` until it ran this tick.
+    hashes: Vec<Mutex<Option<u128>>>,
+}
+
+// SAFETY: the storages outlive the scheduler, and a system's write set is only hashed
+// right after it returned, while every system writing the same storages waits for it
+unsafe impl Send for SystemAudit {}
+unsafe impl Sync for SystemAudit {}
+
+impl SystemAudit {
+    pub(crate) fn new(write_sets: Vec<Vec<*const dyn StorageLike>>) -> Self {
+        SystemAudit {
+            hashes: write_sets.iter().map(|_| Mutex::new(None)).collect(),
+            write_sets,
+        }
+    }
+
+    /// Hashes the write set of system `idx`, which just ran.
+    pub(crate) fn record(&self, idx: usize) {
+        let mut slot = self.hashes[idx].lock().unwrap();
+        let mut state = StateHasher::new();
+        slot.hash(&mut state);
+        for &storage in &self.write_sets[idx] {
+            unsafe { (*storage).state_hash() }.hash(&mut state);
+        }
+        *slot = Some(state.finish128());
+    }
+
+    /// Takes the hashes of the tick and starts over.
+    pub(crate) fn take(&self) -> Vec<Option<u128>> {
+        self.hashes
+            .iter()
+            .map(|slot| slot.lock().unwrap().take())
+            .collect()
+    }
+}
+
+/// Write-set hashes of every system for one simulated tick, see the module docs.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct TickAudit {
+    pub tick: Tick,
+    /// Whether the tick ran with `run_sequential`.
+    pub sequential: bool,
+    /// Name and write-set hash of every system, in wavefront order.
+    pub systems: Vec<(&'static str, Option<u128>)>,
+}
+
+impl TickAudit {
+    /// Names of the systems whose writes differ from `other`'s, in wavefront order.
+    /// Both audits must come from worlds built with the same systems.
+    pub fn diff(&self, other: &TickAudit) -> Vec<&'static str> {
+        self.systems
+            .iter()
+            .zip(&other.systems)
+            .filter(|(ours, theirs)| ours != theirs)
+            .map(|((name, _), _)| *name)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+#[path = "audit.tests.rs"]
+mod tests;
@@ -0,0 +1,142 @@
+use super::*;
+use crate::prelude::*;
+use std::sync::atomic::{AtomicI32, Ordering};
+
+#[derive(Component, Clone, Default, Debug, PartialEq, Hash)]
+struct Position {
+    x: i32,
+}
+
+#[derive(Component, Clone, Default, Debug, PartialEq, Hash)]
+struct Velocity {
+    dx: i32,
+}
+
+#[derive(Component, Clone, Default, Debug, PartialEq, Hash)]
+struct Heat {
+    value: i32,
+}
+
+/// Shared by every world, so a system reading it is not a function of its world's state.
+static GLOBAL: AtomicI32 = AtomicI32::new(0);
+
+/// Indices `OrderSystem` visited, across every world of the test binary.
+static VISITED: Mutex<Vec<u32>> = Mutex::new(Vec::new());
+
+system! {
+    MoveSystem {
+        query! {
+            fn step(pos: &mut ViewMut<Position>, vel: View<Velocity>) {
+                pos.x += vel.dx;
+            }
+        }
+    }
+}
+
+system! {
+    HeatSystem {
+        query! {
+            fn warm(heat: &mut ViewMut<Heat>) {
+                heat.value += GLOBAL.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+    }
+}
+
+system! {
+    OrderSystem {
+        query! {
+            fn visit(entity: View<Entity>, _pos: View<Position>) {
+                VISITED.lock().unwrap().push(entity.index());
+            }
+        }
+    }
+}
+
+fn test_world(nondeterministic: bool) -> World {
+    let mut world = World::new();
+    world.add_system::<MoveSystem>();
+    if nondeterministic {
+        world.add_system::<HeatSystem>();
+    }
+    world.build_scheduler();
+    world.set_determinism_audit(true);
+
+    for i in 0..300 {
+        let e = world.spawn();
+        world.set(e, &Position { x: i });
+        world.set(e, &Velocity { dx: i % 3 });
+        world.set(e, &Heat { value: 0 });
+    }
+    world
+}
+
+fn diffs(parallel: &mut World, sequential: &mut World) -> Vec<Vec<&'static str>> {
+    let (ours, theirs) = (parallel.take_audits(), sequential.take_audits());
+    assert_eq!(ours.len(), theirs.len());
+    ours.iter().zip(&theirs).map(|(a, b)| a.diff(b)).collect()
+}
+
+#[test]
+fn test_parallel_and_sequential_writes_match() {
+    let (mut parallel, mut sequential) = (test_world(false), test_world(false));
+    for _ in 0..5 {
+        parallel.run();
+        sequential.run_sequential();
+    }
+
+    let (ours, theirs) = (parallel.take_audits(), sequential.take_audits());
+    assert_eq!(ours.len(), 5);
+    assert!(!ours[0].sequential && theirs[0].sequential);
+    assert!(ours.iter().zip(&theirs).all(|(a, b)| a.diff(b).is_empty()));
+
+    // Every tick hashes what the system wrote
+    let hashes: Vec<Option<u128>> = ours
+        .iter()
+        .map(|audit| audit.systems.iter().find(|(n, _)| n.ends_with("MoveSystem")).unwrap().1)
+        .collect();
+    assert!(hashes.iter().all(Option::is_some));
+    assert_ne!(hashes[0], hashes[1]);
+
+    // Stopping records nothing more
+    parallel.set_determinism_audit(false);
+    parallel.run();
+    assert!(parallel.take_audits().is_empty());
+}
+
+#[test]
+fn test_audit_names_the_nondeterministic_system() {
+    let (mut parallel, mut sequential) = (test_world(true), test_world(true));
+    parallel.run();
+    sequential.run_sequential();
+
+    // The cleanup system of `Heat` inherits the difference
+    let diffs = diffs(&mut parallel, &mut sequential);
+    assert_eq!(diffs.len(), 1);
+    assert_eq!(diffs[0][0], std::any::type_name::<HeatSystem>());
+    assert!(!diffs[0].iter().any(|name| name.ends_with("MoveSystem")));
+}
+
+#[test]
+fn test_queries_visit_entities_in_ascending_index_order() {
+    let mut world = World::new();
+    world.add_system::<OrderSystem>();
+    world.build_scheduler();
+
+    // Spread over several inner and root blocks, set out of order
+    let entities: Vec<Entity> = (0..20_000).map(|_| world.spawn()).collect();
+    for &i in &[19_000usize, 5, 300, 16_384, 129, 0, 127, 128] {
+        world.set(entities[i], &Position { x: 0 });
+    }
+
+    VISITED.lock().unwrap().clear();
+    world.run();
+    let visited = std::mem::take(&mut *VISITED.lock().unwrap());
+    assert_eq!(visited, [0, 5, 127, 128, 129, 300, 16_384, 19_000]);
+
+    let indices: Vec<u32> = world
+        .query::<(&Entity, &Position)>()
+        .map(|(entity, _)| entity.index())
+        .collect();
+    assert_eq!(indices, visited);
+}
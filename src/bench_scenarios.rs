@@ -0,0 +1,276 @@
+//! Stable benchmark scenarios.
+//!
+//! The crate's criterion suite (`cargo bench --bench scenarios`) runs exactly these
+//! scenarios, so regressions in storage or macro codegen show up under stable names. They
+//! can also be run without criterion, e.g. to compare hardware:
+//!
+//! ```ignore
+//! for m in rollback_ecs::bench_scenarios::run_all(100) {
+//!     println!("{:40} {:?}", m.name, m.per_iteration());
+//! }
+//! ```
+//!
+//! Scenario names and workloads are part of the suite's contract: change a workload only
+//! together with its name, so results stay comparable across versions.
+//!
+//! | Group      | Name                      | One iteration                                   |
+//! |------------|---------------------------|-------------------------------------------------|
+//! | `spawn`    | `spawn/{n}`               | spawns `n` entities with one component          |
+//! | `iterate`  | `iterate/{k}_of_3/{d}pct` | a tick querying `k` components at density `d`   |
+//! | `rollback` | `rollback/depth_{n}`      | `n` ticks writing every entity, then rolls back |
+//! | `snapshot` | `snapshot/record_{d}pct`  | one tick writing `d`% of entities, rolled back  |
+//! | `snapshot` | `snapshot/wire_packet`    | encodes one storage as a wire snapshot          |
+//!
+//! Worlds hold `ENTITIES` entities unless stated otherwise. Scenarios that write roll
+//! back within each iteration, so the rollback history doesn't grow while measuring.
+
+use crate::component::Component;
+use crate::system::system;
+use crate::tick::Tick;
+use crate::wire::{DecodeError, PacketWriter, Reader, Wire};
+use crate::world::World;
+use std::time::{Duration, Instant};
+
+/// Entities in every world-based scenario.
+pub const ENTITIES: u32 = 10_000;
+
+/// Spawn batch sizes.
+pub const SPAWN_COUNTS: [u32; 2] = [1_000, 10_000];
+
+/// Percentages of entities holding the queried components.
+pub const DENSITIES: [u32; 3] = [100, 50, 10];
+
+/// Ticks simulated and rolled back per rollback iteration.
+pub const ROLLBACK_DEPTHS: [u32; 3] = [1, 8, 32];
+
+#[derive(Component, Default, Clone, Debug, PartialEq)]
+struct BenchA {
+    value: i32,
+}
+
+#[derive(Component, Default, Clone, Debug, PartialEq)]
+struct BenchB {
+    value: i32,
+}
+
+#[derive(Component, Default, Clone, Debug, PartialEq)]
+struct BenchC {
+    value: i32,
+}
+
+impl Wire for BenchA {
+    fn encode(&self, out: &mut Vec<u8>) {
+        self.value.encode(out);
+    }
+
+    fn decode(reader: &mut Reader<'_>) -> Result<Self, DecodeError> {
+        Ok(BenchA {
+            value: i32::decode(reader)?,
+        })
+    }
+}
+
+system! {
+    IterateOne {
+        query! {
+            fn iterate(a: View<BenchA>) {
+                std::hint::black_box(a.value);
+            }
+        }
+    }
+}
+
+system! {
+    IterateTwo {
+        query! {
+            fn iterate(a: View<BenchA>, b: View<BenchB>) {
+                std::hint::black_box(a.value + b.value);
+            }
+        }
+    }
+}
+
+system! {
+    IterateThree {
+        query! {
+            fn iterate(a: View<BenchA>, b: View<BenchB>, c: View<BenchC>) {
+                std::hint::black_box(a.value + b.value + c.value);
+            }
+        }
+    }
+}
+
+system! {
+    WriteA {
+        query! {
+            fn write(a: &mut ViewMut<BenchA>) {
+                a.value += 1;
+            }
+        }
+    }
+}
+
+/// One benchmark workload, see the module docs.
+pub struct Scenario {
+    name: String,
+    prepare: Box<dyn Fn() -> Box<dyn FnMut()>>,
+}
+
+impl Scenario {
+    fn new<S: FnMut() + 'static>(name: String, prepare: impl Fn() -> S + 'static) -> Self {
+        Scenario {
+            name,
+            prepare: Box::new(move || Box::new(prepare())),
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Builds the scenario's state and returns one iteration of its workload. Setup isn't
+    /// part of the measurement; each call of the returned closure is.
+    pub fn prepare(&self) -> Box<dyn FnMut()> {
+        (self.prepare)()
+    }
+
+    /// Times `iterations` iterations after one warmup iteration.
+    pub fn measure(&self, iterations: u32) -> Measurement {
+        let mut step = self.prepare();
+        step();
+
+        let start = Instant::now();
+        for _ in 0..iterations {
+            step();
+        }
+
+        Measurement {
+            name: self.name.clone(),
+            iterations,
+            total: start.elapsed(),
+        }
+    }
+}
+
+/// Timing of one scenario, from `Scenario::measure`.
+#[derive(Clone, Debug)]
+pub struct Measurement {
+    pub name: String,
+    pub iterations: u32,
+    pub total: Duration,
+}
+
+impl Measurement {
+    pub fn per_iteration(&self) -> Duration {
+        self.total / self.iterations.max(1)
+    }
+}
+
+/// Every scenario, in a fixed order.
+pub fn all() -> Vec<Scenario> {
+    let mut scenarios = Vec::new();
+
+    for count in SPAWN_COUNTS {
+        scenarios.push(Scenario::new(format!("spawn/{}", count), move || {
+            move || {
+                let mut world = World::new();
+                for i in 0..count {
+                    let e = world.spawn();
+                    world.set(e, &BenchA { value: i as i32 });
+                }
+                std::hint::black_box(&world);
+            }
+        }));
+    }
+
+    for components in 1..=3 {
+        for density in DENSITIES {
+            let name = format!("iterate/{}_of_3/{}pct", components, density);
+            scenarios.push(Scenario::new(name, move || {
+                let mut world = populated_world(density);
+                match components {
+                    1 => world.add_system::<IterateOne>(),
+                    2 => world.add_system::<IterateTwo>(),
+                    _ => world.add_system::<IterateThree>(),
+                }
+                world.build_scheduler();
+                move || world.run()
+            }));
+        }
+    }
+
+    for depth in ROLLBACK_DEPTHS {
+        scenarios.push(Scenario::new(
+            format!("rollback/depth_{}", depth),
+            move || {
+                let mut world = writing_world(100);
+                move || {
+                    let start = world.current_tick();
+                    for _ in 0..depth {
+                        world.run();
+                    }
+                    world.resimulate_from(start);
+                }
+            },
+        ));
+    }
+
+    for density in DENSITIES {
+        let name = format!("snapshot/record_{}pct", density);
+        scenarios.push(Scenario::new(name, move || {
+            let mut world = writing_world(density);
+            move || {
+                let start = world.current_tick();
+                world.run();
+                world.resimulate_from(start);
+            }
+        }));
+    }
+
+    scenarios.push(Scenario::new("snapshot/wire_packet".to_string(), || {
+        let world = populated_world(100);
+        move || {
+            let mut writer = PacketWriter::snapshot(Tick::new(0));
+            world.write_snapshot::<BenchA>(&mut writer);
+            std::hint::black_box(writer.finish());
+        }
+    }));
+
+    scenarios
+}
+
+/// Measures every scenario with `iterations` iterations each.
+pub fn run_all(iterations: u32) -> Vec<Measurement> {
+    all().iter().map(|s| s.measure(iterations)).collect()
+}
+
+/// `ENTITIES` entities, with `BenchA`, `BenchB` and `BenchC` on `density`% of them.
+fn populated_world(density: u32) -> World {
+    let mut world = World::new();
+    let stride = 100 / density;
+
+    for i in 0..ENTITIES {
+        let e = world.spawn();
+        if i % stride == 0 {
+            let value = i as i32;
+            world.set(e, &BenchA { value });
+            world.set(e, &BenchB { value });
+            world.set(e, &BenchC { value });
+        }
+    }
+    world
+}
+
+/// A populated world whose one system writes `BenchA` every tick. One tick has already
+/// run, so the history covers the tick iterations start from.
+fn writing_world(density: u32) -> World {
+    let mut world = populated_world(density);
+    world.add_system::<WriteA>();
+    world.build_scheduler();
+    world.run();
+    world
+}
+
+#[cfg(test)]
+#[path = "bench_scenarios.tests.rs"]
+mod tests;
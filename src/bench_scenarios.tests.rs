@@ -0,0 +1,42 @@
+use super::*;
+use std::collections::BTreeSet;
+
+#[test]
+fn test_scenario_names_are_unique_and_stable() {
+    let names: Vec<String> = all().iter().map(|s| s.name().to_string()).collect();
+    let unique: BTreeSet<&String> = names.iter().collect();
+    assert_eq!(unique.len(), names.len());
+
+    assert_eq!(names.first().map(String::as_str), Some("spawn/1000"));
+    assert!(names.iter().any(|n| n == "iterate/3_of_3/10pct"));
+    assert!(names.iter().any(|n| n == "rollback/depth_32"));
+    assert_eq!(
+        names.last().map(String::as_str),
+        Some("snapshot/wire_packet")
+    );
+}
+
+#[test]
+fn test_writing_scenarios_leave_history_bounded() {
+    let mut world = writing_world(10);
+    let start = world.current_tick();
+
+    for _ in 0..3 {
+        world.run();
+        world.resimulate_from(start);
+    }
+    assert_eq!(world.current_tick(), start);
+
+    let storage = world.get_storage::<BenchA>();
+    assert_eq!(
+        unsafe { (*storage.get()).get(0) },
+        Some(&BenchA { value: 1 })
+    );
+}
+
+#[test]
+fn test_every_scenario_runs() {
+    let measurements = run_all(1);
+    assert_eq!(measurements.len(), all().len());
+    assert!(measurements.iter().all(|m| m.iterations == 1));
+}
@@ -0,0 +1,208 @@
+//! Optional AABB broadphase (`physics-broadphase` feature).
+//!
+//! Give entities a `Collider` and schedule the `BroadphaseSystem`. Every tick it bins the
+//! colliders into a uniform grid, tests the boxes sharing a cell and writes the
+//! overlapping pairs to `BroadphasePairs`: each pair is stored once, on the entity with
+//! the lower index, as a sorted list of its partners. Narrowphase systems read
+//! `BroadphasePairs` and the scheduler orders them after the broadphase automatically,
+//! since it writes what they read.
+//!
+//! Boxes use integer coordinates (e.g. fixed-point) so results are identical on every
+//! peer. The grid is rebuilt from scratch each tick and the pairs are an ordinary
+//! component, so rollback and resimulation need nothing special.
+//!
+//! # Example
+//! ```ignore
+//! world.add_system::<BroadphaseSystem>(); // or BroadphaseSystem::with_cell_size
+//! world.set(ship, &Collider::new([0, 0], [32, 32]));
+//!
+//! system! {
+//!     Narrowphase {
+//!         query! {
+//!             fn hits(e: View<Entity>, pairs: View<BroadphasePairs>) {
+//!                 for other in &pairs.others { /* exact test between *e and *other */ }
+//!             }
+//!         }
+//!     }
+//! }
+//! ```
+
+use crate::component::Component;
+use crate::entity::Entity;
+use crate::scheduler::PipelineStage;
+use crate::storage::{ComponentStorage, Storage};
+use crate::world::World;
+use std::any::TypeId;
+use std::cell::UnsafeCell;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::rc::Rc;
+
+/// Axis-aligned box, `min` inclusive and `max` exclusive. Two colliders can only pair if
+/// their `layers` share a bit.
+#[derive(Component, Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Collider {
+    pub min: [i32; 2],
+    pub max: [i32; 2],
+    pub layers: u32,
+}
+
+impl Collider {
+    /// A box on every layer.
+    pub fn new(min: [i32; 2], max: [i32; 2]) -> Self {
+        Collider {
+            min,
+            max,
+            layers: u32::MAX,
+        }
+    }
+
+    pub fn with_layers(mut self, layers: u32) -> Self {
+        self.layers = layers;
+        self
+    }
+
+    pub fn overlaps(&self, other: &Collider) -> bool {
+        self.layers & other.layers != 0
+            && self.min[0] < other.max[0]
+            && other.min[0] < self.max[0]
+            && self.min[1] < other.max[1]
+            && other.min[1] < self.max[1]
+    }
+
+    fn is_empty(&self) -> bool {
+        self.max[0] <= self.min[0] || self.max[1] <= self.min[1]
+    }
+}
+
+impl Default for Collider {
+    fn default() -> Self {
+        Collider::new([0, 0], [0, 0])
+    }
+}
+
+/// Colliders overlapping this entity's that have a higher index, in ascending index
+/// order. Absent on entities without such a pair.
+#[derive(Component, Clone, Default, PartialEq, Eq, Debug)]
+pub struct BroadphasePairs {
+    pub others: Vec<Entity>,
+}
+
+/// Finds overlapping `Collider`s each tick, see the module docs.
+pub struct BroadphaseSystem {
+    pub colliders: Rc<UnsafeCell<Storage<Collider>>>,
+    pub pairs: Rc<UnsafeCell<Storage<BroadphasePairs>>>,
+    pub entities: Rc<UnsafeCell<Storage<Entity>>>,
+    pub cell_size: i32,
+}
+
+unsafe impl Send for BroadphaseSystem {}
+unsafe impl Sync for BroadphaseSystem {}
+
+impl BroadphaseSystem {
+    /// Cell size used by `create`, in collider units.
+    pub const DEFAULT_CELL_SIZE: i32 = 64;
+
+    /// Creates the system with a custom grid cell size; schedule it with
+    /// `World::add_system_instance`. Cells about the size of a typical collider work best.
+    ///
+    /// # Panics
+    /// Panics if `cell_size` isn't positive.
+    pub fn with_cell_size(world: &mut World, cell_size: i32) -> Self {
+        assert!(cell_size > 0, "broadphase cell size must be positive");
+        BroadphaseSystem {
+            colliders: world.get_storage::<Collider>(),
+            pairs: world.get_storage::<BroadphasePairs>(),
+            entities: world.get_storage::<Entity>(),
+            cell_size,
+        }
+    }
+
+    /// All overlapping pairs as `(lower index, higher index)`, in ascending order.
+    fn find_pairs(&self, colliders: &Storage<Collider>) -> BTreeSet<(u32, u32)> {
+        // Cell order doesn't matter: pairs are collected into an ordered set
+        let mut cells: HashMap<(i32, i32), Vec<u32>> = HashMap::new();
+        colliders.visit(|index, collider| {
+            if collider.is_empty() {
+                return;
+            }
+
+            let lo = collider.min.map(|v| v.div_euclid(self.cell_size));
+            let hi = collider.max.map(|v| (v - 1).div_euclid(self.cell_size));
+            for cx in lo[0]..=hi[0] {
+                for cy in lo[1]..=hi[1] {
+                    cells.entry((cx, cy)).or_default().push(index);
+                }
+            }
+        });
+
+        let mut pairs = BTreeSet::new();
+        for members in cells.values() {
+            for (i, &a) in members.iter().enumerate() {
+                for &b in &members[i + 1..] {
+                    let (ca, cb) = (colliders.get(a).unwrap(), colliders.get(b).unwrap());
+                    if ca.overlaps(cb) {
+                        pairs.insert((a.min(b), a.max(b)));
+                    }
+                }
+            }
+        }
+        pairs
+    }
+}
+
+impl PipelineStage for BroadphaseSystem {
+    fn type_id(&self) -> TypeId {
+        TypeId::of::<Self>()
+    }
+
+    fn run(&self) {
+        let colliders = unsafe { &*self.colliders.get() };
+        let pairs = unsafe { &mut *self.pairs.get() };
+        let entities = unsafe { &*self.entities.get() };
+
+        let mut found: BTreeMap<u32, BroadphasePairs> = BTreeMap::new();
+        for (a, b) in self.find_pairs(colliders) {
+            if let Some(&other) = entities.get(b) {
+                found.entry(a).or_default().others.push(other);
+            }
+        }
+
+        // Only touch slots whose pairs changed, keeping snapshots small
+        let mut stale = Vec::new();
+        pairs.visit(|index, _| {
+            if !found.contains_key(&index) {
+                stale.push(index);
+            }
+        });
+        for index in stale {
+            pairs.remove(index);
+        }
+
+        for (index, new) in found {
+            if pairs.get(index) != Some(&new) {
+                pairs.set(index, &new);
+            }
+        }
+    }
+
+    fn reads(&self) -> &'static [TypeId] {
+        static READS: &[TypeId] = &[TypeId::of::<Collider>(), TypeId::of::<Entity>()];
+        READS
+    }
+
+    fn writes(&self) -> &'static [TypeId] {
+        crate::scheduler::type_id_slice::<BroadphasePairs>()
+    }
+
+    fn parent(&self) -> Option<TypeId> {
+        Some(TypeId::of::<crate::scheduler::SimulationGroup>())
+    }
+
+    fn create(world: &mut World) -> Self {
+        Self::with_cell_size(world, Self::DEFAULT_CELL_SIZE)
+    }
+}
+
+#[cfg(test)]
+#[path = "broadphase.tests.rs"]
+mod tests;
@@ -0,0 +1,98 @@
+use super::*;
+use crate::prelude::*;
+
+#[derive(Component, Clone, Default, Debug, PartialEq)]
+struct Hits {
+    count: u32,
+}
+
+system! {
+    CountHits {
+        query! {
+            fn count_hits(hits: &mut ViewMut<Hits>, pairs: View<BroadphasePairs>) {
+                hits.count += pairs.others.len() as u32;
+            }
+        }
+    }
+}
+
+fn world_with(cell_size: i32) -> World {
+    let mut world = World::new();
+    let system = BroadphaseSystem::with_cell_size(&mut world, cell_size);
+    world.add_system_instance(Box::new(system));
+    world.add_system::<CountHits>();
+    world.build_scheduler();
+    world
+}
+
+fn pairs_of(world: &mut World, entity: Entity) -> Vec<Entity> {
+    let storage = world.get_storage::<BroadphasePairs>();
+    unsafe { (*storage.get()).get(entity.index()) }
+        .map(|p| p.others.clone())
+        .unwrap_or_default()
+}
+
+#[test]
+fn test_overlapping_colliders_pair_once_on_lower_index() {
+    let mut world = world_with(16);
+    let a = world.spawn();
+    let b = world.spawn();
+    let c = world.spawn();
+    let far = world.spawn();
+    world.set(a, &Collider::new([0, 0], [40, 40]));
+    world.set(b, &Collider::new([30, 30], [50, 50]));
+    world.set(c, &Collider::new([-10, 35], [5, 45]));
+    world.set(far, &Collider::new([1000, 1000], [1010, 1010]));
+    world.run();
+
+    assert_eq!(pairs_of(&mut world, a), vec![b, c]);
+    assert_eq!(pairs_of(&mut world, b), vec![]);
+    assert_eq!(pairs_of(&mut world, c), vec![]);
+    assert_eq!(pairs_of(&mut world, far), vec![]);
+
+    // Touching edges don't overlap, and layers must share a bit
+    let d = world.spawn();
+    let e = world.spawn();
+    world.set(d, &Collider::new([2000, 0], [2010, 10]));
+    world.set(e, &Collider::new([2010, 0], [2020, 10]));
+    world.set(far, &Collider::new([0, 0], [10, 10]).with_layers(0));
+    world.run();
+    assert_eq!(pairs_of(&mut world, d), vec![]);
+    assert_eq!(pairs_of(&mut world, a), vec![b, c]);
+}
+
+#[test]
+fn test_pairs_follow_movement_and_roll_back() {
+    let mut world = world_with(BroadphaseSystem::DEFAULT_CELL_SIZE);
+    let a = world.spawn();
+    let b = world.spawn();
+    world.set(a, &Collider::new([0, 0], [10, 10]));
+    world.set(b, &Collider::new([5, 5], [15, 15]));
+    world.run();
+    let overlapping = world.current_tick();
+    assert_eq!(pairs_of(&mut world, a), vec![b]);
+
+    world.set(b, &Collider::new([500, 500], [510, 510]));
+    world.run();
+    assert_eq!(pairs_of(&mut world, a), vec![]);
+
+    world.resimulate_from(overlapping);
+    assert_eq!(pairs_of(&mut world, a), vec![b]);
+}
+
+#[test]
+fn test_narrowphase_sees_pairs_in_the_same_tick() {
+    let mut world = world_with(8);
+    let a = world.spawn();
+    let b = world.spawn();
+    world.set(a, &Collider::new([0, 0], [100, 100]));
+    world.set(a, &Hits::default());
+    world.set(b, &Collider::new([-50, -50], [1, 1]));
+    world.run();
+
+    let hits = world.get_storage::<Hits>();
+    assert_eq!(
+        unsafe { (*hits.get()).get(a.index()) },
+        Some(&Hits { count: 1 })
+    );
+}
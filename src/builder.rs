@@ -0,0 +1,130 @@
+//! Declaring a world up front: components, resources, systems and groups registered before
+//! the first tick.
+//!
+//! A `World` registers lazily: the first access to a storage creates it together with its
+//! cleanup system, and systems, plugins and loop groups added after `build_scheduler()`
+//! invalidate the built schedule, which is rebuilt before the next tick. That is handy in
+//! tests, but in a game it hides a rebuild in whichever tick first touches a component.
+//!
+//! `WorldBuilder` collects every declaration and `build()` creates the storages (warmed
+//! with a `WarmupPlan` if given), inserts the resources and builds the scheduler once. The
+//! entity and `Destroyed` storages are always registered, so spawning and destroying need
+//! no declaration.
+//!
+//! In strict mode the built world also rejects late registration: creating a storage or
+//! resource cell that wasn't declared, and adding or removing systems, plugins or loop
+//! groups, panic with the name of what was missing, so the schedule built at startup is
+//! the one every tick runs. Everything else, including enabling and disabling systems,
+//! works as usual.
+//!
+//! # Example
+//! ```ignore
+//! let mut world = WorldBuilder::new()
+//!     .component::<Position>()
+//!     .component::<Velocity>()
+//!     .tag("frozen")
+//!     .resource(Gravity(-9.8))
+//!     .plugin(NetworkPlugin)
+//!     .system::<MoveSystem>()
+//!     .warmup(WarmupPlan::new().prefab::<(Position, Velocity)>(1_000))
+//!     .strict()
+//!     .build();
+//! world.run();
+//! ```
+
+use crate::component::{Component, Destroyed};
+use crate::entity::Entity;
+use crate::plugin::Plugin;
+use crate::scheduler::{LoopGroup, PipelineGroup, PipelineStage};
+use crate::warmup::WarmupPlan;
+use crate::world::World;
+
+/// Collects the declarations of a world, see the module docs.
+pub struct WorldBuilder {
+    world: World,
+    warmup: Option<WarmupPlan>,
+    strict: bool,
+}
+
+impl WorldBuilder {
+    pub fn new() -> Self {
+        let mut world = World::new();
+        world.get_storage::<Entity>();
+        world.get_storage::<Destroyed>();
+        WorldBuilder {
+            world,
+            warmup: None,
+            strict: false,
+        }
+    }
+
+    /// Registers the storage of `T` and its cleanup system.
+    pub fn component<T: Component>(mut self) -> Self {
+        self.world.get_storage::<T>();
+        self
+    }
+
+    /// Registers a tag by name, see `World::register_tag`. Tags get their bits in
+    /// declaration order.
+    pub fn tag(mut self, name: &'static str) -> Self {
+        self.world.register_tag(name);
+        self
+    }
+
+    /// Sets the initial value of resource `T`.
+    pub fn resource<T: Clone + 'static>(mut self, value: T) -> Self {
+        self.world.insert_resource(value);
+        self
+    }
+
+    /// Adds the system `S`, registering the storages it uses.
+    pub fn system<S: PipelineStage>(mut self) -> Self {
+        self.world.add_system::<S>();
+        self
+    }
+
+    /// Makes the pipeline group `G` a loop group, see `World::add_loop_group`.
+    pub fn loop_group<G: PipelineGroup>(mut self, group: LoopGroup) -> Self {
+        self.world.add_loop_group::<G>(group);
+        self
+    }
+
+    /// Builds `plugin` into the world, see `World::add_plugin`.
+    pub fn plugin<P: Plugin>(mut self, plugin: P) -> Self {
+        self.world.add_plugin(plugin);
+        self
+    }
+
+    /// Preallocates the storages for the population of `plan` when the world is built,
+    /// registering every storage it uses.
+    pub fn warmup(mut self, plan: WarmupPlan) -> Self {
+        self.warmup = Some(plan);
+        self
+    }
+
+    /// Makes the built world reject late registration, see the module docs.
+    pub fn strict(mut self) -> Self {
+        self.strict = true;
+        self
+    }
+
+    /// Creates the declared storages and builds the scheduler.
+    pub fn build(mut self) -> World {
+        if let Some(plan) = &self.warmup {
+            self.world.warmup_plan(plan);
+        }
+        self.world.build_scheduler();
+        self.world.set_strict(self.strict);
+        self.world
+    }
+}
+
+impl Default for WorldBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+#[path = "builder.tests.rs"]
+mod tests;
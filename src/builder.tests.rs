@@ -0,0 +1,82 @@
+use super::*;
+use crate::prelude::*;
+
+#[derive(Component, Clone, Default, PartialEq, Debug)]
+struct Fuel(u32);
+
+#[derive(Component, Clone, Default, PartialEq, Debug)]
+struct Cargo(u32);
+
+#[derive(Clone, Default, PartialEq, Debug)]
+struct Burn(u32);
+
+system! {
+    BurnSystem {
+        query! {
+            fn burn(fuel: &mut ViewMut<Fuel>, rate: Res<Burn>) {
+                fuel.0 -= rate.0;
+            }
+        }
+    }
+}
+
+fn strict_world() -> World {
+    WorldBuilder::new()
+        .component::<Cargo>()
+        .resource(Burn(2))
+        .system::<BurnSystem>()
+        .warmup(WarmupPlan::new().prefab::<(Fuel, Cargo)>(500))
+        .strict()
+        .build()
+}
+
+#[test]
+fn test_builder_registers_everything_up_front() {
+    let mut world = strict_world();
+    assert!(world.is_strict());
+    assert!(world.scheduler().is_some());
+    let warmed = world.component_memory::<Fuel>();
+
+    let entities: Vec<Entity> = (0..500).map(|_| world.spawn()).collect();
+    for &e in &entities {
+        world.set(e, &Fuel(10));
+        world.set(e, &Cargo(1));
+    }
+    world.run();
+    world.destroy(entities[0]);
+    world.run();
+
+    assert_eq!(world.get::<Fuel>(entities[1]), Some(&Fuel(6)));
+    assert_eq!(world.get::<Fuel>(entities[0]), None);
+    assert_eq!(world.component_memory::<Fuel>(), warmed);
+    world.insert_resource(Burn(1));
+}
+
+#[test]
+#[should_panic(expected = "late registration of storage")]
+fn test_strict_world_rejects_undeclared_components() {
+    #[derive(Component, Clone, Default, PartialEq, Debug)]
+    struct Hull(u32);
+
+    let mut world = strict_world();
+    let e = world.spawn();
+    world.set(e, &Hull(1));
+}
+
+#[test]
+#[should_panic(expected = "late registration of system")]
+fn test_strict_world_rejects_systems_after_build() {
+    let mut world = WorldBuilder::new().strict().build();
+    world.add_system_dynamic::<BurnSystem>();
+}
+
+#[test]
+fn test_lenient_builder_still_registers_late() {
+    let mut world = WorldBuilder::new().resource(Burn(1)).build();
+    assert!(!world.is_strict());
+    world.add_system_dynamic::<BurnSystem>();
+    let e = world.spawn();
+    world.set(e, &Fuel(3));
+    world.run();
+    assert_eq!(world.get::<Fuel>(e), Some(&Fuel(2)));
+}
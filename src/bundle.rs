@@ -0,0 +1,78 @@
+//! Bundles of components inserted together.
+//!
+//! A `Bundle` is a tuple of up to eight components, or a struct deriving `Bundle` whose
+//! fields are all components. `World::spawn_bundle` and `World::insert_bundle` check the
+//! entity once and then write each component straight to its storage, one `set` per
+//! storage. Components set through a bundle are change-tracked and rolled back like those
+//! set one by one with `World::set`. When a bundle holds the same component type twice,
+//! the later one wins.
+//!
+//! # Example
+//! ```ignore
+//! #[derive(Bundle, Clone)]
+//! struct ShipBundle {
+//!     position: Position,
+//!     velocity: Velocity,
+//!     health: Health,
+//! }
+//!
+//! let ship = world.spawn_bundle(ShipBundle { position, velocity, health });
+//! world.insert_bundle(ship, (Shield(50), Target::default()));
+//! ```
+
+use crate::component::Component;
+use crate::entity::Entity;
+use crate::world::World;
+
+pub use rollback_macros::Bundle;
+
+/// A set of components inserted with one call, see the module docs.
+pub trait Bundle: 'static {
+    /// Sets every component of the bundle through `writer`.
+    fn write(&self, writer: &mut BundleWriter<'_>);
+}
+
+/// Sets the components of a bundle on an entity `World::insert_bundle` already checked.
+pub struct BundleWriter<'w> {
+    world: &'w mut World,
+    entity: Entity,
+}
+
+impl<'w> BundleWriter<'w> {
+    pub(crate) fn new(world: &'w mut World, entity: Entity) -> Self {
+        BundleWriter { world, entity }
+    }
+
+    /// Sets `component` on the bundle's entity.
+    pub fn set<T: Component>(&mut self, component: &T) {
+        self.world.write_component(self.entity, component);
+    }
+
+    /// Sets every component of a nested bundle.
+    pub fn set_bundle<B: Bundle>(&mut self, bundle: &B) {
+        bundle.write(self);
+    }
+}
+
+macro_rules! impl_bundle {
+    ($($t:ident: $i:tt),+) => {
+        impl<$($t: Component),+> Bundle for ($($t,)+) {
+            fn write(&self, writer: &mut BundleWriter<'_>) {
+                $(writer.set(&self.$i);)+
+            }
+        }
+    };
+}
+
+impl_bundle!(A: 0);
+impl_bundle!(A: 0, B: 1);
+impl_bundle!(A: 0, B: 1, C: 2);
+impl_bundle!(A: 0, B: 1, C: 2, D: 3);
+impl_bundle!(A: 0, B: 1, C: 2, D: 3, E: 4);
+impl_bundle!(A: 0, B: 1, C: 2, D: 3, E: 4, F: 5);
+impl_bundle!(A: 0, B: 1, C: 2, D: 3, E: 4, F: 5, G: 6);
+impl_bundle!(A: 0, B: 1, C: 2, D: 3, E: 4, F: 5, G: 6, H: 7);
+
+#[cfg(test)]
+#[path = "bundle.tests.rs"]
+mod tests;
@@ -0,0 +1,88 @@
+use super::*;
+use crate::prelude::*;
+
+#[derive(Component, Clone, Default, Debug, PartialEq)]
+struct Position {
+    x: i32,
+}
+
+#[derive(Component, Clone, Default, Debug, PartialEq)]
+struct Velocity {
+    dx: i32,
+}
+
+#[derive(Component, Clone, Default, Debug, PartialEq)]
+struct Health(u32);
+
+#[derive(Bundle, Clone)]
+struct Body {
+    position: Position,
+    velocity: Velocity,
+}
+
+#[derive(Bundle, Clone)]
+struct Ship {
+    #[bundle]
+    body: Body,
+    health: Health,
+}
+
+fn world() -> World {
+    let mut world = World::new();
+    world.get_storage::<Position>();
+    world.get_storage::<Velocity>();
+    world.get_storage::<Health>();
+    world.build_scheduler();
+    world
+}
+
+#[test]
+fn test_spawn_bundle_sets_every_component() {
+    let mut world = world();
+    let ship = world.spawn_bundle(Ship {
+        body: Body {
+            position: Position { x: 1 },
+            velocity: Velocity { dx: 2 },
+        },
+        health: Health(3),
+    });
+    assert_eq!(world.get::<Position>(ship), Some(&Position { x: 1 }));
+    assert_eq!(world.get::<Velocity>(ship), Some(&Velocity { dx: 2 }));
+    assert_eq!(world.get::<Health>(ship), Some(&Health(3)));
+
+    // Tuples are bundles too, and later components win
+    world.insert_bundle(ship, (Health(4), Position { x: 5 }, Health(6)));
+    assert_eq!(world.get::<Position>(ship), Some(&Position { x: 5 }));
+    assert_eq!(world.get::<Health>(ship), Some(&Health(6)));
+}
+
+#[test]
+fn test_bundles_roll_back_like_single_sets() {
+    let mut world = world();
+    let start = world.current_tick();
+    world.run();
+
+    let ship = world.spawn();
+    world.insert_bundle(ship, (Position { x: 1 }, Velocity { dx: 1 }));
+    world.run();
+    world.insert_bundle(ship, (Position { x: 2 }, Health(9)));
+    world.run();
+
+    world.rollback(Tick::new(start.value() + 1));
+    assert_eq!(world.get::<Position>(ship), Some(&Position { x: 1 }));
+    assert_eq!(world.get::<Health>(ship), None);
+
+    world.rollback(start);
+    assert_eq!(world.get::<Position>(ship), None);
+    assert_eq!(world.get::<Velocity>(ship), None);
+}
+
+#[test]
+#[should_panic(expected = "which does not exist")]
+fn test_insert_bundle_on_missing_entity_panics() {
+    let mut world = world();
+    let ship = world.spawn();
+    world.destroy(ship);
+    world.run();
+    world.insert_bundle(ship, (Health(1),));
+}
@@ -7,6 +7,53 @@ pub fn next_id() -> usize {
     NEXT_ID.fetch_add(1, Ordering::Relaxed)
 }
 
+/// Most component types a process can use. Type indices from `next_id` must stay below it.
+pub const MAX_COMPONENTS: usize = 256;
+
+/// A set of component type indices, such as the storages a world has created.
+#[derive(Clone, Copy, Default, Debug, PartialEq, Eq)]
+pub struct ComponentMask([u128; MAX_COMPONENTS / 128]);
+
+impl ComponentMask {
+    pub fn contains(&self, id: usize) -> bool {
+        id < MAX_COMPONENTS && (self.0[id / 128] >> (id % 128)) & 1 != 0
+    }
+
+    /// # Panics
+    /// Panics if `id` is not below `MAX_COMPONENTS`.
+    pub fn insert(&mut self, id: usize) {
+        self.0[id / 128] |= 1 << (id % 128);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.iter().all(|&bits| bits == 0)
+    }
+
+    /// The indices in `self` but not in `other`.
+    pub fn without(self, other: ComponentMask) -> ComponentMask {
+        ComponentMask(std::array::from_fn(|i| self.0[i] & !other.0[i]))
+    }
+
+    /// The indices in the set, in ascending order.
+    pub fn iter(self) -> impl Iterator<Item = usize> {
+        self.0.into_iter().enumerate().flat_map(|(word, mut bits)| {
+            std::iter::from_fn(move || {
+                let bit = bits.trailing_zeros() as usize;
+                bits &= bits.wrapping_sub(1);
+                (bit < 128).then_some(word * 128 + bit)
+            })
+        })
+    }
+}
+
+impl std::ops::BitOr for ComponentMask {
+    type Output = ComponentMask;
+
+    fn bitor(self, other: ComponentMask) -> ComponentMask {
+        ComponentMask(std::array::from_fn(|i| self.0[i] | other.0[i]))
+    }
+}
+
 pub trait Resource: Any + Clone + Default
 where
     Self: Sized,
@@ -14,11 +61,101 @@ where
     fn type_index() -> usize;
 }
 
+/// How a component type takes part in rollback, see `Component::ROLLBACK`.
+#[derive(Clone, Copy, Default, Debug, PartialEq, Eq)]
+pub enum RollbackPolicy {
+    /// Writes are recorded and rollback restores the values of the target tick.
+    #[default]
+    Snapshot,
+    /// Nothing is recorded and rollback leaves the values as they are, for render-only
+    /// state that the next tick overwrites anyway. Components of entities the rollback
+    /// despawns are still dropped.
+    Ignore,
+    /// Nothing is recorded and rollback drops every value, for derived caches that
+    /// systems rebuild on demand.
+    Reset,
+}
+
+/// Whether storages record the previous values of `T` for rollback.
+pub(crate) const fn records_history<T: Component>() -> bool {
+    !T::IS_TEMPORARY && matches!(T::ROLLBACK, RollbackPolicy::Snapshot)
+}
+
 pub trait Component: Resource {
+    /// Backend the world stores this component in, see `ComponentStorage`. Defaults to
+    /// the block tree; the derive switches to `SparseStorage` with
+    /// `#[component(storage = "sparse")]`.
+    type Storage: crate::storage::ComponentStorage<Item = Self> = crate::storage::Storage<Self>;
+
     /// Returns true if this component type is temporary and should not be tracked by rollback.
     /// Defaults to false. Temporary components should override this to return true.
     const IS_TEMPORARY: bool = false;
 
+    /// What rollback does with this component, from `#[component(rollback = "...")]`.
+    /// Defaults to `RollbackPolicy::Snapshot`.
+    const ROLLBACK: RollbackPolicy = RollbackPolicy::Snapshot;
+
+    /// Alignment `Storage::dense_block` slices of this type start on, from
+    /// `#[component(align(16))]` or `align(32)`. Zero if unspecified; every block is
+    /// aligned to `block::BLOCK_DATA_ALIGN` either way.
+    const BLOCK_ALIGN: usize = 0;
+
+    /// True if the type has `#[component(weak)]` fields, which its cleanup system resets
+    /// when their target is destroyed. Set by the derive.
+    const HAS_WEAK_REFS: bool = false;
+
+    /// Calls `f` with every `#[component(weak)]` field. Generated by the derive.
+    fn visit_weak_refs(&self, _f: &mut dyn FnMut(&crate::entity::EntityWeak)) {}
+
+    /// Calls `f` with every `#[component(weak)]` field, mutably. Generated by the derive.
+    fn visit_weak_refs_mut(&mut self, _f: &mut dyn FnMut(&mut crate::entity::EntityWeak)) {}
+
+    /// True for relation components, whose cleanup system also removes them when their
+    /// target is destroyed. Set by the derive for a `#[component(target)]` field.
+    const IS_RELATION: bool = false;
+
+    /// The entity a relation component links to, see the `relation` module. Generated by
+    /// the derive.
+    fn relation_target(&self) -> Option<crate::entity::Entity> {
+        None
+    }
+
+    /// Name the type's storage is listed under in `World::state_hash`, so it must be the
+    /// same on every peer and unique among the components. The derive uses the module path
+    /// and the type name, which change only when the type is moved or renamed. Defaults to
+    /// `std::any::type_name`, whose output isn't guaranteed across compiler versions.
+    fn stable_name() -> &'static str {
+        std::any::type_name::<Self>()
+    }
+
+    /// How values enter `World::state_hash`: `Hashable::hash_state` for types implementing
+    /// `Hash`, `None` for others, which only contribute which entities have them. Generated
+    /// by the derive.
+    fn state_hasher() -> Option<fn(&Self, &mut crate::statehash::StateHasher)> {
+        None
+    }
+
+    /// How `World::save_snapshot` encodes values when no codec is registered: `WireCodec`
+    /// for `SerializableComponent` types, `None` for others, which are left out. Generated
+    /// by the derive.
+    fn wire_codec() -> Option<&'static dyn crate::wire::SnapshotCodec<Self>> {
+        None
+    }
+
+    /// How `World::export_snapshot` converts values: `Some` for types implementing
+    /// `Serialize` and `Deserialize`, `None` for others, which are left out. Generated by
+    /// the derive.
+    #[cfg(feature = "serde")]
+    fn serde_fns() -> Option<crate::export::SerdeFns<Self>> {
+        None
+    }
+
+    /// True for `Copy` types, whose rollback snapshots and restores copy runs of slots
+    /// bytewise instead of cloning and dropping them one by one. Generated by the derive.
+    fn is_pod() -> bool {
+        false
+    }
+
     /// Returns the cleanup system for this component type as a boxed PipelineStage.
     /// The world will automatically schedule it when the component storage is first accessed.
     ///
@@ -38,6 +175,136 @@ pub trait Component: Resource {
     }
 }
 
+/// Picked by method resolution when `T: Copy`.
+#[doc(hidden)]
+pub trait ViaCopy<T> {
+    fn is_pod(&self) -> bool;
+}
+
+impl<T: Copy> ViaCopy<T> for crate::statehash::Probe<T> {
+    fn is_pod(&self) -> bool {
+        true
+    }
+}
+
+/// The fallback for types that are only `Clone`, one autoref further away.
+#[doc(hidden)]
+pub trait ViaClone<T> {
+    fn is_pod(&self) -> bool;
+}
+
+impl<T> ViaClone<T> for &crate::statehash::Probe<T> {
+    fn is_pod(&self) -> bool {
+        false
+    }
+}
+
+/// Expands to `Component::serde_fns` inside a component impl when the `serde` feature is
+/// on, and to nothing otherwise. Used by `#[derive(Component)]`, which can't see the
+/// features of this crate.
+#[cfg(feature = "serde")]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __component_serde_fns {
+    () => {
+        fn serde_fns() -> Option<$crate::export::SerdeFns<Self>> {
+            #[allow(unused_imports)]
+            use $crate::export::{ViaNoSerde, ViaSerde};
+            (&$crate::statehash::Probe::<Self>::new()).serde_fns()
+        }
+    };
+}
+
+#[cfg(not(feature = "serde"))]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __component_serde_fns {
+    () => {};
+}
+
+/// A tuple of up to eight component types, e.g. `(Position, Velocity)`, for APIs that act
+/// on several storages at once such as `World::watch_query` and `WarmupPlan::prefab`.
+pub trait ComponentSet {
+    /// Type-erased handles to the storages of every component in the set, creating them
+    /// if needed.
+    fn storages(world: &mut crate::world::World) -> Vec<Box<dyn crate::rollback::StorageLike>>;
+}
+
+macro_rules! impl_component_set {
+    ($($t:ident),+) => {
+        impl<$($t: Component),+> ComponentSet for ($($t,)+) {
+            fn storages(
+                world: &mut crate::world::World,
+            ) -> Vec<Box<dyn crate::rollback::StorageLike>> {
+                vec![$(Box::new(world.get_storage::<$t>()) as Box<dyn crate::rollback::StorageLike>),+]
+            }
+        }
+    };
+}
+
+impl_component_set!(A);
+impl_component_set!(A, B);
+impl_component_set!(A, B, C);
+impl_component_set!(A, B, C, D);
+impl_component_set!(A, B, C, D, E);
+impl_component_set!(A, B, C, D, E, F);
+impl_component_set!(A, B, C, D, E, F, G);
+impl_component_set!(A, B, C, D, E, F, G, H);
+
+/// A component type compiled into the program. `#[derive(Component)]` submits one for
+/// every component, so a world can create storages for types it never touched locally.
+pub struct ComponentRegistration {
+    pub type_name: fn() -> &'static str,
+    pub type_id: fn() -> std::any::TypeId,
+    /// `wire::component_id` of the type.
+    pub component_id: fn() -> u64,
+    /// Creates the type's storage in `world`, as `World::get_storage` does.
+    pub register: fn(&mut crate::world::World),
+    /// Decodes the type's section of a whole-world snapshot without creating the storage.
+    pub decode_snapshot: SnapshotSectionDecoder,
+}
+
+/// See `ComponentRegistration::decode_snapshot`.
+pub type SnapshotSectionDecoder =
+    fn(
+        &crate::world::World,
+        &crate::wire::Packet<'_>,
+    ) -> Option<Result<Box<dyn crate::savestate::SavedStorage>, crate::wire::DecodeError>>;
+
+impl ComponentRegistration {
+    pub const fn of<T: Component>() -> Self {
+        ComponentRegistration {
+            type_name: std::any::type_name::<T>,
+            type_id: std::any::TypeId::of::<T>,
+            component_id: crate::wire::component_id::<T>,
+            register: |world| {
+                world.get_storage::<T>();
+            },
+            decode_snapshot: crate::rollback::decode_snapshot_section::<T>,
+        }
+    }
+}
+
+inventory::collect!(ComponentRegistration);
+
+/// Every derived component type linked into the program, ordered by `component_id` so
+/// the order is the same on every peer running the same build.
+pub fn registered_components() -> Vec<&'static ComponentRegistration> {
+    let mut all: Vec<_> = inventory::iter::<ComponentRegistration>().collect();
+    all.sort_by_key(|r| (r.component_id)());
+    all
+}
+
+/// A marker component without fields, such as `Frozen` or `Boss`. `#[derive(Tag)]`
+/// derives `Component` and this trait, and rejects structs with fields.
+///
+/// Tags are zero-sized, so their blocks and rollback snapshots hold only bitmasks, see
+/// `Block::MASKS_ONLY`.
+///
+/// ```ignore
+/// #[derive(Tag, Clone, Default)]
+/// struct Frozen;
+/// ```
 pub trait Tag: Any
 where
     Self: Sized,
@@ -57,6 +324,10 @@ impl crate::component::Resource for Destroyed {
 impl crate::component::Component for Destroyed {
     const IS_TEMPORARY: bool = true;
 
+    fn stable_name() -> &'static str {
+        concat!(module_path!(), "::Destroyed")
+    }
+
     fn cleanup_system(
         world: &mut crate::world::World,
     ) -> Box<dyn crate::scheduler::PipelineStage> {
@@ -1,4 +1,4 @@
-use crate::component::{Component, Resource, Tag};
+use crate::component::{Component, ComponentMask, Resource, Tag, MAX_COMPONENTS};
 
 #[derive(Default, Clone)]
 struct TestComponent1 {
@@ -248,3 +248,66 @@ fn test_resource_sized() {
     assert_sized::<TestComponent1>();
     assert_sized::<TestTag1>();
 }
+
+#[test]
+fn test_component_mask_spans_every_index() {
+    let mut mask = ComponentMask::default();
+    assert!(mask.is_empty());
+    for id in [0, 5, 127, 128, 200, MAX_COMPONENTS - 1] {
+        mask.insert(id);
+    }
+    assert!(mask.contains(128) && !mask.contains(129) && !mask.contains(MAX_COMPONENTS));
+    assert_eq!(mask.iter().collect::<Vec<_>>(), vec![0, 5, 127, 128, 200, MAX_COMPONENTS - 1]);
+
+    let mut low = ComponentMask::default();
+    low.insert(5);
+    low.insert(200);
+    assert_eq!(mask.without(low).iter().collect::<Vec<_>>(), vec![0, 127, 128, MAX_COMPONENTS - 1]);
+    assert_eq!((mask.without(low) | low), mask);
+}
+
+#[derive(Component, Clone, Default, Debug, PartialEq)]
+struct Health(u32);
+
+#[derive(Component, Clone, Default, Debug, PartialEq)]
+#[component(rollback = "ignore")]
+struct Sprite(u32);
+
+#[derive(Component, Clone, Default, Debug, PartialEq)]
+#[component(rollback = "reset")]
+struct PathCache(u32);
+
+#[test]
+fn test_rollback_policy() {
+    use crate::component::RollbackPolicy;
+    use crate::storage::ComponentStorage;
+    use crate::world::World;
+
+    assert_eq!(Health::ROLLBACK, RollbackPolicy::Snapshot);
+    assert_eq!(Sprite::ROLLBACK, RollbackPolicy::Ignore);
+
+    let mut world = World::new();
+    let e = world.spawn();
+    world.set(e, &Health(1));
+    world.set(e, &Sprite(1));
+    world.set(e, &PathCache(1));
+    world.build_scheduler();
+    let start = world.current_tick();
+    world.run();
+
+    world.set(e, &Health(2));
+    world.set(e, &Sprite(2));
+    world.set(e, &PathCache(2));
+    let late = world.spawn();
+    world.set(late, &Sprite(3));
+    world.run();
+
+    world.rollback(start);
+    assert_eq!(world.get::<Health>(e), Some(&Health(1)));
+    // Ignored components keep their latest value, but not past their entity
+    assert_eq!(world.get::<Sprite>(e), Some(&Sprite(2)));
+    assert_eq!(world.storage_ref::<Sprite>().unwrap().len(), 1);
+    // Reset components are dropped, to be rebuilt by the resimulation
+    assert_eq!(world.get::<PathCache>(e), None);
+    assert!(world.storage_ref::<PathCache>().unwrap().is_empty());
+}
@@ -0,0 +1,260 @@
+//! Copy-on-write forks for speculative execution.
+//!
+//! AI lookahead and "preview" features want to try a few changes, inspect the outcome and
+//! throw it away, without touching the real world or its rollback history.
+//! `Storage::fork_cow()` returns a `CowStorage` that reads through to its parent and copies
+//! an inner block (128 slots) the first time it writes to it, so a fork that changes a
+//! handful of components costs a handful of block copies. `World::fork_cow()` does the
+//! same for every storage of a world, forking each one lazily on first write.
+//!
+//! A fork borrows its parent, so the parent can't change while the fork is alive. Forks
+//! record no rollback history and are simply dropped when done. Components using
+//! `SparseStorage` can't be forked.
+//!
+//! # Example
+//! ```ignore
+//! let mut preview = world.fork_cow();
+//! preview.set(unit, &Position { x: 10, y: 4 });
+//! let score = evaluate(&preview);
+//! drop(preview); // the world is unchanged
+//! ```
+
+use crate::block::Block;
+use crate::component::Component;
+use crate::entity::Entity;
+use crate::storage::{ComponentStorage, Storage};
+use crate::world::World;
+use std::collections::BTreeMap;
+
+/// Copy-on-write view of a `Storage<T>`, see the module docs.
+pub struct CowStorage<'a, T> {
+    parent: Option<&'a Storage<T>>,
+    /// Inner blocks copied on first write, keyed by `ri * 128 + mi`.
+    blocks: BTreeMap<u32, Box<Block<T>>>,
+}
+
+impl<'a, T: Component> CowStorage<'a, T> {
+    pub(crate) fn new(parent: Option<&'a Storage<T>>) -> Self {
+        CowStorage {
+            parent,
+            blocks: BTreeMap::new(),
+        }
+    }
+
+    /// The block holding `(ri, mi)` as this fork sees it.
+    fn block(&self, ri: u32, mi: u32) -> Option<&Block<T>> {
+        match self.blocks.get(&(ri * 128 + mi)) {
+            Some(block) => Some(block),
+            None => self.parent?.inner(ri, mi),
+        }
+    }
+
+    /// The fork's own copy of block `(ri, mi)`, copying the parent's on first access.
+    fn block_mut(&mut self, ri: u32, mi: u32) -> &mut Block<T> {
+        let parent = self.parent;
+        self.blocks.entry(ri * 128 + mi).or_insert_with(|| {
+            let copy = parent
+                .and_then(|p| p.inner(ri, mi))
+                .map(|b| b.clone_occupied())
+                .unwrap_or_default();
+            Box::new(copy)
+        })
+    }
+
+    pub fn get(&self, index: u32) -> Option<&T> {
+        let (ri, mi, ii) = (index >> 14, (index >> 7) & 0x7F, index & 0x7F);
+        let block = self.block(ri, mi)?;
+
+        if (block.presence_mask >> ii) & 1 == 0 {
+            return None;
+        }
+        Some(unsafe { block.data[ii as usize].assume_init_ref() })
+    }
+
+    pub fn contains(&self, index: u32) -> bool {
+        self.get(index).is_some()
+    }
+
+    /// Sets the component at `index` in the fork only.
+    ///
+    /// # Panics
+    /// Panics if `index` is out of bounds.
+    pub fn set(&mut self, index: u32, value: &T) {
+        let (ri, mi, ii) = (index >> 14, (index >> 7) & 0x7F, index & 0x7F);
+        if ri >= 128 {
+            panic!("Index out of bounds: {}", index);
+        }
+
+        let block = self.block_mut(ri, mi);
+        if (block.presence_mask >> ii) & 1 != 0 {
+            unsafe { block.data[ii as usize].assume_init_drop() };
+        }
+        block.data[ii as usize].write(value.clone());
+        block.presence_mask |= 1 << ii;
+        block.absence_mask |= 1 << ii;
+    }
+
+    /// Removes the component at `index` in the fork only.
+    pub fn remove(&mut self, index: u32) {
+        if !self.contains(index) {
+            return;
+        }
+
+        let (ri, mi, ii) = (index >> 14, (index >> 7) & 0x7F, index & 0x7F);
+        let block = self.block_mut(ri, mi);
+        unsafe { block.data[ii as usize].assume_init_drop() };
+        block.presence_mask &= !(1 << ii);
+        block.absence_mask &= !(1 << ii);
+    }
+
+    /// Calls `f` for every component the fork sees, in ascending index order.
+    pub fn visit<'b>(&'b self, mut f: impl FnMut(u32, &'b T)) {
+        let mut keys: Vec<u32> = self.blocks.keys().copied().collect();
+
+        if let Some(parent) = self.parent {
+            let mut root = parent.root_mask();
+            while root != 0 {
+                let ri = root.trailing_zeros();
+                root &= !(1u128 << ri);
+
+                let mut middle = parent.middle_mask(ri);
+                while middle != 0 {
+                    let mi = middle.trailing_zeros();
+                    middle &= !(1u128 << mi);
+                    keys.push(ri * 128 + mi);
+                }
+            }
+        }
+        keys.sort_unstable();
+        keys.dedup();
+
+        for key in keys {
+            let Some(block) = self.block(key / 128, key % 128) else {
+                continue;
+            };
+
+            let mut m = block.presence_mask;
+            while m != 0 {
+                let ii = m.trailing_zeros();
+                m &= !(1u128 << ii);
+                f(key * 128 + ii, unsafe {
+                    block.data[ii as usize].assume_init_ref()
+                });
+            }
+        }
+    }
+
+    /// Number of components the fork sees.
+    pub fn len(&self) -> usize {
+        let mut count = 0;
+        self.visit(|_, _| count += 1);
+        count
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Number of inner blocks copied from the parent (or created) so far.
+    pub fn copied_blocks(&self) -> usize {
+        self.blocks.len()
+    }
+}
+
+trait ForkedStorage {
+    fn copied_blocks(&self) -> usize;
+}
+
+impl<T: Component> ForkedStorage for CowStorage<'_, T> {
+    fn copied_blocks(&self) -> usize {
+        CowStorage::copied_blocks(self)
+    }
+}
+
+/// Copy-on-write view of a whole `World`, see the module docs.
+pub struct WorldFork<'a> {
+    world: &'a World,
+    /// Storages written to so far, keyed by component type index.
+    forks: BTreeMap<usize, Box<dyn ForkedStorage + 'a>>,
+}
+
+impl<'a> WorldFork<'a> {
+    pub(crate) fn new(world: &'a World) -> Self {
+        WorldFork {
+            world,
+            forks: BTreeMap::new(),
+        }
+    }
+
+    /// The world's storage for `T`, if it has one.
+    fn parent<T: Component<Storage = Storage<T>>>(&self) -> Option<&'a Storage<T>> {
+        self.world.storage_ref::<T>()
+    }
+
+    /// The fork of the storage for `T`, created on first access.
+    pub fn storage<T: Component<Storage = Storage<T>>>(&mut self) -> &mut CowStorage<'a, T> {
+        let parent = self.parent::<T>();
+        let fork = self
+            .forks
+            .entry(T::type_index())
+            .or_insert_with(|| Box::new(CowStorage::new(parent)));
+
+        // The entry for a type index always holds a `CowStorage` of that type
+        unsafe { &mut *(&mut **fork as *mut dyn ForkedStorage as *mut CowStorage<'a, T>) }
+    }
+
+    fn forked<T: Component<Storage = Storage<T>>>(&self) -> Option<&CowStorage<'a, T>> {
+        let fork = self.forks.get(&T::type_index())?;
+        Some(unsafe { &*(&**fork as *const dyn ForkedStorage as *const CowStorage<'a, T>) })
+    }
+
+    /// Returns true if `entity` exists in the world.
+    pub fn is_alive(&self, entity: Entity) -> bool {
+        self.parent::<Entity>()
+            .and_then(|s| s.get(entity.index()))
+            .is_some_and(|e| e.generation() == entity.generation())
+    }
+
+    /// Returns the component as the fork sees it.
+    pub fn get<T: Component<Storage = Storage<T>>>(&self, entity: Entity) -> Option<&T> {
+        if !self.is_alive(entity) {
+            return None;
+        }
+
+        match self.forked::<T>() {
+            Some(fork) => fork.get(entity.index()),
+            None => self.parent::<T>()?.get(entity.index()),
+        }
+    }
+
+    /// Sets a component in the fork only.
+    ///
+    /// # Panics
+    /// Panics if the entity doesn't exist in the world.
+    pub fn set<T: Component<Storage = Storage<T>>>(&mut self, entity: Entity, component: &T) {
+        if !self.is_alive(entity) {
+            panic!(
+                "Attempted to set component on entity {} which does not exist",
+                entity.index()
+            );
+        }
+
+        self.storage::<T>().set(entity.index(), component);
+    }
+
+    /// Removes a component in the fork only.
+    pub fn remove<T: Component<Storage = Storage<T>>>(&mut self, entity: Entity) {
+        if self.is_alive(entity) {
+            self.storage::<T>().remove(entity.index());
+        }
+    }
+
+    /// Number of inner blocks copied across all forked storages.
+    pub fn copied_blocks(&self) -> usize {
+        self.forks.values().map(|f| f.copied_blocks()).sum()
+    }
+}
+
+#[cfg(test)]
+#[path = "cow.tests.rs"]
+mod tests;
@@ -0,0 +1,95 @@
+use super::*;
+
+#[derive(Component, Default, Clone, Debug, PartialEq)]
+struct Position {
+    x: i32,
+}
+
+#[derive(Component, Default, Clone, Debug, PartialEq)]
+struct Health {
+    hp: u32,
+}
+
+#[test]
+fn test_storage_fork_copies_only_written_blocks() {
+    let mut storage = Storage::<Position>::new();
+    for i in 0..300 {
+        storage.set(i, &Position { x: i as i32 });
+    }
+
+    let mut fork = storage.fork_cow();
+    assert_eq!(fork.get(5), Some(&Position { x: 5 }));
+    assert_eq!(fork.copied_blocks(), 0);
+
+    fork.set(5, &Position { x: -1 });
+    fork.remove(6);
+    fork.set(20000, &Position { x: 7 });
+    assert_eq!(fork.copied_blocks(), 2);
+
+    assert_eq!(fork.get(5), Some(&Position { x: -1 }));
+    assert_eq!(fork.get(6), None);
+    assert_eq!(fork.get(200), Some(&Position { x: 200 }));
+    assert_eq!(fork.len(), 300);
+
+    let mut visited = Vec::new();
+    fork.visit(|i, _| visited.push(i));
+    assert_eq!(visited.first(), Some(&0));
+    assert_eq!(visited.last(), Some(&20000));
+    assert!(!visited.contains(&6));
+
+    drop(fork);
+    assert_eq!(storage.get(5), Some(&Position { x: 5 }));
+    assert_eq!(storage.get(6), Some(&Position { x: 6 }));
+    assert_eq!(storage.get(20000), None);
+}
+
+#[test]
+fn test_world_fork_leaves_world_and_history_untouched() {
+    let mut world = World::new();
+    let a = world.spawn();
+    let b = world.spawn();
+    world.set(a, &Position { x: 1 });
+    world.set(b, &Position { x: 2 });
+    world.set(a, &Health { hp: 10 });
+    world.build_scheduler();
+    world.run();
+    let window = world.rollback_window();
+    let tick = world.current_tick();
+
+    let mut fork = world.fork_cow();
+    fork.set(a, &Position { x: 100 });
+    fork.remove::<Health>(a);
+    fork.set(b, &Health { hp: 3 });
+
+    assert_eq!(fork.get::<Position>(a), Some(&Position { x: 100 }));
+    assert_eq!(fork.get::<Position>(b), Some(&Position { x: 2 }));
+    assert_eq!(fork.get::<Health>(a), None);
+    assert_eq!(fork.get::<Health>(b), Some(&Health { hp: 3 }));
+    assert_eq!(fork.copied_blocks(), 2);
+    drop(fork);
+
+    let positions = world.get_storage::<Position>();
+    let health = world.get_storage::<Health>();
+    unsafe {
+        assert_eq!((*positions.get()).get(a.index()), Some(&Position { x: 1 }));
+        assert_eq!((*health.get()).get(a.index()), Some(&Health { hp: 10 }));
+        assert_eq!((*health.get()).get(b.index()), None);
+    }
+    assert_eq!(world.rollback_window(), window);
+    assert_eq!(world.current_tick(), tick);
+}
+
+#[test]
+fn test_world_fork_ignores_stale_entities() {
+    let mut world = World::new();
+    let a = world.spawn();
+    world.set(a, &Position { x: 1 });
+    let stale = Entity::new(a.index(), a.generation() + 1);
+
+    let mut fork = world.fork_cow();
+    assert!(fork.is_alive(a));
+    assert!(!fork.is_alive(stale));
+    assert_eq!(fork.get::<Position>(stale), None);
+    fork.remove::<Position>(stale);
+    assert_eq!(fork.get::<Position>(a), Some(&Position { x: 1 }));
+}
@@ -0,0 +1,484 @@
+//! Deterministic fixed-point math for gameplay code.
+//!
+//! `f32`/`f64` results can differ between platforms and compilers (fused multiply-add,
+//! x87 precision, libm implementations of `sin` and `atan2`), which desyncs peers that
+//! simulate the same inputs. Everything here is integer arithmetic: `Fixed` is a Q16.16
+//! number, `Vec2`/`Vec3` are vectors of them, and `Rot` is a 2D rotation stored as a binary
+//! angle (65536 steps per turn). Square roots use integer `isqrt`, `sin`/`cos` interpolate a
+//! quarter-wave table and `Rot::atan2` is CORDIC, so every operation gives the same bits on
+//! native and wasm32 targets.
+//!
+//! Arithmetic wraps on overflow, in debug builds too, so results never depend on the
+//! build profile. `Fixed` covers roughly ±32768 with a resolution of 1/65536.
+//! The `fixed` module has these as `Fx32`, `Fx32Vec2` and `Fx32Vec3`, next to Q32.32
+//! `Fx64` versions for larger ranges.
+//!
+//! # Example
+//! ```ignore
+//! let to_target = (target - position).normalize();
+//! let facing = Rot::atan2(to_target.y, to_target.x);
+//! velocity = (facing * Rot::from_degrees(15)).rotate(Vec2::new(speed, Fixed::ZERO));
+//! ```
+
+use std::ops::{Add, AddAssign, Div, Mul, Neg, Sub, SubAssign};
+
+/// Fractional bits of `Fixed`.
+pub const FRAC_BITS: u32 = 16;
+
+/// A Q16.16 fixed-point number.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Default)]
+pub struct Fixed(i32);
+
+impl Fixed {
+    pub const ZERO: Fixed = Fixed(0);
+    pub const ONE: Fixed = Fixed(1 << FRAC_BITS);
+    pub const HALF: Fixed = Fixed(1 << (FRAC_BITS - 1));
+    pub const MAX: Fixed = Fixed(i32::MAX);
+    pub const MIN: Fixed = Fixed(i32::MIN);
+
+    pub const fn from_raw(raw: i32) -> Self {
+        Fixed(raw)
+    }
+
+    pub const fn raw(self) -> i32 {
+        self.0
+    }
+
+    pub const fn from_int(value: i32) -> Self {
+        Fixed(value.wrapping_shl(FRAC_BITS))
+    }
+
+    /// `num / den`, rounded toward zero.
+    ///
+    /// # Panics
+    /// Panics if `den` is zero.
+    pub const fn from_ratio(num: i32, den: i32) -> Self {
+        Fixed((((num as i64) << FRAC_BITS) / den as i64) as i32)
+    }
+
+    /// The integer part, rounded toward negative infinity.
+    pub const fn floor_int(self) -> i32 {
+        self.0 >> FRAC_BITS
+    }
+
+    /// For presentation only; never feed the result back into the simulation.
+    pub fn to_f32(self) -> f32 {
+        self.0 as f32 / (1 << FRAC_BITS) as f32
+    }
+
+    pub const fn abs(self) -> Self {
+        Fixed(self.0.wrapping_abs())
+    }
+
+    /// Square root. Negative values have no root and return zero.
+    pub fn sqrt(self) -> Self {
+        if self.0 <= 0 {
+            return Fixed::ZERO;
+        }
+        Fixed(((self.0 as u64) << FRAC_BITS).isqrt() as i32)
+    }
+}
+
+impl Add for Fixed {
+    type Output = Fixed;
+
+    fn add(self, rhs: Fixed) -> Fixed {
+        Fixed(self.0.wrapping_add(rhs.0))
+    }
+}
+
+impl Sub for Fixed {
+    type Output = Fixed;
+
+    fn sub(self, rhs: Fixed) -> Fixed {
+        Fixed(self.0.wrapping_sub(rhs.0))
+    }
+}
+
+impl Mul for Fixed {
+    type Output = Fixed;
+
+    fn mul(self, rhs: Fixed) -> Fixed {
+        Fixed(((self.0 as i64 * rhs.0 as i64) >> FRAC_BITS) as i32)
+    }
+}
+
+impl Div for Fixed {
+    type Output = Fixed;
+
+    /// # Panics
+    /// Panics if `rhs` is zero.
+    fn div(self, rhs: Fixed) -> Fixed {
+        Fixed((((self.0 as i64) << FRAC_BITS) / rhs.0 as i64) as i32)
+    }
+}
+
+impl Neg for Fixed {
+    type Output = Fixed;
+
+    fn neg(self) -> Fixed {
+        Fixed(self.0.wrapping_neg())
+    }
+}
+
+impl AddAssign for Fixed {
+    fn add_assign(&mut self, rhs: Fixed) {
+        *self = *self + rhs;
+    }
+}
+
+impl SubAssign for Fixed {
+    fn sub_assign(&mut self, rhs: Fixed) {
+        *self = *self - rhs;
+    }
+}
+
+/// Square root of a sum of squared raw values, as a `Fixed`. Saturates at `Fixed::MAX`.
+fn root_of_squares(squares: u64) -> Fixed {
+    Fixed(squares.isqrt().min(i32::MAX as u64) as i32)
+}
+
+/// `value / len` for a vector component, with `len` nonzero.
+fn scale_down(value: Fixed, len: Fixed) -> Fixed {
+    Fixed((((value.0 as i64) << FRAC_BITS) / len.0 as i64) as i32)
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Default)]
+pub struct Vec2 {
+    pub x: Fixed,
+    pub y: Fixed,
+}
+
+impl Vec2 {
+    pub const ZERO: Vec2 = Vec2::new(Fixed::ZERO, Fixed::ZERO);
+    pub const X: Vec2 = Vec2::new(Fixed::ONE, Fixed::ZERO);
+    pub const Y: Vec2 = Vec2::new(Fixed::ZERO, Fixed::ONE);
+
+    pub const fn new(x: Fixed, y: Fixed) -> Self {
+        Vec2 { x, y }
+    }
+
+    pub fn dot(self, rhs: Vec2) -> Fixed {
+        self.x * rhs.x + self.y * rhs.y
+    }
+
+    /// The z component of the 3D cross product, positive if `rhs` is counterclockwise.
+    pub fn cross(self, rhs: Vec2) -> Fixed {
+        self.x * rhs.y - self.y * rhs.x
+    }
+
+    pub fn length(self) -> Fixed {
+        let (x, y) = (self.x.0 as i64, self.y.0 as i64);
+        root_of_squares((x * x) as u64 + (y * y) as u64)
+    }
+
+    /// The vector scaled to length one, or zero for the zero vector.
+    pub fn normalize(self) -> Vec2 {
+        let len = self.length();
+        if len == Fixed::ZERO {
+            return Vec2::ZERO;
+        }
+        Vec2::new(scale_down(self.x, len), scale_down(self.y, len))
+    }
+
+    /// Direction of the vector, see `Rot::atan2`.
+    pub fn angle(self) -> Rot {
+        Rot::atan2(self.y, self.x)
+    }
+}
+
+impl Add for Vec2 {
+    type Output = Vec2;
+
+    fn add(self, rhs: Vec2) -> Vec2 {
+        Vec2::new(self.x + rhs.x, self.y + rhs.y)
+    }
+}
+
+impl Sub for Vec2 {
+    type Output = Vec2;
+
+    fn sub(self, rhs: Vec2) -> Vec2 {
+        Vec2::new(self.x - rhs.x, self.y - rhs.y)
+    }
+}
+
+impl Mul<Fixed> for Vec2 {
+    type Output = Vec2;
+
+    fn mul(self, rhs: Fixed) -> Vec2 {
+        Vec2::new(self.x * rhs, self.y * rhs)
+    }
+}
+
+impl Neg for Vec2 {
+    type Output = Vec2;
+
+    fn neg(self) -> Vec2 {
+        Vec2::new(-self.x, -self.y)
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Default)]
+pub struct Vec3 {
+    pub x: Fixed,
+    pub y: Fixed,
+    pub z: Fixed,
+}
+
+impl Vec3 {
+    pub const ZERO: Vec3 = Vec3::new(Fixed::ZERO, Fixed::ZERO, Fixed::ZERO);
+
+    pub const fn new(x: Fixed, y: Fixed, z: Fixed) -> Self {
+        Vec3 { x, y, z }
+    }
+
+    pub fn dot(self, rhs: Vec3) -> Fixed {
+        self.x * rhs.x + self.y * rhs.y + self.z * rhs.z
+    }
+
+    pub fn cross(self, rhs: Vec3) -> Vec3 {
+        Vec3::new(
+            self.y * rhs.z - self.z * rhs.y,
+            self.z * rhs.x - self.x * rhs.z,
+            self.x * rhs.y - self.y * rhs.x,
+        )
+    }
+
+    pub fn length(self) -> Fixed {
+        let (x, y, z) = (self.x.0 as i64, self.y.0 as i64, self.z.0 as i64);
+        root_of_squares((x * x) as u64 + (y * y) as u64 + (z * z) as u64)
+    }
+
+    /// The vector scaled to length one, or zero for the zero vector.
+    pub fn normalize(self) -> Vec3 {
+        let len = self.length();
+        if len == Fixed::ZERO {
+            return Vec3::ZERO;
+        }
+        Vec3::new(
+            scale_down(self.x, len),
+            scale_down(self.y, len),
+            scale_down(self.z, len),
+        )
+    }
+
+    /// The vector rotated about the z axis, see `Rot::rotate`.
+    pub fn rotate_z(self, rot: Rot) -> Vec3 {
+        let xy = rot.rotate(Vec2::new(self.x, self.y));
+        Vec3::new(xy.x, xy.y, self.z)
+    }
+}
+
+impl Add for Vec3 {
+    type Output = Vec3;
+
+    fn add(self, rhs: Vec3) -> Vec3 {
+        Vec3::new(self.x + rhs.x, self.y + rhs.y, self.z + rhs.z)
+    }
+}
+
+impl Sub for Vec3 {
+    type Output = Vec3;
+
+    fn sub(self, rhs: Vec3) -> Vec3 {
+        Vec3::new(self.x - rhs.x, self.y - rhs.y, self.z - rhs.z)
+    }
+}
+
+impl Mul<Fixed> for Vec3 {
+    type Output = Vec3;
+
+    fn mul(self, rhs: Fixed) -> Vec3 {
+        Vec3::new(self.x * rhs, self.y * rhs, self.z * rhs)
+    }
+}
+
+impl Neg for Vec3 {
+    type Output = Vec3;
+
+    fn neg(self) -> Vec3 {
+        Vec3::new(-self.x, -self.y, -self.z)
+    }
+}
+
+/// Binary angle steps per quarter turn.
+const QUARTER: u32 = 1 << 14;
+
+/// `sin` of every 64th step of the first quarter turn, inclusive of both ends.
+const SIN_TABLE: [i32; 257] = [
+    0, 402, 804, 1206, 1608, 2010, 2412, 2814, 3216, 3617, 4019, 4420, 4821, 5222, 5623, 6023,
+    6424, 6824, 7224, 7623, 8022, 8421, 8820, 9218, 9616, 10014, 10411, 10808, 11204, 11600, 11996,
+    12391, 12785, 13180, 13573, 13966, 14359, 14751, 15143, 15534, 15924, 16314, 16703, 17091,
+    17479, 17867, 18253, 18639, 19024, 19409, 19792, 20175, 20557, 20939, 21320, 21699, 22078,
+    22457, 22834, 23210, 23586, 23961, 24335, 24708, 25080, 25451, 25821, 26190, 26558, 26925,
+    27291, 27656, 28020, 28383, 28745, 29106, 29466, 29824, 30182, 30538, 30893, 31248, 31600,
+    31952, 32303, 32652, 33000, 33347, 33692, 34037, 34380, 34721, 35062, 35401, 35738, 36075,
+    36410, 36744, 37076, 37407, 37736, 38064, 38391, 38716, 39040, 39362, 39683, 40002, 40320,
+    40636, 40951, 41264, 41576, 41886, 42194, 42501, 42806, 43110, 43412, 43713, 44011, 44308,
+    44604, 44898, 45190, 45480, 45769, 46056, 46341, 46624, 46906, 47186, 47464, 47741, 48015,
+    48288, 48559, 48828, 49095, 49361, 49624, 49886, 50146, 50404, 50660, 50914, 51166, 51417,
+    51665, 51911, 52156, 52398, 52639, 52878, 53114, 53349, 53581, 53812, 54040, 54267, 54491,
+    54714, 54934, 55152, 55368, 55582, 55794, 56004, 56212, 56418, 56621, 56823, 57022, 57219,
+    57414, 57607, 57798, 57986, 58172, 58356, 58538, 58718, 58896, 59071, 59244, 59415, 59583,
+    59750, 59914, 60075, 60235, 60392, 60547, 60700, 60851, 60999, 61145, 61288, 61429, 61568,
+    61705, 61839, 61971, 62101, 62228, 62353, 62476, 62596, 62714, 62830, 62943, 63054, 63162,
+    63268, 63372, 63473, 63572, 63668, 63763, 63854, 63944, 64031, 64115, 64197, 64277, 64354,
+    64429, 64501, 64571, 64639, 64704, 64766, 64827, 64884, 64940, 64993, 65043, 65091, 65137,
+    65180, 65220, 65259, 65294, 65328, 65358, 65387, 65413, 65436, 65457, 65476, 65492, 65505,
+    65516, 65525, 65531, 65535, 65536,
+];
+
+/// `atan(2^-i)` in 1/2^24 turns, for CORDIC.
+const ATAN_TABLE: [i64; 22] = [
+    2097152, 1238021, 654136, 332050, 166669, 83416, 41718, 20860, 10430, 5215, 2608, 1304, 652,
+    326, 163, 81, 41, 20, 10, 5, 3, 1,
+];
+
+/// `sin` of `steps` into the first quarter turn, `steps <= QUARTER`.
+fn quarter_sin(steps: u32) -> i32 {
+    let i = (steps >> 6) as usize;
+    let frac = (steps & 63) as i32;
+    if frac == 0 {
+        return SIN_TABLE[i];
+    }
+    SIN_TABLE[i] + (((SIN_TABLE[i + 1] - SIN_TABLE[i]) * frac) >> 6)
+}
+
+/// A 2D rotation, stored as a binary angle of 65536 steps per turn counterclockwise.
+/// Composing rotations adds angles and wraps around the full turn.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Default)]
+pub struct Rot(u16);
+
+impl Rot {
+    pub const IDENTITY: Rot = Rot(0);
+
+    pub const fn from_steps(steps: u16) -> Self {
+        Rot(steps)
+    }
+
+    pub const fn steps(self) -> u16 {
+        self.0
+    }
+
+    /// Rounded to the nearest step.
+    pub const fn from_degrees(degrees: i32) -> Self {
+        let steps = (degrees as i64 * 65536 + 180).div_euclid(360);
+        Rot(steps as u16)
+    }
+
+    pub fn sin(self) -> Fixed {
+        let steps = self.0 as u32;
+        let within = steps % QUARTER;
+        Fixed(match steps / QUARTER {
+            0 => quarter_sin(within),
+            1 => quarter_sin(QUARTER - within),
+            2 => -quarter_sin(within),
+            _ => -quarter_sin(QUARTER - within),
+        })
+    }
+
+    pub fn cos(self) -> Fixed {
+        Rot(self.0.wrapping_add(QUARTER as u16)).sin()
+    }
+
+    pub fn inverse(self) -> Rot {
+        Rot(self.0.wrapping_neg())
+    }
+
+    pub fn rotate(self, v: Vec2) -> Vec2 {
+        let (sin, cos) = (self.sin(), self.cos());
+        Vec2::new(v.x * cos - v.y * sin, v.x * sin + v.y * cos)
+    }
+
+    /// Angle of the point `(x, y)` from the positive x axis, computed with CORDIC. The
+    /// origin gives the identity.
+    pub fn atan2(y: Fixed, x: Fixed) -> Rot {
+        let (mut x, mut y) = (x.0 as i64, y.0 as i64);
+        if x == 0 && y == 0 {
+            return Rot::IDENTITY;
+        }
+
+        // Rotate into the right half plane, where CORDIC converges
+        let mut angle: i64 = 0;
+        if x < 0 {
+            x = -x;
+            y = -y;
+            angle = 1 << 23;
+        }
+
+        // Scale up so small inputs keep their precision through the shifts below
+        let shift = (x.max(y.abs()).leading_zeros() as i64 - 25).max(0);
+        x <<= shift;
+        y <<= shift;
+
+        for (i, step) in ATAN_TABLE.iter().enumerate() {
+            let (dx, dy) = (y >> i, x >> i);
+            if y > 0 {
+                x += dx;
+                y -= dy;
+                angle += step;
+            } else {
+                x -= dx;
+                y += dy;
+                angle -= step;
+            }
+        }
+
+        Rot(((angle + 128) >> 8) as u16)
+    }
+}
+
+impl Mul for Rot {
+    type Output = Rot;
+
+    fn mul(self, rhs: Rot) -> Rot {
+        Rot(self.0.wrapping_add(rhs.0))
+    }
+}
+
+/// Inputs swept by the cross-platform checksum tests.
+#[cfg(test)]
+pub(crate) const SWEEP_SAMPLES: u32 = 2000;
+
+/// `sweep_checksum(SWEEP_SAMPLES)` on every platform.
+#[cfg(test)]
+pub(crate) const SWEEP_CHECKSUM: u64 = 0x9f02_7892_e603_c919;
+
+/// Folds the results of every operation over a seeded sweep of inputs into one value. The
+/// same value is asserted natively and in `wasm_tests`, so any platform difference fails
+/// one of them.
+#[cfg(test)]
+pub(crate) fn sweep_checksum(samples: u32) -> u64 {
+    let mut rng = crate::rng::EntityRng::new(0x5eed, 0, 0, crate::tick::Tick::new(0));
+    let mut results = Vec::new();
+    for _ in 0..samples {
+        let mut fixed = || Fixed((rng.next_u32() as i32) >> 8);
+        let (a, b, c) = (fixed(), fixed(), fixed());
+        let rot = Rot(a.0 as u16);
+        let v = Vec2::new(a, b);
+        let w = Vec3::new(a, b, c);
+
+        results.extend([
+            a * b,
+            a / Fixed::from_int(7),
+            a.abs().sqrt(),
+            rot.sin(),
+            rot.cos(),
+        ]);
+        results.push(Fixed(Rot::atan2(a, b).0 as i32));
+        let (n, r) = (v.normalize(), rot.rotate(v));
+        results.extend([v.length(), n.x, n.y, v.dot(v), r.x, r.y]);
+        let (n, x) = (w.normalize(), w.cross(w.rotate_z(rot)));
+        results.extend([w.length(), n.x, n.y, n.z, x.x, x.y, x.z]);
+    }
+
+    // FNV-1a over the raw bits
+    results.iter().fold(0xcbf2_9ce4_8422_2325, |hash, value| {
+        (hash ^ value.0 as u32 as u64).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+#[cfg(test)]
+#[path = "det_math.tests.rs"]
+mod tests;
@@ -0,0 +1,97 @@
+use super::*;
+use crate::rng::EntityRng;
+use crate::tick::Tick;
+
+fn to_f64(value: Fixed) -> f64 {
+    value.raw() as f64 / 65536.0
+}
+
+#[test]
+fn test_fixed_arithmetic() {
+    let a = Fixed::from_int(3);
+    let b = Fixed::from_ratio(1, 4);
+
+    assert_eq!(a + b, Fixed::from_ratio(13, 4));
+    assert_eq!(a * b, Fixed::from_ratio(3, 4));
+    assert_eq!(a / b, Fixed::from_int(12));
+    assert_eq!(-b, Fixed::from_ratio(-1, 4));
+    assert_eq!(Fixed::from_int(9).sqrt(), a);
+    assert_eq!(Fixed::from_int(-9).sqrt(), Fixed::ZERO);
+    assert_eq!(Fixed::from_ratio(-1, 2).floor_int(), -1);
+    assert_eq!(Fixed::MAX + Fixed::from_raw(1), Fixed::MIN);
+}
+
+#[test]
+fn test_trig_matches_float_within_tolerance() {
+    for steps in (0..=u16::MAX).step_by(97) {
+        let rot = Rot::from_steps(steps);
+        let radians = steps as f64 / 65536.0 * std::f64::consts::TAU;
+
+        assert!(
+            (to_f64(rot.sin()) - radians.sin()).abs() < 1e-4,
+            "sin at {steps}"
+        );
+        assert!(
+            (to_f64(rot.cos()) - radians.cos()).abs() < 1e-4,
+            "cos at {steps}"
+        );
+    }
+
+    assert_eq!(Rot::from_degrees(90).sin(), Fixed::ONE);
+    assert_eq!(Rot::from_degrees(180).cos(), -Fixed::ONE);
+    assert_eq!(Rot::from_degrees(-90), Rot::from_degrees(270));
+}
+
+#[test]
+fn test_atan2_inverts_rotation() {
+    let mut rng = EntityRng::new(1, 2, 3, Tick::new(0));
+    for _ in 0..1000 {
+        let rot = Rot::from_steps(rng.next_u32() as u16);
+        let length = Fixed::from_raw(rng.range(1 << 12..1 << 24) as i32);
+        let angle = rot.rotate(Vec2::X * length).angle();
+
+        let error = angle.steps().wrapping_sub(rot.steps()) as i16;
+        assert!(error.abs() <= 8, "{rot:?} came back as {angle:?}");
+    }
+
+    assert_eq!(Rot::atan2(Fixed::ZERO, Fixed::ZERO), Rot::IDENTITY);
+    assert_eq!(Rot::atan2(Fixed::ZERO, -Fixed::ONE), Rot::from_degrees(180));
+    assert_eq!(Rot::atan2(-Fixed::ONE, Fixed::ZERO), Rot::from_degrees(270));
+}
+
+#[test]
+fn test_vector_operations() {
+    let v = Vec2::new(Fixed::from_int(3), Fixed::from_int(4));
+    assert_eq!(v.length(), Fixed::from_int(5));
+    assert_eq!(
+        v.normalize(),
+        Vec2::new(Fixed::from_ratio(3, 5), Fixed::from_ratio(4, 5))
+    );
+    assert_eq!(v.dot(Vec2::Y), Fixed::from_int(4));
+    assert_eq!(Vec2::X.cross(Vec2::Y), Fixed::ONE);
+    assert_eq!(Vec2::ZERO.normalize(), Vec2::ZERO);
+
+    let x = Vec3::new(Fixed::ONE, Fixed::ZERO, Fixed::ZERO);
+    let y = Vec3::new(Fixed::ZERO, Fixed::ONE, Fixed::ZERO);
+    assert_eq!(x.cross(y), Vec3::new(Fixed::ZERO, Fixed::ZERO, Fixed::ONE));
+    assert_eq!(x.rotate_z(Rot::from_degrees(90)), y);
+
+    let mut rng = EntityRng::new(4, 5, 6, Tick::new(0));
+    for _ in 0..1000 {
+        let mut component = || Fixed::from_raw((rng.next_u32() as i32) >> 10);
+        let w = Vec3::new(component(), component(), component());
+        if w.length() > Fixed::from_ratio(1, 16) {
+            let error = to_f64(w.normalize().length()) - 1.0;
+            assert!(
+                error.abs() < 1e-3,
+                "{w:?} normalized to length {}",
+                1.0 + error
+            );
+        }
+    }
+}
+
+#[test]
+fn test_sweep_is_bit_identical() {
+    assert_eq!(sweep_checksum(SWEEP_SAMPLES), SWEEP_CHECKSUM);
+}
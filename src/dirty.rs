@@ -0,0 +1,162 @@
+//! Dirty flags for render code, fed from the simulation's change masks.
+//!
+//! Change masks only live for one tick, while a renderer consumes at its own frame rate
+//! and may skip ticks or run several frames per tick. `dirty_bridge!(Position ->
+//! RenderDirtyPosition)` declares a marker component, and `World::add_dirty_bridge` adds a
+//! system that sets it on every entity whose `Position` changed. The marker stays until the
+//! render side takes it with `World::take_dirty`, however many ticks that is.
+//!
+//! Markers belong to the local presentation, not to the simulation: they are temporary
+//! components, so rollback doesn't touch them, and the storage is left out of
+//! `World::hash_tree`, so peers rendering at different rates still agree. After a rollback
+//! or an earlier savestate is loaded, restored values aren't change-tracked, so the first
+//! bridge run on the rewound timeline flags every entity with the source component.
+//!
+//! The bridge reads the source component, so the scheduler runs it after every system
+//! writing it in the simulation group.
+//!
+//! # Example
+//! ```ignore
+//! dirty_bridge!(Position -> RenderDirtyPosition);
+//!
+//! world.add_dirty_bridge::<RenderDirtyPosition>();
+//! world.build_scheduler();
+//! world.run();
+//!
+//! for entity in world.take_dirty::<RenderDirtyPosition>() {
+//!     sprites.move_to(entity, world.get::<Position>(entity));
+//! }
+//! ```
+
+use crate::component::Component;
+use crate::rng::RngClock;
+use crate::scheduler::{PipelineStage, SimulationGroup, type_id_slice};
+use crate::storage::ComponentStorage;
+use crate::tick::Tick;
+use crate::world::World;
+use std::any::TypeId;
+use std::cell::{Cell, UnsafeCell};
+use std::rc::Rc;
+
+/// A marker set by a dirty bridge on entities whose `Source` changed. Implemented by
+/// `dirty_bridge!`.
+pub trait DirtyMarker: Component {
+    type Source: Component;
+}
+
+/// Declares a marker component `$marker` and bridges the change masks of `$source` into
+/// it, see the module docs.
+#[macro_export]
+macro_rules! dirty_bridge {
+    ($($source:ident)::+ -> $marker:ident) => {
+        #[derive(Default, Clone, Debug, PartialEq)]
+        pub struct $marker;
+
+        impl $crate::component::Resource for $marker {
+            fn type_index() -> usize {
+                static TYPE_INDEX: ::std::sync::OnceLock<usize> = ::std::sync::OnceLock::new();
+                *TYPE_INDEX.get_or_init(|| $crate::component::next_id())
+            }
+        }
+
+        impl $crate::component::Component for $marker {
+            const IS_TEMPORARY: bool = true;
+
+            fn stable_name() -> &'static str {
+                concat!(module_path!(), "::", stringify!($marker))
+            }
+        }
+
+        impl $crate::dirty::DirtyMarker for $marker {
+            type Source = $($source)::+;
+        }
+    };
+}
+
+/// Sets `M` on every entity whose `M::Source` changed this tick. Added with
+/// `World::add_dirty_bridge`.
+pub struct DirtyBridge<M: DirtyMarker> {
+    source: Rc<UnsafeCell<<M::Source as Component>::Storage>>,
+    markers: Rc<UnsafeCell<M::Storage>>,
+    clock: Rc<RngClock>,
+    /// Tick of the previous run, to notice the timeline being rewound.
+    last_tick: Cell<Option<Tick>>,
+}
+
+// SAFETY: the storages are only touched from `run`, which the scheduler never runs
+// alongside a writer of the source or another user of the markers
+unsafe impl<M: DirtyMarker> Send for DirtyBridge<M> {}
+unsafe impl<M: DirtyMarker> Sync for DirtyBridge<M> {}
+
+impl<M: DirtyMarker> PipelineStage for DirtyBridge<M> {
+    fn type_id(&self) -> TypeId {
+        TypeId::of::<Self>()
+    }
+
+    fn run(&self) {
+        let source = unsafe { &*self.source.get() };
+        let markers = unsafe { &mut *self.markers.get() };
+        let mut flag = |index: u32| {
+            if markers.get(index).is_none() {
+                markers.set(index, &M::default());
+            }
+        };
+
+        let tick = self.clock.tick.get();
+        let rewound = self
+            .last_tick
+            .replace(Some(tick))
+            .is_some_and(|last| !tick.is_after(last));
+        if rewound {
+            source.visit(|index, _| flag(index));
+            return;
+        }
+
+        let mut outer_mask = source.root_changed_mask();
+        while outer_mask != 0 {
+            let ri = outer_mask.trailing_zeros();
+            let mut middle_mask = source.middle_changed_mask(ri);
+
+            while middle_mask != 0 {
+                let mi = middle_mask.trailing_zeros();
+                // Removals count too, so the renderer drops what it drew
+                let mut inner_mask = source.inner_changed_mask(ri, mi);
+
+                while inner_mask != 0 {
+                    let ii = inner_mask.trailing_zeros();
+                    flag(ri * 16384 + mi * 128 + ii);
+                    inner_mask &= !(1u128 << ii);
+                }
+
+                middle_mask &= !(1u128 << mi);
+            }
+
+            outer_mask &= !(1u128 << ri);
+        }
+    }
+
+    fn create(world: &mut World) -> Self {
+        DirtyBridge {
+            source: world.get_storage::<M::Source>(),
+            markers: world.get_storage::<M>(),
+            clock: world.tick_clock(),
+            last_tick: Cell::new(None),
+        }
+    }
+
+    fn reads(&self) -> &'static [TypeId] {
+        type_id_slice::<M::Source>()
+    }
+
+    fn writes(&self) -> &'static [TypeId] {
+        type_id_slice::<M>()
+    }
+
+    fn parent(&self) -> Option<TypeId> {
+        Some(TypeId::of::<SimulationGroup>())
+    }
+}
+
+#[cfg(test)]
+#[path = "dirty.tests.rs"]
+mod tests;
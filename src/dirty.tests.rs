@@ -0,0 +1,90 @@
+use crate::prelude::*;
+
+#[derive(Component, Default, Clone, Debug, PartialEq)]
+pub struct Position {
+    pub x: i32,
+}
+
+dirty_bridge!(Position -> RenderDirtyPosition);
+
+system! {
+    MoveOddSystem {
+        query! {
+            fn step(entity: Entity, position: &mut ViewMut<Position>) {
+                if entity.index() % 2 == 1 {
+                    position.x += 1;
+                }
+            }
+        }
+    }
+}
+
+fn world(count: usize) -> (World, Vec<Entity>) {
+    let mut world = World::new();
+    world.get_storage::<Position>();
+    world.add_dirty_bridge::<RenderDirtyPosition>();
+    world.build_scheduler();
+
+    let entities: Vec<Entity> = (0..count).map(|_| world.spawn()).collect();
+    for &e in &entities {
+        world.set(e, &Position::default());
+    }
+    (world, entities)
+}
+
+#[test]
+fn test_markers_stay_until_taken() {
+    let (mut world, entities) = world(4);
+    world.run();
+    assert_eq!(world.take_dirty::<RenderDirtyPosition>(), entities);
+    assert!(world.take_dirty::<RenderDirtyPosition>().is_empty());
+
+    world.set(entities[2], &Position { x: 5 });
+    world.run();
+    world.run();
+    world.run();
+    assert_eq!(world.take_dirty::<RenderDirtyPosition>(), vec![entities[2]]);
+
+    world.storage_mut::<Position>().remove(entities[3].index());
+    world.run();
+    assert_eq!(world.take_dirty::<RenderDirtyPosition>(), vec![entities[3]]);
+}
+
+#[test]
+fn test_rewound_timeline_flags_everything() {
+    let mut world = World::new();
+    world.get_storage::<Position>();
+    world.add_system::<MoveOddSystem>();
+    world.add_dirty_bridge::<RenderDirtyPosition>();
+    world.build_scheduler();
+    let entities: Vec<Entity> = (0..4).map(|_| world.spawn()).collect();
+    for &e in &entities {
+        world.set(e, &Position::default());
+    }
+
+    let start = world.current_tick();
+    world.run();
+    world.run();
+    world.take_dirty::<RenderDirtyPosition>();
+
+    world.rollback(start);
+    world.run();
+    assert_eq!(world.take_dirty::<RenderDirtyPosition>(), entities);
+
+    world.run();
+    assert_eq!(
+        world.take_dirty::<RenderDirtyPosition>(),
+        vec![entities[1], entities[3]]
+    );
+}
+
+#[test]
+fn test_markers_are_not_hashed() {
+    let (mut a, _) = world(3);
+    let (mut b, _) = world(3);
+    a.run();
+    b.run();
+    a.take_dirty::<RenderDirtyPosition>();
+
+    assert_eq!(a.hash_tree().root(), b.hash_tree().root());
+}
@@ -0,0 +1,158 @@
+//! Type-erased component values for scripting layers.
+//!
+//! A `DynValue` owns one component value together with the functions needed to clone it
+//! and to store it in a world, so code without compile-time component types (script
+//! bindings, editors, consoles) can pass components around. `World::set_dyn` stores it
+//! through `World::set`, with the usual change tracking, so scripted mutations roll back
+//! and resimulate like native ones. `World::get_dyn` reads a component by its
+//! `wire::component_id`.
+//!
+//! # Example
+//! ```ignore
+//! // The binding layer creates values from the types it knows about
+//! let value = DynValue::new(Health { value: 10 });
+//! world.set_dyn(entity, &value);
+//!
+//! // Scripts only deal in component ids
+//! let current = world.get_dyn(entity, component_id::<Health>()).unwrap();
+//! let mut copy = current.to_value();
+//! copy.downcast_mut::<Health>().unwrap().value -= 1;
+//! world.set_dyn(entity, &copy);
+//! ```
+
+use crate::component::Component;
+use crate::entity::Entity;
+use crate::wire::component_id;
+use crate::world::World;
+use std::any::Any;
+use std::fmt;
+
+/// The erased operations of one component type.
+#[derive(Clone, Copy)]
+pub(crate) struct DynVtable {
+    component: u64,
+    type_name: &'static str,
+    clone: fn(&dyn Any) -> Box<dyn Any>,
+    set: fn(&mut World, Entity, &dyn Any),
+}
+
+impl DynVtable {
+    pub(crate) fn of<T: Component>() -> Self {
+        DynVtable {
+            component: component_id::<T>(),
+            type_name: std::any::type_name::<T>(),
+            clone: |value| Box::new(downcast::<T>(value).clone()),
+            set: |world, entity, value| world.set(entity, downcast::<T>(value)),
+        }
+    }
+
+    pub(crate) fn component_id(&self) -> u64 {
+        self.component
+    }
+}
+
+fn downcast<T: Any>(value: &dyn Any) -> &T {
+    value
+        .downcast_ref()
+        .expect("DynValue holds the vtable's type")
+}
+
+/// An owned component value of a type known only at runtime.
+pub struct DynValue {
+    vtable: DynVtable,
+    value: Box<dyn Any>,
+}
+
+impl DynValue {
+    pub fn new<T: Component>(value: T) -> Self {
+        DynValue {
+            vtable: DynVtable::of::<T>(),
+            value: Box::new(value),
+        }
+    }
+
+    /// `wire::component_id` of the held type.
+    pub fn component_id(&self) -> u64 {
+        self.vtable.component
+    }
+
+    pub fn type_name(&self) -> &'static str {
+        self.vtable.type_name
+    }
+
+    pub fn downcast_ref<T: Component>(&self) -> Option<&T> {
+        self.value.downcast_ref()
+    }
+
+    pub fn downcast_mut<T: Component>(&mut self) -> Option<&mut T> {
+        self.value.downcast_mut()
+    }
+
+    /// Sets the value on `entity` with `World::set`.
+    pub(crate) fn set_on(&self, world: &mut World, entity: Entity) {
+        (self.vtable.set)(world, entity, &*self.value)
+    }
+}
+
+impl Clone for DynValue {
+    fn clone(&self) -> Self {
+        DynValue {
+            vtable: self.vtable,
+            value: (self.vtable.clone)(&*self.value),
+        }
+    }
+}
+
+impl fmt::Debug for DynValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("DynValue")
+            .field(&self.vtable.type_name)
+            .finish()
+    }
+}
+
+/// A borrowed component value of a type known only at runtime, from `World::get_dyn`.
+#[derive(Clone, Copy)]
+pub struct DynValueRef<'a> {
+    vtable: DynVtable,
+    value: &'a dyn Any,
+}
+
+impl<'a> DynValueRef<'a> {
+    pub(crate) fn new(vtable: DynVtable, value: &'a dyn Any) -> Self {
+        DynValueRef { vtable, value }
+    }
+
+    /// `wire::component_id` of the referenced type.
+    pub fn component_id(&self) -> u64 {
+        self.vtable.component
+    }
+
+    pub fn type_name(&self) -> &'static str {
+        self.vtable.type_name
+    }
+
+    pub fn downcast_ref<T: Component>(&self) -> Option<&'a T> {
+        self.value.downcast_ref()
+    }
+
+    /// Clones the value into a `DynValue`.
+    pub fn to_value(self) -> DynValue {
+        DynValue {
+            vtable: self.vtable,
+            value: (self.vtable.clone)(self.value),
+        }
+    }
+}
+
+impl fmt::Debug for DynValueRef<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("DynValueRef")
+            .field(&self.vtable.type_name)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+#[path = "dynamic.tests.rs"]
+mod tests;
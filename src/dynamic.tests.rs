@@ -0,0 +1,69 @@
+use super::*;
+use crate::tick::Tick;
+
+#[derive(Component, Default, Clone, Debug, PartialEq)]
+struct Health {
+    value: i32,
+}
+
+#[derive(Component, Default, Clone, Debug, PartialEq)]
+struct Mana {
+    value: i32,
+}
+
+fn world_with_entity() -> (World, Entity) {
+    let mut world = World::new();
+    world.get_storage::<Health>();
+    world.get_storage::<Mana>();
+    world.build_scheduler();
+    let e = world.spawn();
+    (world, e)
+}
+
+#[test]
+fn test_dyn_value_round_trip() {
+    let (mut world, e) = world_with_entity();
+    let value = DynValue::new(Health { value: 10 });
+    assert_eq!(value.component_id(), component_id::<Health>());
+    assert!(value.type_name().ends_with("Health"));
+    assert!(value.downcast_ref::<Mana>().is_none());
+
+    world.set_dyn(e, &value);
+    let current = world.get_dyn(e, component_id::<Health>()).unwrap();
+    assert_eq!(
+        current.downcast_ref::<Health>(),
+        Some(&Health { value: 10 })
+    );
+    assert!(world.get_dyn(e, component_id::<Mana>()).is_none());
+
+    let mut copy = current.to_value();
+    copy.downcast_mut::<Health>().unwrap().value -= 1;
+    world.set_dyn(e, &copy.clone());
+    let current = world.get_dyn(e, component_id::<Health>()).unwrap();
+    assert_eq!(current.downcast_ref::<Health>(), Some(&Health { value: 9 }));
+
+    // Stale handles and unknown components read nothing
+    assert!(
+        world
+            .get_dyn(Entity::new(e.index(), 7), component_id::<Health>())
+            .is_none()
+    );
+    assert!(world.get_dyn(e, 42).is_none());
+}
+
+#[test]
+fn test_dyn_set_rolls_back_like_native_set() {
+    let (mut world, e) = world_with_entity();
+    world.set(e, &Health { value: 1 });
+    world.run();
+    let before = world.current_tick();
+
+    world.set_dyn(e, &DynValue::new(Health { value: 2 }));
+    world.set_dyn(e, &DynValue::new(Mana { value: 3 }));
+    world.run();
+
+    world.rollback(Tick::new(before.value() - 1));
+    let health = world.get_dyn(e, component_id::<Health>()).unwrap();
+    assert_eq!(health.downcast_ref::<Health>(), Some(&Health { value: 1 }));
+    assert!(world.get_dyn(e, component_id::<Mana>()).is_none());
+}
@@ -0,0 +1,193 @@
+//! Rollback-safe side-effect emission for audio, VFX and other presentation layers.
+//!
+//! Systems that play a sound or spawn a particle when something happens would replay the
+//! effect every time a tick is resimulated, and effects of a mispredicted timeline would
+//! stay on screen after the correction. Instead, systems emit effects through an
+//! `Effects<E>` parameter, each with a stable key (for example `effect_key(entity, kind)`),
+//! and presentation code drains a clean stream of `EffectEvent`s from the world.
+//!
+//! At the end of every tick the world compares what the tick emitted against what the same
+//! tick emitted the last time it was simulated, by `(tick, key)`:
+//!
+//! - effects emitted again during resimulation are suppressed, they were already surfaced;
+//! - effects that are new after a correction are surfaced once, as `EffectEvent::Emit`;
+//! - effects that were surfaced but aren't emitted any more are reported as
+//!   `EffectEvent::Cancel`, so a sound that should never have played can be stopped.
+//!
+//! Ticks older than the rollback window can't be resimulated, so their keys are forgotten.
+//! Within one tick the first emission of a key wins, in `SequenceKey` order, so parallel
+//! and sequential runs surface the same effects.
+//!
+//! # Example
+//! ```ignore
+//! system! {
+//!     HitSoundSystem {
+//!         query! {
+//!             fn play(entity: View<Entity>, hit: View<Hit>, sounds: Effects<Sound>) {
+//!                 sounds.emit(effect_key(*entity, 0), Sound::Impact(hit.strength));
+//!             }
+//!         }
+//!     }
+//! }
+//!
+//! world.run();
+//! for event in world.effects::<Sound>().drain() {
+//!     audio.handle(event);
+//! }
+//! ```
+
+use crate::entity::Entity;
+use crate::sequence::{Lane, SequencedLanes};
+use crate::tick::Tick;
+use std::any::Any;
+use std::cell::RefCell;
+use std::collections::{BTreeMap, BTreeSet};
+use std::rc::Rc;
+
+/// Stable key for the effect `kind` of `entity`, unique per entity and generation.
+pub fn effect_key(entity: Entity, kind: u32) -> u64 {
+    ((entity.to_bits() as u64) << 32) | kind as u64
+}
+
+/// A change to the effects presentation layers should be showing.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum EffectEvent<E> {
+    /// The effect happened at `tick`. Surfaced once per `(tick, key)`.
+    Emit { tick: Tick, key: u64, effect: E },
+    /// A previously emitted effect was undone by a rollback correction.
+    Cancel { tick: Tick, key: u64 },
+}
+
+struct EffectState<E> {
+    /// Keys surfaced for every tick still inside the rollback window.
+    surfaced: BTreeMap<Tick, BTreeSet<u64>>,
+    events: Vec<EffectEvent<E>>,
+}
+
+/// Emission buffer and surfaced-effect record for effects of type `E`.
+pub struct EffectQueue<E> {
+    lanes: SequencedLanes<(u64, E)>,
+    external: Rc<Lane<(u64, E)>>,
+    state: RefCell<EffectState<E>>,
+}
+
+impl<E> EffectQueue<E> {
+    pub fn new() -> Self {
+        let lanes = SequencedLanes::new();
+        let external = lanes.lane(crate::sequence::EXTERNAL_STAGE);
+        EffectQueue {
+            lanes,
+            external,
+            state: RefCell::new(EffectState {
+                surfaced: BTreeMap::new(),
+                events: Vec::new(),
+            }),
+        }
+    }
+
+    /// Emits an effect from outside any system, attributed to the next simulated tick.
+    pub fn emit(&self, key: u64, effect: E) {
+        self.external.push((key, effect));
+    }
+
+    /// Moves out every event surfaced since the last call, in tick order.
+    pub fn drain(&self) -> Vec<EffectEvent<E>> {
+        std::mem::take(&mut self.state.borrow_mut().events)
+    }
+
+    /// Number of events waiting to be drained.
+    pub fn pending(&self) -> usize {
+        self.state.borrow().events.len()
+    }
+
+    /// Compares the effects emitted by `tick` with those surfaced for it before.
+    fn end_tick(&self, tick: Tick, oldest: Tick) {
+        let mut emitted: BTreeMap<u64, E> = BTreeMap::new();
+        for (_, (key, effect)) in self.lanes.drain() {
+            emitted.entry(key).or_insert(effect);
+        }
+
+        let state = &mut *self.state.borrow_mut();
+        let previous = state.surfaced.remove(&tick).unwrap_or_default();
+
+        for &key in previous.iter().filter(|k| !emitted.contains_key(k)) {
+            state.events.push(EffectEvent::Cancel { tick, key });
+        }
+
+        let keys: BTreeSet<u64> = emitted.keys().copied().collect();
+        for (key, effect) in emitted {
+            if !previous.contains(&key) {
+                state.events.push(EffectEvent::Emit { tick, key, effect });
+            }
+        }
+
+        if !keys.is_empty() {
+            state.surfaced.insert(tick, keys);
+        }
+        state.surfaced.retain(|t, _| !t.is_before(oldest));
+    }
+}
+
+impl<E> Default for EffectQueue<E> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Type-erased access to effect queues so the world can close ticks.
+pub trait EffectsLike: Any {
+    /// Surfaces the effects emitted while simulating `tick` and forgets ticks before
+    /// `oldest`.
+    fn end_tick(&self, tick: Tick, oldest: Tick);
+    /// Drops emissions not yet attributed to a tick.
+    fn clear_pending(&self);
+    /// Drops pending emissions, undrained events and the record of surfaced effects.
+    fn clear(&self);
+    fn as_any_rc(self: Rc<Self>) -> Rc<dyn Any>;
+}
+
+impl<E: 'static> EffectsLike for EffectQueue<E> {
+    fn end_tick(&self, tick: Tick, oldest: Tick) {
+        EffectQueue::end_tick(self, tick, oldest)
+    }
+
+    fn clear_pending(&self) {
+        self.lanes.clear()
+    }
+
+    fn clear(&self) {
+        self.lanes.clear();
+        let state = &mut *self.state.borrow_mut();
+        state.surfaced.clear();
+        state.events.clear();
+    }
+
+    fn as_any_rc(self: Rc<Self>) -> Rc<dyn Any> {
+        self
+    }
+}
+
+/// Emitting half of an effect queue, owned by one system.
+pub struct Effects<E> {
+    lane: Rc<Lane<(u64, E)>>,
+}
+
+impl<E> Effects<E> {
+    /// Creates an emitter stamping its effects with the given stage hash.
+    pub fn new(queue: &EffectQueue<E>, stage: u64) -> Self {
+        Effects {
+            lane: queue.lanes.lane(stage),
+        }
+    }
+
+    /// Emits `effect` under `key` for the tick being simulated. Emitting the same key
+    /// again in the same tick has no effect.
+    pub fn emit(&self, key: u64, effect: E) {
+        // The lane is owned by this system
+        self.lane.push((key, effect));
+    }
+}
+
+#[cfg(test)]
+#[path = "effects.tests.rs"]
+mod tests;
@@ -0,0 +1,100 @@
+use super::*;
+use crate::component::Component;
+use crate::prelude::system;
+use crate::world::World;
+
+#[derive(Component, Default, Clone, Debug, PartialEq)]
+struct Hit {
+    strength: u32,
+}
+
+system! {
+    ImpactSystem {
+        query! {
+            fn play(entity: View<Entity>, hit: View<Hit>, sounds: Effects<u32>) Remove=[Hit] {
+                sounds.emit(effect_key(*entity, 0), hit.strength);
+            }
+        }
+    }
+}
+
+fn emit(tick: u32, entity: Entity, effect: u32) -> EffectEvent<u32> {
+    EffectEvent::Emit {
+        tick: Tick::new(tick),
+        key: effect_key(entity, 0),
+        effect,
+    }
+}
+
+#[test]
+fn test_resimulation_suppresses_repeats_and_surfaces_corrections() {
+    let mut world = World::new();
+    world.add_system::<ImpactSystem>();
+    world.build_scheduler();
+    let sounds = world.effects::<u32>();
+    let a = world.spawn();
+    let b = world.spawn();
+    world.run();
+
+    world.set(a, &Hit { strength: 3 });
+    world.set(b, &Hit { strength: 5 });
+    world.run();
+    assert_eq!(sounds.drain(), vec![emit(1, a, 3), emit(1, b, 5)]);
+
+    // Same inputs: nothing new to show
+    world.resimulate_from(Tick::new(1));
+    world.set(a, &Hit { strength: 3 });
+    world.set(b, &Hit { strength: 5 });
+    world.run();
+    assert!(sounds.drain().is_empty());
+
+    // Corrected inputs: b was never hit
+    world.resimulate_from(Tick::new(1));
+    world.set(a, &Hit { strength: 3 });
+    world.run();
+    assert_eq!(
+        sounds.drain(),
+        vec![EffectEvent::Cancel {
+            tick: Tick::new(1),
+            key: effect_key(b, 0),
+        }]
+    );
+
+    world.resimulate_from(Tick::new(1));
+    world.set(a, &Hit { strength: 3 });
+    world.set(b, &Hit { strength: 6 });
+    world.run();
+    assert_eq!(sounds.drain(), vec![emit(1, b, 6)]);
+}
+
+#[test]
+fn test_queue_keeps_first_emission_and_forgets_old_ticks() {
+    let queue = EffectQueue::<&str>::new();
+    let first = queue.lanes.lane(1);
+    let second = queue.lanes.lane(2);
+    second.push((7, "late"));
+    first.push((7, "early"));
+    queue.emit(9, "external");
+
+    queue.end_tick(Tick::new(4), Tick::new(0));
+    assert_eq!(queue.pending(), 2);
+    assert_eq!(
+        queue.drain(),
+        vec![
+            EffectEvent::Emit {
+                tick: Tick::new(4),
+                key: 7,
+                effect: "early",
+            },
+            EffectEvent::Emit {
+                tick: Tick::new(4),
+                key: 9,
+                effect: "external",
+            },
+        ]
+    );
+
+    // Once tick 4 leaves the window it's forgotten
+    queue.end_tick(Tick::new(5), Tick::new(5));
+    assert!(queue.state.borrow().surfaced.is_empty());
+}
@@ -11,10 +11,20 @@ impl crate::component::Resource for Entity {
 }
 
 impl Component for Entity {
+    fn stable_name() -> &'static str {
+        concat!(module_path!(), "::Entity")
+    }
+
     fn cleanup_system(world: &mut crate::world::World) -> Box<dyn crate::scheduler::PipelineStage> {
         use crate::scheduler::PipelineStage;
         Box::new(crate::system::DestroySystem::create(world))
     }
+
+    fn wire_codec() -> Option<&'static dyn crate::wire::SnapshotCodec<Self>> {
+        Some(&crate::wire::WireCodec)
+    }
+
+    crate::__component_serde_fns!();
 }
 
 impl Entity {
@@ -32,6 +42,18 @@ impl Entity {
         Entity((index << Self::GENERATION_BITS) | generation)
     }
 
+    /// The packed representation, e.g. for passing entities through `ffi`.
+    #[inline(always)]
+    pub fn to_bits(self) -> u32 {
+        self.0
+    }
+
+    /// Inverse of `to_bits`.
+    #[inline(always)]
+    pub fn from_bits(bits: u32) -> Self {
+        Entity(bits)
+    }
+
     #[inline(always)]
     pub fn none() -> Self {
         Entity(0)
@@ -85,3 +107,79 @@ impl std::fmt::Debug for Entity {
         )
     }
 }
+
+/// A reference to an entity that stops resolving once the entity is destroyed.
+///
+/// A plain `Entity` stored in a component keeps pointing at its index after the target is
+/// destroyed. `EntityWeak::get` checks the generation against the world's entity storage
+/// instead, and returns `None` as soon as the target is marked destroyed.
+///
+/// A slot freed by the cleanup sweep keeps counting generations when it is reused, so a
+/// weak reference that outlives the sweep stays stale until the 10-bit generation wraps
+/// around after 1024 reuses of the index. Fields marked `#[component(weak)]` in a
+/// `Component` derive are cleared before that can matter: the component's cleanup system
+/// resets them to `EntityWeak::none()` in the tick their target is destroyed, before the
+/// slot is freed:
+/// ```ignore
+/// #[derive(Component, Clone, Default)]
+/// struct Homing {
+///     #[component(weak)]
+///     target: EntityWeak,
+/// }
+/// ```
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Default, Debug)]
+pub struct EntityWeak(Entity);
+
+impl EntityWeak {
+    pub fn new(entity: Entity) -> Self {
+        EntityWeak(entity)
+    }
+
+    pub fn none() -> Self {
+        EntityWeak(Entity::none())
+    }
+
+    pub fn is_none(&self) -> bool {
+        self.0.is_none()
+    }
+
+    pub fn clear(&mut self) {
+        *self = Self::none();
+    }
+
+    /// The referenced entity, whether or not it is still alive.
+    pub fn entity(&self) -> Entity {
+        self.0
+    }
+
+    /// Returns the entity if it still exists and isn't marked destroyed.
+    pub fn get(&self, world: &crate::world::World) -> Option<Entity> {
+        let entities = world.storage_ref::<Entity>();
+        let destroyed = world.storage_ref::<crate::component::Destroyed>();
+        self.resolve(entities, destroyed)
+    }
+
+    pub(crate) fn resolve(
+        &self,
+        entities: Option<&crate::storage::Storage<Entity>>,
+        destroyed: Option<&crate::storage::Storage<crate::component::Destroyed>>,
+    ) -> Option<Entity> {
+        if self.is_none() {
+            return None;
+        }
+
+        let current = entities?.get(self.0.index())?;
+        let marked = destroyed.is_some_and(|d| d.get(self.0.index()).is_some());
+        (current.generation() == self.0.generation() && !marked).then_some(self.0)
+    }
+}
+
+impl From<Entity> for EntityWeak {
+    fn from(entity: Entity) -> Self {
+        EntityWeak(entity)
+    }
+}
+
+#[cfg(test)]
+#[path = "entity.tests.rs"]
+mod tests;
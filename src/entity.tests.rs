@@ -0,0 +1,98 @@
+use super::*;
+use crate::prelude::*;
+
+#[derive(Component, Clone, Default, Debug, PartialEq)]
+struct Homing {
+    #[component(weak)]
+    target: EntityWeak,
+    // Not marked weak: keeps pointing at the old entity
+    origin: EntityWeak,
+}
+
+#[derive(Component, Clone, Default, Debug, PartialEq)]
+struct Tether(#[component(weak)] EntityWeak, u32);
+
+fn test_world() -> World {
+    let mut world = World::new();
+    world.get_storage::<Homing>();
+    world.get_storage::<Tether>();
+    world.build_scheduler();
+    world
+}
+
+#[test]
+fn test_entity_weak_resolves_until_destroyed() {
+    let mut world = test_world();
+    let target = world.spawn();
+    let weak = EntityWeak::new(target);
+    assert_eq!(weak.get(&world), Some(target));
+    assert_eq!(EntityWeak::none().get(&world), None);
+
+    // Marked destroyed: no longer resolves, even before the cleanup sweep
+    world.destroy(target);
+    assert_eq!(weak.get(&world), None);
+    world.run();
+    assert_eq!(weak.get(&world), None);
+}
+
+#[test]
+fn test_weak_fields_are_cleared_when_target_is_destroyed() {
+    let mut world = test_world();
+    let target = world.spawn();
+    let other = world.spawn();
+    let missile = world.spawn();
+    world.set(
+        missile,
+        &Homing {
+            target: target.into(),
+            origin: target.into(),
+        },
+    );
+    world.set(missile, &Tether(other.into(), 7));
+    world.run();
+
+    world.destroy(target);
+    world.run();
+
+    let homing = world.get_storage::<Homing>();
+    let tether = world.get_storage::<Tether>();
+    let homing = unsafe { (*homing.get()).get(missile.index()).unwrap().clone() };
+    assert!(homing.target.is_none());
+    assert_eq!(homing.origin.entity(), target);
+    assert_eq!(homing.origin.get(&world), None);
+
+    let tether = unsafe { (*tether.get()).get(missile.index()).unwrap().clone() };
+    assert_eq!(tether, Tether(other.into(), 7));
+}
+
+#[test]
+fn test_weak_field_clearing_rolls_back() {
+    let mut world = test_world();
+    let target = world.spawn();
+    let missile = world.spawn();
+    world.set(
+        missile,
+        &Homing {
+            target: target.into(),
+            origin: EntityWeak::none(),
+        },
+    );
+    world.run();
+    let before = world.current_tick();
+
+    world.destroy(target);
+    world.run();
+    let homing = world.get_storage::<Homing>();
+    assert!(unsafe {
+        (*homing.get())
+            .get(missile.index())
+            .unwrap()
+            .target
+            .is_none()
+    });
+
+    world.resimulate_from(before);
+    // Clearing the field is an ordinary tracked change
+    let restored = unsafe { (*homing.get()).get(missile.index()).unwrap().target };
+    assert_eq!(restored.entity(), target);
+}
@@ -0,0 +1,28 @@
+//! Complete mini-simulations built only on the public API (`examples_lib` feature).
+//!
+//! Each submodule is a small game a user can copy as a starting point, and its tests run
+//! it end to end, so a change to the public API that breaks a typical game shows up here:
+//!
+//! - `platformer`: fixed-point gravity, jumping and one-way platforms. Physics systems are
+//!   children of one pipeline group, landings are mailbox messages, and the level and
+//!   counters are resources.
+//! - `projectile`: projectiles drawn from a fixed pool of entities. They expire through
+//!   `World::set_with_ttl`, and a `WasRemoved` query puts them back into the pool, which
+//!   is a resource so rollback restores it too.
+//! - `echo`: two peers echoing each other's inputs over a lossy `NetSim` link. Echoes are
+//!   rollback-safe effects, so mispredictions surface as cancellations instead of
+//!   duplicate sounds.
+//!
+//! # Example
+//! ```ignore
+//! use rollback_ecs::examples::platformer::{self, Level, PlayerInput};
+//!
+//! let mut world = platformer::world(Level::flat());
+//! let player = platformer::spawn_player(&mut world, Vec2::ZERO);
+//! platformer::set_input(&mut world, player, PlayerInput { dir: 1, jump: true });
+//! world.run();
+//! ```
+
+pub mod echo;
+pub mod platformer;
+pub mod projectile;
@@ -0,0 +1,90 @@
+//! Two peers echoing each other's shouts over a simulated network.
+//!
+//! Each peer owns one player. Every frame a player may shout a number; the input is sent to
+//! the other peer through `NetSim`, which predicts it until it arrives and resimulates when
+//! the prediction was wrong. `EchoSystem` counts the shouts each player made and emits an
+//! `Echo` effect for every one, so presentation code can play it.
+//!
+//! Mispredicted frames emit echoes that never happened. Because echoes go through
+//! `Effects`, a correction surfaces them as `EffectEvent::Cancel` followed by the real echo,
+//! instead of playing both, and resimulating a frame that was predicted right plays
+//! nothing twice.
+
+use crate::effects::effect_key;
+use crate::netsim::{NetSim, NetSimConfig};
+use crate::prelude::*;
+
+/// The number a player shouts this frame, 0 for silence. Written from the frame's input.
+#[derive(Component, Default, Clone, Debug, PartialEq, Hash)]
+pub struct Voice {
+    pub shout: u32,
+}
+
+/// What a player has shouted so far.
+#[derive(Component, Default, Clone, Debug, PartialEq, Hash)]
+pub struct Heard {
+    pub count: u32,
+    pub total: u64,
+}
+
+/// A shout to play back, emitted once per player and frame.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Echo {
+    pub player: Entity,
+    pub shout: u32,
+}
+
+system! {
+    EchoSystem {
+        query! {
+            fn echo(
+                entity: View<Entity>,
+                voice: View<Voice>,
+                heard: &mut ViewMut<Heard>,
+                echoes: Effects<Echo>
+            ) {
+                if voice.shout != 0 {
+                    heard.count += 1;
+                    heard.total += voice.shout as u64;
+                    let echo = Echo { player: *entity, shout: voice.shout };
+                    echoes.emit(effect_key(*entity, 0), echo);
+                }
+            }
+        }
+    }
+}
+
+/// Builds one peer's world with both players, the first spawned being player 0.
+pub fn world() -> World {
+    let mut world = World::new();
+    world.add_system::<EchoSystem>();
+    world.build_scheduler();
+
+    for _ in 0..2 {
+        let player = world.spawn();
+        world.set(player, &Voice::default());
+        world.set(player, &Heard::default());
+    }
+    world
+}
+
+/// The players in player order.
+pub fn players(world: &World) -> Vec<Entity> {
+    world.iter_entities().collect()
+}
+
+/// Writes one frame's shouts, see `netsim::ApplyInputs`.
+pub fn apply(world: &mut World, shouts: [&u32; 2]) {
+    for (player, &shout) in players(world).into_iter().zip(shouts) {
+        world.set(player, &Voice { shout });
+    }
+}
+
+/// A session of two peers running `world` over links configured by `config`.
+pub fn session(config: NetSimConfig) -> NetSim<u32> {
+    NetSim::new(config, world, apply)
+}
+
+#[cfg(test)]
+#[path = "echo.tests.rs"]
+mod tests;
@@ -0,0 +1,72 @@
+use super::*;
+use crate::effects::EffectEvent;
+use crate::netsim::LinkConfig;
+use std::collections::BTreeMap;
+
+/// Shouts every fourth frame, offset per player, so predictions are often wrong.
+fn script(player: usize, frame: u32) -> u32 {
+    if (frame + player as u32 * 3).is_multiple_of(4) {
+        frame + 1
+    } else {
+        0
+    }
+}
+
+fn lossy() -> NetSimConfig {
+    NetSimConfig {
+        link: LinkConfig {
+            latency: 3,
+            jitter: 2,
+            loss: 0.2,
+        },
+        seed: 7,
+        ..Default::default()
+    }
+}
+
+#[test]
+fn test_peers_converge_and_play_each_echo_once() {
+    const FRAMES: u32 = 64;
+    let mut sim = session(lossy());
+    sim.run(FRAMES, script);
+    sim.assert_converged();
+
+    for p in 0..2 {
+        let peer = sim.peer_mut(p);
+        assert!(peer.rollbacks() > 0);
+        let world = peer.world_mut();
+        let players = players(world);
+
+        // What presentation ends up showing after applying every correction
+        let mut playing = BTreeMap::new();
+        for event in world.effects::<Echo>().drain() {
+            match event {
+                EffectEvent::Emit { tick, key, effect } => {
+                    assert!(playing.insert((tick, key), effect.shout).is_none());
+                }
+                EffectEvent::Cancel { tick, key } => {
+                    assert!(playing.remove(&(tick, key)).is_some());
+                }
+            }
+        }
+
+        let mut expected = BTreeMap::new();
+        for (player, &entity) in players.iter().enumerate() {
+            let shouts: Vec<u32> = (0..FRAMES)
+                .map(|frame| script(player, frame))
+                .filter(|&shout| shout != 0)
+                .collect();
+            let heard = world.get::<Heard>(entity).unwrap();
+            assert_eq!(heard.count as usize, shouts.len());
+            assert_eq!(heard.total, shouts.iter().map(|&s| s as u64).sum::<u64>());
+
+            for frame in 0..FRAMES {
+                let shout = script(player, frame);
+                if shout != 0 {
+                    expected.insert((Tick::new(frame + 1), effect_key(entity, 0)), shout);
+                }
+            }
+        }
+        assert_eq!(playing, expected);
+    }
+}
@@ -0,0 +1,184 @@
+//! Deterministic platformer physics.
+//!
+//! Players have a `Body` and a `PlayerInput` written by the game before every tick. The
+//! physics systems are children of `PhysicsGroup` and run in a fixed chain: `ControlSystem`
+//! turns input into velocity, `IntegrateSystem` applies gravity, moves and lands bodies on
+//! the level's one-way platforms, and every landing is sent as a `Landed` message to
+//! `LandingSystem`, which counts them in the `Stats` resource.
+//!
+//! All math is `det_math` fixed point and the level is a resource, so peers running the
+//! same inputs agree bit for bit and a rollback restores bodies and counters together.
+
+use crate::det_math::{Fixed, Vec2};
+use crate::prelude::*;
+
+/// Horizontal speed while a direction is held, per tick.
+pub const RUN_SPEED: Fixed = Fixed::from_ratio(1, 2);
+/// Upward speed at the start of a jump, per tick.
+pub const JUMP_SPEED: Fixed = Fixed::from_int(2);
+/// Downward acceleration, per tick squared.
+pub const GRAVITY: Fixed = Fixed::from_ratio(1, 8);
+
+/// Position and velocity of a player. `pos` is the bottom center.
+#[derive(Component, Default, Clone, Debug, PartialEq, Hash)]
+pub struct Body {
+    pub pos: Vec2,
+    pub vel: Vec2,
+    /// Standing on a platform at the end of the last tick.
+    pub grounded: bool,
+}
+
+/// What a player does this tick.
+#[derive(Component, Default, Clone, Copy, Debug, PartialEq, Hash)]
+pub struct PlayerInput {
+    /// -1 left, 0 none, 1 right.
+    pub dir: i32,
+    /// Jumps if the player is grounded.
+    pub jump: bool,
+}
+
+/// A one-way platform: bodies falling onto `top` between `left` and `right` land, bodies
+/// moving up pass through.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Platform {
+    pub left: Fixed,
+    pub right: Fixed,
+    pub top: Fixed,
+}
+
+/// The platforms of the level.
+#[derive(Clone, Default, Debug, PartialEq)]
+pub struct Level {
+    pub platforms: Vec<Platform>,
+}
+
+impl Level {
+    /// Wide ground at height 0.
+    pub fn flat() -> Self {
+        Level {
+            platforms: vec![Platform {
+                left: Fixed::from_int(-1000),
+                right: Fixed::from_int(1000),
+                top: Fixed::ZERO,
+            }],
+        }
+    }
+
+    /// The top of the highest platform the body crossed moving from `from` to `to`.
+    fn landing(&self, from: Vec2, to: Vec2) -> Option<Fixed> {
+        self.platforms
+            .iter()
+            .filter(|p| from.y >= p.top && to.y < p.top && p.left <= to.x && to.x <= p.right)
+            .map(|p| p.top)
+            .max()
+    }
+}
+
+/// Counters kept by the simulation.
+#[derive(Clone, Default, Debug, PartialEq)]
+pub struct Stats {
+    pub landings: u32,
+}
+
+/// A body touched down this tick.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Landed {
+    pub player: Entity,
+    /// Downward speed at impact.
+    pub speed: Fixed,
+}
+
+/// Parent of all physics systems.
+#[rollback_macros::pipeline_group]
+pub struct PhysicsGroup;
+
+system! {
+    ControlSystem {
+        query! {
+            fn control(body: &mut ViewMut<Body>, input: View<PlayerInput>) Parent=PhysicsGroup {
+                body.vel.x = RUN_SPEED * Fixed::from_int(input.dir.signum());
+                if input.jump && body.grounded {
+                    body.vel.y = JUMP_SPEED;
+                }
+            }
+        }
+    }
+}
+
+system! {
+    IntegrateSystem {
+        query! {
+            fn integrate(
+                entity: View<Entity>,
+                body: &mut ViewMut<Body>,
+                level: Res<Level>,
+                landed: Mailbox<Landed, LandingSystem>
+            ) Parent=PhysicsGroup After=[ControlSystem] {
+                body.vel.y -= GRAVITY;
+                let from = body.pos;
+                let to = from + body.vel;
+
+                match level.landing(from, to) {
+                    Some(top) if body.vel.y <= Fixed::ZERO => {
+                        if !body.grounded {
+                            landed.send(Landed { player: *entity, speed: -body.vel.y });
+                        }
+                        body.pos = Vec2::new(to.x, top);
+                        body.vel.y = Fixed::ZERO;
+                        body.grounded = true;
+                    }
+                    _ => {
+                        body.pos = to;
+                        body.grounded = false;
+                    }
+                }
+            }
+        }
+    }
+}
+
+system! {
+    LandingSystem {
+        query! {
+            fn tally(landed: Inbox<Landed>, stats: ResMut<Stats>) Parent=PhysicsGroup {
+                stats.landings += landed.len() as u32;
+            }
+        }
+    }
+}
+
+/// Builds a world simulating `level`.
+pub fn world(level: Level) -> World {
+    let mut world = World::new();
+    world.get_storage::<Body>();
+    world.get_storage::<PlayerInput>();
+    world.add_system::<ControlSystem>();
+    world.add_system::<IntegrateSystem>();
+    world.add_system::<LandingSystem>();
+    world.build_scheduler();
+    world.insert_resource(level);
+    world.insert_resource(Stats::default());
+    world
+}
+
+/// Spawns a player standing at `at`.
+pub fn spawn_player(world: &mut World, at: Vec2) -> Entity {
+    let player = world.spawn();
+    let body = Body {
+        pos: at,
+        vel: Vec2::ZERO,
+        grounded: true,
+    };
+    world.set(player, &body);
+    world.set(player, &PlayerInput::default());
+    player
+}
+
+/// Sets the input `player` uses from the next tick on.
+pub fn set_input(world: &mut World, player: Entity, input: PlayerInput) {
+    world.set(player, &input);
+}
+
+#[cfg(test)]
+#[path = "platformer.tests.rs"]
+mod tests;
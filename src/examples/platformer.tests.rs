@@ -0,0 +1,125 @@
+use super::*;
+
+fn body(world: &World, player: Entity) -> Body {
+    world.get::<Body>(player).cloned().unwrap()
+}
+
+fn run(world: &mut World, ticks: u32) {
+    for _ in 0..ticks {
+        world.run();
+    }
+}
+
+/// Runs right for a while, jumping twice.
+fn script(frame: u32) -> PlayerInput {
+    PlayerInput {
+        dir: if frame < 40 { 1 } else { 0 },
+        jump: frame == 5 || frame == 40,
+    }
+}
+
+#[test]
+fn test_jump_lands_once() {
+    let mut world = world(Level::flat());
+    let player = spawn_player(&mut world, Vec2::ZERO);
+    set_input(&mut world, player, PlayerInput { dir: 1, jump: true });
+    world.run();
+    assert!(!body(&world, player).grounded);
+    assert_eq!(body(&world, player).pos.y, JUMP_SPEED - GRAVITY);
+
+    set_input(
+        &mut world,
+        player,
+        PlayerInput {
+            dir: 0,
+            jump: false,
+        },
+    );
+    let mut airtime = 1;
+    while !body(&world, player).grounded {
+        world.run();
+        airtime += 1;
+    }
+    assert_eq!(airtime, 32);
+    assert_eq!(body(&world, player).pos, Vec2::new(RUN_SPEED, Fixed::ZERO));
+    assert_eq!(world.resource::<Stats>(), Some(&Stats { landings: 1 }));
+
+    // Standing doesn't count as landing again
+    world.run();
+    assert_eq!(world.resource::<Stats>(), Some(&Stats { landings: 1 }));
+}
+
+#[test]
+fn test_one_way_platform_and_walking_off() {
+    let mut level = Level::flat();
+    level.platforms.push(Platform {
+        left: Fixed::ZERO,
+        right: Fixed::from_int(2),
+        top: Fixed::from_int(3),
+    });
+    let mut world = world(level);
+    let player = spawn_player(&mut world, Vec2::new(Fixed::ONE, Fixed::from_int(5)));
+
+    // Falls onto the platform
+    run(&mut world, 8);
+    assert_eq!(body(&world, player).pos.y, Fixed::from_int(3));
+    assert!(body(&world, player).grounded);
+
+    // Walks off its edge and drops to the ground
+    set_input(
+        &mut world,
+        player,
+        PlayerInput {
+            dir: 1,
+            jump: false,
+        },
+    );
+    run(&mut world, 20);
+    assert_eq!(body(&world, player).pos.y, Fixed::ZERO);
+    assert_eq!(world.resource::<Stats>(), Some(&Stats { landings: 2 }));
+
+    // Jumps up through the platform from below and lands on top
+    world.set(
+        player,
+        &Body {
+            pos: Vec2::new(Fixed::ONE, Fixed::ZERO),
+            ..body(&world, player)
+        },
+    );
+    set_input(&mut world, player, PlayerInput { dir: 0, jump: true });
+    world.run();
+    set_input(&mut world, player, PlayerInput::default());
+    run(&mut world, 30);
+    assert_eq!(body(&world, player).pos.y, Fixed::from_int(3));
+    assert_eq!(world.resource::<Stats>(), Some(&Stats { landings: 3 }));
+}
+
+#[test]
+fn test_resimulation_is_bit_identical() {
+    let mut world = world(Level::flat());
+    let players = [
+        spawn_player(&mut world, Vec2::ZERO),
+        spawn_player(&mut world, Vec2::new(Fixed::from_int(4), Fixed::ZERO)),
+    ];
+    let start = world.current_tick();
+    let mut hashes = Vec::new();
+    for frame in 0..80 {
+        for &player in &players {
+            set_input(&mut world, player, script(frame));
+        }
+        world.run();
+        hashes.push(world.compute_state_hash());
+    }
+    let stats = world.resource::<Stats>().cloned().unwrap();
+    assert_eq!(stats.landings, 4);
+
+    world.resimulate_from(Tick::new(start.value() + 20));
+    for frame in 20..80 {
+        for &player in &players {
+            set_input(&mut world, player, script(frame));
+        }
+        world.run();
+        assert_eq!(world.compute_state_hash(), hashes[frame as usize]);
+    }
+    assert_eq!(world.resource::<Stats>(), Some(&stats));
+}
@@ -0,0 +1,98 @@
+//! Projectiles drawn from a fixed pool of entities.
+//!
+//! `world(n)` spawns `n` idle entities up front and keeps them in the `Pool` resource.
+//! `fire` takes one, gives it a `Motion` and a `Projectile` that expires after its range
+//! through `World::set_with_ttl`, and `FlightSystem` moves every projectile. A tick after
+//! a `Projectile` expired, `RecycleSystem` sees the slot through `WasRemoved=[Projectile]`,
+//! drops its `Motion` and returns the entity to the pool, so no entity is ever spawned or
+//! destroyed during play.
+//!
+//! The pool is a resource and the expiry times are kept per tick, so a rollback puts back
+//! exactly the projectiles that were in flight and the pool as it was.
+
+use crate::det_math::Vec2;
+use crate::prelude::*;
+
+/// Position and velocity of a projectile in flight.
+#[derive(Component, Default, Clone, Debug, PartialEq, Hash)]
+pub struct Motion {
+    pub pos: Vec2,
+    pub vel: Vec2,
+}
+
+/// Marks a projectile in flight. Removed when its time to live runs out.
+#[derive(Component, Default, Clone, Debug, PartialEq, Hash)]
+pub struct Projectile {
+    /// The entity that fired it.
+    pub owner: Option<Entity>,
+}
+
+/// Idle projectile entities, taken from the back.
+#[derive(Clone, Default, Debug, PartialEq)]
+pub struct Pool {
+    pub idle: Vec<Entity>,
+}
+
+system! {
+    FlightSystem {
+        query! {
+            fn fly(motion: &mut ViewMut<Motion>, _projectile: View<Projectile>) {
+                motion.pos = motion.pos + motion.vel;
+            }
+        }
+    }
+}
+
+system! {
+    RecycleSystem {
+        query! {
+            fn recycle(entity: View<Entity>, _motion: View<Motion>, pool: ResMut<Pool>)
+                WasRemoved=[Projectile] Remove=[Motion] {
+                pool.idle.push(*entity);
+            }
+        }
+    }
+}
+
+/// Builds a world with `capacity` pooled projectiles.
+pub fn world(capacity: u32) -> World {
+    let mut world = World::new();
+    world.get_storage::<Motion>();
+    world.get_storage::<Projectile>();
+    world.enable_ttl::<Projectile>();
+    world.add_system::<FlightSystem>();
+    world.add_system::<RecycleSystem>();
+    world.build_scheduler();
+
+    let mut idle: Vec<Entity> = (0..capacity).map(|_| world.spawn()).collect();
+    // Lowest index first out
+    idle.reverse();
+    world.insert_resource(Pool { idle });
+    world
+}
+
+/// Fires a projectile from `pos` that flies for `ttl` ticks, or returns `None` if the
+/// pool is empty.
+pub fn fire(
+    world: &mut World,
+    owner: Option<Entity>,
+    pos: Vec2,
+    vel: Vec2,
+    ttl: u32,
+) -> Option<Entity> {
+    let projectile = world.resource_mut::<Pool>()?.idle.pop()?;
+    world.set(projectile, &Motion { pos, vel });
+    world.set_with_ttl(projectile, &Projectile { owner }, ttl);
+    Some(projectile)
+}
+
+/// Number of projectiles in flight.
+pub fn in_flight(world: &World) -> usize {
+    world
+        .storage_ref::<Projectile>()
+        .map_or(0, |storage| storage.iter().count())
+}
+
+#[cfg(test)]
+#[path = "projectile.tests.rs"]
+mod tests;
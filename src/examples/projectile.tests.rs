@@ -0,0 +1,75 @@
+use super::*;
+use crate::det_math::Fixed;
+
+fn right() -> Vec2 {
+    Vec2::new(Fixed::ONE, Fixed::ZERO)
+}
+
+fn idle(world: &World) -> Vec<Entity> {
+    world.resource::<Pool>().unwrap().idle.clone()
+}
+
+#[test]
+fn test_projectiles_expire_and_return_to_pool() {
+    let mut world = world(2);
+    let pooled = idle(&world);
+    let first = fire(&mut world, None, Vec2::ZERO, right(), 3).unwrap();
+    let second = fire(&mut world, None, Vec2::ZERO, right(), 5).unwrap();
+    assert_eq!(first, pooled[1]);
+    assert!(fire(&mut world, None, Vec2::ZERO, right(), 1).is_none());
+
+    world.run();
+    world.run();
+    world.run();
+    assert_eq!(
+        world.get::<Motion>(first).unwrap().pos.x,
+        Fixed::from_int(3)
+    );
+    assert_eq!(in_flight(&world), 1);
+
+    // Recycled the tick after expiring, without moving again
+    world.run();
+    assert_eq!(idle(&world), vec![first]);
+    assert_eq!(world.get::<Motion>(first), None);
+    assert!(world.contains(first));
+
+    let again = fire(&mut world, None, Vec2::ZERO, right(), 1).unwrap();
+    assert_eq!(again, first);
+    for _ in 0..3 {
+        world.run();
+    }
+    assert_eq!(in_flight(&world), 0);
+    assert_eq!(idle(&world).len(), 2);
+    assert!(world.contains(second));
+}
+
+#[test]
+fn test_rollback_restores_pool_and_flight() {
+    let mut world = world(4);
+    let owner = Some(world.spawn());
+    fire(&mut world, owner, Vec2::ZERO, right(), 2);
+    world.run();
+    let tick = world.current_tick();
+    let volley = |world: &mut World| {
+        for i in 0..2 {
+            let pos = Vec2::new(Fixed::ZERO, Fixed::from_int(i));
+            fire(world, owner, pos, right(), 4).unwrap();
+        }
+    };
+    volley(&mut world);
+    let mut hashes = Vec::new();
+    for _ in 0..6 {
+        world.run();
+        hashes.push(world.compute_state_hash());
+    }
+    assert_eq!(idle(&world).len(), 4);
+
+    world.resimulate_from(tick);
+    assert_eq!(idle(&world).len(), 3);
+    assert_eq!(in_flight(&world), 1);
+    volley(&mut world);
+    for hash in hashes {
+        world.run();
+        assert_eq!(world.compute_state_hash(), hash);
+    }
+}
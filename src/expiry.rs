@@ -0,0 +1,218 @@
+//! Time-to-live for components.
+//!
+//! `World::set_with_ttl(entity, &component, ttl_ticks)` sets a component that removes itself
+//! after `ttl_ticks` ticks, so status effects and temporary markers don't each need their
+//! own countdown system. The component is visible for `ttl_ticks` ticks starting with the
+//! current one and is removed by `ExpirySystem<T>` (in `CleanupGroup`) at the end of the
+//! last of them.
+//!
+//! Expiry times live in a per-component `ExpiryTable<T>` rather than in the storage. Every
+//! change to the table is logged with the tick it happened at, and `World::rollback` undoes
+//! the changes newer than the target tick together with the storages, so a resimulated
+//! tick removes exactly what the original removed.
+//!
+//! Setting `T` again with `set_with_ttl` replaces its expiry. A plain `World::set` keeps
+//! it; use `World::clear_ttl` to make the component permanent again.
+//!
+//! Each component type needs its `ExpirySystem<T>` scheduled: call `World::enable_ttl::<T>()`
+//! before `build_scheduler()` (a `set_with_ttl` before the build does it implicitly).
+//!
+//! # Example
+//! ```ignore
+//! world.enable_ttl::<Stunned>();
+//! world.build_scheduler();
+//! // ...
+//! world.set_with_ttl(target, &Stunned {}, 30);
+//! ```
+
+use crate::component::Component;
+use crate::entity::Entity;
+use crate::scheduler::PipelineStage;
+use crate::storage::{ComponentStorage, Storage};
+use crate::tick::Tick;
+use crate::world::World;
+use std::any::{Any, TypeId};
+use std::cell::{Cell, UnsafeCell};
+use std::collections::BTreeMap;
+use std::rc::Rc;
+
+/// When one entity's component expires.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Expiry {
+    /// Generation of the entity the component was set on.
+    pub generation: u32,
+    /// Last tick the component is visible in; it is removed during this tick's cleanup.
+    pub last_tick: Tick,
+}
+
+/// Rollback-aware expiry times for component `T`, keyed by entity index.
+pub struct ExpiryTable<T> {
+    entries: UnsafeCell<BTreeMap<u32, Expiry>>,
+    /// Undo log: `(tick, index, previous entry)` for every change, oldest first.
+    history: UnsafeCell<Vec<(Tick, u32, Option<Expiry>)>>,
+    /// World tick of the run in progress, set by the world before the scheduler runs.
+    tick: Cell<Tick>,
+    _marker: std::marker::PhantomData<T>,
+}
+
+impl<T> ExpiryTable<T> {
+    pub fn new() -> Self {
+        ExpiryTable {
+            entries: UnsafeCell::new(BTreeMap::new()),
+            history: UnsafeCell::new(Vec::new()),
+            tick: Cell::new(Tick::new(0)),
+            _marker: std::marker::PhantomData,
+        }
+    }
+
+    /// Expiry of the component on entity `index`, if it has one.
+    /// Must not be called while the scheduler is running.
+    pub fn get(&self, index: u32) -> Option<Expiry> {
+        unsafe { (*self.entries.get()).get(&index).copied() }
+    }
+
+    /// Number of components with a pending expiry.
+    pub fn len(&self) -> usize {
+        unsafe { (*self.entries.get()).len() }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Sets or clears the expiry of entity `index`, logging the change at `tick`.
+    pub(crate) fn put(&self, tick: Tick, index: u32, expiry: Option<Expiry>) {
+        let entries = unsafe { &mut *self.entries.get() };
+
+        let previous = match expiry {
+            Some(expiry) => entries.insert(index, expiry),
+            None => entries.remove(&index),
+        };
+
+        if previous != expiry {
+            unsafe { (*self.history.get()).push((tick, index, previous)) };
+        }
+    }
+
+    /// Undoes every change made after `target_tick`.
+    fn rollback(&self, target_tick: Tick) {
+        let entries = unsafe { &mut *self.entries.get() };
+        let history = unsafe { &mut *self.history.get() };
+
+        while let Some(&(tick, index, previous)) = history.last() {
+            if !tick.is_after(target_tick) {
+                break;
+            }
+
+            history.pop();
+            match previous {
+                Some(expiry) => entries.insert(index, expiry),
+                None => entries.remove(&index),
+            };
+        }
+    }
+}
+
+impl<T> Default for ExpiryTable<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Type-erased access to expiry tables so the world can stamp and roll them back.
+pub trait ExpiryLike: Any {
+    fn begin_tick(&self, tick: Tick, oldest: Tick);
+    fn rollback(&self, target_tick: Tick);
+    /// Drops every expiry along with the undo log.
+    fn clear(&self);
+    fn as_any_rc(self: Rc<Self>) -> Rc<dyn Any>;
+}
+
+impl<T: 'static> ExpiryLike for ExpiryTable<T> {
+    fn begin_tick(&self, tick: Tick, oldest: Tick) {
+        self.tick.set(tick);
+        // Entries before the window can never be restored
+        unsafe { (*self.history.get()).retain(|(logged, _, _)| !logged.is_before(oldest)) };
+    }
+
+    fn rollback(&self, target_tick: Tick) {
+        ExpiryTable::rollback(self, target_tick);
+    }
+
+    fn clear(&self) {
+        unsafe {
+            (*self.entries.get()).clear();
+            (*self.history.get()).clear();
+        }
+    }
+
+    fn as_any_rc(self: Rc<Self>) -> Rc<dyn Any> {
+        self
+    }
+}
+
+/// Removes components whose ttl ran out, see the module docs.
+pub struct ExpirySystem<T: Component> {
+    pub storage: Rc<UnsafeCell<T::Storage>>,
+    pub entity_storage: Rc<UnsafeCell<Storage<Entity>>>,
+    pub table: Rc<ExpiryTable<T>>,
+}
+
+unsafe impl<T: Component> Send for ExpirySystem<T> {}
+unsafe impl<T: Component> Sync for ExpirySystem<T> {}
+
+impl<T: Component> PipelineStage for ExpirySystem<T> {
+    fn type_id(&self) -> TypeId {
+        TypeId::of::<Self>()
+    }
+
+    fn run(&self) {
+        let storage = unsafe { &mut *self.storage.get() };
+        let entities = unsafe { &*self.entity_storage.get() };
+        let tick = self.table.tick.get();
+
+        let expired: Vec<(u32, Expiry)> = unsafe { &*self.table.entries.get() }
+            .iter()
+            .filter(|(_, expiry)| !expiry.last_tick.is_after(tick))
+            .map(|(&index, &expiry)| (index, expiry))
+            .collect();
+
+        for (index, expiry) in expired {
+            // The entity may have been destroyed and its index reused since
+            let alive = entities
+                .get(index)
+                .is_some_and(|e| e.generation() == expiry.generation);
+
+            if alive && storage.get(index).is_some() {
+                storage.remove(index);
+            }
+
+            self.table.put(tick, index, None);
+        }
+    }
+
+    fn reads(&self) -> &'static [TypeId] {
+        static READS: &[TypeId] = &[TypeId::of::<Entity>()];
+        READS
+    }
+
+    fn writes(&self) -> &'static [TypeId] {
+        crate::scheduler::type_id_slice::<T>()
+    }
+
+    fn parent(&self) -> Option<TypeId> {
+        Some(TypeId::of::<crate::scheduler::CleanupGroup>())
+    }
+
+    fn create(world: &mut World) -> Self {
+        Self {
+            storage: world.get_storage::<T>(),
+            entity_storage: world.get_storage::<Entity>(),
+            table: world.expiry_table_or_insert::<T>(),
+        }
+    }
+}
+
+#[cfg(test)]
+#[path = "expiry.tests.rs"]
+mod tests;
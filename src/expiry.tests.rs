@@ -0,0 +1,119 @@
+use super::*;
+use crate::component::Component;
+use crate::world::World;
+
+#[derive(Component, Default, Clone, Debug, PartialEq)]
+struct Stunned {
+    source: u32,
+}
+
+fn stunned(world: &mut World, entity: Entity) -> Option<u32> {
+    let storage = world.get_storage::<Stunned>();
+    unsafe { (*storage.get()).get(entity.index()).map(|s| s.source) }
+}
+
+fn world() -> World {
+    let mut world = World::new();
+    world.enable_ttl::<Stunned>();
+    world.build_scheduler();
+    world
+}
+
+#[test]
+fn test_component_removed_after_ttl() {
+    let mut world = world();
+    let e = world.spawn();
+    world.run(); // tick 0
+
+    world.set_with_ttl(e, &Stunned { source: 1 }, 3);
+    assert_eq!(world.expires_at::<Stunned>(e), Some(Tick::new(3)));
+
+    world.run(); // tick 1
+    world.run(); // tick 2
+    assert_eq!(stunned(&mut world, e), Some(1));
+
+    world.run(); // tick 3: last visible tick, removed in its cleanup
+    assert_eq!(stunned(&mut world, e), None);
+    assert_eq!(world.expires_at::<Stunned>(e), None);
+    assert!(world.expiry_table::<Stunned>().unwrap().is_empty());
+}
+
+#[test]
+fn test_reset_replaces_and_clear_keeps() {
+    let mut world = world();
+    let a = world.spawn();
+    let b = world.spawn();
+
+    world.set_with_ttl(a, &Stunned { source: 1 }, 1);
+    world.set_with_ttl(a, &Stunned { source: 2 }, 5);
+    world.set_with_ttl(b, &Stunned { source: 3 }, 1);
+    world.clear_ttl::<Stunned>(b);
+
+    for _ in 0..3 {
+        world.run();
+    }
+
+    assert_eq!(stunned(&mut world, a), Some(2));
+    assert_eq!(stunned(&mut world, b), Some(3));
+
+    world.run();
+    world.run();
+    assert_eq!(stunned(&mut world, a), None);
+    assert_eq!(stunned(&mut world, b), Some(3));
+}
+
+#[test]
+fn test_stale_generation_is_not_expired() {
+    let mut world = world();
+    let e = world.spawn();
+    world.set(e, &Stunned { source: 2 });
+
+    // An expiry left over from an earlier entity at the same index
+    let stale = Expiry {
+        generation: e.generation() + 1,
+        last_tick: Tick::new(0),
+    };
+    let table = world.expiry_table::<Stunned>().unwrap();
+    table.put(Tick::new(0), e.index(), Some(stale));
+
+    world.run();
+    assert_eq!(stunned(&mut world, e), Some(2));
+    assert!(table.is_empty());
+}
+
+#[test]
+fn test_expiry_resimulates_after_rollback() {
+    let mut world = world();
+    let e = world.spawn();
+    world.run(); // tick 0
+    world.run(); // tick 1
+
+    world.set_with_ttl(e, &Stunned { source: 1 }, 2);
+    world.run(); // tick 2
+    world.run(); // tick 3: removed
+    world.run(); // tick 4
+    assert_eq!(stunned(&mut world, e), None);
+
+    world.rollback(Tick::new(2));
+    assert_eq!(stunned(&mut world, e), Some(1));
+    assert_eq!(world.expires_at::<Stunned>(e), Some(Tick::new(3)));
+
+    world.run(); // tick 2 (replay)
+    assert_eq!(stunned(&mut world, e), Some(1));
+    world.run(); // tick 3 (replay): removed again at the same tick
+    assert_eq!(stunned(&mut world, e), None);
+
+    // Rolling back past the set undoes the ttl along with the component
+    world.rollback(Tick::new(1));
+    assert_eq!(stunned(&mut world, e), None);
+    assert_eq!(world.expires_at::<Stunned>(e), None);
+}
+
+#[test]
+#[should_panic(expected = "without enable_ttl")]
+fn test_set_with_ttl_after_build_requires_enable() {
+    let mut world = World::new();
+    let e = world.spawn();
+    world.build_scheduler();
+    world.set_with_ttl(e, &Stunned { source: 1 }, 1);
+}
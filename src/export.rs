@@ -0,0 +1,840 @@
+//! Serde support for components and world state (`serde` feature).
+//!
+//! `#[derive(Component)]` picks up `Serialize + Deserialize` on its own: every component
+//! type implementing both gets `Component::serde_fns`, the same way `Hash` feeds the state
+//! hash. `World::export_snapshot` captures every such component into a `Snapshot`, which is
+//! itself `Serialize`/`Deserialize`, so it can be written as JSON for debugging dumps or
+//! with a binary format for replay files. `Snapshot::delta` turns two captures into the
+//! per-tick changes between them, and `World::import_snapshot` applies either kind.
+//!
+//! Component values are held as `Value`s, a small tree mirroring serde's data model, so a
+//! snapshot doesn't depend on the format it ends up in. Human-readable formats see the
+//! natural shape (`{"hp": 10}`), binary formats a tagged one they can read back without
+//! `deserialize_any`. Unlike the `wire` format, nothing here is meant for hostile input or
+//! tight packets.
+//!
+//! # Example
+//! ```ignore
+//! #[derive(Component, Clone, Default, Serialize, Deserialize)]
+//! struct Health {
+//!     hp: i32,
+//! }
+//!
+//! let before = world.export_snapshot()?;
+//! world.run();
+//! let after = world.export_snapshot()?;
+//! replay.push(serde_json::to_string(&after.delta(&before))?);
+//!
+//! // Later, on a world at `before`
+//! replica.import_snapshot(&serde_json::from_str(&replay[0])?)?;
+//! ```
+
+use crate::entity::Entity;
+use crate::statehash::Probe;
+use crate::tick::Tick;
+use serde::de::value::{MapDeserializer, SeqDeserializer};
+use serde::de::{self, DeserializeOwned, IntoDeserializer, Visitor};
+use serde::ser;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// A value in serde's data model. Structs and maps are `Map`s, tuples and sequences
+/// `Seq`s, and enum variants follow serde's external tagging: a unit variant is its name,
+/// any other variant a one-entry `Map` from its name to its content.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    Unit,
+    Bool(bool),
+    I64(i64),
+    U64(u64),
+    F64(f64),
+    String(String),
+    Bytes(Vec<u8>),
+    None,
+    Some(Box<Value>),
+    Seq(Vec<Value>),
+    /// Entries in serialization order.
+    Map(Vec<(Value, Value)>),
+}
+
+/// Why a value could not be converted to or from a `Value`, or a snapshot applied.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ValueError(pub String);
+
+impl fmt::Display for ValueError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for ValueError {}
+
+impl ser::Error for ValueError {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        ValueError(msg.to_string())
+    }
+}
+
+impl de::Error for ValueError {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        ValueError(msg.to_string())
+    }
+}
+
+/// Converts `value` into a `Value`.
+pub fn to_value<T: Serialize + ?Sized>(value: &T) -> Result<Value, ValueError> {
+    value.serialize(ValueSerializer)
+}
+
+/// Converts a `Value` back into a `T`.
+pub fn from_value<T: DeserializeOwned>(value: Value) -> Result<T, ValueError> {
+    T::deserialize(value)
+}
+
+/// How a component type is converted to and from `Value`s, see `Component::serde_fns`.
+pub struct SerdeFns<T> {
+    pub to_value: fn(&T) -> Result<Value, ValueError>,
+    pub from_value: fn(Value) -> Result<T, ValueError>,
+}
+
+impl<T> Clone for SerdeFns<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for SerdeFns<T> {}
+
+/// Picked by method resolution when `T: Serialize + DeserializeOwned`.
+#[doc(hidden)]
+pub trait ViaSerde<T> {
+    fn serde_fns(&self) -> Option<SerdeFns<T>>;
+}
+
+impl<T: Serialize + DeserializeOwned> ViaSerde<T> for Probe<T> {
+    fn serde_fns(&self) -> Option<SerdeFns<T>> {
+        Some(SerdeFns {
+            to_value: to_value::<T>,
+            from_value: from_value::<T>,
+        })
+    }
+}
+
+/// The fallback for types without serde impls, one autoref further away.
+#[doc(hidden)]
+pub trait ViaNoSerde<T> {
+    fn serde_fns(&self) -> Option<SerdeFns<T>>;
+}
+
+impl<T> ViaNoSerde<T> for &Probe<T> {
+    fn serde_fns(&self) -> Option<SerdeFns<T>> {
+        None
+    }
+}
+
+impl Serialize for Entity {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u32(self.to_bits())
+    }
+}
+
+impl<'de> Deserialize<'de> for Entity {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        u32::deserialize(deserializer).map(Entity::from_bits)
+    }
+}
+
+/// The components of one type in a `Snapshot`.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct ComponentValues {
+    /// Values by ascending entity index.
+    pub set: Vec<(u32, Value)>,
+    /// Indices whose component a delta removes, ascending. Empty in full snapshots.
+    pub removed: Vec<u32>,
+}
+
+/// Every serde-enabled component of a world at one tick, or the changes between two such
+/// captures. See the module docs.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Snapshot {
+    /// The tick the state was captured at.
+    pub tick: Tick,
+    /// For deltas, the tick of the snapshot they apply to; `None` for full snapshots.
+    pub base: Option<Tick>,
+    /// Components by type name.
+    pub components: BTreeMap<String, ComponentValues>,
+}
+
+impl Snapshot {
+    /// The changes turning the full snapshot `base` into `self`, which must be full too.
+    pub fn delta(&self, base: &Snapshot) -> Snapshot {
+        debug_assert!(self.base.is_none() && base.base.is_none(), "delta of a delta");
+        let empty = ComponentValues::default();
+        let mut components = BTreeMap::new();
+
+        let names = self.components.keys().chain(base.components.keys());
+        for name in names {
+            if components.contains_key(name) {
+                continue;
+            }
+            let ours = self.components.get(name).unwrap_or(&empty);
+            let theirs = base.components.get(name).unwrap_or(&empty);
+            let changes = diff_values(&theirs.set, &ours.set);
+            if !changes.set.is_empty() || !changes.removed.is_empty() {
+                components.insert(name.clone(), changes);
+            }
+        }
+
+        Snapshot {
+            tick: self.tick,
+            base: Some(base.tick),
+            components,
+        }
+    }
+
+    /// Whether this is a delta rather than a full snapshot.
+    pub fn is_delta(&self) -> bool {
+        self.base.is_some()
+    }
+}
+
+/// Merges two ascending value lists into the sets and removals turning `base` into
+/// `current`.
+fn diff_values(base: &[(u32, Value)], current: &[(u32, Value)]) -> ComponentValues {
+    let mut changes = ComponentValues::default();
+    let (mut b, mut c) = (base.iter().peekable(), current.iter().peekable());
+
+    loop {
+        match (b.peek(), c.peek()) {
+            (None, None) => return changes,
+            (Some((bi, bv)), Some((ci, cv))) if bi == ci => {
+                if bv != cv {
+                    changes.set.push((*ci, cv.clone()));
+                }
+                b.next();
+                c.next();
+            }
+            (Some((bi, _)), Some((ci, _))) if bi < ci => {
+                changes.removed.push(*bi);
+                b.next();
+            }
+            (Some((bi, _)), None) => {
+                changes.removed.push(*bi);
+                b.next();
+            }
+            (_, Some((ci, cv))) => {
+                changes.set.push((*ci, cv.clone()));
+                c.next();
+            }
+        }
+    }
+}
+
+impl Serialize for Value {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if !serializer.is_human_readable() {
+            return Tagged::from(self).serialize(serializer);
+        }
+
+        match self {
+            Value::Unit => serializer.serialize_unit(),
+            Value::Bool(v) => serializer.serialize_bool(*v),
+            Value::I64(v) => serializer.serialize_i64(*v),
+            Value::U64(v) => serializer.serialize_u64(*v),
+            Value::F64(v) => serializer.serialize_f64(*v),
+            Value::String(v) => serializer.serialize_str(v),
+            Value::Bytes(v) => serializer.serialize_bytes(v),
+            Value::None => serializer.serialize_none(),
+            Value::Some(v) => serializer.serialize_some(v),
+            Value::Seq(items) => serializer.collect_seq(items),
+            Value::Map(entries) => serializer.collect_map(entries.iter().map(|(k, v)| (k, v))),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Value {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        if deserializer.is_human_readable() {
+            deserializer.deserialize_any(ValueVisitor)
+        } else {
+            TaggedOwned::deserialize(deserializer).map(Value::from)
+        }
+    }
+}
+
+/// `Value` for binary formats, which can't `deserialize_any`.
+#[derive(Serialize)]
+#[serde(rename = "Value")]
+enum Tagged<'a> {
+    Unit,
+    Bool(bool),
+    I64(i64),
+    U64(u64),
+    F64(f64),
+    String(&'a str),
+    Bytes(&'a [u8]),
+    None,
+    Some(&'a Value),
+    Seq(&'a [Value]),
+    Map(&'a [(Value, Value)]),
+}
+
+#[derive(Deserialize)]
+#[serde(rename = "Value")]
+enum TaggedOwned {
+    Unit,
+    Bool(bool),
+    I64(i64),
+    U64(u64),
+    F64(f64),
+    String(String),
+    Bytes(Vec<u8>),
+    None,
+    Some(Box<Value>),
+    Seq(Vec<Value>),
+    Map(Vec<(Value, Value)>),
+}
+
+impl<'a> From<&'a Value> for Tagged<'a> {
+    fn from(value: &'a Value) -> Self {
+        match value {
+            Value::Unit => Tagged::Unit,
+            Value::Bool(v) => Tagged::Bool(*v),
+            Value::I64(v) => Tagged::I64(*v),
+            Value::U64(v) => Tagged::U64(*v),
+            Value::F64(v) => Tagged::F64(*v),
+            Value::String(v) => Tagged::String(v),
+            Value::Bytes(v) => Tagged::Bytes(v),
+            Value::None => Tagged::None,
+            Value::Some(v) => Tagged::Some(v),
+            Value::Seq(v) => Tagged::Seq(v),
+            Value::Map(v) => Tagged::Map(v),
+        }
+    }
+}
+
+impl From<TaggedOwned> for Value {
+    fn from(value: TaggedOwned) -> Self {
+        match value {
+            TaggedOwned::Unit => Value::Unit,
+            TaggedOwned::Bool(v) => Value::Bool(v),
+            TaggedOwned::I64(v) => Value::I64(v),
+            TaggedOwned::U64(v) => Value::U64(v),
+            TaggedOwned::F64(v) => Value::F64(v),
+            TaggedOwned::String(v) => Value::String(v),
+            TaggedOwned::Bytes(v) => Value::Bytes(v),
+            TaggedOwned::None => Value::None,
+            TaggedOwned::Some(v) => Value::Some(v),
+            TaggedOwned::Seq(v) => Value::Seq(v),
+            TaggedOwned::Map(v) => Value::Map(v),
+        }
+    }
+}
+
+struct ValueVisitor;
+
+impl<'de> Visitor<'de> for ValueVisitor {
+    type Value = Value;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("any value")
+    }
+
+    fn visit_bool<E>(self, v: bool) -> Result<Value, E> {
+        Ok(Value::Bool(v))
+    }
+
+    fn visit_i64<E>(self, v: i64) -> Result<Value, E> {
+        Ok(Value::I64(v))
+    }
+
+    fn visit_u64<E>(self, v: u64) -> Result<Value, E> {
+        Ok(Value::U64(v))
+    }
+
+    fn visit_f64<E>(self, v: f64) -> Result<Value, E> {
+        Ok(Value::F64(v))
+    }
+
+    fn visit_str<E>(self, v: &str) -> Result<Value, E> {
+        Ok(Value::String(v.to_string()))
+    }
+
+    fn visit_string<E>(self, v: String) -> Result<Value, E> {
+        Ok(Value::String(v))
+    }
+
+    fn visit_bytes<E>(self, v: &[u8]) -> Result<Value, E> {
+        Ok(Value::Bytes(v.to_vec()))
+    }
+
+    fn visit_byte_buf<E>(self, v: Vec<u8>) -> Result<Value, E> {
+        Ok(Value::Bytes(v))
+    }
+
+    fn visit_none<E>(self) -> Result<Value, E> {
+        Ok(Value::None)
+    }
+
+    fn visit_some<D: Deserializer<'de>>(self, deserializer: D) -> Result<Value, D::Error> {
+        Value::deserialize(deserializer).map(|v| Value::Some(Box::new(v)))
+    }
+
+    fn visit_unit<E>(self) -> Result<Value, E> {
+        Ok(Value::Unit)
+    }
+
+    fn visit_newtype_struct<D: Deserializer<'de>>(
+        self,
+        deserializer: D,
+    ) -> Result<Value, D::Error> {
+        Value::deserialize(deserializer)
+    }
+
+    fn visit_seq<A: de::SeqAccess<'de>>(self, mut seq: A) -> Result<Value, A::Error> {
+        let mut items = Vec::new();
+        while let Some(item) = seq.next_element()? {
+            items.push(item);
+        }
+        Ok(Value::Seq(items))
+    }
+
+    fn visit_map<A: de::MapAccess<'de>>(self, mut map: A) -> Result<Value, A::Error> {
+        let mut entries = Vec::new();
+        while let Some(entry) = map.next_entry()? {
+            entries.push(entry);
+        }
+        Ok(Value::Map(entries))
+    }
+}
+
+/// Builds `Value`s, see `to_value`.
+struct ValueSerializer;
+
+impl Serializer for ValueSerializer {
+    type Ok = Value;
+    type Error = ValueError;
+    type SerializeSeq = SeqBuilder;
+    type SerializeTuple = SeqBuilder;
+    type SerializeTupleStruct = SeqBuilder;
+    type SerializeTupleVariant = VariantBuilder<SeqBuilder>;
+    type SerializeMap = MapBuilder;
+    type SerializeStruct = MapBuilder;
+    type SerializeStructVariant = VariantBuilder<MapBuilder>;
+
+    fn serialize_bool(self, v: bool) -> Result<Value, ValueError> {
+        Ok(Value::Bool(v))
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<Value, ValueError> {
+        Ok(Value::I64(v as i64))
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<Value, ValueError> {
+        Ok(Value::I64(v as i64))
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<Value, ValueError> {
+        Ok(Value::I64(v as i64))
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<Value, ValueError> {
+        Ok(Value::I64(v))
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<Value, ValueError> {
+        Ok(Value::U64(v as u64))
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<Value, ValueError> {
+        Ok(Value::U64(v as u64))
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<Value, ValueError> {
+        Ok(Value::U64(v as u64))
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<Value, ValueError> {
+        Ok(Value::U64(v))
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<Value, ValueError> {
+        Ok(Value::F64(v as f64))
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<Value, ValueError> {
+        Ok(Value::F64(v))
+    }
+
+    fn serialize_char(self, v: char) -> Result<Value, ValueError> {
+        Ok(Value::String(v.to_string()))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<Value, ValueError> {
+        Ok(Value::String(v.to_string()))
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<Value, ValueError> {
+        Ok(Value::Bytes(v.to_vec()))
+    }
+
+    fn serialize_none(self) -> Result<Value, ValueError> {
+        Ok(Value::None)
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<Value, ValueError> {
+        Ok(Value::Some(Box::new(to_value(value)?)))
+    }
+
+    fn serialize_unit(self) -> Result<Value, ValueError> {
+        Ok(Value::Unit)
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<Value, ValueError> {
+        Ok(Value::Unit)
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+    ) -> Result<Value, ValueError> {
+        Ok(Value::String(variant.to_string()))
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<Value, ValueError> {
+        to_value(value)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<Value, ValueError> {
+        Ok(Value::Map(vec![(
+            Value::String(variant.to_string()),
+            to_value(value)?,
+        )]))
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<SeqBuilder, ValueError> {
+        Ok(SeqBuilder(Vec::with_capacity(len.unwrap_or(0))))
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<SeqBuilder, ValueError> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<SeqBuilder, ValueError> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<VariantBuilder<SeqBuilder>, ValueError> {
+        Ok(VariantBuilder {
+            variant,
+            content: SeqBuilder(Vec::with_capacity(len)),
+        })
+    }
+
+    fn serialize_map(self, len: Option<usize>) -> Result<MapBuilder, ValueError> {
+        Ok(MapBuilder {
+            entries: Vec::with_capacity(len.unwrap_or(0)),
+            key: None,
+        })
+    }
+
+    fn serialize_struct(self, _name: &'static str, len: usize) -> Result<MapBuilder, ValueError> {
+        self.serialize_map(Some(len))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<VariantBuilder<MapBuilder>, ValueError> {
+        Ok(VariantBuilder {
+            variant,
+            content: self.serialize_map(Some(len))?,
+        })
+    }
+}
+
+struct SeqBuilder(Vec<Value>);
+
+impl ser::SerializeSeq for SeqBuilder {
+    type Ok = Value;
+    type Error = ValueError;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), ValueError> {
+        self.0.push(to_value(value)?);
+        Ok(())
+    }
+
+    fn end(self) -> Result<Value, ValueError> {
+        Ok(Value::Seq(self.0))
+    }
+}
+
+impl ser::SerializeTuple for SeqBuilder {
+    type Ok = Value;
+    type Error = ValueError;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), ValueError> {
+        ser::SerializeSeq::serialize_element(self, value)
+    }
+
+    fn end(self) -> Result<Value, ValueError> {
+        ser::SerializeSeq::end(self)
+    }
+}
+
+impl ser::SerializeTupleStruct for SeqBuilder {
+    type Ok = Value;
+    type Error = ValueError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), ValueError> {
+        ser::SerializeSeq::serialize_element(self, value)
+    }
+
+    fn end(self) -> Result<Value, ValueError> {
+        ser::SerializeSeq::end(self)
+    }
+}
+
+struct MapBuilder {
+    entries: Vec<(Value, Value)>,
+    key: Option<Value>,
+}
+
+impl ser::SerializeMap for MapBuilder {
+    type Ok = Value;
+    type Error = ValueError;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<(), ValueError> {
+        self.key = Some(to_value(key)?);
+        Ok(())
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), ValueError> {
+        let key = self
+            .key
+            .take()
+            .ok_or_else(|| ValueError("map value without a key".to_string()))?;
+        self.entries.push((key, to_value(value)?));
+        Ok(())
+    }
+
+    fn end(self) -> Result<Value, ValueError> {
+        Ok(Value::Map(self.entries))
+    }
+}
+
+impl ser::SerializeStruct for MapBuilder {
+    type Ok = Value;
+    type Error = ValueError;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), ValueError> {
+        self.entries
+            .push((Value::String(key.to_string()), to_value(value)?));
+        Ok(())
+    }
+
+    fn end(self) -> Result<Value, ValueError> {
+        Ok(Value::Map(self.entries))
+    }
+}
+
+/// A tuple or struct variant: its content wrapped in a one-entry map keyed by its name.
+struct VariantBuilder<C> {
+    variant: &'static str,
+    content: C,
+}
+
+impl<C> VariantBuilder<C> {
+    fn wrap(variant: &'static str, content: Value) -> Value {
+        Value::Map(vec![(Value::String(variant.to_string()), content)])
+    }
+}
+
+impl ser::SerializeTupleVariant for VariantBuilder<SeqBuilder> {
+    type Ok = Value;
+    type Error = ValueError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), ValueError> {
+        ser::SerializeSeq::serialize_element(&mut self.content, value)
+    }
+
+    fn end(self) -> Result<Value, ValueError> {
+        Ok(Self::wrap(self.variant, Value::Seq(self.content.0)))
+    }
+}
+
+impl ser::SerializeStructVariant for VariantBuilder<MapBuilder> {
+    type Ok = Value;
+    type Error = ValueError;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), ValueError> {
+        ser::SerializeStruct::serialize_field(&mut self.content, key, value)
+    }
+
+    fn end(self) -> Result<Value, ValueError> {
+        Ok(Self::wrap(self.variant, Value::Map(self.content.entries)))
+    }
+}
+
+impl<'de> IntoDeserializer<'de, ValueError> for Value {
+    type Deserializer = Value;
+
+    fn into_deserializer(self) -> Value {
+        self
+    }
+}
+
+impl<'de> Deserializer<'de> for Value {
+    type Error = ValueError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, ValueError> {
+        match self {
+            Value::Unit => visitor.visit_unit(),
+            Value::Bool(v) => visitor.visit_bool(v),
+            Value::I64(v) => visitor.visit_i64(v),
+            Value::U64(v) => visitor.visit_u64(v),
+            Value::F64(v) => visitor.visit_f64(v),
+            Value::String(v) => visitor.visit_string(v),
+            Value::Bytes(v) => visitor.visit_byte_buf(v),
+            Value::None => visitor.visit_none(),
+            Value::Some(v) => visitor.visit_some(*v),
+            Value::Seq(items) => {
+                let mut seq = SeqDeserializer::new(items.into_iter());
+                let value = visitor.visit_seq(&mut seq)?;
+                seq.end()?;
+                Ok(value)
+            }
+            Value::Map(entries) => {
+                let mut map = MapDeserializer::new(entries.into_iter());
+                let value = visitor.visit_map(&mut map)?;
+                map.end()?;
+                Ok(value)
+            }
+        }
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, ValueError> {
+        match self {
+            // Human-readable formats turn `None` into a unit and drop the `Some`
+            Value::None | Value::Unit => visitor.visit_none(),
+            Value::Some(v) => visitor.visit_some(*v),
+            other => visitor.visit_some(other),
+        }
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, ValueError> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, ValueError> {
+        match self {
+            Value::String(variant) => visitor.visit_enum(variant.into_deserializer()),
+            Value::Map(entries) if entries.len() == 1 => {
+                let (variant, content) = entries.into_iter().next().unwrap();
+                visitor.visit_enum(EnumValue { variant, content })
+            }
+            other => Err(ValueError(format!("expected an enum variant, found {:?}", other))),
+        }
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string bytes byte_buf
+        unit unit_struct seq tuple tuple_struct map struct identifier ignored_any
+    }
+}
+
+/// A non-unit enum variant being deserialized.
+struct EnumValue {
+    variant: Value,
+    content: Value,
+}
+
+impl<'de> de::EnumAccess<'de> for EnumValue {
+    type Error = ValueError;
+    type Variant = Value;
+
+    fn variant_seed<V: de::DeserializeSeed<'de>>(
+        self,
+        seed: V,
+    ) -> Result<(V::Value, Value), ValueError> {
+        Ok((seed.deserialize(self.variant)?, self.content))
+    }
+}
+
+impl<'de> de::VariantAccess<'de> for Value {
+    type Error = ValueError;
+
+    fn unit_variant(self) -> Result<(), ValueError> {
+        Ok(())
+    }
+
+    fn newtype_variant_seed<T: de::DeserializeSeed<'de>>(
+        self,
+        seed: T,
+    ) -> Result<T::Value, ValueError> {
+        seed.deserialize(self)
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(
+        self,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value, ValueError> {
+        self.deserialize_any(visitor)
+    }
+
+    fn struct_variant<V: Visitor<'de>>(
+        self,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, ValueError> {
+        self.deserialize_any(visitor)
+    }
+}
+
+#[cfg(test)]
+#[path = "export.tests.rs"]
+mod tests;
@@ -0,0 +1,137 @@
+use super::*;
+use crate::prelude::*;
+use serde::{Deserialize, Serialize};
+
+#[derive(Component, Default, Clone, Debug, PartialEq, Serialize, Deserialize)]
+struct Health {
+    hp: i32,
+}
+
+#[derive(Component, Default, Clone, Debug, PartialEq, Serialize, Deserialize)]
+enum Stance {
+    #[default]
+    Idle,
+    Moving(f32, f32),
+    Casting {
+        spell: String,
+        target: Option<Entity>,
+    },
+}
+
+#[derive(Component, Default, Clone, Debug, PartialEq)]
+struct Cooldown(u32);
+
+system! {
+    DamageSystem {
+        query! {
+            fn damage(health: &mut ViewMut<Health>) {
+                health.hp -= 1;
+            }
+        }
+    }
+}
+
+fn world() -> (World, Vec<Entity>) {
+    let mut world = World::new();
+    world.get_storage::<Health>();
+    world.get_storage::<Stance>();
+    world.get_storage::<Cooldown>();
+    world.add_system::<DamageSystem>();
+    world.build_scheduler();
+
+    let entities: Vec<Entity> = (0..3).map(|_| world.spawn()).collect();
+    for (i, &e) in entities.iter().enumerate() {
+        world.set(e, &Health { hp: 10 * i as i32 });
+        world.set(e, &Cooldown(i as u32));
+    }
+    world.set(entities[1], &Stance::Moving(1.0, -2.5));
+    let spell = Stance::Casting {
+        spell: "bolt".to_string(),
+        target: Some(entities[0]),
+    };
+    world.set(entities[2], &spell);
+    (world, entities)
+}
+
+#[test]
+fn test_values_round_trip() {
+    let stances = vec![
+        Stance::Idle,
+        Stance::Moving(0.5, 3.0),
+        Stance::Casting {
+            spell: "heal".to_string(),
+            target: None,
+        },
+    ];
+    let value = to_value(&stances).unwrap();
+    assert_eq!(from_value::<Vec<Stance>>(value.clone()).unwrap(), stances);
+
+    // Through a human-readable format, which loses the option and integer kinds
+    let json = serde_json::to_string(&value).unwrap();
+    let reread: Value = serde_json::from_str(&json).unwrap();
+    assert_eq!(from_value::<Vec<Stance>>(reread).unwrap(), stances);
+    assert!(from_value::<Health>(Value::Bool(true)).is_err());
+}
+
+#[test]
+fn test_derive_detects_serde() {
+    assert!(Health::serde_fns().is_some());
+    assert!(Entity::serde_fns().is_some());
+    assert!(Cooldown::serde_fns().is_none());
+}
+
+#[test]
+fn test_json_export_loads_into_fresh_world() {
+    let (mut world, entities) = world();
+    world.run();
+    world.destroy(entities[0]);
+    world.run();
+
+    let json = serde_json::to_string(&world.export_snapshot().unwrap()).unwrap();
+    let snapshot: Snapshot = serde_json::from_str(&json).unwrap();
+    assert_eq!(snapshot.tick, world.current_tick());
+    assert!(!snapshot.is_delta());
+    assert!(!snapshot.components.contains_key(std::any::type_name::<Cooldown>()));
+
+    let mut copy = World::new();
+    copy.import_snapshot(&snapshot).unwrap();
+    assert_eq!(copy.iter_entities().collect::<Vec<_>>(), entities[1..]);
+    for &e in &entities[1..] {
+        assert_eq!(copy.get::<Health>(e), world.get::<Health>(e));
+        assert_eq!(copy.get::<Stance>(e), world.get::<Stance>(e));
+        assert_eq!(copy.get::<Cooldown>(e), None);
+    }
+}
+
+#[test]
+fn test_delta_replays_tick() {
+    let (mut replica, _) = world();
+    let (mut world, entities) = world();
+    world.run();
+    replica.run();
+    let before = world.export_snapshot().unwrap();
+
+    world.destroy(entities[2]);
+    world.set(entities[1], &Stance::Idle);
+    world.run();
+    let spawned = world.spawn();
+    world.set(spawned, &Health { hp: 99 });
+    let after = world.export_snapshot().unwrap();
+
+    let delta = after.delta(&before);
+    assert_eq!(delta.base, Some(before.tick));
+    let moved = &delta.components[std::any::type_name::<Stance>()];
+    assert_eq!(moved.removed, vec![entities[2].index()]);
+
+    let json = serde_json::to_string(&delta).unwrap();
+    replica.import_snapshot(&serde_json::from_str(&json).unwrap()).unwrap();
+    assert_eq!(replica.export_snapshot().unwrap().components, after.components);
+    assert!(replica.contains(spawned));
+    assert!(!replica.contains(entities[2]));
+    assert_eq!(replica.get::<Cooldown>(entities[0]), Some(&Cooldown(0)));
+
+    // Importing is an edit the replica can roll back
+    replica.run();
+    replica.rollback(Tick::new(before.tick.value() - 1));
+    assert_eq!(replica.export_snapshot().unwrap().components, before.components);
+}
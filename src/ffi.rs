@@ -0,0 +1,440 @@
+//! C ABI for embedding the simulation in a non-Rust engine (`ffi` feature).
+//!
+//! The game's Rust crate builds its `World` (components, systems, scheduler), picks the
+//! components the engine may touch with `FfiWorld::expose`, and hands the engine a raw
+//! `FfiWorld` pointer. Built as a `cdylib` or `staticlib`, that crate then exports the
+//! functions below; components cross the boundary in their `Wire` encoding.
+//!
+//! ```c
+//! typedef struct RbecsWorld RbecsWorld;
+//! typedef void (*RbecsChangeCallback)(void *userdata, uint32_t entity,
+//!                                     const uint8_t *data, size_t len);
+//!
+//! uint64_t rbecs_component_id(const RbecsWorld *world, const char *name);
+//! uint32_t rbecs_spawn(RbecsWorld *world);
+//! int32_t  rbecs_set(RbecsWorld *world, uint32_t entity, uint64_t component,
+//!                    const uint8_t *data, size_t len);
+//! int32_t  rbecs_get(const RbecsWorld *world, uint32_t entity, uint64_t component,
+//!                    uint8_t *out, size_t cap, size_t *out_len);
+//! void     rbecs_advance_tick(RbecsWorld *world);
+//! uint32_t rbecs_world_subscribe(RbecsWorld *world, uint64_t component,
+//!                                RbecsChangeCallback callback, void *userdata);
+//! void     rbecs_world_unsubscribe(RbecsWorld *world, uint32_t subscription);
+//! void     rbecs_world_free(RbecsWorld *world);
+//! ```
+//!
+//! Entities are passed as `Entity::to_bits`. Functions returning `int32_t` return an
+//! `RbecsStatus`. A subscription's callback runs at the end of every `rbecs_advance_tick`,
+//! once per entity whose component was written since the previous call, with the new
+//! encoding; removals are reported with `data == NULL` and `len == 0`. The first call
+//! reports every entity that has the component. Callbacks must not call back into the
+//! world.
+//!
+//! Only the slots in the storage's changed and removed masks of the tick are encoded, so
+//! a tick costs what it touched rather than what the world holds. When more than that
+//! tick passed since the previous call, as after `world_mut().run()` or a rollback, every
+//! entity is reported again; the ticks resimulated after a rollback may also repeat
+//! values written in the ticks it undid. Loading state doesn't move the tick: call
+//! `FfiWorld::resync` after restoring state through `FfiWorld::world_mut` otherwise.
+//!
+//! # Example
+//! ```ignore
+//! #[unsafe(no_mangle)]
+//! pub extern "C" fn game_create() -> *mut FfiWorld {
+//!     let mut world = build_game_world();
+//!     world.build_scheduler();
+//!     let mut ffi = FfiWorld::new(world);
+//!     ffi.expose::<Position>("Position");
+//!     ffi.into_raw()
+//! }
+//! ```
+
+use crate::component::Component;
+use crate::entity::Entity;
+use crate::storage::ComponentStorage;
+use crate::tick::{Tick, TickDelta};
+use crate::wire::{DecodeError, Reader, Wire, component_id};
+use crate::world::World;
+use std::collections::BTreeMap;
+use std::ffi::{CStr, c_char, c_void};
+use std::marker::PhantomData;
+
+/// Result of the fallible FFI calls.
+#[repr(i32)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum RbecsStatus {
+    Ok = 0,
+    /// The component id was never passed to `FfiWorld::expose`.
+    UnknownComponent = 1,
+    /// The entity doesn't exist, or its generation is stale.
+    DeadEntity = 2,
+    /// The bytes aren't a valid `Wire` encoding of the component.
+    InvalidData = 3,
+    /// The entity doesn't have the component.
+    Missing = 4,
+    /// `out` is too small; `*out_len` holds the required size.
+    BufferTooSmall = 5,
+}
+
+/// Called with `(userdata, entity, data, len)`, see the module docs.
+pub type RbecsChangeCallback =
+    extern "C" fn(userdata: *mut c_void, entity: u32, data: *const u8, len: usize);
+
+/// Type-erased access to one exposed component.
+trait FfiComponent {
+    fn set(&self, world: &mut World, entity: Entity, bytes: &[u8]) -> Result<(), DecodeError>;
+
+    /// Appends the encoding of the component at `index` to `out`, if present.
+    fn get(&self, world: &World, index: u32, out: &mut Vec<u8>) -> bool;
+
+    /// Calls `f` in index order with the encoding of every component written or removed
+    /// in the tick after `since`, the previous one, or of every component if there is
+    /// none. Emptied slots are passed `None`.
+    fn encode_changed(
+        &self,
+        world: &World,
+        since: Option<Tick>,
+        f: &mut dyn FnMut(u32, Option<&[u8]>),
+    );
+}
+
+struct Exposed<T>(PhantomData<fn() -> T>);
+
+impl<T: Component + Clone + Wire> FfiComponent for Exposed<T> {
+    fn set(&self, world: &mut World, entity: Entity, bytes: &[u8]) -> Result<(), DecodeError> {
+        let mut reader = Reader::new(bytes);
+        let value = T::decode(&mut reader)?;
+        if reader.remaining() != 0 {
+            return Err(DecodeError::TrailingBytes(reader.remaining()));
+        }
+        world.set(entity, &value);
+        Ok(())
+    }
+
+    fn get(&self, world: &World, index: u32, out: &mut Vec<u8>) -> bool {
+        match world.storage_ref::<T>().and_then(|s| s.get(index)) {
+            Some(value) => {
+                value.encode(out);
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn encode_changed(
+        &self,
+        world: &World,
+        since: Option<Tick>,
+        f: &mut dyn FnMut(u32, Option<&[u8]>),
+    ) {
+        let Some(storage) = world.storage_ref::<T>() else {
+            return;
+        };
+        let mut buffer = Vec::new();
+        let Some(since) = since else {
+            storage.visit(|index, value| {
+                buffer.clear();
+                value.encode(&mut buffer);
+                f(index, Some(&buffer));
+            });
+            return;
+        };
+
+        let mut root = storage.root_changed_since(since) | storage.root_removed_mask();
+        while root != 0 {
+            let ri = root.trailing_zeros();
+            root &= !(1u128 << ri);
+            let mut middle =
+                storage.middle_changed_since(ri, since) | storage.middle_removed_mask(ri);
+            while middle != 0 {
+                let mi = middle.trailing_zeros();
+                middle &= !(1u128 << mi);
+                let mut inner =
+                    storage.inner_changed_since(ri, mi, since) | storage.inner_removed_mask(ri, mi);
+                while inner != 0 {
+                    let ii = inner.trailing_zeros();
+                    inner &= !(1u128 << ii);
+                    let index = ri * 16384 + mi * 128 + ii;
+                    match storage.get(index) {
+                        Some(value) => {
+                            buffer.clear();
+                            value.encode(&mut buffer);
+                            f(index, Some(&buffer));
+                        }
+                        None => f(index, None),
+                    }
+                }
+            }
+        }
+    }
+}
+
+struct Subscription {
+    id: u32,
+    component: u64,
+    callback: RbecsChangeCallback,
+    userdata: *mut c_void,
+    /// Entities the callback was last told have the component, by index.
+    seen: BTreeMap<u32, Entity>,
+    /// Tick whose writes were the last reported, or `None` to report everything.
+    reported: Option<Tick>,
+}
+
+/// A world driven through the C ABI, see the module docs.
+pub struct FfiWorld {
+    world: World,
+    components: BTreeMap<u64, Box<dyn FfiComponent>>,
+    names: Vec<(String, u64)>,
+    subscriptions: Vec<Subscription>,
+    next_subscription: u32,
+}
+
+impl FfiWorld {
+    /// Wraps a world whose scheduler is already built.
+    pub fn new(world: World) -> Self {
+        FfiWorld {
+            world,
+            components: BTreeMap::new(),
+            names: Vec::new(),
+            subscriptions: Vec::new(),
+            next_subscription: 1,
+        }
+    }
+
+    /// Makes `T` accessible from C under `name` (see `rbecs_component_id`). The id is
+    /// `wire::component_id::<T>()`, so it matches the ids in wire packets.
+    pub fn expose<T: Component + Clone + Wire>(&mut self, name: &str) -> u64 {
+        let id = component_id::<T>();
+        self.components
+            .insert(id, Box::new(Exposed::<T>(PhantomData)));
+        self.names.retain(|(n, _)| n != name);
+        self.names.push((name.to_string(), id));
+        id
+    }
+
+    pub fn world(&self) -> &World {
+        &self.world
+    }
+
+    pub fn world_mut(&mut self) -> &mut World {
+        &mut self.world
+    }
+
+    /// Moves the world to the heap for the engine. Free it with `rbecs_world_free`.
+    pub fn into_raw(self) -> *mut FfiWorld {
+        Box::into_raw(Box::new(self))
+    }
+
+    /// Makes the next `rbecs_advance_tick` report every entity to every subscription, for
+    /// after restoring state through `world_mut`, see the module docs.
+    pub fn resync(&mut self) {
+        for sub in &mut self.subscriptions {
+            sub.reported = None;
+        }
+    }
+
+    /// Reports every subscribed component written since the last call.
+    fn notify(&mut self) {
+        let world = &self.world;
+        let entities = world.storage_ref::<Entity>();
+        let last = world.current_tick() - TickDelta::new(1);
+
+        for sub in &mut self.subscriptions {
+            let Some(component) = self.components.get(&sub.component) else {
+                continue;
+            };
+            let (callback, userdata) = (sub.callback, sub.userdata);
+
+            // Unless only `last` ran since the previous report, start over, telling the
+            // entities no longer there at the end
+            let since = sub
+                .reported
+                .filter(|&reported| reported + TickDelta::new(1) == last);
+            let mut stale = match since {
+                Some(_) => BTreeMap::new(),
+                None => std::mem::take(&mut sub.seen),
+            };
+
+            let seen = &mut sub.seen;
+            component.encode_changed(world, since, &mut |index, bytes| {
+                let entity = bytes.and(entities.and_then(|e| e.get(index)));
+                let old = seen.remove(&index).or_else(|| stale.remove(&index));
+                if let Some(old) = old.filter(|old| Some(old) != entity) {
+                    callback(userdata, old.to_bits(), std::ptr::null(), 0);
+                }
+                if let (Some(entity), Some(bytes)) = (entity, bytes) {
+                    callback(userdata, entity.to_bits(), bytes.as_ptr(), bytes.len());
+                    seen.insert(index, *entity);
+                }
+            });
+            for entity in stale.values() {
+                callback(userdata, entity.to_bits(), std::ptr::null(), 0);
+            }
+
+            sub.reported = Some(last);
+        }
+    }
+}
+
+/// Id of the component exposed under `name`, or 0 if there is none.
+///
+/// # Safety
+/// `world` must come from `FfiWorld::into_raw` and `name` must be a NUL-terminated string.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn rbecs_component_id(world: *const FfiWorld, name: *const c_char) -> u64 {
+    let world = unsafe { &*world };
+    let name = unsafe { CStr::from_ptr(name) };
+    let name = name.to_str().unwrap_or_default();
+    world
+        .names
+        .iter()
+        .find(|(n, _)| n == name)
+        .map_or(0, |(_, id)| *id)
+}
+
+/// Spawns an entity.
+///
+/// # Safety
+/// `world` must come from `FfiWorld::into_raw`.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn rbecs_spawn(world: *mut FfiWorld) -> u32 {
+    let world = unsafe { &mut *world };
+    world.world.spawn().to_bits()
+}
+
+/// Sets `component` on `entity` from its `Wire` encoding in `data[..len]`.
+///
+/// # Safety
+/// `world` must come from `FfiWorld::into_raw` and `data` must be valid for `len` bytes.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn rbecs_set(
+    world: *mut FfiWorld,
+    entity: u32,
+    component: u64,
+    data: *const u8,
+    len: usize,
+) -> RbecsStatus {
+    let world = unsafe { &mut *world };
+    let entity = Entity::from_bits(entity);
+    let Some(exposed) = world.components.get(&component) else {
+        return RbecsStatus::UnknownComponent;
+    };
+    if !world.world.contains(entity) {
+        return RbecsStatus::DeadEntity;
+    }
+
+    let bytes = if len == 0 {
+        &[][..]
+    } else {
+        unsafe { std::slice::from_raw_parts(data, len) }
+    };
+    match exposed.set(&mut world.world, entity, bytes) {
+        Ok(()) => RbecsStatus::Ok,
+        Err(_) => RbecsStatus::InvalidData,
+    }
+}
+
+/// Writes the `Wire` encoding of `component` on `entity` to `out[..cap]` and its length
+/// to `*out_len`. If `cap` is too small nothing is written to `out`, `*out_len` still
+/// receives the required size and `BufferTooSmall` is returned.
+///
+/// # Safety
+/// `world` must come from `FfiWorld::into_raw`, `out` must be valid for `cap` bytes and
+/// `out_len` must be writable.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn rbecs_get(
+    world: *const FfiWorld,
+    entity: u32,
+    component: u64,
+    out: *mut u8,
+    cap: usize,
+    out_len: *mut usize,
+) -> RbecsStatus {
+    let world = unsafe { &*world };
+    let entity = Entity::from_bits(entity);
+    let Some(exposed) = world.components.get(&component) else {
+        return RbecsStatus::UnknownComponent;
+    };
+    if !world.world.contains(entity) {
+        return RbecsStatus::DeadEntity;
+    }
+
+    let mut bytes = Vec::new();
+    if !exposed.get(&world.world, entity.index(), &mut bytes) {
+        return RbecsStatus::Missing;
+    }
+
+    unsafe { *out_len = bytes.len() };
+    if bytes.len() > cap {
+        return RbecsStatus::BufferTooSmall;
+    }
+    unsafe { std::ptr::copy_nonoverlapping(bytes.as_ptr(), out, bytes.len()) };
+    RbecsStatus::Ok
+}
+
+/// Simulates one tick with `World::run`, then runs the subscription callbacks.
+///
+/// # Safety
+/// `world` must come from `FfiWorld::into_raw`.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn rbecs_advance_tick(world: *mut FfiWorld) {
+    let world = unsafe { &mut *world };
+    world.world.run();
+    world.notify();
+}
+
+/// Calls `callback` with `userdata` whenever `component` changes, see the module docs.
+/// Returns the subscription id, or 0 if the component isn't exposed.
+///
+/// # Safety
+/// `world` must come from `FfiWorld::into_raw`, and `userdata` must stay valid for
+/// `callback` until the subscription is removed.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn rbecs_world_subscribe(
+    world: *mut FfiWorld,
+    component: u64,
+    callback: RbecsChangeCallback,
+    userdata: *mut c_void,
+) -> u32 {
+    let world = unsafe { &mut *world };
+    if !world.components.contains_key(&component) {
+        return 0;
+    }
+
+    let id = world.next_subscription;
+    world.next_subscription += 1;
+    world.subscriptions.push(Subscription {
+        id,
+        component,
+        callback,
+        userdata,
+        seen: BTreeMap::new(),
+        reported: None,
+    });
+    id
+}
+
+/// Removes a subscription. Unknown ids are ignored.
+///
+/// # Safety
+/// `world` must come from `FfiWorld::into_raw`.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn rbecs_world_unsubscribe(world: *mut FfiWorld, subscription: u32) {
+    let world = unsafe { &mut *world };
+    world.subscriptions.retain(|sub| sub.id != subscription);
+}
+
+/// Drops a world created by `FfiWorld::into_raw`. Null is ignored.
+///
+/// # Safety
+/// `world` must come from `FfiWorld::into_raw` and must not be used afterwards.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn rbecs_world_free(world: *mut FfiWorld) {
+    if !world.is_null() {
+        drop(unsafe { Box::from_raw(world) });
+    }
+}
+
+#[cfg(test)]
+#[path = "ffi.tests.rs"]
+mod tests;
@@ -0,0 +1,191 @@
+use super::*;
+use crate::prelude::system;
+
+#[derive(Component, Default, Clone, Debug, PartialEq)]
+struct Charge {
+    value: i32,
+}
+
+impl Wire for Charge {
+    fn encode(&self, out: &mut Vec<u8>) {
+        self.value.encode(out);
+    }
+
+    fn decode(reader: &mut Reader<'_>) -> Result<Self, DecodeError> {
+        Ok(Charge {
+            value: i32::decode(reader)?,
+        })
+    }
+}
+
+system! {
+    ChargeSystem {
+        query! {
+            fn charge(charge: &mut ViewMut<Charge>) {
+                if charge.value < 3 {
+                    charge.value += 1;
+                }
+            }
+        }
+    }
+}
+
+fn ffi_world() -> *mut FfiWorld {
+    let mut world = World::new();
+    world.add_system::<ChargeSystem>();
+    world.build_scheduler();
+    let mut ffi = FfiWorld::new(world);
+    ffi.expose::<Charge>("Charge");
+    ffi.into_raw()
+}
+
+fn encode(value: i32) -> Vec<u8> {
+    let mut out = Vec::new();
+    Charge { value }.encode(&mut out);
+    out
+}
+
+type Calls = Vec<(u32, Option<Vec<u8>>)>;
+
+extern "C" fn record(userdata: *mut c_void, entity: u32, data: *const u8, len: usize) {
+    let calls = unsafe { &mut *(userdata as *mut Calls) };
+    let bytes =
+        (!data.is_null()).then(|| unsafe { std::slice::from_raw_parts(data, len) }.to_vec());
+    calls.push((entity, bytes));
+}
+
+#[test]
+fn test_ffi_set_and_get_round_trip() {
+    let world = ffi_world();
+    unsafe {
+        let id = rbecs_component_id(world, c"Charge".as_ptr());
+        assert_eq!(id, component_id::<Charge>());
+        assert_eq!(rbecs_component_id(world, c"Missing".as_ptr()), 0);
+
+        let e = rbecs_spawn(world);
+        let bytes = encode(7);
+        assert_eq!(
+            rbecs_set(world, e, id, bytes.as_ptr(), bytes.len()),
+            RbecsStatus::Ok
+        );
+
+        let mut out = [0u8; 16];
+        let mut len = 0;
+        assert_eq!(
+            rbecs_get(world, e, id, out.as_mut_ptr(), out.len(), &mut len),
+            RbecsStatus::Ok
+        );
+        assert_eq!(&out[..len], &bytes[..]);
+
+        assert_eq!(
+            rbecs_get(world, e, id, out.as_mut_ptr(), 1, &mut len),
+            RbecsStatus::BufferTooSmall
+        );
+        assert_eq!(len, bytes.len());
+        assert_eq!(
+            rbecs_set(world, e, 42, bytes.as_ptr(), bytes.len()),
+            RbecsStatus::UnknownComponent
+        );
+        assert_eq!(
+            rbecs_set(world, e, id, bytes.as_ptr(), 2),
+            RbecsStatus::InvalidData
+        );
+
+        let other = rbecs_spawn(world);
+        assert_eq!(
+            rbecs_get(world, other, id, out.as_mut_ptr(), out.len(), &mut len),
+            RbecsStatus::Missing
+        );
+        let stale = Entity::new(Entity::from_bits(e).index(), 5).to_bits();
+        assert_eq!(
+            rbecs_set(world, stale, id, bytes.as_ptr(), bytes.len()),
+            RbecsStatus::DeadEntity
+        );
+
+        rbecs_world_free(world);
+    }
+}
+
+#[test]
+fn test_ffi_subscription_reports_changes_after_each_tick() {
+    let world = ffi_world();
+    let mut calls: Calls = Vec::new();
+    let userdata = &mut calls as *mut Calls as *mut c_void;
+
+    unsafe {
+        let id = rbecs_component_id(world, c"Charge".as_ptr());
+        let a = rbecs_spawn(world);
+        let b = rbecs_spawn(world);
+        let bytes = encode(1);
+        rbecs_set(world, a, id, bytes.as_ptr(), bytes.len());
+        let sub = rbecs_world_subscribe(world, id, record, userdata);
+        assert_ne!(sub, 0);
+        assert_eq!(rbecs_world_subscribe(world, 42, record, userdata), 0);
+
+        // First tick reports everything, later ticks only what changed
+        rbecs_advance_tick(world);
+        assert_eq!(calls, vec![(a, Some(encode(2)))]);
+        calls.clear();
+
+        let bytes = encode(3);
+        rbecs_set(world, b, id, bytes.as_ptr(), bytes.len());
+        rbecs_advance_tick(world);
+        assert_eq!(calls, vec![(a, Some(encode(3))), (b, Some(encode(3)))]);
+        calls.clear();
+
+        rbecs_advance_tick(world);
+        assert!(calls.is_empty());
+
+        (*world).world_mut().destroy(Entity::from_bits(a));
+        rbecs_advance_tick(world);
+        rbecs_advance_tick(world);
+        assert_eq!(calls, vec![(a, None)]);
+        calls.clear();
+
+        rbecs_world_unsubscribe(world, sub);
+        rbecs_set(world, b, id, encode(0).as_ptr(), 4);
+        rbecs_advance_tick(world);
+        assert!(calls.is_empty());
+
+        rbecs_world_free(world);
+    }
+}
+
+#[test]
+fn test_ffi_subscription_reports_everything_after_restores() {
+    let world = ffi_world();
+    let mut calls: Calls = Vec::new();
+    let userdata = &mut calls as *mut Calls as *mut c_void;
+
+    unsafe {
+        let id = rbecs_component_id(world, c"Charge".as_ptr());
+        let a = rbecs_spawn(world);
+        rbecs_set(world, a, id, encode(3).as_ptr(), 4);
+        rbecs_world_subscribe(world, id, record, userdata);
+        rbecs_advance_tick(world);
+        let reported = (*world).world().current_tick() - TickDelta::new(1);
+        let b = rbecs_spawn(world);
+        rbecs_set(world, a, id, encode(5).as_ptr(), 4);
+        rbecs_set(world, b, id, encode(3).as_ptr(), 4);
+        rbecs_advance_tick(world);
+        calls.clear();
+
+        // Restores aren't writes; the rollback moved the tick, so everything is reported
+        (*world).world_mut().rollback(reported);
+        rbecs_advance_tick(world);
+        assert_eq!(calls, vec![(a, Some(encode(3))), (b, None)]);
+        calls.clear();
+
+        // So does a resync, once the resimulated ticks have caught up
+        rbecs_advance_tick(world);
+        rbecs_advance_tick(world);
+        calls.clear();
+        rbecs_advance_tick(world);
+        assert!(calls.is_empty());
+        (*world).resync();
+        rbecs_advance_tick(world);
+        assert_eq!(calls, vec![(a, Some(encode(3)))]);
+
+        rbecs_world_free(world);
+    }
+}
@@ -0,0 +1,381 @@
+//! Fixed-point numbers with explicit widths, for component fields that must give the same
+//! bits on every peer.
+//!
+//! `Fx32` is Q16.16 and `Fx64` is Q32.32, each with `Vec2`/`Vec3` counterparts. `Fx32` and
+//! its vectors are the `det_math` types under names that say their width, so both kinds can
+//! be imported side by side; `Fx64` is for worlds larger than the ±32768 that `Fx32` covers,
+//! or for accumulators that need more fractional bits. `Fx64` covers roughly ±2^31 with a
+//! resolution of 1/2^32, uses 128-bit intermediates and wraps on overflow like the rest of
+//! `det_math`. Angles stay `Rot`: `sin`/`cos` come from the same table, widened, and
+//! `Fx64Vec2::angle` runs the same CORDIC on the rescaled vector. Converting from `Fx32` is
+//! exact; `Fx64::narrow` drops the extra bits.
+//!
+//! # Example
+//! ```ignore
+//! use rollback_ecs::fixed::{Fx64, Fx64Vec2, Rot};
+//!
+//! #[derive(Component, Clone, Default)]
+//! struct Orbit { position: Fx64Vec2 }
+//!
+//! let far = Fx64Vec2::new(Fx64::from_int(1_000_000), Fx64::ZERO);
+//! let moved = far + Fx64Vec2::X.rotate(Rot::from_degrees(45)) * Fx64::from_ratio(1, 60);
+//! ```
+
+use crate::det_math;
+pub use crate::det_math::{Fixed as Fx32, Rot, Vec2 as Fx32Vec2, Vec3 as Fx32Vec3};
+use std::ops::{Add, AddAssign, Div, Mul, Neg, Sub, SubAssign};
+
+/// Fractional bits of `Fx64`.
+pub const FX64_FRAC_BITS: u32 = 32;
+
+/// A Q32.32 fixed-point number.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Default)]
+pub struct Fx64(i64);
+
+impl Fx64 {
+    pub const ZERO: Fx64 = Fx64(0);
+    pub const ONE: Fx64 = Fx64(1 << FX64_FRAC_BITS);
+    pub const HALF: Fx64 = Fx64(1 << (FX64_FRAC_BITS - 1));
+    pub const MAX: Fx64 = Fx64(i64::MAX);
+    pub const MIN: Fx64 = Fx64(i64::MIN);
+
+    pub const fn from_raw(raw: i64) -> Self {
+        Fx64(raw)
+    }
+
+    pub const fn raw(self) -> i64 {
+        self.0
+    }
+
+    pub const fn from_int(value: i64) -> Self {
+        Fx64(value.wrapping_shl(FX64_FRAC_BITS))
+    }
+
+    /// `num / den`, rounded toward zero.
+    ///
+    /// # Panics
+    /// Panics if `den` is zero.
+    pub const fn from_ratio(num: i64, den: i64) -> Self {
+        Fx64((((num as i128) << FX64_FRAC_BITS) / den as i128) as i64)
+    }
+
+    /// The integer part, rounded toward negative infinity.
+    pub const fn floor_int(self) -> i64 {
+        self.0 >> FX64_FRAC_BITS
+    }
+
+    /// The Q16.16 value, dropping the low fractional bits and wrapping outside its range.
+    pub const fn narrow(self) -> Fx32 {
+        Fx32::from_raw((self.0 >> (FX64_FRAC_BITS - det_math::FRAC_BITS)) as i32)
+    }
+
+    /// For presentation only; never feed the result back into the simulation.
+    pub fn to_f64(self) -> f64 {
+        self.0 as f64 / (1u64 << FX64_FRAC_BITS) as f64
+    }
+
+    pub const fn abs(self) -> Self {
+        Fx64(self.0.wrapping_abs())
+    }
+
+    /// Square root. Negative values have no root and return zero.
+    pub fn sqrt(self) -> Self {
+        if self.0 <= 0 {
+            return Fx64::ZERO;
+        }
+        Fx64(((self.0 as u128) << FX64_FRAC_BITS).isqrt() as i64)
+    }
+}
+
+impl From<Fx32> for Fx64 {
+    /// Widens a Q16.16 value, exactly.
+    fn from(value: Fx32) -> Fx64 {
+        Fx64((value.raw() as i64) << (FX64_FRAC_BITS - det_math::FRAC_BITS))
+    }
+}
+
+impl Add for Fx64 {
+    type Output = Fx64;
+
+    fn add(self, rhs: Fx64) -> Fx64 {
+        Fx64(self.0.wrapping_add(rhs.0))
+    }
+}
+
+impl Sub for Fx64 {
+    type Output = Fx64;
+
+    fn sub(self, rhs: Fx64) -> Fx64 {
+        Fx64(self.0.wrapping_sub(rhs.0))
+    }
+}
+
+impl Mul for Fx64 {
+    type Output = Fx64;
+
+    fn mul(self, rhs: Fx64) -> Fx64 {
+        Fx64(((self.0 as i128 * rhs.0 as i128) >> FX64_FRAC_BITS) as i64)
+    }
+}
+
+impl Div for Fx64 {
+    type Output = Fx64;
+
+    /// # Panics
+    /// Panics if `rhs` is zero.
+    fn div(self, rhs: Fx64) -> Fx64 {
+        Fx64((((self.0 as i128) << FX64_FRAC_BITS) / rhs.0 as i128) as i64)
+    }
+}
+
+impl Neg for Fx64 {
+    type Output = Fx64;
+
+    fn neg(self) -> Fx64 {
+        Fx64(self.0.wrapping_neg())
+    }
+}
+
+impl AddAssign for Fx64 {
+    fn add_assign(&mut self, rhs: Fx64) {
+        *self = *self + rhs;
+    }
+}
+
+impl SubAssign for Fx64 {
+    fn sub_assign(&mut self, rhs: Fx64) {
+        *self = *self - rhs;
+    }
+}
+
+/// Square root of a sum of squared raw values, as a `Fx64`. Saturates at `Fx64::MAX`.
+fn root_of_squares(squares: u128) -> Fx64 {
+    Fx64(squares.isqrt().min(i64::MAX as u128) as i64)
+}
+
+/// `value / len` for a vector component, with `len` nonzero.
+fn scale_down(value: Fx64, len: Fx64) -> Fx64 {
+    Fx64((((value.0 as i128) << FX64_FRAC_BITS) / len.0 as i128) as i64)
+}
+
+/// `sin` and `cos` of `rot`, widened.
+fn sin_cos(rot: Rot) -> (Fx64, Fx64) {
+    (Fx64::from(rot.sin()), Fx64::from(rot.cos()))
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Default)]
+pub struct Fx64Vec2 {
+    pub x: Fx64,
+    pub y: Fx64,
+}
+
+impl Fx64Vec2 {
+    pub const ZERO: Fx64Vec2 = Fx64Vec2::new(Fx64::ZERO, Fx64::ZERO);
+    pub const X: Fx64Vec2 = Fx64Vec2::new(Fx64::ONE, Fx64::ZERO);
+    pub const Y: Fx64Vec2 = Fx64Vec2::new(Fx64::ZERO, Fx64::ONE);
+
+    pub const fn new(x: Fx64, y: Fx64) -> Self {
+        Fx64Vec2 { x, y }
+    }
+
+    pub fn dot(self, rhs: Fx64Vec2) -> Fx64 {
+        self.x * rhs.x + self.y * rhs.y
+    }
+
+    /// The z component of the 3D cross product, positive if `rhs` is counterclockwise.
+    pub fn cross(self, rhs: Fx64Vec2) -> Fx64 {
+        self.x * rhs.y - self.y * rhs.x
+    }
+
+    pub fn length(self) -> Fx64 {
+        let (x, y) = (self.x.0 as i128, self.y.0 as i128);
+        root_of_squares((x * x) as u128 + (y * y) as u128)
+    }
+
+    /// The vector scaled to length one, or zero for the zero vector.
+    pub fn normalize(self) -> Fx64Vec2 {
+        let len = self.length();
+        if len == Fx64::ZERO {
+            return Fx64Vec2::ZERO;
+        }
+        Fx64Vec2::new(scale_down(self.x, len), scale_down(self.y, len))
+    }
+
+    /// Direction of the vector, see `Rot::atan2`.
+    pub fn angle(self) -> Rot {
+        // Drop low bits until both components fit a Q16.16 raw value, keeping the ratio
+        let largest = self.x.0.unsigned_abs().max(self.y.0.unsigned_abs());
+        let shift = (64 - largest.leading_zeros()).saturating_sub(31);
+        Rot::atan2(
+            Fx32::from_raw((self.y.0 >> shift) as i32),
+            Fx32::from_raw((self.x.0 >> shift) as i32),
+        )
+    }
+
+    /// The vector rotated counterclockwise by `rot`.
+    pub fn rotate(self, rot: Rot) -> Fx64Vec2 {
+        let (sin, cos) = sin_cos(rot);
+        Fx64Vec2::new(self.x * cos - self.y * sin, self.x * sin + self.y * cos)
+    }
+}
+
+impl From<Fx32Vec2> for Fx64Vec2 {
+    fn from(v: Fx32Vec2) -> Fx64Vec2 {
+        Fx64Vec2::new(v.x.into(), v.y.into())
+    }
+}
+
+impl Add for Fx64Vec2 {
+    type Output = Fx64Vec2;
+
+    fn add(self, rhs: Fx64Vec2) -> Fx64Vec2 {
+        Fx64Vec2::new(self.x + rhs.x, self.y + rhs.y)
+    }
+}
+
+impl Sub for Fx64Vec2 {
+    type Output = Fx64Vec2;
+
+    fn sub(self, rhs: Fx64Vec2) -> Fx64Vec2 {
+        Fx64Vec2::new(self.x - rhs.x, self.y - rhs.y)
+    }
+}
+
+impl Mul<Fx64> for Fx64Vec2 {
+    type Output = Fx64Vec2;
+
+    fn mul(self, rhs: Fx64) -> Fx64Vec2 {
+        Fx64Vec2::new(self.x * rhs, self.y * rhs)
+    }
+}
+
+impl Neg for Fx64Vec2 {
+    type Output = Fx64Vec2;
+
+    fn neg(self) -> Fx64Vec2 {
+        Fx64Vec2::new(-self.x, -self.y)
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Default)]
+pub struct Fx64Vec3 {
+    pub x: Fx64,
+    pub y: Fx64,
+    pub z: Fx64,
+}
+
+impl Fx64Vec3 {
+    pub const ZERO: Fx64Vec3 = Fx64Vec3::new(Fx64::ZERO, Fx64::ZERO, Fx64::ZERO);
+
+    pub const fn new(x: Fx64, y: Fx64, z: Fx64) -> Self {
+        Fx64Vec3 { x, y, z }
+    }
+
+    pub fn dot(self, rhs: Fx64Vec3) -> Fx64 {
+        self.x * rhs.x + self.y * rhs.y + self.z * rhs.z
+    }
+
+    pub fn cross(self, rhs: Fx64Vec3) -> Fx64Vec3 {
+        Fx64Vec3::new(
+            self.y * rhs.z - self.z * rhs.y,
+            self.z * rhs.x - self.x * rhs.z,
+            self.x * rhs.y - self.y * rhs.x,
+        )
+    }
+
+    pub fn length(self) -> Fx64 {
+        let (x, y, z) = (self.x.0 as i128, self.y.0 as i128, self.z.0 as i128);
+        root_of_squares((x * x) as u128 + (y * y) as u128 + (z * z) as u128)
+    }
+
+    /// The vector scaled to length one, or zero for the zero vector.
+    pub fn normalize(self) -> Fx64Vec3 {
+        let len = self.length();
+        if len == Fx64::ZERO {
+            return Fx64Vec3::ZERO;
+        }
+        Fx64Vec3::new(
+            scale_down(self.x, len),
+            scale_down(self.y, len),
+            scale_down(self.z, len),
+        )
+    }
+
+    /// The vector rotated about the z axis, see `Fx64Vec2::rotate`.
+    pub fn rotate_z(self, rot: Rot) -> Fx64Vec3 {
+        let xy = Fx64Vec2::new(self.x, self.y).rotate(rot);
+        Fx64Vec3::new(xy.x, xy.y, self.z)
+    }
+}
+
+impl From<Fx32Vec3> for Fx64Vec3 {
+    fn from(v: Fx32Vec3) -> Fx64Vec3 {
+        Fx64Vec3::new(v.x.into(), v.y.into(), v.z.into())
+    }
+}
+
+impl Add for Fx64Vec3 {
+    type Output = Fx64Vec3;
+
+    fn add(self, rhs: Fx64Vec3) -> Fx64Vec3 {
+        Fx64Vec3::new(self.x + rhs.x, self.y + rhs.y, self.z + rhs.z)
+    }
+}
+
+impl Sub for Fx64Vec3 {
+    type Output = Fx64Vec3;
+
+    fn sub(self, rhs: Fx64Vec3) -> Fx64Vec3 {
+        Fx64Vec3::new(self.x - rhs.x, self.y - rhs.y, self.z - rhs.z)
+    }
+}
+
+impl Mul<Fx64> for Fx64Vec3 {
+    type Output = Fx64Vec3;
+
+    fn mul(self, rhs: Fx64) -> Fx64Vec3 {
+        Fx64Vec3::new(self.x * rhs, self.y * rhs, self.z * rhs)
+    }
+}
+
+impl Neg for Fx64Vec3 {
+    type Output = Fx64Vec3;
+
+    fn neg(self) -> Fx64Vec3 {
+        Fx64Vec3::new(-self.x, -self.y, -self.z)
+    }
+}
+
+/// `sweep_checksum(det_math::SWEEP_SAMPLES)` on every platform.
+#[cfg(test)]
+pub(crate) const SWEEP_CHECKSUM: u64 = 0x80ad_fcf0_3e5d_2516;
+
+/// Like `det_math::sweep_checksum`, over the `Fx64` operations.
+#[cfg(test)]
+pub(crate) fn sweep_checksum(samples: u32) -> u64 {
+    let mut rng = crate::rng::EntityRng::new(0x5eed, 0, 0, crate::tick::Tick::new(0));
+    let mut results = Vec::new();
+    for _ in 0..samples {
+        let mut fixed = || Fx64((rng.next_u64() as i64) >> 16);
+        let (a, b, c) = (fixed(), fixed(), fixed());
+        let rot = Rot::from_steps(a.0 as u16);
+        let v = Fx64Vec2::new(a, b);
+        let w = Fx64Vec3::new(a, b, c);
+
+        results.extend([a * b, a / Fx64::from_int(7), a.abs().sqrt()]);
+        results.push(Fx64(v.angle().steps() as i64));
+        let (n, r) = (v.normalize(), v.rotate(rot));
+        results.extend([v.length(), n.x, n.y, v.dot(v), r.x, r.y]);
+        let (n, x) = (w.normalize(), w.cross(w.rotate_z(rot)));
+        results.extend([w.length(), n.x, n.y, n.z, x.x, x.y, x.z]);
+    }
+
+    // FNV-1a over the raw bits
+    results.iter().fold(0xcbf2_9ce4_8422_2325, |hash, value| {
+        (hash ^ value.0 as u64).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+#[cfg(test)]
+#[path = "fixed.tests.rs"]
+mod tests;
@@ -0,0 +1,59 @@
+use super::*;
+
+#[test]
+fn test_fx64_arithmetic() {
+    let a = Fx64::from_int(3);
+    let b = Fx64::from_ratio(1, 4);
+
+    assert_eq!(a + b, Fx64::from_ratio(13, 4));
+    assert_eq!(a * b, Fx64::from_ratio(3, 4));
+    assert_eq!(a / b, Fx64::from_int(12));
+    assert_eq!(-b, Fx64::from_ratio(-1, 4));
+    assert_eq!(Fx64::from_int(9).sqrt(), a);
+    assert_eq!(Fx64::from_int(-9).sqrt(), Fx64::ZERO);
+    assert_eq!(Fx64::from_ratio(-1, 2).floor_int(), -1);
+    assert_eq!(Fx64::MAX + Fx64::from_raw(1), Fx64::MIN);
+
+    // Far beyond the Q16.16 range
+    let far = Fx64::from_int(1 << 29);
+    assert_eq!((far * Fx64::from_int(2)).floor_int(), 1 << 30);
+    let squared = Fx64::from_int(1 << 15) * Fx64::from_int(1 << 15);
+    assert_eq!(squared.sqrt(), Fx64::from_int(1 << 15));
+}
+
+#[test]
+fn test_widening_is_exact() {
+    let narrow = Fx32::from_ratio(-7, 3);
+    assert_eq!(Fx64::from(narrow).narrow(), narrow);
+    assert_eq!(Fx64::from(narrow).raw(), (narrow.raw() as i64) << 16);
+    assert_eq!(Fx64Vec2::from(Fx32Vec2::X), Fx64Vec2::X);
+}
+
+#[test]
+fn test_fx64_vectors() {
+    let v = Fx64Vec2::new(Fx64::from_int(3_000_000), Fx64::from_int(4_000_000));
+    assert_eq!(v.length(), Fx64::from_int(5_000_000));
+    let n = v.normalize();
+    assert_eq!((n.x.to_f64() * 1e6).round(), 0.6e6);
+    assert_eq!(Fx64Vec2::ZERO.normalize(), Fx64Vec2::ZERO);
+
+    // The angle of a far vector matches the angle of the same direction close by
+    let rot = Rot::from_degrees(30);
+    let near = Fx64Vec2::X.rotate(rot);
+    let far = near * Fx64::from_int(1 << 30);
+    let error = far.angle().steps().wrapping_sub(rot.steps()) as i16;
+    assert!(error.abs() <= 8, "{:?}", far.angle());
+    assert_eq!(near.angle(), far.angle());
+
+    let w = Fx64Vec3::new(Fx64::ONE, Fx64::ZERO, Fx64::from_int(2));
+    assert_eq!(
+        w.cross(Fx64Vec3::new(Fx64::ZERO, Fx64::ONE, Fx64::ZERO)).z,
+        Fx64::ONE
+    );
+    assert_eq!(w.rotate_z(Rot::from_degrees(90)).y, Fx64::ONE);
+}
+
+#[test]
+fn test_fx64_sweep_is_bit_identical() {
+    assert_eq!(sweep_checksum(det_math::SWEEP_SAMPLES), SWEEP_CHECKSUM);
+}
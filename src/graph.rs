@@ -0,0 +1,416 @@
+//! Component dependency graph and schedule description derived from the schedule.
+//!
+//! `World::component_graph()` maps every component type (and mailbox) to the systems that
+//! read or write it, based on the read/write sets the stages declare to the scheduler.
+//! Because it comes from the same data the scheduler uses, diagrams generated from it can't
+//! drift from the code. Export it with `to_mermaid()` or `to_dot()`.
+//!
+//! `World::describe_schedule()` (or `Scheduler::describe()`) lists the wavefronts the
+//! scheduler computed and every ordering edge between stages, with the reason for it: a
+//! declared `Before`/`After`, or the types one stage writes and the other accesses. It
+//! answers why two systems ended up in different wavefronts without running a tick.
+//!
+//! # Example
+//! ```ignore
+//! let graph = world.component_graph();
+//! std::fs::write("docs/components.mmd", graph.to_mermaid())?;
+//!
+//! let schedule = world.describe_schedule().unwrap();
+//! for edge in schedule.edges_between("MoveSystem", "RenderSystem") {
+//!     println!("{:?}", edge.conflicts); // ["Position"]
+//! }
+//! print!("{}", schedule);
+//! ```
+
+use std::fmt::{self, Write};
+
+/// A component type and the systems accessing it.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct ComponentNode {
+    pub name: String,
+    /// Systems that read the component, sorted by name.
+    pub readers: Vec<String>,
+    /// Systems that write the component, sorted by name.
+    pub writers: Vec<String>,
+}
+
+/// Which systems read and write which component types.
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+pub struct GraphDescription {
+    /// All systems, sorted by name.
+    pub systems: Vec<String>,
+    /// All accessed component types, sorted by name.
+    pub components: Vec<ComponentNode>,
+}
+
+/// Strips module paths from a type name, including inside generic arguments:
+/// `game::ComponentCleanupSystem<game::Position>` becomes `ComponentCleanupSystem<Position>`.
+pub fn short_type_name(name: &str) -> String {
+    let mut out = String::with_capacity(name.len());
+    let mut segment = String::new();
+
+    for c in name.chars() {
+        if c.is_alphanumeric() || c == '_' || c == ':' {
+            segment.push(c);
+        } else {
+            out.push_str(segment.rsplit("::").next().unwrap_or(""));
+            segment.clear();
+            out.push(c);
+        }
+    }
+
+    out.push_str(segment.rsplit("::").next().unwrap_or(""));
+    out
+}
+
+impl GraphDescription {
+    /// Adds an access edge. Used by `World::component_graph`.
+    pub(crate) fn add_access(&mut self, component: &str, system: &str, write: bool) {
+        let node = match self.components.iter_mut().position(|c| c.name == component) {
+            Some(i) => &mut self.components[i],
+            None => {
+                self.components.push(ComponentNode {
+                    name: component.to_string(),
+                    readers: Vec::new(),
+                    writers: Vec::new(),
+                });
+                self.components.last_mut().unwrap()
+            }
+        };
+
+        let list = if write {
+            &mut node.writers
+        } else {
+            &mut node.readers
+        };
+        if !list.iter().any(|s| s == system) {
+            list.push(system.to_string());
+        }
+    }
+
+    /// Sorts systems, components, readers and writers so output is stable.
+    pub(crate) fn sort(&mut self) {
+        self.systems.sort();
+        self.systems.dedup();
+        self.components.sort_by(|a, b| a.name.cmp(&b.name));
+
+        for node in &mut self.components {
+            node.readers.sort();
+            node.writers.sort();
+        }
+    }
+
+    /// Returns the component node with the given name.
+    pub fn component(&self, name: &str) -> Option<&ComponentNode> {
+        self.components.iter().find(|c| c.name == name)
+    }
+
+    /// Renders the graph as a Mermaid flowchart. Writers point at components,
+    /// components point at their readers.
+    pub fn to_mermaid(&self) -> String {
+        let escape = |s: &str| {
+            s.replace('<', "#lt;")
+                .replace('>', "#gt;")
+                .replace('"', "#quot;")
+        };
+        let mut out = String::from("flowchart LR\n");
+
+        for (i, system) in self.systems.iter().enumerate() {
+            let _ = writeln!(out, "    s{}[\"{}\"]", i, escape(system));
+        }
+
+        for (i, node) in self.components.iter().enumerate() {
+            let _ = writeln!(out, "    c{}[(\"{}\")]", i, escape(&node.name));
+        }
+
+        let system_id = |name: &str| self.systems.iter().position(|s| s == name);
+
+        for (i, node) in self.components.iter().enumerate() {
+            for writer in node.writers.iter().filter_map(|w| system_id(w)) {
+                let _ = writeln!(out, "    s{} -->|writes| c{}", writer, i);
+            }
+            for reader in node.readers.iter().filter_map(|r| system_id(r)) {
+                let _ = writeln!(out, "    c{} -->|reads| s{}", i, reader);
+            }
+        }
+
+        out
+    }
+
+    /// Renders the graph in Graphviz DOT format. Systems are boxes, components are
+    /// ellipses.
+    pub fn to_dot(&self) -> String {
+        let quote = |s: &str| format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""));
+        let mut out = String::from("digraph components {\n    rankdir=LR;\n");
+
+        for system in &self.systems {
+            let _ = writeln!(
+                out,
+                "    {} [shape=box, label={}];",
+                quote(&format!("system:{}", system)),
+                quote(system)
+            );
+        }
+
+        for node in &self.components {
+            let _ = writeln!(
+                out,
+                "    {} [shape=ellipse, label={}];",
+                quote(&format!("component:{}", node.name)),
+                quote(&node.name)
+            );
+        }
+
+        for node in &self.components {
+            let component = quote(&format!("component:{}", node.name));
+            for writer in &node.writers {
+                let _ = writeln!(
+                    out,
+                    "    {} -> {} [label=\"writes\"];",
+                    quote(&format!("system:{}", writer)),
+                    component
+                );
+            }
+            for reader in &node.readers {
+                let _ = writeln!(
+                    out,
+                    "    {} -> {} [label=\"reads\"];",
+                    component,
+                    quote(&format!("system:{}", reader))
+                );
+            }
+        }
+
+        out.push_str("}\n");
+        out
+    }
+}
+
+/// A stage of a `ScheduleDescription`: a system, or a loop group with the wavefronts of
+/// its children.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct StageDescription {
+    pub name: String,
+    /// The pipeline group the stage is nested in, if any.
+    pub group: Option<String>,
+    /// Types the stage declares it reads, in declaration order.
+    pub reads: Vec<String>,
+    /// Types the stage declares it writes, in declaration order.
+    pub writes: Vec<String>,
+    /// For a loop group, the wavefronts of its children. Empty for systems.
+    pub children: Vec<Vec<StageDescription>>,
+}
+
+/// An ordering edge of a `ScheduleDescription`: `before` runs in an earlier wavefront than
+/// `after`. An edge that is neither declared nor has conflicts comes from the ordering of
+/// the stages' pipeline groups.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct ScheduleEdge {
+    pub before: String,
+    pub after: String,
+    /// Whether one of the two stages names the other in `Before` or `After`.
+    pub declared: bool,
+    /// Types one stage writes and the other reads or writes, sorted by name.
+    pub conflicts: Vec<String>,
+}
+
+/// The wavefronts and ordering edges of a built schedule, see the module docs.
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+pub struct ScheduleDescription {
+    /// Stages in execution order. Stages of one wavefront may run in parallel.
+    pub wavefronts: Vec<Vec<StageDescription>>,
+    /// Ordering edges between the stages of the outer schedule and between the children
+    /// of each loop group, in schedule order.
+    pub edges: Vec<ScheduleEdge>,
+}
+
+impl ScheduleDescription {
+    /// The edges ordering `a` and `b` directly, in either direction.
+    pub fn edges_between<'a>(
+        &'a self,
+        a: &'a str,
+        b: &'a str,
+    ) -> impl Iterator<Item = &'a ScheduleEdge> {
+        self.edges.iter().filter(move |edge| {
+            (edge.before == a && edge.after == b) || (edge.before == b && edge.after == a)
+        })
+    }
+}
+
+impl ScheduleDescription {
+    /// Renders the schedule in Graphviz DOT format. Each wavefront is a cluster of boxes
+    /// labeled with the stage's group, reads and writes, and a loop group a dashed cluster
+    /// holding its children's wavefronts. Ordering edges are labeled with the conflicting
+    /// types, bold when declared and dashed when they come from group order.
+    pub fn to_dot(&self) -> String {
+        fn escape(s: &str) -> String {
+            s.replace('\\', "\\\\").replace('"', "\\\"")
+        }
+
+        fn clusters(out: &mut String, wavefronts: &[Vec<StageDescription>], prefix: &str) {
+            for (i, wavefront) in wavefronts.iter().enumerate() {
+                let _ = writeln!(out, "    subgraph \"cluster_{}{}\" {{", prefix, i);
+                let _ = writeln!(out, "        label=\"wavefront {}\";", i);
+                for stage in wavefront {
+                    let mut label = vec![escape(&stage.name)];
+                    label.extend(stage.group.as_deref().map(|g| format!("in {}", escape(g))));
+                    if !stage.reads.is_empty() {
+                        label.push(format!("reads: {}", escape(&stage.reads.join(", "))));
+                    }
+                    if !stage.writes.is_empty() {
+                        label.push(format!("writes: {}", escape(&stage.writes.join(", "))));
+                    }
+                    let shape = if stage.children.is_empty() { "box" } else { "box3d" };
+                    let _ = writeln!(
+                        out,
+                        "        \"{}\" [shape={}, label=\"{}\"];",
+                        escape(&stage.name),
+                        shape,
+                        label.join("\\n")
+                    );
+                }
+                let _ = writeln!(out, "    }}");
+
+                for stage in wavefront.iter().filter(|s| !s.children.is_empty()) {
+                    let nested = format!("{}{}_{}_", prefix, i, stage.name);
+                    let _ = writeln!(out, "    subgraph \"cluster_{}\" {{", escape(&nested));
+                    let _ = writeln!(out, "        label=\"loop {}\";", escape(&stage.name));
+                    let _ = writeln!(out, "        style=dashed;");
+                    clusters(out, &stage.children, &escape(&nested));
+                    let _ = writeln!(out, "    }}");
+                }
+            }
+        }
+
+        let mut out = String::from("digraph schedule {\n    rankdir=LR;\n    compound=true;\n");
+        clusters(&mut out, &self.wavefronts, "");
+
+        for edge in &self.edges {
+            let style = if edge.declared {
+                ", style=bold"
+            } else if edge.conflicts.is_empty() {
+                ", style=dashed"
+            } else {
+                ""
+            };
+            let _ = writeln!(
+                out,
+                "    \"{}\" -> \"{}\" [label=\"{}\"{}];",
+                escape(&edge.before),
+                escape(&edge.after),
+                escape(&edge.conflicts.join(", ")),
+                style
+            );
+        }
+
+        out.push_str("}\n");
+        out
+    }
+
+    /// Renders the schedule as a Mermaid flowchart, with a subgraph per wavefront and loop
+    /// group. Ordering edges are labeled with the conflicting types, thick when declared
+    /// and dotted when they come from group order.
+    pub fn to_mermaid(&self) -> String {
+        fn escape(s: &str) -> String {
+            s.replace('<', "#lt;").replace('>', "#gt;").replace('"', "#quot;")
+        }
+
+        fn subgraphs(
+            out: &mut String,
+            ids: &mut Vec<String>,
+            wavefronts: &[Vec<StageDescription>],
+            prefix: &str,
+        ) {
+            for (i, wavefront) in wavefronts.iter().enumerate() {
+                let _ = writeln!(out, "    subgraph {}w{}[\"wavefront {}\"]", prefix, i, i);
+                for stage in wavefront {
+                    let mut label = vec![escape(&stage.name)];
+                    label.extend(stage.group.as_deref().map(|g| format!("in {}", escape(g))));
+                    let _ = writeln!(out, "        n{}[\"{}\"]", ids.len(), label.join("<br/>"));
+                    ids.push(stage.name.clone());
+                }
+                let _ = writeln!(out, "    end");
+
+                for stage in wavefront.iter().filter(|s| !s.children.is_empty()) {
+                    let nested = format!("{}w{}l{}", prefix, i, ids.len());
+                    let title = escape(&stage.name);
+                    let _ = writeln!(out, "    subgraph {}[\"loop {}\"]", nested, title);
+                    subgraphs(out, ids, &stage.children, &nested);
+                    let _ = writeln!(out, "    end");
+                }
+            }
+        }
+
+        let mut out = String::from("flowchart LR\n");
+        let mut ids = Vec::new();
+        subgraphs(&mut out, &mut ids, &self.wavefronts, "");
+
+        let id = |name: &str| ids.iter().position(|n| n == name);
+        for edge in &self.edges {
+            let (Some(before), Some(after)) = (id(&edge.before), id(&edge.after)) else {
+                continue;
+            };
+            let arrow = if edge.declared {
+                "==>"
+            } else if edge.conflicts.is_empty() {
+                "-.->"
+            } else {
+                "-->"
+            };
+            if edge.conflicts.is_empty() {
+                let _ = writeln!(out, "    n{} {} n{}", before, arrow, after);
+            } else {
+                let label = escape(&edge.conflicts.join(", "));
+                let _ = writeln!(out, "    n{} {}|\"{}\"| n{}", before, arrow, label, after);
+            }
+        }
+
+        out
+    }
+}
+
+impl fmt::Display for ScheduleDescription {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fn stages(
+            f: &mut fmt::Formatter<'_>,
+            wavefronts: &[Vec<StageDescription>],
+            indent: usize,
+        ) -> fmt::Result {
+            for (i, wavefront) in wavefronts.iter().enumerate() {
+                writeln!(f, "{:indent$}wavefront {}:", "", i)?;
+                for stage in wavefront {
+                    writeln!(
+                        f,
+                        "{:indent$}  {} reads [{}] writes [{}]",
+                        "",
+                        stage.name,
+                        stage.reads.join(", "),
+                        stage.writes.join(", ")
+                    )?;
+                    stages(f, &stage.children, indent + 4)?;
+                }
+            }
+            Ok(())
+        }
+
+        stages(f, &self.wavefronts, 0)?;
+        for edge in &self.edges {
+            let mut reasons = Vec::new();
+            if edge.declared {
+                reasons.push("declared".to_string());
+            }
+            if !edge.conflicts.is_empty() {
+                reasons.push(format!("conflicts on {}", edge.conflicts.join(", ")));
+            }
+            if reasons.is_empty() {
+                reasons.push("group order".to_string());
+            }
+            writeln!(f, "{} -> {}: {}", edge.before, edge.after, reasons.join("; "))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+#[path = "graph.tests.rs"]
+mod tests;
@@ -0,0 +1,173 @@
+use super::*;
+use crate::component::Component;
+use crate::prelude::system;
+use crate::world::World;
+
+#[derive(Component, Default, Clone, Debug, PartialEq)]
+struct Position {
+    x: f32,
+}
+
+#[derive(Component, Default, Clone, Debug, PartialEq)]
+struct Velocity {
+    x: f32,
+}
+
+system! {
+    MoveSystem {
+        query! {
+            fn step(pos: &mut ViewMut<Position>, vel: View<Velocity>) {
+                pos.x += vel.x;
+            }
+        }
+    }
+}
+
+system! {
+    RenderSystem {
+        query! {
+            fn render(pos: View<Position>) {
+                let _ = pos.x;
+            }
+        }
+    }
+}
+
+fn world() -> World {
+    let mut world = World::new();
+    world.add_system::<MoveSystem>();
+    world.add_system::<RenderSystem>();
+    world
+}
+
+#[test]
+fn test_short_type_name() {
+    assert_eq!(short_type_name("game::Position"), "Position");
+    assert_eq!(
+        short_type_name("a::b::Cleanup<game::Position, std::vec::Vec<u8>>"),
+        "Cleanup<Position, Vec<u8>>"
+    );
+}
+
+#[test]
+fn test_component_graph_maps_readers_and_writers() {
+    let graph = world().component_graph();
+
+    // The auto-scheduled cleanup system also writes the component
+    let position = graph.component("Position").unwrap();
+    assert_eq!(
+        position.writers,
+        vec!["MoveSystem", "PositionCleanupSystem"]
+    );
+    assert_eq!(position.readers, vec!["RenderSystem"]);
+
+    let velocity = graph.component("Velocity").unwrap();
+    assert_eq!(velocity.writers, vec!["VelocityCleanupSystem"]);
+    assert_eq!(velocity.readers, vec!["MoveSystem"]);
+
+    assert!(graph.systems.contains(&"MoveSystem".to_string()));
+    assert!(graph.systems.contains(&"RenderSystem".to_string()));
+}
+
+#[test]
+fn test_component_graph_same_before_and_after_build() {
+    let pending = world().component_graph();
+
+    let mut built = world();
+    built.build_scheduler();
+
+    assert_eq!(pending, built.component_graph());
+}
+
+#[test]
+fn test_exporters() {
+    let graph = world().component_graph();
+
+    let mermaid = graph.to_mermaid();
+    assert!(mermaid.starts_with("flowchart LR\n"));
+    let move_id = graph
+        .systems
+        .iter()
+        .position(|s| s == "MoveSystem")
+        .unwrap();
+    let pos_id = graph
+        .components
+        .iter()
+        .position(|c| c.name == "Position")
+        .unwrap();
+    assert!(mermaid.contains(&format!("s{} -->|writes| c{}", move_id, pos_id)));
+
+    let dot = graph.to_dot();
+    assert!(dot.starts_with("digraph components {"));
+    assert!(dot.contains("\"system:MoveSystem\" -> \"component:Position\" [label=\"writes\"];"));
+    assert!(dot.contains("\"component:Velocity\" -> \"system:MoveSystem\" [label=\"reads\"];"));
+    assert!(dot.trim_end().ends_with('}'));
+}
+
+#[test]
+fn test_describe_schedule_explains_ordering() {
+    let mut world = world();
+    assert!(world.describe_schedule().is_none());
+    world.build_scheduler();
+    let schedule = world.describe_schedule().unwrap();
+
+    let wave_of = |name: &str| {
+        schedule
+            .wavefronts
+            .iter()
+            .position(|wave| wave.iter().any(|stage| stage.name == name))
+            .unwrap()
+    };
+    assert!(wave_of("MoveSystem") < wave_of("RenderSystem"));
+
+    let stage = schedule.wavefronts[wave_of("MoveSystem")]
+        .iter()
+        .find(|stage| stage.name == "MoveSystem")
+        .unwrap();
+    assert_eq!(stage.writes, vec!["Position"]);
+    assert!(stage.reads.contains(&"Velocity".to_string()));
+
+    let edges: Vec<_> = schedule.edges_between("RenderSystem", "MoveSystem").collect();
+    assert_eq!(edges.len(), 1);
+    assert_eq!(edges[0].before, "MoveSystem");
+    assert!(!edges[0].declared);
+    assert_eq!(edges[0].conflicts, vec!["Position"]);
+    assert!(schedule.to_string().contains("MoveSystem -> RenderSystem: conflicts on Position"));
+
+    // Without the world, derived components are still named
+    assert_eq!(world.scheduler().unwrap().describe(), schedule);
+}
+
+#[rollback_macros::pipeline_group]
+struct DriftGroup;
+
+system! {
+    DriftSystem {
+        query! {
+            fn drift(vel: &mut ViewMut<Velocity>) Parent=DriftGroup {
+                vel.x *= 0.5;
+            }
+        }
+    }
+}
+
+#[test]
+fn test_schedule_exports() {
+    let mut world = world();
+    world.add_loop_group::<DriftGroup>(crate::scheduler::LoopGroup::run_until(|_| true, 2));
+    world.add_system::<DriftSystem>();
+    world.build_scheduler();
+    let scheduler = world.scheduler().unwrap();
+
+    let dot = scheduler.to_dot();
+    assert!(dot.starts_with("digraph schedule {"));
+    assert!(dot.contains("label=\"loop DriftGroup\";"));
+    assert!(dot.contains("\"DriftSystem\" [shape=box, label=\"DriftSystem\\nin DriftGroup"));
+    assert!(dot.contains("\"MoveSystem\" -> \"RenderSystem\" [label=\"Position\"];"));
+    assert!(dot.contains("\"DriftGroup\" -> \"MoveSystem\" [label=\"Velocity\"];"));
+
+    let mermaid = scheduler.to_mermaid();
+    assert!(mermaid.starts_with("flowchart LR\n    subgraph w0[\"wavefront 0\"]"));
+    assert!(mermaid.contains("[\"loop DriftGroup\"]"));
+    assert!(mermaid.contains("-->|\"Position\"|"));
+}
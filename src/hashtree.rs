@@ -0,0 +1,506 @@
+//! Hierarchical world hashes for narrowing down desyncs.
+//!
+//! A single `world_checksum` tells two peers that they diverged, not where. `World::hash_tree()`
+//! hashes the world along the block tree instead: one hash per inner block of 128 entity
+//! slots, one per root block (the 128 inner blocks below it) and one for the whole world.
+//! Peers compare the top hash, then the root block hashes, then the inner block hashes of
+//! the root blocks that differ, and end up with the exact 128-slot block that diverged
+//! while exchanging a few hashes per level.
+//!
+//! Block hashes are cached. Component cleanup systems record which blocks their storage
+//! changed each tick (from the change masks, plus the components they discard for
+//! destroyed entities), so `hash_tree()` only rehashes blocks that changed since the last
+//! call. A rollback invalidates the cache.
+//!
+//! Blocks are hashed like `World::state_hash` hashes storages: every occupied slot, plus
+//! the value for component types implementing `Hash` (through `Hashable::hash_state`).
+//! Hashes use the platform-independent `StateHasher`, and storages are identified by type
+//! name, so peers on different platforms, or that registered components in a different
+//! order, agree on the same state.
+//!
+//! To learn which components and entities diverged, one peer sends its
+//! `World::hash_report()` (per-storage block hashes and occupancy, `Wire`-encoded), and the
+//! other calls `World::diff_against` at the same tick. The `DesyncReport` lists each
+//! mismatched storage with its diverging blocks and, per block, the entity indices that
+//! hold the component on one side only or, when both sides hold the same slots, the
+//! occupied slots whose values are suspect.
+//!
+//! # Example
+//! ```ignore
+//! let ours = world.hash_tree();
+//! if ours.root() != theirs.root() {
+//!     for ri in ours.diff_roots(&theirs) {
+//!         for mi in ours.diff_blocks(&theirs, ri) {
+//!             println!("entities {:?} diverged", HashTree::block_range(ri, mi));
+//!         }
+//!     }
+//! }
+//!
+//! let report = world.diff_against(&HashReport::decode(&mut Reader::new(&peer_report))?);
+//! for storage in &report.storages {
+//!     for block in &storage.blocks {
+//!         println!("{} diverged at entities {:?}", storage.name, block.entities);
+//!     }
+//! }
+//! ```
+
+use crate::rollback::StorageLike;
+use crate::sequence::stage_hash;
+use crate::statehash::StateHasher;
+use crate::tick::Tick;
+use crate::wire::{DecodeError, Reader, Wire};
+use std::cell::UnsafeCell;
+use std::collections::{BTreeMap, BTreeSet};
+use std::hash::{Hash, Hasher};
+use std::ops::Range;
+
+/// Blocks of one storage changed since the hash cache last looked, written by the
+/// storage's cleanup system.
+pub struct DirtyBlocks {
+    /// Dirty inner blocks per root block.
+    middles: UnsafeCell<[u128; 128]>,
+}
+
+// SAFETY: only the owning cleanup system writes it while the scheduler runs, and the world
+// only reads it between ticks
+unsafe impl Send for DirtyBlocks {}
+unsafe impl Sync for DirtyBlocks {}
+
+impl DirtyBlocks {
+    pub fn new() -> Self {
+        DirtyBlocks {
+            middles: UnsafeCell::new([0; 128]),
+        }
+    }
+
+    /// Marks inner block `(ri, mi)` dirty.
+    pub fn mark(&self, ri: u32, mi: u32) {
+        unsafe { (*self.middles.get())[ri as usize] |= 1u128 << mi };
+    }
+
+    /// Marks every block with changes recorded in a storage's change masks, given its root
+    /// change mask and a lookup of its middle change masks.
+    pub fn mark_changed(&self, root_changed: u128, middle_changed: impl Fn(u32) -> u128) {
+        let middles = unsafe { &mut *self.middles.get() };
+        let mut root = root_changed;
+
+        while root != 0 {
+            let ri = root.trailing_zeros();
+            root &= !(1u128 << ri);
+            middles[ri as usize] |= middle_changed(ri);
+        }
+    }
+
+    fn take(&self) -> [u128; 128] {
+        std::mem::replace(unsafe { &mut *self.middles.get() }, [0; 128])
+    }
+}
+
+impl Default for DirtyBlocks {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Cached block hashes of one storage.
+struct StorageHashes {
+    /// Inner block hashes keyed by `ri * 128 + mi`, empty blocks omitted.
+    blocks: BTreeMap<u32, u64>,
+}
+
+/// The world's block hash cache, see the module docs.
+#[derive(Default)]
+pub(crate) struct HashCache {
+    /// Change trackers, keyed by component type index.
+    dirty: BTreeMap<usize, std::rc::Rc<DirtyBlocks>>,
+    /// Cached hashes, keyed by component type index.
+    storages: BTreeMap<usize, StorageHashes>,
+}
+
+impl HashCache {
+    /// The change tracker for the storage with type index `id`, created on first use.
+    pub(crate) fn dirty_blocks(&mut self, id: usize) -> std::rc::Rc<DirtyBlocks> {
+        self.dirty.entry(id).or_default().clone()
+    }
+
+    /// Drops every cached hash, e.g. after a rollback rewrote the storages.
+    pub(crate) fn invalidate(&mut self) {
+        self.storages.clear();
+        for dirty in self.dirty.values() {
+            dirty.take();
+        }
+    }
+
+    /// Brings the cached hashes of the storage with type index `id` up to date.
+    pub(crate) fn update(&mut self, id: usize, storage: &dyn StorageLike) {
+        let hash_block = |ri: u32, mi: u32| -> Option<u64> {
+            if storage.inner_mask(ri, mi) == 0 {
+                return None;
+            }
+
+            let mut state = StateHasher::new();
+            storage.hash_block(ri, mi, &mut state);
+            Some(state.finish())
+        };
+
+        // Storages without a cleanup system (temporary components) are always rehashed
+        let tracker = self
+            .dirty
+            .get(&id)
+            .filter(|_| self.storages.contains_key(&id));
+        let Some(tracker) = tracker else {
+            if let Some(tracker) = self.dirty.get(&id) {
+                tracker.take();
+            }
+
+            let mut blocks = BTreeMap::new();
+            for key in occupied_blocks(storage) {
+                if let Some(hash) = hash_block(key / 128, key % 128) {
+                    blocks.insert(key, hash);
+                }
+            }
+            self.storages.insert(id, StorageHashes { blocks });
+            return;
+        };
+
+        // Changes recorded since the last tick plus any made since then
+        tracker.mark_changed(storage.root_changed_mask(), |ri| {
+            storage.middle_changed_mask(ri)
+        });
+        let dirty = tracker.take();
+        let blocks = &mut self.storages.get_mut(&id).unwrap().blocks;
+
+        for (ri, &middle) in dirty.iter().enumerate() {
+            let mut middle = middle;
+            while middle != 0 {
+                let mi = middle.trailing_zeros();
+                middle &= !(1u128 << mi);
+
+                let key = ri as u32 * 128 + mi;
+                match hash_block(ri as u32, mi) {
+                    Some(hash) => blocks.insert(key, hash),
+                    None => blocks.remove(&key),
+                };
+            }
+        }
+    }
+
+    /// Builds the tree from the cached hashes of the given `(type index, type name)`
+    /// storages, which must all be up to date.
+    pub(crate) fn tree(&self, storages: &[(usize, &str)]) -> HashTree {
+        HashTree::build(
+            storages
+                .iter()
+                .map(|&(id, name)| (name, &self.storages[&id].blocks)),
+        )
+    }
+
+    /// Builds a report from the cached hashes of the given `(type index, storage)` pairs,
+    /// which must all be up to date.
+    pub(crate) fn report(&self, tick: Tick, storages: &[(usize, &dyn StorageLike)]) -> HashReport {
+        let storages = storages
+            .iter()
+            .map(|&(id, storage)| {
+                let blocks = self.storages[&id]
+                    .blocks
+                    .iter()
+                    .map(|(&key, &hash)| {
+                        let occupied = storage.inner_mask(key / 128, key % 128);
+                        (key, BlockHash { hash, occupied })
+                    })
+                    .collect();
+                (storage.type_name().to_string(), blocks)
+            })
+            .collect();
+        HashReport { tick, storages }
+    }
+}
+
+/// Keys (`ri * 128 + mi`) of every inner block holding at least one component.
+fn occupied_blocks(storage: &dyn StorageLike) -> Vec<u32> {
+    let mut keys = Vec::new();
+    let mut root = storage.root_mask();
+
+    while root != 0 {
+        let ri = root.trailing_zeros();
+        root &= !(1u128 << ri);
+
+        let mut middle = storage.middle_mask(ri);
+        while middle != 0 {
+            let mi = middle.trailing_zeros();
+            middle &= !(1u128 << mi);
+            keys.push(ri * 128 + mi);
+        }
+    }
+    keys
+}
+
+/// Hashes of the world along the block tree, see the module docs.
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+pub struct HashTree {
+    root: u64,
+    /// Root block hashes keyed by `ri`, empty root blocks omitted.
+    roots: BTreeMap<u32, u64>,
+    /// Inner block hashes keyed by `ri * 128 + mi`, empty blocks omitted.
+    blocks: BTreeMap<u32, u64>,
+}
+
+impl HashTree {
+    /// Combines per-storage block hashes, given as `(type name, blocks)`.
+    pub(crate) fn build<'a>(
+        storages: impl IntoIterator<Item = (&'a str, &'a BTreeMap<u32, u64>)>,
+    ) -> Self {
+        // Ordered by type name hash so registration order doesn't matter
+        let mut storages: Vec<(u64, &BTreeMap<u32, u64>)> = storages
+            .into_iter()
+            .map(|(name, blocks)| (stage_hash(name), blocks))
+            .collect();
+        storages.sort_by_key(|(name, _)| *name);
+
+        let keys: BTreeSet<u32> = storages
+            .iter()
+            .flat_map(|(_, b)| b.keys().copied())
+            .collect();
+
+        let mut blocks = BTreeMap::new();
+        for key in keys {
+            let mut state = StateHasher::new();
+            for (name, storage) in &storages {
+                if let Some(hash) = storage.get(&key) {
+                    (name, hash).hash(&mut state);
+                }
+            }
+            blocks.insert(key, state.finish());
+        }
+
+        let mut roots: BTreeMap<u32, StateHasher> = BTreeMap::new();
+        for (&key, hash) in &blocks {
+            (key, hash).hash(roots.entry(key / 128).or_default());
+        }
+        let roots: BTreeMap<u32, u64> = roots.into_iter().map(|(ri, s)| (ri, s.finish())).collect();
+
+        let mut state = StateHasher::new();
+        for entry in &roots {
+            entry.hash(&mut state);
+        }
+
+        HashTree {
+            root: state.finish(),
+            roots,
+            blocks,
+        }
+    }
+
+    /// Hash of the whole world.
+    pub fn root(&self) -> u64 {
+        self.root
+    }
+
+    /// Hash of root block `ri` (entity indices `ri * 16384 ..`), 0 if it is empty.
+    pub fn root_block(&self, ri: u32) -> u64 {
+        self.roots.get(&ri).copied().unwrap_or(0)
+    }
+
+    /// Hash of inner block `(ri, mi)`, 0 if it is empty.
+    pub fn block(&self, ri: u32, mi: u32) -> u64 {
+        self.blocks.get(&(ri * 128 + mi)).copied().unwrap_or(0)
+    }
+
+    /// Non-empty root blocks and their hashes, in ascending order. This is what a peer sends
+    /// first.
+    pub fn root_blocks(&self) -> impl Iterator<Item = (u32, u64)> + '_ {
+        self.roots.iter().map(|(&ri, &hash)| (ri, hash))
+    }
+
+    /// Non-empty inner blocks of root block `ri` and their hashes, in ascending order.
+    pub fn blocks(&self, ri: u32) -> impl Iterator<Item = (u32, u64)> + '_ {
+        self.blocks
+            .range(ri * 128..(ri + 1) * 128)
+            .map(|(&key, &hash)| (key % 128, hash))
+    }
+
+    /// Root blocks whose hashes differ from `other`'s.
+    pub fn diff_roots(&self, other: &HashTree) -> Vec<u32> {
+        let keys: BTreeSet<u32> = self
+            .roots
+            .keys()
+            .chain(other.roots.keys())
+            .copied()
+            .collect();
+        keys.into_iter()
+            .filter(|&ri| self.root_block(ri) != other.root_block(ri))
+            .collect()
+    }
+
+    /// Inner blocks of root block `ri` whose hashes differ from `other`'s.
+    pub fn diff_blocks(&self, other: &HashTree, ri: u32) -> Vec<u32> {
+        let keys: BTreeSet<u32> = self
+            .blocks(ri)
+            .chain(other.blocks(ri))
+            .map(|(mi, _)| mi)
+            .collect();
+        keys.into_iter()
+            .filter(|&mi| self.block(ri, mi) != other.block(ri, mi))
+            .collect()
+    }
+
+    /// Entity indices covered by inner block `(ri, mi)`.
+    pub fn block_range(ri: u32, mi: u32) -> Range<u32> {
+        let start = ri * 16384 + mi * 128;
+        start..start + 128
+    }
+}
+
+/// Hash and occupancy of one inner block of one storage.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+struct BlockHash {
+    hash: u64,
+    /// Occupied slots of the block.
+    occupied: u128,
+}
+
+/// Per-storage block hashes of the world, for `World::diff_against`. Unlike a `HashTree`
+/// it keeps storages apart, so a peer's report tells which components diverged. Send it
+/// with its `Wire` encoding.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct HashReport {
+    tick: Tick,
+    /// Inner block hashes keyed by type name, then by `ri * 128 + mi`, empty blocks omitted.
+    storages: BTreeMap<String, BTreeMap<u32, BlockHash>>,
+}
+
+impl HashReport {
+    /// The tick the report was taken at.
+    pub fn tick(&self) -> Tick {
+        self.tick
+    }
+
+    /// Compares the report with `other`, taken by a peer at the same tick.
+    pub fn diff(&self, other: &HashReport) -> DesyncReport {
+        let empty = BTreeMap::new();
+        let names: BTreeSet<&String> = self.storages.keys().chain(other.storages.keys()).collect();
+
+        let mut storages = Vec::new();
+        for name in names {
+            let ours = self.storages.get(name).unwrap_or(&empty);
+            let theirs = other.storages.get(name).unwrap_or(&empty);
+            let keys: BTreeSet<u32> = ours.keys().chain(theirs.keys()).copied().collect();
+
+            let mut blocks = Vec::new();
+            for key in keys {
+                let (a, b) = (ours.get(&key), theirs.get(&key));
+                if a == b {
+                    continue;
+                }
+
+                let a_occupied = a.map_or(0, |block| block.occupied);
+                let b_occupied = b.map_or(0, |block| block.occupied);
+                let mut slots = match a_occupied ^ b_occupied {
+                    0 => a_occupied,
+                    differ => differ,
+                };
+
+                let (ri, mi) = (key / 128, key % 128);
+                let start = HashTree::block_range(ri, mi).start;
+                let mut entities = Vec::new();
+                while slots != 0 {
+                    let ii = slots.trailing_zeros();
+                    slots &= !(1u128 << ii);
+                    entities.push(start + ii);
+                }
+                blocks.push(DesyncBlock { ri, mi, entities });
+            }
+
+            if !blocks.is_empty() {
+                storages.push(StorageDesync {
+                    name: name.clone(),
+                    blocks,
+                });
+            }
+        }
+
+        DesyncReport { storages }
+    }
+}
+
+impl Wire for HashReport {
+    fn encode(&self, out: &mut Vec<u8>) {
+        self.tick.encode(out);
+        (self.storages.len() as u32).encode(out);
+        for (name, blocks) in &self.storages {
+            name.encode(out);
+            (blocks.len() as u32).encode(out);
+            for (key, block) in blocks {
+                key.encode(out);
+                block.hash.encode(out);
+                block.occupied.encode(out);
+            }
+        }
+    }
+
+    fn decode(reader: &mut Reader<'_>) -> Result<Self, DecodeError> {
+        let tick = Tick::decode(reader)?;
+        let mut storages = BTreeMap::new();
+
+        for _ in 0..reader.len_prefix(4)? {
+            let name = String::decode(reader)?;
+            let mut blocks = BTreeMap::new();
+            for _ in 0..reader.len_prefix(28)? {
+                let key = u32::decode(reader)?;
+                let hash = u64::decode(reader)?;
+                let occupied = u128::decode(reader)?;
+                blocks.insert(key, BlockHash { hash, occupied });
+            }
+            storages.insert(name, blocks);
+        }
+
+        Ok(HashReport { tick, storages })
+    }
+}
+
+/// Where two worlds diverge, from `World::diff_against`.
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+pub struct DesyncReport {
+    /// The storages that differ, by type name.
+    pub storages: Vec<StorageDesync>,
+}
+
+impl DesyncReport {
+    /// Whether the worlds match.
+    pub fn is_empty(&self) -> bool {
+        self.storages.is_empty()
+    }
+
+    /// Every entity index listed by any storage, in ascending order.
+    pub fn entities(&self) -> Vec<u32> {
+        let indices: BTreeSet<u32> = self
+            .storages
+            .iter()
+            .flat_map(|storage| &storage.blocks)
+            .flat_map(|block| block.entities.iter().copied())
+            .collect();
+        indices.into_iter().collect()
+    }
+}
+
+/// The diverging blocks of one storage.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct StorageDesync {
+    /// Type name of the component.
+    pub name: String,
+    pub blocks: Vec<DesyncBlock>,
+}
+
+/// One diverging inner block of a storage.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct DesyncBlock {
+    pub ri: u32,
+    pub mi: u32,
+    /// The entity indices that have the component on one side only. When both sides hold
+    /// the same slots, one of their values differs, and all of them are listed.
+    pub entities: Vec<u32>,
+}
+
+#[cfg(test)]
+#[path = "hashtree.tests.rs"]
+mod tests;
@@ -0,0 +1,169 @@
+use super::*;
+use crate::prelude::*;
+
+#[derive(Component, Clone, Default, Debug, PartialEq, Hash)]
+struct Position {
+    x: i32,
+}
+
+#[derive(Component, Clone, Default, Debug, PartialEq, Hash)]
+struct Velocity {
+    dx: i32,
+}
+
+system! {
+    MoveSystem {
+        query! {
+            fn step(pos: &mut ViewMut<Position>, vel: View<Velocity>) {
+                pos.x += vel.dx;
+            }
+        }
+    }
+}
+
+fn test_world(count: u32) -> World {
+    let mut world = World::new();
+    world.add_system::<MoveSystem>();
+    world.get_storage::<Position>();
+    world.get_storage::<Velocity>();
+    world.build_scheduler();
+
+    for i in 0..count {
+        let e = world.spawn();
+        world.set(e, &Position { x: i as i32 });
+        if i % 100 == 0 {
+            world.set(e, &Velocity { dx: 1 });
+        }
+    }
+    world
+}
+
+/// The tree computed from scratch, bypassing the cache.
+fn full_tree(world: &World) -> HashTree {
+    let mut cache = HashCache::default();
+    let mut storages = Vec::new();
+    for id in world.mask.iter() {
+        let storage = unsafe { world.storages[id].assume_init_ref() };
+        cache.update(id, storage.as_ref());
+        storages.push((id, storage.type_name()));
+    }
+    cache.tree(&storages)
+}
+
+#[test]
+fn test_drill_down_finds_the_diverging_block() {
+    let mut a = test_world(1000);
+    let mut b = test_world(1000);
+    a.run();
+    b.run();
+    assert_eq!(a.hash_tree(), b.hash_tree());
+
+    // Diverge one entity in the third inner block
+    let positions = b.get_storage::<Position>();
+    unsafe { (*positions.get()).set(300, &Position { x: -7 }) };
+
+    let (ta, tb) = (a.hash_tree(), b.hash_tree());
+    assert_ne!(ta.root(), tb.root());
+    assert_eq!(ta.diff_roots(&tb), vec![0]);
+    assert_eq!(ta.diff_blocks(&tb, 0), vec![2]);
+    assert!(HashTree::block_range(0, 2).contains(&300));
+    assert_eq!(ta.block(0, 3), tb.block(0, 3));
+}
+
+#[test]
+fn test_incremental_hashes_match_full_rehash() {
+    let mut world = test_world(600);
+    assert_eq!(world.hash_tree(), full_tree(&world));
+
+    for tick in 0..6u32 {
+        if tick == 2 {
+            let e = world.spawn();
+            world.set(e, &Position { x: 99 });
+        }
+        if tick == 3 {
+            world.destroy(Entity::new(130, 1));
+        }
+        world.run();
+        assert_eq!(world.hash_tree(), full_tree(&world), "tick {}", tick);
+    }
+
+    // Empty blocks contribute nothing
+    assert_eq!(world.hash_tree().root_block(5), 0);
+    assert_eq!(world.hash_tree().blocks(0).count(), 5);
+}
+
+#[test]
+fn test_rollback_invalidates_cached_hashes() {
+    let mut world = test_world(300);
+    world.run();
+    let start = world.current_tick();
+    let before = world.hash_tree();
+
+    world.run();
+    world.run();
+    assert_ne!(world.hash_tree(), before);
+
+    world.resimulate_from(start);
+    assert_eq!(world.hash_tree(), before);
+    assert_eq!(world.hash_tree(), full_tree(&world));
+}
+
+#[test]
+fn test_diff_against_lists_diverging_storages_and_entities() {
+    let mut a = test_world(1000);
+    let mut b = test_world(1000);
+    a.run();
+    b.run();
+    assert!(a.diff_against(&b.hash_report()).is_empty());
+
+    // One value and one velocity the other peer doesn't have
+    let positions = b.get_storage::<Position>();
+    unsafe { (*positions.get()).set(300, &Position { x: -7 }) };
+    let velocities = b.get_storage::<Velocity>();
+    unsafe { (*velocities.get()).set(510, &Velocity { dx: 4 }) };
+
+    // The report survives its wire encoding
+    let mut bytes = Vec::new();
+    b.hash_report().encode(&mut bytes);
+    let theirs = HashReport::decode(&mut Reader::new(&bytes)).unwrap();
+    assert_eq!(theirs.tick(), b.current_tick());
+
+    let report = a.diff_against(&theirs);
+    let names: Vec<&str> = report.storages.iter().map(|s| s.name.as_str()).collect();
+    assert_eq!(names, [std::any::type_name::<Position>(), std::any::type_name::<Velocity>()]);
+
+    // Values differ somewhere in the block: every occupied slot is a suspect
+    let block = &report.storages[0].blocks[..];
+    assert_eq!((block.len(), block[0].ri, block[0].mi), (1, 0, 2));
+    assert_eq!(block[0].entities, HashTree::block_range(0, 2).collect::<Vec<_>>());
+
+    // Occupancy differs: exactly the entity
+    let block = &report.storages[1].blocks[..];
+    assert_eq!((block.len(), block[0].mi, &block[0].entities[..]), (1, 3, &[510][..]));
+    assert!(report.entities().contains(&510));
+}
+
+#[derive(Component, Clone, Default, Debug, PartialEq, Hash)]
+struct Label(String);
+
+#[test]
+fn test_hashes_are_platform_independent() {
+    let mut world = World::new();
+    world.get_storage::<Label>();
+    for name in ["scout", "tank", "medic"] {
+        let e = world.spawn();
+        world.set(e, &Label(name.to_string()));
+    }
+
+    // Pinned: `StateHasher` writes lengths as 64 bits, so every target gets this value
+    let tree = world.hash_tree();
+    assert_eq!(tree.root(), 18377358110148733399);
+
+    // Values count without any registration
+    let mut renamed = World::new();
+    for name in ["scout", "tank", "sniper"] {
+        let e = renamed.spawn();
+        renamed.set(e, &Label(name.to_string()));
+    }
+    assert_ne!(renamed.hash_tree().root(), tree.root());
+}
@@ -0,0 +1,254 @@
+//! Parent/child relations between entities.
+//!
+//! `World::set_parent(child, parent)` records the relation in two components the crate
+//! maintains: `Parent` on the child and `Children` on the parent, in the order children
+//! were attached. Both are ordinary rollback-tracked components, so `world.rollback`
+//! restores the hierarchy along with everything else, and both enter the state hash.
+//!
+//! `World::destroy` destroys every descendant of the entity with it. The
+//! `HierarchySystem`, `Parent`'s cleanup system, runs in `DestroyGroup` before the
+//! `DestroySystem` and detaches the destroyed entities from parents that survive. Children
+//! of entities destroyed without `World::destroy` (e.g. a `Destroyed` marker set directly)
+//! are detached and become roots.
+//!
+//! Create the `Parent` and `Children` storages before building the scheduler, e.g. with
+//! `world.ensure_all_registered()`, so the `HierarchySystem` is scheduled.
+//!
+//! # Example
+//! ```ignore
+//! world.get_storage::<Parent>();
+//! world.get_storage::<Children>();
+//! world.build_scheduler();
+//!
+//! let ship = world.spawn();
+//! let turret = world.spawn();
+//! world.set_parent(turret, ship);
+//! assert_eq!(world.children_of(ship), &[turret]);
+//! assert_eq!(world.ancestors_of(turret).collect::<Vec<_>>(), vec![ship]);
+//!
+//! world.destroy(ship); // the turret goes with it
+//! ```
+//!
+//! Systems join components of an entity's parent with a `ParentView<T>` parameter. The
+//! function receives the parent's `T` as a `View<T>`, and entities without a parent, or
+//! whose parent lacks `T`, are skipped:
+//! ```ignore
+//! system! {
+//!     FollowShipSystem {
+//!         query! {
+//!             fn follow(pos: &mut ViewMut<Position>, ship: ParentView<Position>) {
+//!                 pos.x = ship.x;
+//!             }
+//!         }
+//!     }
+//! }
+//! ```
+
+use crate::component::{Component, Destroyed, Resource};
+use crate::entity::Entity;
+use crate::hashtree::DirtyBlocks;
+use crate::scheduler::PipelineStage;
+use crate::storage::{ComponentStorage, Storage};
+use crate::system::{ComponentCleanupSystem, DestroySystem};
+use crate::world::World;
+use std::any::TypeId;
+use std::cell::UnsafeCell;
+use std::rc::Rc;
+
+/// The parent of an entity, set by `World::set_parent`.
+#[derive(Clone, Copy, Default, Debug, PartialEq, Eq, Hash)]
+pub struct Parent(pub Entity);
+
+impl Resource for Parent {
+    fn type_index() -> usize {
+        static TYPE_INDEX: std::sync::OnceLock<usize> = std::sync::OnceLock::new();
+        *TYPE_INDEX.get_or_init(crate::component::next_id)
+    }
+}
+
+impl Component for Parent {
+    fn stable_name() -> &'static str {
+        concat!(module_path!(), "::Parent")
+    }
+
+    fn state_hasher() -> Option<fn(&Self, &mut crate::statehash::StateHasher)> {
+        use crate::statehash::ViaHashable;
+        crate::statehash::Probe::<Self>::new().state_hasher()
+    }
+
+    fn is_pod() -> bool {
+        true
+    }
+
+    crate::__component_serde_fns!();
+
+    fn cleanup_system(world: &mut World) -> Box<dyn PipelineStage> {
+        Box::new(HierarchySystem::create(world))
+    }
+}
+
+inventory::submit! {
+    crate::component::ComponentRegistration::of::<Parent>()
+}
+
+/// The children of an entity in the order they were attached, maintained by
+/// `World::set_parent`.
+#[derive(crate::component::Component, Clone, Default, Debug, PartialEq, Eq, Hash)]
+pub struct Children(Vec<Entity>);
+
+impl Children {
+    pub fn as_slice(&self) -> &[Entity] {
+        &self.0
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = Entity> + '_ {
+        self.0.iter().copied()
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub(crate) fn push(&mut self, child: Entity) {
+        self.0.push(child);
+    }
+
+    pub(crate) fn remove(&mut self, child: Entity) {
+        self.0.retain(|c| *c != child);
+    }
+}
+
+/// The `T` of an entity's parent, backing the `ParentView<T>` system parameter. Declared as
+/// a read of `Parent`, `Entity` and `T`.
+pub struct ParentView<T: Component> {
+    parents: Rc<UnsafeCell<Storage<Parent>>>,
+    entities: Rc<UnsafeCell<Storage<Entity>>>,
+    values: Rc<UnsafeCell<T::Storage>>,
+}
+
+impl<T: Component> ParentView<T> {
+    pub fn new(world: &mut World) -> Self {
+        ParentView {
+            parents: world.get_storage::<Parent>(),
+            entities: world.get_storage::<Entity>(),
+            values: world.get_storage::<T>(),
+        }
+    }
+
+    /// The `T` of the parent of the entity at `index`, if it has a live parent with one.
+    pub fn get(&self, index: u32) -> Option<&T> {
+        // The system declares the three storages as read, so no writer runs while it does
+        let parents = unsafe { &*self.parents.get() };
+        let entities = unsafe { &*self.entities.get() };
+        let values = unsafe { &*self.values.get() };
+        let Parent(parent) = *parents.get(index)?;
+        if entities.get(parent.index()) != Some(&parent) {
+            return None;
+        }
+        values.get(parent.index())
+    }
+}
+
+/// Detaches destroyed entities from the hierarchy, then cleans up `Parent` like any
+/// component's cleanup system. See the module docs.
+pub struct HierarchySystem {
+    parents: Rc<UnsafeCell<Storage<Parent>>>,
+    children: Rc<UnsafeCell<Storage<Children>>>,
+    destroyed: Rc<UnsafeCell<Storage<Destroyed>>>,
+    entities: Rc<UnsafeCell<Storage<Entity>>>,
+    children_dirty: Rc<DirtyBlocks>,
+    cleanup: ComponentCleanupSystem<Parent>,
+}
+
+unsafe impl Send for HierarchySystem {}
+unsafe impl Sync for HierarchySystem {}
+
+impl PipelineStage for HierarchySystem {
+    fn type_id(&self) -> TypeId {
+        TypeId::of::<Self>()
+    }
+
+    fn run(&self) {
+        let parents = unsafe { &mut *self.parents.get() };
+        let children = unsafe { &mut *self.children.get() };
+        let destroyed = unsafe { &*self.destroyed.get() };
+        let entities = unsafe { &*self.entities.get() };
+
+        let mut dying = Vec::new();
+        destroyed.visit(|index, _| {
+            if let Some(&entity) = entities.get(index) {
+                dying.push(entity);
+            }
+        });
+        let survives = |entity: Entity| {
+            entities.get(entity.index()) == Some(&entity) && destroyed.get(entity.index()).is_none()
+        };
+
+        // Surviving parents forget their destroyed children
+        for &child in &dying {
+            let parent = parents.get(child.index()).map(|parent| parent.0).filter(|&p| survives(p));
+            if let Some(parent) = parent.filter(|p| children.get(p.index()).is_some()) {
+                children.get_mut(parent.index()).remove(child);
+            }
+        }
+
+        // Surviving children of a destroyed parent become roots. `Children` of the
+        // destroyed entities is already gone, so look them up through `Parent`
+        if !dying.is_empty() {
+            let mut orphans = Vec::new();
+            parents.visit(|index, &Parent(parent)| {
+                if !survives(parent) && destroyed.get(index).is_none() {
+                    orphans.push(index);
+                }
+            });
+            for index in orphans {
+                parents.remove(index);
+            }
+        }
+
+        // `Children` was cleaned up earlier in the tick, so the next tick starts with clean
+        // change masks as it does for every other storage
+        self.children_dirty
+            .mark_changed(children.root_changed_mask(), |ri| children.middle_changed_mask(ri));
+        children.clear_changes();
+        self.cleanup.run();
+    }
+
+    fn reads(&self) -> &'static [TypeId] {
+        static READS: &[TypeId] = &[TypeId::of::<Destroyed>(), TypeId::of::<Entity>()];
+        READS
+    }
+
+    fn writes(&self) -> &'static [TypeId] {
+        static WRITES: &[TypeId] = &[TypeId::of::<Parent>(), TypeId::of::<Children>()];
+        WRITES
+    }
+
+    fn before(&self) -> &'static [TypeId] {
+        static BEFORE: &[TypeId] = &[TypeId::of::<DestroySystem>()];
+        BEFORE
+    }
+
+    fn parent(&self) -> Option<TypeId> {
+        Some(TypeId::of::<crate::scheduler::DestroyGroup>())
+    }
+
+    fn create(world: &mut World) -> Self {
+        HierarchySystem {
+            parents: world.get_storage::<Parent>(),
+            children: world.get_storage::<Children>(),
+            destroyed: world.get_storage::<Destroyed>(),
+            entities: world.get_storage::<Entity>(),
+            children_dirty: world.dirty_blocks(Children::type_index()),
+            cleanup: ComponentCleanupSystem::create(world),
+        }
+    }
+}
+
+#[cfg(test)]
+#[path = "hierarchy.tests.rs"]
+mod tests;
@@ -0,0 +1,152 @@
+use super::*;
+use crate::prelude::*;
+
+#[derive(Clone, Default, Debug, PartialEq)]
+struct Siblings(Vec<(Entity, usize)>);
+
+system! {
+    SiblingsSystem {
+        query! {
+            fn record(me: View<Entity>, family: ParentView<Children>, seen: ResMut<Siblings>) {
+                seen.0.push((*me, family.len()));
+            }
+        }
+    }
+}
+
+fn world() -> World {
+    let mut world = World::new();
+    world.get_storage::<Parent>();
+    world.get_storage::<Children>();
+    world.build_scheduler();
+    world
+}
+
+#[test]
+fn test_set_parent_links_both_ways() {
+    let mut world = world();
+    let root = world.spawn();
+    let a = world.spawn();
+    let b = world.spawn();
+    let leaf = world.spawn();
+    world.set_parent(a, root);
+    world.set_parent(b, root);
+    world.set_parent(leaf, a);
+
+    assert_eq!(world.children_of(root), &[a, b]);
+    assert_eq!(world.parent_of(leaf), Some(a));
+    assert_eq!(world.ancestors_of(leaf).collect::<Vec<_>>(), vec![a, root]);
+    assert_eq!(world.descendants_of(root), vec![a, leaf, b]);
+
+    // Reparenting detaches from the old parent
+    world.set_parent(a, b);
+    assert_eq!(world.children_of(root), &[b]);
+    assert_eq!(world.children_of(b), &[a]);
+    assert_eq!(world.ancestors_of(leaf).collect::<Vec<_>>(), vec![a, b, root]);
+
+    world.remove_parent(a);
+    assert_eq!(world.parent_of(a), None);
+    assert!(world.children_of(b).is_empty());
+}
+
+#[test]
+#[should_panic(expected = "its own ancestor")]
+fn test_set_parent_rejects_cycles() {
+    let mut world = world();
+    let a = world.spawn();
+    let b = world.spawn();
+    world.set_parent(b, a);
+    world.set_parent(a, b);
+}
+
+#[test]
+fn test_destroy_takes_descendants_along() {
+    let mut world = world();
+    let root = world.spawn();
+    let ship = world.spawn();
+    let turret = world.spawn();
+    let barrel = world.spawn();
+    world.set_parent(ship, root);
+    world.set_parent(turret, ship);
+    world.set_parent(barrel, turret);
+    world.run();
+
+    world.destroy(ship);
+    world.run();
+
+    assert!(world.contains(root));
+    assert!(!world.contains(ship));
+    assert!(!world.contains(turret));
+    assert!(!world.contains(barrel));
+    assert!(world.children_of(root).is_empty());
+    assert_eq!(world.storage_ref::<Children>().unwrap().get(turret.index()), None);
+    assert_eq!(world.storage_ref::<Parent>().unwrap().get(turret.index()), None);
+}
+
+#[test]
+fn test_children_of_directly_destroyed_parent_become_roots() {
+    let mut world = world();
+    let parent = world.spawn();
+    let child = world.spawn();
+    world.set_parent(child, parent);
+    world.run();
+
+    world.set(parent, &Destroyed {});
+    world.run();
+
+    assert!(!world.contains(parent));
+    assert!(world.contains(child));
+    assert_eq!(world.parent_of(child), None);
+}
+
+#[test]
+fn test_hierarchy_rolls_back() {
+    let mut world = world();
+    let root = world.spawn();
+    let a = world.spawn();
+    let b = world.spawn();
+    world.set_parent(a, root);
+    world.set_parent(b, root);
+    let linked = world.current_tick();
+    world.run();
+
+    world.set_parent(b, a);
+    world.run();
+    world.destroy(a);
+    world.run();
+    assert!(!world.contains(b));
+    assert!(world.children_of(root).is_empty());
+
+    world.rollback(linked);
+    assert!(world.contains(a));
+    assert!(world.contains(b));
+    assert_eq!(world.children_of(root), &[a, b]);
+    assert!(world.children_of(a).is_empty());
+    assert_eq!(world.parent_of(b), Some(root));
+}
+
+#[test]
+fn test_parent_view_joins_parent_components() {
+    let mut world = World::new();
+    world.get_storage::<Parent>();
+    world.get_storage::<Children>();
+    world.add_system::<SiblingsSystem>();
+    world.build_scheduler();
+    world.insert_resource(Siblings::default());
+
+    let root = world.spawn();
+    let a = world.spawn();
+    let b = world.spawn();
+    let lone = world.spawn();
+    let leaf = world.spawn();
+    world.set_parent(a, root);
+    world.set_parent(b, root);
+    world.set_parent(leaf, lone);
+    assert_eq!(SiblingsSystem::create(&mut world).count(), 3);
+
+    // Roots have no parent, and `lone` loses the component the join asks for
+    world.remove::<Children>(lone);
+    assert_eq!(SiblingsSystem::create(&mut world).count(), 2);
+    world.run();
+    assert_eq!(world.resource::<Siblings>().unwrap().0, vec![(a, 2), (b, 2)]);
+}
@@ -0,0 +1,254 @@
+//! Bridge for streaming component data from loader threads into the simulation.
+//!
+//! Asset loaders run on their own threads and finish at unpredictable times, so they can't
+//! touch storages directly. Instead they push `(Entity, T)` pairs through an
+//! `IngestSender<T>`, which is `Send + Clone` and hands back a `Ticket` per item. Once per
+//! tick the `IngestSystem<T>` (in `InitializationGroup`) drains everything that arrived and
+//! applies it through `Storage::set`, so ingested values get normal change tracking and
+//! rollback snapshots.
+//!
+//! Arrival is nondeterministic, but application is recorded: every drained batch is logged
+//! under the tick that applied it. When the world rolls back and resimulates a tick, the
+//! system replays that tick's recorded batch instead of draining the queue, so
+//! resimulation reproduces the original result. Items that arrive during resimulation wait
+//! for the next new tick.
+//!
+//! # Example
+//! ```ignore
+//! let loader = world.ingest::<Mesh>();
+//! std::thread::spawn(move || {
+//!     let ticket = loader.send(entity, decode_mesh(bytes));
+//! });
+//! ```
+
+use crate::component::Component;
+use crate::entity::Entity;
+use crate::scheduler::PipelineStage;
+use crate::storage::{ComponentStorage, Storage};
+use crate::tick::Tick;
+use crate::world::World;
+use std::any::{Any, TypeId};
+use std::cell::{Cell, UnsafeCell};
+use std::collections::{BTreeMap, HashMap};
+use std::rc::Rc;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{Receiver, Sender, channel};
+
+/// Receipt for one ingested item, used to look up when it was applied.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub struct Ticket(pub u64);
+
+/// Thread-safe handle that queues component values for the next tick.
+pub struct IngestSender<T> {
+    tx: Sender<(Ticket, Entity, T)>,
+    next_ticket: Arc<AtomicU64>,
+}
+
+impl<T> Clone for IngestSender<T> {
+    fn clone(&self) -> Self {
+        IngestSender {
+            tx: self.tx.clone(),
+            next_ticket: self.next_ticket.clone(),
+        }
+    }
+}
+
+impl<T> IngestSender<T> {
+    /// Queues `value` to be set on `entity` at the start of the next tick.
+    /// If the world has been dropped the value is discarded.
+    pub fn send(&self, entity: Entity, value: T) -> Ticket {
+        let ticket = Ticket(self.next_ticket.fetch_add(1, Ordering::Relaxed));
+        let _ = self.tx.send((ticket, entity, value));
+        ticket
+    }
+}
+
+/// What happened to an ingested item when its tick ran.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum IngestStatus {
+    /// The value was set on the entity at this tick.
+    Applied(Tick),
+    /// The entity no longer existed (or had a different generation) at this tick.
+    Dropped(Tick),
+}
+
+/// Record of every batch applied so far, keyed by the tick that applied it.
+pub struct IngestLog<T> {
+    batches: BTreeMap<Tick, Vec<(Ticket, Entity, T)>>,
+    status: HashMap<Ticket, IngestStatus>,
+    /// Newest tick that drained the queue. Ticks up to and including it are replays.
+    last_drained: Option<Tick>,
+}
+
+impl<T> IngestLog<T> {
+    fn new() -> Self {
+        IngestLog {
+            batches: BTreeMap::new(),
+            status: HashMap::new(),
+            last_drained: None,
+        }
+    }
+
+    /// Status of an item, or `None` if it has not been drained yet.
+    pub fn status(&self, ticket: Ticket) -> Option<IngestStatus> {
+        self.status.get(&ticket).copied()
+    }
+
+    /// Items applied at `tick`, in application order.
+    pub fn batch(&self, tick: Tick) -> &[(Ticket, Entity, T)] {
+        self.batches.get(&tick).map(Vec::as_slice).unwrap_or(&[])
+    }
+}
+
+/// Shared state between the world and the `IngestSystem<T>`.
+pub struct IngestQueue<T> {
+    sender: IngestSender<T>,
+    rx: Receiver<(Ticket, Entity, T)>,
+    log: UnsafeCell<IngestLog<T>>,
+    /// World tick of the run in progress, set by the world before the scheduler runs.
+    tick: Cell<Tick>,
+}
+
+impl<T> IngestQueue<T> {
+    pub fn new() -> Self {
+        let (tx, rx) = channel();
+        IngestQueue {
+            sender: IngestSender {
+                tx,
+                next_ticket: Arc::new(AtomicU64::new(0)),
+            },
+            rx,
+            log: UnsafeCell::new(IngestLog::new()),
+            tick: Cell::new(Tick::new(0)),
+        }
+    }
+
+    /// Returns a new sending handle.
+    pub fn sender(&self) -> IngestSender<T> {
+        self.sender.clone()
+    }
+
+    /// The record of applied batches. Must not be called while the scheduler is running.
+    pub fn log(&self) -> &IngestLog<T> {
+        unsafe { &*self.log.get() }
+    }
+}
+
+impl<T> Default for IngestQueue<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Type-erased access to ingest queues so the world can stamp them with the current tick.
+pub trait IngestLike: Any {
+    fn begin_tick(&self, tick: Tick);
+    fn as_any_rc(self: Rc<Self>) -> Rc<dyn Any>;
+}
+
+impl<T: 'static> IngestLike for IngestQueue<T> {
+    fn begin_tick(&self, tick: Tick) {
+        self.tick.set(tick);
+    }
+
+    fn as_any_rc(self: Rc<Self>) -> Rc<dyn Any> {
+        self
+    }
+}
+
+/// Applies queued values at a deterministic point each tick, see the module docs.
+pub struct IngestSystem<T: Component> {
+    pub storage: Rc<UnsafeCell<T::Storage>>,
+    pub entity_storage: Rc<UnsafeCell<Storage<Entity>>>,
+    pub queue: Rc<IngestQueue<T>>,
+}
+
+unsafe impl<T: Component> Send for IngestSystem<T> {}
+unsafe impl<T: Component> Sync for IngestSystem<T> {}
+
+impl<T: Component> IngestSystem<T> {
+    fn apply(
+        storage: &mut T::Storage,
+        entities: &Storage<Entity>,
+        entity: Entity,
+        value: &T,
+    ) -> bool {
+        match entities.get(entity.index()) {
+            Some(current) if current.generation() == entity.generation() => {
+                storage.set(entity.index(), value);
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+impl<T: Component> PipelineStage for IngestSystem<T> {
+    fn type_id(&self) -> TypeId {
+        TypeId::of::<Self>()
+    }
+
+    fn run(&self) {
+        let storage = unsafe { &mut *self.storage.get() };
+        let entities = unsafe { &*self.entity_storage.get() };
+        let log = unsafe { &mut *self.queue.log.get() };
+        let tick = self.queue.tick.get();
+
+        // Resimulating a tick that already drained the queue: replay its recorded batch
+        if log.last_drained.is_some_and(|last| !tick.is_after(last)) {
+            if let Some(batch) = log.batches.get(&tick) {
+                for (_, entity, value) in batch {
+                    Self::apply(storage, entities, *entity, value);
+                }
+            }
+            return;
+        }
+
+        log.last_drained = Some(tick);
+
+        let mut batch: Vec<_> = self.queue.rx.try_iter().collect();
+        if batch.is_empty() {
+            return;
+        }
+
+        // Tickets are handed out in send order across all threads
+        batch.sort_by_key(|(ticket, _, _)| *ticket);
+
+        for (ticket, entity, value) in &batch {
+            let status = if Self::apply(storage, entities, *entity, value) {
+                IngestStatus::Applied(tick)
+            } else {
+                IngestStatus::Dropped(tick)
+            };
+            log.status.insert(*ticket, status);
+        }
+
+        log.batches.insert(tick, batch);
+    }
+
+    fn reads(&self) -> &'static [TypeId] {
+        static READS: &[TypeId] = &[TypeId::of::<Entity>()];
+        READS
+    }
+
+    fn writes(&self) -> &'static [TypeId] {
+        crate::scheduler::type_id_slice::<T>()
+    }
+
+    fn parent(&self) -> Option<TypeId> {
+        Some(TypeId::of::<crate::scheduler::InitializationGroup>())
+    }
+
+    fn create(world: &mut World) -> Self {
+        Self {
+            storage: world.get_storage::<T>(),
+            entity_storage: world.get_storage::<Entity>(),
+            queue: world.ingest_queue::<T>(),
+        }
+    }
+}
+
+#[cfg(test)]
+#[path = "ingest.tests.rs"]
+mod tests;
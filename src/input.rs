@@ -0,0 +1,271 @@
+//! Per-player inputs with prediction, read by systems through `PlayerInput<I>`.
+//!
+//! `World::input_buffer::<Pad>()` holds the inputs of every player for every tick. Inputs
+//! are confirmed with `World::confirm_input(player, tick, pad)` as they become known, in
+//! any order and for ticks not simulated yet. At the start of each tick the buffer freezes
+//! the inputs the tick uses: the confirmed one for each player, or else a prediction that
+//! repeats the player's latest input confirmed before the tick, or `I::default()`.
+//!
+//! Systems read the frozen inputs with a `pads: PlayerInput<Pad>` parameter, declared as a
+//! read of the buffer for conflict detection. When an input is confirmed for a simulated
+//! tick and differs from the one the tick used, the buffer records the misprediction and
+//! `World::sync_inputs` resimulates from that tick up to the present, re-predicting every
+//! later tick. Inputs confirmed for ticks that left the rollback window are kept for
+//! prediction but can no longer correct the past.
+//!
+//! # Example
+//! ```ignore
+//! world.input_buffer::<Pad>().set_players(2);
+//!
+//! system! {
+//!     SteerSystem {
+//!         query! {
+//!             fn steer(ship: &mut ViewMut<Ship>, pads: PlayerInput<Pad>) {
+//!                 ship.turn += pads.get(ship.player).turn;
+//!             }
+//!         }
+//!     }
+//! }
+//!
+//! loop {
+//!     world.confirm_input(LOCAL, world.current_tick(), read_pad())?;
+//!     for (tick, pad) in socket.receive() {
+//!         world.confirm_input(REMOTE, tick, pad)?;
+//!     }
+//!     world.sync_inputs();
+//!     world.run();
+//! }
+//! ```
+
+use crate::tick::Tick;
+use std::any::Any;
+use std::cell::{Cell, UnsafeCell};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::rc::Rc;
+
+/// Why an input was refused.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum InputError {
+    /// The player index is not below `players()`.
+    UnknownPlayer(usize),
+    /// A different input was already confirmed for the player and tick.
+    ConflictingInput { player: usize, tick: Tick },
+}
+
+impl fmt::Display for InputError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InputError::UnknownPlayer(player) => write!(f, "unknown player {}", player),
+            InputError::ConflictingInput { player, tick } => write!(
+                f,
+                "conflicting input for player {} at tick {}",
+                player,
+                tick.value()
+            ),
+        }
+    }
+}
+
+impl std::error::Error for InputError {}
+
+/// Rollback-aware inputs of every player of one world, see the module docs.
+pub struct InputBuffer<I> {
+    /// Confirmed inputs, `confirmed[player][tick]`.
+    confirmed: UnsafeCell<Vec<BTreeMap<Tick, I>>>,
+    /// Inputs each simulated tick used, with whether each was predicted.
+    used: UnsafeCell<BTreeMap<Tick, Vec<(I, bool)>>>,
+    /// Earliest simulated tick that used a wrong prediction.
+    mispredicted: Cell<Option<Tick>>,
+    /// World tick of the run in progress, set by the world before the scheduler runs.
+    tick: Cell<Tick>,
+}
+
+impl<I: Clone + PartialEq + Default> InputBuffer<I> {
+    pub fn new() -> Self {
+        InputBuffer {
+            confirmed: UnsafeCell::new(Vec::new()),
+            used: UnsafeCell::new(BTreeMap::new()),
+            mispredicted: Cell::new(None),
+            tick: Cell::new(Tick::new(0)),
+        }
+    }
+
+    /// Sets the number of players. Inputs of removed players are dropped.
+    pub fn set_players(&self, players: usize) {
+        unsafe { (*self.confirmed.get()).resize_with(players, BTreeMap::new) };
+    }
+
+    pub fn players(&self) -> usize {
+        unsafe { (*self.confirmed.get()).len() }
+    }
+
+    /// The input `player` confirmed for `tick`, if any.
+    pub fn confirmed(&self, player: usize, tick: Tick) -> Option<I> {
+        let confirmed = unsafe { &*self.confirmed.get() };
+        confirmed.get(player)?.get(&tick).cloned()
+    }
+
+    /// Confirms the input of `player` for `tick`. Confirming the same input again is fine.
+    /// If `tick` was simulated with a different prediction, it is recorded as mispredicted
+    /// until `World::sync_inputs`.
+    pub fn confirm(&self, player: usize, tick: Tick, input: I) -> Result<(), InputError> {
+        let confirmed = unsafe { &mut *self.confirmed.get() };
+        let inputs = confirmed
+            .get_mut(player)
+            .ok_or(InputError::UnknownPlayer(player))?;
+        if let Some(existing) = inputs.get(&tick) {
+            if *existing != input {
+                return Err(InputError::ConflictingInput { player, tick });
+            }
+            return Ok(());
+        }
+
+        let used = unsafe { &*self.used.get() };
+        if used
+            .get(&tick)
+            .and_then(|inputs| inputs.get(player))
+            .is_some_and(|(used, _)| *used != input)
+        {
+            let earliest = self.mispredicted.get().map_or(tick, |t| t.min(tick));
+            self.mispredicted.set(Some(earliest));
+        }
+        inputs.insert(tick, input);
+        Ok(())
+    }
+
+    /// Earliest simulated tick that used a wrong prediction, if any.
+    pub fn mispredicted(&self) -> Option<Tick> {
+        self.mispredicted.get()
+    }
+
+    /// The inputs simulated tick `tick` used, confirmed or predicted.
+    pub fn used(&self, tick: Tick) -> Option<PlayerInput<'_, I>> {
+        let used = unsafe { &*self.used.get() };
+        let inputs = used.get(&tick)?;
+        Some(PlayerInput { inputs })
+    }
+
+    /// Read access for a `PlayerInput<I>` parameter.
+    pub fn view(&self) -> PlayerInput<'_, I> {
+        self.used(self.tick.get())
+            .expect("inputs are frozen when the tick begins")
+    }
+
+    /// The input of `player` for `tick`: the confirmed one, or the latest one confirmed
+    /// before it repeated.
+    fn predict(inputs: &BTreeMap<Tick, I>, tick: Tick) -> (I, bool) {
+        match inputs.range(..=tick).next_back() {
+            Some((&confirmed, input)) => (input.clone(), confirmed != tick),
+            None => (I::default(), true),
+        }
+    }
+}
+
+impl<I: Clone + PartialEq + Default> Default for InputBuffer<I> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Type-erased access to input buffers so the world can freeze, roll back and
+/// resimulate them.
+pub trait InputLike: Any {
+    fn begin_tick(&self, tick: Tick, oldest: Tick);
+    fn rollback(&self, target_tick: Tick);
+    /// Takes the earliest mispredicted tick, leaving none.
+    fn take_mispredicted(&self) -> Option<Tick>;
+    /// Drops every input, confirmed or used, keeping the number of players.
+    fn clear(&self);
+    fn as_any_rc(self: Rc<Self>) -> Rc<dyn Any>;
+}
+
+impl<I: Clone + PartialEq + Default + 'static> InputLike for InputBuffer<I> {
+    fn begin_tick(&self, tick: Tick, oldest: Tick) {
+        self.tick.set(tick);
+        let confirmed = unsafe { &mut *self.confirmed.get() };
+        let used = unsafe { &mut *self.used.get() };
+
+        // Ticks before the window can never be resimulated; the latest input confirmed
+        // before it is kept for predictions
+        used.retain(|logged, _| !logged.is_before(oldest));
+        for inputs in confirmed.iter_mut() {
+            let keep = inputs.range(..oldest).next_back().map(|(&t, _)| t);
+            if let Some(keep) = keep {
+                inputs.retain(|&t, _| !t.is_before(keep));
+            }
+        }
+
+        let frame = confirmed
+            .iter()
+            .map(|inputs| Self::predict(inputs, tick))
+            .collect();
+        used.insert(tick, frame);
+    }
+
+    fn rollback(&self, target_tick: Tick) {
+        // Later ticks are simulated again with fresh predictions
+        unsafe { (*self.used.get()).retain(|tick, _| !tick.is_after(target_tick)) };
+        if self
+            .mispredicted
+            .get()
+            .is_some_and(|tick| tick.is_after(target_tick))
+        {
+            self.mispredicted.set(None);
+        }
+    }
+
+    fn take_mispredicted(&self) -> Option<Tick> {
+        self.mispredicted.take()
+    }
+
+    fn clear(&self) {
+        unsafe {
+            (*self.confirmed.get()).iter_mut().for_each(BTreeMap::clear);
+            (*self.used.get()).clear();
+        }
+        self.mispredicted.set(None);
+    }
+
+    fn as_any_rc(self: Rc<Self>) -> Rc<dyn Any> {
+        self
+    }
+}
+
+/// The inputs of every player for the tick being simulated, indexed by player.
+pub struct PlayerInput<'a, I> {
+    inputs: &'a [(I, bool)],
+}
+
+impl<'a, I> PlayerInput<'a, I> {
+    /// The input of `player`.
+    ///
+    /// # Panics
+    /// Panics if `player` is not below `len()`.
+    pub fn get(&self, player: usize) -> &'a I {
+        &self.inputs[player].0
+    }
+
+    /// Whether the input of `player` is a prediction rather than a confirmed input.
+    pub fn is_predicted(&self, player: usize) -> bool {
+        self.inputs[player].1
+    }
+
+    /// The inputs in player order.
+    pub fn iter(&self) -> impl Iterator<Item = &'a I> + 'a {
+        self.inputs.iter().map(|(input, _)| input)
+    }
+
+    /// Number of players.
+    pub fn len(&self) -> usize {
+        self.inputs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.inputs.is_empty()
+    }
+}
+
+#[cfg(test)]
+#[path = "input.tests.rs"]
+mod tests;
@@ -0,0 +1,202 @@
+use super::*;
+use crate::prelude::*;
+
+#[derive(Component, Default, Clone, Debug, PartialEq, Hash)]
+struct Ship {
+    player: usize,
+    x: i32,
+}
+
+system! {
+    SteerSystem {
+        query! {
+            fn steer(ship: &mut ViewMut<Ship>, pads: PlayerInput<i32>) {
+                ship.x += *pads.get(ship.player);
+            }
+        }
+    }
+}
+
+fn world(players: usize) -> World {
+    let mut world = World::new();
+    world.input_buffer::<i32>().set_players(players);
+    world.add_system::<SteerSystem>();
+    world.build_scheduler();
+    for player in 0..players {
+        let e = world.spawn();
+        world.set(e, &Ship { player, x: 0 });
+    }
+    world
+}
+
+fn positions(world: &World) -> Vec<i32> {
+    world
+        .iter_entities()
+        .map(|e| world.get::<Ship>(e).unwrap().x)
+        .collect()
+}
+
+fn tick(start: Tick, offset: u32) -> Tick {
+    Tick::new(start.value() + offset)
+}
+
+#[test]
+fn test_predicts_and_resimulates_late_inputs() {
+    let mut world = world(2);
+    let start = world.current_tick();
+    world.confirm_input(1, start, 5).unwrap();
+    for t in 0..4 {
+        world.confirm_input(0, tick(start, t), 1).unwrap();
+        world.run();
+    }
+    // Player 1 repeats its last confirmed input
+    let buffer = world.input_buffer::<i32>();
+    let used = buffer.used(tick(start, 3)).unwrap();
+    assert_eq!(used.iter().copied().collect::<Vec<_>>(), vec![1, 5]);
+    assert!(!used.is_predicted(0) && used.is_predicted(1));
+    assert_eq!(positions(&world), vec![4, 20]);
+    assert_eq!(world.sync_inputs(), 0);
+
+    // The prediction held at tick 1 and broke at tick 2
+    world.confirm_input(1, tick(start, 1), 5).unwrap();
+    world.confirm_input(1, tick(start, 2), -2).unwrap();
+    assert_eq!(buffer.mispredicted(), Some(tick(start, 2)));
+    assert_eq!(world.sync_inputs(), 2);
+    assert_eq!(world.current_tick(), tick(start, 4));
+    assert_eq!(positions(&world), vec![4, 6]);
+    assert_eq!(buffer.used(tick(start, 3)).unwrap().get(1), &-2);
+
+    // Matching and repeated inputs don't resimulate
+    world.confirm_input(1, tick(start, 3), -2).unwrap();
+    world.confirm_input(1, tick(start, 2), -2).unwrap();
+    assert_eq!(world.sync_inputs(), 0);
+}
+
+#[test]
+fn test_matches_a_world_with_every_input_on_time() {
+    let script = |player: usize, t: u32| ((t / 2 + player as u32) % 3) as i32 - 1;
+    let mut late = world(2);
+    let mut on_time = world(2);
+    let start = late.current_tick();
+
+    for t in 0..20 {
+        for player in 0..2 {
+            on_time
+                .confirm_input(player, tick(start, t), script(player, t))
+                .unwrap();
+        }
+        on_time.run();
+
+        // Player 1's inputs arrive three ticks late
+        late.confirm_input(0, tick(start, t), script(0, t)).unwrap();
+        if t >= 3 {
+            late.confirm_input(1, tick(start, t - 3), script(1, t - 3))
+                .unwrap();
+        }
+        late.sync_inputs();
+        late.run();
+    }
+    for t in 17..20 {
+        late.confirm_input(1, tick(start, t), script(1, t)).unwrap();
+    }
+    assert!(late.sync_inputs() > 0);
+
+    assert_eq!(positions(&late), positions(&on_time));
+    assert_eq!(late.compute_state_hash(), on_time.compute_state_hash());
+}
+
+#[test]
+fn test_rejects_unknown_players_and_conflicts() {
+    let mut world = world(1);
+    let start = world.current_tick();
+    assert_eq!(
+        world.confirm_input(1, start, 3),
+        Err(InputError::UnknownPlayer(1))
+    );
+    world.confirm_input(0, start, 3).unwrap();
+    assert_eq!(
+        world.confirm_input(0, start, 4),
+        Err(InputError::ConflictingInput {
+            player: 0,
+            tick: start
+        })
+    );
+    assert_eq!(world.input_buffer::<i32>().confirmed(0, start), Some(3));
+}
+
+impl crate::wire::Wire for Ship {
+    fn encode(&self, out: &mut Vec<u8>) {
+        (self.player as u32).encode(out);
+        self.x.encode(out);
+    }
+
+    fn decode(reader: &mut crate::wire::Reader<'_>) -> Result<Self, crate::wire::DecodeError> {
+        Ok(Ship {
+            player: u32::decode(reader)? as usize,
+            x: i32::decode(reader)?,
+        })
+    }
+}
+
+#[test]
+fn test_reconcile_resimulates_from_server_state() {
+    let mut server = world(2);
+    let mut client = world(2);
+    let start = server.current_tick();
+
+    // The client only knows its own inputs and keeps predicting player 1 at 5
+    client.confirm_input(1, start, 5).unwrap();
+    for t in 0..5 {
+        client.confirm_input(0, tick(start, t), 1).unwrap();
+        client.run();
+    }
+    assert_eq!(positions(&client), vec![5, 25]);
+
+    for t in 0..3 {
+        server.confirm_input(0, tick(start, t), 1).unwrap();
+        server.confirm_input(1, tick(start, t), if t < 2 { 5 } else { -5 }).unwrap();
+        server.run();
+    }
+    let at = server.current_tick();
+    let snapshot = server.save_snapshot();
+
+    assert_eq!(client.reconcile(at, &snapshot), Ok(2));
+    assert_eq!(client.current_tick(), tick(start, 5));
+    assert_eq!(positions(&client), vec![5, 15]);
+
+    // A broken snapshot leaves the prediction as it was: zero the presence mask of the
+    // first block, after the header, section framing and block index
+    let mut corrupt = snapshot.clone();
+    corrupt[32..48].fill(0);
+    assert!(matches!(
+        client.reconcile(at, &corrupt),
+        Err(crate::wire::DecodeError::InvalidMask { .. })
+    ));
+    assert_eq!(
+        client.reconcile(tick(at, 1), &snapshot),
+        Err(crate::wire::DecodeError::BaseMismatch {
+            expected: tick(at, 1),
+            found: at,
+        })
+    );
+    assert_eq!(positions(&client), vec![5, 15]);
+
+    // Nor does a snapshot from ahead of the client, and a broken one creates no storages
+    for t in 3..7 {
+        server.confirm_input(0, tick(start, t), 1).unwrap();
+        server.confirm_input(1, tick(start, t), 5).unwrap();
+        server.run();
+    }
+    let ahead = server.current_tick();
+    assert_eq!(
+        client.reconcile(ahead, &server.save_snapshot()),
+        Err(crate::wire::DecodeError::TickAhead {
+            tick: ahead,
+            current: tick(start, 5),
+        })
+    );
+    assert_eq!(positions(&client), vec![5, 15]);
+    let mut fresh = World::new();
+    assert!(fresh.load_snapshot(&corrupt).is_err());
+    assert!(fresh.storage_ref::<Ship>().is_none());
+}
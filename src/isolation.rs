@@ -0,0 +1,68 @@
+//! Per-system panic isolation (requires the `panic-isolation` feature).
+//!
+//! Without it, a panic in one user system unwinds out of `World::run`, through the thread
+//! pool, and leaves the world mid-tick. With the feature enabled the scheduler runs every
+//! system under `catch_unwind`:
+//!
+//! - the first failing system is recorded, by its place in the schedule when several fail
+//!   in the same wavefront, so parallel and sequential runs report the same one;
+//! - the rest of its wavefront finishes, the remaining wavefronts are skipped, and the
+//!   world closes the tick without surfacing its effects;
+//! - the world is tainted with a `TickError` naming the system and tick. A tainted world
+//!   refuses to run until `World::rollback` or `World::resimulate_from` restores a
+//!   known-good tick, which clears the taint.
+//!
+//! `World::try_run` returns the error; `World::run` keeps its signature and leaves it in
+//! `World::tainted`.
+//!
+//! # Example
+//! ```ignore
+//! if let Err(error) = world.try_run() {
+//!     log::error!("{}", error);
+//!     world.rollback(last_confirmed);
+//! }
+//! ```
+
+use crate::tick::Tick;
+use std::any::Any;
+use std::fmt;
+
+/// A tick cut short by a panicking system.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct TickError {
+    /// Name of the system that panicked.
+    pub system: &'static str,
+    /// The tick it was simulating.
+    pub tick: Tick,
+    /// The panic message, if it was a string.
+    pub message: String,
+}
+
+impl fmt::Display for TickError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "system {} panicked in tick {}: {}",
+            self.system,
+            self.tick.value(),
+            self.message
+        )
+    }
+}
+
+impl std::error::Error for TickError {}
+
+/// The message of a caught panic payload.
+pub(crate) fn panic_message(payload: &(dyn Any + Send)) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "non-string panic payload".to_string()
+    }
+}
+
+#[cfg(test)]
+#[path = "isolation.tests.rs"]
+mod tests;
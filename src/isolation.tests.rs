@@ -0,0 +1,92 @@
+use super::*;
+use crate::prelude::*;
+
+#[derive(Component, Default, Clone, Debug, PartialEq)]
+struct Fuel {
+    value: i32,
+}
+
+#[derive(Component, Default, Clone, Debug, PartialEq)]
+struct Heat {
+    value: i32,
+}
+
+#[derive(Component, Default, Clone, Debug, PartialEq)]
+struct Burned {
+    value: i32,
+}
+
+system! {
+    FragileSystem {
+        query! {
+            fn burn(fuel: &mut ViewMut<Fuel>) {
+                fuel.value += 1;
+                if fuel.value == 3 {
+                    panic!("out of fuel");
+                }
+            }
+        }
+    }
+}
+
+system! {
+    SiblingSystem {
+        query! {
+            fn warm(heat: &mut ViewMut<Heat>) {
+                heat.value += 1;
+            }
+        }
+    }
+}
+
+system! {
+    AfterSystem {
+        query! {
+            fn tally(fuel: View<Fuel>, burned: &mut ViewMut<Burned>) {
+                burned.value = fuel.value;
+            }
+        }
+    }
+}
+
+fn world() -> (World, Entity) {
+    let mut world = World::new();
+    world.add_system::<FragileSystem>();
+    world.add_system::<SiblingSystem>();
+    world.add_system::<AfterSystem>();
+    world.build_scheduler();
+    let e = world.spawn();
+    world.set(e, &Fuel { value: 0 });
+    world.set(e, &Heat { value: 0 });
+    world.set(e, &Burned { value: 0 });
+    (world, e)
+}
+
+#[test]
+fn test_panicking_system_taints_world_until_rollback() {
+    let (mut world, e) = world();
+    assert!(world.try_run().is_ok());
+    assert!(world.try_run().is_ok());
+
+    let error = world.try_run().unwrap_err();
+    assert_eq!(error.tick, Tick::new(2));
+    assert!(error.system.contains("FragileSystem"));
+    assert_eq!(error.message, "out of fuel");
+    assert!(error.to_string().contains("tick 2"));
+    assert_eq!(world.tainted(), Some(&error));
+
+    // The wavefront finished, later wavefronts were skipped
+    assert_eq!(world.get::<Heat>(e).unwrap().value, 3);
+    assert_eq!(world.get::<Burned>(e).unwrap().value, 2);
+
+    // A tainted world doesn't run
+    let tick = world.current_tick();
+    assert_eq!(world.try_run_sequential(), Err(error));
+    assert_eq!(world.current_tick(), tick);
+
+    world.rollback(Tick::new(1));
+    assert!(world.tainted().is_none());
+    world.set(e, &Fuel { value: 10 });
+    assert!(world.try_run().is_ok());
+    assert_eq!(world.get::<Burned>(e).unwrap().value, 11);
+}
@@ -0,0 +1,200 @@
+//! A world-level journal of component changes, for mirroring the world into something
+//! outside it (a database, an editor, a renderer scene).
+//!
+//! `World::record_changes(true)` starts the journal. From then on every simulated tick
+//! appends one `Change` per entity and component type that was added, updated or removed,
+//! and `World::drain_changes()` hands them out in tick order. Within a tick changes are
+//! ordered by component type index, then additions and updates before removals, then
+//! entity index.
+//!
+//! The journal reads the same change masks `Changed=[...]` filters do, so it costs nothing
+//! while off and one walk of the changed blocks per component while on:
+//! - the component cleanup systems record the occupied changed slots as `Added` (the slot
+//!   was empty at the start of the tick) or `Updated`, before they clear the masks;
+//! - the world records `Removed` from the storages' removal logs once the tick ended, which
+//!   covers `World::remove`, `Remove=[...]` queries and destroyed entities alike.
+//!
+//! A component removed and set again in the same tick is reported as `Added`, so mirrors
+//! should treat `Added` and `Updated` alike as "store the current value". Temporary
+//! components are not journaled, and neither are ticks simulated with `step()` without a
+//! scheduler. A rollback drops the changes of the ticks it undoes that weren't drained yet,
+//! and a tick simulated again replaces its undrained changes. Changes already drained are
+//! not reverted, so mirrors resync after a rollback (e.g. from the `RollbackReport`).
+//!
+//! # Example
+//! ```ignore
+//! world.record_changes(true);
+//! world.run();
+//! for change in world.drain_changes() {
+//!     match change.kind {
+//!         ChangeKind::Added | ChangeKind::Updated => mirror.store(change.entity, change.name),
+//!         ChangeKind::Removed => mirror.delete(change.entity, change.name),
+//!     }
+//! }
+//! ```
+
+use crate::entity::Entity;
+use crate::storage::ComponentStorage;
+use crate::tick::Tick;
+use std::any::TypeId;
+use std::cell::{Cell, UnsafeCell};
+use std::collections::BTreeMap;
+use std::rc::Rc;
+
+/// What happened to a component.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ChangeKind {
+    /// The entity didn't have the component at the start of the tick.
+    Added,
+    /// The component was written, or set again while present.
+    Updated,
+    /// The component was removed, or discarded with its destroyed entity.
+    Removed,
+}
+
+/// One journal entry, see the module docs.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Change {
+    /// The tick the change happened in.
+    pub tick: Tick,
+    pub entity: Entity,
+    /// `TypeId` of the component type.
+    pub component: TypeId,
+    /// Name of the component type.
+    pub name: &'static str,
+    pub kind: ChangeKind,
+}
+
+/// Additions and updates of one storage recorded by its cleanup system during a tick.
+pub struct ChangeLog {
+    enabled: Cell<bool>,
+    /// Entity index and kind of every recorded change.
+    entries: UnsafeCell<Vec<(u32, ChangeKind)>>,
+}
+
+// SAFETY: only the owning cleanup system writes it while the scheduler runs, and the world
+// only reads it between ticks
+unsafe impl Send for ChangeLog {}
+unsafe impl Sync for ChangeLog {}
+
+impl ChangeLog {
+    fn new(enabled: bool) -> Self {
+        ChangeLog {
+            enabled: Cell::new(enabled),
+            entries: UnsafeCell::new(Vec::new()),
+        }
+    }
+
+    /// Whether the world is recording changes.
+    pub fn is_enabled(&self) -> bool {
+        self.enabled.get()
+    }
+
+    /// Records every occupied slot in the change masks of `storage` as `Added` or `Updated`.
+    /// Slots changed and then emptied are left to the removal log, and changes kept from
+    /// earlier ticks (see `World::keep_changes`) were recorded in their own tick.
+    pub fn record_changed<S: ComponentStorage>(&self, storage: &S) {
+        let entries = unsafe { &mut *self.entries.get() };
+        let mut root = storage.root_changed_mask();
+
+        while root != 0 {
+            let ri = root.trailing_zeros();
+            root &= !(1u128 << ri);
+
+            let mut middle = storage.middle_changed_mask(ri);
+            while middle != 0 {
+                let mi = middle.trailing_zeros();
+                middle &= !(1u128 << mi);
+
+                let present = storage.inner_mask(ri, mi);
+                let added = storage.inner_added_mask(ri, mi) & present;
+                let mut changed = storage.inner_pending_mask(ri, mi) & present;
+                while changed != 0 {
+                    let ii = changed.trailing_zeros();
+                    changed &= !(1u128 << ii);
+
+                    let kind = if added & (1u128 << ii) != 0 {
+                        ChangeKind::Added
+                    } else {
+                        ChangeKind::Updated
+                    };
+                    entries.push((ri * 16384 + mi * 128 + ii, kind));
+                }
+            }
+        }
+    }
+
+    fn take(&self) -> Vec<(u32, ChangeKind)> {
+        std::mem::take(unsafe { &mut *self.entries.get() })
+    }
+}
+
+/// The world's change journal, see the module docs.
+#[derive(Default)]
+pub(crate) struct ChangeJournal {
+    enabled: bool,
+    /// Logs fed by the cleanup systems, keyed by component type index.
+    logs: BTreeMap<usize, Rc<ChangeLog>>,
+    /// Changes of finished ticks not drained yet.
+    changes: Vec<Change>,
+}
+
+impl ChangeJournal {
+    pub(crate) fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Starts or stops recording. Stopping forgets the changes not drained yet.
+    pub(crate) fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+        for log in self.logs.values() {
+            log.enabled.set(enabled);
+            log.take();
+        }
+        if !enabled {
+            self.changes.clear();
+        }
+    }
+
+    /// The log for the storage with type index `id`, created on first use.
+    pub(crate) fn log(&mut self, id: usize) -> Rc<ChangeLog> {
+        let enabled = self.enabled;
+        self.logs
+            .entry(id)
+            .or_insert_with(|| Rc::new(ChangeLog::new(enabled)))
+            .clone()
+    }
+
+    /// Type indices of the storages with a log.
+    pub(crate) fn ids(&self) -> Vec<usize> {
+        self.logs.keys().copied().collect()
+    }
+
+    /// Takes what the cleanup system of storage `id` recorded during the tick.
+    pub(crate) fn take_log(&self, id: usize) -> Vec<(u32, ChangeKind)> {
+        self.logs.get(&id).map(|log| log.take()).unwrap_or_default()
+    }
+
+    /// Appends the changes of `tick`, replacing those of an earlier run of `tick` that a
+    /// rollback brought back.
+    pub(crate) fn record(&mut self, tick: Tick, changes: Vec<Change>) {
+        self.changes.retain(|change| change.tick != tick);
+        self.changes.extend(changes);
+    }
+
+    pub(crate) fn drain(&mut self) -> std::vec::Drain<'_, Change> {
+        self.changes.drain(..)
+    }
+
+    /// Forgets the changes of ticks after `target`, and whatever the logs hold.
+    pub(crate) fn rollback(&mut self, target: Tick) {
+        self.changes.retain(|change| !change.tick.is_after(target));
+        for log in self.logs.values() {
+            log.take();
+        }
+    }
+}
+
+#[cfg(test)]
+#[path = "journal.tests.rs"]
+mod tests;
@@ -0,0 +1,101 @@
+use super::*;
+use crate::prelude::*;
+
+#[derive(Component, Default, Clone, Debug, PartialEq)]
+struct Health {
+    hp: i32,
+}
+
+#[derive(Component, Default, Clone, Debug, PartialEq)]
+struct Velocity {
+    x: f32,
+}
+
+system! {
+    RegenSystem {
+        query! {
+            fn regen(health: &mut ViewMut<Health>) {
+                health.hp += 1;
+            }
+        }
+    }
+}
+
+fn drain(world: &mut World) -> Vec<(Entity, &'static str, ChangeKind)> {
+    world
+        .drain_changes()
+        .map(|change| (change.entity, change.name, change.kind))
+        .collect()
+}
+
+#[test]
+fn test_drain_changes() {
+    let health = std::any::type_name::<Health>();
+    let velocity = std::any::type_name::<Velocity>();
+
+    let mut world = World::new();
+    let a = world.spawn();
+    world.set(a, &Health { hp: 1 });
+    world.get_storage::<Velocity>();
+    world.add_system::<RegenSystem>();
+    world.build_scheduler();
+
+    // Nothing is journaled until recording starts
+    world.run();
+    assert_eq!(world.drain_changes().count(), 0);
+
+    world.record_changes(true);
+    let b = world.spawn();
+    world.set(b, &Health { hp: 5 });
+    world.set(b, &Velocity { x: 1.0 });
+    let tick = world.current_tick();
+    world.run();
+    let changes: Vec<Change> = world.drain_changes().collect();
+    assert!(changes.iter().all(|change| change.tick == tick));
+    assert_eq!(changes[0].component, std::any::TypeId::of::<Health>());
+    assert_eq!(
+        changes.iter().map(|c| (c.entity, c.name, c.kind)).collect::<Vec<_>>(),
+        vec![
+            (a, health, ChangeKind::Updated),
+            (b, health, ChangeKind::Added),
+            (b, velocity, ChangeKind::Added),
+        ]
+    );
+
+    // Removals and destroyed entities show up once the tick ended
+    world.remove::<Velocity>(b);
+    world.destroy(a);
+    let checkpoint = world.current_tick();
+    world.run();
+    assert_eq!(
+        drain(&mut world),
+        vec![
+            (b, health, ChangeKind::Updated),
+            (a, health, ChangeKind::Removed),
+            (b, velocity, ChangeKind::Removed),
+        ]
+    );
+
+    // A rollback drops the undrained changes of the ticks it undoes
+    world.run();
+    world.run();
+    world.rollback(checkpoint);
+    assert_eq!(world.drain_changes().count(), 0);
+
+    // and a tick simulated again replaces its undrained changes
+    world.run();
+    assert_eq!(world.drain_changes().count(), 3);
+    let replayed = world.current_tick();
+    world.run();
+    world.run();
+    world.rollback(replayed);
+    world.run();
+    let changes: Vec<Change> = world.drain_changes().collect();
+    assert!(changes.iter().all(|change| change.tick == replayed));
+    assert_eq!(changes.len(), 1);
+    assert_eq!((changes[0].entity, changes[0].kind), (b, ChangeKind::Updated));
+
+    world.record_changes(false);
+    world.run();
+    assert_eq!(world.drain_changes().count(), 0);
+}
@@ -1,25 +1,92 @@
 #![feature(allocator_api)]
+#![feature(associated_type_defaults)]
 
 // Allow this crate to reference itself as ::rollback_ecs::
 // This enables proc macros to use absolute paths that work both internally and externally
 extern crate self as rollback_ecs;
 
-pub mod block;
+pub mod access;
+pub mod audit;
+pub mod bench_scenarios;
+pub mod bundle;
+#[cfg(feature = "physics-broadphase")]
+pub mod broadphase;
+pub mod builder;
 pub mod component;
+pub mod cow;
+pub mod det_math;
+pub mod dirty;
+pub mod dynamic;
+pub mod effects;
 pub mod entity;
+#[cfg(feature = "examples_lib")]
+pub mod examples;
+pub mod expiry;
+#[cfg(feature = "serde")]
+pub mod export;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+pub mod fixed;
+pub mod graph;
+pub mod hashtree;
+pub mod hierarchy;
+pub mod ingest;
+pub mod input;
+#[cfg(feature = "panic-isolation")]
+pub mod isolation;
+pub mod journal;
+pub mod mailbox;
+#[cfg(feature = "model-check")]
+pub mod model;
+pub mod net;
+pub mod netsim;
+pub mod ownership;
+pub mod par;
+pub mod pending;
+pub mod phase;
+pub mod plugin;
 pub mod prelude;
+pub mod profile;
+pub mod query;
+pub mod registry;
+pub mod relation;
+pub mod removal;
+pub mod resource;
+pub mod rng;
 pub mod rollback;
 pub mod safety;
+pub mod savestate;
 pub mod scheduler;
+pub mod send;
+pub mod sequence;
+pub mod session;
+pub mod sparse;
+pub mod state;
+pub mod statehash;
 pub mod storage;
 pub mod system;
-pub mod tick;
+pub mod tags;
+pub mod testing;
+pub mod tickrate;
 pub mod view;
+pub mod warmup;
+pub mod watch;
+#[cfg(feature = "watchdog")]
+pub mod watchdog;
+pub mod wire;
 pub mod world;
+pub mod zone;
 
 #[cfg(target_arch = "wasm32")]
 #[cfg(test)]
 mod wasm_tests;
 
+// The `no_std` core, re-exported under its old paths
+pub use rollback_core::{block, range, tick};
+
+// Used by `#[derive(Component)]` to submit `ComponentRegistration`s
+#[doc(hidden)]
+pub use inventory;
+
 #[allow(unused_imports)]
 use rollback_macros::pipeline_group;
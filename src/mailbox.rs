@@ -0,0 +1,167 @@
+//! Per-stage mailboxes for passing messages between systems within a tick.
+//!
+//! A `Mailbox<M, Target>` lets a system send messages of type `M` to one specific later
+//! system (`Target`). The target reads them through an `Inbox<M>`. Unlike a global event
+//! bus, every mailbox has exactly one receiver, and the scheduler orders the sender before
+//! the receiver automatically.
+//!
+//! Mailboxes only live for a single tick: the world clears them at the end of every
+//! `run()` and on `rollback()`, so resimulation never sees stale messages.
+//!
+//! # Example
+//! ```ignore
+//! system! {
+//!     DamageSystem {
+//!         query! {
+//!             fn damage(hp: View<Health>, hits: Mailbox<Hit, ScoreSystem>) {
+//!                 if hp.value == 0 {
+//!                     hits.send(Hit { points: 10 });
+//!                 }
+//!             }
+//!         }
+//!     }
+//! }
+//!
+//! system! {
+//!     ScoreSystem {
+//!         query! {
+//!             fn score(hits: Inbox<Hit>) {
+//!                 for hit in hits.iter() { /* ... */ }
+//!             }
+//!         }
+//!     }
+//! }
+//! ```
+
+use crate::sequence::{Lane, SequencedLanes};
+use std::any::Any;
+use std::marker::PhantomData;
+use std::rc::Rc;
+
+/// Backing queue shared by the `Mailbox`es addressed to one target and its `Inbox`.
+///
+/// Every sender writes to its own lane, so senders in the same wavefront may run in
+/// parallel. The lanes are merged in `SequenceKey` order when the target reads them.
+pub struct MailboxQueue<M> {
+    lanes: SequencedLanes<M>,
+    name: &'static str,
+}
+
+impl<M> MailboxQueue<M> {
+    pub fn new() -> Self {
+        Self::with_name(std::any::type_name::<Self>())
+    }
+
+    /// Creates a queue with a display name, used in diagnostics and schedule graphs.
+    pub fn with_name(name: &'static str) -> Self {
+        MailboxQueue {
+            lanes: SequencedLanes::new(),
+            name,
+        }
+    }
+
+    /// Number of queued messages.
+    pub fn len(&self) -> usize {
+        self.lanes.len()
+    }
+
+    /// Returns true if no messages are queued.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl<M> Default for MailboxQueue<M> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Type-erased access to mailbox queues so the world can clear them at tick end.
+pub trait MailboxLike: Any {
+    fn clear(&self);
+    fn name(&self) -> &'static str;
+    fn as_any_rc(self: Rc<Self>) -> Rc<dyn Any>;
+}
+
+impl<M: 'static> MailboxLike for MailboxQueue<M> {
+    fn clear(&self) {
+        self.lanes.clear()
+    }
+
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn as_any_rc(self: Rc<Self>) -> Rc<dyn Any> {
+        self
+    }
+}
+
+/// Sending half of a mailbox addressed to the system `Target`.
+pub struct Mailbox<M, Target> {
+    queue: Rc<MailboxQueue<M>>,
+    lane: Rc<Lane<M>>,
+    _target: PhantomData<fn() -> Target>,
+}
+
+impl<M, Target> Mailbox<M, Target> {
+    /// Creates a sender stamping its messages with the given stage hash.
+    pub fn new(queue: Rc<MailboxQueue<M>>, stage: u64) -> Self {
+        let lane = queue.lanes.lane(stage);
+        Mailbox {
+            queue,
+            lane,
+            _target: PhantomData,
+        }
+    }
+
+    /// Queues a message for the target system.
+    pub fn send(&self, message: M) {
+        // The lane is owned by this sender, and the receiver always runs in a later wavefront
+        self.lane.push(message);
+    }
+
+    /// Number of messages sent through this mailbox so far this tick.
+    pub fn len(&self) -> usize {
+        self.lane.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lane.is_empty()
+    }
+
+    /// Total number of messages queued for the target by all senders.
+    pub fn queued(&self) -> usize {
+        self.queue.len()
+    }
+}
+
+/// Receiving half of a mailbox, owned by the target system.
+pub struct Inbox<M> {
+    queue: Rc<MailboxQueue<M>>,
+}
+
+impl<M> Inbox<M> {
+    pub fn new(queue: Rc<MailboxQueue<M>>) -> Self {
+        Inbox { queue }
+    }
+
+    /// Iterates over messages ordered by sender stage, then by send order within a stage.
+    /// The order is identical whether the senders ran in parallel or sequentially.
+    pub fn iter(&self) -> impl Iterator<Item = &M> {
+        self.queue.lanes.merged().iter().map(|(_, m)| m)
+    }
+
+    pub fn len(&self) -> usize {
+        self.queue.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.queue.is_empty()
+    }
+}
+
+#[cfg(test)]
+#[path = "mailbox.tests.rs"]
+mod tests;
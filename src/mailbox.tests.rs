@@ -0,0 +1,215 @@
+use crate::component::Component;
+use crate::mailbox::Mailbox;
+use crate::prelude::system;
+use crate::scheduler::PipelineStage;
+use crate::sequence::stage_key;
+use crate::tick::Tick;
+use crate::world::World;
+use std::any::TypeId;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU32, Ordering};
+
+#[derive(Component, Default, Clone, Debug, PartialEq)]
+struct Health {
+    value: u32,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct Hit {
+    points: u32,
+}
+
+static RECEIVED_POINTS: AtomicU32 = AtomicU32::new(0);
+static RECEIVED_COUNT: AtomicU32 = AtomicU32::new(0);
+
+system! {
+    ScoreSystem {
+        query! {
+            fn score(hits: Inbox<Hit>) {
+                for hit in hits.iter() {
+                    RECEIVED_POINTS.fetch_add(hit.points, Ordering::Relaxed);
+                }
+                RECEIVED_COUNT.fetch_add(hits.len() as u32, Ordering::Relaxed);
+            }
+        }
+    }
+}
+
+system! {
+    DamageSystem {
+        query! {
+            fn damage(hp: View<Health>, hits: Mailbox<Hit, ScoreSystem>) {
+                if hp.value == 0 {
+                    hits.send(Hit { points: 10 });
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn test_mailbox_send_and_receive_across_systems() {
+    let mut world = World::new();
+    world.add_system::<ScoreSystem>();
+    world.add_system::<DamageSystem>();
+
+    let a = world.spawn();
+    let b = world.spawn();
+    let c = world.spawn();
+    world.set(a, &Health { value: 0 });
+    world.set(b, &Health { value: 5 });
+    world.set(c, &Health { value: 0 });
+
+    world.build_scheduler();
+    world.run();
+
+    // Two dead entities sent one message each; the receiver ran exactly once
+    assert_eq!(RECEIVED_POINTS.load(Ordering::Relaxed), 20);
+    assert_eq!(RECEIVED_COUNT.load(Ordering::Relaxed), 2);
+
+    // Mailboxes are cleared at the end of the tick
+    assert!(world.inbox::<Hit, ScoreSystem>().is_empty());
+}
+
+#[test]
+fn test_mailbox_sender_ordered_before_target() {
+    let mut world = World::new();
+    let sender = DamageSystem::create(&mut world);
+    let receiver = ScoreSystem::create(&mut world);
+
+    assert!(sender.before().contains(&TypeId::of::<ScoreSystem>()));
+    assert!(
+        !sender
+            .writes()
+            .contains(&TypeId::of::<Mailbox<Hit, ScoreSystem>>())
+    );
+    assert!(
+        receiver
+            .reads()
+            .contains(&TypeId::of::<Mailbox<Hit, ScoreSystem>>())
+    );
+}
+
+#[test]
+fn test_mailbox_cleared_on_rollback() {
+    let mut world = World::new();
+    let mailbox = world.mailbox::<Hit, ScoreSystem>();
+    let inbox = world.inbox::<Hit, ScoreSystem>();
+
+    mailbox.send(Hit { points: 1 });
+    mailbox.send(Hit { points: 2 });
+    assert_eq!(
+        inbox.iter().map(|h| h.points).collect::<Vec<_>>(),
+        vec![1, 2]
+    );
+
+    world.rollback(Tick::new(0));
+
+    assert!(inbox.is_empty());
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct Note {
+    from: &'static str,
+    value: u32,
+}
+
+static LOG: Mutex<Vec<(&'static str, u32)>> = Mutex::new(Vec::new());
+
+system! {
+    LogSystem {
+        query! {
+            fn log(notes: Inbox<Note>) {
+                let mut log = LOG.lock().unwrap();
+                log.extend(notes.iter().map(|n| (n.from, n.value)));
+            }
+        }
+    }
+}
+
+system! {
+    LeftSender {
+        query! {
+            fn left(hp: View<Health>, notes: Mailbox<Note, LogSystem>) {
+                notes.send(Note { from: "left", value: hp.value });
+            }
+        }
+    }
+}
+
+system! {
+    RightSender {
+        query! {
+            fn right(hp: View<Health>, notes: Mailbox<Note, LogSystem>) {
+                notes.send(Note { from: "right", value: hp.value });
+            }
+        }
+    }
+}
+
+fn run_senders(parallel: bool) -> Vec<(&'static str, u32)> {
+    let mut world = World::new();
+    world.add_system::<LogSystem>();
+    world.add_system::<RightSender>();
+    world.add_system::<LeftSender>();
+
+    for value in 1..=3 {
+        let e = world.spawn();
+        world.set(e, &Health { value });
+    }
+
+    world.build_scheduler();
+    if parallel {
+        world.run();
+    } else {
+        world.run_sequential();
+    }
+
+    std::mem::take(&mut *LOG.lock().unwrap())
+}
+
+#[test]
+fn test_mailbox_merge_order_is_deterministic() {
+    let parallel = run_senders(true);
+    let sequential = run_senders(false);
+
+    assert_eq!(parallel, sequential);
+    assert_eq!(parallel.len(), 6);
+
+    // Messages are grouped by sender stage key, in send order within a stage
+    let (first, second) = if stage_key::<LeftSender>() < stage_key::<RightSender>() {
+        ("left", "right")
+    } else {
+        ("right", "left")
+    };
+    assert_eq!(
+        parallel,
+        vec![
+            (first, 1),
+            (first, 2),
+            (first, 3),
+            (second, 1),
+            (second, 2),
+            (second, 3)
+        ]
+    );
+}
+
+#[test]
+fn test_external_messages_delivered_first() {
+    let mut world = World::new();
+    let system = world.mailbox_from::<Hit, ScoreSystem, DamageSystem>();
+    let external = world.mailbox::<Hit, ScoreSystem>();
+    let inbox = world.inbox::<Hit, ScoreSystem>();
+
+    system.send(Hit { points: 1 });
+    external.send(Hit { points: 2 });
+    system.send(Hit { points: 3 });
+
+    assert_eq!(system.len(), 2);
+    assert_eq!(external.queued(), 3);
+    assert_eq!(
+        inbox.iter().map(|h| h.points).collect::<Vec<_>>(),
+        vec![2, 1, 3]
+    );
+}
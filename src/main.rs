@@ -35,11 +35,7 @@ fn main() {
     world.set(e, &Velocity { x: 1.0, y: 0.0 });
 
     // 4. Run Simulation
-    let storage = world.get_storage::<Position>();
-
-    unsafe {
-        (*storage.get()).set_tick(Tick::new(1));
-    }
+    world.storage_mut::<Position>().set_tick(Tick::new(1));
 
     // Run systems...
     world.run_system::<MovementSystem>();
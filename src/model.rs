@@ -0,0 +1,311 @@
+//! The scheduler's concurrency model, checked (requires the `model-check` feature).
+//!
+//! # Model
+//! - Every storage is guarded by a conceptual readers-writer lock: one system may write
+//!   it, or any number of systems may read it, at a time. A system holds the locks of its
+//!   declared `reads()` and `writes()` for the whole of its `run`.
+//! - Systems in the same wavefront may run at the same time, start in any order and
+//!   finish in any order. Wavefronts are separated by barriers: a wavefront starts after
+//!   every system of the previous one has finished. Loop groups run their children's
+//!   wavefronts the same way, on the calling thread.
+//! - The scheduler separates two systems into different wavefronts whenever one writes a
+//!   type the other reads or writes. So no interleaving of any wavefront can ever ask
+//!   for a lock another running system holds: declared mutable access to a storage is
+//!   always exclusive.
+//!
+//! That last claim is what makes the `unsafe` storage access in systems sound, and this
+//! module turns it into two executable checks:
+//!
+//! - `check_scheduler` explores every state of every wavefront (each system pending,
+//!   running or done, all begin/end orders) and reports the first interleaving in which
+//!   two running systems conflict, with its trace. Systems that conflict with nobody in
+//!   their wavefront commute with everything and are left out of the exploration, which
+//!   keeps it exhaustive over the systems that matter.
+//! - With the feature enabled, the scheduler also takes the locks for real through an
+//!   `AccessTracker` around every system it runs, serially or on the thread pool, and
+//!   panics naming both systems if a lock is ever contended.
+//!
+//! Both check the declared sets; a system touching storages it didn't declare is outside
+//! the model.
+//!
+//! # Example
+//! ```ignore
+//! world.build_scheduler();
+//! let report = model::check_scheduler(world.scheduler().unwrap()).unwrap();
+//! println!("explored {} states", report.states);
+//! ```
+
+use crate::scheduler::{PipelineStage, Scheduler};
+use std::any::TypeId;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::sync::Mutex;
+
+/// Largest number of mutually conflicting systems explored in one wavefront. The state
+/// space grows as 3^n.
+pub const MAX_EXPLORED_SYSTEMS: usize = 12;
+
+/// One event of an interleaving.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Step {
+    Begin(&'static str),
+    End(&'static str),
+}
+
+/// Two systems that held conflicting locks at the same time.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Violation {
+    /// Index of the wavefront, in `Scheduler::stage_wavefronts` order. Zero for
+    /// violations found at runtime.
+    pub wavefront: usize,
+    /// The contended storage.
+    pub component: TypeId,
+    /// The system that held the lock.
+    pub holder: &'static str,
+    /// The system that asked for it.
+    pub entering: &'static str,
+    /// The interleaving that led there, ending with `Step::Begin(entering)`. Empty for
+    /// violations found at runtime.
+    pub trace: Vec<Step>,
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} and {} access {:?} concurrently, at least one of them mutably",
+            self.holder, self.entering, self.component
+        )?;
+        if !self.trace.is_empty() {
+            write!(
+                f,
+                " (wavefront {}, trace: {:?})",
+                self.wavefront, self.trace
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// What a successful check explored.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct ModelReport {
+    pub wavefronts: usize,
+    pub systems: usize,
+    /// Reachable states visited, summed over all wavefronts.
+    pub states: usize,
+}
+
+/// Checks every wavefront of `scheduler`, see the module docs.
+///
+/// # Panics
+/// Panics if a wavefront has more than `MAX_EXPLORED_SYSTEMS` conflicting systems.
+pub fn check_scheduler(scheduler: &Scheduler) -> Result<ModelReport, Violation> {
+    let systems: Vec<&dyn PipelineStage> = scheduler.systems().collect();
+    check_wavefronts(&systems, &scheduler.stage_wavefronts())
+}
+
+/// Checks a schedule given as wavefronts of indices into `systems`.
+///
+/// # Panics
+/// Panics if a wavefront has more than `MAX_EXPLORED_SYSTEMS` conflicting systems.
+pub fn check_wavefronts(
+    systems: &[&dyn PipelineStage],
+    wavefronts: &[Vec<usize>],
+) -> Result<ModelReport, Violation> {
+    let mut report = ModelReport {
+        wavefronts: wavefronts.len(),
+        ..Default::default()
+    };
+
+    for (index, wavefront) in wavefronts.iter().enumerate() {
+        let stages: Vec<&dyn PipelineStage> = wavefront.iter().map(|&i| systems[i]).collect();
+        report.systems += stages.len();
+        report.states += explore(index, &stages)?;
+    }
+
+    Ok(report)
+}
+
+/// The storage `a` and `b` both lock with at least one of them writing, if any.
+fn conflict(a: &dyn PipelineStage, b: &dyn PipelineStage) -> Option<TypeId> {
+    let writes = |s: &dyn PipelineStage, id: &TypeId| s.writes().contains(id);
+    a.writes()
+        .iter()
+        .find(|id| b.reads().contains(id) || writes(b, id))
+        .or_else(|| a.reads().iter().find(|id| writes(b, id)))
+        .copied()
+}
+
+/// Explores every begin/end interleaving of one wavefront and returns the number of
+/// states visited.
+fn explore(wavefront: usize, stages: &[&dyn PipelineStage]) -> Result<usize, Violation> {
+    // Systems without conflicts commute with every other step
+    let relevant: Vec<&dyn PipelineStage> = stages
+        .iter()
+        .enumerate()
+        .filter(|&(i, a)| {
+            stages
+                .iter()
+                .enumerate()
+                .any(|(j, b)| i != j && conflict(*a, *b).is_some())
+        })
+        .map(|(_, s)| *s)
+        .collect();
+    assert!(
+        relevant.len() <= MAX_EXPLORED_SYSTEMS,
+        "wavefront {} has {} conflicting systems, more than the {} the model checker explores",
+        wavefront,
+        relevant.len(),
+        MAX_EXPLORED_SYSTEMS
+    );
+
+    // A state is (running, done) as bitmasks over `relevant`
+    let mut visited = HashSet::new();
+    let mut trace = Vec::new();
+    dfs(wavefront, &relevant, (0, 0), &mut visited, &mut trace)?;
+    Ok(visited.len().max(1))
+}
+
+fn dfs(
+    wavefront: usize,
+    stages: &[&dyn PipelineStage],
+    state: (u32, u32),
+    visited: &mut HashSet<(u32, u32)>,
+    trace: &mut Vec<Step>,
+) -> Result<(), Violation> {
+    if !visited.insert(state) {
+        return Ok(());
+    }
+    let (running, done) = state;
+
+    for (i, stage) in stages.iter().enumerate() {
+        let bit = 1u32 << i;
+
+        if running & bit != 0 {
+            trace.push(Step::End(stage.name()));
+            dfs(
+                wavefront,
+                stages,
+                (running & !bit, done | bit),
+                visited,
+                trace,
+            )?;
+            trace.pop();
+        } else if done & bit == 0 {
+            trace.push(Step::Begin(stage.name()));
+            for (j, holder) in stages.iter().enumerate() {
+                if running & (1 << j) == 0 {
+                    continue;
+                }
+                if let Some(component) = conflict(*stage, *holder) {
+                    return Err(Violation {
+                        wavefront,
+                        component,
+                        holder: holder.name(),
+                        entering: stage.name(),
+                        trace: trace.clone(),
+                    });
+                }
+            }
+            dfs(wavefront, stages, (running | bit, done), visited, trace)?;
+            trace.pop();
+        }
+    }
+
+    Ok(())
+}
+
+enum Hold {
+    Read(Vec<&'static str>),
+    Write(&'static str),
+}
+
+/// Runtime readers-writer bookkeeping of the storages held by running systems. The
+/// scheduler enters it around every system it runs when the `model-check` feature is on.
+#[derive(Default)]
+pub struct AccessTracker {
+    held: Mutex<HashMap<TypeId, Hold>>,
+}
+
+/// Releases a system's locks when dropped.
+pub struct AccessGuard<'a> {
+    tracker: &'a AccessTracker,
+    name: &'static str,
+    reads: &'static [TypeId],
+    writes: &'static [TypeId],
+}
+
+impl AccessTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Takes the locks of `system`'s declared sets, or reports the system holding one of
+    /// them in a conflicting mode. Nothing is taken on conflict.
+    pub fn enter(&self, system: &dyn PipelineStage) -> Result<AccessGuard<'_>, Violation> {
+        let name = system.name();
+        let (reads, writes) = (system.reads(), system.writes());
+        let mut held = self.held.lock().unwrap();
+
+        let conflict = |component: TypeId, holder: &'static str| Violation {
+            wavefront: 0,
+            component,
+            holder,
+            entering: name,
+            trace: Vec::new(),
+        };
+        for id in writes {
+            match held.get(id) {
+                Some(Hold::Write(holder)) => return Err(conflict(*id, holder)),
+                Some(Hold::Read(readers)) => return Err(conflict(*id, readers[0])),
+                None => {}
+            }
+        }
+        for id in reads.iter().filter(|id| !writes.contains(id)) {
+            if let Some(Hold::Write(holder)) = held.get(id) {
+                return Err(conflict(*id, holder));
+            }
+        }
+
+        for &id in writes {
+            held.insert(id, Hold::Write(name));
+        }
+        for &id in reads.iter().filter(|id| !writes.contains(id)) {
+            match held.entry(id).or_insert_with(|| Hold::Read(Vec::new())) {
+                Hold::Read(readers) => readers.push(name),
+                Hold::Write(_) => unreachable!(),
+            }
+        }
+
+        Ok(AccessGuard {
+            tracker: self,
+            name,
+            reads,
+            writes,
+        })
+    }
+}
+
+impl Drop for AccessGuard<'_> {
+    fn drop(&mut self) {
+        let mut held = self.tracker.held.lock().unwrap();
+        for id in self.writes {
+            held.remove(id);
+        }
+        for id in self.reads.iter().filter(|id| !self.writes.contains(id)) {
+            if let Some(Hold::Read(readers)) = held.get_mut(id) {
+                if let Some(i) = readers.iter().position(|r| *r == self.name) {
+                    readers.remove(i);
+                }
+                if readers.is_empty() {
+                    held.remove(id);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+#[path = "model.tests.rs"]
+mod tests;
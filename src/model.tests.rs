@@ -0,0 +1,153 @@
+use super::*;
+use crate::prelude::*;
+
+struct A;
+struct B;
+
+/// A stage with hand-written access sets.
+struct Stage {
+    name: &'static str,
+    reads: &'static [TypeId],
+    writes: &'static [TypeId],
+}
+
+impl Stage {
+    fn new(name: &'static str, reads: Vec<TypeId>, writes: Vec<TypeId>) -> Self {
+        Stage {
+            name,
+            reads: Vec::leak(reads),
+            writes: Vec::leak(writes),
+        }
+    }
+}
+
+impl PipelineStage for Stage {
+    fn run(&self) {}
+
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn type_id(&self) -> TypeId {
+        TypeId::of::<Self>()
+    }
+
+    fn reads(&self) -> &'static [TypeId] {
+        self.reads
+    }
+
+    fn writes(&self) -> &'static [TypeId] {
+        self.writes
+    }
+}
+
+#[derive(Component, Default, Clone, Debug, PartialEq)]
+struct Velocity {
+    value: i32,
+}
+
+#[derive(Component, Default, Clone, Debug, PartialEq)]
+struct Heat {
+    value: i32,
+}
+
+system! {
+    MoveSystem {
+        query! {
+            fn step(velocity: &mut ViewMut<Velocity>) {
+                velocity.value += 1;
+            }
+        }
+    }
+}
+
+system! {
+    CoolSystem {
+        query! {
+            fn cool(heat: &mut ViewMut<Heat>) {
+                heat.value -= 1;
+            }
+        }
+    }
+}
+
+system! {
+    ReadVelocitySystem {
+        query! {
+            fn read(velocity: View<Velocity>, heat: &mut ViewMut<Heat>) {
+                heat.value = velocity.value;
+            }
+        }
+    }
+}
+
+#[test]
+fn test_built_scheduler_satisfies_model() {
+    let mut world = World::new();
+    world.add_system::<MoveSystem>();
+    world.add_system::<CoolSystem>();
+    world.add_system::<ReadVelocitySystem>();
+    world.build_scheduler();
+
+    let report = check_scheduler(world.scheduler().unwrap()).unwrap();
+    assert_eq!(
+        report.wavefronts,
+        world.scheduler().unwrap().stage_wavefronts().len()
+    );
+    assert!(report.systems >= 3);
+
+    // Running for real takes the tracker's locks on every system
+    let e = world.spawn();
+    world.set(e, &Velocity { value: 1 });
+    world.set(e, &Heat { value: 0 });
+    for _ in 0..4 {
+        world.run();
+    }
+}
+
+#[test]
+fn test_conflicting_wavefront_reports_trace() {
+    let a = TypeId::of::<A>();
+    let b = TypeId::of::<B>();
+    let writer = Stage::new("writer", vec![], vec![a]);
+    let reader = Stage::new("reader", vec![a], vec![]);
+    let other = Stage::new("other", vec![b], vec![]);
+    let systems: Vec<&dyn PipelineStage> = vec![&writer, &reader, &other];
+
+    // Readers share, and separate wavefronts never overlap
+    let ok = check_wavefronts(&systems, &[vec![1, 2], vec![0]]).unwrap();
+    assert_eq!(ok.systems, 3);
+
+    let violation = check_wavefronts(&systems, &[vec![2], vec![0, 1, 2]]).unwrap_err();
+    assert_eq!(violation.wavefront, 1);
+    assert_eq!(violation.component, a);
+    assert_eq!(violation.holder, "writer");
+    assert_eq!(violation.entering, "reader");
+    assert_eq!(
+        violation.trace,
+        vec![Step::Begin("writer"), Step::Begin("reader")]
+    );
+    assert!(violation.to_string().contains("writer and reader"));
+}
+
+#[test]
+fn test_tracker_excludes_writers_and_shares_readers() {
+    let a = TypeId::of::<A>();
+    let writer = Stage::new("writer", vec![a], vec![a]);
+    let reader = Stage::new("reader", vec![a], vec![]);
+    let second = Stage::new("second", vec![a], vec![]);
+    let tracker = AccessTracker::new();
+
+    let r1 = tracker.enter(&reader).unwrap();
+    let r2 = tracker.enter(&second).unwrap();
+    let violation = tracker.enter(&writer).err().unwrap();
+    assert_eq!(violation.holder, "reader");
+    drop(r1);
+    assert!(tracker.enter(&writer).is_err());
+    drop(r2);
+
+    let w = tracker.enter(&writer).unwrap();
+    assert_eq!(tracker.enter(&reader).err().unwrap().holder, "writer");
+    drop(w);
+    assert!(tracker.enter(&reader).is_ok());
+}
@@ -0,0 +1,348 @@
+//! Per-tick delta replication from an authoritative server to clients.
+//!
+//! Entities carrying the `Replicated` marker are sent to clients, each through its own
+//! `DeltaEncoder`. `DeltaEncoder` collects the entities whose replicated components changed
+//! from the world's change journal (see the `journal` module) and `encode` writes them as
+//! one `wire` delta packet: per component, the changed 128-slot blocks with a set and a
+//! removed bitmask followed by the `Wire` encoding of the set values. Unchanged entities
+//! and components cost nothing.
+//!
+//! Large worlds only send each client what is relevant to it. A `RelevancyPolicy` set with
+//! `DeltaEncoder::set_relevancy` (any `Fn(&World, Entity) -> bool`, e.g. a radius around
+//! the client's avatar) picks the replicated entities the client sees. The encoder tracks
+//! which entities the client holds: one that becomes relevant is sent in full, with its
+//! handle in an `Entity` section, and one that stops being relevant, or is destroyed, is
+//! dropped from the client with all of its replicated components. Handles keep the
+//! server's indices and generations.
+//!
+//! A client starts from an empty world, so the first packet of an encoder carries every
+//! relevant entity in full. `DeltaDecoder` applies the packets to the client world. Deltas
+//! chain: each one is built on the tick of the previous one (the tick the encoder was
+//! created at for the first), and the decoder rejects a packet built on another tick with
+//! `DecodeError::BaseMismatch`, so they need a reliable ordered channel, or a new encoder
+//! and client world after a loss. Every section is decoded before the world is touched.
+//! Applied values are ordinary edits at the client's current tick, so a predicting client
+//! can still roll them back.
+//!
+//! Both sides must replicate the same component types.
+//!
+//! # Example
+//! ```ignore
+//! // Server
+//! world.record_changes(true);
+//! world.set(ship, &Replicated);
+//! let mut encoder = DeltaEncoder::new(world.current_tick());
+//! encoder.replicate::<Position>();
+//! encoder.set_relevancy(move |world: &World, entity| {
+//!     world.get::<Position>(entity).is_some_and(|p| p.distance(&avatar) < 100)
+//! });
+//! world.run();
+//! encoder.record(world.drain_changes());
+//! send(encoder.encode(&world));
+//!
+//! // Client
+//! let mut decoder = DeltaDecoder::new(base_from_handshake);
+//! decoder.replicate::<Position>();
+//! decoder.apply(&mut client, &receive())?;
+//! ```
+
+use crate::component::{Component, Tag};
+use crate::entity::Entity;
+use crate::journal::Change;
+use crate::storage::ComponentStorage;
+use crate::tick::Tick;
+use crate::wire::{DecodeError, DeltaOp, Packet, PacketKind, PacketWriter, Section, Wire};
+use crate::world::World;
+use std::any::TypeId;
+use std::collections::{BTreeMap, BTreeSet};
+use std::marker::PhantomData;
+
+/// Edits decoded from one section, applied once the whole packet decoded.
+type Apply = Box<dyn FnOnce(&mut World)>;
+
+/// Marks an entity for replication, see the module docs.
+#[derive(Tag, Clone, Copy, Default, Debug, PartialEq, Eq)]
+pub struct Replicated;
+
+/// Picks the replicated entities one client receives, see the module docs.
+pub trait RelevancyPolicy {
+    fn is_relevant(&self, world: &World, entity: Entity) -> bool;
+}
+
+impl<F: Fn(&World, Entity) -> bool> RelevancyPolicy for F {
+    fn is_relevant(&self, world: &World, entity: Entity) -> bool {
+        self(world, entity)
+    }
+}
+
+/// Encoding and decoding of one replicated component type.
+trait ReplicatedComponent {
+    /// Writes the values at `changed` (removals included) and at `entered` (present ones
+    /// only) as one section, if there is anything to write.
+    fn write(
+        &self,
+        world: &World,
+        changed: &BTreeSet<u32>,
+        entered: &BTreeSet<u32>,
+        writer: &mut PacketWriter,
+    );
+    fn decode(&self, section: &Section<'_>) -> Result<Apply, DecodeError>;
+    /// Drops the component of an entity the client no longer holds.
+    fn discard(&self, world: &mut World, index: u32);
+}
+
+struct Replica<T>(PhantomData<T>);
+
+impl<T: Component + Wire> ReplicatedComponent for Replica<T> {
+    fn write(
+        &self,
+        world: &World,
+        changed: &BTreeSet<u32>,
+        entered: &BTreeSet<u32>,
+        writer: &mut PacketWriter,
+    ) {
+        let storage = world.storage_ref::<T>();
+        let value = |index: u32| storage.and_then(|s| s.get(index));
+        let ops: Vec<(u32, Option<&T>)> = changed
+            .union(entered)
+            .map(|&index| (index, value(index)))
+            .filter(|(index, value)| value.is_some() || !entered.contains(index))
+            .collect();
+        if !ops.is_empty() {
+            writer.write_delta_ops(ops);
+        }
+    }
+
+    fn decode(&self, section: &Section<'_>) -> Result<Apply, DecodeError> {
+        let ops = section.decode_delta::<T>()?;
+        Ok(Box::new(move |world: &mut World| {
+            let mut storage = world.storage_mut::<T>();
+            for (index, op) in ops {
+                match op {
+                    DeltaOp::Set(value) => storage.set(index, &value),
+                    DeltaOp::Remove => storage.remove(index),
+                }
+            }
+        }))
+    }
+
+    fn discard(&self, world: &mut World, index: u32) {
+        world.storage_mut::<T>().remove(index);
+    }
+}
+
+/// Writes delta packets of the replicated components for one client, see the module docs.
+pub struct DeltaEncoder {
+    components: BTreeMap<TypeId, Box<dyn ReplicatedComponent>>,
+    relevancy: Option<Box<dyn RelevancyPolicy>>,
+    /// Tick of the last packet, which the next one is built on.
+    base: Tick,
+    /// Changed indices per replicated component since the last packet.
+    dirty: BTreeMap<TypeId, BTreeSet<u32>>,
+    /// The entities the client holds, by index.
+    known: BTreeMap<u32, Entity>,
+}
+
+impl DeltaEncoder {
+    /// Starts encoding for a client holding nothing yet, with packets built on `base`.
+    pub fn new(base: Tick) -> Self {
+        DeltaEncoder {
+            components: BTreeMap::new(),
+            relevancy: None,
+            base,
+            dirty: BTreeMap::new(),
+            known: BTreeMap::new(),
+        }
+    }
+
+    /// Replicates component `T`.
+    pub fn replicate<T: Component + Wire>(&mut self) {
+        let id = TypeId::of::<T>();
+        self.components.insert(id, Box::new(Replica::<T>(PhantomData)));
+        self.dirty.entry(id).or_default();
+    }
+
+    /// Sends the client only the replicated entities `policy` finds relevant, from the
+    /// next packet on. Without a policy every replicated entity is relevant.
+    pub fn set_relevancy(&mut self, policy: impl RelevancyPolicy + 'static) {
+        self.relevancy = Some(Box::new(policy));
+    }
+
+    /// The tick the next packet is built on.
+    pub fn base(&self) -> Tick {
+        self.base
+    }
+
+    /// Collects changes drained from the world's journal. Changes of components that
+    /// aren't replicated are ignored.
+    pub fn record(&mut self, changes: impl IntoIterator<Item = Change>) {
+        for change in changes {
+            if let Some(dirty) = self.dirty.get_mut(&change.component) {
+                dirty.insert(change.entity.index());
+            }
+        }
+    }
+
+    /// Writes everything recorded since the last packet, with the world's current values,
+    /// as a delta packet from `base()` to the world's current tick.
+    pub fn encode(&mut self, world: &World) -> Vec<u8> {
+        let tick = world.current_tick();
+        let mut writer = PacketWriter::delta(self.base, tick);
+        let relevant = self.relevant(world);
+
+        // Handles of entities the client gains or loses, by index
+        let mut handles: BTreeMap<u32, Option<Entity>> = BTreeMap::new();
+        let mut entered = BTreeSet::new();
+        for &index in self.known.keys() {
+            if !relevant.contains_key(&index) {
+                handles.insert(index, None);
+            }
+        }
+        for (&index, &entity) in &relevant {
+            if self.known.get(&index) != Some(&entity) {
+                handles.insert(index, Some(entity));
+                entered.insert(index);
+            }
+        }
+        if !handles.is_empty() {
+            writer.write_delta_ops(handles.iter().map(|(&index, entity)| (index, entity.as_ref())));
+        }
+
+        for (id, component) in &self.components {
+            let changed: BTreeSet<u32> = self.dirty[id]
+                .iter()
+                .copied()
+                .filter(|index| relevant.contains_key(index) && !entered.contains(index))
+                .collect();
+            component.write(world, &changed, &entered, &mut writer);
+        }
+
+        for indices in self.dirty.values_mut() {
+            indices.clear();
+        }
+        self.known = relevant;
+        self.base = tick;
+        writer.finish()
+    }
+
+    /// The replicated entities relevant to the client, by index.
+    fn relevant(&self, world: &World) -> BTreeMap<u32, Entity> {
+        let mut relevant = BTreeMap::new();
+        let (Some(markers), Some(entities)) =
+            (world.storage_ref::<Replicated>(), world.storage_ref::<Entity>())
+        else {
+            return relevant;
+        };
+
+        markers.visit(|index, _| {
+            let Some(&entity) = entities.get(index) else {
+                return;
+            };
+            if self.relevancy.as_ref().is_none_or(|policy| policy.is_relevant(world, entity)) {
+                relevant.insert(index, entity);
+            }
+        });
+        relevant
+    }
+}
+
+/// Applies `DeltaEncoder` packets to a client world, see the module docs.
+pub struct DeltaDecoder {
+    components: BTreeMap<u64, Box<dyn ReplicatedComponent>>,
+    /// Tick of the state the client holds.
+    tick: Tick,
+}
+
+impl DeltaDecoder {
+    /// Starts decoding for a world holding the server state at `tick`, usually the tick
+    /// `World::load_snapshot` returned.
+    pub fn new(tick: Tick) -> Self {
+        DeltaDecoder {
+            components: BTreeMap::new(),
+            tick,
+        }
+    }
+
+    /// Accepts deltas of component `T`.
+    pub fn replicate<T: Component + Wire>(&mut self) {
+        let id = crate::wire::component_id::<T>();
+        self.components.insert(id, Box::new(Replica::<T>(PhantomData)));
+    }
+
+    /// The server tick the client state matches.
+    pub fn tick(&self) -> Tick {
+        self.tick
+    }
+
+    /// Applies one delta packet to `world` and returns the server tick it leads to. The
+    /// world is left untouched on error.
+    pub fn apply(&mut self, world: &mut World, bytes: &[u8]) -> Result<Tick, DecodeError> {
+        let packet = Packet::decode(bytes)?;
+        if packet.kind != PacketKind::Delta {
+            return Err(DecodeError::KindMismatch {
+                expected: PacketKind::Delta,
+                found: packet.kind,
+            });
+        }
+        if packet.base != self.tick {
+            return Err(DecodeError::BaseMismatch {
+                expected: self.tick,
+                found: packet.base,
+            });
+        }
+
+        let entity_id = crate::wire::component_id::<Entity>();
+        let mut entities = Vec::new();
+        let mut edits = Vec::new();
+        for section in packet.sections() {
+            if section.component == entity_id {
+                entities = section.decode_delta::<Entity>()?;
+                continue;
+            }
+            let Some(component) = self.components.get(&section.component) else {
+                return Err(DecodeError::UnknownComponent(section.component));
+            };
+            edits.push(component.decode(section)?);
+        }
+
+        // Entities first, dropping what the client held of the entities it loses, so
+        // components land on the handles the server has
+        let mut dropped = Vec::new();
+        {
+            let mut storage = world.storage_mut::<Entity>();
+            for (index, op) in entities {
+                let old = storage.get(index).copied();
+                let new = match op {
+                    DeltaOp::Set(entity) => Some(entity),
+                    DeltaOp::Remove => None,
+                };
+                if old == new {
+                    continue;
+                }
+                if let Some(old) = old {
+                    storage.retire(&[old]);
+                    storage.remove(index);
+                    dropped.push(index);
+                }
+                if let Some(new) = new {
+                    storage.set(index, &new);
+                }
+            }
+        }
+        for index in dropped {
+            for component in self.components.values() {
+                component.discard(world, index);
+            }
+        }
+        for edit in edits {
+            edit(world);
+        }
+
+        self.tick = packet.tick;
+        Ok(packet.tick)
+    }
+}
+
+#[cfg(test)]
+#[path = "net.tests.rs"]
+mod tests;
@@ -0,0 +1,150 @@
+use super::*;
+use crate::prelude::*;
+use crate::wire::Reader;
+
+#[derive(Component, Default, Clone, Debug, PartialEq)]
+struct Position {
+    x: i32,
+}
+
+impl Wire for Position {
+    fn encode(&self, out: &mut Vec<u8>) {
+        self.x.encode(out);
+    }
+
+    fn decode(reader: &mut Reader<'_>) -> Result<Self, DecodeError> {
+        Ok(Position {
+            x: i32::decode(reader)?,
+        })
+    }
+}
+
+#[derive(Component, Default, Clone, Debug, PartialEq)]
+struct Frozen;
+
+system! {
+    DriftSystem {
+        query! {
+            fn drift(position: &mut ViewMut<Position>) None=[Frozen] {
+                position.x += 1;
+            }
+        }
+    }
+}
+
+fn server() -> (World, Vec<Entity>) {
+    let mut world = World::new();
+    world.get_storage::<Position>();
+    world.get_storage::<Frozen>();
+    world.add_system::<DriftSystem>();
+    world.build_scheduler();
+    world.record_changes(true);
+
+    let entities: Vec<Entity> = (0..3).map(|_| world.spawn()).collect();
+    for (i, &e) in entities.iter().enumerate() {
+        world.set(e, &Position { x: i as i32 * 10 });
+        world.set(e, &Replicated);
+    }
+    world.set(entities[2], &Frozen);
+    (world, entities)
+}
+
+fn connect(server: &World) -> (DeltaEncoder, World, DeltaDecoder) {
+    let mut encoder = DeltaEncoder::new(server.current_tick());
+    encoder.replicate::<Position>();
+    let mut decoder = DeltaDecoder::new(encoder.base());
+    decoder.replicate::<Position>();
+    (encoder, World::new(), decoder)
+}
+
+fn tick(server: &mut World, encoder: &mut DeltaEncoder) -> Vec<u8> {
+    server.run();
+    encoder.record(server.drain_changes());
+    encoder.encode(server)
+}
+
+#[test]
+fn test_deltas_replicate_changes() {
+    let (mut server, entities) = server();
+    let (mut encoder, mut client, mut decoder) = connect(&server);
+
+    let delta = tick(&mut server, &mut encoder);
+    assert_eq!(decoder.apply(&mut client, &delta), Ok(server.current_tick()));
+    for &e in &entities {
+        assert_eq!(client.get::<Position>(e), server.get::<Position>(e));
+    }
+    assert_eq!(client.get::<Position>(entities[0]), Some(&Position { x: 1 }));
+
+    // Only the drifting entities are sent, and unreplicated components not at all
+    let delta = tick(&mut server, &mut encoder);
+    let packet = Packet::decode(&delta).unwrap();
+    assert_eq!(packet.sections().len(), 1);
+    let ops = packet.section::<Position>().unwrap().decode_delta::<Position>().unwrap();
+    assert_eq!(ops.iter().map(|(index, _)| *index).collect::<Vec<_>>(), vec![0, 1]);
+    decoder.apply(&mut client, &delta).unwrap();
+
+    // Spawns and destroys travel with the entity handles
+    server.destroy(entities[0]);
+    let spawned = server.spawn();
+    server.set(spawned, &Position { x: 100 });
+    server.set(spawned, &Replicated);
+    server.remove::<Position>(entities[1]);
+    let delta = tick(&mut server, &mut encoder);
+    decoder.apply(&mut client, &delta).unwrap();
+
+    assert!(client.get::<Entity>(entities[0]).is_none());
+    assert_eq!(client.get::<Position>(entities[1]), None);
+    assert_eq!(client.get::<Position>(spawned), Some(&Position { x: 101 }));
+    assert_eq!(client.get::<Position>(entities[2]), Some(&Position { x: 20 }));
+    assert_eq!(decoder.tick(), server.current_tick());
+}
+
+#[test]
+fn test_delta_on_another_base_is_rejected() {
+    let (mut server, _) = server();
+    let (mut encoder, mut client, mut decoder) = connect(&server);
+
+    let first = tick(&mut server, &mut encoder);
+    let second = tick(&mut server, &mut encoder);
+    let before = client.compute_state_hash();
+    assert_eq!(
+        decoder.apply(&mut client, &second),
+        Err(DecodeError::BaseMismatch {
+            expected: Tick::new(0),
+            found: Tick::new(1),
+        })
+    );
+    assert_eq!(client.compute_state_hash(), before);
+
+    decoder.apply(&mut client, &first).unwrap();
+    decoder.apply(&mut client, &second).unwrap();
+    assert_eq!(decoder.tick(), Tick::new(2));
+}
+
+#[test]
+fn test_relevancy_limits_what_a_client_holds() {
+    let (mut server, entities) = server();
+    let hidden = server.spawn();
+    server.set(hidden, &Position { x: 0 });
+    let (mut encoder, mut client, mut decoder) = connect(&server);
+    encoder.set_relevancy(|world: &World, entity| {
+        world.get::<Position>(entity).is_some_and(|p| p.x < 12)
+    });
+
+    // Entities without the marker or out of range are never sent
+    decoder.apply(&mut client, &tick(&mut server, &mut encoder)).unwrap();
+    assert_eq!(client.get::<Position>(entities[0]), Some(&Position { x: 1 }));
+    assert_eq!(client.get::<Position>(entities[1]), Some(&Position { x: 11 }));
+    assert!(client.get::<Entity>(entities[2]).is_none());
+    assert!(client.get::<Entity>(hidden).is_none());
+
+    // Leaving the range drops the entity, entering it sends the entity in full
+    decoder.apply(&mut client, &tick(&mut server, &mut encoder)).unwrap();
+    assert!(client.get::<Entity>(entities[1]).is_none());
+    assert_eq!(client.storage_ref::<Position>().unwrap().get(entities[1].index()), None);
+
+    server.set(entities[2], &Position { x: 5 });
+    decoder.apply(&mut client, &tick(&mut server, &mut encoder)).unwrap();
+    assert_eq!(client.get::<Position>(entities[2]), Some(&Position { x: 5 }));
+    assert_eq!(client.get::<Position>(entities[0]), Some(&Position { x: 3 }));
+}
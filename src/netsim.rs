@@ -0,0 +1,396 @@
+//! Loopback network harness for testing rollback game logic.
+//!
+//! `NetSim` connects two in-process peers through simulated links with configurable
+//! latency, jitter and packet loss. Each peer runs its own `World` in a `RollbackSession`:
+//! it simulates ahead with predicted remote inputs (the last confirmed one is repeated),
+//! and when the real input arrives and differs it rolls back and replays the mispredicted
+//! frames. Every packet carries all inputs the other peer hasn't
+//! acknowledged yet, so lost packets only delay confirmation.
+//!
+//! Once both peers have confirmed every frame, `assert_converged` compares the per-frame
+//! `world_checksum`s of both sides and reports the first frame that differs. Everything,
+//! including the network, is driven by seeded deterministic randomness, so a failing
+//! configuration reproduces exactly.
+//!
+//! Frame `f` is simulated at world tick `f + 1`; tick 0 is a shared start tick run with
+//! default inputs, so the first frame can still be resimulated.
+//!
+//! Component values are part of the checksum for types implementing `Hash`; other types
+//! contribute which entities have them.
+//!
+//! # Example
+//! ```ignore
+//! let config = NetSimConfig {
+//!     link: LinkConfig { latency: 3, jitter: 2, loss: 0.2 },
+//!     ..Default::default()
+//! };
+//! let mut sim = NetSim::new(config, make_world, apply_inputs);
+//! sim.run(120, |player, frame| script(player, frame));
+//! sim.assert_converged();
+//! ```
+
+use crate::rng::EntityRng;
+use crate::sequence::stage_hash;
+use crate::session::RollbackSession;
+use crate::statehash::StateHasher;
+use crate::tick::Tick;
+use crate::world::World;
+use std::hash::{Hash, Hasher};
+
+/// Properties of one direction of the simulated network, in network steps.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct LinkConfig {
+    /// Steps between sending a packet and its earliest delivery.
+    pub latency: u32,
+    /// Extra delay, uniform in `0..=jitter` steps. Jitter reorders packets.
+    pub jitter: u32,
+    /// Probability that a packet is dropped, in `[0, 1]`.
+    pub loss: f32,
+}
+
+impl Default for LinkConfig {
+    fn default() -> Self {
+        LinkConfig {
+            latency: 0,
+            jitter: 0,
+            loss: 0.0,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct NetSimConfig {
+    /// Used for both directions.
+    pub link: LinkConfig,
+    /// How many frames a peer may simulate past the newest confirmed remote input before
+    /// it stalls.
+    pub max_prediction: u32,
+    /// Seeds the packet loss and jitter rolls.
+    pub seed: u64,
+}
+
+impl Default for NetSimConfig {
+    fn default() -> Self {
+        NetSimConfig {
+            link: LinkConfig::default(),
+            max_prediction: 8,
+            seed: 0,
+        }
+    }
+}
+
+/// Traffic counters of one link.
+#[derive(Clone, Copy, Default, Debug, PartialEq, Eq)]
+pub struct LinkStats {
+    pub sent: u64,
+    pub dropped: u64,
+    pub delivered: u64,
+}
+
+/// Inputs a peer hasn't seen acknowledged yet, plus its own acknowledgement.
+#[derive(Clone, Debug)]
+struct Packet<I> {
+    /// Frame of `inputs[0]`.
+    start: u32,
+    inputs: Vec<I>,
+    /// Number of contiguous remote frames the sender has received.
+    ack: u32,
+}
+
+struct Link<P> {
+    config: LinkConfig,
+    rng: EntityRng,
+    /// `(delivery step, send order, packet)`.
+    in_flight: Vec<(u32, u64, P)>,
+    stats: LinkStats,
+}
+
+impl<P> Link<P> {
+    fn new(config: LinkConfig, seed: u64, stream: u64) -> Self {
+        Link {
+            config,
+            rng: EntityRng::new(seed, stream, 0, Tick::new(0)),
+            in_flight: Vec::new(),
+            stats: LinkStats::default(),
+        }
+    }
+
+    fn send(&mut self, now: u32, packet: P) {
+        let order = self.stats.sent;
+        self.stats.sent += 1;
+
+        if self.rng.chance(self.config.loss) {
+            self.stats.dropped += 1;
+            return;
+        }
+
+        let jitter = self.rng.range(0..self.config.jitter + 1);
+        let at = now + self.config.latency + jitter;
+        self.in_flight.push((at, order, packet));
+    }
+
+    /// Removes the packets due at `now`, in delivery order.
+    fn receive(&mut self, now: u32) -> Vec<P> {
+        let (mut due, pending): (Vec<_>, Vec<_>) = std::mem::take(&mut self.in_flight)
+            .into_iter()
+            .partition(|(at, _, _)| *at <= now);
+
+        self.in_flight = pending;
+        due.sort_by_key(|(at, order, _)| (*at, *order));
+        self.stats.delivered += due.len() as u64;
+        due.into_iter().map(|(_, _, packet)| packet).collect()
+    }
+}
+
+/// Applies both players' inputs for one frame, indexed by player.
+pub type ApplyInputs<I> = fn(&mut World, [&I; 2]);
+
+/// One side of the session.
+pub struct Peer<I> {
+    session: RollbackSession<I>,
+    player: usize,
+    /// Local inputs, indexed by frame.
+    local: Vec<I>,
+    /// Number of local frames the remote peer has acknowledged.
+    acked: u32,
+}
+
+impl<I: Clone + PartialEq + Default + 'static> Peer<I> {
+    fn new(world: World, player: usize, apply: ApplyInputs<I>, max_prediction: u32) -> Self {
+        // Shared start tick, see the module docs
+        let mut session = RollbackSession::new(world, 2, move |w, inputs: &[I]| {
+            apply(w, [&inputs[0], &inputs[1]])
+        });
+        session.set_max_prediction(max_prediction);
+        session.set_checksum(world_checksum);
+
+        Peer {
+            session,
+            player,
+            local: Vec::new(),
+            acked: 0,
+        }
+    }
+
+    pub fn world(&self) -> &World {
+        self.session.world()
+    }
+
+    pub fn world_mut(&mut self) -> &mut World {
+        self.session.world_mut()
+    }
+
+    /// The rollback session simulating this peer's world.
+    pub fn session(&self) -> &RollbackSession<I> {
+        &self.session
+    }
+
+    /// Number of frames simulated so far.
+    pub fn frame(&self) -> u32 {
+        self.session.frame()
+    }
+
+    /// Number of frames whose remote input is confirmed.
+    pub fn confirmed(&self) -> u32 {
+        self.session.confirmed(1 - self.player)
+    }
+
+    /// World checksum after each simulated frame. Entries below `confirmed()` are final.
+    pub fn checksums(&self) -> &[u64] {
+        self.session.checksums()
+    }
+
+    /// Number of times a misprediction forced a resimulation.
+    pub fn rollbacks(&self) -> u32 {
+        self.session.rollbacks()
+    }
+
+    /// Total number of frames simulated again after mispredictions.
+    pub fn resimulated_frames(&self) -> u32 {
+        self.session.resimulated_frames()
+    }
+
+    /// Simulates the next frame with the local input `input`.
+    fn simulate(&mut self, input: I) {
+        let frame = self.frame();
+        self.local.push(input.clone());
+        self.session
+            .add_input(self.player, frame, input)
+            .expect("local inputs are added once");
+        self.session
+            .advance()
+            .expect("NetSim checks the prediction limit");
+    }
+
+    fn receive(&mut self, packet: Packet<I>) {
+        self.acked = self.acked.max(packet.ack);
+
+        for (offset, input) in packet.inputs.into_iter().enumerate() {
+            let frame = packet.start + offset as u32;
+            self.session
+                .add_input(1 - self.player, frame, input)
+                .expect("remote inputs never change");
+        }
+        self.session.sync();
+    }
+
+    fn packet(&self) -> Packet<I> {
+        Packet {
+            start: self.acked,
+            inputs: self.local[self.acked as usize..].to_vec(),
+            ack: self.confirmed(),
+        }
+    }
+}
+
+/// Two rollback peers connected by simulated links, see the module docs.
+pub struct NetSim<I> {
+    peers: [Peer<I>; 2],
+    /// `links[p]` carries packets sent by peer `p`.
+    links: [Link<Packet<I>>; 2],
+    config: NetSimConfig,
+    now: u32,
+}
+
+impl<I: Clone + PartialEq + Default + 'static> NetSim<I> {
+    /// Creates both peers from `make_world`, which must build identical worlds. `apply`
+    /// writes one frame's inputs into a world before it runs.
+    pub fn new(
+        config: NetSimConfig,
+        make_world: impl Fn() -> World,
+        apply: ApplyInputs<I>,
+    ) -> Self {
+        NetSim {
+            peers: [
+                Peer::new(make_world(), 0, apply, config.max_prediction),
+                Peer::new(make_world(), 1, apply, config.max_prediction),
+            ],
+            links: [
+                Link::new(config.link, config.seed, 0),
+                Link::new(config.link, config.seed, 1),
+            ],
+            config,
+            now: 0,
+        }
+    }
+
+    pub fn peer(&self, player: usize) -> &Peer<I> {
+        &self.peers[player]
+    }
+
+    pub fn peer_mut(&mut self, player: usize) -> &mut Peer<I> {
+        &mut self.peers[player]
+    }
+
+    /// Traffic counters of the link carrying `player`'s packets.
+    pub fn link_stats(&self, player: usize) -> LinkStats {
+        self.links[player].stats
+    }
+
+    /// Network steps taken so far.
+    pub fn steps(&self) -> u32 {
+        self.now
+    }
+
+    /// Advances the network by one step: delivers due packets, lets each peer simulate at
+    /// most one new frame (up to `frames`, taking its input from `input(player, frame)`),
+    /// then sends each peer's unacknowledged inputs.
+    pub fn step(&mut self, frames: u32, input: &mut impl FnMut(usize, u32) -> I) {
+        for player in 0..2 {
+            let packets = self.links[1 - player].receive(self.now);
+            let peer = &mut self.peers[player];
+
+            for packet in packets {
+                peer.receive(packet);
+            }
+
+            let frame = peer.frame();
+            if frame < frames && peer.session.can_advance() {
+                peer.simulate(input(player, frame));
+            }
+        }
+
+        for player in 0..2 {
+            let packet = self.peers[player].packet();
+            self.links[player].send(self.now, packet);
+        }
+
+        self.now += 1;
+    }
+
+    /// True once both peers simulated `frames` frames with every remote input confirmed.
+    pub fn settled(&self, frames: u32) -> bool {
+        self.peers
+            .iter()
+            .all(|p| p.frame() == frames && p.confirmed() >= frames)
+    }
+
+    /// Steps until both peers have simulated and confirmed `frames` frames.
+    ///
+    /// # Panics
+    /// Panics if that takes more than `100 * (frames + 10)` steps, e.g. with a loss of 1.
+    pub fn run(&mut self, frames: u32, mut input: impl FnMut(usize, u32) -> I) {
+        let limit = self.now + 100 * (frames + 10);
+
+        while !self.settled(frames) {
+            assert!(
+                self.now < limit,
+                "NetSim did not settle {} frames within {} steps",
+                frames,
+                limit
+            );
+            self.step(frames, &mut input);
+        }
+    }
+
+    /// Returns the first confirmed frame whose checksums differ between the peers.
+    pub fn first_divergence(&self) -> Option<u32> {
+        let confirmed = self.peers[0].confirmed().min(self.peers[1].confirmed()) as usize;
+        let left = self.peers[0].checksums();
+        let right = self.peers[1].checksums();
+
+        (0..confirmed.min(left.len()).min(right.len()))
+            .find(|&f| left[f] != right[f])
+            .map(|f| f as u32)
+    }
+
+    /// Asserts both peers computed identical checksums for every confirmed frame.
+    ///
+    /// # Panics
+    /// Panics naming the first diverging frame, with a diff of the final worlds.
+    pub fn assert_converged(&self) {
+        if let Some(frame) = self.first_divergence() {
+            let diff = crate::testing::world_diff(self.peers[0].world(), self.peers[1].world());
+            panic!(
+                "Peers diverged at frame {} (link {:?})\n\n{}",
+                frame,
+                self.config.link,
+                diff.join("\n")
+            );
+        }
+    }
+}
+
+/// Hashes the world tick and every storage's `state_hash`, in the order of their type name
+/// hashes, so peers that first touched their component types in a different order agree.
+/// Values count for component types implementing `Hash`, see the `statehash` module.
+pub fn world_checksum(world: &World) -> u64 {
+    let mut storages: Vec<(u64, u128)> = world
+        .mask
+        .iter()
+        .map(|id| {
+            let storage = unsafe { world.storages[id].assume_init_ref() };
+            (stage_hash(storage.type_name()), storage.state_hash())
+        })
+        .collect();
+    storages.sort_unstable();
+
+    let mut state = StateHasher::new();
+    world.current_tick().value().hash(&mut state);
+    storages.hash(&mut state);
+    state.finish()
+}
+
+#[cfg(test)]
+#[path = "netsim.tests.rs"]
+mod tests;
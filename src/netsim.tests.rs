@@ -0,0 +1,143 @@
+use super::*;
+use crate::entity::Entity;
+use crate::prelude::*;
+
+#[derive(Component, Default, Clone, Debug, PartialEq, Hash)]
+struct Position {
+    x: i32,
+}
+
+#[derive(Component, Default, Clone, Debug, PartialEq, Hash)]
+struct Control {
+    dx: i32,
+}
+
+system! {
+    MoveSystem {
+        query! {
+            fn step(pos: &mut ViewMut<Position>, control: View<Control>) {
+                pos.x += control.dx;
+            }
+        }
+    }
+}
+
+fn make_world() -> World {
+    let mut world = World::new();
+    world.add_system::<MoveSystem>();
+    world.build_scheduler();
+
+    for _ in 0..2 {
+        let e = world.spawn();
+        world.set(e, &Position { x: 0 });
+        world.set(e, &Control { dx: 0 });
+    }
+    world
+}
+
+fn apply(world: &mut World, inputs: [&i32; 2]) {
+    let entities: Vec<Entity> = world.iter_entities().collect();
+    for (e, &dx) in entities.into_iter().zip(inputs) {
+        world.set(e, &Control { dx });
+    }
+}
+
+/// Changes often enough to be mispredicted regularly.
+fn script(player: usize, frame: u32) -> i32 {
+    ((frame / 3 + player as u32 * 2) % 5) as i32 - 2
+}
+
+fn position(world: &mut World, index: u32) -> i32 {
+    let storage = world.get_storage::<Position>();
+    unsafe { (*storage.get()).get(index).unwrap().x }
+}
+
+fn expected(player: usize, frames: u32) -> i32 {
+    (0..frames).map(|f| script(player, f)).sum()
+}
+
+#[test]
+fn test_ideal_network_converges() {
+    let mut sim = NetSim::new(NetSimConfig::default(), make_world, apply);
+    sim.run(60, script);
+    sim.assert_converged();
+
+    for player in 0..2 {
+        let world = sim.peer_mut(player).world_mut();
+        assert_eq!(position(world, 0), expected(0, 60));
+        assert_eq!(position(world, 1), expected(1, 60));
+    }
+    assert_eq!(sim.link_stats(0).dropped, 0);
+}
+
+#[test]
+fn test_adverse_network_converges() {
+    let config = NetSimConfig {
+        link: LinkConfig {
+            latency: 3,
+            jitter: 4,
+            loss: 0.25,
+        },
+        max_prediction: 8,
+        seed: 11,
+    };
+    let mut sim = NetSim::new(config, make_world, apply);
+    sim.run(120, script);
+    sim.assert_converged();
+
+    assert!(sim.link_stats(0).dropped > 0);
+    assert!(sim.peer(0).rollbacks() > 0 && sim.peer(1).rollbacks() > 0);
+    assert_eq!(sim.peer(0).checksums(), sim.peer(1).checksums());
+
+    let world = sim.peer_mut(1).world_mut();
+    assert_eq!(position(world, 0), expected(0, 120));
+}
+
+#[test]
+fn test_same_seed_reproduces_run() {
+    let config = NetSimConfig {
+        link: LinkConfig {
+            latency: 2,
+            jitter: 3,
+            loss: 0.3,
+        },
+        max_prediction: 4,
+        seed: 5,
+    };
+
+    let mut a = NetSim::new(config, make_world, apply);
+    let mut b = NetSim::new(config, make_world, apply);
+    a.run(40, script);
+    b.run(40, script);
+
+    assert_eq!(a.steps(), b.steps());
+    assert_eq!(a.link_stats(1), b.link_stats(1));
+    assert_eq!(
+        a.peer(0).resimulated_frames(),
+        b.peer(0).resimulated_frames()
+    );
+}
+
+#[test]
+#[should_panic(expected = "Peers diverged at frame")]
+fn test_divergence_is_reported() {
+    let mut sim = NetSim::new(NetSimConfig::default(), make_world, apply);
+    sim.run(10, script);
+
+    // A desync: peer 1's recorded state for frame 4 no longer matches
+    sim.peer_mut(1).session.checksums_mut()[4] ^= 1;
+    sim.assert_converged();
+}
+
+#[test]
+fn test_checksum_ignores_type_indices() {
+    // Type indices depend on which test first touched a type, so a pinned value only holds
+    // if the checksum orders storages by name
+    let world = make_world();
+    assert_eq!(world_checksum(&world), 8917290257113300014);
+
+    let mut moved = make_world();
+    let e = moved.iter_entities().next().unwrap();
+    moved.set(e, &Position { x: 1 });
+    assert_ne!(world_checksum(&moved), world_checksum(&world));
+}
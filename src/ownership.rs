@@ -0,0 +1,82 @@
+//! Ownership of components for client-authoritative netcode.
+//!
+//! In a hybrid model the server owns most state while each client owns a few components
+//! of its own entities, e.g. its aim direction. `World::set_owned::<T>(entity, owner)`
+//! records which peer owns `T` on `entity`; anything without an entry is owned by
+//! `PeerId::AUTHORITY`. Ownership lives in the `Ownership` component, so it is rolled back
+//! and resimulated with the rest of the world.
+//!
+//! Ownership is consumed in two places:
+//! - `World::apply_snapshot_from::<T>(packet, sender)` only takes the values (and
+//!   removals) of `T` that `sender` owns, so during reconciliation the owner's values win
+//!   and everyone else's copies are ignored.
+//! - With `World::set_local_peer`, debug builds reject `World::set` of a component the
+//!   local peer doesn't own.
+//!
+//! # Example
+//! ```ignore
+//! const CLIENT: PeerId = PeerId(1);
+//!
+//! server.set_owned::<Aim>(ship, CLIENT);
+//! client.set_local_peer(Some(CLIENT));
+//! client.set(ship, &Aim { angle: 90 }); // allowed, the client owns it
+//!
+//! // On the server, only the client's Aim values are taken from its packets
+//! server.apply_snapshot_from::<Aim>(&packet, CLIENT)?;
+//! ```
+
+use crate::component::Component;
+
+/// A network peer. Peers agree on ids out of band; `AUTHORITY` is the server.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Default)]
+pub struct PeerId(pub u16);
+
+impl PeerId {
+    /// Owns every component without an explicit owner.
+    pub const AUTHORITY: PeerId = PeerId(0);
+}
+
+/// Owners of an entity's components that aren't owned by `PeerId::AUTHORITY`, keyed by
+/// `wire::component_id`. Managed through `World::set_owned`.
+#[derive(Component, Clone, Default, PartialEq, Eq, Debug)]
+pub struct Ownership {
+    /// `(component id, owner)` pairs sorted by component id.
+    entries: Vec<(u64, PeerId)>,
+}
+
+impl Ownership {
+    /// The owner of the component with wire id `component`.
+    pub fn owner(&self, component: u64) -> PeerId {
+        match self.entries.binary_search_by_key(&component, |(id, _)| *id) {
+            Ok(i) => self.entries[i].1,
+            Err(_) => PeerId::AUTHORITY,
+        }
+    }
+
+    /// Makes `owner` the owner of the component with wire id `component`.
+    pub fn set_owner(&mut self, component: u64, owner: PeerId) {
+        let found = self.entries.binary_search_by_key(&component, |(id, _)| *id);
+        match (found, owner == PeerId::AUTHORITY) {
+            (Ok(i), true) => {
+                self.entries.remove(i);
+            }
+            (Ok(i), false) => self.entries[i].1 = owner,
+            (Err(_), true) => {}
+            (Err(i), false) => self.entries.insert(i, (component, owner)),
+        }
+    }
+
+    /// Whether every component is owned by `PeerId::AUTHORITY`.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// `(component id, owner)` pairs in component id order.
+    pub fn entries(&self) -> &[(u64, PeerId)] {
+        &self.entries
+    }
+}
+
+#[cfg(test)]
+#[path = "ownership.tests.rs"]
+mod tests;
@@ -0,0 +1,108 @@
+use super::*;
+use crate::entity::Entity;
+use crate::tick::Tick;
+use crate::wire::{DecodeError, Packet, PacketWriter, Reader, Wire};
+use crate::world::World;
+
+const CLIENT: PeerId = PeerId(1);
+
+#[derive(Component, Default, Clone, Debug, PartialEq)]
+struct Aim {
+    angle: i32,
+}
+
+impl Wire for Aim {
+    fn encode(&self, out: &mut Vec<u8>) {
+        self.angle.encode(out);
+    }
+
+    fn decode(reader: &mut Reader<'_>) -> Result<Self, DecodeError> {
+        Ok(Aim {
+            angle: i32::decode(reader)?,
+        })
+    }
+}
+
+fn aim(world: &mut World, entity: Entity) -> Option<Aim> {
+    let storage = world.get_storage::<Aim>();
+    unsafe { (*storage.get()).get(entity.index()).cloned() }
+}
+
+fn snapshot(world: &World) -> Vec<u8> {
+    let mut writer = PacketWriter::snapshot(Tick::new(0));
+    world.write_snapshot::<Aim>(&mut writer);
+    writer.finish()
+}
+
+#[test]
+fn test_ownership_entries_and_rollback() {
+    let mut ownership = Ownership::default();
+    ownership.set_owner(7, CLIENT);
+    ownership.set_owner(3, PeerId(2));
+    assert_eq!(ownership.entries(), &[(3, PeerId(2)), (7, CLIENT)]);
+    ownership.set_owner(3, PeerId::AUTHORITY);
+    assert_eq!(ownership.owner(3), PeerId::AUTHORITY);
+    assert_eq!(ownership.owner(7), CLIENT);
+
+    let mut world = World::new();
+    let ship = world.spawn();
+    world.get_storage::<Ownership>();
+    world.build_scheduler();
+    world.run();
+
+    world.set_owned::<Aim>(ship, CLIENT);
+    assert_eq!(world.owner_of::<Aim>(ship), CLIENT);
+    assert_eq!(world.owner_of::<Entity>(ship), PeerId::AUTHORITY);
+    world.run();
+
+    // Ownership changed during tick 1, so restoring its start undoes it
+    world.resimulate_from(Tick::new(1));
+    assert_eq!(world.owner_of::<Aim>(ship), PeerId::AUTHORITY);
+}
+
+#[test]
+fn test_snapshots_only_apply_owned_values() {
+    let mut server = World::new();
+    let mut client = World::new();
+    let (owned, other) = (server.spawn(), server.spawn());
+    assert_eq!((client.spawn(), client.spawn()), (owned, other));
+
+    for world in [&mut server, &mut client] {
+        world.set_owned::<Aim>(owned, CLIENT);
+    }
+    server.set(owned, &Aim { angle: 1 });
+    server.set(other, &Aim { angle: 2 });
+    client.set(owned, &Aim { angle: 90 });
+
+    // The server's copy of the client's aim doesn't overwrite the client's
+    client
+        .apply_snapshot_from::<Aim>(
+            &Packet::decode(&snapshot(&server)).unwrap(),
+            PeerId::AUTHORITY,
+        )
+        .unwrap();
+    assert_eq!(aim(&mut client, owned), Some(Aim { angle: 90 }));
+    assert_eq!(aim(&mut client, other), Some(Aim { angle: 2 }));
+
+    // The client's packet only carries weight for what it owns
+    client.set(other, &Aim { angle: -5 });
+    server
+        .apply_snapshot_from::<Aim>(&Packet::decode(&snapshot(&client)).unwrap(), CLIENT)
+        .unwrap();
+    assert_eq!(aim(&mut server, owned), Some(Aim { angle: 90 }));
+    assert_eq!(aim(&mut server, other), Some(Aim { angle: 2 }));
+}
+
+#[test]
+#[cfg(debug_assertions)]
+#[should_panic(expected = "owned by PeerId(0)")]
+fn test_local_peer_cannot_write_foreign_components() {
+    let mut world = World::new();
+    let ship = world.spawn();
+    world.set_owned::<Aim>(ship, CLIENT);
+    world.set_local_peer(Some(CLIENT));
+    world.set(ship, &Aim { angle: 1 });
+
+    let other = world.spawn();
+    world.set(other, &Aim { angle: 1 });
+}
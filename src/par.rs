@@ -0,0 +1,57 @@
+//! Block-parallel iteration inside one system, used by `system!` with `Parallel = true`.
+//!
+//! The generated `run()` walks the masks as usual but, instead of calling the query
+//! function, collects the matched inner blocks and gathers raw pointers to the matched
+//! components. The blocks are then handed to `for_each_block`, which spreads them over
+//! the rayon pool. Each entity only reaches its own components, so the result doesn't
+//! depend on how blocks are scheduled.
+//!
+//! As in a sequential run, only components actually written through `ViewMut` count as
+//! changed: a task keeps a copy of a component on its first mutable access, and once all
+//! tasks finished the calling thread records those copies with
+//! `ComponentStorage::record_write` in index order, so the rollback snapshot is the same
+//! as a sequential run's.
+//!
+//! # Example
+//! ```ignore
+//! system! {
+//!     IntegrateSystem {
+//!         query! {
+//!             fn integrate(pos: &mut ViewMut<Position>, vel: View<Velocity>) {
+//!                 pos.x += vel.x;
+//!             }
+//!         }
+//!     }
+//!     Parallel = true
+//! }
+//! ```
+
+/// Pointers to the components of one matched entity.
+#[doc(hidden)]
+pub struct Slots<P>(pub P);
+
+// SAFETY: the pointers of different entities never alias, and each `Slots` is only
+// dereferenced by the task running its block
+unsafe impl<P> Send for Slots<P> {}
+unsafe impl<P> Sync for Slots<P> {}
+
+/// Calls `f` on every entity of every block, running the blocks on the rayon pool when
+/// the `parallel` feature is on and in order otherwise. Entities of one block always run
+/// in ascending index order.
+#[doc(hidden)]
+pub fn for_each_block<P>(jobs: &mut [Vec<(u32, Slots<P>)>], f: impl Fn(u32, &mut P) + Sync) {
+    let run_block = |block: &mut Vec<(u32, Slots<P>)>| {
+        for (index, slots) in block {
+            f(*index, &mut slots.0);
+        }
+    };
+
+    #[cfg(feature = "parallel")]
+    {
+        use rayon::prelude::*;
+        jobs.par_iter_mut().for_each(run_block);
+    }
+
+    #[cfg(not(feature = "parallel"))]
+    jobs.iter_mut().for_each(run_block);
+}
@@ -0,0 +1,169 @@
+//! Component writes for networked entities that don't exist locally yet.
+//!
+//! Packets arrive out of order, so a message carrying an entity's components can beat the
+//! message that spawns it. `World::stage_pending(net_id, value)` takes such a write keyed
+//! by the sender's network id for the entity. If the id is already mapped to a live local
+//! entity the value is set right away; otherwise it waits in the world's pending table
+//! until `World::map_net_id` (or `World::spawn_networked`) binds the id, and is then set in
+//! staging order. Writes still waiting after `set_pending_expiry` ticks (60 by default)
+//! are dropped, so ids whose spawn never arrives don't pile up.
+//!
+//! The table is rollback-aware: `World::rollback` forgets writes staged and mappings made
+//! after the target tick, and writes applied or expired after it are pending again, so a
+//! resimulation that replays the same messages applies them at the same ticks.
+//!
+//! # Example
+//! ```ignore
+//! // The Health update for net id 7 arrives first...
+//! world.stage_pending(7, DynValue::new(Health { value: 80 }));
+//! // ...then its spawn message
+//! let ship = world.spawn_networked(7);
+//! assert_eq!(world.get::<Health>(ship), Some(&Health { value: 80 }));
+//! ```
+
+use crate::dynamic::DynValue;
+use crate::entity::Entity;
+use crate::tick::Tick;
+use std::collections::HashMap;
+
+/// Ticks a staged write waits for its entity by default.
+pub const DEFAULT_PENDING_EXPIRY: u32 = 60;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Status {
+    Pending,
+    Applied(Tick),
+    Expired(Tick),
+}
+
+struct PendingWrite {
+    net_id: u64,
+    value: DynValue,
+    staged: Tick,
+    status: Status,
+}
+
+impl PendingWrite {
+    /// Tick the write was applied or expired at.
+    fn resolved_at(&self) -> Option<Tick> {
+        match self.status {
+            Status::Pending => None,
+            Status::Applied(at) | Status::Expired(at) => Some(at),
+        }
+    }
+}
+
+/// Staged writes and network id mappings of one world, see the module docs.
+pub(crate) struct PendingTable {
+    /// In staging order.
+    writes: Vec<PendingWrite>,
+    mapped: HashMap<u64, Entity>,
+    /// Undo log: `(tick, net id, previous mapping)` for every mapping change, oldest first.
+    history: Vec<(Tick, u64, Option<Entity>)>,
+    expiry_ticks: u32,
+}
+
+impl PendingTable {
+    pub(crate) fn new() -> Self {
+        PendingTable {
+            writes: Vec::new(),
+            mapped: HashMap::new(),
+            history: Vec::new(),
+            expiry_ticks: DEFAULT_PENDING_EXPIRY,
+        }
+    }
+
+    /// Drops every staged write and mapping, keeping the expiry setting.
+    pub(crate) fn clear(&mut self) {
+        self.writes.clear();
+        self.mapped.clear();
+        self.history.clear();
+    }
+
+    pub(crate) fn set_expiry(&mut self, ticks: u32) {
+        self.expiry_ticks = ticks;
+    }
+
+    /// The entity `net_id` is mapped to, alive or not.
+    pub(crate) fn entity(&self, net_id: u64) -> Option<Entity> {
+        self.mapped.get(&net_id).copied()
+    }
+
+    pub(crate) fn stage(&mut self, tick: Tick, net_id: u64, value: DynValue) {
+        self.writes.push(PendingWrite {
+            net_id,
+            value,
+            staged: tick,
+            status: Status::Pending,
+        });
+    }
+
+    /// Maps `net_id` to `entity` and returns the writes waiting for it, marked applied.
+    pub(crate) fn map(&mut self, tick: Tick, net_id: u64, entity: Entity) -> Vec<DynValue> {
+        let previous = self.mapped.insert(net_id, entity);
+        if previous != Some(entity) {
+            self.history.push((tick, net_id, previous));
+        }
+
+        let mut ready = Vec::new();
+        for write in &mut self.writes {
+            if write.net_id == net_id && write.status == Status::Pending {
+                write.status = Status::Applied(tick);
+                ready.push(write.value.clone());
+            }
+        }
+        ready
+    }
+
+    /// Expires writes that waited too long at the start of `tick`, and forgets resolved
+    /// writes older than `oldest`, which no rollback can reach.
+    pub(crate) fn begin_tick(&mut self, tick: Tick, oldest: Tick) {
+        let expiry_ticks = self.expiry_ticks as i32;
+        for write in &mut self.writes {
+            if write.status == Status::Pending && tick.diff(write.staged).value() >= expiry_ticks {
+                write.status = Status::Expired(tick);
+            }
+        }
+
+        self.writes
+            .retain(|write| write.resolved_at().is_none_or(|at| !at.is_before(oldest)));
+    }
+
+    /// Writes still waiting for their entity.
+    pub(crate) fn pending(&self) -> usize {
+        self.writes
+            .iter()
+            .filter(|write| write.status == Status::Pending)
+            .count()
+    }
+
+    /// Undoes every staging, mapping, application and expiry after `target_tick`.
+    pub(crate) fn rollback(&mut self, target_tick: Tick) {
+        self.writes
+            .retain(|write| !write.staged.is_after(target_tick));
+        for write in &mut self.writes {
+            if write
+                .resolved_at()
+                .is_some_and(|at| at.is_after(target_tick))
+            {
+                write.status = Status::Pending;
+            }
+        }
+
+        while let Some(&(tick, net_id, previous)) = self.history.last() {
+            if !tick.is_after(target_tick) {
+                break;
+            }
+
+            self.history.pop();
+            match previous {
+                Some(entity) => self.mapped.insert(net_id, entity),
+                None => self.mapped.remove(&net_id),
+            };
+        }
+    }
+}
+
+#[cfg(test)]
+#[path = "pending.tests.rs"]
+mod tests;
@@ -0,0 +1,68 @@
+use crate::dynamic::DynValue;
+use crate::prelude::*;
+
+#[derive(Component, Default, Clone, Debug, PartialEq)]
+struct Health {
+    value: i32,
+}
+
+fn world() -> World {
+    let mut world = World::new();
+    world.get_storage::<Health>();
+    world.build_scheduler();
+    world
+}
+
+#[test]
+fn test_staged_writes_apply_when_mapped() {
+    let mut world = world();
+    world.stage_pending(7, DynValue::new(Health { value: 80 }));
+    world.stage_pending(7, DynValue::new(Health { value: 70 }));
+    assert_eq!(world.pending_count(), 2);
+    world.run();
+
+    let ship = world.spawn_networked(7);
+    assert_eq!(world.get::<Health>(ship), Some(&Health { value: 70 }));
+    assert_eq!(world.net_entity(7), Some(ship));
+    assert_eq!(world.pending_count(), 0);
+
+    // Once mapped, writes go straight to the entity
+    world.stage_pending(7, DynValue::new(Health { value: 60 }));
+    assert_eq!(world.get::<Health>(ship), Some(&Health { value: 60 }));
+}
+
+#[test]
+fn test_rollback_restores_pending_writes() {
+    let mut world = world();
+    world.run();
+    world.stage_pending(7, DynValue::new(Health { value: 80 }));
+    world.run();
+    world.spawn_networked(7);
+    world.run();
+
+    world.rollback(Tick::new(1));
+    assert_eq!(world.net_entity(7), None);
+    assert_eq!(world.pending_count(), 1);
+
+    let ship = world.spawn_networked(7);
+    assert_eq!(world.get::<Health>(ship), Some(&Health { value: 80 }));
+
+    world.rollback(Tick::new(0));
+    assert_eq!(world.pending_count(), 0);
+}
+
+#[test]
+fn test_unmapped_writes_expire() {
+    let mut world = world();
+    world.set_pending_expiry(2);
+    world.stage_pending(9, DynValue::new(Health { value: 1 }));
+
+    world.run();
+    world.run();
+    assert_eq!(world.pending_count(), 1);
+    world.run();
+    assert_eq!(world.pending_count(), 0);
+
+    world.rollback(Tick::new(1));
+    assert_eq!(world.pending_count(), 1);
+}
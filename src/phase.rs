@@ -0,0 +1,52 @@
+//! What the world is doing right now, and which mutations that allows.
+//!
+//! A world is `Idle` between ticks, `Simulating` while `run()` executes the scheduler and
+//! `RollingBack` while `rollback()` restores storages. Only `Idle` allows the out-of-band
+//! mutation APIs (`set`, `spawn`, `destroy`, `set_with_ttl`, tags, ...); calling them in
+//! another phase would race with systems or be undone by the rollback in progress. Debug
+//! builds assert this, release builds skip the check.
+//!
+//! Prefer `World::edit_scope` for out-of-band edits. It asserts the world is idle and
+//! brings every storage and tick-dependent table up to the world tick before running the
+//! closure, so the changes are recorded at the tick they will be simulated in and roll back
+//! correctly. Storages created on first use start at the world tick for the same reason.
+//!
+//! Tick hooks registered with `World::on_tick_start` / `World::on_tick_end` run in the
+//! `RunningHooks` phase with a shared `&World`. Storages are read-only there, and like in
+//! every phase but `Idle`, debug builds reject the out-of-band mutation APIs.
+//!
+//! # Example
+//! ```ignore
+//! world.run();
+//! world.edit_scope(|w| {
+//!     let e = w.spawn();
+//!     w.set(e, &Pickup { value: 5 });
+//! });
+//! ```
+
+use crate::tick::Tick;
+use crate::world::World;
+
+/// Runtime phase of a `World`, see the module docs.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum WorldPhase {
+    /// Between ticks. Out-of-band mutation is allowed.
+    #[default]
+    Idle,
+    /// The scheduler is running systems.
+    Simulating,
+    /// Storages are being restored to an earlier tick.
+    RollingBack,
+    /// Tick hooks are running. Storages are read-only.
+    RunningHooks,
+}
+
+/// Engine glue called around every tick `World::run` simulates, see `World::on_tick_start`.
+pub type TickHook = Box<dyn FnMut(&World, Tick)>;
+
+impl WorldPhase {
+    /// Returns true if the world may be mutated outside of systems in this phase.
+    pub fn allows_mutation(self) -> bool {
+        self == WorldPhase::Idle
+    }
+}
@@ -0,0 +1,47 @@
+//! Plugins: reusable feature packs such as physics, networking or AI, added in one call.
+//!
+//! A plugin registers everything its feature needs in `Plugin::build`: storages (and with
+//! them their cleanup systems), systems, loop groups, resources and states. Pipeline groups
+//! are declared with `#[pipeline_group]` as usual and need no registration.
+//!
+//! `World::add_plugin` builds each plugin type once, so a plugin can add the plugins it
+//! depends on without checking whether the application already did. Plugins added after
+//! `build_scheduler()` work too: their systems join the schedule from the next tick on,
+//! like systems added with `World::add_system_dynamic`.
+//!
+//! # Example
+//! ```ignore
+//! struct PhysicsPlugin { gravity: f32 }
+//!
+//! impl Plugin for PhysicsPlugin {
+//!     fn build(&self, world: &mut World) {
+//!         world.add_plugin(TransformPlugin);
+//!         world.get_storage::<Velocity>();
+//!         world.insert_resource(Gravity(self.gravity));
+//!         world.add_loop_group::<SolverGroup>(LoopGroup::run_until(converged, 8));
+//!         world.add_system::<IntegrateSystem>();
+//!         world.add_system::<SolveContactsSystem>(); // Parent=[SolverGroup]
+//!     }
+//! }
+//!
+//! world.add_plugin(PhysicsPlugin { gravity: -9.8 });
+//! world.build_scheduler();
+//! ```
+
+use crate::world::World;
+
+/// A feature pack registering its storages, systems, groups and resources, see the
+/// module docs.
+pub trait Plugin: 'static {
+    /// Registers the plugin's contents with `world`.
+    fn build(&self, world: &mut World);
+
+    /// The plugin's name, for diagnostics.
+    fn name(&self) -> &'static str {
+        std::any::type_name::<Self>()
+    }
+}
+
+#[cfg(test)]
+#[path = "plugin.tests.rs"]
+mod tests;
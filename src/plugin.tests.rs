@@ -0,0 +1,73 @@
+use super::*;
+use crate::prelude::*;
+
+#[derive(Component, Clone, Default, PartialEq, Debug)]
+struct Fuel(u32);
+
+#[derive(Clone, Default, PartialEq, Debug)]
+struct Burn(u32);
+
+#[derive(Clone, Default, PartialEq, Debug)]
+struct Builds(u32);
+
+system! {
+    BurnSystem {
+        query! {
+            fn burn(fuel: &mut ViewMut<Fuel>, rate: Res<Burn>) {
+                fuel.0 -= rate.0;
+            }
+        }
+    }
+}
+
+struct CorePlugin;
+
+impl Plugin for CorePlugin {
+    fn build(&self, world: &mut World) {
+        let builds = world.resource::<Builds>().map_or(0, |b| b.0);
+        world.insert_resource(Builds(builds + 1));
+    }
+}
+
+struct EnginePlugin {
+    rate: u32,
+}
+
+impl Plugin for EnginePlugin {
+    fn build(&self, world: &mut World) {
+        world.add_plugin(CorePlugin);
+        world.insert_resource(Burn(self.rate));
+        world.add_system::<BurnSystem>();
+    }
+}
+
+#[test]
+fn test_plugin_registers_systems_and_resources() {
+    let mut world = World::new();
+    world.add_plugin(CorePlugin);
+    world.add_plugin(EnginePlugin { rate: 2 });
+    world.build_scheduler();
+    let e = world.spawn();
+    world.set(e, &Fuel(10));
+    world.run();
+
+    assert_eq!(world.get::<Fuel>(e), Some(&Fuel(8)));
+    assert!(world.has_plugin::<EnginePlugin>());
+    // The dependency was built once, by whichever plugin added it first
+    assert_eq!(world.resource::<Builds>(), Some(&Builds(1)));
+}
+
+#[test]
+fn test_plugin_added_after_build_runs_from_next_tick() {
+    let mut world = World::new();
+    world.build_scheduler();
+    let e = world.spawn();
+    world.set(e, &Fuel(10));
+    world.run();
+
+    world.add_plugin(EnginePlugin { rate: 3 });
+    world.add_plugin(EnginePlugin { rate: 5 });
+    world.run();
+    assert_eq!(world.get::<Fuel>(e), Some(&Fuel(7)));
+    assert_eq!(EnginePlugin { rate: 3 }.name(), std::any::type_name::<EnginePlugin>());
+}
@@ -1,6 +1,9 @@
 pub use crate::{component, entity, system, tick, view, world};
 
 pub use crate::{
-    component::Component, entity::Entity, system::system, tick::Tick, view::View, view::ViewMut,
-    world::World,
+    builder::WorldBuilder, bundle::Bundle, component::Component, entity::Entity,
+    entity::EntityWeak, plugin::Plugin, system::system, tags::TagId, tags::TagSet, tick::Tick,
+    view::Aggregate, view::View, view::ViewMut, world::World,
 };
+
+pub use crate::dirty_bridge;
@@ -0,0 +1,110 @@
+//! Per-system timings and critical-path priorities for the scheduler.
+//!
+//! Systems in one wavefront run in parallel, and the wavefront ends when its slowest
+//! dependency chain does. With the thread pool busy, a long system spawned last starts
+//! late and stretches the whole tick. `World::set_profiling(true)` makes the scheduler time
+//! every system, keeping a smoothed duration per system. `World::prioritize_schedule` then
+//! walks the dependency graph from the last wavefront back, giving every system the length
+//! of the longest chain it starts (its own time plus the longest chain among the systems
+//! that depend on it), and reorders each wavefront so the longest chains are spawned first.
+//!
+//! Systems in one wavefront never conflict, so the new order doesn't change any result;
+//! the wavefronts themselves stay the same. A loop group counts as one node whose cost is
+//! the critical path of one iteration of its children. `Scheduler::critical_path` returns
+//! the chain the priorities were based on.
+//!
+//! # Example
+//! ```ignore
+//! world.set_profiling(true);
+//! for _ in 0..60 {
+//!     world.run();
+//! }
+//! world.prioritize_schedule();
+//!
+//! for (name, time) in world.scheduler().unwrap().critical_path() {
+//!     println!("{name}: {time:?}");
+//! }
+//! ```
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+/// Smoothed run time of every system of a scheduler, indexed like its systems.
+pub struct SystemProfile {
+    /// Moving average in nanoseconds, 0 until the system first ran.
+    nanos: Vec<AtomicU64>,
+}
+
+impl SystemProfile {
+    pub fn new(len: usize) -> Self {
+        SystemProfile {
+            nanos: (0..len).map(|_| AtomicU64::new(0)).collect(),
+        }
+    }
+
+    /// Runs `run` and folds its duration into the average of system `idx`.
+    pub fn time(&self, idx: usize, run: impl FnOnce()) {
+        let started = Instant::now();
+        run();
+        let sample = (started.elapsed().as_nanos() as u64).max(1);
+
+        // A system only ever runs on one thread at a time, so load and store don't race
+        let slot = &self.nanos[idx];
+        let average = match slot.load(Ordering::Relaxed) {
+            0 => sample,
+            old => old - old / 8 + sample / 8,
+        };
+        slot.store(average, Ordering::Relaxed);
+    }
+
+    /// The smoothed run time of system `idx`, zero if it never ran.
+    pub fn cost(&self, idx: usize) -> Duration {
+        Duration::from_nanos(self.nanos[idx].load(Ordering::Relaxed))
+    }
+}
+
+/// Longest chain starting at each node, its own cost included. `order` lists the nodes
+/// in dependency order and `successors` the nodes depending on each one.
+pub(crate) fn bottom_levels(
+    order: impl DoubleEndedIterator<Item = usize>,
+    successors: &[Vec<usize>],
+    cost: impl Fn(usize) -> Duration,
+    levels: &mut [Duration],
+) {
+    for node in order.rev() {
+        let tail = successors[node]
+            .iter()
+            .map(|&next| levels[next])
+            .max()
+            .unwrap_or_default();
+        levels[node] = cost(node) + tail;
+    }
+}
+
+/// Follows the longest chain from the best of `starts`, ties going to the lower index.
+pub(crate) fn longest_chain(
+    starts: impl Iterator<Item = usize>,
+    successors: &[Vec<usize>],
+    levels: &[Duration],
+) -> Vec<usize> {
+    let best = |nodes: &mut dyn Iterator<Item = usize>| {
+        nodes.fold(None, |best: Option<usize>, node| match best {
+            Some(b) if levels[b] > levels[node] || (levels[b] == levels[node] && b < node) => {
+                Some(b)
+            }
+            _ => Some(node),
+        })
+    };
+
+    let mut chain = Vec::new();
+    let mut next = best(&mut { starts });
+    while let Some(node) = next {
+        chain.push(node);
+        next = best(&mut successors[node].iter().copied());
+    }
+    chain
+}
+
+#[cfg(test)]
+#[path = "profile.tests.rs"]
+mod tests;
@@ -0,0 +1,104 @@
+use super::*;
+use crate::scheduler::{PipelineStage, Scheduler};
+use std::any::TypeId;
+
+struct QuickSystem;
+struct HeadSystem;
+struct TailSystem;
+
+static AFTER_HEAD: [TypeId; 1] = [TypeId::of::<HeadSystem>()];
+
+impl PipelineStage for QuickSystem {
+    fn run(&self) {
+        std::thread::sleep(Duration::from_millis(1));
+    }
+
+    fn type_id(&self) -> TypeId {
+        TypeId::of::<Self>()
+    }
+}
+
+impl PipelineStage for HeadSystem {
+    fn run(&self) {
+        std::thread::sleep(Duration::from_millis(10));
+    }
+
+    fn type_id(&self) -> TypeId {
+        TypeId::of::<Self>()
+    }
+}
+
+impl PipelineStage for TailSystem {
+    fn run(&self) {
+        std::thread::sleep(Duration::from_millis(20));
+    }
+
+    fn type_id(&self) -> TypeId {
+        TypeId::of::<Self>()
+    }
+
+    fn after(&self) -> &'static [TypeId] {
+        &AFTER_HEAD
+    }
+}
+
+fn scheduler() -> Scheduler {
+    Scheduler::new(vec![
+        Box::new(QuickSystem),
+        Box::new(HeadSystem),
+        Box::new(TailSystem),
+    ])
+}
+
+#[test]
+fn test_bottom_levels_and_longest_chain() {
+    // 0 -> 2 -> 3 and 1 -> 3, costs 1, 5, 2, 1
+    let successors = vec![vec![2], vec![3], vec![3], vec![]];
+    let costs = [1, 5, 2, 1].map(Duration::from_millis);
+    let mut levels = vec![Duration::ZERO; 4];
+    bottom_levels(0..4, &successors, |i| costs[i], &mut levels);
+
+    assert_eq!(levels, [4, 6, 3, 1].map(Duration::from_millis));
+    assert_eq!(longest_chain(0..2, &successors, &levels), vec![1, 3]);
+}
+
+#[test]
+fn test_nothing_recorded_without_profiling() {
+    let mut scheduler = scheduler();
+    scheduler.run();
+
+    assert_eq!(scheduler.system_timings(), None);
+    assert!(scheduler.critical_path().is_empty());
+
+    // Without timings every chain is empty, so index order is kept
+    scheduler.prioritize();
+    assert_eq!(scheduler.wavefronts(), &[vec![0, 1], vec![2]]);
+}
+
+#[test]
+fn test_prioritize_spawns_longest_chain_first() {
+    let mut scheduler = scheduler();
+    scheduler.set_profiling(true);
+    assert_eq!(scheduler.wavefronts(), &[vec![0, 1], vec![2]]);
+
+    scheduler.run();
+    let timings = scheduler.system_timings().unwrap();
+    assert!(timings[2].0.ends_with("TailSystem"));
+    assert!(timings[2].1 >= Duration::from_millis(20));
+
+    let lengths = scheduler.critical_path_lengths();
+    assert!(lengths[1] >= Duration::from_millis(30));
+    assert!(lengths[1] > lengths[0]);
+
+    scheduler.prioritize();
+    assert_eq!(scheduler.wavefronts(), &[vec![1, 0], vec![2]]);
+
+    let path: Vec<&str> = scheduler
+        .critical_path()
+        .into_iter()
+        .map(|(name, _)| name)
+        .collect();
+    assert_eq!(path.len(), 2);
+    assert!(path[0].ends_with("HeadSystem"));
+    assert!(path[1].ends_with("TailSystem"));
+}
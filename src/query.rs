@@ -0,0 +1,341 @@
+//! Runtime queries for code that isn't a `system!`.
+//!
+//! `World::query::<(&A, &mut B)>()` iterates every entity that has all of the requested
+//! components, in ascending index order, with the same three-level mask intersection the
+//! macro generates: middle and inner blocks missing from any storage are skipped without
+//! being visited. Filters are added with builders:
+//!
+//! - `.with::<C>()` requires `C` without fetching it;
+//! - `.without::<D>()` skips entities that have `D`;
+//! - `.changed::<E>()` keeps entities whose `E` changed since the last change clear. Like
+//!   `Changed=[E, F]`, several `changed` filters match a change in any of them;
+//! - `.changed_since::<E>(tick)` keeps entities whose `E` changed in a tick after `tick`,
+//!   like `ChangedSince = tick`, so a UI or network layer can pass the last tick it looked
+//!   at instead of relying on when change masks are cleared. It turns every `changed`
+//!   filter of the query into one comparing against `tick`.
+//!
+//! Entities are visited in strictly ascending index order, whatever the filters.
+//!
+//! Mutable items go through `ComponentStorage::get_mut`, so writes are change-tracked and
+//! rolled back like writes made by systems. The query borrows the world mutably for as
+//! long as its items are alive, and asking for the same component mutably twice panics.
+//!
+//! # Example
+//! ```ignore
+//! for (position, velocity) in world
+//!     .query::<(&mut Position, &Velocity)>()
+//!     .without::<Frozen>()
+//! {
+//!     position.x += velocity.x;
+//! }
+//! ```
+
+use crate::component::{Component, Resource};
+use crate::rollback::StorageLike;
+use crate::storage::ComponentStorage;
+use crate::tick::Tick;
+use crate::world::World;
+use std::cell::UnsafeCell;
+use std::rc::Rc;
+
+type StorageHandle<T> = Rc<UnsafeCell<<T as Component>::Storage>>;
+
+/// One element of a query: `&T` or `&mut T`.
+pub trait QueryTerm {
+    type Component: Component;
+    type Item<'w>;
+
+    const MUTABLE: bool;
+
+    /// # Safety
+    /// The component must be present at `index`, and no other reference to it may be
+    /// alive if the term is mutable.
+    unsafe fn fetch<'w>(storage: &StorageHandle<Self::Component>, index: u32) -> Self::Item<'w>;
+}
+
+impl<T: Component> QueryTerm for &T {
+    type Component = T;
+    type Item<'w> = &'w T;
+
+    const MUTABLE: bool = false;
+
+    unsafe fn fetch<'w>(storage: &StorageHandle<T>, index: u32) -> &'w T {
+        unsafe {
+            (*storage.get())
+                .get(index)
+                .expect("matched index is present")
+        }
+    }
+}
+
+impl<T: Component> QueryTerm for &mut T {
+    type Component = T;
+    type Item<'w> = &'w mut T;
+
+    const MUTABLE: bool = true;
+
+    unsafe fn fetch<'w>(storage: &StorageHandle<T>, index: u32) -> &'w mut T {
+        unsafe { (*storage.get()).get_mut(index) }
+    }
+}
+
+/// What a query fetches: a `QueryTerm` or a tuple of up to eight of them.
+pub trait QueryData {
+    type Item<'w>;
+    type Storages;
+
+    fn storages(world: &mut World) -> Self::Storages;
+
+    /// Type-erased handles for the mask intersection.
+    fn erased(storages: &Self::Storages) -> Vec<Box<dyn StorageLike>>;
+
+    /// Type index, name and mutability of every term, to reject aliasing writes.
+    fn access() -> Vec<(usize, &'static str, bool)>;
+
+    /// # Safety
+    /// Every component must be present at `index`, and each index may be fetched once.
+    unsafe fn fetch<'w>(storages: &Self::Storages, index: u32) -> Self::Item<'w>;
+}
+
+impl<Q: QueryTerm> QueryData for Q {
+    type Item<'w> = Q::Item<'w>;
+    type Storages = StorageHandle<Q::Component>;
+
+    fn storages(world: &mut World) -> Self::Storages {
+        world.get_storage::<Q::Component>()
+    }
+
+    fn erased(storages: &Self::Storages) -> Vec<Box<dyn StorageLike>> {
+        vec![Box::new(storages.clone())]
+    }
+
+    fn access() -> Vec<(usize, &'static str, bool)> {
+        let name = std::any::type_name::<Q::Component>();
+        vec![(Q::Component::type_index(), name, Q::MUTABLE)]
+    }
+
+    unsafe fn fetch<'w>(storages: &Self::Storages, index: u32) -> Self::Item<'w> {
+        unsafe { Q::fetch(storages, index) }
+    }
+}
+
+macro_rules! impl_query_data {
+    ($($t:ident => $i:tt),+) => {
+        impl<$($t: QueryTerm),+> QueryData for ($($t,)+) {
+            type Item<'w> = ($($t::Item<'w>,)+);
+            type Storages = ($(StorageHandle<$t::Component>,)+);
+
+            fn storages(world: &mut World) -> Self::Storages {
+                ($(world.get_storage::<$t::Component>(),)+)
+            }
+
+            fn erased(storages: &Self::Storages) -> Vec<Box<dyn StorageLike>> {
+                vec![$(Box::new(storages.$i.clone()) as Box<dyn StorageLike>),+]
+            }
+
+            fn access() -> Vec<(usize, &'static str, bool)> {
+                let mut access = Vec::new();
+                $(access.extend(<$t as QueryData>::access());)+
+                access
+            }
+
+            unsafe fn fetch<'w>(storages: &Self::Storages, index: u32) -> Self::Item<'w> {
+                unsafe { ($($t::fetch(&storages.$i, index),)+) }
+            }
+        }
+    };
+}
+
+impl_query_data!(A => 0);
+impl_query_data!(A => 0, B => 1);
+impl_query_data!(A => 0, B => 1, C => 2);
+impl_query_data!(A => 0, B => 1, C => 2, D => 3);
+impl_query_data!(A => 0, B => 1, C => 2, D => 3, E => 4);
+impl_query_data!(A => 0, B => 1, C => 2, D => 3, E => 4, F => 5);
+impl_query_data!(A => 0, B => 1, C => 2, D => 3, E => 4, F => 5, G => 6);
+impl_query_data!(A => 0, B => 1, C => 2, D => 3, E => 4, F => 5, G => 6, H => 7);
+
+/// Iterator over the entities matching a query, see the module docs.
+pub struct Query<'w, D: QueryData> {
+    world: &'w mut World,
+    storages: D::Storages,
+    /// Storages every match must have: the fetched ones and the `with` filters.
+    required: Vec<Box<dyn StorageLike>>,
+    without: Vec<Box<dyn StorageLike>>,
+    changed: Vec<Box<dyn StorageLike>>,
+    /// Set by `changed_since`.
+    since: Option<Tick>,
+    cursor: Option<Cursor>,
+}
+
+/// Remaining bits of each level, as in `StorageIter`.
+struct Cursor {
+    outer_mask: u128,
+    middle_mask: u128,
+    inner_mask: u128,
+    ri: u32,
+    mi: u32,
+}
+
+impl<'w, D: QueryData> Query<'w, D> {
+    pub(crate) fn new(world: &'w mut World) -> Self {
+        let access = D::access();
+        for (i, &(id, name, mutable)) in access.iter().enumerate() {
+            let aliased = access[..i]
+                .iter()
+                .any(|&(other, _, m)| other == id && (m || mutable));
+            assert!(!aliased, "query accesses {name} mutably and more than once");
+        }
+
+        let storages = D::storages(world);
+        let required = D::erased(&storages);
+        Query {
+            world,
+            storages,
+            required,
+            without: Vec::new(),
+            changed: Vec::new(),
+            since: None,
+            cursor: None,
+        }
+    }
+
+    /// Only matches entities that also have `T`.
+    pub fn with<T: Component>(mut self) -> Self {
+        self.required.push(Box::new(self.world.get_storage::<T>()));
+        self
+    }
+
+    /// Skips entities that have `T`.
+    pub fn without<T: Component>(mut self) -> Self {
+        self.without.push(Box::new(self.world.get_storage::<T>()));
+        self
+    }
+
+    /// Only matches entities whose `T` changed since the last change clear, or whose
+    /// component of any other `changed` filter did.
+    pub fn changed<T: Component>(mut self) -> Self {
+        self.changed.push(Box::new(self.world.get_storage::<T>()));
+        self
+    }
+
+    /// Only matches entities whose `T` changed in a tick after `since`, or whose component
+    /// of any other `changed` filter did, which then also compare against `since`.
+    pub fn changed_since<T: Component>(mut self, since: Tick) -> Self {
+        self.since = Some(since);
+        self.changed::<T>()
+    }
+
+    fn outer_mask(&self) -> u128 {
+        let mut mask = self
+            .required
+            .iter()
+            .fold(u128::MAX, |m, s| m & s.root_mask());
+        mask &= !self.without.iter().fold(0, |m, s| m | s.root_full_mask());
+        if !self.changed.is_empty() {
+            mask &= self
+                .changed
+                .iter()
+                .fold(0, |m, s| match self.since {
+                    Some(since) => m | s.root_changed_since(since),
+                    None => m | s.root_changed_mask(),
+                });
+        }
+        mask
+    }
+
+    fn middle_mask(&self, ri: u32) -> u128 {
+        let mut mask = self
+            .required
+            .iter()
+            .fold(u128::MAX, |m, s| m & s.middle_mask(ri));
+        mask &= !self
+            .without
+            .iter()
+            .fold(0, |m, s| m | s.middle_full_mask(ri));
+        if !self.changed.is_empty() {
+            mask &= self
+                .changed
+                .iter()
+                .fold(0, |m, s| match self.since {
+                    Some(since) => m | s.middle_changed_since(ri, since),
+                    None => m | s.middle_changed_mask(ri),
+                });
+        }
+        mask
+    }
+
+    fn inner_mask(&self, ri: u32, mi: u32) -> u128 {
+        let mut mask = self
+            .required
+            .iter()
+            .fold(u128::MAX, |m, s| m & s.inner_mask(ri, mi));
+        mask &= !self.without.iter().fold(0, |m, s| m | s.inner_mask(ri, mi));
+        if !self.changed.is_empty() {
+            mask &= self
+                .changed
+                .iter()
+                .fold(0, |m, s| match self.since {
+                    Some(since) => m | s.inner_changed_since(ri, mi, since),
+                    None => m | s.inner_changed_mask(ri, mi),
+                });
+        }
+        mask
+    }
+
+    /// The next matching index.
+    fn advance(&mut self) -> Option<u32> {
+        if self.cursor.is_none() {
+            self.cursor = Some(Cursor {
+                outer_mask: self.outer_mask(),
+                middle_mask: 0,
+                inner_mask: 0,
+                ri: 0,
+                mi: 0,
+            });
+        }
+
+        loop {
+            let cursor = self.cursor.as_mut().expect("cursor was just set");
+            if cursor.inner_mask != 0 {
+                let ii = cursor.inner_mask.trailing_zeros();
+                cursor.inner_mask &= !(1u128 << ii);
+                return Some(cursor.ri * 16384 + cursor.mi * 128 + ii);
+            }
+
+            if cursor.middle_mask != 0 {
+                cursor.mi = cursor.middle_mask.trailing_zeros();
+                cursor.middle_mask &= !(1u128 << cursor.mi);
+                let (ri, mi) = (cursor.ri, cursor.mi);
+                let inner_mask = self.inner_mask(ri, mi);
+                self.cursor.as_mut().expect("cursor is set").inner_mask = inner_mask;
+                continue;
+            }
+
+            if cursor.outer_mask == 0 {
+                return None;
+            }
+
+            cursor.ri = cursor.outer_mask.trailing_zeros();
+            cursor.outer_mask &= !(1u128 << cursor.ri);
+            let ri = cursor.ri;
+            let middle_mask = self.middle_mask(ri);
+            self.cursor.as_mut().expect("cursor is set").middle_mask = middle_mask;
+        }
+    }
+}
+
+impl<'w, D: QueryData> Iterator for Query<'w, D> {
+    type Item = D::Item<'w>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let index = self.advance()?;
+        // Each index is yielded once, so mutable items never alias, and the world stays
+        // borrowed for 'w
+        Some(unsafe { D::fetch(&self.storages, index) })
+    }
+}
+
+#[cfg(test)]
+#[path = "query.tests.rs"]
+mod tests;
@@ -0,0 +1,251 @@
+use crate::prelude::*;
+use crate::tick::TickDelta;
+
+#[derive(Component, Default, Clone, Debug, PartialEq)]
+struct Position {
+    x: i32,
+}
+
+#[derive(Component, Default, Clone, Debug, PartialEq)]
+struct Velocity {
+    x: i32,
+}
+
+#[derive(Component, Default, Clone, Debug, PartialEq)]
+struct Frozen {}
+
+#[test]
+fn test_query_fetches_and_filters() {
+    let mut world = World::new();
+    let mut entities = Vec::new();
+    for i in 0..300 {
+        let entity = world.spawn();
+        world.set(entity, &Position { x: i });
+        if i % 2 == 0 {
+            world.set(entity, &Velocity { x: 1 });
+        }
+        if i % 3 == 0 {
+            world.set(entity, &Frozen {});
+        }
+        entities.push(entity);
+    }
+
+    let mut moved = Vec::new();
+    for (entity, position, velocity) in world
+        .query::<(&Entity, &mut Position, &Velocity)>()
+        .without::<Frozen>()
+    {
+        position.x += velocity.x;
+        moved.push(entity.index());
+    }
+    let expected: Vec<u32> = (0..300).filter(|i| i % 2 == 0 && i % 3 != 0).collect();
+    assert_eq!(moved, expected);
+    assert_eq!(world.get::<Position>(entities[2]), Some(&Position { x: 3 }));
+    assert_eq!(world.get::<Position>(entities[6]), Some(&Position { x: 6 }));
+
+    assert_eq!(world.query::<&Position>().with::<Frozen>().count(), 100);
+}
+
+#[test]
+fn test_query_changed_filter() {
+    let mut world = World::new();
+    let a = world.spawn();
+    let b = world.spawn();
+    world.set(a, &Position { x: 1 });
+    world.set(b, &Position { x: 2 });
+    world.build_scheduler();
+    world.run();
+
+    world.set(b, &Position { x: 5 });
+    let changed: Vec<i32> = world
+        .query::<&Position>()
+        .changed::<Position>()
+        .map(|position| position.x)
+        .collect();
+    assert_eq!(changed, vec![5]);
+}
+
+#[test]
+#[should_panic(expected = "mutably and more than once")]
+fn test_query_rejects_aliasing_writes() {
+    let mut world = World::new();
+    let _ = world.query::<(&mut Position, &Position)>();
+}
+
+#[derive(Component, Default, Clone, Debug, PartialEq)]
+#[component(storage = "sparse")]
+struct Score {
+    points: i32,
+}
+
+/// Last tick `ReportSystem` looked at.
+static REPORTED: std::sync::atomic::AtomicU32 = std::sync::atomic::AtomicU32::new(0);
+static REPORTS: std::sync::Mutex<Vec<i32>> = std::sync::Mutex::new(Vec::new());
+
+system! {
+    ReportSystem {
+        query! {
+            fn report(position: View<Position>) Changed=[Position]
+                ChangedSince = Tick::new(REPORTED.load(std::sync::atomic::Ordering::Relaxed))
+            {
+                REPORTS.lock().unwrap().push(position.x);
+            }
+        }
+    }
+}
+
+#[test]
+fn test_query_changed_since_spans_change_clears() {
+    let mut world = World::new();
+    world.get_storage::<Position>();
+    world.get_storage::<Score>();
+    world.build_scheduler();
+    let entities: Vec<Entity> = (0..300).map(|_| world.spawn()).collect();
+    for (i, &e) in entities.iter().enumerate() {
+        world.set(e, &Position { x: i as i32 });
+        world.set(e, &Score { points: 0 });
+    }
+    world.run();
+    let seen = world.current_tick() - TickDelta::new(1);
+
+    // Two ticks of changes, each cleared by its tick
+    world.set(entities[7], &Position { x: -7 });
+    world.set(entities[250], &Score { points: 1 });
+    world.run();
+    world.run();
+    world.set(entities[200], &Position { x: -200 });
+    world.run();
+    // Pending until the next tick clears it
+    world.set(entities[3], &Position { x: -3 });
+
+    let changed: Vec<i32> = world
+        .query::<&Position>()
+        .changed_since::<Position>(seen)
+        .map(|position| position.x)
+        .collect();
+    assert_eq!(changed, vec![-3, -7, -200]);
+
+    let scored: Vec<u32> = world
+        .query::<&Entity>()
+        .changed_since::<Score>(seen)
+        .map(|entity| entity.index())
+        .collect();
+    assert_eq!(scored, vec![250]);
+
+    // Nothing changed after the current tick, and the first change is recorded per entity
+    let now = world.current_tick();
+    assert_eq!(world.query::<&Position>().changed_since::<Position>(now).count(), 0);
+    assert_eq!(world.changed_tick::<Position>(entities[3]), Some(now));
+    assert_eq!(world.changed_tick::<Position>(entities[7]), Some(seen + TickDelta::new(1)));
+    assert_eq!(world.changed_tick::<Position>(entities[8]), Some(seen));
+}
+
+#[test]
+fn test_storages_created_after_build_are_cleaned_and_kept() {
+    let mut world = World::new();
+    world.build_scheduler();
+    let a = world.spawn();
+    let b = world.spawn();
+
+    // The storage joins the schedule with its cleanup system on the next tick
+    world.set(a, &Position { x: 1 });
+    world.run();
+    assert_eq!(world.query::<&Position>().changed::<Position>().count(), 0);
+    world.keep_changes::<Position>(true);
+    let start = world.current_tick();
+
+    // Every tick records its first write, so rollbacks restore it
+    world.set(a, &Position { x: 2 });
+    world.run();
+    world.set(a, &Position { x: 3 });
+    world.set(b, &Position { x: 4 });
+    world.run();
+    world.rollback(start);
+    assert_eq!(world.get::<Position>(a), Some(&Position { x: 2 }));
+    assert_eq!(world.get::<Position>(b), None);
+
+    // Kept changes span the ticks until cleared
+    world.set(b, &Position { x: 5 });
+    world.run();
+    world.run();
+    assert_eq!(world.kept_changes::<Position>(), vec![a, b]);
+    world.clear_kept_changes::<Position>();
+    assert!(world.kept_changes::<Position>().is_empty());
+    world.set(b, &Position { x: 6 });
+    assert_eq!(world.kept_changes::<Position>(), vec![b]);
+    world.keep_changes::<Position>(false);
+    world.run();
+    assert!(world.kept_changes::<Position>().is_empty());
+}
+
+#[derive(Clone, Default)]
+struct SeenVelocities(Vec<i32>);
+
+system! {
+    SeenVelocitySystem {
+        query! {
+            fn seen_velocity(velocity: View<Velocity>, seen: ResMut<SeenVelocities>)
+                Changed=[Velocity]
+            {
+                seen.0.push(velocity.x);
+            }
+        }
+    }
+}
+
+#[test]
+fn test_kept_changes_reach_changed_systems_until_reset() {
+    let mut world = World::new();
+    world.add_system::<SeenVelocitySystem>();
+    world.build_scheduler();
+    world.insert_resource(SeenVelocities::default());
+    world.keep_changes::<Velocity>(true);
+    let a = world.spawn();
+    world.set(a, &Velocity { x: 1 });
+    world.run();
+    world.run();
+    assert_eq!(world.resource::<SeenVelocities>().unwrap().0, vec![1, 1]);
+
+    // Clearing the kept marks leaves nothing for the system
+    world.clear_kept_changes::<Velocity>();
+    world.run();
+    assert_eq!(world.resource::<SeenVelocities>().unwrap().0, vec![1, 1]);
+
+    // A reset drops the kept marks but keeps the storage opted in
+    world.set(a, &Velocity { x: 2 });
+    world.run();
+    world.reset_to_tick_zero();
+    assert!(world.kept_changes::<Velocity>().is_empty());
+    world.resource_mut::<SeenVelocities>().unwrap().0.clear();
+    let b = world.spawn();
+    world.set(b, &Velocity { x: 3 });
+    world.run();
+    world.run();
+    assert_eq!(world.resource::<SeenVelocities>().unwrap().0, vec![3, 3]);
+    assert_eq!(world.kept_changes::<Velocity>(), vec![b]);
+}
+
+#[test]
+fn test_changed_since_clause_in_systems() {
+    let mut world = World::new();
+    world.add_system::<ReportSystem>();
+    world.build_scheduler();
+    let a = world.spawn();
+    let b = world.spawn();
+    world.set(a, &Position { x: 1 });
+    world.set(b, &Position { x: 2 });
+    let seen = world.current_tick();
+    REPORTED.store(seen.value(), std::sync::atomic::Ordering::Relaxed);
+    world.run();
+    assert!(REPORTS.lock().unwrap().is_empty());
+
+    // Reported again after its change was cleared, until the report moves on
+    world.set(b, &Position { x: 5 });
+    world.run();
+    world.run();
+    assert_eq!(*REPORTS.lock().unwrap(), vec![5, 5]);
+
+    REPORTED.store(world.current_tick().value() - 1, std::sync::atomic::Ordering::Relaxed);
+    world.run();
+    assert_eq!(*REPORTS.lock().unwrap(), vec![5, 5]);
+}
@@ -0,0 +1,75 @@
+//! Insertion-ordered maps keyed by `TypeId`.
+//!
+//! `HashMap` iterates in an order that changes from run to run, and `TypeId`'s `Ord` is
+//! not stable across builds either. The world iterates its per-type queues and tables
+//! while simulating (draining ingests, expiring components), so those maps iterate in
+//! registration order instead, which is the same in every run that registers the same
+//! types in the same order.
+
+use std::any::TypeId;
+
+/// A map from `TypeId` to `V` that iterates in insertion order. Lookups are linear, which
+/// is fine for the handful of entries a world registers.
+pub struct TypeRegistry<V> {
+    entries: Vec<(TypeId, V)>,
+}
+
+impl<V> TypeRegistry<V> {
+    pub fn new() -> Self {
+        TypeRegistry {
+            entries: Vec::new(),
+        }
+    }
+
+    pub fn get(&self, id: &TypeId) -> Option<&V> {
+        self.entries.iter().find(|(k, _)| k == id).map(|(_, v)| v)
+    }
+
+    pub fn contains_key(&self, id: &TypeId) -> bool {
+        self.get(id).is_some()
+    }
+
+    /// Returns the value for `id`, inserting `f()` at the end if it's missing.
+    pub fn get_or_insert_with(&mut self, id: TypeId, f: impl FnOnce() -> V) -> &V {
+        let index = match self.entries.iter().position(|(k, _)| *k == id) {
+            Some(index) => index,
+            None => {
+                self.entries.push((id, f()));
+                self.entries.len() - 1
+            }
+        };
+        &self.entries[index].1
+    }
+
+    /// Sets the value for `id`, keeping its position if it was already present.
+    pub fn insert(&mut self, id: TypeId, value: V) {
+        match self.entries.iter_mut().find(|(k, _)| *k == id) {
+            Some((_, v)) => *v = value,
+            None => self.entries.push((id, value)),
+        }
+    }
+
+    /// Entries in insertion order.
+    pub fn iter(&self) -> impl Iterator<Item = (&TypeId, &V)> {
+        self.entries.iter().map(|(k, v)| (k, v))
+    }
+
+    /// Values in insertion order.
+    pub fn values(&self) -> impl Iterator<Item = &V> {
+        self.entries.iter().map(|(_, v)| v)
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+impl<V> Default for TypeRegistry<V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
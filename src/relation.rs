@@ -0,0 +1,243 @@
+//! Relation components: components whose value links their entity to another one.
+//!
+//! Mark the `Entity` field holding the link with `#[component(target)]`. The component is
+//! then kept in a `Relation` storage, which answers the reverse question of which entities
+//! link to a given one through `World::relations_to`:
+//! ```ignore
+//! #[derive(Component, Clone, Default)]
+//! struct Targeting(#[component(target)] Entity);
+//!
+//! world.set(turret, &Targeting(ship));
+//! assert_eq!(world.relations_to::<Targeting>(ship), vec![turret]);
+//! ```
+//!
+//! Links are cleaned up on both sides: destroying the source drops the component like any
+//! other, and destroying the target removes the component from every entity linking to it,
+//! in the cleanup pass of the tick the target is destroyed in. Those removals are recorded
+//! like `World::remove`, so they match `WasRemoved=[...]` filters and rollback restores the
+//! links. The values live in a regular `Storage`, so every link change is in the rollback
+//! history; the reverse index is derived from the values and rebuilt on the first lookup
+//! after a change, so it is never out of step with a rollback.
+
+use crate::component::Component;
+use crate::entity::Entity;
+use crate::storage::{ComponentStorage, MemoryStats, Storage};
+use crate::tick::Tick;
+use std::cell::RefCell;
+use std::collections::HashMap;
+
+/// Storage for a relation component `T`, see the module docs.
+pub struct Relation<T: Component> {
+    values: Storage<T>,
+    /// Source indices per target, in ascending order. `None` after any change.
+    sources: RefCell<Option<HashMap<Entity, Vec<u32>>>>,
+}
+
+impl<T: Component> Relation<T> {
+    /// Indices of the entities whose `T` links to `target`, in ascending order.
+    pub fn sources_of(&self, target: Entity) -> Vec<u32> {
+        let mut sources = self.sources.borrow_mut();
+        let sources = sources.get_or_insert_with(|| {
+            let mut sources: HashMap<Entity, Vec<u32>> = HashMap::new();
+            self.values.visit(|index, value| {
+                if let Some(target) = value.relation_target() {
+                    sources.entry(target).or_default().push(index);
+                }
+            });
+            sources
+        });
+        sources.get(&target).cloned().unwrap_or_default()
+    }
+
+    fn invalidate(&mut self) {
+        *self.sources.get_mut() = None;
+    }
+}
+
+impl<T: Component> ComponentStorage for Relation<T> {
+    type Item = T;
+
+    fn new() -> Self {
+        Relation {
+            values: Storage::new(),
+            sources: RefCell::new(None),
+        }
+    }
+
+    fn set_tick(&mut self, tick: Tick) {
+        self.values.set_tick(tick);
+    }
+
+    fn tick(&self) -> Tick {
+        self.values.tick()
+    }
+
+    fn get(&self, index: u32) -> Option<&T> {
+        self.values.get(index)
+    }
+
+    fn get_mut(&mut self, index: u32) -> &mut T {
+        self.invalidate();
+        self.values.get_mut(index)
+    }
+
+    fn set(&mut self, index: u32, value: &T) {
+        self.invalidate();
+        self.values.set(index, value);
+    }
+
+    fn remove(&mut self, index: u32) {
+        self.invalidate();
+        self.values.remove(index);
+    }
+
+    fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    fn rollback(&mut self, target_tick: Tick) {
+        self.invalidate();
+        self.values.rollback(target_tick);
+    }
+
+    fn clear_changes(&mut self) {
+        self.values.clear_changes();
+    }
+
+    fn prune_history(&mut self, oldest: Tick) {
+        self.values.prune_history(oldest);
+    }
+
+    fn clear(&mut self) {
+        self.invalidate();
+        self.values.clear();
+    }
+
+    fn shrink(&mut self) -> usize {
+        self.values.shrink()
+    }
+
+    fn discard(&mut self, ri: u32, mi: u32, mask: u128) {
+        self.invalidate();
+        self.values.discard(ri, mi, mask);
+    }
+
+    fn visit<'a>(&'a self, f: impl FnMut(u32, &'a T)) {
+        self.values.visit(f);
+    }
+
+    fn mark_changed(&mut self, ri: u32, mi: u32, mask: u128) {
+        self.invalidate();
+        self.values.mark_changed(ri, mi, mask);
+    }
+
+    fn slot_ptr(&mut self, index: u32) -> *mut T {
+        self.invalidate();
+        self.values.slot_ptr(index)
+    }
+
+    fn root_mask(&self) -> u128 {
+        self.values.root_mask()
+    }
+
+    fn root_full_mask(&self) -> u128 {
+        self.values.root_full_mask()
+    }
+
+    fn root_changed_mask(&self) -> u128 {
+        self.values.root_changed_mask()
+    }
+
+    fn root_added_mask(&self) -> u128 {
+        self.values.root_added_mask()
+    }
+
+    fn root_removed_mask(&self) -> u128 {
+        self.values.root_removed_mask()
+    }
+
+    fn middle_mask(&self, ri: u32) -> u128 {
+        self.values.middle_mask(ri)
+    }
+
+    fn middle_full_mask(&self, ri: u32) -> u128 {
+        self.values.middle_full_mask(ri)
+    }
+
+    fn middle_changed_mask(&self, ri: u32) -> u128 {
+        self.values.middle_changed_mask(ri)
+    }
+
+    fn middle_added_mask(&self, ri: u32) -> u128 {
+        self.values.middle_added_mask(ri)
+    }
+
+    fn middle_removed_mask(&self, ri: u32) -> u128 {
+        self.values.middle_removed_mask(ri)
+    }
+
+    fn inner_mask(&self, ri: u32, mi: u32) -> u128 {
+        self.values.inner_mask(ri, mi)
+    }
+
+    fn inner_changed_mask(&self, ri: u32, mi: u32) -> u128 {
+        self.values.inner_changed_mask(ri, mi)
+    }
+
+    fn inner_added_mask(&self, ri: u32, mi: u32) -> u128 {
+        self.values.inner_added_mask(ri, mi)
+    }
+
+    fn inner_removed_mask(&self, ri: u32, mi: u32) -> u128 {
+        self.values.inner_removed_mask(ri, mi)
+    }
+
+    fn removed_value(&self, index: u32) -> Option<&T> {
+        self.values.removed_value(index)
+    }
+
+    fn root_changed_since(&self, since: Tick) -> u128 {
+        self.values.root_changed_since(since)
+    }
+
+    fn middle_changed_since(&self, ri: u32, since: Tick) -> u128 {
+        self.values.middle_changed_since(ri, since)
+    }
+
+    fn inner_changed_since(&self, ri: u32, mi: u32, since: Tick) -> u128 {
+        self.values.inner_changed_since(ri, mi, since)
+    }
+
+    fn changed_tick(&self, index: u32) -> Option<Tick> {
+        self.values.changed_tick(index)
+    }
+
+    fn set_keep_changes(&mut self, keep: bool) {
+        self.values.set_keep_changes(keep);
+    }
+
+    fn clear_kept_changes(&mut self) {
+        self.values.clear_kept_changes();
+    }
+
+    fn inner_pending_mask(&self, ri: u32, mi: u32) -> u128 {
+        self.values.inner_pending_mask(ri, mi)
+    }
+
+    fn warmup(&mut self, max_index: u32) {
+        self.values.warmup(max_index);
+    }
+
+    fn memory_stats(&self) -> MemoryStats {
+        let mut stats = self.values.memory_stats();
+        if let Some(sources) = self.sources.borrow().as_ref() {
+            stats.bytes += sources.capacity() * std::mem::size_of::<(Entity, Vec<u32>)>()
+                + sources.values().map(|s| s.capacity() * 4).sum::<usize>();
+        }
+        stats
+    }
+}
+
+#[cfg(test)]
+#[path = "relation.tests.rs"]
+mod tests;
@@ -0,0 +1,82 @@
+use super::*;
+use crate::prelude::*;
+
+#[derive(Component, Clone, Default, Debug, PartialEq)]
+struct Targeting(#[component(target)] Entity);
+
+#[derive(Component, Clone, Default, Debug, PartialEq)]
+struct Escort {
+    #[component(target)]
+    leader: Entity,
+    distance: u32,
+}
+
+fn world() -> World {
+    let mut world = World::new();
+    world.get_storage::<Targeting>();
+    world.get_storage::<Escort>();
+    world.build_scheduler();
+    world
+}
+
+#[test]
+fn test_relations_to_follows_link_changes() {
+    let mut world = world();
+    let ship = world.spawn();
+    let station = world.spawn();
+    let a = world.spawn();
+    let b = world.spawn();
+    let c = world.spawn();
+    world.set(c, &Targeting(ship));
+    world.set(a, &Targeting(ship));
+    world.set(b, &Targeting(station));
+    world.set(b, &Escort { leader: ship, distance: 3 });
+
+    assert_eq!(world.relations_to::<Targeting>(ship), vec![a, c]);
+    assert_eq!(world.relations_to::<Targeting>(station), vec![b]);
+    assert_eq!(world.relations_to::<Escort>(ship), vec![b]);
+
+    world.get_mut::<Targeting>(a).unwrap().0 = station;
+    world.remove::<Targeting>(c);
+    assert!(world.relations_to::<Targeting>(ship).is_empty());
+    assert_eq!(world.relations_to::<Targeting>(station), vec![a, b]);
+}
+
+#[test]
+fn test_destroying_either_side_drops_the_link() {
+    let mut world = world();
+    let ship = world.spawn();
+    let turret = world.spawn();
+    let drone = world.spawn();
+    world.set(turret, &Targeting(ship));
+    world.set(drone, &Targeting(turret));
+    world.run();
+
+    world.destroy(turret);
+    world.run();
+    assert!(world.contains(drone));
+    assert_eq!(world.get::<Targeting>(drone), None);
+    assert!(world.relations_to::<Targeting>(ship).is_empty());
+    assert_eq!(world.storage_ref::<Targeting>().unwrap().len(), 0);
+}
+
+#[test]
+fn test_link_cleanup_rolls_back() {
+    let mut world = world();
+    let ship = world.spawn();
+    let a = world.spawn();
+    let b = world.spawn();
+    world.set(a, &Targeting(ship));
+    world.set(b, &Targeting(ship));
+    world.run();
+    let linked = world.current_tick();
+    world.run();
+
+    world.destroy(ship);
+    world.run();
+    assert_eq!(world.get::<Targeting>(a), None);
+
+    world.rollback(linked);
+    assert_eq!(world.get::<Targeting>(a), Some(&Targeting(ship)));
+    assert_eq!(world.relations_to::<Targeting>(ship), vec![a, b]);
+}
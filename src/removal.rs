@@ -0,0 +1,166 @@
+//! `Removed<T>` events for components dropped by `Remove = [T]` queries.
+//!
+//! A `Remove = [T]` clause discards `T` from every matched entity after the query function
+//! ran. `WasRemoved = [T]` lets a system see those slots one tick later; systems that must
+//! react in the same tick, e.g. to refund an item or free an effect slot, take a
+//! `removed: RemovedEvents<T>` parameter instead and get every removal of the tick with
+//! the removed value.
+//!
+//! Ordering is part of the schema:
+//!
+//! - Every system with `Remove = [T]` declares a write of `RemovalQueue<T>` and every
+//!   reader a read, so the scheduler runs readers after all removers. Two removers of the
+//!   same `T` both write `T` and never share a wavefront, so events are in schedule order,
+//!   then ascending entity index, on every peer and in parallel or sequential runs.
+//! - A system with a `RemovedEvents` parameter defaults to `Parent = CleanupGroup`, which
+//!   runs after the whole `SimulationGroup`, so reactions see every removal the simulation
+//!   made. Removals made in `DestroyGroup` come after the cleanup and aren't delivered.
+//!
+//! Events only live for the tick: the world clears them at the end of every `run()` and
+//! on `rollback()`, so resimulation emits them again. Values are only cloned while some
+//! system reads the queue.
+//!
+//! # Example
+//! ```ignore
+//! system! {
+//!     ExpireShieldSystem {
+//!         query! {
+//!             fn expire(timer: View<ShieldTimer>) {}
+//!         }
+//!     }
+//!     All=[Shield]
+//!     Remove=[Shield]
+//! }
+//!
+//! system! {
+//!     RefundSystem {
+//!         query! {
+//!             fn refund(removed: RemovedEvents<Shield>) {
+//!                 for event in removed.iter() {
+//!                     println!("entity {} lost {:?}", event.index, event.value);
+//!                 }
+//!             }
+//!         }
+//!     }
+//! }
+//! ```
+
+use crate::storage::ComponentStorage;
+use std::any::Any;
+use std::cell::{Cell, UnsafeCell};
+use std::rc::Rc;
+
+/// A component value removed by a `Remove = [T]` query this tick.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Removed<T> {
+    /// Index of the entity the component was removed from.
+    pub index: u32,
+    pub value: T,
+}
+
+/// Removals of `T` made this tick, shared by the removers and readers of `T`.
+pub struct RemovalQueue<T> {
+    events: UnsafeCell<Vec<Removed<T>>>,
+    /// Set once a reader exists, so removers without readers don't clone anything.
+    listened: Cell<bool>,
+}
+
+impl<T> RemovalQueue<T> {
+    pub fn new() -> Self {
+        RemovalQueue {
+            events: UnsafeCell::new(Vec::new()),
+            listened: Cell::new(false),
+        }
+    }
+
+    /// Number of removals recorded this tick.
+    pub fn len(&self) -> usize {
+        unsafe { (*self.events.get()).len() }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl<T> Default for RemovalQueue<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Type-erased access to removal queues so the world can clear them at tick end.
+pub trait RemovalLike: Any {
+    fn clear(&self);
+    fn as_any_rc(self: Rc<Self>) -> Rc<dyn Any>;
+}
+
+impl<T: 'static> RemovalLike for RemovalQueue<T> {
+    fn clear(&self) {
+        unsafe { (*self.events.get()).clear() }
+    }
+
+    fn as_any_rc(self: Rc<Self>) -> Rc<dyn Any> {
+        self
+    }
+}
+
+/// Discards the components in `mask` of inner block `(ri, mi)`, as
+/// `ComponentStorage::discard`, recording them in `queue` first if anyone reads it. Called
+/// by the code `system!` generates for `Remove = [T]`.
+#[doc(hidden)]
+pub fn discard_into<S: ComponentStorage>(
+    storage: &mut S,
+    queue: &RemovalQueue<S::Item>,
+    ri: u32,
+    mi: u32,
+    mask: u128,
+) {
+    if queue.listened.get() {
+        // SAFETY: removers of `T` never run alongside each other or a reader
+        let events = unsafe { &mut *queue.events.get() };
+        let mut present = mask & storage.inner_mask(ri, mi);
+        while present != 0 {
+            let ii = present.trailing_zeros();
+            present &= !(1u128 << ii);
+
+            let index = ri * 16384 + mi * 128 + ii;
+            if let Some(value) = storage.get(index) {
+                events.push(Removed {
+                    index,
+                    value: value.clone(),
+                });
+            }
+        }
+    }
+    storage.discard(ri, mi, mask);
+}
+
+/// Reads the `Removed<T>` events of the tick, see the module docs.
+pub struct RemovedEvents<T> {
+    queue: Rc<RemovalQueue<T>>,
+}
+
+impl<T> RemovedEvents<T> {
+    pub fn new(queue: Rc<RemovalQueue<T>>) -> Self {
+        queue.listened.set(true);
+        RemovedEvents { queue }
+    }
+
+    /// Iterates over the removals in schedule order, then ascending entity index.
+    pub fn iter(&self) -> impl Iterator<Item = &Removed<T>> {
+        unsafe { (*self.queue.events.get()).iter() }
+    }
+
+    pub fn len(&self) -> usize {
+        self.queue.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.queue.is_empty()
+    }
+}
+
+#[cfg(test)]
+#[path = "removal.tests.rs"]
+mod tests;
@@ -0,0 +1,126 @@
+use super::*;
+use crate::prelude::*;
+use crate::scheduler::{CleanupGroup, PipelineStage};
+use std::any::TypeId;
+
+#[derive(Component, Default, Clone, Debug, PartialEq)]
+struct Shield {
+    hp: i32,
+}
+
+#[derive(Component, Default, Clone, Debug, PartialEq)]
+struct Broken;
+
+#[derive(Clone, Debug, Default, PartialEq)]
+struct Refunds(Vec<(u32, i32)>);
+
+system! {
+    BreakSystem {
+        query! {
+            fn shatter(_broken: View<Broken>) Remove=[Shield, Broken] {}
+        }
+    }
+}
+
+system! {
+    RefundSystem {
+        query! {
+            fn refund(removed: RemovedEvents<Shield>, refunds: ResMut<Refunds>) {
+                for event in removed.iter() {
+                    refunds.0.push((event.index, event.value.hp));
+                }
+            }
+        }
+    }
+}
+
+fn world(with_reader: bool) -> (World, Vec<Entity>) {
+    let mut world = World::new();
+    world.get_storage::<Shield>();
+    world.get_storage::<Broken>();
+    world.add_system::<BreakSystem>();
+    if with_reader {
+        world.add_system::<RefundSystem>();
+    }
+    world.build_scheduler();
+    world.insert_resource(Refunds::default());
+
+    let entities: Vec<Entity> = (0..4).map(|_| world.spawn()).collect();
+    for (i, &e) in entities.iter().enumerate() {
+        world.set(e, &Shield { hp: i as i32 * 10 });
+    }
+    (world, entities)
+}
+
+#[test]
+fn test_schema_orders_readers_after_removers() {
+    let mut world = world(true).0;
+    let queue = TypeId::of::<RemovalQueue<Shield>>();
+
+    let breaker = BreakSystem::create(&mut world);
+    let reader = RefundSystem::create(&mut world);
+    assert!(breaker.writes().contains(&queue));
+    assert!(
+        !breaker
+            .writes()
+            .contains(&TypeId::of::<RemovalQueue<Entity>>())
+    );
+    assert!(reader.reads().contains(&queue));
+    assert_eq!(reader.parent(), Some(TypeId::of::<CleanupGroup>()));
+}
+
+#[test]
+fn test_removals_delivered_in_the_same_tick() {
+    let (mut world, entities) = world(true);
+    world.set(entities[3], &Broken);
+    world.set(entities[1], &Broken);
+    world.set(entities[2], &Broken);
+    world.storage_mut::<Shield>().remove(entities[2].index());
+    world.run();
+
+    // Entity 2 had no shield left, the others in index order
+    let expected = vec![(entities[1].index(), 10), (entities[3].index(), 30)];
+    assert_eq!(
+        world.resource::<Refunds>(),
+        Some(&Refunds(expected.clone()))
+    );
+    assert_eq!(world.get::<Shield>(entities[1]), None);
+
+    // Events only live for the tick
+    assert!(world.removal_queue::<Shield>().is_empty());
+    world.run();
+    assert_eq!(world.resource::<Refunds>(), Some(&Refunds(expected)));
+}
+
+#[test]
+fn test_resimulation_emits_again() {
+    let (mut world, entities) = world(true);
+    world.run();
+    let tick = world.current_tick();
+    let hit = |world: &mut World| {
+        world.set(entities[0], &Shield { hp: 7 });
+        world.set(entities[0], &Broken);
+    };
+    hit(&mut world);
+    world.run();
+    let refunds = Refunds(vec![(entities[0].index(), 7)]);
+    assert_eq!(world.resource::<Refunds>(), Some(&refunds));
+
+    world.resimulate_from(tick);
+    assert_eq!(world.resource::<Refunds>(), Some(&Refunds::default()));
+    hit(&mut world);
+    world.run();
+    assert_eq!(world.resource::<Refunds>(), Some(&refunds));
+}
+
+#[test]
+fn test_nothing_recorded_without_readers() {
+    let (mut world, entities) = world(false);
+    let queue = world.removal_queue::<Shield>();
+    world.set(entities[0], &Broken);
+
+    let breaker = BreakSystem::create(&mut world);
+    breaker.run();
+    assert!(queue.is_empty());
+    assert_eq!(world.get::<Shield>(entities[0]), None);
+}
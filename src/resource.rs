@@ -0,0 +1,191 @@
+//! Per-world singletons such as gravity, map configuration or match rules.
+//!
+//! `World::insert_resource(Gravity { .. })` stores one value of a type for the whole
+//! world, outside the component storages. Systems read it with a `gravity: Res<Gravity>`
+//! parameter and write it with `wind: ResMut<Wind>`; the scheduler treats them like reads
+//! and writes of a component, so a `ResMut` never runs alongside another user of the same
+//! resource. A system with only resource (or mailbox) parameters runs once per tick.
+//!
+//! Resources are rolled back with the world: the first write in a tick logs the previous
+//! value with the tick, and `World::rollback` restores the values logged after the target
+//! tick. Inserting and removing a resource are logged the same way. Log entries older than
+//! the rollback window are dropped at the start of each tick.
+//!
+//! # Example
+//! ```ignore
+//! world.insert_resource(Gravity { y: -10 });
+//!
+//! system! {
+//!     FallSystem {
+//!         query! {
+//!             fn fall(velocity: &mut ViewMut<Velocity>, gravity: Res<Gravity>) {
+//!                 velocity.y += gravity.y;
+//!             }
+//!         }
+//!     }
+//! }
+//! ```
+
+use crate::tick::Tick;
+use std::any::Any;
+use std::cell::{Cell, UnsafeCell};
+use std::ops::{Deref, DerefMut};
+use std::rc::Rc;
+
+/// Rollback-aware slot holding the resource `T` of one world.
+pub struct ResourceCell<T> {
+    value: UnsafeCell<Option<T>>,
+    /// Undo log: `(tick, value before the tick's first write)`, oldest first.
+    history: UnsafeCell<Vec<(Tick, Option<T>)>>,
+    /// World tick of the run in progress, set by the world before the scheduler runs.
+    tick: Cell<Tick>,
+}
+
+impl<T: Clone> ResourceCell<T> {
+    pub fn new() -> Self {
+        ResourceCell {
+            value: UnsafeCell::new(None),
+            history: UnsafeCell::new(Vec::new()),
+            tick: Cell::new(Tick::new(0)),
+        }
+    }
+
+    /// The resource, if it was inserted.
+    pub fn get(&self) -> Option<&T> {
+        unsafe { (*self.value.get()).as_ref() }
+    }
+
+    /// The resource slot for writing at `tick`, logging its value first if this is the
+    /// first write of the tick.
+    #[allow(clippy::mut_from_ref)]
+    pub(crate) fn slot_mut(&self, tick: Tick) -> &mut Option<T> {
+        let value = unsafe { &mut *self.value.get() };
+        let history = unsafe { &mut *self.history.get() };
+        if history.last().is_none_or(|&(logged, _)| logged != tick) {
+            history.push((tick, value.clone()));
+        }
+        value
+    }
+
+    /// Read access for a `Res<T>` parameter.
+    ///
+    /// # Panics
+    /// Panics if the resource was never inserted.
+    pub fn res(&self) -> Res<'_, T> {
+        Res {
+            value: self.get().unwrap_or_else(|| missing::<T>()),
+        }
+    }
+
+    /// Write access for a `ResMut<T>` parameter, logged at the tick being simulated.
+    ///
+    /// # Panics
+    /// Panics if the resource was never inserted.
+    pub fn res_mut(&self) -> ResMut<'_, T> {
+        if self.get().is_none() {
+            missing::<T>();
+        }
+        ResMut { cell: self }
+    }
+
+    /// Undoes every write made after `target_tick`.
+    fn rollback(&self, target_tick: Tick) {
+        let value = unsafe { &mut *self.value.get() };
+        let history = unsafe { &mut *self.history.get() };
+
+        while history
+            .last()
+            .is_some_and(|(tick, _)| tick.is_after(target_tick))
+        {
+            let (_, previous) = history.pop().expect("history is not empty");
+            *value = previous;
+        }
+    }
+}
+
+impl<T: Clone> Default for ResourceCell<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn missing<T>() -> ! {
+    panic!(
+        "Resource {} was not inserted, call World::insert_resource first",
+        std::any::type_name::<T>()
+    )
+}
+
+/// Type-erased access to resource cells so the world can stamp and roll them back.
+pub trait ResourceLike: Any {
+    fn begin_tick(&self, tick: Tick, oldest: Tick);
+    fn rollback(&self, target_tick: Tick);
+    /// Drops the undo log, keeping the value.
+    fn forget_history(&self);
+    /// Name of the resource type, for diagnostics.
+    fn type_name(&self) -> &'static str;
+    fn as_any_rc(self: Rc<Self>) -> Rc<dyn Any>;
+}
+
+impl<T: Clone + 'static> ResourceLike for ResourceCell<T> {
+    fn begin_tick(&self, tick: Tick, oldest: Tick) {
+        self.tick.set(tick);
+        // Entries before the window can never be restored
+        unsafe { (*self.history.get()).retain(|(logged, _)| !logged.is_before(oldest)) };
+    }
+
+    fn rollback(&self, target_tick: Tick) {
+        ResourceCell::rollback(self, target_tick);
+    }
+
+    fn forget_history(&self) {
+        unsafe { (*self.history.get()).clear() };
+    }
+
+    fn type_name(&self) -> &'static str {
+        std::any::type_name::<T>()
+    }
+
+    fn as_any_rc(self: Rc<Self>) -> Rc<dyn Any> {
+        self
+    }
+}
+
+/// Shared access to a resource from a system.
+pub struct Res<'a, T> {
+    value: &'a T,
+}
+
+impl<'a, T> Deref for Res<'a, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        self.value
+    }
+}
+
+/// Exclusive access to a resource from a system. Writes are logged for rollback.
+pub struct ResMut<'a, T: Clone> {
+    cell: &'a ResourceCell<T>,
+}
+
+impl<'a, T: Clone> Deref for ResMut<'a, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        self.cell.get().expect("checked by res_mut")
+    }
+}
+
+impl<'a, T: Clone> DerefMut for ResMut<'a, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        self.cell
+            .slot_mut(self.cell.tick.get())
+            .as_mut()
+            .expect("checked by res_mut")
+    }
+}
+
+#[cfg(test)]
+#[path = "resource.tests.rs"]
+mod tests;
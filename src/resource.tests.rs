@@ -0,0 +1,92 @@
+use crate::prelude::*;
+use crate::resource::ResourceCell;
+use crate::scheduler::PipelineStage;
+use std::any::TypeId;
+
+#[derive(Component, Default, Clone, Debug, PartialEq)]
+struct Position {
+    x: i32,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct Wind {
+    x: i32,
+}
+
+system! {
+    GustSystem {
+        query! {
+            fn gust(wind: ResMut<Wind>) {
+                wind.x += 1;
+            }
+        }
+    }
+}
+
+system! {
+    DriftSystem {
+        query! {
+            fn drift(position: &mut ViewMut<Position>, wind: Res<Wind>) {
+                position.x += wind.x;
+            }
+        }
+    }
+}
+
+#[test]
+fn test_resource_insert_and_rollback() {
+    let mut world = World::new();
+    world.build_scheduler();
+    assert_eq!(world.resource::<Wind>(), None);
+
+    world.insert_resource(Wind { x: 1 });
+    world.run();
+    world.resource_mut::<Wind>().unwrap().x = 2;
+    world.resource_mut::<Wind>().unwrap().x = 3;
+    world.run();
+    assert_eq!(world.remove_resource::<Wind>(), Some(Wind { x: 3 }));
+    world.run();
+
+    world.rollback(Tick::new(1));
+    assert_eq!(world.resource::<Wind>(), Some(&Wind { x: 3 }));
+    world.rollback(Tick::new(0));
+    assert_eq!(world.resource::<Wind>(), Some(&Wind { x: 1 }));
+}
+
+#[test]
+fn test_res_params_are_ordered_and_rolled_back() {
+    let mut world = World::new();
+    world.get_storage::<Position>();
+    world.add_system::<DriftSystem>();
+    world.add_system::<GustSystem>();
+    world.build_scheduler();
+    world.insert_resource(Wind { x: 0 });
+
+    let stage = DriftSystem::create(&mut world);
+    let cell = TypeId::of::<ResourceCell<Wind>>();
+    assert!(stage.reads().contains(&cell));
+    assert!(GustSystem::create(&mut world).writes().contains(&cell));
+
+    let e = world.spawn();
+    world.set(e, &Position::default());
+    let start = world.current_tick();
+    world.run();
+    world.run();
+    world.run();
+
+    // The gust runs before the drift reads it, every tick
+    assert_eq!(world.resource::<Wind>(), Some(&Wind { x: 3 }));
+    assert_eq!(world.get::<Position>(e), Some(&Position { x: 6 }));
+
+    world.rollback(start);
+    assert_eq!(world.resource::<Wind>(), Some(&Wind { x: 1 }));
+    world.run();
+    assert_eq!(world.get::<Position>(e), Some(&Position { x: 3 }));
+}
+
+#[test]
+#[should_panic(expected = "was not inserted")]
+fn test_missing_resource_panics() {
+    let mut world = World::new();
+    GustSystem::create(&mut world).run();
+}
@@ -0,0 +1,218 @@
+//! Deterministic per-entity random streams.
+//!
+//! A shared RNG makes every roll depend on how many rolls came before it, i.e. on iteration
+//! order and on which systems happened to run in parallel. `EntityRng` is instead derived
+//! from `(world seed, system, entity index, tick)`, so an entity's rolls are the same no
+//! matter the order entities are visited in, whether the schedule runs in parallel or
+//! sequentially, and when a tick is resimulated after a rollback. Each system gets its own
+//! stream, so two systems rolling for the same entity in the same tick are independent.
+//!
+//! For one shared sequence of rolls, e.g. a card deck or loot table drawn from by a single
+//! system, insert a `RollbackRng` resource and take an `rng: Rng` parameter. Its state is a
+//! resource like any other, so `World::rollback` restores it and a resimulated tick draws
+//! the same values. A system with an `Rng` parameter writes the resource, so systems drawing
+//! from it never run in parallel and take turns in schedule order.
+//!
+//! # Example
+//! ```ignore
+//! system! {
+//!     CritSystem {
+//!         query! {
+//!             fn crit(attack: &mut ViewMut<Attack>, rng: EntityRng) {
+//!                 attack.crit = rng.chance(0.1);
+//!             }
+//!         }
+//!     }
+//! }
+//!
+//! world.insert_resource(RollbackRng::new(match_seed));
+//!
+//! system! {
+//!     DealSystem {
+//!         query! {
+//!             fn deal(deck: ResMut<Deck>, rng: Rng) {
+//!                 let card = rng.range(0..deck.len() as u32);
+//!                 deck.draw(card as usize);
+//!             }
+//!         }
+//!     }
+//! }
+//! ```
+
+use crate::resource::ResMut;
+use crate::tick::Tick;
+use std::cell::Cell;
+use std::ops::Range;
+use std::rc::Rc;
+
+const GOLDEN_GAMMA: u64 = 0x9e37_79b9_7f4a_7c15;
+
+/// SplitMix64 finalizer.
+const fn mix(mut z: u64) -> u64 {
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+/// Random stream for one entity in one system at one tick.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EntityRng {
+    state: u64,
+}
+
+impl EntityRng {
+    pub fn new(seed: u64, stream: u64, entity_index: u32, tick: Tick) -> Self {
+        let mut state = mix(seed);
+        state = mix(state ^ stream);
+        state = mix(state ^ entity_index as u64);
+        state = mix(state ^ tick.value() as u64);
+        EntityRng { state }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(GOLDEN_GAMMA);
+        mix(self.state)
+    }
+
+    pub fn next_u32(&mut self) -> u32 {
+        (self.next_u64() >> 32) as u32
+    }
+
+    /// Uniform float in `[0, 1)`.
+    pub fn next_f32(&mut self) -> f32 {
+        unit_f32(self.next_u64())
+    }
+
+    /// Uniform integer in `range`.
+    ///
+    /// # Panics
+    /// Panics if the range is empty.
+    pub fn range(&mut self, range: Range<u32>) -> u32 {
+        assert!(
+            range.start < range.end,
+            "EntityRng::range called with an empty range"
+        );
+        scale(self.next_u32(), range)
+    }
+
+    /// Returns true with probability `p`.
+    pub fn chance(&mut self, p: f32) -> bool {
+        self.next_f32() < p
+    }
+}
+
+/// Uniform float in `[0, 1)` from the high bits of `bits`.
+fn unit_f32(bits: u64) -> f32 {
+    (bits >> 40) as f32 / (1u64 << 24) as f32
+}
+
+/// Maps `bits` into the non-empty `range`.
+fn scale(bits: u32, range: Range<u32>) -> u32 {
+    let span = (range.end - range.start) as u64;
+    // Multiply-shift keeps the bias negligible for game-sized spans
+    range.start + ((bits as u64 * span) >> 32) as u32
+}
+
+/// A seeded xoshiro256** generator kept as a world resource, so rollback restores its
+/// state. See the module docs.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RollbackRng {
+    state: [u64; 4],
+}
+
+impl RollbackRng {
+    pub fn new(seed: u64) -> Self {
+        // SplitMix64 expands the seed, which never yields the all-zero state
+        let mut z = seed;
+        let state = std::array::from_fn(|_| {
+            z = z.wrapping_add(GOLDEN_GAMMA);
+            mix(z)
+        });
+        RollbackRng { state }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        let [s0, s1, s2, s3] = &mut self.state;
+        let result = s1.wrapping_mul(5).rotate_left(7).wrapping_mul(9);
+        let t = *s1 << 17;
+        *s2 ^= *s0;
+        *s3 ^= *s1;
+        *s1 ^= *s2;
+        *s0 ^= *s3;
+        *s2 ^= t;
+        *s3 = s3.rotate_left(45);
+        result
+    }
+
+    pub fn next_u32(&mut self) -> u32 {
+        (self.next_u64() >> 32) as u32
+    }
+
+    /// Uniform float in `[0, 1)`.
+    pub fn next_f32(&mut self) -> f32 {
+        unit_f32(self.next_u64())
+    }
+
+    /// Uniform integer in `range`.
+    ///
+    /// # Panics
+    /// Panics if the range is empty.
+    pub fn range(&mut self, range: Range<u32>) -> u32 {
+        assert!(
+            range.start < range.end,
+            "RollbackRng::range called with an empty range"
+        );
+        scale(self.next_u32(), range)
+    }
+
+    /// Returns true with probability `p`.
+    pub fn chance(&mut self, p: f32) -> bool {
+        self.next_f32() < p
+    }
+}
+
+/// The `rng: Rng` system parameter: write access to the `RollbackRng` resource, the same
+/// as `ResMut<RollbackRng>`.
+pub type Rng<'a> = ResMut<'a, RollbackRng>;
+
+/// World-wide seed and tick shared by all `RngSource`s.
+pub struct RngClock {
+    pub seed: Cell<u64>,
+    pub tick: Cell<Tick>,
+}
+
+impl RngClock {
+    pub fn new(seed: u64) -> Self {
+        RngClock {
+            seed: Cell::new(seed),
+            tick: Cell::new(Tick::new(0)),
+        }
+    }
+}
+
+/// Hands out `EntityRng`s for one system. Held by the system stage; created with
+/// `World::entity_rng`.
+pub struct RngSource {
+    clock: Rc<RngClock>,
+    stream: u64,
+}
+
+impl RngSource {
+    pub fn new(clock: Rc<RngClock>, stream: u64) -> Self {
+        RngSource { clock, stream }
+    }
+
+    /// The random stream of `entity_index` for the tick being simulated.
+    pub fn for_entity(&self, entity_index: u32) -> EntityRng {
+        EntityRng::new(
+            self.clock.seed.get(),
+            self.stream,
+            entity_index,
+            self.clock.tick.get(),
+        )
+    }
+}
+
+#[cfg(test)]
+#[path = "rng.tests.rs"]
+mod tests;
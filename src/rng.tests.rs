@@ -0,0 +1,213 @@
+use super::*;
+use crate::component::Component;
+use crate::prelude::system;
+use crate::world::World;
+
+#[derive(Component, Default, Clone, Debug, PartialEq)]
+struct Roll {
+    value: u32,
+}
+
+#[derive(Component, Default, Clone, Debug, PartialEq)]
+struct Other {
+    value: u32,
+}
+
+system! {
+    RollSystem {
+        query! {
+            fn roll(roll: &mut ViewMut<Roll>, rng: EntityRng) {
+                roll.value = rng.next_u32();
+            }
+        }
+    }
+}
+
+system! {
+    OtherRollSystem {
+        query! {
+            fn roll(other: &mut ViewMut<Other>, rng: EntityRng) {
+                other.value = rng.next_u32();
+            }
+        }
+    }
+}
+
+fn world(seed: u64, count: usize) -> (World, Vec<u32>) {
+    let mut world = World::new();
+    world.set_seed(seed);
+    world.add_system::<RollSystem>();
+    world.add_system::<OtherRollSystem>();
+
+    let indices = (0..count)
+        .map(|_| {
+            let e = world.spawn();
+            world.set(e, &Roll::default());
+            world.set(e, &Other::default());
+            e.index()
+        })
+        .collect();
+
+    world.build_scheduler();
+    (world, indices)
+}
+
+fn rolls(world: &mut World, indices: &[u32]) -> Vec<u32> {
+    let storage = world.get_storage::<Roll>();
+    indices
+        .iter()
+        .map(|&i| unsafe { (*storage.get()).get(i).unwrap().value })
+        .collect()
+}
+
+#[test]
+fn test_same_inputs_same_stream() {
+    let mut a = EntityRng::new(1, 2, 3, Tick::new(4));
+    let mut b = EntityRng::new(1, 2, 3, Tick::new(4));
+
+    for _ in 0..16 {
+        assert_eq!(a.next_u64(), b.next_u64());
+    }
+}
+
+#[test]
+fn test_inputs_select_independent_streams() {
+    let first =
+        |seed, stream, index, tick| EntityRng::new(seed, stream, index, Tick::new(tick)).next_u64();
+    let base = first(1, 2, 3, 4);
+
+    assert_ne!(base, first(9, 2, 3, 4));
+    assert_ne!(base, first(1, 9, 3, 4));
+    assert_ne!(base, first(1, 2, 9, 4));
+    assert_ne!(base, first(1, 2, 3, 9));
+}
+
+#[test]
+fn test_range_and_floats_stay_in_bounds() {
+    let mut rng = EntityRng::new(7, 0, 0, Tick::new(0));
+
+    for _ in 0..1000 {
+        let v = rng.range(10..13);
+        assert!((10..13).contains(&v));
+        let f = rng.next_f32();
+        assert!((0.0..1.0).contains(&f));
+    }
+
+    assert!(!rng.chance(0.0));
+    assert!(rng.chance(1.0));
+}
+
+#[test]
+#[should_panic(expected = "empty range")]
+fn test_empty_range_panics() {
+    EntityRng::new(0, 0, 0, Tick::new(0)).range(5..5);
+}
+
+#[test]
+fn test_parallel_and_sequential_agree() {
+    let (mut parallel, indices) = world(42, 300);
+    let (mut sequential, _) = world(42, 300);
+
+    for _ in 0..3 {
+        parallel.run();
+        sequential.run_sequential();
+        assert_eq!(
+            rolls(&mut parallel, &indices),
+            rolls(&mut sequential, &indices)
+        );
+    }
+
+    // Different entities and ticks get different rolls
+    let values = rolls(&mut parallel, &indices);
+    assert_ne!(values[0], values[1]);
+}
+
+#[test]
+fn test_systems_and_seeds_use_separate_streams() {
+    let (mut world, indices) = world(42, 1);
+    let (mut reseeded, _) = self::world(43, 1);
+    world.run();
+    reseeded.run();
+
+    let storage = world.get_storage::<Other>();
+    let other = unsafe { (*storage.get()).get(indices[0]).unwrap().value };
+    let roll = rolls(&mut world, &indices)[0];
+
+    assert_ne!(roll, other);
+    assert_ne!(roll, rolls(&mut reseeded, &indices)[0]);
+    assert_eq!(world.seed(), 42);
+}
+
+#[test]
+fn test_resimulation_reproduces_rolls() {
+    let (mut world, indices) = world(7, 8);
+
+    world.run(); // tick 0
+    world.run(); // tick 1
+    world.run(); // tick 2
+    let expected = rolls(&mut world, &indices);
+
+    world.rollback(Tick::new(1));
+    world.run(); // tick 1 (replay)
+    world.run(); // tick 2 (replay)
+
+    assert_eq!(rolls(&mut world, &indices), expected);
+}
+
+#[test]
+fn test_rollback_rng_matches_reference() {
+    // First outputs of xoshiro256** for the state SplitMix64 expands from seed 0
+    let mut rng = RollbackRng::new(0);
+    assert_eq!(
+        rng.state,
+        [
+            0xe220_a839_7b1d_cdaf,
+            0x6e78_9e6a_a1b9_65f4,
+            0x06c4_5d18_8009_454f,
+            0xf88b_b8a8_724c_81ec,
+        ]
+    );
+    assert_eq!(rng.next_u64(), 0x99ec_5f36_cb75_f2b4);
+
+    let mut other = RollbackRng::new(1);
+    assert_ne!(RollbackRng::new(0).next_u64(), other.next_u64());
+    for _ in 0..1000 {
+        assert!((10..13).contains(&other.range(10..13)));
+        assert!((0.0..1.0).contains(&other.next_f32()));
+    }
+}
+
+#[derive(Clone, Default, Debug, PartialEq)]
+struct Draws(Vec<u32>);
+
+system! {
+    DrawSystem {
+        query! {
+            fn draw(draws: ResMut<Draws>, rng: Rng) {
+                draws.0.push(rng.range(0..1000));
+            }
+        }
+    }
+}
+
+#[test]
+fn test_rollback_rng_resimulates_same_draws() {
+    let mut world = World::new();
+    world.add_system::<DrawSystem>();
+    world.build_scheduler();
+    world.insert_resource(Draws::default());
+    world.insert_resource(RollbackRng::new(99));
+
+    world.run();
+    let t = world.current_tick();
+    world.run();
+    world.run();
+    let expected = world.resource::<Draws>().unwrap().clone();
+    assert_eq!(expected.0.len(), 3);
+    assert_ne!(expected.0[1], expected.0[2]);
+
+    world.rollback(t);
+    assert_eq!(world.resource::<Draws>().unwrap().0, expected.0[..2]);
+    world.run();
+    assert_eq!(world.resource::<Draws>(), Some(&expected));
+}
@@ -1,16 +1,95 @@
-use crate::component::Component;
-use crate::storage::Storage;
+use crate::entity::Entity;
+use crate::savestate::{SavedComponents, SavedStorage};
+use crate::storage::ComponentStorage;
 use crate::tick::Tick;
-use std::any::Any;
+use crate::component::{Component, RollbackPolicy};
+#[cfg(feature = "serde")]
+use crate::export::{Value, ValueError};
+use crate::wire::{DecodeError, Packet, PacketWriter};
+use std::any::{Any, TypeId};
 use std::cell::UnsafeCell;
+use std::hash::Hash;
 use std::rc::Rc;
 
+#[cfg(feature = "serde")]
+pub use crate::export::{ComponentValues, Snapshot};
+pub use rollback_core::rollback::{
+    DeltaCodec, DeltaCompressible, RollbackWindow, apply_delta, encode_delta,
+};
+
+/// Describes a rollback request that reached past the retained history.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct RollbackOverflow {
+    /// The tick that was requested.
+    pub requested: Tick,
+    /// The history that was actually available.
+    pub window: RollbackWindow,
+}
+
+/// Entities whose existence changed in a rollback, returned by `World::rollback`.
+///
+/// Entities spawned after the target tick didn't exist at that tick, so the rollback
+/// removes them along with all of their components. Their indices are free again, and
+/// since `spawn` always takes the lowest free index, a resimulation that spawns the same
+/// entities in the same order gets the same indices back.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct RollbackReport {
+    /// The tick the world was restored to. Differs from the requested tick when the
+    /// overflow handler clamped the rollback.
+    pub target: Tick,
+    /// Whether the world was rolled back at all. False when the overflow handler returned
+    /// `OverflowAction::Ignore`, or when the requested tick is in the future.
+    pub applied: bool,
+    /// Entities alive before the rollback that don't exist at the target, by index.
+    pub despawned: Vec<Entity>,
+    /// Entities that exist at the target but weren't alive before the rollback, by index.
+    pub restored: Vec<Entity>,
+}
+
+/// What the world should do after a rollback overflow handler ran.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum OverflowAction {
+    /// Roll back as far as possible (to `window.oldest`). Like any rollback, this
+    /// clears mailboxes, removals and pending effects and rewinds the journal.
+    Clamp,
+    /// Leave the world untouched. Use this when the game will disconnect, pause,
+    /// or request a full state resync instead.
+    Ignore,
+}
+
+/// How much rollback history the world keeps, see `World::set_rollback_config`.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct RollbackConfig {
+    /// Ticks `rollback()` may reach back. History older than that is pruned and stays
+    /// unreachable even if the length grows again. `None` keeps every tick not confirmed
+    /// with `World::confirm`.
+    pub history_len: Option<u32>,
+    /// Prune every this many ticks. Larger intervals walk the history less often but hold
+    /// up to that many extra ticks of it.
+    pub prune_interval: u32,
+}
+
+impl Default for RollbackConfig {
+    fn default() -> Self {
+        RollbackConfig {
+            history_len: None,
+            prune_interval: 1,
+        }
+    }
+}
+
+/// Callback invoked by `World::rollback` when the target tick is older than the retained window.
+pub type RollbackOverflowHandler = Box<dyn FnMut(&RollbackOverflow) -> OverflowAction>;
+
 pub trait Rollback {
     fn rollback(&self, target_tick: Tick);
 }
 
 pub trait SetTick {
     fn set_tick(&self, tick: Tick);
+
+    /// The tick last set, see `ComponentStorage::tick`.
+    fn tick(&self) -> Tick;
 }
 
 /// A trait that combines Any, Rollback, and SetTick for storage types.
@@ -18,26 +97,417 @@ pub trait SetTick {
 pub trait StorageLike: Any + Rollback + SetTick {
     /// Downcast to Any for type erasure
     fn as_any(&self) -> &dyn Any;
+
+    /// Name of the component type held by this storage.
+    fn type_name(&self) -> &'static str;
+
+    /// `TypeId` of the component type held by this storage.
+    fn component_type_id(&self) -> TypeId;
+
+    /// `Component::stable_name` of the component type held by this storage.
+    fn stable_name(&self) -> &'static str;
+
+    /// Indices of all entities that currently have this component, in ascending order.
+    fn indices(&self) -> Vec<u32>;
+
+    /// See `ComponentStorage::is_empty`.
+    fn is_empty(&self) -> bool;
+
+    /// Sets a copy of the component at `index` on `entity` in another world, if present.
+    fn copy_to(&self, index: u32, dest: &mut crate::world::World, entity: crate::entity::Entity);
+
+    /// The component at `index`, if present, for `World::get_dyn`.
+    fn get_any(&self, index: u32) -> Option<&dyn Any>;
+
+    /// Drops the component at `index`, if present, without recording it for rollback.
+    fn discard_index(&self, index: u32);
+
+    /// Clears the change masks, as the storage's cleanup system does at the end of a tick.
+    fn clear_changes(&self);
+
+    /// See `ComponentStorage::set_keep_changes`.
+    fn set_keep_changes(&self, keep: bool);
+
+    /// See `ComponentStorage::clear_kept_changes`.
+    fn clear_kept_changes(&self);
+
+    /// See `ComponentStorage::prune_history`.
+    fn prune_history(&self, oldest: Tick);
+
+    /// See `ComponentStorage::clear`.
+    fn clear(&self);
+
+    /// See `ComponentStorage::shrink`.
+    fn shrink(&self) -> usize;
+
+    /// Presence masks, see `ComponentStorage::root_mask`.
+    fn root_mask(&self) -> u128;
+    fn middle_mask(&self, ri: u32) -> u128;
+    fn inner_mask(&self, ri: u32, mi: u32) -> u128;
+
+    /// Full masks, see `ComponentStorage::root_full_mask`.
+    fn root_full_mask(&self) -> u128;
+    fn middle_full_mask(&self, ri: u32) -> u128;
+
+    /// Change masks, see `ComponentStorage::root_changed_mask`.
+    fn root_changed_mask(&self) -> u128;
+    fn middle_changed_mask(&self, ri: u32) -> u128;
+    fn inner_changed_mask(&self, ri: u32, mi: u32) -> u128;
+
+    /// Masks of changes after a tick, see `ComponentStorage::root_changed_since`.
+    fn root_changed_since(&self, since: Tick) -> u128;
+    fn middle_changed_since(&self, ri: u32, since: Tick) -> u128;
+    fn inner_changed_since(&self, ri: u32, mi: u32, since: Tick) -> u128;
+
+    /// Indices whose component was removed in the tick before the current one, in
+    /// ascending order. See `ComponentStorage::inner_removed_mask`.
+    fn removed_indices(&self) -> Vec<u32>;
+
+    /// See `ComponentStorage::warmup`.
+    fn warmup(&self, max_index: u32);
+
+    fn memory_stats(&self) -> crate::storage::MemoryStats;
+
+    /// Sub-hash of the storage for `World::state_hash`: every occupied index, followed by
+    /// the value if the component type is `Hashable`.
+    fn state_hash(&self) -> u128;
+
+    /// Hashes inner block `(ri, mi)` like `state_hash` does the whole storage, for the
+    /// block hashes of `World::hash_tree`.
+    fn hash_block(&self, ri: u32, mi: u32, state: &mut crate::statehash::StateHasher);
+
+    /// Clones every component for a save slot, see the `savestate` module.
+    fn save_state(&self) -> Box<dyn SavedStorage>;
+
+    /// Replaces every component with the ones in `state`, or removes them all if there is
+    /// none. The writes are recorded for rollback like any other edit.
+    fn load_state(&self, state: Option<&dyn SavedStorage>);
+
+    /// Writes the storage as a section of `World::save_snapshot`'s packet, with the codec
+    /// registered on `world` or `Component::wire_codec`. Writes nothing and returns false if
+    /// the component has neither.
+    fn write_snapshot(&self, world: &crate::world::World, writer: &mut PacketWriter) -> bool;
+
+    /// Decodes the storage's section of a `World::save_snapshot` packet for `load_state`,
+    /// a missing section meaning no components. `None` if the component isn't serialized.
+    fn decode_snapshot(
+        &self,
+        world: &crate::world::World,
+        packet: &Packet<'_>,
+    ) -> Option<Result<Box<dyn SavedStorage>, DecodeError>>;
+
+    /// Every component as a `Value`, by ascending index, for `World::export_snapshot`.
+    /// `None` if the component type isn't serde-enabled.
+    #[cfg(feature = "serde")]
+    fn export_values(&self) -> Option<Result<Vec<(u32, Value)>, ValueError>>;
+
+    /// Converts values from `export_values` back for `load_state` or `apply_values`.
+    #[cfg(feature = "serde")]
+    fn decode_values(
+        &self,
+        values: &[(u32, Value)],
+    ) -> Option<Result<Box<dyn SavedStorage>, ValueError>>;
+
+    /// Sets the components in `set` and removes the ones at `removed`, leaving the rest,
+    /// for `World::import_snapshot` of a delta. Recorded for rollback like `load_state`.
+    #[cfg(feature = "serde")]
+    fn apply_values(&self, set: &dyn SavedStorage, removed: &[u32]);
 }
 
-impl<T: Component + Clone> Rollback for Rc<UnsafeCell<Storage<T>>> {
+impl<S: ComponentStorage> Rollback for Rc<UnsafeCell<S>> {
     fn rollback(&self, target_tick: Tick) {
         unsafe {
+            if S::Item::ROLLBACK == RollbackPolicy::Reset {
+                (*self.get()).clear();
+                (*self.get()).set_tick(target_tick);
+                return;
+            }
             (*self.get()).rollback(target_tick);
         }
     }
 }
 
-impl<T: Component> SetTick for Rc<UnsafeCell<Storage<T>>> {
+impl<S: ComponentStorage> SetTick for Rc<UnsafeCell<S>> {
     fn set_tick(&self, tick: Tick) {
         unsafe {
             (*self.get()).set_tick(tick);
         }
     }
+
+    fn tick(&self) -> Tick {
+        unsafe { (*self.get()).tick() }
+    }
 }
 
-impl<T: Component> StorageLike for Rc<UnsafeCell<Storage<T>>> {
+/// Decodes the section of `T` in a whole-world snapshot with the codec registered on
+/// `world` or `Component::wire_codec`, see `StorageLike::decode_snapshot`. Needs no
+/// storage, so snapshots can be checked before any is created for them.
+pub(crate) fn decode_snapshot_section<T: Component>(
+    world: &crate::world::World,
+    packet: &Packet<'_>,
+) -> Option<Result<Box<dyn SavedStorage>, DecodeError>> {
+    let codec = world.snapshot_codec::<T>().or(T::wire_codec())?;
+    let values = match packet.section::<T>() {
+        Some(section) => section.decode_snapshot_with(codec),
+        None => Ok(Vec::new()),
+    };
+    Some(values.map(|values| Box::new(SavedComponents { values }) as Box<dyn SavedStorage>))
+}
+
+impl<S: ComponentStorage> StorageLike for Rc<UnsafeCell<S>> {
     fn as_any(&self) -> &dyn Any {
         self as &dyn Any
     }
+
+    fn type_name(&self) -> &'static str {
+        std::any::type_name::<S::Item>()
+    }
+
+    fn stable_name(&self) -> &'static str {
+        S::Item::stable_name()
+    }
+
+    fn is_empty(&self) -> bool {
+        unsafe { (*self.get()).is_empty() }
+    }
+
+    fn component_type_id(&self) -> TypeId {
+        TypeId::of::<S::Item>()
+    }
+
+    fn indices(&self) -> Vec<u32> {
+        let mut indices = Vec::new();
+        unsafe { (*self.get()).visit(|index, _| indices.push(index)) };
+        indices
+    }
+
+    fn copy_to(&self, index: u32, dest: &mut crate::world::World, entity: crate::entity::Entity) {
+        if let Some(value) = unsafe { (*self.get()).get(index) } {
+            dest.set(entity, &value.clone());
+        }
+    }
+
+    fn get_any(&self, index: u32) -> Option<&dyn Any> {
+        unsafe { (*self.get()).get(index) }.map(|value| value as &dyn Any)
+    }
+
+    fn discard_index(&self, index: u32) {
+        unsafe { (*self.get()).discard(index >> 14, (index >> 7) & 0x7F, 1u128 << (index & 0x7F)) }
+    }
+
+    fn clear_changes(&self) {
+        unsafe { (*self.get()).clear_changes() }
+    }
+
+    fn set_keep_changes(&self, keep: bool) {
+        unsafe { (*self.get()).set_keep_changes(keep) }
+    }
+
+    fn clear_kept_changes(&self) {
+        unsafe { (*self.get()).clear_kept_changes() }
+    }
+
+    fn prune_history(&self, oldest: Tick) {
+        unsafe { (*self.get()).prune_history(oldest) }
+    }
+
+    fn clear(&self) {
+        unsafe { (*self.get()).clear() }
+    }
+
+    fn shrink(&self) -> usize {
+        unsafe { (*self.get()).shrink() }
+    }
+
+    fn root_mask(&self) -> u128 {
+        unsafe { (*self.get()).root_mask() }
+    }
+
+    fn middle_mask(&self, ri: u32) -> u128 {
+        unsafe { (*self.get()).middle_mask(ri) }
+    }
+
+    fn inner_mask(&self, ri: u32, mi: u32) -> u128 {
+        unsafe { (*self.get()).inner_mask(ri, mi) }
+    }
+
+    fn root_full_mask(&self) -> u128 {
+        unsafe { (*self.get()).root_full_mask() }
+    }
+
+    fn middle_full_mask(&self, ri: u32) -> u128 {
+        unsafe { (*self.get()).middle_full_mask(ri) }
+    }
+
+    fn root_changed_mask(&self) -> u128 {
+        unsafe { (*self.get()).root_changed_mask() }
+    }
+
+    fn middle_changed_mask(&self, ri: u32) -> u128 {
+        unsafe { (*self.get()).middle_changed_mask(ri) }
+    }
+
+    fn inner_changed_mask(&self, ri: u32, mi: u32) -> u128 {
+        unsafe { (*self.get()).inner_changed_mask(ri, mi) }
+    }
+
+    fn root_changed_since(&self, since: Tick) -> u128 {
+        unsafe { (*self.get()).root_changed_since(since) }
+    }
+
+    fn middle_changed_since(&self, ri: u32, since: Tick) -> u128 {
+        unsafe { (*self.get()).middle_changed_since(ri, since) }
+    }
+
+    fn inner_changed_since(&self, ri: u32, mi: u32, since: Tick) -> u128 {
+        unsafe { (*self.get()).inner_changed_since(ri, mi, since) }
+    }
+
+    fn removed_indices(&self) -> Vec<u32> {
+        let storage = unsafe { &*self.get() };
+        let mut indices = Vec::new();
+        let mut root = storage.root_removed_mask();
+        while root != 0 {
+            let ri = root.trailing_zeros();
+            root &= !(1u128 << ri);
+
+            let mut middle = storage.middle_removed_mask(ri);
+            while middle != 0 {
+                let mi = middle.trailing_zeros();
+                middle &= !(1u128 << mi);
+
+                let mut inner = storage.inner_removed_mask(ri, mi);
+                while inner != 0 {
+                    let ii = inner.trailing_zeros();
+                    inner &= !(1u128 << ii);
+                    indices.push(ri * 16384 + mi * 128 + ii);
+                }
+            }
+        }
+        indices
+    }
+
+    fn warmup(&self, max_index: u32) {
+        unsafe { (*self.get()).warmup(max_index) }
+    }
+
+    fn memory_stats(&self) -> crate::storage::MemoryStats {
+        unsafe { (*self.get()).memory_stats() }
+    }
+
+    fn state_hash(&self) -> u128 {
+        let hash_value = S::Item::state_hasher();
+        let mut state = crate::statehash::StateHasher::new();
+        unsafe {
+            (*self.get()).visit(|index, value| {
+                index.hash(&mut state);
+                if let Some(hash_value) = hash_value {
+                    hash_value(value, &mut state);
+                }
+            })
+        };
+        state.finish128()
+    }
+
+    fn hash_block(&self, ri: u32, mi: u32, state: &mut crate::statehash::StateHasher) {
+        let hash_value = S::Item::state_hasher();
+        let storage = unsafe { &*self.get() };
+        let mut mask = storage.inner_mask(ri, mi);
+        while mask != 0 {
+            let ii = mask.trailing_zeros();
+            mask &= !(1u128 << ii);
+
+            let index = ri * 16384 + mi * 128 + ii;
+            index.hash(state);
+            if let (Some(hash_value), Some(value)) = (hash_value, storage.get(index)) {
+                hash_value(value, state);
+            }
+        }
+    }
+
+    fn save_state(&self) -> Box<dyn SavedStorage> {
+        let mut values = Vec::new();
+        unsafe { (*self.get()).visit(|index, value| values.push((index, value.clone()))) };
+        Box::new(SavedComponents { values })
+    }
+
+    fn load_state(&self, state: Option<&dyn SavedStorage>) {
+        let saved = state
+            .and_then(|state| state.as_any().downcast_ref::<SavedComponents<S::Item>>())
+            .map_or(&[][..], |state| &state.values);
+
+        let indices = self.indices();
+        let storage = unsafe { &mut *self.get() };
+        for index in indices {
+            if saved.binary_search_by_key(&index, |(i, _)| *i).is_err() {
+                storage.remove(index);
+            }
+        }
+        for (index, value) in saved {
+            storage.set(*index, value);
+        }
+    }
+
+    fn write_snapshot(&self, world: &crate::world::World, writer: &mut PacketWriter) -> bool {
+        let Some(codec) = world
+            .snapshot_codec::<S::Item>()
+            .or(S::Item::wire_codec())
+        else {
+            return false;
+        };
+        writer.write_snapshot_of(unsafe { &*self.get() }, codec);
+        true
+    }
+
+    fn decode_snapshot(
+        &self,
+        world: &crate::world::World,
+        packet: &Packet<'_>,
+    ) -> Option<Result<Box<dyn SavedStorage>, DecodeError>> {
+        decode_snapshot_section::<S::Item>(world, packet)
+    }
+
+    #[cfg(feature = "serde")]
+    fn export_values(&self) -> Option<Result<Vec<(u32, Value)>, ValueError>> {
+        let fns = S::Item::serde_fns()?;
+        let mut values = Vec::new();
+        let mut error = None;
+        unsafe {
+            (*self.get()).visit(|index, value| {
+                if error.is_none() {
+                    match (fns.to_value)(value) {
+                        Ok(value) => values.push((index, value)),
+                        Err(e) => error = Some(e),
+                    }
+                }
+            })
+        };
+        Some(error.map_or(Ok(values), Err))
+    }
+
+    #[cfg(feature = "serde")]
+    fn decode_values(
+        &self,
+        values: &[(u32, Value)],
+    ) -> Option<Result<Box<dyn SavedStorage>, ValueError>> {
+        let fns = S::Item::serde_fns()?;
+        let values = values
+            .iter()
+            .map(|(index, value)| (fns.from_value)(value.clone()).map(|value| (*index, value)))
+            .collect::<Result<Vec<_>, _>>();
+        Some(values.map(|values| Box::new(SavedComponents { values }) as Box<dyn SavedStorage>))
+    }
+
+    #[cfg(feature = "serde")]
+    fn apply_values(&self, set: &dyn SavedStorage, removed: &[u32]) {
+        let storage = unsafe { &mut *self.get() };
+        for &index in removed {
+            storage.remove(index);
+        }
+        if let Some(set) = set.as_any().downcast_ref::<SavedComponents<S::Item>>() {
+            for (index, value) in &set.values {
+                storage.set(*index, value);
+            }
+        }
+    }
 }
@@ -0,0 +1,108 @@
+//! Named, user-managed full-state checkpoints ("savestates").
+//!
+//! The rollback history only reaches back through the rolling window, and rolling back
+//! discards everything after the target. Training modes and TAS tools instead want to keep
+//! a few states around indefinitely and jump between them. `World::save_slot(name)` clones
+//! every component of every storage into a slot kept outside the rollback history, and
+//! `World::load_slot(name)` replaces the world's components with the slot's.
+//!
+//! Loading is an edit like `World::apply_snapshot`: it happens at the current tick, is
+//! change-tracked and can itself be rolled back. The tick counter keeps advancing; the slot
+//! only records the tick it was saved at. Entities alive at load time that the slot doesn't
+//! have are retired like destroyed entities, so their handles stay stale.
+//!
+//! Slots hold component storages only. Pending effects, mailboxes, ingest queues and
+//! component TTLs are not part of them.
+//!
+//! # Example
+//! ```ignore
+//! world.save_slot("before-boss");
+//! // ...
+//! if let Some(saved_at) = world.load_slot("before-boss") {
+//!     println!("restored state of tick {}", saved_at.value());
+//! }
+//! for slot in world.slots() {
+//!     println!("{}: {} components, {} bytes", slot.name, slot.components, slot.bytes);
+//! }
+//! ```
+
+use crate::tick::Tick;
+use std::any::Any;
+use std::collections::HashMap;
+
+/// The components of one storage in a save slot, type-erased.
+pub trait SavedStorage: Any {
+    fn as_any(&self) -> &dyn Any;
+
+    /// Number of components saved.
+    fn len(&self) -> usize;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Approximate bytes held, counting each value's inline size only.
+    fn bytes(&self) -> usize;
+}
+
+/// Every component of type `T`, by ascending entity index.
+pub struct SavedComponents<T> {
+    pub values: Vec<(u32, T)>,
+}
+
+impl<T: 'static> SavedStorage for SavedComponents<T> {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    fn bytes(&self) -> usize {
+        std::mem::size_of::<Self>() + self.values.capacity() * std::mem::size_of::<(u32, T)>()
+    }
+}
+
+/// A full copy of the world's component storages.
+pub(crate) struct SaveSlot {
+    pub(crate) tick: Tick,
+    /// Keyed by component type index.
+    pub(crate) storages: HashMap<usize, Box<dyn SavedStorage>>,
+}
+
+impl SaveSlot {
+    /// The saved components of `T`, empty if the world had no storage for it.
+    pub(crate) fn components<T: crate::component::Component>(&self) -> &[(u32, T)] {
+        self.storages
+            .get(&T::type_index())
+            .and_then(|saved| saved.as_any().downcast_ref::<SavedComponents<T>>())
+            .map_or(&[], |saved| &saved.values)
+    }
+
+    pub(crate) fn info(&self, name: &str) -> SlotInfo {
+        SlotInfo {
+            name: name.to_string(),
+            tick: self.tick,
+            components: self.storages.values().map(|s| s.len()).sum(),
+            bytes: std::mem::size_of::<Self>()
+                + self.storages.values().map(|s| s.bytes()).sum::<usize>(),
+        }
+    }
+}
+
+/// Summary of a save slot, see `World::slots`.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct SlotInfo {
+    pub name: String,
+    /// Tick the world was at when the slot was saved.
+    pub tick: Tick,
+    /// Components saved, over all storages.
+    pub components: usize,
+    /// Approximate memory held by the slot.
+    pub bytes: usize,
+}
+
+#[cfg(test)]
+#[path = "savestate.tests.rs"]
+mod tests;
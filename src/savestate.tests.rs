@@ -0,0 +1,48 @@
+use crate::prelude::*;
+
+#[derive(Component, Default, Clone, Debug, PartialEq)]
+struct Health {
+    value: i32,
+}
+
+#[test]
+fn test_load_slot_restores_saved_world() {
+    let mut world = World::new();
+    let kept = world.spawn();
+    let destroyed = world.spawn();
+    world.set(kept, &Health { value: 10 });
+    world.set(destroyed, &Health { value: 20 });
+    world.build_scheduler();
+    world.run();
+    world.save_slot("checkpoint");
+
+    world.set(kept, &Health { value: 99 });
+    world.destroy(destroyed);
+    world.run();
+    let spawned = world.spawn();
+    world.set(spawned, &Health { value: 30 });
+    world.run();
+
+    assert_eq!(world.load_slot("checkpoint"), Some(Tick::new(1)));
+    assert_eq!(world.load_slot("missing"), None);
+
+    assert_eq!(world.get::<Health>(kept), Some(&Health { value: 10 }));
+    assert_eq!(world.get::<Health>(destroyed), Some(&Health { value: 20 }));
+    assert_eq!(world.get::<Health>(spawned), None);
+    assert_eq!(world.current_tick(), Tick::new(3));
+
+    // Loading is an ordinary edit, so the tick after it can be rolled back to
+    world.run();
+    world.rollback(Tick::new(3));
+    assert_eq!(world.get::<Health>(kept), Some(&Health { value: 10 }));
+
+    let slots = world.slots();
+    assert_eq!(slots.len(), 1);
+    assert_eq!(slots[0].name, "checkpoint");
+    assert_eq!(slots[0].tick, Tick::new(1));
+    assert_eq!(slots[0].components, 4);
+    assert!(world.slot_memory() >= 2 * std::mem::size_of::<(u32, Health)>());
+
+    assert!(world.delete_slot("checkpoint"));
+    assert!(world.slots().is_empty());
+}
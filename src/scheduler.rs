@@ -1,8 +1,14 @@
+use crate::component::{ComponentRegistration, Destroyed};
+use crate::entity::Entity;
+use crate::graph::{short_type_name, ScheduleDescription, ScheduleEdge, StageDescription};
 use crate::world::World;
 #[cfg(feature = "parallel")]
 use rayon::ThreadPool;
 use std::any::TypeId;
-use std::collections::{HashMap, HashSet};
+use crate::audit::SystemAudit;
+use crate::profile::SystemProfile;
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::time::Duration;
 
 /// Default pipeline groups for organizing systems
 #[rollback_macros::pipeline_group]
@@ -68,6 +74,194 @@ pub trait PipelineGroup: Sized + 'static {
     }
 }
 
+/// A pipeline group compiled into the program. `#[pipeline_group]` submits one for every
+/// group, so the scheduler can apply a group's run condition, set with `RunIf`, `InState`,
+/// `OnEnter` or `OnExit`, to every system nested in it.
+pub struct GroupRegistration {
+    pub group: fn() -> TypeId,
+    pub name: fn() -> &'static str,
+    pub parent: fn() -> Option<TypeId>,
+    pub run_if: Option<fn(&World) -> bool>,
+}
+
+impl GroupRegistration {
+    pub const fn of<G: PipelineGroup>(run_if: Option<fn(&World) -> bool>) -> Self {
+        GroupRegistration {
+            group: TypeId::of::<G>,
+            name: || G::instance().name(),
+            parent: || G::instance().parent(),
+            run_if,
+        }
+    }
+}
+
+inventory::collect!(GroupRegistration);
+
+/// The run conditions of `system` and of every pipeline group it is nested in.
+fn run_conditions(
+    system: &dyn PipelineStage,
+    groups: &HashMap<TypeId, &GroupRegistration>,
+) -> Vec<fn(&World) -> bool> {
+    let mut conditions: Vec<_> = system.run_if().into_iter().collect();
+    let mut visited = HashSet::new();
+    let mut group = system.parent();
+    while let Some(id) = group.filter(|id| visited.insert(*id)) {
+        let Some(registration) = groups.get(&id) else {
+            break;
+        };
+        conditions.extend(registration.run_if);
+        group = (registration.parent)();
+    }
+    conditions
+}
+
+/// Runs the systems of a pipeline group repeatedly within one tick until a convergence
+/// test passes, e.g. for iterative constraint solvers. Register it with
+/// `World::add_loop_group` before building the scheduler.
+///
+/// The group's direct children are scheduled as one unit, at the position given by the
+/// group's own `Parent` (`SimulationGroup` if it has none), `After` and `Before` and the
+/// children's ordering constraints on systems outside the group, with the union of the children's read and write sets. Each
+/// iteration runs the children in their own wavefronts, then calls the test with the
+/// world; the loop stops once it returns `true` or after `max_iters` iterations.
+///
+/// All iterations happen inside the same tick, so change masks accumulate across them and
+/// rollback snapshots keep the value from before the first one. As long as the test only
+/// looks at simulated state, every peer runs the same number of iterations.
+pub struct LoopGroup {
+    until: Box<dyn Fn(&World) -> bool + Send + Sync>,
+    max_iters: u32,
+}
+
+impl LoopGroup {
+    /// Repeats the group until `until` returns `true`, at most `max_iters` times.
+    ///
+    /// # Panics
+    /// Panics if `max_iters` is zero.
+    pub fn run_until(
+        until: impl Fn(&World) -> bool + Send + Sync + 'static,
+        max_iters: u32,
+    ) -> Self {
+        assert!(max_iters > 0, "loop groups must run at least once");
+        LoopGroup {
+            until: Box::new(until),
+            max_iters,
+        }
+    }
+
+    /// Attaches the loop to the pipeline group `G`.
+    pub(crate) fn bind<G: PipelineGroup>(self) -> BoundLoop {
+        let group = G::instance();
+        BoundLoop {
+            group: TypeId::of::<G>(),
+            name: group.name(),
+            parent: group.parent().or(Some(TypeId::of::<SimulationGroup>())),
+            before: group.before(),
+            after: group.after(),
+            body: self,
+        }
+    }
+}
+
+/// A `LoopGroup` attached to its pipeline group.
+pub(crate) struct BoundLoop {
+    group: TypeId,
+    name: &'static str,
+    parent: Option<TypeId>,
+    before: &'static [TypeId],
+    after: &'static [TypeId],
+    body: LoopGroup,
+}
+
+impl BoundLoop {
+    pub(crate) fn group(&self) -> TypeId {
+        self.group
+    }
+}
+
+/// Stands in for a loop group's children while the outer schedule is computed.
+struct LoopProxy {
+    group: TypeId,
+    name: &'static str,
+    parent: Option<TypeId>,
+    before: &'static [TypeId],
+    after: &'static [TypeId],
+    reads: &'static [TypeId],
+    writes: &'static [TypeId],
+}
+
+impl PipelineStage for LoopProxy {
+    fn run(&self) {
+        unreachable!("loop groups are run by the scheduler");
+    }
+
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn type_id(&self) -> TypeId {
+        self.group
+    }
+
+    fn before(&self) -> &'static [TypeId] {
+        self.before
+    }
+
+    fn after(&self) -> &'static [TypeId] {
+        self.after
+    }
+
+    fn reads(&self) -> &'static [TypeId] {
+        self.reads
+    }
+
+    fn writes(&self) -> &'static [TypeId] {
+        self.writes
+    }
+
+    fn parent(&self) -> Option<TypeId> {
+        self.parent
+    }
+}
+
+/// A system or loop node as `Scheduler::describe` sees it.
+struct StageNode {
+    id: TypeId,
+    name: String,
+    parent: Option<TypeId>,
+    before: &'static [TypeId],
+    after: &'static [TypeId],
+    reads: Vec<TypeId>,
+    writes: Vec<TypeId>,
+}
+
+impl StageNode {
+    fn describe(
+        &self,
+        name_of: &dyn Fn(TypeId) -> String,
+        groups: &HashMap<TypeId, &'static str>,
+        children: Vec<Vec<StageDescription>>,
+    ) -> StageDescription {
+        StageDescription {
+            name: self.name.clone(),
+            group: self.parent.map(|id| match groups.get(&id) {
+                Some(name) => short_type_name(name),
+                None => name_of(id),
+            }),
+            reads: self.reads.iter().map(|&id| name_of(id)).collect(),
+            writes: self.writes.iter().map(|&id| name_of(id)).collect(),
+            children,
+        }
+    }
+}
+
+/// A scheduled loop group.
+struct LoopNode {
+    bound: BoundLoop,
+    /// Wavefronts of the group's children, as indices into `Scheduler::systems`.
+    wavefronts: Vec<Vec<usize>>,
+}
+
 /// Trait for pipeline stages that can be scheduled and executed.
 /// Implementors must ensure they only access data that is safe to share across
 /// the scheduler's parallel wavefront execution. In practice this means honoring
@@ -97,6 +291,14 @@ pub trait PipelineStage: Send + Sync + 'static {
         None
     }
 
+    /// Returns the condition the system runs under, set with `RunIf`, `InState`, `OnEnter`
+    /// or `OnExit` in `system!`. It is evaluated against the world before the system's
+    /// wavefront of every tick, together with the conditions of its pipeline groups, and
+    /// the system is skipped when any returns `false`.
+    fn run_if(&self) -> Option<fn(&World) -> bool> {
+        None
+    }
+
     /// Creates a new instance of this system from the world.
     /// This method is only available for Sized types (not trait objects).
     fn create(_world: &mut World) -> Self
@@ -108,6 +310,67 @@ pub trait PipelineStage: Send + Sync + 'static {
     }
 }
 
+/// A condition deciding each tick whether a system runs, attached with `RunIf = C` in
+/// `system!`:
+/// ```ignore
+/// struct Unpaused;
+///
+/// impl RunCondition for Unpaused {
+///     fn should_run(world: &World) -> bool {
+///         world.resource::<Paused>().is_none_or(|paused| !paused.0)
+///     }
+/// }
+///
+/// system! {
+///     MoveSystem {
+///         query! {
+///             fn step(pos: &mut ViewMut<Position>, vel: View<Velocity>) RunIf = Unpaused {
+///                 pos.x += vel.x;
+///             }
+///         }
+///     }
+/// }
+/// ```
+///
+/// The condition sees the world as the systems of earlier wavefronts left it. Like a loop
+/// group's convergence test, it keeps peers in step as long as it only looks at simulated
+/// state, which a rollback restores, so a resimulated tick skips the same systems.
+pub trait RunCondition: 'static {
+    fn should_run(world: &World) -> bool;
+}
+
+/// Returns a static one-element type set holding `T`, for generic stages whose read or
+/// write sets depend on a type parameter. A `static` can't, so one slice is leaked per type.
+pub(crate) fn type_id_slice<T: 'static>() -> &'static [TypeId] {
+    static SLICES: std::sync::Mutex<Vec<(TypeId, &'static [TypeId])>> =
+        std::sync::Mutex::new(Vec::new());
+
+    let id = TypeId::of::<T>();
+    let mut slices = SLICES.lock().expect("Type set cache poisoned");
+    if let Some((_, slice)) = slices.iter().find(|(t, _)| *t == id) {
+        return slice;
+    }
+
+    let slice: &'static [TypeId] = Box::leak(Box::new([id]));
+    slices.push((id, slice));
+    slice
+}
+
+/// Returns a static copy of `ids` for stages whose type sets are only known at runtime.
+/// Identical sets share one leaked slice, so rebuilding a scheduler doesn't leak again.
+fn leak_type_set(ids: Vec<TypeId>) -> &'static [TypeId] {
+    static SETS: std::sync::Mutex<Vec<&'static [TypeId]>> = std::sync::Mutex::new(Vec::new());
+
+    let mut sets = SETS.lock().expect("Type set cache poisoned");
+    if let Some(set) = sets.iter().find(|set| **set == ids.as_slice()) {
+        return set;
+    }
+
+    let set: &'static [TypeId] = Box::leak(ids.into_boxed_slice());
+    sets.push(set);
+    set
+}
+
 /// A scheduler that uses Kahn's topological sort algorithm to order systems
 /// based on their dependencies (after, before, reads, writes).
 ///
@@ -118,9 +381,34 @@ pub struct Scheduler {
     systems: Vec<Box<dyn PipelineStage>>,
     /// Pre-computed wavefronts - each wavefront contains indices of systems that can run in parallel
     wavefronts: Vec<Vec<usize>>,
+    /// Loop groups, referenced from `wavefronts` as `systems.len() + index`
+    loops: Vec<LoopNode>,
+    /// Loop groups without children, kept for `into_parts`
+    idle_loops: Vec<BoundLoop>,
+    /// Systems (and loop nodes) that must run after each system or loop node
+    successors: Vec<Vec<usize>>,
+    /// Per-system timings, see the `profile` module
+    profile: Option<SystemProfile>,
+    /// Per-system write-set hashes, see the `audit` module
+    audit: Option<SystemAudit>,
+    /// Per system, its run conditions and those of its pipeline groups
+    conditions: Vec<Vec<fn(&World) -> bool>>,
+    /// Per system, whether it was switched off with `set_enabled`
+    disabled: Vec<bool>,
+    /// Whether any system has a run condition or is disabled, so wavefronts need filtering
+    gated: bool,
     /// Thread pool for parallel execution (only used when parallel feature is enabled)
     #[cfg(feature = "parallel")]
     thread_pool: ThreadPool,
+    /// Optional overrun diagnostics
+    #[cfg(feature = "watchdog")]
+    watchdog: Option<crate::watchdog::Watchdog>,
+    /// Runtime check of the concurrency model
+    #[cfg(feature = "model-check")]
+    tracker: crate::model::AccessTracker,
+    /// First system that panicked this tick, as (index, name, message)
+    #[cfg(feature = "panic-isolation")]
+    failure: std::sync::Mutex<Option<(usize, &'static str, String)>>,
 }
 
 impl Scheduler {
@@ -134,6 +422,12 @@ impl Scheduler {
     /// in the pipeline ordering. Systems in the same wavefront can run in any order,
     /// but ordering between wavefronts must be deterministic.
     pub fn new(systems: Vec<Box<dyn PipelineStage>>) -> Self {
+        Self::with_loops(systems, Vec::new())
+    }
+
+    /// Creates a scheduler whose loop groups run their children repeatedly, see
+    /// `LoopGroup`.
+    pub(crate) fn with_loops(systems: Vec<Box<dyn PipelineStage>>, loops: Vec<BoundLoop>) -> Self {
         #[cfg(feature = "parallel")]
         let thread_pool = rayon::ThreadPoolBuilder::new().build().expect("Failed to create thread pool for parallel scheduler");
 
@@ -141,32 +435,219 @@ impl Scheduler {
             return Self {
                 systems,
                 wavefronts: vec![],
+                loops: vec![],
+                idle_loops: loops,
+                successors: vec![],
+                profile: None,
+                audit: None,
+                conditions: vec![],
+                disabled: vec![],
+                gated: false,
                 #[cfg(feature = "parallel")]
                 thread_pool,
+                #[cfg(feature = "watchdog")]
+                watchdog: None,
+                #[cfg(feature = "model-check")]
+                tracker: Default::default(),
+                #[cfg(feature = "panic-isolation")]
+                failure: Default::default(),
             };
         }
 
-        let wavefronts = Self::compute_wavefronts(&systems);
+        let (wavefronts, loops, idle_loops, successors) = Self::schedule(&systems, loops);
+        let groups: HashMap<TypeId, &GroupRegistration> = inventory::iter::<GroupRegistration>()
+            .map(|registration| ((registration.group)(), registration))
+            .collect();
+        let conditions: Vec<_> = systems
+            .iter()
+            .map(|system| run_conditions(system.as_ref(), &groups))
+            .collect();
+        let gated = conditions.iter().any(|c| !c.is_empty());
 
         Self {
+            conditions,
+            disabled: vec![false; systems.len()],
             systems,
             wavefronts,
+            loops,
+            idle_loops,
+            successors,
+            profile: None,
+            audit: None,
+            gated,
             #[cfg(feature = "parallel")]
             thread_pool,
+            #[cfg(feature = "watchdog")]
+            watchdog: None,
+            #[cfg(feature = "model-check")]
+            tracker: Default::default(),
+            #[cfg(feature = "panic-isolation")]
+            failure: Default::default(),
         }
     }
 
+    /// Computes the wavefronts of `systems`, with the children of each loop group replaced
+    /// by a single node that gets a wavefront of its own.
+    ///
+    /// Also returns the loop groups without children, and the dependency graph over systems
+    /// and loop nodes, listing for every node the nodes that must run after it. Children of
+    /// a loop group only list each other.
+    fn schedule(
+        systems: &[Box<dyn PipelineStage>],
+        loops: Vec<BoundLoop>,
+    ) -> (Vec<Vec<usize>>, Vec<LoopNode>, Vec<BoundLoop>, Vec<Vec<usize>>) {
+        let member_of: Vec<Option<usize>> = systems
+            .iter()
+            .map(|s| loops.iter().position(|l| s.parent() == Some(l.group)))
+            .collect();
+
+        let mut proxies = Vec::new();
+        let mut nodes = Vec::new();
+        let mut idle = Vec::new();
+        let mut proxy_of = vec![None; loops.len()];
+        let mut successors = vec![Vec::new(); systems.len() + loops.len()];
+
+        for (k, bound) in loops.into_iter().enumerate() {
+            let body: Vec<usize> = (0..systems.len())
+                .filter(|&i| member_of[i] == Some(k))
+                .collect();
+            if body.is_empty() {
+                idle.push(bound);
+                continue;
+            }
+
+            let stages: Vec<&dyn PipelineStage> =
+                body.iter().map(|&i| systems[i].as_ref()).collect();
+            let (inner, graph) = Self::compute_wavefronts(&stages, &[]);
+            let wavefronts = inner
+                .into_iter()
+                .map(|wavefront| wavefront.into_iter().map(|j| body[j]).collect())
+                .collect();
+            for (j, next) in graph.into_iter().enumerate() {
+                successors[body[j]] = next.into_iter().map(|n| body[n]).collect();
+            }
+
+            // Constraints between children are handled by the inner wavefronts
+            let own: Vec<TypeId> = stages.iter().map(|s| s.type_id()).collect();
+            let union = |sets: &mut dyn Iterator<Item = &'static [TypeId]>| {
+                let mut ids = Vec::new();
+                for &id in sets.flatten() {
+                    if !own.contains(&id) && !ids.contains(&id) {
+                        ids.push(id);
+                    }
+                }
+                leak_type_set(ids)
+            };
+
+            proxy_of[k] = Some(proxies.len());
+            proxies.push(LoopProxy {
+                group: bound.group,
+                name: bound.name,
+                parent: bound.parent,
+                before: union(
+                    &mut std::iter::once(bound.before).chain(stages.iter().map(|s| s.before())),
+                ),
+                after: union(
+                    &mut std::iter::once(bound.after).chain(stages.iter().map(|s| s.after())),
+                ),
+                reads: union(&mut stages.iter().map(|s| s.reads())),
+                writes: union(&mut stages.iter().map(|s| s.writes())),
+            });
+            nodes.push(LoopNode { bound, wavefronts });
+        }
+
+        // Each loop takes the place of its first child, so index-based tie breaks between
+        // writers stay the same
+        let mut outer: Vec<&dyn PipelineStage> = Vec::new();
+        let mut outer_ids = Vec::new();
+        let mut aliases = Vec::new();
+        let mut placed = vec![false; proxies.len()];
+
+        for (i, system) in systems.iter().enumerate() {
+            let Some(p) = member_of[i].and_then(|k| proxy_of[k]) else {
+                outer.push(system.as_ref());
+                outer_ids.push(i);
+                continue;
+            };
+
+            if !placed[p] {
+                placed[p] = true;
+                outer.push(&proxies[p]);
+                outer_ids.push(systems.len() + p);
+            }
+            // Constraints on a child from outside the loop apply to the whole loop
+            let index = outer_ids
+                .iter()
+                .position(|&id| id == systems.len() + p)
+                .unwrap();
+            aliases.push((system.type_id(), index));
+        }
+
+        let (outer_wavefronts, graph) = Self::compute_wavefronts(&outer, &aliases);
+        for (j, next) in graph.into_iter().enumerate() {
+            successors[outer_ids[j]] = next.into_iter().map(|n| outer_ids[n]).collect();
+        }
+
+        let mut wavefronts = Vec::new();
+        for wavefront in outer_wavefronts {
+            let (systems_only, loops_only): (Vec<usize>, Vec<usize>) = wavefront
+                .into_iter()
+                .map(|j| outer_ids[j])
+                .partition(|&id| id < systems.len());
+
+            if !systems_only.is_empty() {
+                wavefronts.push(systems_only);
+            }
+            // Loops run on the calling thread, which evaluates their convergence tests
+            wavefronts.extend(loops_only.into_iter().map(|id| vec![id]));
+        }
+
+        successors.truncate(systems.len() + nodes.len());
+        (wavefronts, nodes, idle, successors)
+    }
+
+    /// Takes the scheduler apart into the systems and loop groups it was built from, in
+    /// registration order for the systems, so a new scheduler can be built with changes.
+    pub(crate) fn into_parts(self) -> (Vec<Box<dyn PipelineStage>>, Vec<BoundLoop>) {
+        let mut loops: Vec<BoundLoop> = self.loops.into_iter().map(|node| node.bound).collect();
+        loops.extend(self.idle_loops);
+        (self.systems, loops)
+    }
+
+    /// Rebuilds the scheduler over the same systems and loop groups, e.g. after changing
+    /// what they depend on. Profiling, disabled systems and the watchdog are reset.
+    pub fn rebuild(self) -> Self {
+        let (systems, loops) = self.into_parts();
+        Self::with_loops(systems, loops)
+    }
+
     /// Creates a new scheduler from a vector of systems, taking ownership.
     /// This is an alias for `new()` for convenience.
     pub fn from_systems(systems: Vec<Box<dyn PipelineStage>>) -> Self {
         Self::new(systems)
     }
 
-    /// Returns a reference to the computed wavefronts.
+    /// Returns a reference to the computed wavefronts. A loop group appears as a wavefront
+    /// holding the single index `len() + n`, `n` counting loop groups in schedule order.
     pub fn wavefronts(&self) -> &[Vec<usize>] {
         &self.wavefronts
     }
 
+    /// Returns the wavefronts in execution order with every loop group expanded into the
+    /// wavefronts of its children, so each holds indices into `systems` only.
+    pub fn stage_wavefronts(&self) -> Vec<Vec<usize>> {
+        let mut out = Vec::with_capacity(self.wavefronts.len());
+        for wavefront in &self.wavefronts {
+            match wavefront.as_slice() {
+                &[idx] if idx >= self.systems.len() => {
+                    out.extend(self.loops[idx - self.systems.len()].wavefronts.iter().cloned())
+                }
+                _ => out.push(wavefront.clone()),
+            }
+        }
+        out
+    }
+
     /// Returns the number of systems in this scheduler.
     pub fn len(&self) -> usize {
         self.systems.len()
@@ -184,44 +665,344 @@ impl Scheduler {
     /// This is the optimized execution path that uses pre-computed wavefronts
     /// for parallel execution of independent systems.
     ///
+    /// # Panics
+    /// Panics on reaching a loop group, whose convergence test needs the world; the world
+    /// runs its scheduler through `World::run`.
     pub fn run(&self) {
+        self.execute(None, false);
+    }
+
+    /// Executes all systems sequentially in topological order (flattened wavefronts).
+    /// This is the sequential version that executes systems one by one.
+    /// Use this when you need deterministic sequential execution or when systems
+    /// are not thread-safe.
+    ///
+    /// # Panics
+    /// Panics on reaching a loop group, like `run`.
+    pub fn run_sequential(&self) {
+        self.execute(None, true);
+    }
+
+    /// Like `run`, evaluating loop group convergence tests against `world`.
+    pub(crate) fn run_in(&self, world: &World) {
+        self.execute(Some(world), false);
+    }
+
+    /// Like `run_sequential`, evaluating loop group convergence tests against `world`.
+    pub(crate) fn run_sequential_in(&self, world: &World) {
+        self.execute(Some(world), true);
+    }
+
+    fn execute(&self, world: Option<&World>, sequential: bool) {
+        for (i, wavefront) in self.wavefronts.iter().enumerate() {
+            self.begin_wavefront(i);
+
+            match wavefront.as_slice() {
+                &[idx] if idx >= self.systems.len() => {
+                    self.run_loop(&self.loops[idx - self.systems.len()], world, sequential)
+                }
+                _ => self.run_wavefront(wavefront, world, sequential),
+            }
+
+            self.end_wavefront();
+
+            #[cfg(feature = "panic-isolation")]
+            if self.has_failed() {
+                return;
+            }
+        }
+    }
+
+    fn run_wavefront(&self, wavefront: &[usize], world: Option<&World>, sequential: bool) {
+        if !self.gated {
+            return self.run_systems(wavefront, sequential);
+        }
+
+        // Conditions run on the calling thread, before any system of the wavefront
+        let runnable: Vec<usize> = wavefront
+            .iter()
+            .copied()
+            .filter(|&idx| !self.disabled[idx])
+            .filter(|&idx| {
+                self.conditions[idx].iter().all(|condition| {
+                    condition(world.expect("Run conditions need the world, use World::run"))
+                })
+            })
+            .collect();
+        self.run_systems(&runnable, sequential);
+    }
+
+    #[allow(unused_variables)]
+    fn run_systems(&self, wavefront: &[usize], sequential: bool) {
         #[cfg(feature = "parallel")]
-        {
-            for wavefront in &self.wavefronts {
-                if wavefront.len() <= 1 {
-                    for &idx in wavefront {
-                        self.systems[idx].run();
-                    }
-                    continue;
+        if !sequential && wavefront.len() > 1 {
+            self.thread_pool.scope(|scope| {
+                for &idx in wavefront {
+                    scope.spawn(move |_| {
+                        self.run_stage(idx);
+                    });
                 }
+            });
+            return;
+        }
 
-                self.thread_pool.scope(|scope| {
-                    for &idx in wavefront {
-                        let system = &self.systems[idx];
-                        scope.spawn(move |_| {
-                            system.run();
-                        });
-                    }
-                });
+        for &idx in wavefront {
+            self.run_stage(idx);
+        }
+    }
+
+    fn run_loop(&self, node: &LoopNode, world: Option<&World>, sequential: bool) {
+        let world = world.expect("Loop groups need the world, run them with World::run");
+
+        for _ in 0..node.bound.body.max_iters {
+            for wavefront in &node.wavefronts {
+                self.run_wavefront(wavefront, Some(world), sequential);
+
+                #[cfg(feature = "panic-isolation")]
+                if self.has_failed() {
+                    return;
+                }
+            }
+
+            if (node.bound.body.until)(world) {
+                break;
             }
         }
-        #[cfg(not(feature = "parallel"))]
+    }
+
+    #[inline]
+    fn run_stage(&self, idx: usize) {
+        let system = &self.systems[idx];
+
+        #[cfg(feature = "model-check")]
+        let _access = self
+            .tracker
+            .enter(system.as_ref())
+            .unwrap_or_else(|violation| panic!("{}", violation));
+
+        #[cfg(feature = "panic-isolation")]
         {
-            // Without parallel feature, just run sequentially
-            self.run_sequential();
+            let run = std::panic::AssertUnwindSafe(|| self.run_system(idx, system.as_ref()));
+            if let Err(payload) = std::panic::catch_unwind(run) {
+                let message = crate::isolation::panic_message(payload.as_ref());
+                let mut failure = self.failure.lock().unwrap();
+                // Lowest schedule index wins, so parallel runs report the same system
+                if failure.as_ref().is_none_or(|(first, _, _)| idx < *first) {
+                    *failure = Some((idx, system.name(), message));
+                }
+            }
+        }
+
+        #[cfg(not(feature = "panic-isolation"))]
+        self.run_system(idx, system.as_ref());
+
+        if let Some(audit) = &self.audit {
+            audit.record(idx);
         }
     }
 
-    /// Executes all systems sequentially in topological order (flattened wavefronts).
-    /// This is the sequential version that executes systems one by one.
-    /// Use this when you need deterministic sequential execution or when systems
-    /// are not thread-safe.
-    pub fn run_sequential(&self) {
-        for wavefront in &self.wavefronts {
-            for &idx in wavefront {
-                self.systems[idx].run();
+    #[inline]
+    fn run_system(&self, idx: usize, system: &dyn PipelineStage) {
+        if let Some(profile) = &self.profile {
+            profile.time(idx, || self.run_timed(system));
+            return;
+        }
+
+        self.run_timed(system);
+    }
+
+    #[inline]
+    fn run_timed(&self, system: &dyn PipelineStage) {
+        #[cfg(feature = "watchdog")]
+        if let Some(watchdog) = &self.watchdog {
+            watchdog.time(system.name(), PipelineStage::type_id(system), || system.run());
+            return;
+        }
+
+        system.run();
+    }
+
+    #[cfg(feature = "panic-isolation")]
+    fn has_failed(&self) -> bool {
+        self.failure.lock().unwrap().is_some()
+    }
+
+    /// Removes and returns the name and panic message of the system that failed the last
+    /// run, if any.
+    #[cfg(feature = "panic-isolation")]
+    pub fn take_failure(&self) -> Option<(&'static str, String)> {
+        self.failure
+            .lock()
+            .unwrap()
+            .take()
+            .map(|(_, name, message)| (name, message))
+    }
+
+    #[inline]
+    #[allow(unused_variables)]
+    fn begin_wavefront(&self, index: usize) {
+        #[cfg(feature = "watchdog")]
+        if let Some(watchdog) = &self.watchdog {
+            watchdog.begin_wavefront(index);
+        }
+    }
+
+    #[inline]
+    fn end_wavefront(&self) {
+        #[cfg(feature = "watchdog")]
+        if let Some(watchdog) = &self.watchdog {
+            watchdog.end_wavefront();
+        }
+    }
+
+    /// Installs (or with `None`, removes) the overrun watchdog.
+    #[cfg(feature = "watchdog")]
+    pub fn set_watchdog(&mut self, config: Option<crate::watchdog::WatchdogConfig>) {
+        self.watchdog = config.map(crate::watchdog::Watchdog::new);
+    }
+
+    /// Removes and returns the overruns recorded by the watchdog so far.
+    #[cfg(feature = "watchdog")]
+    pub fn take_overruns(&self) -> Vec<crate::watchdog::Overrun> {
+        self.watchdog
+            .as_ref()
+            .map(|w| w.take_overruns())
+            .unwrap_or_default()
+    }
+
+    /// Switches the system with type id `system` off (or back on). A disabled system is
+    /// skipped until it is enabled again. Returns false if no such system is scheduled.
+    pub fn set_enabled(&mut self, system: TypeId, enabled: bool) -> bool {
+        let Some(idx) = self.systems.iter().position(|s| s.type_id() == system) else {
+            return false;
+        };
+        self.disabled[idx] = !enabled;
+        self.gated =
+            self.disabled.contains(&true) || self.conditions.iter().any(|c| !c.is_empty());
+        true
+    }
+
+    /// Whether the system with type id `system` is scheduled and enabled.
+    pub fn is_enabled(&self, system: TypeId) -> bool {
+        self.systems
+            .iter()
+            .position(|s| s.type_id() == system)
+            .is_some_and(|idx| !self.disabled[idx])
+    }
+
+    /// Starts (or with `false`, stops and forgets) timing every system, see the `profile`
+    /// module.
+    pub fn set_profiling(&mut self, enabled: bool) {
+        self.profile = enabled.then(|| SystemProfile::new(self.systems.len()));
+    }
+
+    /// Starts (or with `None`, stops) hashing every system's write set after it ran, see
+    /// the `audit` module.
+    pub(crate) fn set_audit(&mut self, audit: Option<SystemAudit>) {
+        self.audit = audit;
+    }
+
+    /// Takes the write-set hashes of the last tick, by system in wavefront order (index
+    /// order within a wavefront), or `None` while the audit is off.
+    pub(crate) fn take_audit(&self) -> Option<Vec<(&'static str, Option<u128>)>> {
+        let hashes = self.audit.as_ref()?.take();
+        let mut order: Vec<Vec<usize>> = self.stage_wavefronts();
+        order.iter_mut().for_each(|wavefront| wavefront.sort_unstable());
+        Some(
+            order
+                .into_iter()
+                .flatten()
+                .map(|idx| (self.systems[idx].name(), hashes[idx]))
+                .collect(),
+        )
+    }
+
+    /// Returns the smoothed run time of every system in index order, or `None` while
+    /// profiling is off. Systems that haven't run yet report zero.
+    pub fn system_timings(&self) -> Option<Vec<(&'static str, Duration)>> {
+        let profile = self.profile.as_ref()?;
+        Some(
+            self.systems
+                .iter()
+                .enumerate()
+                .map(|(i, s)| (s.name(), profile.cost(i)))
+                .collect(),
+        )
+    }
+
+    /// Longest chain of dependent systems starting at every system and loop node, indexed
+    /// like `wavefronts`, from the profiled timings. All zero while profiling is off.
+    pub fn critical_path_lengths(&self) -> Vec<Duration> {
+        let cost = |idx: usize| {
+            self.profile
+                .as_ref()
+                .map(|p| p.cost(idx))
+                .unwrap_or_default()
+        };
+        let mut levels = vec![Duration::ZERO; self.successors.len()];
+
+        for node in &self.loops {
+            let order = node.wavefronts.iter().flatten().copied();
+            crate::profile::bottom_levels(order, &self.successors, cost, &mut levels);
+        }
+
+        // One iteration of a loop takes as long as the longest chain of its children
+        let loop_costs: Vec<Duration> = self
+            .loops
+            .iter()
+            .map(|node| {
+                let starts = node.wavefronts.first().into_iter().flatten();
+                starts.map(|&child| levels[child]).max().unwrap_or_default()
+            })
+            .collect();
+        let node_cost = |idx: usize| match idx.checked_sub(self.systems.len()) {
+            Some(k) => loop_costs[k],
+            None => cost(idx),
+        };
+
+        let order = self.wavefronts.iter().flatten().copied();
+        crate::profile::bottom_levels(order, &self.successors, node_cost, &mut levels);
+        levels
+    }
+
+    /// Returns the longest chain of dependent systems by profiled run time, as names and
+    /// smoothed run times in execution order, loop groups expanded into the longest chain
+    /// of one iteration of their children. Empty while profiling is off.
+    pub fn critical_path(&self) -> Vec<(&'static str, Duration)> {
+        let Some(profile) = &self.profile else {
+            return Vec::new();
+        };
+        let levels = self.critical_path_lengths();
+        let starts = self.wavefronts.first().into_iter().flatten().copied();
+
+        let mut path = Vec::new();
+        for idx in crate::profile::longest_chain(starts, &self.successors, &levels) {
+            match idx.checked_sub(self.systems.len()) {
+                Some(k) => {
+                    let starts = self.loops[k].wavefronts[0].iter().copied();
+                    for child in crate::profile::longest_chain(starts, &self.successors, &levels) {
+                        path.push((self.systems[child].name(), profile.cost(child)));
+                    }
+                }
+                None => path.push((self.systems[idx].name(), profile.cost(idx))),
             }
         }
+        path
+    }
+
+    /// Reorders every wavefront so systems starting the longest chains are spawned first,
+    /// ties keeping index order. The wavefronts themselves don't change, and neither do
+    /// results, since systems sharing a wavefront never conflict.
+    pub fn prioritize(&mut self) {
+        let levels = self.critical_path_lengths();
+        let by_priority = |wavefront: &mut Vec<usize>| {
+            wavefront.sort_by(|&a, &b| levels[b].cmp(&levels[a]).then(a.cmp(&b)));
+        };
+
+        self.wavefronts.iter_mut().for_each(by_priority);
+        for node in &mut self.loops {
+            node.wavefronts.iter_mut().for_each(by_priority);
+        }
     }
 
     /// Returns an iterator over the systems.
@@ -229,28 +1010,167 @@ impl Scheduler {
         self.systems.iter().map(|s| s.as_ref())
     }
 
+    /// Describes the wavefronts and the ordering edges between stages, see
+    /// `graph::ScheduleDescription`. Component types are named after their derive
+    /// registration; `World::describe_schedule` also names resources and mailboxes.
+    pub fn describe(&self) -> ScheduleDescription {
+        let mut names: HashMap<TypeId, &'static str> = inventory::iter::<ComponentRegistration>()
+            .map(|registration| ((registration.type_id)(), (registration.type_name)()))
+            .collect();
+        // The built-in components have no derive
+        names.insert(TypeId::of::<Entity>(), "Entity");
+        names.insert(TypeId::of::<Destroyed>(), "Destroyed");
+        self.describe_with(&|id| match names.get(&id) {
+            Some(name) => short_type_name(name),
+            None => format!("{:?}", id),
+        })
+    }
+
+    /// Renders the schedule in Graphviz DOT format, see `ScheduleDescription::to_dot`.
+    pub fn to_dot(&self) -> String {
+        self.describe().to_dot()
+    }
+
+    /// Renders the schedule as a Mermaid flowchart, see
+    /// `ScheduleDescription::to_mermaid`.
+    pub fn to_mermaid(&self) -> String {
+        self.describe().to_mermaid()
+    }
+
+    /// `describe`, naming accessed types with `name_of`.
+    pub(crate) fn describe_with(&self, name_of: &dyn Fn(TypeId) -> String) -> ScheduleDescription {
+        let nodes = self.systems.len() + self.loops.len();
+        let stages: Vec<StageNode> = (0..nodes).map(|node| self.stage_node(node)).collect();
+        let groups: HashMap<TypeId, &'static str> = inventory::iter::<GroupRegistration>()
+            .map(|registration| ((registration.group)(), (registration.name)()))
+            .collect();
+
+        let describe = |node: usize| {
+            let stage = &stages[node];
+            let children = match node.checked_sub(self.systems.len()) {
+                Some(k) => self.loops[k]
+                    .wavefronts
+                    .iter()
+                    .map(|wavefront| {
+                        wavefront
+                            .iter()
+                            .map(|&i| stages[i].describe(name_of, &groups, Vec::new()))
+                            .collect()
+                    })
+                    .collect(),
+                None => Vec::new(),
+            };
+            stage.describe(name_of, &groups, children)
+        };
+
+        let wavefronts = self
+            .wavefronts
+            .iter()
+            .map(|wavefront| wavefront.iter().map(|&node| describe(node)).collect())
+            .collect();
+
+        let mut edges = Vec::new();
+        for (node, next) in self.successors.iter().enumerate() {
+            let a = &stages[node];
+            for &after in next {
+                let b = &stages[after];
+                let declared = a.before.contains(&b.id) || b.after.contains(&a.id);
+
+                let mut conflicts: Vec<String> = a
+                    .writes
+                    .iter()
+                    .filter(|id| b.reads.contains(id) || b.writes.contains(id))
+                    .chain(a.reads.iter().filter(|id| b.writes.contains(id)))
+                    .map(|&id| name_of(id))
+                    .collect();
+                conflicts.sort();
+                conflicts.dedup();
+
+                edges.push(ScheduleEdge {
+                    before: a.name.clone(),
+                    after: b.name.clone(),
+                    declared,
+                    conflicts,
+                });
+            }
+        }
+
+        ScheduleDescription { wavefronts, edges }
+    }
+
+    /// The identity, ordering constraints and access sets of a system or loop node, a loop
+    /// group accessing what its children do.
+    fn stage_node(&self, node: usize) -> StageNode {
+        let Some(k) = node.checked_sub(self.systems.len()) else {
+            let system = self.systems[node].as_ref();
+            return StageNode {
+                id: PipelineStage::type_id(system),
+                name: short_type_name(system.name()),
+                parent: system.parent(),
+                before: system.before(),
+                after: system.after(),
+                reads: system.reads().to_vec(),
+                writes: system.writes().to_vec(),
+            };
+        };
+
+        let bound = &self.loops[k].bound;
+        let mut reads = Vec::new();
+        let mut writes = Vec::new();
+        for &i in self.loops[k].wavefronts.iter().flatten() {
+            for id in self.systems[i].reads() {
+                if !reads.contains(id) {
+                    reads.push(*id);
+                }
+            }
+            for id in self.systems[i].writes() {
+                if !writes.contains(id) {
+                    writes.push(*id);
+                }
+            }
+        }
+        StageNode {
+            id: bound.group,
+            name: short_type_name(bound.name),
+            parent: bound.parent,
+            before: bound.before,
+            after: bound.after,
+            reads,
+            writes,
+        }
+    }
+
     /// Computes wavefronts for a slice of systems.
     /// This is an internal helper used during construction.
     ///
     /// # Panics
     /// Panics if there's a circular dependency or any source of non-determinism.
-    fn compute_wavefronts(systems: &[Box<dyn PipelineStage>]) -> Vec<Vec<usize>> {
+    /// `aliases` maps additional type ids to node indices, so constraints naming them apply
+    /// to that node. Also returns the dependency graph, listing for every system the systems
+    /// that must run after it.
+    fn compute_wavefronts(
+        systems: &[&dyn PipelineStage],
+        aliases: &[(TypeId, usize)],
+    ) -> (Vec<Vec<usize>>, Vec<Vec<usize>>) {
         if systems.is_empty() {
-            return vec![];
+            return (vec![], vec![]);
         }
 
         let num_systems = systems.len();
 
-        // Build dependency graph
-        let mut graph: Vec<HashSet<usize>> = vec![HashSet::new(); num_systems];
+        // Ordered sets so edges are visited in index order, independent of hasher seeds
+        let mut graph: Vec<BTreeSet<usize>> = vec![BTreeSet::new(); num_systems];
         let mut in_degree = vec![0; num_systems];
 
         // Build system index map for fast lookups
-        let system_indices: HashMap<TypeId, usize> = systems
+        let mut system_indices: HashMap<TypeId, usize> = systems
             .iter()
             .enumerate()
             .map(|(i, s)| (s.type_id(), i))
             .collect();
+        for &(id, index) in aliases {
+            system_indices.entry(id).or_insert(index);
+        }
 
         // Build group-to-systems mapping for handling group dependencies
         let mut group_to_systems: HashMap<TypeId, Vec<usize>> = HashMap::new();
@@ -290,7 +1210,7 @@ impl Scheduler {
 
                     // Check if parent is a system in our list
                     if let Some(&parent_idx) = system_indices.get(&parent_id) {
-                        let parent_system = systems[parent_idx].as_ref();
+                        let parent_system = systems[parent_idx];
                         all_before.extend_from_slice(parent_system.before());
                         all_after.extend_from_slice(parent_system.after());
                         current_parent = parent_system.parent();
@@ -388,7 +1308,7 @@ impl Scheduler {
         for (i, system) in systems.iter().enumerate() {
             // Collect all dependencies including recursive parent dependencies and group dependencies
             let (all_before, all_after, all_after_groups, all_before_groups) =
-                collect_parent_dependencies(system.as_ref(), &system_indices, &group_to_systems);
+                collect_parent_dependencies(*system, &system_indices, &group_to_systems);
 
             // Handle 'after' dependencies: this system runs after these systems
             // Include both direct and inherited from parents
@@ -438,6 +1358,11 @@ impl Scheduler {
                 }
             }
 
+        }
+
+        // Conflict edges are resolved only after every explicit (After/Before/group) edge is
+        // in the graph, so they can defer to explicit ordering regardless of system index.
+        for (i, system) in systems.iter().enumerate() {
             // Handle dependencies for systems that write to components.
             // Rule: If system X writes to component K, then any system that reads OR writes K
             // must not be in the same wavefront as X (they must run sequentially).
@@ -635,7 +1560,7 @@ impl Scheduler {
                 // Collect all dependencies this system should have
                 let (all_before, all_after, all_after_groups, all_before_groups) =
                     collect_parent_dependencies(
-                        systems[system_idx].as_ref(),
+                        systems[system_idx],
                         &system_indices,
                         &group_to_systems,
                     );
@@ -734,7 +1659,8 @@ impl Scheduler {
             }
         }
 
-        wavefronts
+        let successors = graph.into_iter().map(|next| next.into_iter().collect()).collect();
+        (wavefronts, successors)
     }
 }
 
@@ -0,0 +1,103 @@
+//! An `unsafe` escape hatch for moving a whole `World` to another thread, for servers
+//! running one simulation per worker.
+//!
+//! This is not a thread-safe world. A world shares its storages and resources between
+//! itself and its systems through `Rc`s, and components, hooks and run conditions need not
+//! be `Send`, so `World` is not `Send` and nothing here changes that. Making it `Send`
+//! without `unsafe` would take `Arc`-based handles throughout the world and the `system!`
+//! output, plus `Send` bounds on components and registered closures; that isn't done.
+//!
+//! What `SendWorld` offers instead: moving a world is sound as long as every one of those
+//! `Rc`s moves with it and everything it holds may change threads. `SendWorld` wraps a
+//! world whose caller vouches for that, and only lends the world to `Send` closures
+//! returning `Send` values, so a handle such as `World::get_storage` returns can't be
+//! captured by the closure or returned from it. The bounds can't see everything a closure
+//! does with `&mut World`: it could still stash a handle in a `thread_local!` or a leaked
+//! allocation. Both `SendWorld::new` and `SendWorld::with` are therefore `unsafe`, and the
+//! soundness rests entirely on their callers.
+//!
+//! # Example
+//! ```ignore
+//! let mut world = build_match();
+//! world.build_scheduler();
+//! // SAFETY: no storage or resource handle of the world is kept outside it
+//! let mut world = unsafe { SendWorld::new(world) };
+//!
+//! let worker = std::thread::spawn(move || {
+//!     for _ in 0..600 {
+//!         // SAFETY: running the world keeps its handles inside it
+//!         unsafe { world.with(|world| world.run()) };
+//!     }
+//!     world
+//! });
+//! let checksum = worker.join().unwrap().into_inner().compute_state_hash();
+//! ```
+//!
+//! Handles can't be returned from `with`:
+//! ```compile_fail,E0277
+//! # use rollback_ecs::prelude::*;
+//! # use rollback_ecs::send::SendWorld;
+//! # #[derive(Component, Clone, Default)]
+//! # struct Counter(u32);
+//! fn leak(world: &mut SendWorld) {
+//!     let storage = unsafe { world.with(|world| world.get_storage::<Counter>()) };
+//! }
+//! ```
+//!
+//! nor captured by its closure:
+//! ```compile_fail,E0277
+//! # use rollback_ecs::prelude::*;
+//! # use rollback_ecs::send::SendWorld;
+//! # #[derive(Component, Clone, Default)]
+//! # struct Counter(u32);
+//! fn smuggle(world: &mut SendWorld, other: &mut World) {
+//!     let storage = other.get_storage::<Counter>();
+//!     unsafe { world.with(move |_| drop(storage)) };
+//! }
+//! ```
+
+use crate::world::World;
+
+/// A `World` that can be sent to another thread, see the module docs.
+pub struct SendWorld {
+    world: World,
+}
+
+// SAFETY: `new` requires every `Rc` of the world to be owned by the world, and `with` only
+// lends it to closures whose callers promise not to move one out, so they all change
+// threads together
+unsafe impl Send for SendWorld {}
+
+impl SendWorld {
+    /// Wraps `world` so it can be sent to another thread.
+    ///
+    /// # Safety
+    /// No handle the world hands out (storages from `World::get_storage`, resource cells,
+    /// senders and the like) may be alive outside it, including in thread-locals, every
+    /// component and resource type in it must be `Send`, and every closure registered with
+    /// the world (tick hooks, overflow handlers, run conditions) must only capture `Send`
+    /// data.
+    pub unsafe fn new(world: World) -> Self {
+        SendWorld { world }
+    }
+
+    /// Calls `f` with the world. `f` and its result are `Send`, so they can't capture or
+    /// return the world's `Rc`s.
+    ///
+    /// # Safety
+    /// `f` must not keep any handle the world hands out (see `new`) alive past the call
+    /// outside the world, e.g. in a thread-local, a static or a leaked allocation, and must
+    /// only register closures with the world that capture `Send` data.
+    pub unsafe fn with<R: Send>(&mut self, f: impl FnOnce(&mut World) -> R + Send) -> R {
+        f(&mut self.world)
+    }
+
+    /// Unwraps the world on the thread it ended up on.
+    pub fn into_inner(self) -> World {
+        self.world
+    }
+}
+
+#[cfg(test)]
+#[path = "send.tests.rs"]
+mod tests;
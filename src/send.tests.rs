@@ -0,0 +1,50 @@
+use super::*;
+use crate::prelude::*;
+
+#[derive(Component, Clone, Default, PartialEq, Debug)]
+struct Counter(u32);
+
+system! {
+    CountSystem {
+        query! {
+            fn tally(counter: &mut ViewMut<Counter>) {
+                counter.0 += 1;
+            }
+        }
+    }
+}
+
+fn build() -> (World, Entity) {
+    let mut world = World::new();
+    world.add_system::<CountSystem>();
+    world.build_scheduler();
+    let e = world.spawn();
+    world.set(e, &Counter(0));
+    (world, e)
+}
+
+#[test]
+fn test_world_runs_on_another_thread() {
+    let (world, e) = build();
+    // SAFETY: no handle of the world is kept outside it
+    let mut world = unsafe { SendWorld::new(world) };
+
+    let worker = std::thread::spawn(move || {
+        for _ in 0..10 {
+            // SAFETY: running the world keeps its handles inside it
+            unsafe { world.with(|world| world.run()) };
+        }
+        world
+    });
+    let mut world = worker.join().unwrap();
+    // SAFETY: the closure only reads a component value
+    let counter = unsafe { world.with(|world| world.get::<Counter>(e).cloned()) };
+    assert_eq!(counter, Some(Counter(10)));
+
+    let (mut local, _) = build();
+    for _ in 0..10 {
+        local.run();
+    }
+    let world = world.into_inner();
+    assert_eq!(world.compute_state_hash(), local.compute_state_hash());
+}
@@ -0,0 +1,213 @@
+//! Stable ordering keys for deferred operations.
+//!
+//! Systems in the same wavefront may run in any order (or concurrently), so operations they
+//! defer - commands, events, mailbox messages - cannot be merged in arrival order without
+//! breaking determinism. Every deferred operation is instead tagged with a `SequenceKey`:
+//! a stable hash of the emitting stage's type name plus a per-stage counter. Buffers are
+//! sorted by key before they are applied at the barrier, so parallel and sequential runs
+//! observe exactly the same order.
+//!
+//! Stage hashes come from `std::any::type_name`, which is stable for a given build; all
+//! peers of a rollback session are expected to run the same binary.
+
+use std::cell::{Cell, UnsafeCell};
+use std::rc::Rc;
+
+/// Ordering key of a deferred operation: `(stage hash, per-stage sequence number)`.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub struct SequenceKey {
+    pub stage: u64,
+    pub seq: u32,
+}
+
+/// FNV-1a hash of a stage name. Unlike `TypeId`, the result is the same on every run.
+pub const fn stage_hash(name: &str) -> u64 {
+    let bytes = name.as_bytes();
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    let mut i = 0;
+
+    while i < bytes.len() {
+        hash ^= bytes[i] as u64;
+        hash = hash.wrapping_mul(0x0000_0100_0000_01b3);
+        i += 1;
+    }
+
+    hash
+}
+
+/// Stable stage hash of the type `S`.
+pub fn stage_key<S: ?Sized>() -> u64 {
+    stage_hash(std::any::type_name::<S>())
+}
+
+/// Stage hash used for operations emitted from outside any system (e.g. by the game loop).
+/// It sorts before every system's operations.
+pub const EXTERNAL_STAGE: u64 = 0;
+
+/// Per-stage append-only buffer. Each emitting stage owns its own lane, so stages in the
+/// same wavefront never write to shared memory.
+pub struct Lane<M> {
+    stage: u64,
+    next_seq: Cell<u32>,
+    items: UnsafeCell<Vec<(SequenceKey, M)>>,
+}
+
+impl<M> Lane<M> {
+    pub fn new(stage: u64) -> Self {
+        Lane {
+            stage,
+            next_seq: Cell::new(0),
+            items: UnsafeCell::new(Vec::new()),
+        }
+    }
+
+    /// Stage hash this lane stamps onto its operations.
+    pub fn stage(&self) -> u64 {
+        self.stage
+    }
+
+    /// Appends an operation, stamping it with the next key of this stage.
+    pub fn push(&self, item: M) -> SequenceKey {
+        let key = SequenceKey {
+            stage: self.stage,
+            seq: self.next_seq.get(),
+        };
+        self.next_seq.set(key.seq.wrapping_add(1));
+
+        // SAFETY: a lane is only written by the stage that owns it.
+        unsafe { (*self.items.get()).push((key, item)) };
+        key
+    }
+
+    /// Number of operations not yet merged.
+    pub fn len(&self) -> usize {
+        unsafe { (*self.items.get()).len() }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// A set of lanes merged into one deterministically ordered sequence at the barrier.
+pub struct SequencedLanes<M> {
+    lanes: UnsafeCell<Vec<Rc<Lane<M>>>>,
+    merged: UnsafeCell<Vec<(SequenceKey, M)>>,
+}
+
+impl<M> SequencedLanes<M> {
+    pub fn new() -> Self {
+        SequencedLanes {
+            lanes: UnsafeCell::new(Vec::new()),
+            merged: UnsafeCell::new(Vec::new()),
+        }
+    }
+
+    /// Creates a new lane for the given stage. Must not be called while systems are running.
+    pub fn lane(&self, stage: u64) -> Rc<Lane<M>> {
+        let lane = Rc::new(Lane::new(stage));
+        unsafe { (*self.lanes.get()).push(lane.clone()) };
+        lane
+    }
+
+    /// Drains every lane into the merged buffer and sorts it by `SequenceKey`.
+    /// This is the barrier: call it only once all emitting stages have finished.
+    pub fn merge(&self) {
+        let lanes = unsafe { &*self.lanes.get() };
+        let merged = unsafe { &mut *self.merged.get() };
+
+        let mut drained = false;
+        for lane in lanes {
+            let items = unsafe { &mut *lane.items.get() };
+            if !items.is_empty() {
+                merged.append(items);
+                drained = true;
+            }
+        }
+
+        if drained {
+            // Stable sort: duplicate keys (two lanes of the same stage) keep lane order
+            merged.sort_by_key(|(key, _)| *key);
+        }
+    }
+
+    /// Merges pending operations and returns the ordered result.
+    pub fn merged(&self) -> &[(SequenceKey, M)] {
+        self.merge();
+        unsafe { &*self.merged.get() }
+    }
+
+    /// Merges pending operations and moves them out in order.
+    pub fn drain(&self) -> Vec<(SequenceKey, M)> {
+        self.merge();
+        unsafe { std::mem::take(&mut *self.merged.get()) }
+    }
+
+    /// Total number of operations, merged or pending.
+    pub fn len(&self) -> usize {
+        let lanes = unsafe { &*self.lanes.get() };
+        let merged = unsafe { (*self.merged.get()).len() };
+        merged + lanes.iter().map(|l| l.len()).sum::<usize>()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Drops all operations and resets every lane's counter.
+    pub fn clear(&self) {
+        let lanes = unsafe { &*self.lanes.get() };
+        for lane in lanes {
+            unsafe { (*lane.items.get()).clear() };
+            lane.next_seq.set(0);
+        }
+        unsafe { (*self.merged.get()).clear() };
+    }
+}
+
+impl<M> Default for SequencedLanes<M> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stage_hash_is_stable() {
+        assert_eq!(stage_hash(""), 0xcbf2_9ce4_8422_2325);
+        assert_eq!(stage_hash("a"), 0xaf63_dc4c_8601_ec8c);
+        assert_ne!(stage_key::<u32>(), stage_key::<u64>());
+    }
+
+    #[test]
+    fn test_merge_orders_by_stage_then_sequence() {
+        let lanes = SequencedLanes::<&'static str>::new();
+        let late = lanes.lane(20);
+        let early = lanes.lane(10);
+
+        late.push("late-0");
+        early.push("early-0");
+        late.push("late-1");
+        early.push("early-1");
+
+        let order: Vec<_> = lanes.drain().into_iter().map(|(_, m)| m).collect();
+        assert_eq!(order, vec!["early-0", "early-1", "late-0", "late-1"]);
+    }
+
+    #[test]
+    fn test_clear_resets_counters() {
+        let lanes = SequencedLanes::<u32>::new();
+        let lane = lanes.lane(1);
+
+        lane.push(1);
+        lane.push(2);
+        assert_eq!(lanes.len(), 2);
+
+        lanes.clear();
+        assert!(lanes.is_empty());
+        assert_eq!(lane.push(3).seq, 0);
+    }
+}
@@ -0,0 +1,312 @@
+//! GGPO-style rollback sessions.
+//!
+//! `RollbackSession` drives a `World` frame by frame from the inputs of several players.
+//! Inputs are added with `add_input` as they become known: local ones right away, remote
+//! ones whenever the network delivers them, in any order. `advance` simulates the next
+//! frame, predicting every input that hasn't arrived by repeating the player's last
+//! confirmed one. When an input arrives for a frame that was already simulated with a
+//! different prediction, the session rolls the world back to that frame and resimulates
+//! up to the present with the corrected inputs, before the next `advance` or on `sync`.
+//! Frames before the mispredicted one used the right inputs and are kept.
+//!
+//! Frame `f` is simulated at world tick `start + 1 + f`, where `start` is the world's tick
+//! when the session was created. The session runs `start` itself with default inputs, so
+//! frame 0 can be resimulated like any other.
+//!
+//! `set_max_prediction` bounds how far the session runs ahead of the last frame every
+//! player's input is confirmed for, which also bounds the rollback depth. With a checksum
+//! function set, the session records a checksum after every simulated frame, so peers can
+//! compare confirmed frames to detect desyncs. `netsim::NetSim` runs two sessions over a
+//! simulated network.
+//!
+//! # Example
+//! ```ignore
+//! let mut session = RollbackSession::new(world, 2, |world, inputs: &[Pad]| {
+//!     for (player, input) in players(world).into_iter().zip(inputs) {
+//!         world.set(player, input);
+//!     }
+//! });
+//!
+//! loop {
+//!     let frame = session.frame();
+//!     session.add_input(LOCAL, frame, read_pad())?;
+//!     for (frame, input) in socket.receive() {
+//!         session.add_input(REMOTE, frame, input)?;
+//!     }
+//!     if session.can_advance() {
+//!         session.advance()?;
+//!     }
+//! }
+//! ```
+
+use crate::tick::Tick;
+use crate::world::World;
+use std::fmt;
+
+/// Writes one frame's inputs, indexed by player, into the world before it runs.
+pub type ApplyFrame<I> = Box<dyn Fn(&mut World, &[I])>;
+
+/// Why a session call was refused.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SessionError {
+    /// The player index is not below `players()`.
+    UnknownPlayer(usize),
+    /// A different input was already added for the player and frame.
+    ConflictingInput { player: usize, frame: u32 },
+    /// The next frame would be more than `max_prediction` frames past the last frame
+    /// every player's input is confirmed for.
+    PredictionLimit,
+}
+
+impl fmt::Display for SessionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SessionError::UnknownPlayer(player) => write!(f, "unknown player {}", player),
+            SessionError::ConflictingInput { player, frame } => write!(
+                f,
+                "conflicting input for player {} at frame {}",
+                player, frame
+            ),
+            SessionError::PredictionLimit => write!(f, "prediction limit reached"),
+        }
+    }
+}
+
+impl std::error::Error for SessionError {}
+
+/// A world driven by the inputs of several players, see the module docs.
+pub struct RollbackSession<I> {
+    world: World,
+    apply: ApplyFrame<I>,
+    /// The tick run with default inputs before frame 0.
+    start: Tick,
+    /// Added inputs, `inputs[player][frame]`.
+    inputs: Vec<Vec<Option<I>>>,
+    /// Number of contiguous confirmed frames per player.
+    confirmed: Vec<u32>,
+    /// Inputs each simulated frame used, confirmed or predicted.
+    used: Vec<Vec<I>>,
+    /// Earliest simulated frame that used a wrong prediction.
+    mispredicted: Option<u32>,
+    max_prediction: u32,
+    checksum: Option<fn(&World) -> u64>,
+    /// Checksum after each simulated frame, if a checksum function is set.
+    checksums: Vec<u64>,
+    rollbacks: u32,
+    resimulated_frames: u32,
+}
+
+impl<I: Clone + PartialEq + Default> RollbackSession<I> {
+    /// Creates a session of `players` players over `world` and runs its start tick with
+    /// default inputs. `apply` writes a frame's inputs into the world.
+    pub fn new(
+        mut world: World,
+        players: usize,
+        apply: impl Fn(&mut World, &[I]) + 'static,
+    ) -> Self {
+        let start = world.current_tick();
+        let neutral = vec![I::default(); players];
+        world.edit_scope(|w| apply(w, &neutral));
+        world.run();
+
+        RollbackSession {
+            world,
+            apply: Box::new(apply),
+            start,
+            inputs: vec![Vec::new(); players],
+            confirmed: vec![0; players],
+            used: Vec::new(),
+            mispredicted: None,
+            max_prediction: 8,
+            checksum: None,
+            checksums: Vec::new(),
+            rollbacks: 0,
+            resimulated_frames: 0,
+        }
+    }
+
+    /// Sets how many frames the session may run past the last frame every player's input
+    /// is confirmed for. Defaults to 8.
+    pub fn set_max_prediction(&mut self, frames: u32) {
+        self.max_prediction = frames;
+    }
+
+    /// Records `checksum(world)` after every simulated frame, see `checksums`. Call it
+    /// before the first `advance`.
+    pub fn set_checksum(&mut self, checksum: fn(&World) -> u64) {
+        self.checksum = Some(checksum);
+    }
+
+    pub fn world(&self) -> &World {
+        &self.world
+    }
+
+    pub fn world_mut(&mut self) -> &mut World {
+        &mut self.world
+    }
+
+    pub fn players(&self) -> usize {
+        self.inputs.len()
+    }
+
+    /// Number of frames simulated so far, which is also the next frame to simulate.
+    pub fn frame(&self) -> u32 {
+        self.used.len() as u32
+    }
+
+    /// Number of contiguous frames whose input from `player` is confirmed.
+    ///
+    /// # Panics
+    /// Panics if `player` is not below `players()`.
+    pub fn confirmed(&self, player: usize) -> u32 {
+        self.confirmed[player]
+    }
+
+    /// Number of contiguous frames whose inputs from every player are confirmed. Once
+    /// simulated, these frames are final.
+    pub fn confirmed_frame(&self) -> u32 {
+        self.confirmed.iter().copied().min().unwrap_or(u32::MAX)
+    }
+
+    /// The world tick frame `frame` is simulated at.
+    pub fn tick_of(&self, frame: u32) -> Tick {
+        Tick::new(self.start.value() + 1 + frame)
+    }
+
+    /// The inputs simulated frame `frame` used last, confirmed or predicted.
+    pub fn inputs_used(&self, frame: u32) -> Option<&[I]> {
+        self.used.get(frame as usize).map(Vec::as_slice)
+    }
+
+    /// Checksum after each simulated frame, see `set_checksum`. Entries below
+    /// `confirmed_frame()` are final.
+    pub fn checksums(&self) -> &[u64] {
+        &self.checksums
+    }
+
+    #[cfg(test)]
+    pub(crate) fn checksums_mut(&mut self) -> &mut [u64] {
+        &mut self.checksums
+    }
+
+    /// Number of times a misprediction forced a rollback.
+    pub fn rollbacks(&self) -> u32 {
+        self.rollbacks
+    }
+
+    /// Total number of frames simulated again after mispredictions.
+    pub fn resimulated_frames(&self) -> u32 {
+        self.resimulated_frames
+    }
+
+    /// Adds the confirmed input of `player` for `frame`. Adding the same input again is
+    /// fine. If `frame` was already simulated with a different prediction, the session
+    /// resimulates from it on the next `advance` or `sync`.
+    pub fn add_input(&mut self, player: usize, frame: u32, input: I) -> Result<(), SessionError> {
+        let inputs = self
+            .inputs
+            .get_mut(player)
+            .ok_or(SessionError::UnknownPlayer(player))?;
+        let index = frame as usize;
+        if inputs.len() <= index {
+            inputs.resize(index + 1, None);
+        }
+        if let Some(existing) = &inputs[index] {
+            if *existing != input {
+                return Err(SessionError::ConflictingInput { player, frame });
+            }
+            return Ok(());
+        }
+
+        if self
+            .used
+            .get(index)
+            .is_some_and(|used| used[player] != input)
+        {
+            self.mispredicted = Some(self.mispredicted.map_or(frame, |f| f.min(frame)));
+        }
+        inputs[index] = Some(input);
+
+        let confirmed = &mut self.confirmed[player];
+        while inputs.get(*confirmed as usize).is_some_and(Option::is_some) {
+            *confirmed += 1;
+        }
+        Ok(())
+    }
+
+    /// Whether `advance` would simulate a frame rather than hit the prediction limit.
+    pub fn can_advance(&self) -> bool {
+        self.frame() < self.confirmed_frame().saturating_add(self.max_prediction)
+    }
+
+    /// Resimulates pending mispredictions, then simulates the next frame and returns it.
+    pub fn advance(&mut self) -> Result<u32, SessionError> {
+        self.sync();
+        if !self.can_advance() {
+            return Err(SessionError::PredictionLimit);
+        }
+
+        let frame = self.frame();
+        self.simulate(frame);
+        Ok(frame)
+    }
+
+    /// Rolls back to the earliest mispredicted frame and resimulates up to the present.
+    /// Returns the number of frames simulated again, 0 if every prediction held.
+    pub fn sync(&mut self) -> u32 {
+        let Some(frame) = self.mispredicted.take() else {
+            return 0;
+        };
+
+        // Later frames may have used a stale prediction too; replaying from the first wrong
+        // frame re-predicts all of them
+        self.rollbacks += 1;
+        self.world.resimulate_from(self.tick_of(frame));
+        let end = self.frame();
+        for f in frame..end {
+            self.simulate(f);
+        }
+        self.resimulated_frames += end - frame;
+        end - frame
+    }
+
+    /// The input of `player` for `frame`: the confirmed one, or the last confirmed one
+    /// repeated.
+    fn predict(&self, player: usize, frame: u32) -> I {
+        let inputs = &self.inputs[player];
+        if let Some(Some(input)) = inputs.get(frame as usize) {
+            return input.clone();
+        }
+
+        self.confirmed[player]
+            .checked_sub(1)
+            .and_then(|last| inputs[last as usize].clone())
+            .unwrap_or_default()
+    }
+
+    fn simulate(&mut self, frame: u32) {
+        let inputs: Vec<I> = (0..self.players())
+            .map(|player| self.predict(player, frame))
+            .collect();
+        self.world.edit_scope(|w| (self.apply)(w, &inputs));
+        self.world.run();
+
+        let index = frame as usize;
+        if index < self.used.len() {
+            self.used[index] = inputs;
+        } else {
+            self.used.push(inputs);
+        }
+        if let Some(checksum) = self.checksum {
+            let checksum = checksum(&self.world);
+            match self.checksums.get_mut(index) {
+                Some(slot) => *slot = checksum,
+                None => self.checksums.push(checksum),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+#[path = "session.tests.rs"]
+mod tests;
@@ -0,0 +1,146 @@
+use super::*;
+use crate::entity::Entity;
+use crate::prelude::*;
+
+#[derive(Component, Default, Clone, Debug, PartialEq, Hash)]
+struct Position {
+    x: i32,
+}
+
+#[derive(Component, Default, Clone, Debug, PartialEq, Hash)]
+struct Control {
+    dx: i32,
+}
+
+system! {
+    MoveSystem {
+        query! {
+            fn step(pos: &mut ViewMut<Position>, control: View<Control>) {
+                pos.x += control.dx;
+            }
+        }
+    }
+}
+
+fn players(world: &World) -> Vec<Entity> {
+    world.iter_entities().collect()
+}
+
+fn session(players_count: usize) -> RollbackSession<i32> {
+    let mut world = World::new();
+    world.add_system::<MoveSystem>();
+    world.build_scheduler();
+    for _ in 0..players_count {
+        let e = world.spawn();
+        world.set(e, &Position { x: 0 });
+        world.set(e, &Control { dx: 0 });
+    }
+
+    RollbackSession::new(world, players_count, |world, inputs: &[i32]| {
+        for (player, &dx) in players(world).into_iter().zip(inputs) {
+            world.set(player, &Control { dx });
+        }
+    })
+}
+
+fn positions(session: &RollbackSession<i32>) -> Vec<i32> {
+    let world = session.world();
+    players(world)
+        .into_iter()
+        .map(|e| world.get::<Position>(e).unwrap().x)
+        .collect()
+}
+
+#[test]
+fn test_predicts_and_corrects_late_inputs() {
+    let mut session = session(3);
+    assert_eq!(session.tick_of(0), Tick::new(1));
+
+    // Player 0 is local, 1 and 2 lag behind
+    session.add_input(1, 0, 5).unwrap();
+    for frame in 0..4 {
+        session.add_input(0, frame, 1).unwrap();
+        assert_eq!(session.advance(), Ok(frame));
+    }
+    // Player 1 repeats its last confirmed input, player 2 has none yet
+    assert_eq!(session.inputs_used(3), Some(&[1, 5, 0][..]));
+    assert_eq!(positions(&session), vec![4, 20, 0]);
+    assert_eq!(session.confirmed_frame(), 0);
+
+    // Player 1's predictions held, player 2 diverges at frame 1
+    for frame in 1..4 {
+        session.add_input(1, frame, 5).unwrap();
+    }
+    session.add_input(2, 0, 0).unwrap();
+    session.add_input(2, 1, -2).unwrap();
+    assert_eq!(session.rollbacks(), 0);
+    assert_eq!(session.sync(), 3);
+    assert_eq!(positions(&session), vec![4, 20, -6]);
+    assert_eq!(session.inputs_used(3), Some(&[1, 5, -2][..]));
+    assert_eq!(session.confirmed_frame(), 2);
+
+    // Correct predictions and repeated inputs don't resimulate
+    session.add_input(2, 2, -2).unwrap();
+    session.add_input(2, 1, -2).unwrap();
+    assert_eq!(session.sync(), 0);
+    assert_eq!((session.rollbacks(), session.resimulated_frames()), (1, 3));
+}
+
+#[test]
+fn test_matches_a_session_with_every_input_on_time() {
+    let script = |player: usize, frame: u32| ((frame / 2 + player as u32) % 3) as i32 - 1;
+    let mut late = session(2);
+    let mut on_time = session(2);
+    late.set_checksum(crate::netsim::world_checksum);
+    on_time.set_checksum(crate::netsim::world_checksum);
+
+    for frame in 0..20 {
+        for player in 0..2 {
+            on_time
+                .add_input(player, frame, script(player, frame))
+                .unwrap();
+        }
+        on_time.advance().unwrap();
+
+        // Player 1's inputs arrive three frames late
+        late.add_input(0, frame, script(0, frame)).unwrap();
+        if frame >= 3 {
+            late.add_input(1, frame - 3, script(1, frame - 3)).unwrap();
+        }
+        late.advance().unwrap();
+    }
+    for frame in 17..20 {
+        late.add_input(1, frame, script(1, frame)).unwrap();
+    }
+    late.sync();
+
+    assert!(late.rollbacks() > 0);
+    assert_eq!(late.checksums(), on_time.checksums());
+    assert_eq!(positions(&late), positions(&on_time));
+}
+
+#[test]
+fn test_rejects_bad_inputs_and_stops_at_prediction_limit() {
+    let mut session = session(2);
+    session.set_max_prediction(2);
+    assert_eq!(
+        session.add_input(2, 0, 1),
+        Err(SessionError::UnknownPlayer(2))
+    );
+    session.add_input(0, 0, 1).unwrap();
+    assert_eq!(
+        session.add_input(0, 0, 2),
+        Err(SessionError::ConflictingInput {
+            player: 0,
+            frame: 0
+        })
+    );
+
+    assert!(session.advance().is_ok());
+    assert!(session.advance().is_ok());
+    assert!(!session.can_advance());
+    assert_eq!(session.advance(), Err(SessionError::PredictionLimit));
+
+    session.add_input(1, 0, 0).unwrap();
+    assert_eq!(session.advance(), Ok(2));
+}
@@ -0,0 +1,513 @@
+//! Hash map storage backend for ultra-sparse components.
+//!
+//! The block tree allocates a 128-slot inner block (plus its parents) for the first
+//! component in a region, which is wasteful for a component only one or two entities ever
+//! have, such as a single `GameRules` entity. `SparseStorage` keeps values in a hash map
+//! and only the masks of occupied blocks, so memory is proportional to the number of
+//! components. Lookups hash, so prefer the default block storage for anything dense.
+//!
+//! Select it per component:
+//! ```ignore
+//! #[derive(Component, Clone, Default)]
+//! #[component(storage = "sparse")]
+//! struct GameRules { round_time: u32 }
+//! ```
+//!
+//! It implements `ComponentStorage` with the same index split and rollback semantics as
+//! `Storage`, so systems, cleanup and rollback work unchanged. The wire format only
+//! supports the block storage; `SparseStorage::verify_invariants` stands in for
+//! `safety::verify_storage_invariants`.
+
+use crate::component::{records_history, Component};
+use crate::storage::{ChangeTicks, ComponentStorage, KeptChanges, MemoryStats, RemovedLog};
+use crate::tick::Tick;
+use std::collections::HashMap;
+
+/// Sparse storage for component `T`, see the module docs.
+pub struct SparseStorage<T> {
+    values: HashMap<u32, T>,
+    /// Middle blocks (`ri`) holding at least one component.
+    root: u128,
+    root_changed: u128,
+    /// Occupied inner blocks per middle block, keyed by `ri`.
+    middles: HashMap<u32, u128>,
+    middles_changed: HashMap<u32, u128>,
+    /// Occupied slots per inner block, keyed by `ri * 128 + mi`.
+    inners: HashMap<u32, u128>,
+    inners_changed: HashMap<u32, u128>,
+    /// Added subsets of the changed masks.
+    root_added: u128,
+    middles_added: HashMap<u32, u128>,
+    inners_added: HashMap<u32, u128>,
+    removed: RemovedLog<T>,
+    change_ticks: ChangeTicks,
+    /// Changes kept across `clear_changes`, `None` unless kept, see `World::keep_changes`.
+    kept: Option<Box<KeptChanges>>,
+    /// Undo log: `(tick, index, previous value)` for the first change to a slot per tick,
+    /// oldest first.
+    history: Vec<(Tick, u32, Option<T>)>,
+    current_tick: Tick,
+}
+
+fn split(index: u32) -> (u32, u32, u32) {
+    (index >> 14, (index >> 7) & 0x7F, index & 0x7F)
+}
+
+impl<T: Component> SparseStorage<T> {
+    fn occupy(&mut self, index: u32) {
+        let (ri, mi, ii) = split(index);
+        *self.inners.entry(ri * 128 + mi).or_default() |= 1 << ii;
+        *self.middles.entry(ri).or_default() |= 1 << mi;
+        self.root |= 1 << ri;
+    }
+
+    fn vacate(&mut self, index: u32) {
+        let (ri, mi, ii) = split(index);
+
+        let Some(inner) = self.inners.get_mut(&(ri * 128 + mi)) else {
+            return;
+        };
+        *inner &= !(1 << ii);
+        if *inner != 0 {
+            return;
+        }
+        self.inners.remove(&(ri * 128 + mi));
+
+        if let Some(middle) = self.middles.get_mut(&ri) {
+            *middle &= !(1 << mi);
+            if *middle == 0 {
+                self.middles.remove(&ri);
+                self.root &= !(1 << ri);
+            }
+        }
+    }
+
+    /// Marks `index` changed, logging its previous value the first time in a tick.
+    fn touch(&mut self, index: u32) {
+        let (ri, mi, ii) = split(index);
+        let changed = self.inners_changed.entry(ri * 128 + mi).or_default();
+
+        if (*changed >> ii) & 1 != 0 {
+            return;
+        }
+
+        *changed |= 1 << ii;
+        *self.middles_changed.entry(ri).or_default() |= 1 << mi;
+        self.root_changed |= 1 << ri;
+
+        if records_history::<T>() {
+            let previous = self.values.get(&index).cloned();
+            self.history.push((self.current_tick, index, previous));
+        }
+    }
+
+    fn mark_added(&mut self, index: u32) {
+        let (ri, mi, ii) = split(index);
+        *self.inners_added.entry(ri * 128 + mi).or_default() |= 1 << ii;
+        *self.middles_added.entry(ri).or_default() |= 1 << mi;
+        self.root_added |= 1 << ri;
+    }
+
+    fn unmark_added(&mut self, index: u32) {
+        let (ri, mi, ii) = split(index);
+        if let Some(added) = self.inners_added.get_mut(&(ri * 128 + mi)) {
+            *added &= !(1 << ii);
+        }
+    }
+
+    /// Iterates over `(index, &value)` pairs in ascending index order.
+    pub fn iter(&self) -> impl Iterator<Item = (u32, &T)> {
+        let mut entries: Vec<_> = self.values.iter().map(|(&i, v)| (i, v)).collect();
+        entries.sort_unstable_by_key(|(i, _)| *i);
+        entries.into_iter()
+    }
+
+    /// Verifies that the occupancy masks match the stored values, like
+    /// `safety::verify_storage_invariants` does for the block storage.
+    ///
+    /// Returns `Err` with a description of the first violation found.
+    pub fn verify_invariants(&self) -> Result<(), String> {
+        for &index in self.values.keys() {
+            let (ri, mi, ii) = split(index);
+            if (self.inner_mask(ri, mi) >> ii) & 1 == 0 {
+                return Err(format!("Index {}: value without inner mask bit", index));
+            }
+        }
+
+        let mut slots = 0;
+        for (&key, &inner) in &self.inners {
+            let (ri, mi) = (key / 128, key % 128);
+            if inner == 0 {
+                return Err(format!("Inner[{}, {}]: empty mask is kept", ri, mi));
+            }
+            if (self.middle_mask(ri) >> mi) & 1 == 0 {
+                return Err(format!("Inner[{}, {}]: not in the middle mask", ri, mi));
+            }
+            slots += inner.count_ones() as usize;
+        }
+        if slots != self.values.len() {
+            return Err(format!(
+                "Inner masks hold {} slots but {} values are stored",
+                slots,
+                self.values.len()
+            ));
+        }
+
+        for (&ri, &middle) in &self.middles {
+            if middle == 0 {
+                return Err(format!("Middle[{}]: empty mask is kept", ri));
+            }
+            if (self.root >> ri) & 1 == 0 {
+                return Err(format!("Middle[{}]: not in the root mask", ri));
+            }
+            if middle.count_ones() as usize
+                != (0..128).filter(|&mi| self.inner_mask(ri, mi) != 0).count()
+            {
+                return Err(format!("Middle[{}]: mask disagrees with its inner masks", ri));
+            }
+        }
+        if self.root.count_ones() as usize != self.middles.len() {
+            return Err("Root: mask disagrees with the middle masks".to_string());
+        }
+
+        Ok(())
+    }
+}
+
+impl<T: Component> Default for SparseStorage<T> {
+    fn default() -> Self {
+        <Self as ComponentStorage>::new()
+    }
+}
+
+impl<T: Component> ComponentStorage for SparseStorage<T> {
+    type Item = T;
+
+    fn new() -> Self {
+        SparseStorage {
+            values: HashMap::new(),
+            root: 0,
+            root_changed: 0,
+            middles: HashMap::new(),
+            middles_changed: HashMap::new(),
+            inners: HashMap::new(),
+            inners_changed: HashMap::new(),
+            root_added: 0,
+            middles_added: HashMap::new(),
+            inners_added: HashMap::new(),
+            removed: RemovedLog::new(),
+            change_ticks: ChangeTicks::new(),
+            kept: None,
+            history: Vec::new(),
+            // Matches `Storage::new`
+            current_tick: Tick::new(1),
+        }
+    }
+
+    fn set_tick(&mut self, tick: Tick) {
+        self.current_tick = tick;
+    }
+
+    fn tick(&self) -> Tick {
+        self.current_tick
+    }
+
+    fn get(&self, index: u32) -> Option<&T> {
+        self.values.get(&index)
+    }
+
+    fn get_mut(&mut self, index: u32) -> &mut T {
+        if !self.values.contains_key(&index) {
+            panic!("Index out of bounds: {}", index);
+        }
+
+        self.touch(index);
+        self.values
+            .get_mut(&index)
+            .expect("Component checked above")
+    }
+
+    fn set(&mut self, index: u32, value: &T) {
+        if index >> 14 >= 128 {
+            panic!("Index out of bounds: {}", index);
+        }
+
+        self.touch(index);
+        if self.values.insert(index, value.clone()).is_none() {
+            self.mark_added(index);
+            self.removed.restore(self.current_tick, index);
+        }
+        self.occupy(index);
+    }
+
+    fn remove(&mut self, index: u32) {
+        if !self.values.contains_key(&index) {
+            return;
+        }
+
+        self.touch(index);
+        let value = self.values.remove(&index).expect("Component checked above");
+        self.vacate(index);
+        self.unmark_added(index);
+        if !T::IS_TEMPORARY {
+            self.removed.record(self.current_tick, index, value);
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    fn rollback(&mut self, target_tick: Tick) {
+        if self
+            .history
+            .last()
+            .is_some_and(|(tick, _, _)| tick.is_after(target_tick))
+            && target_tick.is_after(self.current_tick)
+        {
+            panic!(
+                "Cannot rollback to future tick: current tick is {}, target tick is {}",
+                self.current_tick.value(),
+                target_tick.value()
+            );
+        }
+
+        while let Some((tick, _, _)) = self.history.last() {
+            if !tick.is_after(target_tick) {
+                break;
+            }
+
+            let (_, index, previous) = self.history.pop().expect("History checked above");
+            match previous {
+                Some(value) => {
+                    self.values.insert(index, value);
+                    self.occupy(index);
+                }
+                None => {
+                    self.values.remove(&index);
+                    self.vacate(index);
+                }
+            }
+        }
+
+        self.removed.rollback(target_tick);
+        self.clear_changes();
+        self.current_tick = target_tick;
+    }
+
+    fn prune_history(&mut self, oldest: Tick) {
+        let stale = self
+            .history
+            .partition_point(|(tick, _, _)| !tick.is_after(oldest));
+        self.history.drain(..stale);
+        self.removed.prune(oldest);
+    }
+
+    fn shrink(&mut self) -> usize {
+        self.values.shrink_to_fit();
+        self.middles.shrink_to_fit();
+        self.inners.shrink_to_fit();
+        self.history.shrink_to_fit();
+        0
+    }
+
+    fn clear(&mut self) {
+        // Keeping changes is configuration rather than state
+        let keep = self.kept.is_some();
+        *self = Self::new();
+        self.set_keep_changes(keep);
+    }
+
+    fn clear_changes(&mut self) {
+        for (&key, &mask) in &self.inners_changed {
+            self.change_ticks.record(key / 128, key % 128, mask, self.current_tick);
+            if let Some(kept) = &mut self.kept {
+                kept.record(key / 128, key % 128, mask);
+            }
+        }
+        self.root_changed = 0;
+        self.middles_changed.clear();
+        self.inners_changed.clear();
+        self.root_added = 0;
+        self.middles_added.clear();
+        self.inners_added.clear();
+    }
+
+    fn slot_ptr(&mut self, index: u32) -> *mut T {
+        match self.values.get_mut(&index) {
+            Some(value) => value,
+            None => panic!("No component at index {}", index),
+        }
+    }
+
+    fn discard(&mut self, ri: u32, mi: u32, mask: u128) {
+        let mut mask = mask & self.inner_mask(ri, mi);
+
+        while mask != 0 {
+            let ii = mask.trailing_zeros();
+            let index = ri * 16384 + mi * 128 + ii;
+            let value = self.values.remove(&index).expect("Slot is in the inner mask");
+            self.vacate(index);
+            self.unmark_added(index);
+            if !T::IS_TEMPORARY {
+                self.removed.record(self.current_tick, index, value);
+            }
+            mask &= !(1u128 << ii);
+        }
+    }
+
+    fn visit<'a>(&'a self, mut f: impl FnMut(u32, &'a T)) {
+        for (index, value) in self.iter() {
+            f(index, value);
+        }
+    }
+
+    fn root_mask(&self) -> u128 {
+        self.root
+    }
+
+    fn root_full_mask(&self) -> u128 {
+        // A sparse storage is never expected to fill a 16K-entity block
+        0
+    }
+
+    fn root_changed_mask(&self) -> u128 {
+        let kept = self.kept.as_ref().map_or(0, |kept| kept.root_mask());
+        self.root_changed | (kept & self.root)
+    }
+
+    fn root_added_mask(&self) -> u128 {
+        self.root_added
+    }
+
+    fn root_removed_mask(&self) -> u128 {
+        self.removed.root_mask(self.current_tick)
+    }
+
+    fn middle_mask(&self, ri: u32) -> u128 {
+        self.middles.get(&ri).copied().unwrap_or(0)
+    }
+
+    fn middle_full_mask(&self, ri: u32) -> u128 {
+        let mut full = 0;
+        let mut middle = self.middle_mask(ri);
+
+        while middle != 0 {
+            let mi = middle.trailing_zeros();
+            if self.inner_mask(ri, mi) == u128::MAX {
+                full |= 1 << mi;
+            }
+            middle &= !(1u128 << mi);
+        }
+
+        full
+    }
+
+    fn middle_changed_mask(&self, ri: u32) -> u128 {
+        let kept = self.kept.as_ref().map_or(0, |kept| kept.middle_mask(ri));
+        self.middles_changed.get(&ri).copied().unwrap_or(0) | (kept & self.middle_mask(ri))
+    }
+
+    fn middle_added_mask(&self, ri: u32) -> u128 {
+        self.middles_added.get(&ri).copied().unwrap_or(0)
+    }
+
+    fn middle_removed_mask(&self, ri: u32) -> u128 {
+        self.removed.middle_mask(self.current_tick, ri)
+    }
+
+    fn inner_mask(&self, ri: u32, mi: u32) -> u128 {
+        self.inners.get(&(ri * 128 + mi)).copied().unwrap_or(0)
+    }
+
+    fn inner_changed_mask(&self, ri: u32, mi: u32) -> u128 {
+        let kept = self.kept.as_ref().map_or(0, |kept| kept.inner_mask(ri, mi));
+        self.inner_pending_mask(ri, mi) | (kept & self.inner_mask(ri, mi))
+    }
+
+    fn inner_pending_mask(&self, ri: u32, mi: u32) -> u128 {
+        self.inners_changed
+            .get(&(ri * 128 + mi))
+            .copied()
+            .unwrap_or(0)
+    }
+
+    fn inner_added_mask(&self, ri: u32, mi: u32) -> u128 {
+        self.inners_added
+            .get(&(ri * 128 + mi))
+            .copied()
+            .unwrap_or(0)
+    }
+
+    fn inner_removed_mask(&self, ri: u32, mi: u32) -> u128 {
+        self.removed.inner_mask(self.current_tick, ri, mi)
+    }
+
+    fn removed_value(&self, index: u32) -> Option<&T> {
+        self.removed.value(self.current_tick, index)
+    }
+
+    fn root_changed_since(&self, since: Tick) -> u128 {
+        let pending = if self.current_tick.is_after(since) { self.root_changed } else { 0 };
+        self.change_ticks.root_mask(since) | pending
+    }
+
+    fn middle_changed_since(&self, ri: u32, since: Tick) -> u128 {
+        let pending = if self.current_tick.is_after(since) {
+            self.middles_changed.get(&ri).copied().unwrap_or(0)
+        } else {
+            0
+        };
+        self.change_ticks.middle_mask(ri, since) | pending
+    }
+
+    fn inner_changed_since(&self, ri: u32, mi: u32, since: Tick) -> u128 {
+        let pending = if self.current_tick.is_after(since) {
+            self.inner_pending_mask(ri, mi)
+        } else {
+            0
+        };
+        self.change_ticks.inner_mask(ri, mi, since) | pending
+    }
+
+    fn changed_tick(&self, index: u32) -> Option<Tick> {
+        let (ri, mi, ii) = split(index);
+        if (self.inner_pending_mask(ri, mi) >> ii) & 1 != 0 {
+            return Some(self.current_tick);
+        }
+        self.change_ticks.tick_of(index)
+    }
+
+    fn set_keep_changes(&mut self, keep: bool) {
+        if keep != self.kept.is_some() {
+            self.kept = keep.then(Box::default);
+        }
+    }
+
+    fn clear_kept_changes(&mut self) {
+        if let Some(kept) = &mut self.kept {
+            **kept = KeptChanges::default();
+        }
+    }
+
+    fn memory_stats(&self) -> MemoryStats {
+        let masks = self.middles.capacity()
+            + self.middles_changed.capacity()
+            + self.inners.capacity()
+            + self.inners_changed.capacity()
+            + self.middles_added.capacity()
+            + self.inners_added.capacity();
+
+        MemoryStats {
+            middle_blocks: 0,
+            inner_blocks: 0,
+            bytes: std::mem::size_of::<Self>()
+                + self.values.capacity() * std::mem::size_of::<(u32, T)>()
+                + masks * std::mem::size_of::<(u32, u128)>(),
+        }
+    }
+}
+
+#[cfg(test)]
+#[path = "sparse.tests.rs"]
+mod tests;
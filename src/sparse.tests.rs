@@ -0,0 +1,179 @@
+use super::*;
+use crate::component::Component;
+use crate::entity::Entity;
+use crate::prelude::system;
+use crate::world::World;
+
+#[derive(Component, Default, Clone, Debug, PartialEq)]
+#[component(storage = "sparse")]
+struct GameRules {
+    round: u32,
+}
+
+#[derive(Component, Default, Clone, Debug, PartialEq)]
+struct Health {
+    value: u32,
+}
+
+system! {
+    AdvanceRoundSystem {
+        query! {
+            fn advance(rules: &mut ViewMut<GameRules>) {
+                rules.round += 1;
+            }
+        }
+    }
+}
+
+system! {
+    HealUnruledSystem {
+        query! {
+            fn heal(health: &mut ViewMut<Health>) None=[GameRules] {
+                health.value += 1;
+            }
+        }
+    }
+}
+
+fn rules(world: &mut World, entity: Entity) -> Option<u32> {
+    let storage = world.get_storage::<GameRules>();
+    unsafe { (*storage.get()).get(entity.index()).map(|r| r.round) }
+}
+
+#[test]
+fn test_masks_track_occupied_slots() {
+    let mut storage = SparseStorage::<GameRules>::new();
+    let far = 3 * 16384 + 5 * 128 + 7;
+
+    storage.set(far, &GameRules { round: 1 });
+    storage.set(2, &GameRules { round: 2 });
+
+    assert_eq!(storage.len(), 2);
+    assert_eq!(storage.root_mask(), (1 << 3) | 1);
+    assert_eq!(storage.middle_mask(3), 1 << 5);
+    assert_eq!(storage.inner_mask(3, 5), 1 << 7);
+    assert_eq!(storage.inner_changed_mask(0, 0), 1 << 2);
+
+    storage.remove(far);
+    assert_eq!(storage.root_mask(), 1);
+    assert_eq!(storage.middle_mask(3), 0);
+    assert_eq!(storage.get(far), None);
+    // Removal still counts as a change
+    assert_eq!(storage.inner_changed_mask(3, 5), 1 << 7);
+
+    storage.discard(0, 0, 1 << 2);
+    assert!(storage.is_empty());
+    assert_eq!(storage.root_mask(), 0);
+}
+
+#[test]
+fn test_rollback_restores_values() {
+    let mut storage = SparseStorage::<GameRules>::new();
+    storage.set_tick(Tick::new(1));
+    storage.set(10, &GameRules { round: 1 });
+    storage.clear_changes();
+
+    storage.set_tick(Tick::new(2));
+    storage.get_mut(10).round = 2;
+    storage.set(11, &GameRules { round: 5 });
+    storage.clear_changes();
+
+    storage.set_tick(Tick::new(3));
+    storage.remove(10);
+
+    storage.rollback(Tick::new(1));
+    assert_eq!(storage.get(10), Some(&GameRules { round: 1 }));
+    assert_eq!(storage.get(11), None);
+    assert_eq!(storage.inner_mask(0, 0), 1 << 10);
+}
+
+#[test]
+fn test_verify_invariants() {
+    let mut storage = SparseStorage::<GameRules>::new();
+    for (tick, index) in [(1, 3), (2, 200), (3, 70_000), (4, 201)] {
+        storage.set_tick(Tick::new(tick));
+        storage.set(index, &GameRules { round: tick });
+        storage.clear_changes();
+    }
+    storage.remove(200);
+    assert_eq!(storage.verify_invariants(), Ok(()));
+
+    storage.rollback(Tick::new(2));
+    assert_eq!(storage.verify_invariants(), Ok(()));
+
+    storage.values.insert(5_000, GameRules::default());
+    assert!(storage.verify_invariants().is_err());
+}
+
+#[test]
+fn test_prune_history_keeps_newer_ticks() {
+    let mut storage = SparseStorage::<GameRules>::new();
+    for tick in 1..=4 {
+        storage.set_tick(Tick::new(tick));
+        storage.set(10, &GameRules { round: tick });
+        storage.clear_changes();
+    }
+
+    storage.prune_history(Tick::new(2));
+    assert_eq!(storage.history.len(), 2);
+    storage.rollback(Tick::new(2));
+    assert_eq!(storage.get(10), Some(&GameRules { round: 2 }));
+}
+
+#[test]
+fn test_sparse_component_in_systems() {
+    let mut world = World::new();
+    world.add_system::<AdvanceRoundSystem>();
+    world.add_system::<HealUnruledSystem>();
+
+    let ruled = world.spawn();
+    let unruled = world.spawn();
+    world.set(ruled, &GameRules { round: 0 });
+    world.set(ruled, &Health { value: 0 });
+    world.set(unruled, &Health { value: 0 });
+    world.build_scheduler();
+
+    world.run();
+    world.run();
+
+    assert_eq!(rules(&mut world, ruled), Some(2));
+    let health = world.get_storage::<Health>();
+    unsafe {
+        assert_eq!((*health.get()).get(ruled.index()).unwrap().value, 0);
+        assert_eq!((*health.get()).get(unruled.index()).unwrap().value, 2);
+    }
+}
+
+#[test]
+fn test_sparse_component_rolls_back_with_world() {
+    let mut world = World::new();
+    world.add_system::<AdvanceRoundSystem>();
+    let e = world.spawn();
+    world.set(e, &GameRules { round: 0 });
+    world.build_scheduler();
+
+    world.run(); // tick 0
+    world.run(); // tick 1
+    world.run(); // tick 2
+    assert_eq!(rules(&mut world, e), Some(3));
+
+    world.rollback(Tick::new(1));
+    assert_eq!(rules(&mut world, e), Some(2));
+
+    world.run(); // tick 1 (replay)
+    world.run(); // tick 2 (replay)
+    assert_eq!(rules(&mut world, e), Some(4));
+}
+
+#[test]
+fn test_destroyed_entity_loses_sparse_component() {
+    let mut world = World::new();
+    let e = world.spawn();
+    world.set(e, &GameRules { round: 1 });
+    world.build_scheduler();
+
+    world.destroy(e);
+    world.run();
+
+    assert_eq!(rules(&mut world, e), None);
+}
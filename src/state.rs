@@ -0,0 +1,157 @@
+//! Game states such as menu, gameplay or paused, deciding which systems run.
+//!
+//! `World::insert_state(GameState::Menu)` stores a `State<GameState>` resource. Systems
+//! and pipeline groups name the states they run in with scheduling clauses:
+//!
+//! - `InState = GameState::Playing` runs every tick the state is `Playing`;
+//! - `OnEnter = GameState::Playing` runs only in the tick the state became `Playing`;
+//! - `OnExit = GameState::Menu` runs only in the tick the state stopped being `Menu`.
+//!
+//! A transition is requested with `World::set_state`, or from a system with a
+//! `ResMut<State<GameState>>` parameter and `State::set`, and takes effect at the start of
+//! the next tick, before any system runs, so every system of a tick sees the same state.
+//! The initial state counts as entered in the tick it was inserted before.
+//!
+//! The state is a resource, so its transitions are recorded per tick and `World::rollback`
+//! restores the state active at the target tick along with the rest of the world. A
+//! resimulated tick applies the same pending transition and runs the same systems.
+//!
+//! # Example
+//! ```ignore
+//! #[derive(Clone, PartialEq, Debug)]
+//! enum GameState { Menu, Playing }
+//!
+//! #[pipeline_group(InState = GameState::Playing)]
+//! struct GameplayGroup;
+//!
+//! system! {
+//!     ResetScoreSystem {
+//!         query! {
+//!             fn reset_score(score: &mut ViewMut<Score>) OnEnter = GameState::Playing {
+//!                 score.0 = 0;
+//!             }
+//!         }
+//!     }
+//! }
+//!
+//! world.insert_state(GameState::Menu);
+//! world.set_state(GameState::Playing); // GameplayGroup runs from the next tick on
+//! ```
+
+use crate::resource::ResourceCell;
+use crate::tick::Tick;
+use crate::world::World;
+use std::any::Any;
+use std::rc::Rc;
+
+/// The active state of type `S` and the last transition, see the module docs.
+#[derive(Clone, Debug)]
+pub struct State<S> {
+    current: S,
+    previous: Option<S>,
+    entered: Tick,
+    next: Option<S>,
+}
+
+impl<S: Clone + PartialEq + 'static> State<S> {
+    pub(crate) fn new(initial: S, tick: Tick) -> Self {
+        State {
+            current: initial,
+            previous: None,
+            entered: tick,
+            next: None,
+        }
+    }
+
+    /// The active state.
+    pub fn get(&self) -> &S {
+        &self.current
+    }
+
+    /// The state active before the last transition, `None` before the first one.
+    pub fn previous(&self) -> Option<&S> {
+        self.previous.as_ref()
+    }
+
+    /// The tick the active state was entered in.
+    pub fn entered(&self) -> Tick {
+        self.entered
+    }
+
+    /// The transition waiting for the next tick, if any.
+    pub fn next(&self) -> Option<&S> {
+        self.next.as_ref()
+    }
+
+    /// Requests a transition to `next` at the start of the next tick, replacing any other
+    /// pending request. A transition to the active state does nothing.
+    pub fn set(&mut self, next: S) {
+        self.next = Some(next);
+    }
+
+    fn apply(&mut self, tick: Tick) {
+        let Some(next) = self.next.take() else {
+            return;
+        };
+        if next != self.current {
+            self.previous = Some(std::mem::replace(&mut self.current, next));
+            self.entered = tick;
+        }
+    }
+}
+
+/// Whether the state of type `S` is `state`. Backs the `InState` clause.
+pub fn in_state<S: Clone + PartialEq + 'static>(world: &World, state: &S) -> bool {
+    world
+        .resource::<State<S>>()
+        .is_some_and(|active| active.get() == state)
+}
+
+/// Whether the state of type `S` became `state` in the current tick. Backs the `OnEnter`
+/// clause.
+pub fn on_enter<S: Clone + PartialEq + 'static>(world: &World, state: &S) -> bool {
+    world.resource::<State<S>>().is_some_and(|active| {
+        active.entered() == world.current_tick() && active.get() == state
+    })
+}
+
+/// Whether the state of type `S` stopped being `state` in the current tick. Backs the
+/// `OnExit` clause.
+pub fn on_exit<S: Clone + PartialEq + 'static>(world: &World, state: &S) -> bool {
+    world.resource::<State<S>>().is_some_and(|active| {
+        active.entered() == world.current_tick() && active.previous() == Some(state)
+    })
+}
+
+/// Type-erased access to states so the world can apply their transitions.
+pub trait StateLike: Any {
+    /// Applies the pending transition, logged for rollback at `tick`.
+    fn begin_tick(&self, tick: Tick);
+}
+
+/// Applies the transitions of the `State<S>` resource.
+pub(crate) struct StateDriver<S> {
+    cell: Rc<ResourceCell<State<S>>>,
+}
+
+impl<S> StateDriver<S> {
+    pub(crate) fn new(cell: Rc<ResourceCell<State<S>>>) -> Self {
+        StateDriver { cell }
+    }
+}
+
+impl<S: Clone + PartialEq + 'static> StateLike for StateDriver<S> {
+    fn begin_tick(&self, tick: Tick) {
+        // Only a pending transition is logged, idle ticks leave the undo log alone
+        if !self.cell.get().is_some_and(|state| state.next.is_some()) {
+            return;
+        }
+        if let Some(state) = self.cell.slot_mut(tick).as_mut() {
+            state.apply(tick);
+        }
+    }
+}
+
+#[cfg(test)]
+#[path = "state.tests.rs"]
+mod tests;
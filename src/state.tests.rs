@@ -0,0 +1,138 @@
+use super::*;
+use crate::prelude::*;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum GameState {
+    Menu,
+    Playing,
+}
+
+#[derive(Clone, Default, Debug, PartialEq)]
+struct Log(Vec<&'static str>);
+
+#[rollback_macros::pipeline_group(InState = GameState::Playing)]
+struct GameplayGroup;
+
+system! {
+    PlaySystem {
+        query! {
+            fn play(log: ResMut<Log>) Parent = GameplayGroup {
+                log.0.push("play");
+            }
+        }
+    }
+}
+
+system! {
+    MenuSystem {
+        query! {
+            fn menu(log: ResMut<Log>) InState = GameState::Menu {
+                log.0.push("menu");
+            }
+        }
+    }
+}
+
+system! {
+    EnterPlayingSystem {
+        query! {
+            fn enter_playing(log: ResMut<Log>) OnEnter = GameState::Playing {
+                log.0.push("enter playing");
+            }
+        }
+    }
+}
+
+system! {
+    ExitMenuSystem {
+        query! {
+            fn exit_menu(log: ResMut<Log>) OnExit = GameState::Menu {
+                log.0.push("exit menu");
+            }
+        }
+    }
+}
+
+fn world() -> World {
+    let mut world = World::new();
+    world.add_system::<ExitMenuSystem>();
+    world.add_system::<EnterPlayingSystem>();
+    world.add_system::<PlaySystem>();
+    world.add_system::<MenuSystem>();
+    world.build_scheduler();
+    world.insert_resource(Log::default());
+    world.insert_state(GameState::Menu);
+    world
+}
+
+fn take_log(world: &mut World) -> Vec<&'static str> {
+    std::mem::take(&mut world.resource_mut::<Log>().unwrap().0)
+}
+
+#[test]
+fn test_transitions_apply_at_the_next_tick() {
+    let mut world = world();
+    world.run();
+    assert_eq!(take_log(&mut world), vec!["menu"]);
+
+    world.set_state(GameState::Playing);
+    assert_eq!(world.state::<GameState>(), Some(&GameState::Menu));
+    world.run();
+    assert_eq!(world.state::<GameState>(), Some(&GameState::Playing));
+    assert_eq!(take_log(&mut world), vec!["exit menu", "enter playing", "play"]);
+
+    // Enter and exit systems only run in the tick of the transition
+    world.run();
+    assert_eq!(take_log(&mut world), vec!["play"]);
+
+    // A transition to the active state does nothing
+    world.set_state(GameState::Playing);
+    world.run();
+    assert_eq!(take_log(&mut world), vec!["play"]);
+}
+
+system! {
+    PauseSystem {
+        query! {
+            fn pause(state: ResMut<State<GameState>>) InState = GameState::Playing {
+                state.set(GameState::Menu);
+            }
+        }
+    }
+}
+
+#[test]
+fn test_rollback_restores_the_active_state() {
+    let mut world = World::new();
+    world.add_system::<PauseSystem>();
+    world.build_scheduler();
+    world.insert_state(GameState::Menu);
+    world.run();
+
+    world.set_state(GameState::Playing);
+    let playing = world.current_tick();
+    world.run();
+    // The system's request takes effect one tick later
+    assert_eq!(world.state::<GameState>(), Some(&GameState::Playing));
+    world.run();
+    assert_eq!(world.state::<GameState>(), Some(&GameState::Menu));
+    let state = world.resource::<State<GameState>>().unwrap();
+    assert_eq!(state.previous(), Some(&GameState::Playing));
+    assert_eq!(state.entered(), Tick::new(playing.value() + 1));
+
+    world.rollback(playing);
+    assert_eq!(world.state::<GameState>(), Some(&GameState::Playing));
+    assert_eq!(
+        world.resource::<State<GameState>>().unwrap().next(),
+        Some(&GameState::Menu)
+    );
+    world.run();
+    assert_eq!(world.state::<GameState>(), Some(&GameState::Menu));
+}
+
+#[test]
+#[should_panic(expected = "call World::insert_state first")]
+fn test_set_state_without_state_panics() {
+    let mut world = World::new();
+    world.set_state(GameState::Playing);
+}
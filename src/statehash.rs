@@ -0,0 +1,260 @@
+//! Per-tick world state hashes for spotting desyncs.
+//!
+//! Peers in rollback netcode exchange a hash of their state for every confirmed tick; the
+//! first tick where the hashes differ is a desync. `World::record_state_hashes(true)`
+//! makes the world hash its state after every simulated tick, and `World::state_hash(tick)`
+//! returns the hash recorded for `tick` while it is inside the rollback window. A rollback
+//! drops the hashes of the ticks it undoes, so resimulated ticks record fresh ones.
+//!
+//! Every storage contributes which entities have the component and, for component types
+//! implementing `Hash`, their values. `#[derive(Component)]` picks that up on its own
+//! through `Hashable`, which every hashable component implements; nothing has to be
+//! registered. Each non-empty storage gets a sub-hash keyed by `Component::stable_name`, so
+//! `StateHash::diff` names the storages that diverged; empty storages are left out, so a
+//! storage one peer merely created doesn't read as a desync. Dirty bridge markers are left
+//! out too, as in `World::hash_tree`.
+//!
+//! Hashes are platform-independent: `StateHasher` is 128-bit FNV-1a over little-endian
+//! integers, so peers on different platforms agree on the same state. Peers should run the
+//! same build, though: the `Hash` impls values go through, and `std::any::type_name` for
+//! components implemented by hand without a `stable_name`, may change between compiler
+//! versions.
+//!
+//! # Example
+//! ```ignore
+//! world.record_state_hashes(true);
+//! world.run();
+//!
+//! let ours = world.state_hash(tick).unwrap();
+//! if ours.digest() != theirs.digest() {
+//!     println!("desync at {:?} in {:?}", tick, ours.diff(&theirs));
+//! }
+//! ```
+
+use crate::component::Component;
+use crate::tick::Tick;
+use std::hash::{Hash, Hasher};
+use std::marker::PhantomData;
+
+const FNV_OFFSET: u128 = 0x6c62272e07bb014262b821756295c58d;
+const FNV_PRIME: u128 = 0x0000000001000000000000000000013b;
+
+/// Platform-independent 128-bit FNV-1a hasher. Integers are written little-endian and
+/// `usize` as 64 bits, so the same values hash the same everywhere.
+#[derive(Clone)]
+pub struct StateHasher {
+    state: u128,
+}
+
+impl StateHasher {
+    pub fn new() -> Self {
+        StateHasher { state: FNV_OFFSET }
+    }
+
+    /// The full 128-bit hash.
+    pub fn finish128(&self) -> u128 {
+        self.state
+    }
+}
+
+impl Default for StateHasher {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Hasher for StateHasher {
+    fn finish(&self) -> u64 {
+        fold(self.state)
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.state ^= byte as u128;
+            self.state = self.state.wrapping_mul(FNV_PRIME);
+        }
+    }
+
+    fn write_u16(&mut self, i: u16) {
+        self.write(&i.to_le_bytes());
+    }
+
+    fn write_u32(&mut self, i: u32) {
+        self.write(&i.to_le_bytes());
+    }
+
+    fn write_u64(&mut self, i: u64) {
+        self.write(&i.to_le_bytes());
+    }
+
+    fn write_u128(&mut self, i: u128) {
+        self.write(&i.to_le_bytes());
+    }
+
+    fn write_usize(&mut self, i: usize) {
+        self.write_u64(i as u64);
+    }
+
+    fn write_i16(&mut self, i: i16) {
+        self.write_u16(i as u16);
+    }
+
+    fn write_i32(&mut self, i: i32) {
+        self.write_u32(i as u32);
+    }
+
+    fn write_i64(&mut self, i: i64) {
+        self.write_u64(i as u64);
+    }
+
+    fn write_i128(&mut self, i: i128) {
+        self.write_u128(i as u128);
+    }
+
+    fn write_isize(&mut self, i: isize) {
+        self.write_u64(i as u64);
+    }
+}
+
+fn fold(hash: u128) -> u64 {
+    (hash as u64) ^ ((hash >> 64) as u64)
+}
+
+/// A component whose values are part of the world state hash. Implemented for every
+/// component type implementing `Hash`.
+pub trait Hashable: Component {
+    fn hash_state(&self, state: &mut StateHasher);
+}
+
+impl<T: Component + Hash> Hashable for T {
+    fn hash_state(&self, state: &mut StateHasher) {
+        self.hash(state);
+    }
+}
+
+/// Lets `#[derive(Component)]` find out whether the type is `Hashable` or
+/// `SerializableComponent`, see `Component::state_hasher` and `Component::wire_codec`.
+#[doc(hidden)]
+pub struct Probe<T>(PhantomData<T>);
+
+impl<T> Probe<T> {
+    pub fn new() -> Self {
+        Probe(PhantomData)
+    }
+}
+
+impl<T> Default for Probe<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Picked by method resolution when `T: Hashable`.
+#[doc(hidden)]
+pub trait ViaHashable<T> {
+    fn state_hasher(&self) -> Option<fn(&T, &mut StateHasher)>;
+}
+
+impl<T: Hashable> ViaHashable<T> for Probe<T> {
+    fn state_hasher(&self) -> Option<fn(&T, &mut StateHasher)> {
+        Some(T::hash_state)
+    }
+}
+
+/// The fallback for types without `Hash`, one autoref further away.
+#[doc(hidden)]
+pub trait ViaOpaque<T> {
+    fn state_hasher(&self) -> Option<fn(&T, &mut StateHasher)>;
+}
+
+impl<T> ViaOpaque<T> for &Probe<T> {
+    fn state_hasher(&self) -> Option<fn(&T, &mut StateHasher)> {
+        None
+    }
+}
+
+/// Hash of the world state at the end of one tick.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StateHash {
+    /// The tick that was simulated.
+    pub tick: Tick,
+    digest: u128,
+    /// Sub-hash per non-empty storage, sorted by `Component::stable_name`.
+    components: Vec<(&'static str, u128)>,
+}
+
+impl StateHash {
+    /// Combines the sub-hashes, given in any order.
+    pub(crate) fn new(tick: Tick, mut components: Vec<(&'static str, u128)>) -> Self {
+        components.sort_unstable();
+
+        let mut state = StateHasher::new();
+        tick.value().hash(&mut state);
+        for (name, hash) in &components {
+            name.hash(&mut state);
+            hash.hash(&mut state);
+        }
+
+        StateHash {
+            tick,
+            digest: state.finish128(),
+            components,
+        }
+    }
+
+    /// The 128-bit digest of the whole state.
+    pub fn digest(&self) -> u128 {
+        self.digest
+    }
+
+    /// The digest folded to 64 bits, for packets with little room.
+    pub fn digest64(&self) -> u64 {
+        fold(self.digest)
+    }
+
+    /// Sub-hashes of every non-empty storage, sorted by `Component::stable_name`.
+    pub fn components(&self) -> &[(&'static str, u128)] {
+        &self.components
+    }
+
+    /// Names of the storages whose sub-hashes differ from `other`'s, including storages
+    /// only one side has.
+    pub fn diff(&self, other: &StateHash) -> Vec<&'static str> {
+        let mut names = Vec::new();
+        let (mut ours, mut theirs) = (self.components.iter(), other.components.iter());
+        let (mut a, mut b) = (ours.next(), theirs.next());
+
+        loop {
+            match (a, b) {
+                (Some(x), Some(y)) if x.0 == y.0 => {
+                    if x.1 != y.1 {
+                        names.push(x.0);
+                    }
+                    a = ours.next();
+                    b = theirs.next();
+                }
+                (Some(x), Some(y)) if x.0 < y.0 => {
+                    names.push(x.0);
+                    a = ours.next();
+                }
+                (Some(_), Some(y)) => {
+                    names.push(y.0);
+                    b = theirs.next();
+                }
+                (Some(x), None) => {
+                    names.push(x.0);
+                    a = ours.next();
+                }
+                (None, Some(y)) => {
+                    names.push(y.0);
+                    b = theirs.next();
+                }
+                (None, None) => return names,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+#[path = "statehash.tests.rs"]
+mod tests;
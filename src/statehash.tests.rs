@@ -0,0 +1,152 @@
+use super::*;
+use crate::prelude::*;
+
+#[derive(Component, Default, Clone, Debug, PartialEq, Hash)]
+struct Health {
+    hp: i32,
+}
+
+#[derive(Component, Default, Clone, Debug, PartialEq)]
+struct Velocity {
+    x: f32,
+}
+
+system! {
+    RegenSystem {
+        query! {
+            fn regen(health: &mut ViewMut<Health>) {
+                health.hp += 1;
+            }
+        }
+    }
+}
+
+fn world() -> (World, Entity) {
+    let mut world = World::new();
+    world.get_storage::<Health>();
+    world.get_storage::<Velocity>();
+    world.add_system::<RegenSystem>();
+    world.build_scheduler();
+    world.record_state_hashes(true);
+
+    let e = world.spawn();
+    world.set(e, &Health { hp: 10 });
+    world.set(e, &Velocity { x: 1.0 });
+    (world, e)
+}
+
+fn name_of<T: Component>() -> &'static str {
+    T::stable_name()
+}
+
+#[test]
+fn test_hasher_is_little_endian_fnv() {
+    assert_eq!(StateHasher::new().finish128(), FNV_OFFSET);
+
+    let mut a = StateHasher::new();
+    a.write_u32(0x0403_0201);
+    let mut b = StateHasher::new();
+    b.write(&[1, 2, 3, 4]);
+    assert_eq!(a.finish128(), b.finish128());
+
+    let mut c = StateHasher::new();
+    c.write_usize(7);
+    let mut d = StateHasher::new();
+    d.write_u64(7);
+    assert_eq!(c.finish(), d.finish());
+}
+
+#[test]
+fn test_derive_detects_hash() {
+    assert!(Health::state_hasher().is_some());
+    assert!(Velocity::state_hasher().is_none());
+}
+
+#[test]
+fn test_diff_names_diverged_storage() {
+    let (mut a, ea) = world();
+    let (mut b, eb) = world();
+    a.run();
+    b.run();
+    assert_eq!(a.compute_state_hash(), b.compute_state_hash());
+
+    // Values of non-hashable components don't count, their presence does
+    b.set(eb, &Velocity { x: 2.0 });
+    assert_eq!(a.compute_state_hash(), b.compute_state_hash());
+
+    a.set(ea, &Health { hp: 0 });
+    let (ours, theirs) = (a.compute_state_hash(), b.compute_state_hash());
+    assert_ne!(ours.digest(), theirs.digest());
+    assert_eq!(ours.diff(&theirs), vec![name_of::<Health>()]);
+
+    a.storage_mut::<Velocity>().remove(ea.index());
+    let ours = a.compute_state_hash();
+    let mut diverged = ours.diff(&b.compute_state_hash());
+    diverged.sort_unstable();
+    let mut expected = vec![name_of::<Health>(), name_of::<Velocity>()];
+    expected.sort_unstable();
+    assert_eq!(diverged, expected);
+}
+
+#[test]
+fn test_stable_name_is_module_path_and_type() {
+    assert_eq!(name_of::<Health>(), "rollback_ecs::statehash::tests::Health");
+    assert_eq!(name_of::<Entity>(), "rollback_ecs::entity::Entity");
+}
+
+#[test]
+fn test_empty_storages_are_left_out() {
+    #[derive(Component, Default, Clone, Hash)]
+    struct Unused;
+
+    let (mut a, _) = world();
+    let (b, _) = world();
+    a.get_storage::<Unused>();
+    assert_eq!(a.compute_state_hash(), b.compute_state_hash());
+
+    let e = a.spawn();
+    a.set(e, &Unused);
+    a.storage_mut::<Unused>().remove(e.index());
+    a.storage_mut::<Entity>().remove(e.index());
+    assert_eq!(a.compute_state_hash(), b.compute_state_hash());
+}
+
+#[test]
+fn test_recorded_per_tick_and_rolled_back() {
+    let (mut world, _) = world();
+    let start = world.current_tick();
+    world.run();
+    world.run();
+    world.run();
+
+    let second = world
+        .state_hash(Tick::new(start.value() + 1))
+        .cloned()
+        .unwrap();
+    let third = world
+        .state_hash(Tick::new(start.value() + 2))
+        .cloned()
+        .unwrap();
+    assert_ne!(second.digest(), third.digest());
+    assert_eq!(world.compute_state_hash(), third);
+
+    world.resimulate_from(second.tick);
+    assert!(world.state_hash(start).is_some());
+    assert!(world.state_hash(second.tick).is_none());
+
+    // Resimulation records the same hashes again
+    world.run();
+    assert_eq!(world.state_hash(second.tick), Some(&second));
+
+    // After a rollback the tick runs again under the same number, replacing its hash
+    let first = world.state_hash(start).cloned().unwrap();
+    world.rollback(start);
+    world.run();
+    let rerun = world.state_hash(start).unwrap();
+    assert_ne!(rerun, &first);
+    assert_eq!(rerun.components(), second.components());
+    assert!(world.state_hash(second.tick).is_none());
+
+    world.record_state_hashes(false);
+    assert!(world.state_hash(start).is_none());
+}
@@ -2,35 +2,921 @@ use std::mem::MaybeUninit;
 
 use crate::block::Block;
 use crate::block::RollbackBlock;
-use crate::component::Component;
-use crate::tick::Tick;
+use crate::component::{records_history, Component};
+use crate::rollback::{DeltaCodec, DeltaCompressible};
+use crate::tick::{Tick, TickDelta};
 use crate::world::World;
+use std::collections::{BTreeMap, HashMap};
+
+/// A backend holding every component of one type, selected per component with
+/// `#[component(storage = "dense")]` (the default, `Storage`) or `"sparse"`
+/// (`SparseStorage`).
+///
+/// Every backend addresses entities the same way - `index = ri * 16384 + mi * 128 + ii` -
+/// and exposes masks for each level of that split. Queries intersect these masks to find
+/// matching entities, so systems work with any backend.
+///
+/// Mask contract, mirroring the block tree:
+/// - `root_mask` / `middle_mask` may report blocks with no components left, but must cover
+///   every occupied slot. `inner_mask` is exactly the occupied slots.
+/// - `*_full_mask` may under-report; it only lets `None=[...]` filters skip whole blocks.
+/// - `*_changed_mask` covers slots set, mutated or removed since the last `clear_changes`.
+/// - `*_added_mask` covers slots that were empty and have been set since the last
+///   `clear_changes`, a subset of the changed slots. Removing the component again clears
+///   the slot's bit; the block bits above may over-report.
+/// - `*_removed_mask` covers slots whose component was removed in the tick before the
+///   current one (`set_tick`), and is rolled back with the storage.
+/// - `*_changed_since(since)` covers the changed slots plus the slots whose change was
+///   cleared in a tick after `since`, see `ChangeTicks`. Block bits may over-report.
+pub trait ComponentStorage: Sized + 'static {
+    type Item: Component;
+
+    fn new() -> Self;
+
+    /// Sets the tick that changes are recorded under for rollback.
+    fn set_tick(&mut self, tick: Tick);
+
+    /// The tick changes are recorded under, see `set_tick`.
+    fn tick(&self) -> Tick;
+
+    fn get(&self, index: u32) -> Option<&Self::Item>;
+
+    /// Mutable access that marks the slot changed.
+    ///
+    /// # Panics
+    /// Panics if there is no component at `index`.
+    fn get_mut(&mut self, index: u32) -> &mut Self::Item;
+
+    fn set(&mut self, index: u32, value: &Self::Item);
+
+    fn remove(&mut self, index: u32);
+
+    fn len(&self) -> usize;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Reverts every change recorded after `target_tick`.
+    fn rollback(&mut self, target_tick: Tick);
+
+    /// Clears the changed masks at every level.
+    fn clear_changes(&mut self);
+
+    /// Drops the history only a rollback before `oldest` would need.
+    fn prune_history(&mut self, oldest: Tick);
+
+    /// Drops every component along with the whole rollback history, as if the storage
+    /// were new. Used by `World::clear_entities`.
+    fn clear(&mut self) {
+        *self = Self::new();
+    }
+
+    /// Drops the components in `mask` of inner block `(ri, mi)` without recording them for
+    /// rollback. Used for `Remove=[...]` queries and destroyed-entity cleanup.
+    fn discard(&mut self, ri: u32, mi: u32, mask: u128);
+
+    /// Frees memory held for components that are gone, see `Storage::shrink`. Returns
+    /// the number of blocks freed, zero for backends without blocks.
+    fn shrink(&mut self) -> usize {
+        0
+    }
+
+    /// Calls `f` for every component in ascending index order.
+    fn visit<'a>(&'a self, f: impl FnMut(u32, &'a Self::Item));
+
+    /// Marks the components in `mask` of inner block `(ri, mi)` changed and records them
+    /// for rollback, as `get_mut` would one by one.
+    fn mark_changed(&mut self, ri: u32, mi: u32, mask: u128) {
+        let mut mask = mask & self.inner_mask(ri, mi);
+        while mask != 0 {
+            let ii = mask.trailing_zeros();
+            self.get_mut(ri * 16384 + mi * 128 + ii);
+            mask &= !(1u128 << ii);
+        }
+    }
+
+    /// Marks the component at `index` changed, recording `previous` for rollback as its
+    /// value before the write, as `get_mut` would have before the write. `Parallel = true`
+    /// systems record the writes of their tasks this way once all of them finished.
+    ///
+    /// # Panics
+    /// Panics if there is no component at `index`.
+    fn record_write(&mut self, index: u32, previous: Self::Item) {
+        // Put the old value back for `get_mut` to record, then write the new one again
+        let current = unsafe { std::ptr::replace(self.slot_ptr(index), previous) };
+        *self.get_mut(index) = current;
+    }
+
+    /// Pointer to the component at `index`, without marking it changed. Only for writes
+    /// recorded with `mark_changed` or `record_write`.
+    ///
+    /// # Panics
+    /// Panics if there is no component at `index`.
+    fn slot_ptr(&mut self, index: u32) -> *mut Self::Item;
+
+    /// Middle blocks that may hold components.
+    fn root_mask(&self) -> u128;
+    /// Middle blocks whose every slot is occupied.
+    fn root_full_mask(&self) -> u128;
+    /// Middle blocks with changes.
+    fn root_changed_mask(&self) -> u128;
+    /// Middle blocks with added components.
+    fn root_added_mask(&self) -> u128;
+    /// Middle blocks with components removed in the previous tick.
+    fn root_removed_mask(&self) -> u128;
+
+    /// Inner blocks of middle block `ri` that may hold components.
+    fn middle_mask(&self, ri: u32) -> u128;
+    /// Inner blocks of middle block `ri` whose every slot is occupied.
+    fn middle_full_mask(&self, ri: u32) -> u128;
+    /// Inner blocks of middle block `ri` with changes.
+    fn middle_changed_mask(&self, ri: u32) -> u128;
+    /// Inner blocks of middle block `ri` with added components.
+    fn middle_added_mask(&self, ri: u32) -> u128;
+    /// Inner blocks of middle block `ri` with components removed in the previous tick.
+    fn middle_removed_mask(&self, ri: u32) -> u128;
+
+    /// Occupied slots of inner block `(ri, mi)`.
+    fn inner_mask(&self, ri: u32, mi: u32) -> u128;
+    /// Changed slots of inner block `(ri, mi)`.
+    fn inner_changed_mask(&self, ri: u32, mi: u32) -> u128;
+    /// Slots of inner block `(ri, mi)` whose component was added.
+    fn inner_added_mask(&self, ri: u32, mi: u32) -> u128;
+    /// Slots of inner block `(ri, mi)` whose component was removed in the previous tick.
+    fn inner_removed_mask(&self, ri: u32, mi: u32) -> u128;
+
+    /// The value removed from `index` in the previous tick, if any.
+    fn removed_value(&self, index: u32) -> Option<&Self::Item>;
+
+    /// Middle blocks with components changed in a tick after `since`, the current one
+    /// included. See `ChangedSince = tick`.
+    fn root_changed_since(&self, since: Tick) -> u128;
+    /// Inner blocks of middle block `ri` with components changed after `since`.
+    fn middle_changed_since(&self, ri: u32, since: Tick) -> u128;
+    /// Slots of inner block `(ri, mi)` whose component changed after `since`.
+    fn inner_changed_since(&self, ri: u32, mi: u32, since: Tick) -> u128;
+    /// The tick the slot at `index` last changed in, if it ever did.
+    fn changed_tick(&self, index: u32) -> Option<Tick>;
+
+    /// Keeps the changed masks across `clear_changes` until `clear_kept_changes`, or with
+    /// `false` stops keeping them, see `World::keep_changes`.
+    fn set_keep_changes(&mut self, keep: bool);
+    /// Forgets the changes kept across `clear_changes`.
+    fn clear_kept_changes(&mut self);
+    /// Slots of inner block `(ri, mi)` changed since the last `clear_changes`, leaving out
+    /// the kept ones `inner_changed_mask` also reports.
+    fn inner_pending_mask(&self, ri: u32, mi: u32) -> u128;
+
+    /// Allocates and pre-touches everything needed to hold components at indices up to
+    /// `max_index`, so later `set`s don't allocate. Backends without preallocation ignore it.
+    fn warmup(&mut self, _max_index: u32) {}
+
+    /// Memory currently held by the component data structures.
+    fn memory_stats(&self) -> MemoryStats;
+}
+
+/// Memory held by one component storage, excluding rollback history.
+#[derive(Clone, Copy, Default, Debug, PartialEq, Eq)]
+pub struct MemoryStats {
+    /// Allocated middle blocks (16384 slots each). Zero for backends without blocks.
+    pub middle_blocks: usize,
+    /// Allocated inner blocks (128 slots each). Zero for backends without blocks.
+    pub inner_blocks: usize,
+    /// Approximate heap and inline bytes of the data structures.
+    pub bytes: usize,
+}
+
+/// Memory held by the rollback history of one `Storage`, see `Storage::history_stats`.
+#[derive(Clone, Copy, Default, Debug, PartialEq, Eq)]
+pub struct HistoryStats {
+    /// Inner block snapshots cloned as whole blocks.
+    pub blocks: usize,
+    /// Inner block snapshots kept as per-entity records, see `SnapshotGranularity`.
+    pub records: usize,
+    /// Previous values stored as byte deltas, see `Storage::enable_delta_snapshots`.
+    pub deltas: usize,
+    /// Approximate heap and inline bytes of every snapshot.
+    pub bytes: usize,
+}
+
+/// How a storage records the previous values of an inner block for rollback.
+#[derive(Clone, Copy, Default, Debug, PartialEq, Eq)]
+pub enum SnapshotGranularity {
+    /// Every changed inner block gets a 128-slot snapshot block. Cheapest to write and
+    /// restore when most slots of a block change each tick.
+    #[default]
+    Block,
+    /// Inner blocks with fewer than `threshold` changed slots in a tick keep a short list
+    /// of per-entity records; the `threshold`th change promotes the list to a block.
+    /// Saves memory when changes are sparse.
+    Adaptive { threshold: u32 },
+}
 
 pub struct Storage<T> {
     pub root: Block<Box<Block<Box<Block<T>>>>>,
     pub snapshot: Option<Box<RollbackStorage<T>>>,
     pub current_tick: Tick,
+    /// Inner blocks with a non-zero `changed_mask`, see `dirty_block_count`.
+    dirty_blocks: u32,
+    /// Inner blocks visited by `clear_changes`, for tests asserting that idle subtrees
+    /// are skipped.
+    #[cfg(test)]
+    pub(crate) cleared_blocks: u32,
+    /// Order of `set` calls within each tick, see `sequence_of`.
+    #[cfg(feature = "insert-sequence")]
+    sequences: InsertSequences,
+    /// Generations of freed entity slots. Only used by `Storage<Entity>`.
+    retired: RetiredGenerations,
+    removed: RemovedLog<T>,
+    change_ticks: ChangeTicks,
+    /// Changes kept across `clear_changes`, `None` unless kept, see `World::keep_changes`.
+    kept: Option<Box<KeptChanges>>,
+    granularity: SnapshotGranularity,
+    /// Set by `enable_delta_snapshots`.
+    delta: Option<DeltaCodec<T>>,
+}
+
+/// Position of a `set` among all `set`s of one storage: the tick it happened in and a
+/// per-tick counter. Orders by tick, then by counter.
+#[cfg(feature = "insert-sequence")]
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub struct InsertSeq {
+    pub tick: Tick,
+    pub seq: u32,
+}
+
+/// Sequence numbers of the latest `set` of every index, with older ones kept for rollback.
+#[cfg(feature = "insert-sequence")]
+struct InsertSequences {
+    tick: Tick,
+    next: u32,
+    /// Per index, newest last. At most one entry per tick.
+    history: std::collections::HashMap<u32, Vec<InsertSeq>>,
+}
+
+#[cfg(feature = "insert-sequence")]
+impl InsertSequences {
+    fn new() -> Self {
+        InsertSequences {
+            tick: Tick::new(0),
+            next: 0,
+            history: std::collections::HashMap::new(),
+        }
+    }
+
+    fn record(&mut self, index: u32, tick: Tick) {
+        if tick != self.tick {
+            self.tick = tick;
+            self.next = 0;
+        }
+        let entry = InsertSeq {
+            tick,
+            seq: self.next,
+        };
+        self.next += 1;
+
+        let stack = self.history.entry(index).or_default();
+        match stack.last_mut() {
+            Some(last) if last.tick == tick => *last = entry,
+            _ => stack.push(entry),
+        }
+    }
+
+    fn latest(&self, index: u32) -> Option<InsertSeq> {
+        self.history.get(&index)?.last().copied()
+    }
+
+    /// Forgets sequences recorded after `target_tick` and resumes counting after the
+    /// ones recorded at it.
+    fn rollback(&mut self, target_tick: Tick) {
+        let mut next = 0;
+        self.history.retain(|_, stack| {
+            while stack.last().is_some_and(|e| e.tick.is_after(target_tick)) {
+                stack.pop();
+            }
+            if let Some(last) = stack.last().filter(|e| e.tick == target_tick) {
+                next = next.max(last.seq + 1);
+            }
+            !stack.is_empty()
+        });
+        self.tick = target_tick;
+        self.next = next;
+    }
+
+    /// Forgets sequences superseded before `oldest`.
+    fn prune(&mut self, oldest: Tick) {
+        for stack in self.history.values_mut() {
+            let stale = stack.iter().take_while(|e| !e.tick.is_after(oldest)).count();
+            stack.drain(..stale.saturating_sub(1));
+        }
+    }
+}
+
+/// Last generation of every freed entity index, so a respawn in the slot continues
+/// counting instead of handing out a generation a stale handle still holds. Older entries
+/// are kept for rollback.
+struct RetiredGenerations {
+    /// Per index, newest last.
+    history: std::collections::HashMap<u32, Vec<(Tick, u32)>>,
+}
+
+impl RetiredGenerations {
+    fn new() -> Self {
+        RetiredGenerations {
+            history: std::collections::HashMap::new(),
+        }
+    }
+
+    fn retire(&mut self, tick: Tick, entity: Entity) {
+        self.history
+            .entry(entity.index())
+            .or_default()
+            .push((tick, entity.generation()));
+    }
+
+    /// The entity freed from `index` during `tick`, if any.
+    fn retired_at(&self, tick: Tick, index: u32) -> Option<Entity> {
+        let stack = self.history.get(&index)?;
+        let &(_, generation) = stack.iter().rev().find(|(retired, _)| *retired == tick)?;
+        Some(Entity::new(index, generation))
+    }
+
+    fn latest(&self, index: u32) -> u32 {
+        self.history
+            .get(&index)
+            .and_then(|stack| stack.last())
+            .map_or(0, |&(_, generation)| generation)
+    }
+
+    /// Forgets slots freed after `target_tick`.
+    fn rollback(&mut self, target_tick: Tick) {
+        self.history.retain(|_, stack| {
+            while stack.last().is_some_and(|(tick, _)| tick.is_after(target_tick)) {
+                stack.pop();
+            }
+            !stack.is_empty()
+        });
+    }
+
+    /// Forgets generations superseded before `oldest`; the latest one per slot stays.
+    fn prune(&mut self, oldest: Tick) {
+        for stack in self.history.values_mut() {
+            let stale = stack.iter().take_while(|(tick, _)| !tick.is_after(oldest)).count();
+            stack.drain(..stale.saturating_sub(1));
+        }
+    }
+}
+
+/// Components removed in one tick: their masks at every level and their last values.
+struct RemovedSet<T> {
+    root: u128,
+    middles: HashMap<u32, u128>,
+    inners: HashMap<u32, u128>,
+    values: HashMap<u32, T>,
+}
+
+/// Change marks a storage keeps across `clear_changes`, see `World::keep_changes`. Shared
+/// by both storage backends.
+#[derive(Default)]
+pub(crate) struct KeptChanges {
+    root: u128,
+    /// Inner blocks with kept changes, keyed by `ri`.
+    middles: HashMap<u32, u128>,
+    /// Kept slots, keyed by `ri * 128 + mi`.
+    inners: HashMap<u32, u128>,
+}
+
+impl KeptChanges {
+    pub(crate) fn record(&mut self, ri: u32, mi: u32, mask: u128) {
+        if mask == 0 {
+            return;
+        }
+        self.root |= 1 << ri;
+        *self.middles.entry(ri).or_default() |= 1 << mi;
+        *self.inners.entry(ri * 128 + mi).or_default() |= mask;
+    }
+
+    pub(crate) fn root_mask(&self) -> u128 {
+        self.root
+    }
+
+    pub(crate) fn middle_mask(&self, ri: u32) -> u128 {
+        self.middles.get(&ri).copied().unwrap_or(0)
+    }
+
+    pub(crate) fn inner_mask(&self, ri: u32, mi: u32) -> u128 {
+        self.inners.get(&(ri * 128 + mi)).copied().unwrap_or(0)
+    }
+}
+
+/// Tick of the last change of every slot, for `ChangedSince = tick` queries. Recorded when
+/// the change masks are cleared, so the changes of the tick being simulated (or made since
+/// the last tick) are still only in the masks. Newest ticks are kept per inner and middle
+/// block so queries skip blocks without newer changes. Shared by both storage backends.
+///
+/// A rollback leaves the recorded ticks alone: undone changes keep reporting the ticks
+/// they were made in until they are made again.
+pub(crate) struct ChangeTicks {
+    /// Newest change per middle block, keyed by `ri`.
+    middles: HashMap<u32, Tick>,
+    /// Per inner block, keyed by `ri * 128 + mi`.
+    inners: HashMap<u32, Box<BlockTicks>>,
+}
+
+struct BlockTicks {
+    newest: Tick,
+    /// Slots with a recorded tick.
+    recorded: u128,
+    ticks: [Tick; 128],
+}
+
+/// The later of two ticks.
+fn later(a: Tick, b: Tick) -> Tick {
+    if b.is_after(a) { b } else { a }
+}
+
+impl ChangeTicks {
+    pub(crate) fn new() -> Self {
+        ChangeTicks {
+            middles: HashMap::new(),
+            inners: HashMap::new(),
+        }
+    }
+
+    /// Records that the slots in `mask` of inner block `(ri, mi)` changed during `tick`.
+    pub(crate) fn record(&mut self, ri: u32, mi: u32, mask: u128, tick: Tick) {
+        if mask == 0 {
+            return;
+        }
+        let block = self.inners.entry(ri * 128 + mi).or_insert_with(|| {
+            Box::new(BlockTicks {
+                newest: tick,
+                recorded: 0,
+                ticks: [tick; 128],
+            })
+        });
+        let mut m = mask;
+        while m != 0 {
+            let ii = m.trailing_zeros();
+            m &= !(1u128 << ii);
+            block.ticks[ii as usize] = tick;
+        }
+        block.recorded |= mask;
+        block.newest = later(block.newest, tick);
+
+        let middle = self.middles.entry(ri).or_insert(tick);
+        *middle = later(*middle, tick);
+    }
+
+    /// Middle blocks with slots recorded after `since`.
+    pub(crate) fn root_mask(&self, since: Tick) -> u128 {
+        self.middles
+            .iter()
+            .filter(|(_, newest)| newest.is_after(since))
+            .fold(0, |mask, (&ri, _)| mask | 1 << ri)
+    }
+
+    /// Inner blocks of middle block `ri` with slots recorded after `since`.
+    pub(crate) fn middle_mask(&self, ri: u32, since: Tick) -> u128 {
+        if !self.middles.get(&ri).is_some_and(|newest| newest.is_after(since)) {
+            return 0;
+        }
+        let mut mask = 0;
+        for mi in 0..128 {
+            let block = self.inners.get(&(ri * 128 + mi));
+            if block.is_some_and(|block| block.newest.is_after(since)) {
+                mask |= 1 << mi;
+            }
+        }
+        mask
+    }
+
+    /// Slots of inner block `(ri, mi)` recorded after `since`.
+    pub(crate) fn inner_mask(&self, ri: u32, mi: u32, since: Tick) -> u128 {
+        let Some(block) = self.inners.get(&(ri * 128 + mi)) else {
+            return 0;
+        };
+        if !block.newest.is_after(since) {
+            return 0;
+        }
+        let (mut recorded, mut mask) = (block.recorded, 0);
+        while recorded != 0 {
+            let ii = recorded.trailing_zeros();
+            recorded &= !(1u128 << ii);
+            if block.ticks[ii as usize].is_after(since) {
+                mask |= 1 << ii;
+            }
+        }
+        mask
+    }
+
+    /// The tick `index` last changed in, if recorded.
+    pub(crate) fn tick_of(&self, index: u32) -> Option<Tick> {
+        let (key, ii) = (index >> 7, index & 0x7F);
+        let block = self.inners.get(&key)?;
+        ((block.recorded >> ii) & 1 != 0).then(|| block.ticks[ii as usize])
+    }
+}
+
+/// Components removed per tick, for `WasRemoved=[...]` queries. A tick sees the removals of
+/// the tick before it; removals between ticks are recorded under the tick that follows
+/// them, like any other write. The log is rolled back with the storage so resimulated ticks
+/// see the same removals as the first time. Shared by both storage backends.
+pub(crate) struct RemovedLog<T> {
+    ticks: BTreeMap<Tick, RemovedSet<T>>,
+}
+
+impl<T> RemovedLog<T> {
+    pub(crate) fn new() -> Self {
+        RemovedLog {
+            ticks: BTreeMap::new(),
+        }
+    }
+
+    /// Records the removal of `value` from `index` during `tick`.
+    pub(crate) fn record(&mut self, tick: Tick, index: u32, value: T) {
+        let (ri, mi, ii) = (index >> 14, (index >> 7) & 0x7F, index & 0x7F);
+        let set = self.ticks.entry(tick).or_insert_with(|| RemovedSet {
+            root: 0,
+            middles: HashMap::new(),
+            inners: HashMap::new(),
+            values: HashMap::new(),
+        });
+        set.root |= 1 << ri;
+        *set.middles.entry(ri).or_default() |= 1 << mi;
+        *set.inners.entry(ri * 128 + mi).or_default() |= 1 << ii;
+        set.values.insert(index, value);
+    }
+
+    /// Forgets a removal from `index` during `tick` once the component is set again in
+    /// the same tick. Block bits above the slot are left to over-report.
+    pub(crate) fn restore(&mut self, tick: Tick, index: u32) {
+        let Some(set) = self.ticks.get_mut(&tick) else {
+            return;
+        };
+        if set.values.remove(&index).is_some() {
+            let (ri, mi, ii) = (index >> 14, (index >> 7) & 0x7F, index & 0x7F);
+            if let Some(inner) = set.inners.get_mut(&(ri * 128 + mi)) {
+                *inner &= !(1 << ii);
+            }
+        }
+    }
+
+    /// Removals visible while simulating `current`: those of the tick before it.
+    fn visible(&self, current: Tick) -> Option<&RemovedSet<T>> {
+        self.ticks.get(&(current - TickDelta::new(1)))
+    }
+
+    pub(crate) fn root_mask(&self, current: Tick) -> u128 {
+        self.visible(current).map_or(0, |set| set.root)
+    }
+
+    pub(crate) fn middle_mask(&self, current: Tick, ri: u32) -> u128 {
+        self.visible(current)
+            .and_then(|set| set.middles.get(&ri).copied())
+            .unwrap_or(0)
+    }
+
+    pub(crate) fn inner_mask(&self, current: Tick, ri: u32, mi: u32) -> u128 {
+        self.visible(current)
+            .and_then(|set| set.inners.get(&(ri * 128 + mi)).copied())
+            .unwrap_or(0)
+    }
+
+    pub(crate) fn value(&self, current: Tick, index: u32) -> Option<&T> {
+        self.visible(current)?.values.get(&index)
+    }
+
+    /// Forgets removals made after `target_tick`.
+    pub(crate) fn rollback(&mut self, target_tick: Tick) {
+        self.ticks.retain(|tick, _| !tick.is_after(target_tick));
+    }
+
+    /// Forgets removals no tick from `oldest` on can see.
+    pub(crate) fn prune(&mut self, oldest: Tick) {
+        self.ticks.retain(|tick, _| !tick.is_before(oldest));
+    }
+}
+
+/// Per-entity rollback records of one inner block, values in ascending slot order.
+pub struct SparseSnapshot<T> {
+    pub updated_mask: u128,
+    pub added_mask: u128,
+    pub values: Vec<T>,
+}
+
+/// Per-entity rollback records of one inner block with delta snapshots enabled. A slot in
+/// `delta_mask` is stored as a delta against the value the next snapshot recorded for it;
+/// the other recorded slots keep full values, in ascending slot order.
+pub struct DeltaSnapshot<T> {
+    pub updated_mask: u128,
+    pub added_mask: u128,
+    pub delta_mask: u128,
+    pub values: Vec<T>,
+    pub deltas: Vec<Box<[u8]>>,
+    codec: DeltaCodec<T>,
+}
+
+impl<T> DeltaSnapshot<T> {
+    fn value_at(&self, ii: u32) -> usize {
+        (self.updated_mask & !self.delta_mask & ((1u128 << ii) - 1)).count_ones() as usize
+    }
+
+    fn delta_at(&self, ii: u32) -> usize {
+        (self.delta_mask & ((1u128 << ii) - 1)).count_ones() as usize
+    }
+
+    /// Replaces the full value of slot `ii` by its delta against `next`, the value the
+    /// following snapshot recorded.
+    fn compress(&mut self, ii: u32, next: &T) {
+        let bit = 1u128 << ii;
+        if self.updated_mask & !self.delta_mask & bit == 0 {
+            return;
+        }
+        let value = self.values.remove(self.value_at(ii));
+        if self.values.len() * 2 < self.values.capacity() {
+            self.values.shrink_to_fit();
+        }
+        let delta = (self.codec.encode)(&value, next);
+        self.deltas.insert(self.delta_at(ii), delta);
+        self.delta_mask |= bit;
+    }
+
+    /// Turns the delta of slot `ii` back into a full value, `next` being the value it was
+    /// taken against.
+    fn decompress(&mut self, ii: u32, next: &T) {
+        let bit = 1u128 << ii;
+        if self.delta_mask & bit == 0 {
+            return;
+        }
+        let delta = self.deltas.remove(self.delta_at(ii));
+        self.delta_mask &= !bit;
+        let value = (self.codec.apply)(next, &delta);
+        self.values.insert(self.value_at(ii), value);
+    }
+}
+
+/// The rollback record of one inner block in one tick, see `SnapshotGranularity`.
+pub enum InnerSnapshot<T> {
+    Sparse(Box<SparseSnapshot<T>>),
+    Block(Box<RollbackBlock<T>>),
+    Delta(Box<DeltaSnapshot<T>>),
+}
+
+impl<T: Clone> InnerSnapshot<T> {
+    fn new(granularity: SnapshotGranularity, delta: Option<DeltaCodec<T>>) -> Self {
+        if let Some(codec) = delta {
+            return InnerSnapshot::Delta(Box::new(DeltaSnapshot {
+                updated_mask: 0,
+                added_mask: 0,
+                delta_mask: 0,
+                values: Vec::new(),
+                deltas: Vec::new(),
+                codec,
+            }));
+        }
+        match granularity {
+            SnapshotGranularity::Block => InnerSnapshot::Block(Box::new(empty_rollback_block())),
+            SnapshotGranularity::Adaptive { .. } => {
+                InnerSnapshot::Sparse(Box::new(SparseSnapshot {
+                    updated_mask: 0,
+                    added_mask: 0,
+                    values: Vec::new(),
+                }))
+            }
+        }
+    }
+
+    /// Slots whose previous value is recorded.
+    pub fn updated_mask(&self) -> u128 {
+        match self {
+            InnerSnapshot::Sparse(sparse) => sparse.updated_mask,
+            InnerSnapshot::Block(block) => block.updated_mask,
+            InnerSnapshot::Delta(delta) => delta.updated_mask,
+        }
+    }
+
+    /// Slots that were empty before the tick.
+    pub fn added_mask(&self) -> u128 {
+        match self {
+            InnerSnapshot::Sparse(sparse) => sparse.added_mask,
+            InnerSnapshot::Block(block) => block.added_mask,
+            InnerSnapshot::Delta(delta) => delta.added_mask,
+        }
+    }
+
+    /// Recorded slots stored as deltas rather than values.
+    pub fn delta_mask(&self) -> u128 {
+        match self {
+            InnerSnapshot::Delta(delta) => delta.delta_mask,
+            _ => 0,
+        }
+    }
+
+    /// The previous value of slot `ii`, which must be in `updated_mask` and not in
+    /// `delta_mask`.
+    pub fn value(&self, ii: u32) -> &T {
+        debug_assert!((self.updated_mask() >> ii) & 1 != 0, "Slot {} has no recorded value", ii);
+        debug_assert!((self.delta_mask() >> ii) & 1 == 0, "Slot {} is stored as a delta", ii);
+        match self {
+            InnerSnapshot::Sparse(sparse) => {
+                &sparse.values[(sparse.updated_mask & ((1u128 << ii) - 1)).count_ones() as usize]
+            }
+            InnerSnapshot::Block(block) => unsafe { block.data[ii as usize].assume_init_ref() },
+            InnerSnapshot::Delta(delta) => &delta.values[delta.value_at(ii)],
+        }
+    }
+
+    /// The previous value of slot `ii` recorded by `snapshots[0]`, given the later
+    /// snapshots of the same inner block, oldest first. Deltas are applied back from the
+    /// first later full value of the slot.
+    fn recorded_value(snapshots: &[&InnerSnapshot<T>], ii: u32) -> T {
+        let bit = 1u128 << ii;
+        let chain: Vec<&InnerSnapshot<T>> = snapshots
+            .iter()
+            .copied()
+            .filter(|inner| inner.updated_mask() & bit != 0)
+            .collect();
+        let full = chain
+            .iter()
+            .position(|inner| inner.delta_mask() & bit == 0)
+            .expect("a delta chain ends in a full value");
+
+        let mut value = chain[full].value(ii).clone();
+        for inner in chain[..full].iter().rev() {
+            if let InnerSnapshot::Delta(delta) = inner {
+                value = (delta.codec.apply)(&value, &delta.deltas[delta.delta_at(ii)]);
+            }
+        }
+        value
+    }
+
+    fn mark_updated(&mut self, ii: u32, prev_value: &T, granularity: SnapshotGranularity) {
+        let bit = 1u128 << ii;
+        if let InnerSnapshot::Delta(delta) = self {
+            // Older deltas may be taken against this value, so the first record stays
+            if delta.updated_mask & bit == 0 {
+                delta.values.insert(delta.value_at(ii), prev_value.clone());
+                delta.updated_mask |= bit;
+            }
+            return;
+        }
+        if let InnerSnapshot::Sparse(sparse) = self {
+            let at = (sparse.updated_mask & (bit - 1)).count_ones() as usize;
+            if sparse.updated_mask & bit != 0 {
+                sparse.values[at] = prev_value.clone();
+                return;
+            }
+
+            let threshold = match granularity {
+                SnapshotGranularity::Adaptive { threshold } => threshold as usize,
+                SnapshotGranularity::Block => 0,
+            };
+            if sparse.values.len() + 1 < threshold {
+                sparse.values.insert(at, prev_value.clone());
+                sparse.updated_mask |= bit;
+                return;
+            }
+
+            // Dense enough now: move the records into a block
+            let mut block = Box::new(empty_rollback_block());
+            block.updated_mask = sparse.updated_mask;
+            block.added_mask = sparse.added_mask;
+            let mut mask = sparse.updated_mask;
+            for value in sparse.values.drain(..) {
+                let i = mask.trailing_zeros();
+                mask &= !(1u128 << i);
+                block.data[i as usize].write(value);
+            }
+            *self = InnerSnapshot::Block(block);
+        }
+
+        if let InnerSnapshot::Block(block) = self {
+            block.updated_mask |= bit;
+            block.data[ii as usize] = MaybeUninit::new(prev_value.clone());
+        }
+    }
+
+    fn mark_added(&mut self, ii: u32) {
+        match self {
+            InnerSnapshot::Sparse(sparse) => sparse.added_mask |= 1u128 << ii,
+            InnerSnapshot::Block(block) => block.added_mask |= 1u128 << ii,
+            InnerSnapshot::Delta(delta) => delta.added_mask |= 1u128 << ii,
+        }
+    }
+
+    fn bytes(&self) -> usize {
+        match self {
+            InnerSnapshot::Sparse(sparse) => {
+                std::mem::size_of::<SparseSnapshot<T>>()
+                    + sparse.values.capacity() * std::mem::size_of::<T>()
+            }
+            InnerSnapshot::Block(_) => std::mem::size_of::<RollbackBlock<T>>(),
+            InnerSnapshot::Delta(delta) => {
+                std::mem::size_of::<DeltaSnapshot<T>>()
+                    + delta.values.capacity() * std::mem::size_of::<T>()
+                    + delta.deltas.capacity() * std::mem::size_of::<Box<[u8]>>()
+                    + delta.deltas.iter().map(|bytes| bytes.len()).sum::<usize>()
+            }
+        }
+    }
+}
+
+/// Copies the slots in `mask` from `src` to `dst`, one `copy_nonoverlapping` per run of
+/// consecutive slots.
+///
+/// # Safety
+/// `U` must be `Copy`, so slots can be duplicated bytewise and overwritten without drops.
+unsafe fn copy_runs<U>(
+    mut mask: u128,
+    src: &[MaybeUninit<U>; 128],
+    dst: &mut [MaybeUninit<U>; 128],
+) {
+    while mask != 0 {
+        let start = mask.trailing_zeros();
+        let len = (mask >> start).trailing_ones();
+        unsafe {
+            std::ptr::copy_nonoverlapping(
+                src.as_ptr().add(start as usize),
+                dst.as_mut_ptr().add(start as usize),
+                len as usize,
+            );
+        }
+        // Clears the lowest run of ones
+        mask &= mask.wrapping_add(1u128 << start);
+    }
+}
+
+fn empty_rollback_block<U>() -> RollbackBlock<U> {
+    RollbackBlock {
+        updated_mask: 0,
+        added_mask: 0,
+        data: std::array::from_fn(|_| std::mem::MaybeUninit::uninit()),
+    }
 }
 
 pub struct RollbackStorage<T> {
-    pub root: RollbackBlock<Box<RollbackBlock<Box<RollbackBlock<T>>>>>,
+    pub root: RollbackBlock<Box<RollbackBlock<InnerSnapshot<T>>>>,
     pub tick: Tick,
     pub prev: Option<Box<RollbackStorage<T>>>,
+    granularity: SnapshotGranularity,
+    delta: Option<DeltaCodec<T>>,
 }
 
+/// How many older snapshots `RollbackStorage::mark_updated` searches for the previous
+/// record of a slot to store as a delta.
+const DELTA_SEARCH_TICKS: usize = 8;
+
 impl<T: Component> RollbackStorage<T> {
-    fn new(prev: Option<Box<Self>>, tick: Tick) -> Box<Self> {
+    fn new(
+        prev: Option<Box<Self>>,
+        tick: Tick,
+        granularity: SnapshotGranularity,
+        delta: Option<DeltaCodec<T>>,
+    ) -> Box<Self> {
         Box::new(RollbackStorage {
             tick,
-            root: RollbackBlock {
-                updated_mask: 0,
-                added_mask: 0,
-                data: std::array::from_fn(|_| std::mem::MaybeUninit::uninit()),
-            },
+            root: empty_rollback_block(),
             prev,
+            granularity,
+            delta,
         })
     }
 
+    /// The record of inner block `(ri, mi)` in this snapshot, if any.
+    fn inner_mut(&mut self, ri: u32, mi: u32) -> Option<&mut InnerSnapshot<T>> {
+        if (self.root.updated_mask >> ri) & 1 == 0 {
+            return None;
+        }
+        let middle = unsafe { self.root.data[ri as usize].assume_init_mut() };
+        if (middle.updated_mask >> mi) & 1 == 0 {
+            return None;
+        }
+        Some(unsafe { middle.data[mi as usize].assume_init_mut() })
+    }
+
+    /// Stores the previous record of slot `(ri, mi, ii)` in an older snapshot as a delta
+    /// against `next`, the value this snapshot just recorded.
+    fn compress_previous(&mut self, ri: u32, mi: u32, ii: u32, next: &T) {
+        let mut older = self.prev.as_deref_mut();
+        for _ in 0..DELTA_SEARCH_TICKS {
+            let Some(snapshot) = older else {
+                return;
+            };
+            let recorded = snapshot
+                .inner_mut(ri, mi)
+                .filter(|inner| (inner.updated_mask() | inner.added_mask()) & (1u128 << ii) != 0);
+            if let Some(inner) = recorded {
+                if let InnerSnapshot::Delta(delta) = inner {
+                    delta.compress(ii, next);
+                }
+                return;
+            }
+            older = snapshot.prev.as_deref_mut();
+        }
+    }
+
     pub fn mark_updated(&mut self, ri: u32, mi: u32, ii: u32, prev_value: &T) {
         debug_assert!(ri < 128, "ri index out of bounds: {}", ri);
         debug_assert!(mi < 128, "mi index out of bounds: {}", mi);
@@ -55,13 +941,7 @@ impl<T: Component> RollbackStorage<T> {
 
         // Ensure inner block exists
         if (middle.updated_mask >> mi) & 1 == 0 {
-            let new_inner = RollbackBlock {
-                updated_mask: 0,
-                added_mask: 0,
-                data: std::array::from_fn(|_| std::mem::MaybeUninit::uninit()),
-            };
-
-            middle.data[mi as usize].write(Box::new(new_inner));
+            middle.data[mi as usize].write(InnerSnapshot::new(self.granularity, self.delta));
             middle.updated_mask |= 1 << mi;
         }
 
@@ -72,9 +952,50 @@ impl<T: Component> RollbackStorage<T> {
         // Note: We overwrite if it exists, but for rollback log we usually only insert once per tick (checked by changed_mask)
 
         debug_assert!(ii < 128, "ii index out of bounds in inner block: {}", ii);
-        inner.updated_mask |= 1 << ii;
+        let recorded = (inner.updated_mask() >> ii) & 1 != 0;
+        inner.mark_updated(ii, prev_value, self.granularity);
+        if !recorded && self.delta.is_some() {
+            self.compress_previous(ri, mi, ii, prev_value);
+        }
+    }
 
-        inner.data[ii as usize] = MaybeUninit::new(prev_value.clone());
+    /// Records the previous values of the slots in `mask` of inner block `(ri, mi)`, as
+    /// `mark_updated` would one by one. `Copy` components recorded into a block snapshot
+    /// copy runs of slots at once.
+    pub fn mark_updated_mask(
+        &mut self,
+        ri: u32,
+        mi: u32,
+        mask: u128,
+        data: &[MaybeUninit<T>; 128],
+    ) {
+        let pod = T::is_pod() || Block::<T>::MASKS_ONLY;
+        if pod && self.delta.is_none() && self.granularity == SnapshotGranularity::Block {
+            if (self.root.updated_mask >> ri) & 1 == 0 {
+                self.root.data[ri as usize].write(Box::new(empty_rollback_block()));
+                self.root.updated_mask |= 1 << ri;
+            }
+            let middle = unsafe { self.root.data[ri as usize].assume_init_mut() };
+            if (middle.updated_mask >> mi) & 1 == 0 {
+                middle.data[mi as usize].write(InnerSnapshot::new(self.granularity, None));
+                middle.updated_mask |= 1 << mi;
+            }
+            let inner = unsafe { middle.data[mi as usize].assume_init_mut() };
+            if let InnerSnapshot::Block(block) = inner {
+                // SAFETY: `is_pod` is only true for `Copy` types, and zero-sized types
+                // without drop glue have no bytes to copy
+                unsafe { copy_runs(mask, data, &mut block.data) };
+                block.updated_mask |= mask;
+                return;
+            }
+        }
+
+        let mut mask = mask;
+        while mask != 0 {
+            let ii = mask.trailing_zeros();
+            mask &= !(1u128 << ii);
+            self.mark_updated(ri, mi, ii, unsafe { data[ii as usize].assume_init_ref() });
+        }
     }
 
     pub fn mark_added(&mut self, ri: u32, mi: u32, ii: u32) {
@@ -103,13 +1024,7 @@ impl<T: Component> RollbackStorage<T> {
 
         // Ensure inner block exists
         if (middle.updated_mask >> mi) & 1 == 0 {
-            let new_inner = RollbackBlock {
-                updated_mask: 0,
-                added_mask: 0,
-                data: std::array::from_fn(|_| std::mem::MaybeUninit::uninit()),
-            };
-
-            middle.data[mi as usize].write(Box::new(new_inner));
+            middle.data[mi as usize].write(InnerSnapshot::new(self.granularity, self.delta));
             middle.updated_mask |= 1 << mi;
         }
 
@@ -117,7 +1032,7 @@ impl<T: Component> RollbackStorage<T> {
         let inner = unsafe { middle.data[mi as usize].assume_init_mut() };
 
         debug_assert!(ii < 128, "ii index out of bounds in inner block: {}", ii);
-        inner.added_mask |= 1 << ii;
+        inner.mark_added(ii);
     }
 }
 
@@ -127,6 +1042,17 @@ impl<T: Component> Storage<T> {
             root: Block::new(),
             snapshot: None,
             current_tick: Tick::new(1),
+            dirty_blocks: 0,
+            #[cfg(test)]
+            cleared_blocks: 0,
+            #[cfg(feature = "insert-sequence")]
+            sequences: InsertSequences::new(),
+            retired: RetiredGenerations::new(),
+            removed: RemovedLog::new(),
+            change_ticks: ChangeTicks::new(),
+            kept: None,
+            granularity: SnapshotGranularity::Block,
+            delta: None,
         }
     }
 
@@ -148,8 +1074,16 @@ impl<T: Component> Storage<T> {
         T::cleanup_system(world)
     }
 
+    /// Clears the change masks. Only branches flagged in the parent's `changed_mask` are
+    /// visited, so the cost is proportional to `dirty_block_count`, not to the storage size.
     pub fn clear_changes(&mut self) {
-        let root = &mut self.root;
+        if self.dirty_blocks == 0 && self.root.changed_mask == 0 {
+            return;
+        }
+        self.dirty_blocks = 0;
+
+        let tick = self.current_tick;
+        let (root, change_ticks, kept) = (&mut self.root, &mut self.change_ticks, &mut self.kept);
 
         // Iterate only over middle blocks that have changes
         let mut middle_iter = root.changed_mask & root.presence_mask;
@@ -171,25 +1105,41 @@ impl<T: Component> Storage<T> {
                 let inner = unsafe { middle.data[mi as usize].assume_init_mut() };
 
                 // Clear inner changed_mask
+                change_ticks.record(ri, mi, inner.changed_mask, tick);
+                if let Some(kept) = kept {
+                    kept.record(ri, mi, inner.changed_mask);
+                }
                 inner.changed_mask = 0;
+                inner.added_mask = 0;
+                #[cfg(test)]
+                {
+                    self.cleared_blocks += 1;
+                }
 
                 inner_iter &= !(1 << mi);
             }
 
             // Clear middle changed_mask
             middle.changed_mask = 0;
+            middle.added_mask = 0;
 
             middle_iter &= !(1 << ri);
         }
 
         // Clear root changed_mask
         root.changed_mask = 0;
+        root.added_mask = 0;
     }
 
     pub fn rollback(&mut self, target_tick: Tick)
     where
         T: Clone,
     {
+        #[cfg(feature = "insert-sequence")]
+        self.sequences.rollback(target_tick);
+        self.retired.rollback(target_tick);
+        self.removed.rollback(target_tick);
+
         // Collect all snapshots that need to be rolled back (tick > target_tick)
         // Pre-allocate with estimated capacity to avoid repeated allocations
         // Most rollbacks are shallow (1-10 snapshots), but we allocate for worst case
@@ -232,11 +1182,74 @@ impl<T: Component> Storage<T> {
             // Reverse so oldest (closest to target_tick) is first
             snapshots_to_rollback.reverse();
             Self::rollback_with_bitmasks(&snapshots_to_rollback, &mut self.root);
+            self.dirty_blocks = self.count_dirty_blocks();
+            if snapshots_to_rollback.iter().any(|snapshot| snapshot.delta.is_some()) {
+                let mut kept = self.snapshot.take();
+                self.decompress_deltas(&snapshots_to_rollback, kept.as_deref_mut());
+                self.snapshot = kept;
+            }
         }
 
         self.current_tick = target_tick;
     }
 
+    /// Turns the deltas of `kept` taken against values of the `rolled_back` snapshots
+    /// back into full values. Each such delta is the latest kept record of its slot, and
+    /// was taken against the value the rollback just restored.
+    fn decompress_deltas(
+        &self,
+        rolled_back: &[Box<RollbackStorage<T>>],
+        mut kept: Option<&mut RollbackStorage<T>>,
+    ) {
+        // Slots recorded by the rolled back snapshots, per inner block
+        let mut pending: BTreeMap<(u32, u32), u128> = BTreeMap::new();
+        for snapshot in rolled_back {
+            let mut middles = snapshot.root.updated_mask;
+            while middles != 0 {
+                let ri = middles.trailing_zeros();
+                middles &= !(1u128 << ri);
+
+                let middle = unsafe { snapshot.root.data[ri as usize].assume_init_ref() };
+                let mut inners = middle.updated_mask;
+                while inners != 0 {
+                    let mi = inners.trailing_zeros();
+                    inners &= !(1u128 << mi);
+
+                    let inner = unsafe { middle.data[mi as usize].assume_init_ref() };
+                    *pending.entry((ri, mi)).or_default() |= inner.updated_mask();
+                }
+            }
+        }
+
+        while let Some(snapshot) = kept {
+            pending.retain(|&(ri, mi), slots| {
+                let Some(inner) = snapshot.inner_mut(ri, mi) else {
+                    return true;
+                };
+                let recorded = inner.updated_mask() | inner.added_mask();
+                if let InnerSnapshot::Delta(delta) = inner {
+                    let mut deltas = *slots & delta.delta_mask;
+                    while deltas != 0 {
+                        let ii = deltas.trailing_zeros();
+                        deltas &= !(1u128 << ii);
+
+                        let value = self.get(ri * 16384 + mi * 128 + ii);
+                        debug_assert!(value.is_some(), "Delta base {} was not restored", ii);
+                        if let Some(value) = value {
+                            delta.decompress(ii, value);
+                        }
+                    }
+                }
+                *slots &= !recorded;
+                *slots != 0
+            });
+            if pending.is_empty() {
+                break;
+            }
+            kept = snapshot.prev.as_deref_mut();
+        }
+    }
+
     fn rollback_with_bitmasks(
         snapshots: &[Box<RollbackStorage<T>>],
         block: &mut Block<Box<Block<Box<Block<T>>>>>,
@@ -268,6 +1281,7 @@ impl<T: Component> Storage<T> {
             block.presence_mask &= !(1 << i);
             block.absence_mask &= !(1 << i);
             block.changed_mask &= !(1 << i);
+            block.added_mask &= !(1 << i);
             debug_assert_eq!(block.absence_mask & !block.presence_mask, 0, "absence_mask should be subset of presence_mask");
         }
 
@@ -317,6 +1331,7 @@ impl<T: Component> Storage<T> {
             }
 
             block.changed_mask &= !(1 << i);
+            block.added_mask &= !(1 << i);
         }
     }
 
@@ -324,7 +1339,7 @@ impl<T: Component> Storage<T> {
         snapshots: &[Box<RollbackStorage<T>>],
         snapshot_idx: usize,
         root_idx: u32,
-        _snapshot: &RollbackBlock<Box<RollbackBlock<T>>>,
+        _snapshot: &RollbackBlock<InnerSnapshot<T>>,
         block: &mut Block<Box<Block<T>>>,
     ) where
         T: Clone,
@@ -332,7 +1347,7 @@ impl<T: Component> Storage<T> {
         // Pre-compute and cache relevant snapshot middle blocks to avoid repeated unsafe accesses
         struct CachedMiddle<'a, U> {
             snapshot_idx: usize,
-            middle: &'a RollbackBlock<Box<RollbackBlock<U>>>,
+            middle: &'a RollbackBlock<InnerSnapshot<U>>,
         }
         
         debug_assert!(root_idx < 128, "root_idx out of bounds: {}", root_idx);
@@ -374,6 +1389,7 @@ impl<T: Component> Storage<T> {
             block.presence_mask &= !(1 << i);
             block.absence_mask &= !(1 << i);
             block.changed_mask &= !(1 << i);
+            block.added_mask &= !(1 << i);
         }
 
         // Handle updates: for each slot, find the earliest snapshot that has it
@@ -423,6 +1439,7 @@ impl<T: Component> Storage<T> {
             }
 
             block.changed_mask &= !(1 << i);
+            block.added_mask &= !(1 << i);
         }
     }
 
@@ -431,7 +1448,7 @@ impl<T: Component> Storage<T> {
         snapshot_idx: usize,
         root_idx: u32,
         middle_idx: u32,
-        _snapshot: &RollbackBlock<T>,
+        _snapshot: &InnerSnapshot<T>,
         block: &mut Block<T>,
     ) where
         T: Clone,
@@ -443,7 +1460,7 @@ impl<T: Component> Storage<T> {
         // Pre-compute and cache relevant snapshot inner blocks to avoid repeated unsafe accesses
         // This stores inner_block_reference for snapshots that have this block
         struct CachedInner<'a, U> {
-            inner: &'a RollbackBlock<U>,
+            inner: &'a InnerSnapshot<U>,
         }
         
         let mut cached_inners: Vec<CachedInner<'_, T>> = Vec::with_capacity(snapshots.len() - snapshot_idx);
@@ -460,13 +1477,27 @@ impl<T: Component> Storage<T> {
             }
         }
 
+        if T::is_pod() || Block::<T>::MASKS_ONLY {
+            let blocks: Option<Vec<&RollbackBlock<T>>> = cached_inners
+                .iter()
+                .map(|cached| match cached.inner {
+                    InnerSnapshot::Block(block) => Some(&**block),
+                    _ => None,
+                })
+                .collect();
+            if let Some(blocks) = blocks {
+                Self::rollback_pod_block(&blocks, block);
+                return;
+            }
+        }
+
         // Compute union of all masks for this inner block across relevant snapshots
         let mut all_updated_mask = 0u128;
         let mut all_added_mask = 0u128;
 
         for cached in &cached_inners {
-            all_updated_mask |= cached.inner.updated_mask;
-            all_added_mask |= cached.inner.added_mask;
+            all_updated_mask |= cached.inner.updated_mask();
+            all_added_mask |= cached.inner.added_mask();
         }
 
         // Handle additions
@@ -487,6 +1518,7 @@ impl<T: Component> Storage<T> {
             block.presence_mask &= !(1 << i);
             block.absence_mask &= !(1 << i);
             block.changed_mask &= !(1 << i);
+            block.added_mask &= !(1 << i);
             debug_assert_eq!(block.absence_mask & !block.presence_mask, 0, "absence_mask should be subset of presence_mask");
         }
 
@@ -507,8 +1539,8 @@ impl<T: Component> Storage<T> {
             let mut earliest_updated: Option<&CachedInner<'_, T>> = None;
 
             for cached in &cached_inners {
-                let has_updated = (cached.inner.updated_mask >> i) & 1 != 0;
-                let has_added = (cached.inner.added_mask >> i) & 1 != 0;
+                let has_updated = (cached.inner.updated_mask() >> i) & 1 != 0;
+                let has_added = (cached.inner.added_mask() >> i) & 1 != 0;
 
                 // Check for added mask (for removal check)
                 if earliest_added.is_none() && (has_updated || has_added) {
@@ -530,7 +1562,7 @@ impl<T: Component> Storage<T> {
             // If so, the slot didn't exist before that snapshot, so remove it
             if let Some(cached) = earliest_added {
                 // If earliest snapshot has "added" but not "updated", remove the slot
-                if (cached.inner.added_mask >> i) & 1 != 0 && (cached.inner.updated_mask >> i) & 1 == 0 {
+                if (cached.inner.added_mask() >> i) & 1 != 0 && (cached.inner.updated_mask() >> i) & 1 == 0 {
                     // Slot was added in earliest snapshot, so it didn't exist before - remove it
                     unsafe {
                         if (block.presence_mask >> i) & 1 != 0 {
@@ -540,6 +1572,7 @@ impl<T: Component> Storage<T> {
                     block.presence_mask &= !(1 << i);
                     block.absence_mask &= !(1 << i);
                     block.changed_mask &= !(1 << i);
+                    block.added_mask &= !(1 << i);
                     debug_assert_eq!(block.absence_mask & !block.presence_mask, 0, "absence_mask should be subset of presence_mask");
                     continue; // Skip restoration
                 }
@@ -556,8 +1589,17 @@ impl<T: Component> Storage<T> {
                     }
 
                     // Restore value from the earliest snapshot that has it
-                    debug_assert!((cached.inner.updated_mask >> i) & 1 != 0, "Cached inner should have this slot");
-                    let old_val = cached.inner.data[i as usize].assume_init_ref().clone();
+                    debug_assert!((cached.inner.updated_mask() >> i) & 1 != 0, "Cached inner should have this slot");
+                    let old_val = if (cached.inner.delta_mask() >> i) & 1 == 0 {
+                        cached.inner.value(i).clone()
+                    } else {
+                        let later: Vec<&InnerSnapshot<T>> = cached_inners
+                            .iter()
+                            .map(|later| later.inner)
+                            .skip_while(|later| !std::ptr::eq(*later, cached.inner))
+                            .collect();
+                        InnerSnapshot::recorded_value(&later, i)
+                    };
                     block.data[i as usize].write(old_val);
                 }
 
@@ -568,21 +1610,53 @@ impl<T: Component> Storage<T> {
             }
 
             block.changed_mask &= !(1 << i);
+            block.added_mask &= !(1 << i);
         }
     }
 
+    /// `rollback_inner_block_with_bitmasks` for `Copy` components and tags recorded in
+    /// block snapshots, oldest first: copies runs of slots from the newest snapshot to the
+    /// oldest, so each slot ends with its earliest recorded value, without drops.
+    fn rollback_pod_block(snapshots: &[&RollbackBlock<T>], block: &mut Block<T>) {
+        // A slot whose earliest record is an addition didn't exist before
+        let mut recorded = 0u128;
+        let mut removed = 0u128;
+        for snapshot in snapshots {
+            removed |= snapshot.added_mask & !snapshot.updated_mask & !recorded;
+            recorded |= snapshot.added_mask | snapshot.updated_mask;
+        }
+        let restored = recorded & !removed;
+
+        for snapshot in snapshots.iter().rev() {
+            // SAFETY: only called for `Copy` types and zero-sized types without drop glue
+            unsafe { copy_runs(snapshot.updated_mask & restored, &snapshot.data, &mut block.data) };
+        }
+
+        block.presence_mask = (block.presence_mask | restored) & !removed;
+        block.absence_mask = (block.absence_mask | restored) & !removed;
+        block.changed_mask &= !recorded;
+        block.added_mask &= !recorded;
+        debug_assert_eq!(
+            block.absence_mask & !block.presence_mask,
+            0,
+            "absence_mask should be subset of presence_mask"
+        );
+    }
+
     fn ensure_snapshot(
         snapshot: &mut Option<Box<RollbackStorage<T>>>,
         tick: Tick,
+        granularity: SnapshotGranularity,
+        delta: Option<DeltaCodec<T>>,
     ) -> &mut RollbackStorage<T> {
         match snapshot {
             None => {
-                *snapshot = Some(RollbackStorage::new(None, tick));
+                *snapshot = Some(RollbackStorage::new(None, tick, granularity, delta));
                 snapshot.as_deref_mut().expect("Failed to get mutable reference to newly created snapshot")
             }
             Some(s) if s.tick != tick => {
                 let old = snapshot.take().expect("Failed to take snapshot from Some variant");
-                *snapshot = Some(RollbackStorage::new(Some(old), tick));
+                *snapshot = Some(RollbackStorage::new(Some(old), tick, granularity, delta));
                 snapshot.as_deref_mut().expect("Failed to get mutable reference to newly created snapshot")
             }
             Some(s) => {
@@ -592,6 +1666,18 @@ impl<T: Component> Storage<T> {
         }
     }
 
+    /// Iterates over `(index, &value)` pairs of all present components in ascending index order.
+    pub fn iter(&self) -> StorageIter<'_, T> {
+        StorageIter {
+            storage: self,
+            outer_mask: self.root.presence_mask,
+            middle_mask: 0,
+            inner_mask: 0,
+            ri: 0,
+            mi: 0,
+        }
+    }
+
     pub fn len(&self) -> usize {
         let root = &self.root;
         let mut count = 0;
@@ -672,27 +1758,50 @@ impl<T: Component> Storage<T> {
         debug_assert!(ii < 128, "ii index out of bounds: {}", ii);
 
         if (inner.changed_mask >> ii) & 1 == 0 {
-            // Only track rollback for non-temporary components
-            if !T::IS_TEMPORARY {
+            // Only track rollback for components that keep a history, see `RollbackPolicy`
+            if records_history::<T>() {
                 if is_present {
-                    Self::ensure_snapshot(&mut self.snapshot, self.current_tick).mark_updated(
+                    Self::ensure_snapshot(
+                        &mut self.snapshot,
+                        self.current_tick,
+                        self.granularity,
+                        self.delta,
+                    )
+                    .mark_updated(
                         ri,
                         mi,
                         ii,
                         unsafe { inner.data[ii as usize].assume_init_ref() },
                     );
                 } else {
-                    Self::ensure_snapshot(&mut self.snapshot, self.current_tick)
-                        .mark_added(ri, mi, ii);
+                    Self::ensure_snapshot(
+                        &mut self.snapshot,
+                        self.current_tick,
+                        self.granularity,
+                        self.delta,
+                    )
+                    .mark_added(ri, mi, ii);
                 }
             }
             // Mark as changed
+            if inner.changed_mask == 0 {
+                self.dirty_blocks += 1;
+            }
             inner.changed_mask |= 1 << ii;
 
             // Propagate changed_mask up the hierarchy
             middle.changed_mask |= 1 << mi;
             root.changed_mask |= 1 << ri;
         }
+        if !is_present {
+            inner.added_mask |= 1 << ii;
+            middle.added_mask |= 1 << mi;
+            root.added_mask |= 1 << ri;
+            self.removed.restore(self.current_tick, index);
+        }
+
+        #[cfg(feature = "insert-sequence")]
+        self.sequences.record(index, self.current_tick);
 
         // Set the value
         if is_present {
@@ -754,10 +1863,16 @@ impl<T: Component> Storage<T> {
         debug_assert!((inner.presence_mask >> ii) & 1 != 0, "Component should exist before removal");
 
         // Log change for rollback if not already changed in this tick
-        // Only track rollback for non-temporary components
+        // Only track rollback for components that keep a history, see `RollbackPolicy`
         if (inner.changed_mask >> ii) & 1 == 0 {
-            if !T::IS_TEMPORARY {
-                Self::ensure_snapshot(&mut self.snapshot, self.current_tick).mark_updated(
+            if records_history::<T>() {
+                Self::ensure_snapshot(
+                    &mut self.snapshot,
+                    self.current_tick,
+                    self.granularity,
+                    self.delta,
+                )
+                .mark_updated(
                     ri,
                     mi,
                     ii,
@@ -765,6 +1880,9 @@ impl<T: Component> Storage<T> {
                 );
             }
 
+            if inner.changed_mask == 0 {
+                self.dirty_blocks += 1;
+            }
             inner.changed_mask |= 1 << ii;
             middle.changed_mask |= 1 << mi;
             root.changed_mask |= 1 << ri;
@@ -773,14 +1891,16 @@ impl<T: Component> Storage<T> {
         // NOTE: We clear presence_mask here because it should track current existence
         // This means we treat the slot as uninitialized after removal
 
-        // Drop the value
-        unsafe {
-            inner.data[ii as usize].assume_init_drop();
+        // Move the value into the removal log, or drop it for temporary components
+        let value = unsafe { inner.data[ii as usize].assume_init_read() };
+        if !T::IS_TEMPORARY {
+            self.removed.record(self.current_tick, index, value);
         }
 
-        // Clear presence and absence bits
+        // Clear presence and absence bits, and the added bit if it was added this tick
         inner.presence_mask &= !(1 << ii);
         inner.absence_mask &= !(1 << ii);
+        inner.added_mask &= !(1 << ii);
         debug_assert_eq!(inner.absence_mask & !inner.presence_mask, 0, "absence_mask should be subset of presence_mask after removal");
 
         // Maintain invariant: propagate non-fullness up the hierarchy
@@ -866,11 +1986,17 @@ impl<T: Component> Storage<T> {
         debug_assert!((inner.presence_mask >> ii) & 1 != 0, "Component should exist");
 
         // Set changed_mask at all levels only if not already set at inner level
-        // Only track rollback for non-temporary components
+        // Only track rollback for components that keep a history, see `RollbackPolicy`
         // Note: We already verified presence_mask above, so we know it's set
         if (inner.changed_mask >> ii) & 1 == 0 {
-            if !T::IS_TEMPORARY {
-                Self::ensure_snapshot(&mut self.snapshot, self.current_tick).mark_updated(
+            if records_history::<T>() {
+                Self::ensure_snapshot(
+                    &mut self.snapshot,
+                    self.current_tick,
+                    self.granularity,
+                    self.delta,
+                )
+                .mark_updated(
                     ri,
                     mi,
                     ii,
@@ -878,6 +2004,9 @@ impl<T: Component> Storage<T> {
                 );
             }
 
+            if inner.changed_mask == 0 {
+                self.dirty_blocks += 1;
+            }
             inner.changed_mask |= 1 << ii;
             middle.changed_mask |= 1 << mi;
             root.changed_mask |= 1 << ri;
@@ -887,9 +2016,689 @@ impl<T: Component> Storage<T> {
     }
 }
 
+impl<T: Component> Storage<T> {
+    fn middle(&self, ri: u32) -> Option<&Block<Box<Block<T>>>> {
+        if (self.root.presence_mask >> ri) & 1 == 0 {
+            return None;
+        }
+        Some(unsafe { self.root.data[ri as usize].assume_init_ref() })
+    }
+
+    pub(crate) fn inner(&self, ri: u32, mi: u32) -> Option<&Block<T>> {
+        let middle = self.middle(ri)?;
+        if (middle.presence_mask >> mi) & 1 == 0 {
+            return None;
+        }
+        Some(unsafe { middle.data[mi as usize].assume_init_ref() })
+    }
+
+    /// The components of inner block `(ri, mi)` as one slice, if all 128 slots are
+    /// occupied. The slice starts on a `BLOCK_DATA_ALIGN` boundary, so SIMD loops over dense
+    /// runs can use aligned loads.
+    pub fn dense_block(&self, ri: u32, mi: u32) -> Option<&[T]> {
+        let slice = self.inner(ri, mi)?.as_slice()?;
+        debug_assert_eq!(slice.as_ptr() as usize % T::BLOCK_ALIGN.max(1), 0);
+        Some(slice)
+    }
+
+    /// Mutable `dense_block`. Every slot is marked changed and recorded for rollback
+    /// first, as 128 `get_mut` calls would.
+    pub fn dense_block_mut(&mut self, ri: u32, mi: u32) -> Option<&mut [T]> {
+        self.dense_block(ri, mi)?;
+
+        self.mark_block_changed(ri, mi, u128::MAX);
+
+        let middle = unsafe { self.root.data[ri as usize].assume_init_mut() };
+        let inner = unsafe { middle.data[mi as usize].assume_init_mut() };
+        inner.as_mut_slice()
+    }
+
+    /// Marks the occupied slots in `mask` of inner block `(ri, mi)` changed, recording the
+    /// ones not yet changed this tick in the snapshot.
+    fn mark_block_changed(&mut self, ri: u32, mi: u32, mask: u128) {
+        if (self.root.presence_mask >> ri) & 1 == 0 {
+            return;
+        }
+        let root = &mut self.root;
+        let middle = unsafe { root.data[ri as usize].assume_init_mut() };
+        if (middle.presence_mask >> mi) & 1 == 0 {
+            return;
+        }
+        let inner = unsafe { middle.data[mi as usize].assume_init_mut() };
+
+        let unchanged = mask & inner.presence_mask & !inner.changed_mask;
+        if unchanged != 0 {
+            if inner.changed_mask == 0 {
+                self.dirty_blocks += 1;
+            }
+            inner.changed_mask |= unchanged;
+            middle.changed_mask |= 1 << mi;
+            root.changed_mask |= 1 << ri;
+
+            if records_history::<T>() {
+                Self::ensure_snapshot(
+                    &mut self.snapshot,
+                    self.current_tick,
+                    self.granularity,
+                    self.delta,
+                )
+                .mark_updated_mask(ri, mi, unchanged, &inner.data);
+            }
+        }
+    }
+
+    /// Returns a copy-on-write view of this storage. The view shares every block with
+    /// `self` and copies an inner block only when it first writes to it, see the `cow`
+    /// module.
+    pub fn fork_cow(&self) -> crate::cow::CowStorage<'_, T> {
+        crate::cow::CowStorage::new(Some(self))
+    }
+
+    /// Allocates every middle and inner block covering indices `0..=max_index` and
+    /// pre-touches their unoccupied slots, so `set` never allocates below `max_index`.
+    /// Blocks are never freed, so the warmup lasts for the storage's lifetime.
+    ///
+    /// # Panics
+    /// Panics if `max_index` is out of bounds.
+    pub fn warmup(&mut self, max_index: u32) {
+        let last_ri = max_index >> 14;
+        if last_ri >= 128 {
+            panic!("Index out of bounds: {}", max_index);
+        }
+
+        for ri in 0..=last_ri {
+            self.root.ensure_child_exists(ri);
+            let middle = unsafe { self.root.data[ri as usize].assume_init_mut() };
+
+            let last_mi = if ri == last_ri { (max_index >> 7) & 0x7F } else { 127 };
+            for mi in 0..=last_mi {
+                if (middle.presence_mask >> mi) & 1 == 0 {
+                    let mut inner = Box::new(Block::new());
+                    inner.pre_touch();
+                    middle.data[mi as usize].write(inner);
+                    middle.presence_mask |= 1 << mi;
+                }
+            }
+        }
+    }
+
+    /// Number of inner blocks (128 slots each) changed since the last `clear_changes`.
+    /// Per-tick work that follows the change masks (cleanup, block hashing, delta
+    /// encoding) visits only these blocks, so this is the storage's share of the tick's
+    /// bookkeeping cost.
+    pub fn dirty_block_count(&self) -> u32 {
+        self.dirty_blocks
+    }
+
+    /// Returns when the component at `index` was last `set`: its tick and its position
+    /// among the `set`s of that tick in this storage, or `None` if there is no component.
+    /// The order is the deterministic order systems ran in, and rollback restores it, so it
+    /// can break ties ("first hit wins") without depending on iteration order.
+    #[cfg(feature = "insert-sequence")]
+    pub fn sequence_of(&self, index: u32) -> Option<InsertSeq> {
+        self.get(index)?;
+        self.sequences.latest(index)
+    }
+
+    /// Recounts the inner blocks with changes, following only changed branches.
+    fn count_dirty_blocks(&self) -> u32 {
+        let root = &self.root;
+        let mut count = 0;
+
+        let mut middles = root.changed_mask & root.presence_mask;
+        while middles != 0 {
+            let ri = middles.trailing_zeros();
+            middles &= !(1u128 << ri);
+
+            let middle = unsafe { root.data[ri as usize].assume_init_ref() };
+            let mut inners = middle.changed_mask & middle.presence_mask;
+            while inners != 0 {
+                let mi = inners.trailing_zeros();
+                inners &= !(1u128 << mi);
+
+                let inner = unsafe { middle.data[mi as usize].assume_init_ref() };
+                if inner.changed_mask != 0 {
+                    count += 1;
+                }
+            }
+        }
+
+        count
+    }
+
+    pub fn memory_stats(&self) -> MemoryStats {
+        let mut stats = MemoryStats {
+            bytes: std::mem::size_of::<Self>(),
+            ..Default::default()
+        };
+
+        let mut middles = self.root.presence_mask;
+        while middles != 0 {
+            let ri = middles.trailing_zeros();
+            middles &= !(1u128 << ri);
+
+            let middle = unsafe { self.root.data[ri as usize].assume_init_ref() };
+            let inners = middle.presence_mask.count_ones() as usize;
+            stats.middle_blocks += 1;
+            stats.inner_blocks += inners;
+            stats.bytes += std::mem::size_of::<Block<Box<Block<T>>>>()
+                + inners * std::mem::size_of::<Block<T>>();
+        }
+
+        stats
+    }
+
+    /// Frees the inner blocks without components, and the middle blocks left without
+    /// inner blocks, returning the number of blocks freed. Blocks the rollback history
+    /// still records are kept, as are blocks with uncleared changes; shrinking again after
+    /// `prune_history` frees them. Undoes `World::warmup` for empty blocks.
+    pub fn shrink(&mut self) -> usize {
+        // A rollback restores into the blocks any snapshot records, so they must stay
+        let mut pinned_root = 0u128;
+        let mut pinned_middles = [0u128; 128];
+        let mut snapshot = self.snapshot.as_deref();
+        while let Some(current) = snapshot {
+            pinned_root |= current.root.updated_mask | current.root.added_mask;
+
+            let mut middles = current.root.updated_mask;
+            while middles != 0 {
+                let ri = middles.trailing_zeros();
+                middles &= !(1u128 << ri);
+
+                let middle = unsafe { current.root.data[ri as usize].assume_init_ref() };
+                pinned_middles[ri as usize] |= middle.updated_mask | middle.added_mask;
+            }
+
+            snapshot = current.prev.as_deref();
+        }
+
+        let mut freed = 0;
+        let root = &mut self.root;
+        let mut middles = root.presence_mask;
+        while middles != 0 {
+            let ri = middles.trailing_zeros();
+            middles &= !(1u128 << ri);
+
+            let middle = unsafe { root.data[ri as usize].assume_init_mut() };
+            let mut inners = middle.presence_mask & !pinned_middles[ri as usize];
+            while inners != 0 {
+                let mi = inners.trailing_zeros();
+                inners &= !(1u128 << mi);
+
+                let inner = unsafe { middle.data[mi as usize].assume_init_ref() };
+                if inner.presence_mask != 0 || inner.changed_mask != 0 {
+                    continue;
+                }
+
+                unsafe { middle.data[mi as usize].assume_init_drop() };
+                middle.presence_mask &= !(1 << mi);
+                middle.changed_mask &= !(1 << mi);
+                middle.added_mask &= !(1 << mi);
+                freed += 1;
+            }
+
+            if middle.presence_mask == 0 && (pinned_root >> ri) & 1 == 0 {
+                unsafe { root.data[ri as usize].assume_init_drop() };
+                root.presence_mask &= !(1 << ri);
+                root.changed_mask &= !(1 << ri);
+                root.added_mask &= !(1 << ri);
+                freed += 1;
+            }
+        }
+
+        freed
+    }
+
+    /// Drops the snapshots of ticks up to `oldest`, which a rollback to `oldest` or later
+    /// never restores, along with the other history only older rollbacks need.
+    pub fn prune_history(&mut self, oldest: Tick) {
+        #[cfg(feature = "insert-sequence")]
+        self.sequences.prune(oldest);
+        self.retired.prune(oldest);
+        self.removed.prune(oldest);
+
+        let mut link = &mut self.snapshot;
+        while link.as_ref().is_some_and(|snapshot| snapshot.tick.is_after(oldest)) {
+            link = &mut link.as_mut().expect("checked above").prev;
+        }
+        // Unlinked one by one, a long chain would overflow the stack when dropped
+        let mut stale = link.take();
+        while let Some(mut snapshot) = stale {
+            stale = snapshot.prev.take();
+        }
+    }
+
+    /// Memory held by the rollback snapshots of every tick still in the history.
+    pub fn history_stats(&self) -> HistoryStats {
+        let mut stats = HistoryStats::default();
+
+        let mut snapshot = self.snapshot.as_deref();
+        while let Some(current) = snapshot {
+            stats.bytes += std::mem::size_of::<RollbackStorage<T>>();
+
+            let mut middles = current.root.updated_mask;
+            while middles != 0 {
+                let ri = middles.trailing_zeros();
+                middles &= !(1u128 << ri);
+
+                let middle = unsafe { current.root.data[ri as usize].assume_init_ref() };
+                stats.bytes += std::mem::size_of::<RollbackBlock<InnerSnapshot<T>>>();
+
+                let mut inners = middle.updated_mask;
+                while inners != 0 {
+                    let mi = inners.trailing_zeros();
+                    inners &= !(1u128 << mi);
+
+                    let inner = unsafe { middle.data[mi as usize].assume_init_ref() };
+                    match inner {
+                        InnerSnapshot::Sparse(_) => stats.records += 1,
+                        InnerSnapshot::Block(_) => stats.blocks += 1,
+                        InnerSnapshot::Delta(delta) => {
+                            stats.records += 1;
+                            stats.deltas += delta.delta_mask.count_ones() as usize;
+                        }
+                    }
+                    stats.bytes += inner.bytes();
+                }
+            }
+
+            snapshot = current.prev.as_deref();
+        }
+
+        stats
+    }
+
+    /// How ticks from now on record their rollback snapshots. A tick already being
+    /// recorded keeps its granularity.
+    pub fn set_snapshot_granularity(&mut self, granularity: SnapshotGranularity) {
+        self.granularity = granularity;
+    }
+
+    pub fn snapshot_granularity(&self) -> SnapshotGranularity {
+        self.granularity
+    }
+
+    /// Records the previous values of ticks from now on as per-entity records, storing
+    /// each as a byte delta against the next value recorded for the same entity, see
+    /// `DeltaCompressible`. Replaces the snapshot granularity and stays on.
+    pub fn enable_delta_snapshots(&mut self)
+    where
+        T: DeltaCompressible,
+    {
+        self.delta = Some(DeltaCodec::new());
+    }
+
+    pub fn delta_snapshots(&self) -> bool {
+        self.delta.is_some()
+    }
+
+    /// Drops the components in `mask` of inner block `(ri, mi)` without change tracking,
+    /// keeping the fullness masks up to date.
+    pub fn discard(&mut self, ri: u32, mi: u32, mask: u128) {
+        let root = &mut self.root;
+        if (root.presence_mask >> ri) & 1 == 0 {
+            return;
+        }
+
+        let middle = unsafe { root.data[ri as usize].assume_init_mut() };
+        if (middle.presence_mask >> mi) & 1 == 0 {
+            return;
+        }
+
+        let inner = unsafe { middle.data[mi as usize].assume_init_mut() };
+        let mut drop_mask = mask & inner.presence_mask;
+        while drop_mask != 0 {
+            let ii = drop_mask.trailing_zeros();
+            let value = unsafe { inner.data[ii as usize].assume_init_read() };
+            if !T::IS_TEMPORARY {
+                self.removed.record(self.current_tick, ri * 16384 + mi * 128 + ii, value);
+            }
+            drop_mask &= !(1u128 << ii);
+        }
+
+        inner.presence_mask &= !mask;
+        inner.absence_mask &= !mask;
+        inner.added_mask &= !mask;
+
+        if inner.absence_mask != u128::MAX {
+            middle.absence_mask &= !(1u128 << mi);
+        }
+        if middle.absence_mask != u128::MAX {
+            root.absence_mask &= !(1u128 << ri);
+        }
+    }
+}
+
+impl<T: Component> ComponentStorage for Storage<T> {
+    type Item = T;
+
+    fn new() -> Self {
+        Storage::new()
+    }
+
+    fn set_tick(&mut self, tick: Tick) {
+        Storage::set_tick(self, tick)
+    }
+
+    fn tick(&self) -> Tick {
+        self.current_tick
+    }
+
+    fn get(&self, index: u32) -> Option<&T> {
+        Storage::get(self, index)
+    }
+
+    fn get_mut(&mut self, index: u32) -> &mut T {
+        Storage::get_mut(self, index)
+    }
+
+    fn set(&mut self, index: u32, value: &T) {
+        Storage::set(self, index, value)
+    }
+
+    fn remove(&mut self, index: u32) {
+        Storage::remove(self, index)
+    }
+
+    fn len(&self) -> usize {
+        Storage::len(self)
+    }
+
+    fn rollback(&mut self, target_tick: Tick) {
+        Storage::rollback(self, target_tick)
+    }
+
+    fn clear(&mut self) {
+        // The snapshot settings stay, they are configuration rather than state
+        let granularity = self.granularity;
+        let delta = self.delta.take();
+        let keep = self.kept.is_some();
+        *self = Storage::new();
+        self.granularity = granularity;
+        self.delta = delta;
+        self.set_keep_changes(keep);
+    }
+
+    fn clear_changes(&mut self) {
+        Storage::clear_changes(self)
+    }
+
+    fn prune_history(&mut self, oldest: Tick) {
+        Storage::prune_history(self, oldest)
+    }
+
+    fn discard(&mut self, ri: u32, mi: u32, mask: u128) {
+        Storage::discard(self, ri, mi, mask)
+    }
+
+    fn shrink(&mut self) -> usize {
+        Storage::shrink(self)
+    }
+
+    fn visit<'a>(&'a self, mut f: impl FnMut(u32, &'a T)) {
+        for (index, value) in self.iter() {
+            f(index, value);
+        }
+    }
+
+    fn mark_changed(&mut self, ri: u32, mi: u32, mask: u128) {
+        self.mark_block_changed(ri, mi, mask)
+    }
+
+    fn slot_ptr(&mut self, index: u32) -> *mut T {
+        let (ri, mi, ii) = (index >> 14, (index >> 7) & 0x7F, index & 0x7F);
+        assert!(
+            (self.inner_mask(ri, mi) >> ii) & 1 != 0,
+            "No component at index {}",
+            index
+        );
+
+        let middle = unsafe { self.root.data[ri as usize].assume_init_mut() };
+        let inner = unsafe { middle.data[mi as usize].assume_init_mut() };
+        inner.data[ii as usize].as_mut_ptr()
+    }
+
+    #[inline]
+    fn root_mask(&self) -> u128 {
+        self.root.presence_mask
+    }
+
+    #[inline]
+    fn root_full_mask(&self) -> u128 {
+        self.root.absence_mask
+    }
+
+    #[inline]
+    fn root_changed_mask(&self) -> u128 {
+        let kept = self.kept.as_ref().map_or(0, |kept| kept.root_mask());
+        self.root.changed_mask | (kept & self.root.presence_mask)
+    }
+
+    #[inline]
+    fn root_added_mask(&self) -> u128 {
+        self.root.added_mask
+    }
+
+    #[inline]
+    fn root_removed_mask(&self) -> u128 {
+        self.removed.root_mask(self.current_tick)
+    }
+
+    #[inline]
+    fn middle_mask(&self, ri: u32) -> u128 {
+        self.middle(ri).map_or(0, |m| m.presence_mask)
+    }
+
+    #[inline]
+    fn middle_full_mask(&self, ri: u32) -> u128 {
+        self.middle(ri).map_or(0, |m| m.absence_mask)
+    }
+
+    #[inline]
+    fn middle_changed_mask(&self, ri: u32) -> u128 {
+        let kept = self.kept.as_ref().map_or(0, |kept| kept.middle_mask(ri));
+        self.middle(ri).map_or(0, |m| m.changed_mask | (kept & m.presence_mask))
+    }
+
+    #[inline]
+    fn middle_added_mask(&self, ri: u32) -> u128 {
+        self.middle(ri).map_or(0, |m| m.added_mask)
+    }
+
+    #[inline]
+    fn middle_removed_mask(&self, ri: u32) -> u128 {
+        self.removed.middle_mask(self.current_tick, ri)
+    }
+
+    #[inline]
+    fn inner_mask(&self, ri: u32, mi: u32) -> u128 {
+        self.inner(ri, mi).map_or(0, |b| b.presence_mask)
+    }
+
+    #[inline]
+    fn inner_changed_mask(&self, ri: u32, mi: u32) -> u128 {
+        let kept = self.kept.as_ref().map_or(0, |kept| kept.inner_mask(ri, mi));
+        self.inner(ri, mi).map_or(0, |b| b.changed_mask | (kept & b.presence_mask))
+    }
+
+    #[inline]
+    fn inner_pending_mask(&self, ri: u32, mi: u32) -> u128 {
+        self.inner(ri, mi).map_or(0, |b| b.changed_mask)
+    }
+
+    #[inline]
+    fn inner_added_mask(&self, ri: u32, mi: u32) -> u128 {
+        self.inner(ri, mi).map_or(0, |b| b.added_mask)
+    }
+
+    #[inline]
+    fn inner_removed_mask(&self, ri: u32, mi: u32) -> u128 {
+        self.removed.inner_mask(self.current_tick, ri, mi)
+    }
+
+    fn removed_value(&self, index: u32) -> Option<&T> {
+        self.removed.value(self.current_tick, index)
+    }
+
+    fn root_changed_since(&self, since: Tick) -> u128 {
+        let pending = if self.current_tick.is_after(since) { self.root.changed_mask } else { 0 };
+        self.change_ticks.root_mask(since) | pending
+    }
+
+    fn middle_changed_since(&self, ri: u32, since: Tick) -> u128 {
+        let pending = if self.current_tick.is_after(since) {
+            self.middle(ri).map_or(0, |m| m.changed_mask)
+        } else {
+            0
+        };
+        self.change_ticks.middle_mask(ri, since) | pending
+    }
+
+    fn inner_changed_since(&self, ri: u32, mi: u32, since: Tick) -> u128 {
+        let pending = if self.current_tick.is_after(since) {
+            self.inner_pending_mask(ri, mi)
+        } else {
+            0
+        };
+        self.change_ticks.inner_mask(ri, mi, since) | pending
+    }
+
+    fn changed_tick(&self, index: u32) -> Option<Tick> {
+        let (ri, mi, ii) = (index >> 14, (index >> 7) & 0x7F, index & 0x7F);
+        if (self.inner_pending_mask(ri, mi) >> ii) & 1 != 0 {
+            return Some(self.current_tick);
+        }
+        self.change_ticks.tick_of(index)
+    }
+
+    fn set_keep_changes(&mut self, keep: bool) {
+        if keep != self.kept.is_some() {
+            self.kept = keep.then(Box::default);
+        }
+    }
+
+    fn clear_kept_changes(&mut self) {
+        if let Some(kept) = &mut self.kept {
+            **kept = KeptChanges::default();
+        }
+    }
+
+    fn warmup(&mut self, max_index: u32) {
+        Storage::warmup(self, max_index)
+    }
+
+    fn memory_stats(&self) -> MemoryStats {
+        Storage::memory_stats(self)
+    }
+}
+
+/// Iterator over present components of a `Storage`, see `Storage::iter`.
+pub struct StorageIter<'a, T> {
+    storage: &'a Storage<T>,
+    outer_mask: u128,
+    middle_mask: u128,
+    inner_mask: u128,
+    ri: u32,
+    mi: u32,
+}
+
+impl<'a, T> Iterator for StorageIter<'a, T> {
+    type Item = (u32, &'a T);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let root = &self.storage.root;
+
+        loop {
+            if self.inner_mask != 0 {
+                let ii = self.inner_mask.trailing_zeros();
+                self.inner_mask &= !(1u128 << ii);
+
+                let middle = unsafe { root.data[self.ri as usize].assume_init_ref() };
+                let inner = unsafe { middle.data[self.mi as usize].assume_init_ref() };
+                let value = unsafe { inner.data[ii as usize].assume_init_ref() };
+
+                return Some((self.ri * 16384 + self.mi * 128 + ii, value));
+            }
+
+            if self.middle_mask != 0 {
+                self.mi = self.middle_mask.trailing_zeros();
+                self.middle_mask &= !(1u128 << self.mi);
+
+                let middle = unsafe { root.data[self.ri as usize].assume_init_ref() };
+                self.inner_mask = unsafe { middle.data[self.mi as usize].assume_init_ref() }.presence_mask;
+                continue;
+            }
+
+            if self.outer_mask == 0 {
+                return None;
+            }
+
+            self.ri = self.outer_mask.trailing_zeros();
+            self.outer_mask &= !(1u128 << self.ri);
+            self.middle_mask = unsafe { root.data[self.ri as usize].assume_init_ref() }.presence_mask;
+        }
+    }
+}
+
 use crate::entity::Entity;
 
 impl Storage<Entity> {
+    /// Records `entities` as freed before the `DestroySystem` drops their slots: the old
+    /// values go into the rollback history, so rolling back past the destroy brings them
+    /// back, and their generations are kept so respawns in the slots continue from them.
+    pub(crate) fn retire(&mut self, entities: &[Entity]) {
+        for &entity in entities {
+            let ri = entity.index() >> 14;
+            let mi = (entity.index() >> 7) & 0x7F;
+            let ii = entity.index() & 0x7F;
+            let middle = unsafe { self.root.data[ri as usize].assume_init_mut() };
+            let inner = unsafe { middle.data[mi as usize].assume_init_mut() };
+
+            if (inner.changed_mask >> ii) & 1 == 0 {
+                Self::ensure_snapshot(
+                    &mut self.snapshot,
+                    self.current_tick,
+                    self.granularity,
+                    self.delta,
+                )
+                .mark_updated(ri, mi, ii, &entity);
+                if inner.changed_mask == 0 {
+                    self.dirty_blocks += 1;
+                }
+                inner.changed_mask |= 1 << ii;
+                middle.changed_mask |= 1 << mi;
+                self.root.changed_mask |= 1 << ri;
+            }
+            self.retired.retire(self.current_tick, entity);
+        }
+    }
+
+    /// The entity destroyed at `index` during `tick`, while the tick is in the rollback
+    /// window.
+    pub(crate) fn retired_at(&self, tick: Tick, index: u32) -> Option<Entity> {
+        self.retired.retired_at(tick, index)
+    }
+
+    /// Drops every entity like `ComponentStorage::clear`, but keeps the latest generation
+    /// of every slot, logged at `tick`, so handles of the dropped entities go stale instead
+    /// of naming the entities spawned next.
+    pub(crate) fn clear_retiring(&mut self, tick: Tick) {
+        let mut latest: HashMap<u32, u32> = HashMap::new();
+        for (&index, stack) in &self.retired.history {
+            if let Some(&(_, generation)) = stack.last() {
+                latest.insert(index, generation);
+            }
+        }
+        for (index, entity) in self.iter() {
+            latest.insert(index, entity.generation());
+        }
+
+        ComponentStorage::clear(self);
+        for (index, generation) in latest {
+            self.retired.history.insert(index, vec![(tick, generation)]);
+        }
+    }
+
     pub fn spawn(&mut self) -> Entity {
         let root = &mut self.root;
 
@@ -952,8 +2761,9 @@ impl Storage<Entity> {
                 let is_respawn = (inner.presence_mask >> ii) & 1 != 0;
                 
                 if !is_respawn {
-                    // First time initializing this slot - it's a new spawn
-                    inner.data[ii as usize].write(Entity::new(global_index, 0));
+                    // Free slot - continue from the generation of its last occupant, if any
+                    let generation = self.retired.latest(global_index);
+                    inner.data[ii as usize].write(Entity::new(global_index, generation));
                     inner.presence_mask |= 1 << ii;
                 }
 
@@ -965,12 +2775,22 @@ impl Storage<Entity> {
                 if (inner.changed_mask >> ii) & 1 == 0 {
                     if is_respawn {
                         // Entity was present, this is a respawn - save old state
-                        Self::ensure_snapshot(&mut self.snapshot, self.current_tick)
-                            .mark_updated(ri, mi, ii, entity);
+                        Self::ensure_snapshot(
+                            &mut self.snapshot,
+                            self.current_tick,
+                            self.granularity,
+                            self.delta,
+                        )
+                        .mark_updated(ri, mi, ii, entity);
                     } else {
                         // Entity slot was not present, this is a new spawn
-                        Self::ensure_snapshot(&mut self.snapshot, self.current_tick)
-                            .mark_added(ri, mi, ii);
+                        Self::ensure_snapshot(
+                            &mut self.snapshot,
+                            self.current_tick,
+                            self.granularity,
+                            self.delta,
+                        )
+                        .mark_added(ri, mi, ii);
                     }
                 }
 
@@ -981,7 +2801,11 @@ impl Storage<Entity> {
                 debug_assert_eq!(inner.absence_mask & !inner.presence_mask, 0, "absence_mask should be subset of presence_mask");
 
                 // Mark as changed - spawning/respawning is a change
+                if inner.changed_mask == 0 {
+                    self.dirty_blocks += 1;
+                }
                 inner.changed_mask |= 1 << ii;
+                inner.added_mask |= 1 << ii;
 
                 // Maintain invariant: propagate fullness up the hierarchy
                 if inner.absence_mask == u128::MAX {
@@ -998,9 +2822,11 @@ impl Storage<Entity> {
 
             // Propagate changed_mask up the hierarchy
             middle.changed_mask |= 1 << mi;
+            middle.added_mask |= 1 << mi;
         }
 
         root.changed_mask |= 1 << ri;
+        root.added_mask |= 1 << ri;
 
         // Re-traverse to return the reference.
         unsafe {
@@ -1,7 +1,8 @@
 use crate::component::{Component, Resource};
 use crate::entity::Entity;
 use crate::safety::verify_storage_invariants;
-use crate::storage::Storage;
+use crate::rollback::{apply_delta, encode_delta, DeltaCompressible};
+use crate::storage::{ComponentStorage, SnapshotGranularity, Storage};
 use crate::tick::Tick;
 
 #[test]
@@ -1457,3 +1458,464 @@ fn test_rollback_root_block_fullness_propagation() {
         assert_eq!(root.absence_mask.count_ones(), 128, "Root should have all 128 middle blocks marked as full after rollback");
     }
 }
+
+/// A storage with 20 000 components whose changes have been cleared.
+fn idle_storage() -> Storage<u32> {
+    let mut storage = Storage::<u32>::new();
+    storage.set_tick(Tick::new(1));
+    for i in 0..20_000 {
+        storage.set(i, &i);
+    }
+    storage.clear_changes();
+    storage.cleared_blocks = 0;
+    storage
+}
+
+#[test]
+fn test_clear_changes_visits_only_dirty_blocks() {
+    let mut storage = idle_storage();
+    assert_eq!(storage.dirty_block_count(), 0);
+    assert!(storage.memory_stats().inner_blocks > 100);
+
+    storage.set_tick(Tick::new(2));
+    storage.set(5, &0);
+    storage.set(6, &0);
+    *storage.get_mut(130) += 1;
+    storage.remove(16_384 + 7);
+    assert_eq!(storage.dirty_block_count(), 3);
+
+    storage.clear_changes();
+    assert_eq!(storage.cleared_blocks, 3);
+    assert_eq!(storage.dirty_block_count(), 0);
+
+    // Nothing changed since: no block is visited
+    storage.clear_changes();
+    assert_eq!(storage.cleared_blocks, 3);
+}
+
+#[test]
+fn test_snapshot_records_only_dirty_blocks() {
+    let mut storage = idle_storage();
+
+    storage.set_tick(Tick::new(2));
+    storage.set(5, &0);
+    storage.set(16_384 + 7, &0);
+    storage.set(19_999, &0);
+
+    let snapshot = storage.snapshot.as_ref().unwrap();
+    assert_eq!(snapshot.tick, Tick::new(2));
+    let mut inner_blocks = 0;
+    let mut middles = snapshot.root.updated_mask;
+    while middles != 0 {
+        let ri = middles.trailing_zeros();
+        middles &= !(1u128 << ri);
+        let middle = unsafe { snapshot.root.data[ri as usize].assume_init_ref() };
+        inner_blocks += middle.updated_mask.count_ones();
+    }
+    assert_eq!(inner_blocks, 3);
+    assert_eq!(storage.dirty_block_count(), 3);
+
+    // Rolling back the tick leaves nothing dirty
+    storage.rollback(Tick::new(1));
+    assert_eq!(storage.dirty_block_count(), 0);
+    assert_eq!(*storage.get(5).unwrap(), 5);
+}
+
+#[cfg(feature = "insert-sequence")]
+#[test]
+fn test_sequence_of_orders_sets_and_survives_rollback() {
+    use crate::storage::InsertSeq;
+    let seq = |tick, seq| {
+        Some(InsertSeq {
+            tick: Tick::new(tick),
+            seq,
+        })
+    };
+
+    let mut storage = Storage::<u32>::new();
+    storage.set_tick(Tick::new(1));
+    storage.set(40, &1);
+    storage.set(3, &1);
+    assert_eq!(storage.sequence_of(40), seq(1, 0));
+    assert_eq!(storage.sequence_of(3), seq(1, 1));
+    assert_eq!(storage.sequence_of(7), None);
+    storage.clear_changes();
+
+    // Counting restarts every tick; a set in the same tick replaces the entry
+    storage.set_tick(Tick::new(2));
+    storage.set(3, &2);
+    storage.set(7, &2);
+    storage.set(3, &3);
+    assert_eq!(storage.sequence_of(3), seq(2, 2));
+    assert_eq!(storage.sequence_of(7), seq(2, 1));
+    storage.remove(40);
+    assert_eq!(storage.sequence_of(40), None);
+    storage.clear_changes();
+
+    storage.rollback(Tick::new(1));
+    assert_eq!(storage.sequence_of(3), seq(1, 1));
+    assert_eq!(storage.sequence_of(40), seq(1, 0));
+    assert_eq!(storage.sequence_of(7), None);
+
+    // Setting again at the restored tick continues after its existing sequences
+    storage.set(9, &1);
+    assert_eq!(storage.sequence_of(9), seq(1, 2));
+}
+
+#[test]
+fn test_added_mask_tracks_only_new_components() {
+    let mut storage = Storage::<u32>::new();
+    storage.set(1, &1);
+    storage.set(2, &1);
+    storage.clear_changes();
+    assert_eq!(storage.inner_added_mask(0, 0), 0);
+
+    storage.set_tick(Tick::new(2));
+    storage.set(1, &2);
+    storage.set(3, &2);
+    storage.set(4, &2);
+    storage.remove(4);
+    assert_eq!(storage.inner_changed_mask(0, 0), 0b11010);
+    assert_eq!(storage.inner_added_mask(0, 0), 0b1000);
+    assert_eq!(storage.middle_added_mask(0), 1);
+    assert_eq!(storage.root_added_mask(), 1);
+
+    storage.clear_changes();
+    assert_eq!(storage.inner_added_mask(0, 0), 0);
+    assert_eq!(storage.root_added_mask(), 0);
+}
+
+#[derive(Component, Clone, Default)]
+#[component(align(32))]
+struct Lanes {
+    values: [f32; 8],
+}
+
+#[test]
+fn test_dense_block_is_aligned_and_tracked() {
+    assert_eq!(Lanes::BLOCK_ALIGN, 32);
+
+    let mut storage = Storage::<Lanes>::new();
+    for i in 0..127 {
+        storage.set(128 + i, &Lanes::default());
+    }
+    assert!(storage.dense_block(0, 1).is_none());
+    storage.set(255, &Lanes::default());
+    storage.clear_changes();
+
+    let slice = storage.dense_block(0, 1).unwrap();
+    assert_eq!(slice.len(), 128);
+    assert_eq!(slice.as_ptr() as usize % crate::block::BLOCK_DATA_ALIGN, 0);
+
+    storage.set_tick(Tick::new(2));
+    for lanes in storage.dense_block_mut(0, 1).unwrap() {
+        lanes.values[0] = 1.0;
+    }
+    assert_eq!(storage.inner_changed_mask(0, 1), u128::MAX);
+
+    storage.rollback(Tick::new(1));
+    assert!(storage.iter().all(|(_, lanes)| lanes.values[0] == 0.0));
+}
+
+/// Sparse edits in block (0, 0), a dense rewrite of block (0, 1), a removal and an
+/// addition, over ticks 2 to 4.
+fn edit_history(granularity: SnapshotGranularity) -> Storage<u32> {
+    let mut storage = idle_storage();
+    storage.set_snapshot_granularity(granularity);
+
+    storage.set_tick(Tick::new(2));
+    storage.set(5, &100);
+    storage.set(7, &101);
+    storage.clear_changes();
+
+    storage.set_tick(Tick::new(3));
+    for i in 128..256 {
+        storage.set(i, &0);
+    }
+    storage.remove(5);
+    storage.set(30_000, &7);
+    storage.clear_changes();
+
+    storage.set_tick(Tick::new(4));
+    storage.set(7, &102);
+    storage.set(9, &103);
+    storage.clear_changes();
+    storage
+}
+
+#[test]
+fn test_adaptive_snapshots_restore_like_blocks() {
+    let base = idle_storage().history_stats();
+    let mut blocks = edit_history(SnapshotGranularity::Block);
+    let mut adaptive = edit_history(SnapshotGranularity::Adaptive { threshold: 8 });
+
+    let (b, a) = (blocks.history_stats(), adaptive.history_stats());
+    assert_eq!(b.blocks - base.blocks, 5);
+    assert_eq!(b.records, 0);
+    // Only the rewritten block reached the threshold
+    assert_eq!(a.blocks - base.blocks, 1);
+    assert_eq!(a.records, 4);
+    assert!(a.bytes < b.bytes);
+    assert_eq!(adaptive.memory_stats(), blocks.memory_stats());
+
+    for tick in [3, 2, 1] {
+        blocks.rollback(Tick::new(tick));
+        adaptive.rollback(Tick::new(tick));
+        let expected: Vec<(u32, u32)> = blocks.iter().map(|(i, v)| (i, *v)).collect();
+        let restored: Vec<(u32, u32)> = adaptive.iter().map(|(i, v)| (i, *v)).collect();
+        assert_eq!(restored, expected, "tick {}", tick);
+        verify_storage_invariants(&adaptive).unwrap();
+    }
+    assert_eq!(adaptive.get(5), Some(&5));
+    assert_eq!(adaptive.get(30_000), None);
+}
+
+#[test]
+fn test_sparse_records_keep_first_value_and_promote() {
+    let mut storage = idle_storage();
+    storage.set_snapshot_granularity(SnapshotGranularity::Adaptive { threshold: 4 });
+    storage.set_tick(Tick::new(2));
+
+    // Records stay in slot order whatever order the changes come in
+    for index in [9, 3, 6] {
+        storage.set(index, &0);
+        storage.set(index, &1);
+    }
+    let history = |storage: &Storage<u32>| {
+        let stats = storage.history_stats();
+        (stats.records, stats.blocks)
+    };
+    let before = history(&idle_storage());
+    assert_eq!(history(&storage), (1, before.1));
+
+    storage.set(1, &0);
+    assert_eq!(history(&storage), (0, before.1 + 1));
+
+    storage.rollback(Tick::new(1));
+    for index in [1, 3, 6, 9] {
+        assert_eq!(storage.get(index), Some(&index));
+    }
+}
+
+#[derive(Component, Clone, Copy, Default, Debug, PartialEq)]
+#[repr(C)]
+struct Pose {
+    joints: [i32; 32],
+}
+
+unsafe impl DeltaCompressible for Pose {}
+
+/// Moves one joint of every pose per tick, removing pose 3 at tick 5 and bringing it back
+/// at tick 7.
+fn animate(storage: &mut Storage<Pose>, ticks: std::ops::RangeInclusive<u32>, speed: i32) {
+    for tick in ticks {
+        storage.set_tick(Tick::new(tick));
+        for index in 0..200 {
+            if storage.get(index).is_some() {
+                storage.get_mut(index).joints[tick as usize % 32] += speed * index as i32;
+            }
+        }
+        match tick {
+            5 => storage.remove(3),
+            7 => storage.set(3, &Pose::default()),
+            _ => {}
+        }
+        storage.clear_changes();
+    }
+}
+
+fn posed_storage(delta: bool) -> Storage<Pose> {
+    let mut storage = Storage::<Pose>::new();
+    if delta {
+        storage.enable_delta_snapshots();
+    }
+    storage.set_tick(Tick::new(1));
+    for index in 0..200 {
+        storage.set(index, &Pose { joints: [index as i32; 32] });
+    }
+    storage.clear_changes();
+    animate(&mut storage, 2..=12, 1);
+    storage
+}
+
+fn poses(storage: &Storage<Pose>) -> Vec<(u32, Pose)> {
+    storage.iter().map(|(index, pose)| (index, *pose)).collect()
+}
+
+#[test]
+fn test_delta_snapshots_restore_like_full_values() {
+    let mut full = posed_storage(false);
+    let mut delta = posed_storage(true);
+    assert!(delta.delta_snapshots());
+
+    let (f, d) = (full.history_stats(), delta.history_stats());
+    assert_eq!(f.deltas, 0);
+    // Every record but the latest of each pose is a delta
+    assert!(d.deltas > 190 * 9);
+    assert!(d.bytes * 3 < f.bytes);
+
+    full.rollback(Tick::new(10));
+    delta.rollback(Tick::new(10));
+    assert_eq!(poses(&delta), poses(&full));
+
+    // Records made after a rollback chain onto the decompressed ones
+    animate(&mut full, 11..=14, -3);
+    animate(&mut delta, 11..=14, -3);
+    for tick in [12, 6, 4, 1] {
+        full.rollback(Tick::new(tick));
+        delta.rollback(Tick::new(tick));
+        assert_eq!(poses(&delta), poses(&full), "tick {}", tick);
+        verify_storage_invariants(&delta).unwrap();
+    }
+    assert_eq!(delta.get(3), Some(&Pose { joints: [3; 32] }));
+}
+
+#[test]
+fn test_delta_round_trip() {
+    let base = Pose { joints: [7; 32] };
+    let mut value = base;
+    assert!(encode_delta(&value, &base).is_empty());
+
+    value.joints[0] = -1;
+    value.joints[31] = 1 << 20;
+    let delta = encode_delta(&value, &base);
+    assert!(delta.len() < 16);
+    assert_eq!(apply_delta(&base, &delta), value);
+}
+
+/// Whole-block and single-slot edits, removals and additions over ticks 2 to 4.
+fn block_history<T: Component<Storage = Storage<T>>>(make: fn(i32) -> T) -> Storage<T> {
+    let mut storage = Storage::<T>::new();
+    storage.set_tick(Tick::new(1));
+    for i in 0..300 {
+        storage.set(i, &make(i as i32));
+    }
+    storage.clear_changes();
+
+    storage.set_tick(Tick::new(2));
+    storage.mark_changed(0, 0, 0x5555_5555_5555_5555_5555_5555_5555_5555);
+    for i in (0..128).step_by(2) {
+        *storage.get_mut(i) = make(1000 + i as i32);
+    }
+    storage.remove(5);
+    storage.set(1000, &make(-1));
+    storage.clear_changes();
+
+    storage.set_tick(Tick::new(3));
+    storage.mark_changed(0, 1, u128::MAX);
+    for i in 128..256 {
+        *storage.get_mut(i) = make(2000 + i as i32);
+    }
+    storage.remove(200);
+    storage.set(5, &make(-5));
+    storage.clear_changes();
+
+    storage.set_tick(Tick::new(4));
+    for i in 0..20 {
+        storage.set(i, &make(3000));
+    }
+    storage.remove(1000);
+    storage.clear_changes();
+    storage
+}
+
+#[test]
+fn test_copy_components_roll_back_like_clones() {
+    // `Pose` is `Copy`, `Lanes` only `Clone`
+    assert!(Pose::is_pod());
+    assert!(!Lanes::is_pod());
+    let pose = |x| Pose { joints: [x; 32] };
+
+    // Rolling back one tick, then several at once
+    for tick in [3, 2, 1] {
+        let mut poses = block_history(pose);
+        let mut lanes = block_history(|x| Lanes { values: [x as f32; 8] });
+        poses.rollback(Tick::new(tick));
+        lanes.rollback(Tick::new(tick));
+        let expected: Vec<(u32, i32)> =
+            lanes.iter().map(|(i, v)| (i, v.values[0] as i32)).collect();
+        let restored: Vec<(u32, i32)> = poses.iter().map(|(i, v)| (i, v.joints[0])).collect();
+        assert_eq!(restored, expected, "tick {}", tick);
+        verify_storage_invariants(&poses).unwrap();
+    }
+
+    let mut poses = block_history(pose);
+    poses.rollback(Tick::new(1));
+    assert_eq!(poses.get(5), Some(&pose(5)));
+    assert_eq!(poses.get(1000), None);
+    assert_eq!(poses.len(), 300);
+}
+
+#[derive(crate::component::Tag, Clone, Default, Debug, PartialEq)]
+struct Frozen;
+
+#[test]
+fn test_tag_storage_rolls_back_masks() {
+    let mut storage = Storage::<Frozen>::new();
+    storage.set_tick(Tick::new(1));
+    for i in 0..200 {
+        storage.set(i, &Frozen);
+    }
+    storage.clear_changes();
+
+    storage.set_tick(Tick::new(2));
+    storage.remove(3);
+    storage.set(500, &Frozen);
+    storage.clear_changes();
+
+    storage.rollback(Tick::new(1));
+    assert_eq!(storage.get(3), Some(&Frozen));
+    assert_eq!(storage.get(500), None);
+    assert_eq!(storage.len(), 200);
+    assert!(verify_storage_invariants(&storage).is_ok());
+
+    // Inner blocks of a tag hold no slot data
+    let stats = storage.memory_stats();
+    assert!(stats.bytes < std::mem::size_of::<Storage<Frozen>>() + 16 * 1024);
+}
+
+/// `idle_storage` after tick 2 removed every component but the one at index 0.
+fn despawned_storage() -> Storage<u32> {
+    let mut storage = idle_storage();
+    storage.set_tick(Tick::new(2));
+    for i in 1..20_000 {
+        storage.remove(i);
+    }
+    storage.clear_changes();
+    storage
+}
+
+#[test]
+fn test_shrink_keeps_blocks_the_history_records() {
+    let mut storage = despawned_storage();
+    assert_eq!(storage.shrink(), 0);
+
+    storage.rollback(Tick::new(1));
+    assert_eq!(storage.len(), 20_000);
+    assert_eq!(storage.get(19_999), Some(&19_999));
+    assert!(verify_storage_invariants(&storage).is_ok());
+}
+
+#[test]
+fn test_shrink_frees_empty_blocks_once_pruned() {
+    let mut storage = despawned_storage();
+    let before = storage.memory_stats();
+    storage.prune_history(Tick::new(2));
+
+    // 157 inner blocks held the components and one still does; the second middle block
+    // goes with the others
+    assert_eq!(storage.shrink(), 156 + 1);
+    let after = storage.memory_stats();
+    assert_eq!((after.middle_blocks, after.inner_blocks), (1, 1));
+    assert!(after.bytes < before.bytes);
+    assert_eq!(storage.get(0), Some(&0));
+    assert!(verify_storage_invariants(&storage).is_ok());
+
+    // Freed blocks are allocated again on demand
+    storage.set_tick(Tick::new(3));
+    storage.set(19_999, &7);
+    storage.clear_changes();
+    storage.rollback(Tick::new(2));
+    assert_eq!(storage.get(19_999), None);
+    assert_eq!(storage.len(), 1);
+}
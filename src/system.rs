@@ -2,14 +2,19 @@ pub use rollback_macros::system;
 use std::any::TypeId;
 // Query trait not used in inlined macro run
 
-use crate::component::{Component, Destroyed};
-use crate::entity::Entity;
+use crate::component::{Component, Destroyed, Resource};
+use crate::hashtree::DirtyBlocks;
+use crate::journal::ChangeLog;
+use crate::entity::{Entity, EntityWeak};
 use crate::scheduler::PipelineStage;
+use crate::storage::ComponentStorage;
 use crate::world::World;
 
 pub struct DestroySystem {
     pub entity_storage: std::rc::Rc<std::cell::UnsafeCell<crate::storage::Storage<Entity>>>,
     pub destroyed_storage: std::rc::Rc<std::cell::UnsafeCell<crate::storage::Storage<Destroyed>>>,
+    /// Blocks of the entity storage this system frees, for the world's hash cache.
+    pub dirty: std::rc::Rc<DirtyBlocks>,
 }
 
 unsafe impl Send for DestroySystem {}
@@ -24,6 +29,15 @@ impl PipelineStage for DestroySystem {
         let entity_storage = unsafe { &mut *self.entity_storage.get() };
         let destroyed_storage = unsafe { &mut *self.destroyed_storage.get() };
 
+        // Keep the old values and generations of the entities freed below for rollback
+        let mut retiring = Vec::new();
+        destroyed_storage.visit(|index, _| {
+            if let Some(entity) = entity_storage.get(index) {
+                retiring.push(*entity);
+            }
+        });
+        entity_storage.retire(&retiring);
+
         let entity_root = &mut entity_storage.root;
         let destroyed_root = &mut destroyed_storage.root;
 
@@ -65,6 +79,9 @@ impl PipelineStage for DestroySystem {
 
                 // Find entities that have both Entity and Destroyed present
                 let mut inner_mask = entity_inner.presence_mask & destroyed_inner.presence_mask;
+                if inner_mask != 0 {
+                    self.dirty.mark(oi, mi);
+                }
 
                 while inner_mask != 0 {
                     let start = inner_mask.trailing_zeros();
@@ -132,6 +149,13 @@ impl PipelineStage for DestroySystem {
 
             outer_mask &= !(1u128 << oi);
         }
+
+        // Spawns and destroys are recorded once per slot and tick, so the next tick starts
+        // with clean change masks like every other storage
+        self.dirty.mark_changed(entity_storage.root_changed_mask(), |ri| {
+            entity_storage.middle_changed_mask(ri)
+        });
+        entity_storage.clear_changes();
     }
 
     fn reads(&self) -> &'static [TypeId] {
@@ -151,6 +175,7 @@ impl PipelineStage for DestroySystem {
         Self {
             entity_storage: world.get_storage::<Entity>(),
             destroyed_storage: world.get_storage::<Destroyed>(),
+            dirty: world.dirty_blocks(Entity::type_index()),
         }
     }
 }
@@ -166,15 +191,18 @@ system! {
 }
 
 pub struct ChangedMaskCleanupSystem<T: Component> {
-    pub storage: std::rc::Rc<std::cell::UnsafeCell<crate::storage::Storage<T>>>,
+    pub storage: std::rc::Rc<std::cell::UnsafeCell<T::Storage>>,
 }
 
 unsafe impl<T: Component> Send for ChangedMaskCleanupSystem<T> {}
 unsafe impl<T: Component> Sync for ChangedMaskCleanupSystem<T> {}
 
 pub struct ComponentCleanupSystem<T: Component> {
-    pub t_storage: std::rc::Rc<std::cell::UnsafeCell<crate::storage::Storage<T>>>,
+    pub t_storage: std::rc::Rc<std::cell::UnsafeCell<T::Storage>>,
     pub destroyed_storage: std::rc::Rc<std::cell::UnsafeCell<crate::storage::Storage<Destroyed>>>,
+    pub entity_storage: std::rc::Rc<std::cell::UnsafeCell<crate::storage::Storage<Entity>>>,
+    pub dirty: std::rc::Rc<DirtyBlocks>,
+    pub journal: std::rc::Rc<ChangeLog>,
 }
 
 unsafe impl<T: Component> Send for ComponentCleanupSystem<T> {}
@@ -189,137 +217,103 @@ impl<T: Component> PipelineStage for ComponentCleanupSystem<T> {
         let t_storage = unsafe { &mut *self.t_storage.get() };
         let destroyed_storage = unsafe { &*self.destroyed_storage.get() };
 
-        let t_root = &mut t_storage.root;
-        let d_root = &destroyed_storage.root;
-
         // Remove components for entities with Destroyed tag
-        // Iterate through all outer blocks where the target component exists
-        let mut outer_mask = t_root.presence_mask;
+        // Only visit blocks where both the target component and Destroyed exist
+        let mut outer_mask = t_storage.root_mask() & destroyed_storage.root_mask();
 
         while outer_mask != 0 {
             let ri = outer_mask.trailing_zeros();
-
-            // Check if Destroyed storage has this outer block - if not, skip
-            if (d_root.presence_mask >> ri) & 1 == 0 {
-                outer_mask &= !(1 << ri);
-                continue;
-            }
-
-            let t_middle = unsafe { t_root.data[ri as usize].assume_init_mut() };
-            let d_middle = unsafe { d_root.data[ri as usize].assume_init_ref() };
-
-            // Iterate through all middle blocks where the target component exists
-            let mut middle_mask = t_middle.presence_mask;
+            let mut middle_mask = t_storage.middle_mask(ri) & destroyed_storage.middle_mask(ri);
 
             while middle_mask != 0 {
                 let mi = middle_mask.trailing_zeros();
 
-                // Check if Destroyed storage has this middle block - if not, skip
-                if (d_middle.presence_mask >> mi) & 1 == 0 {
-                    middle_mask &= !(1 << mi);
-                    continue;
-                }
-
-                let t_inner = unsafe { t_middle.data[mi as usize].assume_init_mut() };
-                let d_inner = unsafe { d_middle.data[mi as usize].assume_init_ref() };
-
-                // Use masking to find entities that have both T and Destroyed components present
-                // presence_mask is sufficient - absence_mask is kept in sync at inner block level
-                let t_occupied = t_inner.presence_mask;
-                let d_occupied = d_inner.presence_mask;
-
-                // Intersect masks to find entities with both T and Destroyed
-                let mut inner_mask = t_occupied & d_occupied;
-
-                while inner_mask != 0 {
-                    let start = inner_mask.trailing_zeros();
-                    let run = (inner_mask >> start).trailing_ones();
-
-                    let range_mask = if run == 128 {
-                        u128::MAX
-                    } else {
-                        ((1u128 << run) - 1) << start
-                    };
-
-                    // Remove components for all entities in this range
-                    for ii in start..(start + run) {
-                        unsafe {
-                            t_inner.data[ii as usize].assume_init_drop();
-                        }
-                    }
-
-                    // Clear presence/absence bits (slots are fully free)
-                    t_inner.presence_mask &= !range_mask;
-                    t_inner.absence_mask &= !range_mask;
-
-                    inner_mask &= !range_mask;
-                }
-
-                // Maintain absence invariant: propagate fullness/non-fullness up
-                if t_inner.absence_mask != u128::MAX {
-                    t_middle.absence_mask &= !(1 << mi);
-                    t_root.absence_mask &= !(1 << ri);
-                } else {
-                    t_middle.absence_mask |= 1 << mi;
+                // Entities that have both T and Destroyed present
+                let inner_mask = t_storage.inner_mask(ri, mi) & destroyed_storage.inner_mask(ri, mi);
+                if inner_mask != 0 {
+                    t_storage.discard(ri, mi, inner_mask);
+                    self.dirty.mark(ri, mi);
                 }
 
                 middle_mask &= !(1 << mi);
             }
 
-            // After processing all middle blocks, check if all are full for this outer block
-            let t_middle_check = unsafe { t_root.data[ri as usize].assume_init_ref() };
-            // Check if all middle blocks are full
-            // At middle level, absence_mask means "all inner blocks are full", not just "exists"
-            // So we need to check if presence_mask & absence_mask == presence_mask (all present blocks are full)
-            let all_middle_full = (t_middle_check.presence_mask & t_middle_check.absence_mask)
-                == t_middle_check.presence_mask;
-            if all_middle_full {
-                t_root.absence_mask |= 1 << ri;
-            }
-
             outer_mask &= !(1 << ri);
         }
 
-        // Second, clear all changed_mask bits (merged ChangedMaskCleanupSystem functionality)
-        // Iterate over all middle blocks that have changes
-        let mut middle_iter = t_root.changed_mask & t_root.presence_mask;
-
-        while middle_iter != 0 {
-            let ri = middle_iter.trailing_zeros();
-            let middle = unsafe { t_root.data[ri as usize].assume_init_mut() };
-
-            // Iterate over all inner blocks that have changes
-            let mut inner_iter = middle.changed_mask & middle.presence_mask;
-            while inner_iter != 0 {
-                let mi = inner_iter.trailing_zeros();
-                let inner = unsafe { middle.data[mi as usize].assume_init_mut() };
-
-                // Clear inner changed_mask
-                inner.changed_mask = 0;
+        // Reset #[component(weak)] fields pointing at entities destroyed this tick. Runs
+        // before the DestroySystem, so the targets still carry their Destroyed marker.
+        if T::HAS_WEAK_REFS && destroyed_storage.root_mask() != 0 {
+            let entities = unsafe { &*self.entity_storage.get() };
+            let alive = |weak: &EntityWeak| {
+                weak.is_none() || weak.resolve(Some(entities), Some(destroyed_storage)).is_some()
+            };
+
+            let mut dangling = Vec::new();
+            t_storage.visit(|index, component| {
+                let mut dead = false;
+                component.visit_weak_refs(&mut |weak| dead |= !alive(weak));
+                if dead {
+                    dangling.push(index);
+                }
+            });
 
-                inner_iter &= !(1 << mi);
+            for index in dangling {
+                t_storage.get_mut(index).visit_weak_refs_mut(&mut |weak| {
+                    if !alive(weak) {
+                        weak.clear();
+                    }
+                });
             }
+        }
 
-            // Clear middle changed_mask
-            middle.changed_mask = 0;
-
-            middle_iter &= !(1 << ri);
+        // Remove relations whose target was destroyed this tick, recorded like
+        // `World::remove` so rollback restores the links
+        if T::IS_RELATION && destroyed_storage.root_mask() != 0 {
+            let entities = unsafe { &*self.entity_storage.get() };
+            let mut dangling = Vec::new();
+            t_storage.visit(|index, component| {
+                let dead = component.relation_target().is_some_and(|target| {
+                    entities.get(target.index()) != Some(&target)
+                        || destroyed_storage.get(target.index()).is_some()
+                });
+                if dead {
+                    dangling.push(index);
+                }
+            });
+            for index in dangling {
+                t_storage.remove(index);
+            }
         }
 
-        // Clear root changed_mask
-        t_root.changed_mask = 0;
+        // Second, clear all changed_mask bits (merged ChangedMaskCleanupSystem functionality),
+        // handing them to the world's hash cache and change journal first
+        if !T::IS_TEMPORARY && self.journal.is_enabled() {
+            self.journal.record_changed(t_storage);
+        }
+        self.dirty
+            .mark_changed(t_storage.root_changed_mask(), |ri| t_storage.middle_changed_mask(ri));
+        t_storage.clear_changes();
     }
 
     fn create(world: &mut World) -> Self {
         Self {
             t_storage: world.get_storage::<T>(),
             destroyed_storage: world.get_storage::<Destroyed>(),
+            entity_storage: world.get_storage::<Entity>(),
+            dirty: world.dirty_blocks(T::type_index()),
+            journal: world.change_log(T::type_index()),
         }
     }
 
     fn reads(&self) -> &'static [std::any::TypeId] {
         static READS: &[std::any::TypeId] = &[std::any::TypeId::of::<Destroyed>()];
-        READS
+        // Resolving weak references and relation targets also reads the entity generations
+        static READS_WEAK: &[std::any::TypeId] = &[
+            std::any::TypeId::of::<Destroyed>(),
+            std::any::TypeId::of::<Entity>(),
+        ];
+        if T::HAS_WEAK_REFS || T::IS_RELATION { READS_WEAK } else { READS }
     }
 
     fn writes(&self) -> &'static [std::any::TypeId] {
@@ -337,35 +331,7 @@ impl<T: Component> PipelineStage for ChangedMaskCleanupSystem<T> {
 
     fn run(&self) {
         let storage = unsafe { &mut *self.storage.get() };
-        let root = &mut storage.root;
-
-        // Iterate only over middle blocks that have changes
-        let mut middle_iter = root.changed_mask & root.presence_mask;
-
-        while middle_iter != 0 {
-            let ri = middle_iter.trailing_zeros();
-            let middle = unsafe { root.data[ri as usize].assume_init_mut() };
-
-            // Iterate only over inner blocks that have changes
-            let mut inner_iter = middle.changed_mask & middle.presence_mask;
-            while inner_iter != 0 {
-                let mi = inner_iter.trailing_zeros();
-                let inner = unsafe { middle.data[mi as usize].assume_init_mut() };
-
-                // Clear inner changed_mask
-                inner.changed_mask = 0;
-
-                inner_iter &= !(1 << mi);
-            }
-
-            // Clear middle changed_mask
-            middle.changed_mask = 0;
-
-            middle_iter &= !(1 << ri);
-        }
-
-        // Clear root changed_mask
-        root.changed_mask = 0;
+        storage.clear_changes();
     }
 
     fn create(world: &mut World) -> Self {
@@ -996,4 +962,354 @@ mod tests {
             12
         );
     }
+
+    #[derive(Component, Default, Clone)]
+    struct Armor {}
+
+    #[derive(Component, Default, Clone, Debug, PartialEq)]
+    struct Charge {
+        value: i32,
+    }
+
+    system! {
+        ArmoredChargeSystem {
+            query! {
+                fn bump(charge: &mut ViewMut<Charge>) None=[Armor] {
+                    charge.value += 1;
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn stage_count_matches_query_without_running() {
+        let mut world = World::new();
+        let stage = ArmoredChargeSystem::create(&mut world);
+
+        for i in 0..300 {
+            let e = world.spawn();
+            world.set(e, &Charge { value: 0 });
+            if i % 3 == 0 {
+                world.set(e, &Armor {});
+            }
+        }
+
+        assert_eq!(stage.count(), 200);
+        assert_eq!(world.count_matching::<(Charge,)>(), 300);
+        assert_eq!(world.count_matching::<(Charge, Armor)>(), 100);
+        assert_eq!(world.count_matching::<(Test,)>(), 0);
+
+        // Counting doesn't run the system
+        let storage = world.storage_ref::<Charge>().unwrap();
+        assert!(storage.iter().all(|(_, c)| c.value == 0));
+    }
+
+    #[derive(Component, Default, Clone, Debug, PartialEq)]
+    struct Owner {
+        entity: Entity,
+    }
+
+    system! {
+        RecordOwnerSystem {
+            query! {
+                fn record(entity: Entity, owner: &mut ViewMut<Owner>) {
+                    owner.entity = entity;
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn query_receives_entity_handle() {
+        let mut world = World::new();
+        world.add_system::<RecordOwnerSystem>();
+        world.build_scheduler();
+
+        // Reuse a slot so the handle carries a bumped generation
+        let first = world.spawn();
+        world.destroy(first);
+        world.run();
+        let entities: Vec<Entity> = (0..130).map(|_| world.spawn()).collect();
+        for &e in &entities {
+            world.set(e, &Owner::default());
+        }
+        world.run();
+
+        assert_eq!(entities[0].index(), first.index());
+        assert_ne!(entities[0], first);
+        let stage = RecordOwnerSystem::create(&mut world);
+        assert!(stage.reads().contains(&TypeId::of::<Entity>()));
+        for &e in &entities {
+            assert_eq!(world.get::<Owner>(e).unwrap().entity, e);
+        }
+    }
+
+    const ARENA_B: std::ops::Range<u32> = 100..250;
+
+    system! {
+        ArenaBChargeSystem {
+            query! {
+                fn bump(charge: &mut ViewMut<Charge>) Range = ARENA_B {
+                    charge.value += 1;
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn range_clause_scopes_query_to_indices() {
+        let mut world = World::new();
+        world.get_storage::<Charge>();
+        world.add_system::<ArenaBChargeSystem>();
+        world.build_scheduler();
+
+        let entities: Vec<Entity> = (0..300).map(|_| world.spawn()).collect();
+        for &e in &entities {
+            world.set(e, &Charge::default());
+        }
+        world.run();
+
+        for &e in &entities {
+            let expected = if ARENA_B.contains(&e.index()) { 1 } else { 0 };
+            assert_eq!(world.get::<Charge>(e).unwrap().value, expected);
+        }
+        let stage = ArenaBChargeSystem::create(&mut world);
+        assert_eq!(stage.count(), ARENA_B.len());
+    }
+
+    system! {
+        EitherChargeSystem {
+            query! {
+                fn bump(charge: &mut ViewMut<Charge>) Or=[[Armor, Test], [Owner]] {
+                    charge.value += 1;
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn or_clause_matches_union_of_groups() {
+        let mut world = World::new();
+        world.get_storage::<Charge>();
+        world.get_storage::<Armor>();
+        world.get_storage::<Test>();
+        world.get_storage::<Owner>();
+        world.add_system::<EitherChargeSystem>();
+        world.build_scheduler();
+
+        let entities: Vec<Entity> = (0..300).map(|_| world.spawn()).collect();
+        for (i, &e) in entities.iter().enumerate() {
+            world.set(e, &Charge::default());
+            if i.is_multiple_of(3) {
+                world.set(e, &Armor {});
+            }
+            if i.is_multiple_of(5) {
+                world.set(e, &Test::default());
+            }
+            if i.is_multiple_of(7) {
+                world.set(e, &Owner::default());
+            }
+        }
+        let matches = |i: usize| i.is_multiple_of(15) || i.is_multiple_of(7);
+
+        let stage = EitherChargeSystem::create(&mut world);
+        assert_eq!(stage.count(), (0..300).filter(|&i| matches(i)).count());
+        world.run();
+
+        for (i, &e) in entities.iter().enumerate() {
+            let expected = if matches(i) { 1 } else { 0 };
+            assert_eq!(world.get::<Charge>(e).unwrap().value, expected);
+        }
+    }
+
+    system! {
+        ChargeInitSystem {
+            query! {
+                fn init(charge: &mut ViewMut<Charge>) Added=[Charge] {
+                    charge.value += 10;
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn added_clause_skips_updated_components() {
+        let mut world = World::new();
+        world.get_storage::<Charge>();
+        world.add_system::<ChargeInitSystem>();
+        world.build_scheduler();
+
+        let first = world.spawn();
+        world.set(first, &Charge::default());
+        world.run();
+        world.run();
+        assert_eq!(world.get::<Charge>(first).unwrap().value, 10);
+
+        // An update is a change but not an addition
+        world.set(first, &Charge { value: 1 });
+        let second = world.spawn();
+        world.set(second, &Charge::default());
+        let stage = ChargeInitSystem::create(&mut world);
+        assert_eq!(stage.count(), 1);
+        world.run();
+
+        assert_eq!(world.get::<Charge>(first).unwrap().value, 1);
+        assert_eq!(world.get::<Charge>(second).unwrap().value, 10);
+    }
+
+    system! {
+        UnarmoredChargeSystem {
+            query! {
+                fn react(charge: &mut ViewMut<Charge>) WasRemoved=[Armor] {
+                    charge.value += 1;
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn was_removed_clause_sees_previous_tick_removals() {
+        let mut world = World::new();
+        world.get_storage::<Charge>();
+        world.get_storage::<Armor>();
+        world.add_system::<UnarmoredChargeSystem>();
+        world.build_scheduler();
+
+        let entities: Vec<Entity> = (0..3).map(|_| world.spawn()).collect();
+        for &e in &entities {
+            world.set(e, &Charge::default());
+            world.set(e, &Armor {});
+        }
+        world.run();
+        let removed_at = world.current_tick();
+        world.storage_mut::<Armor>().remove(entities[1].index());
+
+        world.run();
+        world.run();
+        let values: Vec<i32> = entities
+            .iter()
+            .map(|&e| world.get::<Charge>(e).unwrap().value)
+            .collect();
+        assert_eq!(values, vec![0, 1, 0]);
+
+        // A removal between ticks belongs to the next tick, so it is seen one tick later;
+        // resimulating from before it sees it again
+        world.rollback(removed_at);
+        world.run();
+        let armor = world.storage::<Armor>().unwrap();
+        assert!(armor.removed_value(entities[1].index()).is_some());
+        drop(armor);
+        world.run();
+        assert_eq!(world.get::<Charge>(entities[1]).unwrap().value, 1);
+    }
+
+    #[derive(Component, Default, Clone, Debug, PartialEq)]
+    struct Rate {
+        value: i32,
+    }
+
+    system! {
+        ParallelChargeSystem {
+            query! {
+                fn charge(charge: &mut ViewMut<Charge>, rate: View<Rate>) None=[Armor] Parallel = true {
+                    charge.value += rate.value;
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn parallel_query_matches_sequential_and_rolls_back() {
+        let mut world = World::new();
+        world.get_storage::<Charge>();
+        world.get_storage::<Rate>();
+        world.get_storage::<Armor>();
+        world.add_system::<ParallelChargeSystem>();
+        world.build_scheduler();
+
+        // Spread over several inner blocks, with gaps
+        let entities: Vec<Entity> = (0..1000).map(|_| world.spawn()).collect();
+        for (i, &e) in entities.iter().enumerate() {
+            world.set(e, &Charge::default());
+            if !i.is_multiple_of(4) {
+                world.set(e, &Rate { value: i as i32 });
+            }
+            if i.is_multiple_of(5) {
+                world.set(e, &Armor {});
+            }
+        }
+        let expected = |i: usize, ticks: i32| {
+            if i.is_multiple_of(4) || i.is_multiple_of(5) {
+                0
+            } else {
+                i as i32 * ticks
+            }
+        };
+
+        let first = world.current_tick();
+        world.run();
+        world.run();
+        for (i, &e) in entities.iter().enumerate() {
+            assert_eq!(world.get::<Charge>(e).unwrap().value, expected(i, 2));
+        }
+
+        world.rollback(first);
+        for (i, &e) in entities.iter().enumerate() {
+            assert_eq!(world.get::<Charge>(e).unwrap().value, expected(i, 1));
+        }
+    }
+
+    system! {
+        ParallelCapSystem {
+            query! {
+                fn cap(charge: &mut ViewMut<Charge>) Parallel = true {
+                    if charge.value > 10 {
+                        charge.value = 10;
+                    }
+                }
+            }
+        }
+    }
+
+    #[derive(Clone, Default)]
+    struct Capped(Vec<i32>);
+
+    system! {
+        CappedSystem {
+            query! {
+                fn capped(charge: View<Charge>, capped: ResMut<Capped>) Changed=[Charge] After=[ParallelCapSystem] {
+                    capped.0.push(charge.value);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn parallel_query_only_marks_written_components() {
+        let mut world = World::new();
+        world.get_storage::<Charge>();
+        world.add_system::<ParallelCapSystem>();
+        world.add_system::<CappedSystem>();
+        world.build_scheduler();
+        world.insert_resource(Capped::default());
+
+        let entities: Vec<Entity> = (0..300).map(|_| world.spawn()).collect();
+        world.run();
+        for (i, &e) in entities.iter().enumerate() {
+            world.set(e, &Charge { value: i as i32 });
+        }
+        world.run();
+        world.resource_mut::<Capped>().unwrap().0.clear();
+
+        let capped_at = world.current_tick();
+        world.storage_mut::<Charge>().get_mut(entities[5].index()).value = 50;
+        world.run();
+        // Nothing else crossed the cap since the last tick, so only the write is a change
+        assert_eq!(world.resource::<Capped>().unwrap().0, vec![10]);
+        assert_eq!(world.get::<Charge>(entities[5]).unwrap().value, 10);
+
+        world.rollback(crate::tick::Tick::new(capped_at.value() - 1));
+        assert_eq!(world.get::<Charge>(entities[5]).unwrap().value, 5);
+        assert_eq!(world.get::<Charge>(entities[200]).unwrap().value, 10);
+    }
 }
@@ -0,0 +1,166 @@
+//! Bitset tag groups.
+//!
+//! Boolean markers modelled as full components each cost a storage and a query clause.
+//! `TagSet` packs up to 64 user-defined tags into a single `u64` component, so systems can
+//! filter on many flags with one storage and a mask check per entity.
+//!
+//! A `TagId` is a bit index. Declare tags as constants with `TagId::from_index`, or
+//! register them by name per world with `World::register_tag` (or `WorldBuilder::tag`),
+//! which hands out bits from 0 up in registration order; keep constants clear of those, for
+//! example by counting down from 63. Both give every peer the same bits, as long as names
+//! are registered up front, in the same order, before the systems that filter on them are
+//! added.
+//!
+//! In `Has=[...]` / `Not=[...]` clauses, `tag("name")` looks the name up in the world the
+//! system is created for. The masks are computed once when the system is created, so
+//! running it costs only the per-entity bit checks.
+//!
+//! # Example
+//! ```ignore
+//! const BURNING: TagId = TagId::from_index(63);
+//!
+//! let frozen = world.register_tag("frozen");
+//! world.add_tag(entity, frozen);
+//!
+//! system! {
+//!     ThawSystem {
+//!         query! {
+//!             fn thaw(pos: &mut ViewMut<Position>) Has=[tag("frozen")] Not=[BURNING] {
+//!                 // Only entities tagged "frozen" and not "burning"
+//!             }
+//!         }
+//!     }
+//! }
+//! ```
+
+use crate::component::Component;
+
+/// Maximum number of distinct tags a `TagSet` can hold.
+pub const MAX_TAGS: usize = 64;
+
+/// Identifier of a registered tag (its bit index inside a `TagSet`).
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct TagId(u8);
+
+impl TagId {
+    /// Creates a tag id from a raw bit index.
+    ///
+    /// # Panics
+    /// Panics if `index >= MAX_TAGS`.
+    pub const fn from_index(index: u8) -> Self {
+        assert!((index as usize) < MAX_TAGS, "TagId index out of range");
+        TagId(index)
+    }
+
+    /// Returns the bit index of this tag.
+    #[inline(always)]
+    pub fn index(self) -> u8 {
+        self.0
+    }
+
+    /// Returns the single-bit mask of this tag.
+    #[inline(always)]
+    pub fn bit(self) -> u64 {
+        1u64 << self.0
+    }
+}
+
+/// Tag names registered with a world, in registration order.
+#[derive(Default)]
+pub(crate) struct TagRegistry {
+    names: Vec<&'static str>,
+}
+
+impl TagRegistry {
+    /// See `World::register_tag`.
+    pub(crate) fn register(&mut self, name: &'static str) -> TagId {
+        if let Some(id) = self.get(name) {
+            return id;
+        }
+
+        if self.names.len() >= MAX_TAGS {
+            panic!(
+                "Cannot register tag '{}': at most {} tags are supported",
+                name, MAX_TAGS
+            );
+        }
+
+        self.names.push(name);
+        TagId((self.names.len() - 1) as u8)
+    }
+
+    pub(crate) fn get(&self, name: &str) -> Option<TagId> {
+        let index = self.names.iter().position(|n| *n == name)?;
+        Some(TagId(index as u8))
+    }
+
+    pub(crate) fn name(&self, id: TagId) -> Option<&'static str> {
+        self.names.get(id.0 as usize).copied()
+    }
+}
+
+/// Fixed-size bitset of up to 64 tags stored as a single component.
+#[derive(Component, Default, Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct TagSet {
+    pub bits: u64,
+}
+
+impl TagSet {
+    /// Creates an empty tag set.
+    pub fn new() -> Self {
+        TagSet { bits: 0 }
+    }
+
+    /// Creates a tag set containing the given tags.
+    pub fn from_tags(tags: &[TagId]) -> Self {
+        let mut set = TagSet::new();
+        for &t in tags {
+            set.insert(t);
+        }
+        set
+    }
+
+    #[inline(always)]
+    pub fn insert(&mut self, tag: TagId) {
+        self.bits |= tag.bit();
+    }
+
+    #[inline(always)]
+    pub fn remove(&mut self, tag: TagId) {
+        self.bits &= !tag.bit();
+    }
+
+    #[inline(always)]
+    pub fn contains(&self, tag: TagId) -> bool {
+        self.bits & tag.bit() != 0
+    }
+
+    #[inline(always)]
+    pub fn is_empty(&self) -> bool {
+        self.bits == 0
+    }
+
+    /// Returns true if every bit of `has` is set and no bit of `not` is set.
+    /// This is the check the `system!` macro emits for `Has=[...]` / `Not=[...]` clauses.
+    #[inline(always)]
+    pub fn matches(&self, has: u64, not: u64) -> bool {
+        (self.bits & has) == has && (self.bits & not) == 0
+    }
+
+    /// Iterates over the tags contained in this set.
+    pub fn iter(&self) -> impl Iterator<Item = TagId> {
+        let mut bits = self.bits;
+        std::iter::from_fn(move || {
+            if bits == 0 {
+                return None;
+            }
+            let i = bits.trailing_zeros();
+            bits &= !(1u64 << i);
+            Some(TagId(i as u8))
+        })
+    }
+}
+
+#[cfg(test)]
+#[path = "tags.tests.rs"]
+mod tests;
@@ -0,0 +1,132 @@
+use crate::component::Component;
+use crate::prelude::system;
+use crate::tags::{TagId, TagSet, MAX_TAGS};
+use crate::world::World;
+
+#[derive(Component, Default, Clone, Debug, PartialEq)]
+struct Hits {
+    count: u32,
+}
+
+#[test]
+fn test_register_tag_is_idempotent() {
+    let mut world = World::new();
+    let a = world.register_tag("idempotent");
+    let b = world.register_tag("idempotent");
+
+    assert_eq!(a, b);
+    assert_eq!(world.tag("idempotent"), a);
+    assert_eq!(world.tag_name(a), Some("idempotent"));
+    assert!((a.index() as usize) < MAX_TAGS);
+}
+
+#[test]
+fn test_tags_are_numbered_per_world_in_registration_order() {
+    let mut first = World::new();
+    let mut second = World::new();
+    first.register_tag("unrelated");
+
+    for world in [&mut first, &mut second] {
+        world.register_tag("enemy");
+        world.register_tag("shielded");
+    }
+
+    assert_eq!(first.tag("enemy"), TagId::from_index(1));
+    assert_eq!(second.tag("enemy"), TagId::from_index(0));
+    assert_eq!(second.tag("shielded"), TagId::from_index(1));
+}
+
+#[test]
+#[should_panic(expected = "Tag 'missing' is not registered")]
+fn test_unregistered_tag_panics() {
+    World::new().tag("missing");
+}
+
+#[test]
+fn test_tagset_insert_remove_matches() {
+    let a = TagId::from_index(1);
+    let b = TagId::from_index(5);
+
+    let mut set = TagSet::from_tags(&[a]);
+    assert!(set.contains(a));
+    assert!(!set.contains(b));
+    assert!(set.matches(a.bit(), b.bit()));
+
+    set.insert(b);
+    assert!(!set.matches(a.bit(), b.bit()));
+    assert_eq!(set.iter().collect::<Vec<_>>(), vec![a, b]);
+
+    set.remove(a);
+    set.remove(b);
+    assert!(set.is_empty());
+}
+
+#[test]
+fn test_world_add_remove_tag() {
+    let mut world = World::new();
+    let e = world.spawn();
+    let frozen = world.register_tag("frozen");
+
+    assert!(!world.has_tag(e, frozen));
+
+    world.add_tag(e, frozen);
+    assert!(world.has_tag(e, frozen));
+
+    world.remove_tag(e, frozen);
+    assert!(!world.has_tag(e, frozen));
+}
+
+#[test]
+fn test_system_has_and_not_clauses() {
+    const SHIELDED: TagId = TagId::from_index(1);
+
+    system! {
+        TagFilteredSystem {
+            query! {
+                fn hit(hits: &mut ViewMut<Hits>) Has=[tag("enemy")] Not=[tag("shielded")] {
+                    hits.count += 1;
+                }
+            }
+        }
+    }
+
+    system! {
+        NotOnlySystem {
+            query! {
+                fn hit(hits: &mut ViewMut<Hits>) Not=[SHIELDED] {
+                    hits.count += 10;
+                }
+            }
+        }
+    }
+
+    let mut world = World::new();
+    let enemy = world.register_tag("enemy");
+    let shielded = world.register_tag("shielded");
+
+    let plain = world.spawn();
+    let enemy_e = world.spawn();
+    let shielded_enemy = world.spawn();
+
+    for e in [plain, enemy_e, shielded_enemy] {
+        world.set(e, &Hits { count: 0 });
+    }
+
+    world.add_tag(enemy_e, enemy);
+    world.add_tag(shielded_enemy, enemy);
+    world.add_tag(shielded_enemy, shielded);
+
+    world.run_system::<TagFilteredSystem>();
+
+    let hits = world.get_storage::<Hits>();
+    assert_eq!(unsafe { (*hits.get()).get(plain.index()).unwrap().count }, 0);
+    assert_eq!(unsafe { (*hits.get()).get(enemy_e.index()).unwrap().count }, 1);
+    assert_eq!(unsafe { (*hits.get()).get(shielded_enemy.index()).unwrap().count }, 0);
+
+    // Entities without any TagSet pass a pure Not filter
+    world.run_system::<NotOnlySystem>();
+
+    assert_eq!(unsafe { (*hits.get()).get(plain.index()).unwrap().count }, 10);
+    assert_eq!(unsafe { (*hits.get()).get(enemy_e.index()).unwrap().count }, 11);
+    assert_eq!(unsafe { (*hits.get()).get(shielded_enemy.index()).unwrap().count }, 0);
+}
@@ -0,0 +1,330 @@
+//! Readable diffs for storage and world equality assertions in tests.
+//!
+//! Rollback and parallel-equivalence tests usually compare two worlds that should be
+//! identical. Asserting one value at a time stops at the first mismatch and prints raw
+//! values; the helpers here collect every difference and render them as a table keyed by
+//! entity index, narrowing mismatched values down to the differing fields.
+//!
+//! Field-level differences are derived from the pretty `Debug` output of the component,
+//! so any `#[derive(Debug)]` type gets them for free.
+//!
+//! # Example
+//! ```ignore
+//! testing::register_comparable::<Position>();
+//! testing::assert_world_eq(&rolled_back, &reference);
+//! ```
+
+use crate::component::Component;
+use crate::rollback::StorageLike;
+use crate::storage::ComponentStorage;
+use crate::world::World;
+use std::cell::UnsafeCell;
+use std::fmt::Debug;
+use std::rc::Rc;
+use std::sync::Mutex;
+
+/// A single mismatching entity in a storage comparison.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct DiffRow {
+    pub index: u32,
+    pub left: String,
+    pub right: String,
+}
+
+const MISSING: &str = "<missing>";
+const PRESENT: &str = "<present>";
+
+/// Renders the fields that differ between two values.
+/// Falls back to the full compact `Debug` output when the shapes differ.
+fn field_diff<T: Debug>(left: &T, right: &T) -> (String, String) {
+    let left_pretty = format!("{:#?}", left);
+    let right_pretty = format!("{:#?}", right);
+    let left_lines: Vec<&str> = left_pretty.lines().collect();
+    let right_lines: Vec<&str> = right_pretty.lines().collect();
+
+    if left_lines.len() != right_lines.len() || left_lines.len() <= 1 {
+        return (format!("{:?}", left), format!("{:?}", right));
+    }
+
+    let mut left_fields = Vec::new();
+    let mut right_fields = Vec::new();
+
+    for (l, r) in left_lines.iter().zip(right_lines.iter()) {
+        if l != r {
+            left_fields.push(l.trim().trim_end_matches(',').to_string());
+            right_fields.push(r.trim().trim_end_matches(',').to_string());
+        }
+    }
+
+    (left_fields.join(", "), right_fields.join(", "))
+}
+
+/// Collects every entity whose component differs between two storages.
+pub fn storage_diff<S: ComponentStorage>(left: &S, right: &S) -> Vec<DiffRow>
+where
+    S::Item: PartialEq + Debug,
+{
+    let (mut left_entries, mut right_entries) = (Vec::new(), Vec::new());
+    left.visit(|index, value| left_entries.push((index, value)));
+    right.visit(|index, value| right_entries.push((index, value)));
+
+    let mut rows = Vec::new();
+    let mut l = left_entries.into_iter().peekable();
+    let mut r = right_entries.into_iter().peekable();
+
+    loop {
+        match (l.peek(), r.peek()) {
+            (None, None) => break,
+            (Some(&(li, lv)), Some(&(ri, rv))) if li == ri => {
+                if lv != rv {
+                    let (left, right) = field_diff(lv, rv);
+                    rows.push(DiffRow {
+                        index: li,
+                        left,
+                        right,
+                    });
+                }
+                l.next();
+                r.next();
+            }
+            (Some(&(li, lv)), Some(&(ri, _))) if li < ri => {
+                rows.push(DiffRow {
+                    index: li,
+                    left: format!("{:?}", lv),
+                    right: MISSING.to_string(),
+                });
+                l.next();
+            }
+            (Some(&(li, lv)), None) => {
+                rows.push(DiffRow {
+                    index: li,
+                    left: format!("{:?}", lv),
+                    right: MISSING.to_string(),
+                });
+                l.next();
+            }
+            (_, Some(&(ri, rv))) => {
+                rows.push(DiffRow {
+                    index: ri,
+                    left: MISSING.to_string(),
+                    right: format!("{:?}", rv),
+                });
+                r.next();
+            }
+        }
+    }
+
+    rows
+}
+
+/// Renders diff rows as an aligned `entity | left | right` table.
+pub fn render_table(rows: &[DiffRow]) -> String {
+    let index_width = rows
+        .iter()
+        .map(|r| r.index.to_string().len())
+        .max()
+        .unwrap_or(0)
+        .max("entity".len());
+    let left_width = rows
+        .iter()
+        .map(|r| r.left.len())
+        .max()
+        .unwrap_or(0)
+        .max("left".len());
+
+    let mut out = format!(
+        " {:>iw$} | {:<lw$} | right\n",
+        "entity",
+        "left",
+        iw = index_width,
+        lw = left_width
+    );
+    out.push_str(&format!(
+        "-{}-+-{}-+-{}\n",
+        "-".repeat(index_width),
+        "-".repeat(left_width),
+        "-".repeat(5)
+    ));
+
+    for row in rows {
+        out.push_str(&format!(
+            " {:>iw$} | {:<lw$} | {}\n",
+            row.index,
+            row.left,
+            row.right,
+            iw = index_width,
+            lw = left_width
+        ));
+    }
+
+    out
+}
+
+/// Asserts two storages hold equal components for the same entities.
+///
+/// # Panics
+/// Panics with a table of every differing entity.
+pub fn assert_storage_eq<S: ComponentStorage>(left: &S, right: &S)
+where
+    S::Item: PartialEq + Debug,
+{
+    let rows = storage_diff(left, right);
+
+    if !rows.is_empty() {
+        panic!(
+            "assert_storage_eq failed for `{}` ({} differing entities)\n{}",
+            std::any::type_name::<S::Item>(),
+            rows.len(),
+            render_table(&rows)
+        );
+    }
+}
+
+type StorageComparator = fn(&dyn StorageLike, &dyn StorageLike) -> Vec<DiffRow>;
+
+static COMPARATORS: Mutex<Vec<(usize, StorageComparator)>> = Mutex::new(Vec::new());
+
+fn compare_erased<T: Component + PartialEq + Debug>(
+    left: &dyn StorageLike,
+    right: &dyn StorageLike,
+) -> Vec<DiffRow> {
+    let downcast = |s: &dyn StorageLike| unsafe {
+        let raw = s.as_any() as *const dyn std::any::Any as *const Rc<UnsafeCell<T::Storage>>;
+        (*raw).clone()
+    };
+
+    let left = downcast(left);
+    let right = downcast(right);
+
+    unsafe { storage_diff(&*left.get(), &*right.get()) }
+}
+
+/// Registers `T` so `assert_world_eq` compares its values, not just which entities have it.
+/// `Component` does not require `PartialEq` or `Debug`, so value comparison is opt-in.
+pub fn register_comparable<T: Component + PartialEq + Debug>() {
+    let mut comparators = COMPARATORS.lock().expect("Comparator registry poisoned");
+    let id = T::type_index();
+
+    if !comparators.iter().any(|(i, _)| *i == id) {
+        comparators.push((id, compare_erased::<T>));
+    }
+}
+
+fn comparator_for(type_index: usize) -> Option<StorageComparator> {
+    let comparators = COMPARATORS.lock().expect("Comparator registry poisoned");
+    comparators
+        .iter()
+        .find(|(i, _)| *i == type_index)
+        .map(|(_, c)| *c)
+}
+
+/// Collects the differences between two worlds, one rendered section per component type.
+/// Returns an empty vector if the worlds are equal.
+///
+/// Storages that exist in only one world are ignored when they are empty. Component types
+/// registered with `register_comparable` are compared by value; all other types are
+/// compared by which entities have them.
+pub fn world_diff(left: &World, right: &World) -> Vec<String> {
+    let mut sections = Vec::new();
+
+    if left.current_tick() != right.current_tick() {
+        sections.push(format!(
+            "current tick: left {:?}, right {:?}\n",
+            left.current_tick(),
+            right.current_tick()
+        ));
+    }
+
+    for id in (left.mask | right.mask).iter() {
+        let l = if left.mask.contains(id) {
+            Some(unsafe { left.storages[id].assume_init_ref().as_ref() })
+        } else {
+            None
+        };
+        let r = if right.mask.contains(id) {
+            Some(unsafe { right.storages[id].assume_init_ref().as_ref() })
+        } else {
+            None
+        };
+
+        let name = l.or(r).map(|s| s.type_name()).unwrap_or("<unknown>");
+
+        let rows = match (l, r) {
+            (Some(l), Some(r)) => match comparator_for(id) {
+                Some(compare) => compare(l, r),
+                None => presence_rows(&l.indices(), &r.indices()),
+            },
+            (Some(l), None) => presence_rows(&l.indices(), &[]),
+            (None, Some(r)) => presence_rows(&[], &r.indices()),
+            (None, None) => Vec::new(),
+        };
+
+        if !rows.is_empty() {
+            sections.push(format!(
+                "`{}` ({} differing entities)\n{}",
+                name,
+                rows.len(),
+                render_table(&rows)
+            ));
+        }
+    }
+
+    sections
+}
+
+fn presence_rows(left: &[u32], right: &[u32]) -> Vec<DiffRow> {
+    let mut rows = Vec::new();
+    let (mut i, mut j) = (0, 0);
+
+    while i < left.len() || j < right.len() {
+        match (left.get(i), right.get(j)) {
+            (Some(l), Some(r)) if l == r => {
+                i += 1;
+                j += 1;
+            }
+            (Some(&l), Some(&r)) if l < r => {
+                rows.push(DiffRow {
+                    index: l,
+                    left: PRESENT.to_string(),
+                    right: MISSING.to_string(),
+                });
+                i += 1;
+            }
+            (Some(&l), None) => {
+                rows.push(DiffRow {
+                    index: l,
+                    left: PRESENT.to_string(),
+                    right: MISSING.to_string(),
+                });
+                i += 1;
+            }
+            (_, Some(&r)) => {
+                rows.push(DiffRow {
+                    index: r,
+                    left: MISSING.to_string(),
+                    right: PRESENT.to_string(),
+                });
+                j += 1;
+            }
+            (None, None) => break,
+        }
+    }
+
+    rows
+}
+
+/// Asserts two worlds hold the same components for the same entities.
+///
+/// # Panics
+/// Panics with a per-component table of every differing entity.
+pub fn assert_world_eq(left: &World, right: &World) {
+    let sections = world_diff(left, right);
+
+    if !sections.is_empty() {
+        panic!("assert_world_eq failed\n\n{}", sections.join("\n"));
+    }
+}
+
+#[cfg(test)]
+#[path = "testing.tests.rs"]
+mod tests;
@@ -0,0 +1,104 @@
+use crate::component::Component;
+use crate::storage::Storage;
+use crate::testing::{
+    assert_storage_eq, assert_world_eq, register_comparable, render_table, storage_diff, world_diff,
+    DiffRow,
+};
+use crate::world::World;
+
+#[derive(Component, Default, Clone, Debug, PartialEq)]
+struct Position {
+    x: i32,
+    y: i32,
+}
+
+#[derive(Component, Default, Clone, Debug, PartialEq)]
+struct Marker {}
+
+#[test]
+fn test_storage_diff_reports_fields_and_missing() {
+    let mut left = Storage::<Position>::new();
+    let mut right = Storage::<Position>::new();
+
+    left.set(1, &Position { x: 1, y: 2 });
+    right.set(1, &Position { x: 1, y: 3 });
+    left.set(5, &Position { x: 0, y: 0 });
+    right.set(200, &Position { x: 7, y: 7 });
+    left.set(9, &Position { x: 4, y: 4 });
+    right.set(9, &Position { x: 4, y: 4 });
+
+    let rows = storage_diff(&left, &right);
+
+    assert_eq!(
+        rows,
+        vec![
+            DiffRow {
+                index: 1,
+                left: "y: 2".to_string(),
+                right: "y: 3".to_string(),
+            },
+            DiffRow {
+                index: 5,
+                left: "Position { x: 0, y: 0 }".to_string(),
+                right: "<missing>".to_string(),
+            },
+            DiffRow {
+                index: 200,
+                left: "<missing>".to_string(),
+                right: "Position { x: 7, y: 7 }".to_string(),
+            },
+        ]
+    );
+
+    let table = render_table(&rows);
+    assert!(table.contains("entity | left"));
+    assert!(table.contains("      1 | y: 2"));
+}
+
+#[test]
+#[should_panic(expected = "assert_storage_eq failed")]
+fn test_assert_storage_eq_panics_on_difference() {
+    let mut left = Storage::<Position>::new();
+    let right = Storage::<Position>::new();
+    left.set(3, &Position { x: 1, y: 1 });
+
+    assert_storage_eq(&left, &right);
+}
+
+#[test]
+fn test_assert_world_eq_equal_worlds() {
+    register_comparable::<Position>();
+
+    let mut a = World::new();
+    let mut b = World::new();
+
+    for world in [&mut a, &mut b] {
+        let e = world.spawn();
+        world.set(e, &Position { x: 3, y: 4 });
+    }
+
+    // An empty storage on one side only does not count as a difference
+    b.get_storage::<Marker>();
+
+    assert_world_eq(&a, &b);
+}
+
+#[test]
+fn test_world_diff_by_value_and_presence() {
+    register_comparable::<Position>();
+
+    let mut a = World::new();
+    let mut b = World::new();
+
+    let ea = a.spawn();
+    let eb = b.spawn();
+    a.set(ea, &Position { x: 1, y: 1 });
+    b.set(eb, &Position { x: 2, y: 1 });
+    a.set(ea, &Marker {});
+
+    let sections = world_diff(&a, &b);
+
+    assert_eq!(sections.len(), 2);
+    assert!(sections.iter().any(|s| s.contains("Position") && s.contains("x: 1") && s.contains("x: 2")));
+    assert!(sections.iter().any(|s| s.contains("Marker") && s.contains("<present>")));
+}
@@ -0,0 +1,160 @@
+//! Simulation rate metadata and deterministic mid-match rate changes.
+//!
+//! The world doesn't own a wall clock; `World::advance(dt)` turns the time the caller
+//! measured into ticks of `tick_duration()` each. Games change how much time one tick
+//! stands for: a slow lobby rate before the match, slow-motion effects, a faster rate for
+//! a final round. `TickRateLog` records the rate in ticks per second as a starting rate plus
+//! change points keyed by tick, so the rate of any tick is a pure function of the log.
+//!
+//! `World::rescale_tick_rate` adds a change point at a tick that hasn't been simulated
+//! yet. Change points are inputs, not state: rollback keeps them, and resimulated ticks
+//! see the same rate they had the first time. The log is `Wire` encodable so it can travel
+//! with the replay or input stream; a replay or late joiner that loads it with
+//! `World::load_tick_rate_log` switches rate at the same tick as everybody else.
+//!
+//! # Example
+//! ```ignore
+//! world.set_tick_rate(30);
+//! // ...lobby...
+//! let start = world.current_tick();
+//! world.rescale_tick_rate(60, start);
+//! send(&world.tick_rate_log().to_bytes());
+//!
+//! // Late joiner
+//! joiner.load_tick_rate_log(TickRateLog::from_bytes(&bytes)?);
+//! let dt = joiner.tick_duration();
+//! ```
+
+use crate::tick::Tick;
+use crate::wire::{DecodeError, Reader, Wire};
+use std::time::Duration;
+
+/// Rate a world starts with unless `World::set_tick_rate` says otherwise.
+pub const DEFAULT_TICK_RATE: u32 = 60;
+
+/// A switch to `rate` ticks per second, effective from tick `at` on.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct TickRateChange {
+    pub at: Tick,
+    pub rate: u32,
+}
+
+impl Wire for TickRateChange {
+    fn encode(&self, out: &mut Vec<u8>) {
+        self.at.encode(out);
+        self.rate.encode(out);
+    }
+
+    fn decode(reader: &mut Reader<'_>) -> Result<Self, DecodeError> {
+        let at = Tick::decode(reader)?;
+        let rate = u32::decode(reader)?;
+        if rate == 0 {
+            return Err(DecodeError::InvalidValue("tick rate must be positive"));
+        }
+        Ok(TickRateChange { at, rate })
+    }
+}
+
+/// The starting rate and every rate change, ordered by tick.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct TickRateLog {
+    initial: u32,
+    changes: Vec<TickRateChange>,
+}
+
+impl TickRateLog {
+    /// # Panics
+    /// Panics if `initial` is zero.
+    pub fn new(initial: u32) -> Self {
+        assert!(initial > 0, "tick rate must be positive");
+        TickRateLog {
+            initial,
+            changes: Vec::new(),
+        }
+    }
+
+    /// Rate before the first change point.
+    pub fn initial(&self) -> u32 {
+        self.initial
+    }
+
+    /// Change points, ordered by tick.
+    pub fn changes(&self) -> &[TickRateChange] {
+        &self.changes
+    }
+
+    /// Rate in effect at `tick`.
+    pub fn rate_at(&self, tick: Tick) -> u32 {
+        let after = self.changes.partition_point(|c| c.at <= tick);
+        match after {
+            0 => self.initial,
+            n => self.changes[n - 1].rate,
+        }
+    }
+
+    /// Records a change point, replacing any other change at the same tick.
+    ///
+    /// # Panics
+    /// Panics if `change.rate` is zero.
+    pub fn record(&mut self, change: TickRateChange) {
+        assert!(change.rate > 0, "tick rate must be positive");
+        match self.changes.binary_search_by(|c| c.at.cmp(&change.at)) {
+            Ok(i) => self.changes[i] = change,
+            Err(i) => self.changes.insert(i, change),
+        }
+    }
+
+    pub(crate) fn set_initial(&mut self, rate: u32) {
+        assert!(rate > 0, "tick rate must be positive");
+        self.initial = rate;
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        self.encode(&mut out);
+        out
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, DecodeError> {
+        let mut reader = Reader::new(bytes);
+        let log = Self::decode(&mut reader)?;
+        if reader.remaining() > 0 {
+            return Err(DecodeError::TrailingBytes(reader.remaining()));
+        }
+        Ok(log)
+    }
+}
+
+impl Default for TickRateLog {
+    fn default() -> Self {
+        Self::new(DEFAULT_TICK_RATE)
+    }
+}
+
+impl Wire for TickRateLog {
+    fn encode(&self, out: &mut Vec<u8>) {
+        self.initial.encode(out);
+        self.changes.encode(out);
+    }
+
+    fn decode(reader: &mut Reader<'_>) -> Result<Self, DecodeError> {
+        let initial = u32::decode(reader)?;
+        if initial == 0 {
+            return Err(DecodeError::InvalidValue("tick rate must be positive"));
+        }
+        let changes = Vec::<TickRateChange>::decode(reader)?;
+        if changes.windows(2).any(|w| w[0].at >= w[1].at) {
+            return Err(DecodeError::InvalidValue("tick rate changes out of order"));
+        }
+        Ok(TickRateLog { initial, changes })
+    }
+}
+
+/// Length of one tick at `rate` ticks per second.
+pub fn tick_duration(rate: u32) -> Duration {
+    Duration::from_secs(1) / rate
+}
+
+#[cfg(test)]
+#[path = "tickrate.tests.rs"]
+mod tests;
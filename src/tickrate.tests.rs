@@ -0,0 +1,55 @@
+use super::*;
+
+fn change(at: u32, rate: u32) -> TickRateChange {
+    TickRateChange {
+        at: Tick::new(at),
+        rate,
+    }
+}
+
+#[test]
+fn test_rate_at_follows_change_points() {
+    let mut log = TickRateLog::new(30);
+    log.record(change(20, 120));
+    log.record(change(10, 60));
+
+    assert_eq!(log.rate_at(Tick::new(0)), 30);
+    assert_eq!(log.rate_at(Tick::new(9)), 30);
+    assert_eq!(log.rate_at(Tick::new(10)), 60);
+    assert_eq!(log.rate_at(Tick::new(19)), 60);
+    assert_eq!(log.rate_at(Tick::new(500)), 120);
+
+    // Recording at the same tick replaces
+    log.record(change(10, 15));
+    assert_eq!(log.changes(), &[change(10, 15), change(20, 120)]);
+    assert_eq!(
+        tick_duration(log.rate_at(Tick::new(10))),
+        Duration::from_secs(1) / 15
+    );
+}
+
+#[test]
+fn test_log_wire_round_trip_and_validation() {
+    let mut log = TickRateLog::new(30);
+    log.record(change(5, 60));
+    log.record(change(8, 20));
+    let bytes = log.to_bytes();
+    assert_eq!(TickRateLog::from_bytes(&bytes), Ok(log));
+
+    let mut trailing = bytes.clone();
+    trailing.push(0);
+    assert_eq!(
+        TickRateLog::from_bytes(&trailing),
+        Err(DecodeError::TrailingBytes(1))
+    );
+
+    let mut zero = Vec::new();
+    0u32.encode(&mut zero);
+    Vec::<TickRateChange>::new().encode(&mut zero);
+    assert!(TickRateLog::from_bytes(&zero).is_err());
+
+    let mut unordered = Vec::new();
+    30u32.encode(&mut unordered);
+    vec![change(8, 20), change(5, 60)].encode(&mut unordered);
+    assert!(TickRateLog::from_bytes(&unordered).is_err());
+}
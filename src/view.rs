@@ -1,6 +1,8 @@
 use crate::component::Component;
-use crate::storage::Storage;
+use crate::storage::ComponentStorage;
+use std::cell::UnsafeCell;
 use std::ops::{Deref, DerefMut};
+use std::rc::Rc;
 
 pub struct View<'a, T: Component> {
     pub data: &'a T,
@@ -20,14 +22,58 @@ impl<'a, T: Component> Deref for View<'a, T> {
     }
 }
 
+/// Mutable access to a component from a system. The component is marked changed, and its
+/// previous value recorded for rollback, on the first mutable access only, so a system
+/// that reads through a `ViewMut` and writes conditionally leaves unwritten components
+/// unchanged.
 pub struct ViewMut<'a, T: Component> {
-    pub storage: &'a mut Storage<T>,
-    pub index: u32,
+    target: Target<'a, T>,
+}
+
+enum Target<'a, T: Component> {
+    /// Marked changed on the first mutable access.
+    Tracked { storage: &'a mut T::Storage, index: u32 },
+    /// Slot of a `Parallel = true` task, which keeps the value before the first mutable
+    /// access in `previous` for the calling thread to record once the tasks finished.
+    Deferred {
+        value: &'a mut T,
+        previous: &'a mut Option<T>,
+    },
 }
 
 impl<'a, T: Component + PartialEq + Clone> ViewMut<'a, T> {
-    pub fn new(storage: &'a mut Storage<T>, index: u32) -> Self {
-        Self { storage, index }
+    pub fn new(storage: &'a mut T::Storage, index: u32) -> Self {
+        Self {
+            target: Target::Tracked { storage, index },
+        }
+    }
+
+    /// A view of a component whose change is recorded later with
+    /// `ComponentStorage::record_write`, from the value left in `previous`.
+    #[doc(hidden)]
+    pub fn deferred(value: &'a mut T, previous: &'a mut Option<T>) -> Self {
+        Self {
+            target: Target::Deferred { value, previous },
+        }
+    }
+}
+
+impl<'a, T: Component> ViewMut<'a, T> {
+    /// Writes `value` to the field `get_mut` selects unless `get` already reads an equal
+    /// value, and returns whether it wrote. An unchanged value leaves the component
+    /// unmarked, so `Changed` filters skip it and no rollback snapshot is taken. Backs the
+    /// setters `#[component(track_fields)]` generates.
+    pub fn set_field<F: PartialEq>(
+        &mut self,
+        get: impl FnOnce(&T) -> &F,
+        get_mut: impl FnOnce(&mut T) -> &mut F,
+        value: F,
+    ) -> bool {
+        if *get(&**self) == value {
+            return false;
+        }
+        *get_mut(&mut **self) = value;
+        true
     }
 }
 
@@ -35,12 +81,95 @@ impl<'a, T: Component> Deref for ViewMut<'a, T> {
     type Target = T;
 
     fn deref(&self) -> &Self::Target {
-        self.storage.get(self.index).expect("Index out of bounds")
+        match &self.target {
+            Target::Tracked { storage, index } => storage.get(*index).expect("Index out of bounds"),
+            Target::Deferred { value, .. } => value,
+        }
     }
 }
 
 impl<'a, T: Component> DerefMut for ViewMut<'a, T> {
     fn deref_mut(&mut self) -> &mut Self::Target {
-        self.storage.get_mut(self.index)
+        match &mut self.target {
+            Target::Tracked { storage, index } => storage.get_mut(*index),
+            Target::Deferred { value, previous } => {
+                if previous.is_none() {
+                    **previous = Some((**value).clone());
+                }
+                value
+            }
+        }
     }
 }
+
+/// Read-only access to every component of a storage, for systems computing aggregates
+/// (team health, unit centroids). Declared as a read of `T`, so the scheduler never runs
+/// it alongside a writer of `T`.
+///
+/// Traversal is always in ascending entity index order, the same on every peer and for
+/// parallel and sequential runs, so floating-point folds are reproducible.
+///
+/// # Example
+/// ```ignore
+/// system! {
+///     TeamHealthSystem {
+///         query! {
+///             fn total(health: Aggregate<Health>, out: Mailbox<u64, HudSystem>) {
+///                 out.send(health.fold(0, |acc, _, h| acc + h.value as u64));
+///             }
+///         }
+///     }
+/// }
+/// ```
+pub struct Aggregate<T: Component> {
+    storage: Rc<UnsafeCell<T::Storage>>,
+}
+
+impl<T: Component> Aggregate<T> {
+    pub fn new(storage: Rc<UnsafeCell<T::Storage>>) -> Self {
+        Self { storage }
+    }
+
+    fn storage(&self) -> &T::Storage {
+        // The system declares T as read, so no writer runs while it does
+        unsafe { &*self.storage.get() }
+    }
+
+    /// Folds every component with its entity index, in ascending index order.
+    pub fn fold<A>(&self, init: A, mut f: impl FnMut(A, u32, &T) -> A) -> A {
+        let mut acc = Some(init);
+        self.storage().visit(|index, value| {
+            acc = Some(f(acc.take().expect("accumulator is always put back"), index, value));
+        });
+        acc.expect("accumulator is always put back")
+    }
+
+    /// Maps every component and combines the results left to right, in ascending index
+    /// order. Returns `None` for an empty storage.
+    pub fn reduce<R>(
+        &self,
+        mut map: impl FnMut(u32, &T) -> R,
+        mut combine: impl FnMut(R, R) -> R,
+    ) -> Option<R> {
+        self.fold(None, |acc, index, value| {
+            let mapped = map(index, value);
+            Some(match acc {
+                Some(acc) => combine(acc, mapped),
+                None => mapped,
+            })
+        })
+    }
+
+    /// Number of components in the storage.
+    pub fn count(&self) -> usize {
+        self.storage().len()
+    }
+
+    pub fn get(&self, index: u32) -> Option<&T> {
+        self.storage().get(index)
+    }
+}
+
+#[cfg(test)]
+#[path = "view.tests.rs"]
+mod tests;
@@ -0,0 +1,153 @@
+use super::*;
+use crate::prelude::system;
+use crate::world::World;
+use std::any::TypeId;
+use std::sync::Mutex;
+
+#[derive(Component, Default, Clone, Debug, PartialEq)]
+struct Health {
+    value: u32,
+}
+
+static TOTALS: Mutex<Vec<(u32, Vec<u32>)>> = Mutex::new(Vec::new());
+
+system! {
+    TeamHealthSystem {
+        query! {
+            fn total(health: Aggregate<Health>) {
+                let total = health.fold(0, |acc, _, h| acc + h.value);
+                let order = health.fold(Vec::new(), |mut order, index, _| {
+                    order.push(index);
+                    order
+                });
+                TOTALS.lock().unwrap().push((total, order));
+            }
+        }
+    }
+}
+
+system! {
+    RegenSystem {
+        query! {
+            fn regen(health: &mut ViewMut<Health>) {
+                health.value += 1;
+            }
+        }
+    }
+}
+
+#[test]
+fn test_aggregate_folds_in_index_order_once_per_tick() {
+    let mut world = World::new();
+    world.add_system::<TeamHealthSystem>();
+    world.add_system::<RegenSystem>();
+    world.build_scheduler();
+
+    let entities: Vec<_> = (0..300).map(|_| world.spawn()).collect();
+    for &i in &[250, 3, 129, 40] {
+        world.set(entities[i], &Health { value: 1 });
+    }
+    world.run();
+
+    let totals = std::mem::take(&mut *TOTALS.lock().unwrap());
+    assert_eq!(totals.len(), 1);
+    let (total, order) = &totals[0];
+    // Either side of the regen, but never in the middle of it
+    assert!(*total == 4 || *total == 8);
+    assert_eq!(order, &vec![3, 40, 129, 250]);
+
+    // Declared as a read, so it never shares a wavefront with the writer
+    let scheduler = world.scheduler().unwrap();
+    let stage = scheduler
+        .systems()
+        .find(|s| s.name().ends_with("TeamHealthSystem"))
+        .unwrap();
+    assert_eq!(stage.reads(), &[TypeId::of::<Health>()]);
+    assert!(stage.writes().is_empty());
+    let (aggregate, regen) = (
+        scheduler
+            .systems()
+            .position(|s| s.name().ends_with("TeamHealthSystem"))
+            .unwrap(),
+        scheduler
+            .systems()
+            .position(|s| s.name().ends_with("RegenSystem"))
+            .unwrap(),
+    );
+    assert!(
+        !scheduler
+            .wavefronts()
+            .iter()
+            .any(|w| w.contains(&aggregate) && w.contains(&regen))
+    );
+}
+
+#[test]
+fn test_aggregate_reduce_and_count() {
+    let mut world = World::new();
+    let storage = world.get_storage::<Health>();
+    let aggregate = Aggregate::<Health>::new(storage);
+    assert_eq!(aggregate.reduce(|_, h| h.value, u32::max), None);
+
+    for value in [4, 9, 2] {
+        let e = world.spawn();
+        world.set(e, &Health { value });
+    }
+    assert_eq!(aggregate.count(), 3);
+    assert_eq!(aggregate.reduce(|_, h| h.value, u32::max), Some(9));
+    assert_eq!(aggregate.get(1), Some(&Health { value: 9 }));
+}
+
+#[derive(Component, Default, Clone, Debug, PartialEq)]
+#[component(track_fields)]
+struct Aim {
+    target: u32,
+    angle: i32,
+}
+
+#[derive(Clone, Default)]
+struct AimTarget(u32);
+
+#[derive(Clone, Default)]
+struct AimChanges(Vec<u32>);
+
+system! {
+    AimSystem {
+        query! {
+            fn aim(aim: &mut ViewMut<Aim>, target: Res<AimTarget>) {
+                aim.set_target(target.0);
+                aim.set_angle(0);
+            }
+        }
+    }
+}
+
+system! {
+    AimChangedSystem {
+        query! {
+            fn aim_changed(aim: View<Aim>, changes: ResMut<AimChanges>) Changed=[Aim] After=[AimSystem] {
+                changes.0.push(aim.target);
+            }
+        }
+    }
+}
+
+#[test]
+fn test_track_fields_setters_only_mark_changed_values() {
+    let mut world = World::new();
+    world.add_system::<AimSystem>();
+    world.add_system::<AimChangedSystem>();
+    world.build_scheduler();
+    world.insert_resource(AimTarget(3));
+    world.insert_resource(AimChanges::default());
+    let e = world.spawn();
+    world.set(e, &Aim::default());
+    world.run();
+    world.run();
+    world.resource_mut::<AimTarget>().unwrap().0 = 5;
+    world.run();
+
+    // Only the first tick and the retarget write a new value
+    assert_eq!(world.resource::<AimChanges>().unwrap().0, vec![3, 5]);
+    assert_eq!(world.get::<Aim>(e), Some(&Aim { target: 5, angle: 0 }));
+}
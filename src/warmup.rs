@@ -0,0 +1,91 @@
+//! Preallocating component blocks before a match starts.
+//!
+//! The block storage allocates a middle block the first time an index lands in a new
+//! 16384-entity region and an inner block for each new 128-entity run. When entities spread
+//! into fresh blocks mid-match those allocations show up as hitches. Warming up allocates
+//! and pre-touches every block a storage will need up front; blocks are never freed, so
+//! steady-state ticks don't allocate in the block tree afterwards. Rollback history is
+//! recorded separately and still allocates while ticks run.
+//!
+//! `World::warmup::<T>(max_index)` warms one component. A `WarmupPlan` describes the
+//! expected population as prefabs (component sets with an entity count) and warms every
+//! storage they use, plus the entity and `Destroyed` storages, for the combined population.
+//! Entities take the lowest free index, so a population of `n` uses indices `0..n`.
+//!
+//! Check the result with `World::memory_stats`: the block counts stay unchanged while the
+//! population stays within the plan.
+//!
+//! # Example
+//! ```ignore
+//! let plan = WarmupPlan::new()
+//!     .prefab::<(Position, Velocity, Health)>(64)
+//!     .prefab::<(Position, Projectile)>(2_000);
+//! world.warmup_plan(&plan);
+//! ```
+
+use crate::component::{ComponentSet, Destroyed};
+use crate::entity::Entity;
+use crate::rollback::StorageLike;
+use crate::world::World;
+
+type StorageSet = fn(&mut World) -> Vec<Box<dyn StorageLike>>;
+
+/// Expected entity population, see the module docs.
+#[derive(Clone, Default)]
+pub struct WarmupPlan {
+    prefabs: Vec<(StorageSet, u32)>,
+    headroom: u32,
+}
+
+impl WarmupPlan {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Expects up to `count` entities with the components in `Q`.
+    pub fn prefab<Q: ComponentSet>(mut self, count: u32) -> Self {
+        self.prefabs.push((Q::storages, count));
+        self
+    }
+
+    /// Extra indices to warm beyond the planned population, e.g. for entities destroyed
+    /// and respawned within the same tick.
+    pub fn headroom(mut self, extra: u32) -> Self {
+        self.headroom = extra;
+        self
+    }
+
+    /// Total number of entities the plan covers, headroom included.
+    pub fn population(&self) -> u32 {
+        self.prefabs
+            .iter()
+            .fold(self.headroom, |total, (_, count)| {
+                total.saturating_add(*count)
+            })
+    }
+
+    /// Highest index the plan warms, or `None` for an empty plan.
+    pub fn max_index(&self) -> Option<u32> {
+        self.population().checked_sub(1)
+    }
+
+    pub(crate) fn apply(&self, world: &mut World) {
+        let Some(max_index) = self.max_index() else {
+            return;
+        };
+
+        world.warmup::<Entity>(max_index);
+        world.warmup::<Destroyed>(max_index);
+
+        // Prefabs share the index space, so each component covers the whole population
+        for (storages, _) in &self.prefabs {
+            for storage in storages(world) {
+                storage.warmup(max_index);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+#[path = "warmup.tests.rs"]
+mod tests;
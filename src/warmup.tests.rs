@@ -0,0 +1,98 @@
+use super::*;
+use crate::prelude::*;
+use crate::storage::MemoryStats;
+
+#[derive(Component, Default, Clone, Debug, PartialEq)]
+struct Position {
+    x: i32,
+}
+
+#[derive(Component, Default, Clone, Debug, PartialEq)]
+struct Velocity {
+    dx: i32,
+}
+
+#[derive(Component, Default, Clone, Debug, PartialEq)]
+struct Projectile {}
+
+#[derive(Component, Default, Clone, Debug, PartialEq)]
+#[component(storage = "sparse")]
+struct Rules {}
+
+system! {
+    MoveSystem {
+        query! {
+            fn step(pos: &mut ViewMut<Position>, vel: View<Velocity>) {
+                pos.x += vel.dx;
+            }
+        }
+    }
+}
+
+#[test]
+fn test_warmup_allocates_covering_blocks() {
+    let mut world = World::new();
+    world.warmup::<Position>(300);
+
+    let stats = world.component_memory::<Position>();
+    assert_eq!((stats.middle_blocks, stats.inner_blocks), (1, 3));
+
+    // Crosses into the second middle block
+    world.warmup::<Position>(16384);
+    let stats = world.component_memory::<Position>();
+    assert_eq!((stats.middle_blocks, stats.inner_blocks), (2, 129));
+
+    let e = world.spawn();
+    world.set(e, &Position { x: 1 });
+    assert_eq!(world.component_memory::<Position>(), stats);
+}
+
+#[test]
+fn test_plan_keeps_steady_state_allocation_free() {
+    let mut world = World::new();
+    world.add_system::<MoveSystem>();
+
+    let plan = WarmupPlan::new()
+        .prefab::<(Position, Velocity)>(100)
+        .prefab::<(Position, Projectile)>(200)
+        .headroom(4);
+    assert_eq!(plan.max_index(), Some(303));
+
+    world.warmup_plan(&plan);
+    world.build_scheduler();
+    let warmed: Vec<(&str, MemoryStats)> = world.memory_stats();
+
+    for i in 0..300 {
+        let e = world.spawn();
+        world.set(e, &Position { x: 0 });
+        if i < 100 {
+            world.set(e, &Velocity { dx: 2 });
+        } else {
+            world.set(e, &Projectile {});
+        }
+    }
+
+    for _ in 0..3 {
+        world.run();
+    }
+
+    assert_eq!(world.memory_stats(), warmed);
+    let storage = world.get_storage::<Position>();
+    assert_eq!(unsafe { (*storage.get()).get(0) }, Some(&Position { x: 6 }));
+    assert_eq!(
+        unsafe { (*storage.get()).get(150) },
+        Some(&Position { x: 0 })
+    );
+}
+
+#[test]
+fn test_empty_plan_and_sparse_storage_are_untouched() {
+    let mut world = World::new();
+    world.warmup_plan(&WarmupPlan::new());
+    assert!(world.memory_stats().is_empty());
+
+    let before = world.component_memory::<Rules>();
+    world.warmup::<Rules>(10_000);
+    assert_eq!(world.component_memory::<Rules>(), before);
+    assert_eq!(before.inner_blocks, 0);
+}
@@ -95,3 +95,20 @@ fn test_wasm_system_execution() {
     assert_eq!(pos.unwrap().y, 2.0);
 }
 
+
+// Test that fixed-point math gives the same bits as on native targets
+#[wasm_bindgen_test]
+fn test_wasm_det_math_matches_native() {
+    use crate::det_math::{SWEEP_CHECKSUM, SWEEP_SAMPLES, sweep_checksum};
+
+    assert_eq!(sweep_checksum(SWEEP_SAMPLES), SWEEP_CHECKSUM);
+}
+
+// Test that the Q32.32 `Fx64` types give the same bits as on native targets
+#[wasm_bindgen_test]
+fn test_wasm_fixed_matches_native() {
+    use crate::det_math::SWEEP_SAMPLES;
+    use crate::fixed;
+
+    assert_eq!(fixed::sweep_checksum(SWEEP_SAMPLES), fixed::SWEEP_CHECKSUM);
+}
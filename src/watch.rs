@@ -0,0 +1,194 @@
+//! Retained query results for UI data binding.
+//!
+//! `World::watch_query::<(Health, Player)>()` returns a `QueryWatch` whose member list is
+//! always current: it holds every entity that has all of the watched components. The world
+//! refreshes its watches at the end of every tick and after a rollback, and the UI reads the
+//! members or drains `WatchEvent::Joined` / `WatchEvent::Left` notifications instead of
+//! re-running the query itself.
+//!
+//! A refresh intersects the storages' presence masks block by block and compares them with
+//! the masks seen by the previous refresh, so only blocks that changed produce events and
+//! entities that stay members cost nothing. Call `QueryWatch::refresh` to pick up changes
+//! made between ticks right away.
+//!
+//! Dropping every handle of a watch unregisters it.
+//!
+//! # Example
+//! ```ignore
+//! let players = world.watch_query::<(Player, Health)>();
+//! world.run();
+//! for event in players.drain_events() {
+//!     match event {
+//!         WatchEvent::Joined(e) => ui.add_row(e),
+//!         WatchEvent::Left(e) => ui.remove_row(e),
+//!     }
+//! }
+//! ```
+
+use crate::component::ComponentSet;
+use crate::entity::Entity;
+use crate::rollback::StorageLike;
+use crate::storage::Storage;
+use crate::world::World;
+use std::cell::{RefCell, UnsafeCell};
+use std::collections::{BTreeMap, HashMap};
+use std::rc::Rc;
+
+/// A change in a watch's membership.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum WatchEvent {
+    /// The entity gained the last missing component.
+    Joined(Entity),
+    /// The entity lost one of the components, or was destroyed.
+    Left(Entity),
+}
+
+pub(crate) struct WatchState {
+    storages: Vec<Box<dyn StorageLike>>,
+    entities: Rc<UnsafeCell<Storage<Entity>>>,
+    /// Membership masks from the last refresh, keyed by `ri * 128 + mi`.
+    masks: HashMap<u32, u128>,
+    members: BTreeMap<u32, Entity>,
+    events: Vec<WatchEvent>,
+}
+
+impl WatchState {
+    fn root(&self) -> u128 {
+        self.storages
+            .iter()
+            .fold(u128::MAX, |m, s| m & s.root_mask())
+    }
+
+    fn middle(&self, ri: u32) -> u128 {
+        self.storages
+            .iter()
+            .fold(u128::MAX, |m, s| m & s.middle_mask(ri))
+    }
+
+    fn inner(&self, ri: u32, mi: u32) -> u128 {
+        self.storages
+            .iter()
+            .fold(u128::MAX, |m, s| m & s.inner_mask(ri, mi))
+    }
+
+    fn refresh(&mut self) {
+        let entities = unsafe { &*self.entities.get() };
+
+        // Blocks that are members now, plus blocks that were members last time
+        let mut blocks: Vec<u32> = self.masks.keys().copied().collect();
+        let mut root = self.root();
+        while root != 0 {
+            let ri = root.trailing_zeros();
+            root &= !(1u128 << ri);
+
+            let mut middle = self.middle(ri);
+            while middle != 0 {
+                let mi = middle.trailing_zeros();
+                middle &= !(1u128 << mi);
+                if !self.masks.contains_key(&(ri * 128 + mi)) {
+                    blocks.push(ri * 128 + mi);
+                }
+            }
+        }
+        blocks.sort_unstable();
+
+        for block in blocks {
+            let (ri, mi) = (block / 128, block % 128);
+            let old = self.masks.get(&block).copied().unwrap_or(0);
+            let new = self.inner(ri, mi);
+
+            let mut diff = old ^ new;
+            while diff != 0 {
+                let ii = diff.trailing_zeros();
+                diff &= !(1u128 << ii);
+                let index = block * 128 + ii;
+
+                if (new >> ii) & 1 != 0 {
+                    if let Some(&entity) = entities.get(index) {
+                        self.members.insert(index, entity);
+                        self.events.push(WatchEvent::Joined(entity));
+                    }
+                } else if let Some(entity) = self.members.remove(&index) {
+                    self.events.push(WatchEvent::Left(entity));
+                }
+            }
+
+            if new == 0 {
+                self.masks.remove(&block);
+            } else {
+                self.masks.insert(block, new);
+            }
+        }
+    }
+}
+
+/// Handle to a retained query, see the module docs. Clones share the same watch.
+#[derive(Clone)]
+pub struct QueryWatch {
+    state: Rc<RefCell<WatchState>>,
+}
+
+impl QueryWatch {
+    pub(crate) fn new<Q: ComponentSet>(world: &mut World) -> Self {
+        let state = WatchState {
+            storages: Q::storages(world),
+            entities: world.get_storage::<Entity>(),
+            masks: HashMap::new(),
+            members: BTreeMap::new(),
+            events: Vec::new(),
+        };
+
+        let watch = QueryWatch {
+            state: Rc::new(RefCell::new(state)),
+        };
+        watch.refresh();
+        watch
+    }
+
+    pub(crate) fn state(&self) -> &Rc<RefCell<WatchState>> {
+        &self.state
+    }
+
+    /// Brings the members up to date with the storages. The world calls this at the end of
+    /// every tick and after a rollback.
+    pub fn refresh(&self) {
+        self.state.borrow_mut().refresh();
+    }
+
+    /// Current members in ascending index order.
+    pub fn entities(&self) -> Vec<Entity> {
+        self.state.borrow().members.values().copied().collect()
+    }
+
+    pub fn contains(&self, entity: Entity) -> bool {
+        self.state.borrow().members.get(&entity.index()) == Some(&entity)
+    }
+
+    pub fn len(&self) -> usize {
+        self.state.borrow().members.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Membership changes since the last call, oldest first. Entities present when the
+    /// watch was created are reported as joined.
+    pub fn drain_events(&self) -> Vec<WatchEvent> {
+        std::mem::take(&mut self.state.borrow_mut().events)
+    }
+}
+
+pub(crate) fn refresh_all(watches: &mut Vec<std::rc::Weak<RefCell<WatchState>>>) {
+    watches.retain(|watch| match watch.upgrade() {
+        Some(state) => {
+            state.borrow_mut().refresh();
+            true
+        }
+        None => false,
+    });
+}
+
+#[cfg(test)]
+#[path = "watch.tests.rs"]
+mod tests;
@@ -0,0 +1,98 @@
+use super::*;
+use crate::component::Component;
+use crate::tick::Tick;
+
+#[derive(Component, Default, Clone, Debug, PartialEq)]
+struct Health {
+    hp: u32,
+}
+
+#[derive(Component, Default, Clone, Debug, PartialEq)]
+struct Player {}
+
+fn world() -> World {
+    let mut world = World::new();
+    world.get_storage::<Health>();
+    world.get_storage::<Player>();
+    world.build_scheduler();
+    world
+}
+
+#[test]
+fn test_watch_reports_existing_members_as_joined() {
+    let mut world = world();
+    let a = world.spawn();
+    let b = world.spawn();
+    world.set(a, &Health { hp: 1 });
+    world.set(a, &Player {});
+    world.set(b, &Health { hp: 2 });
+
+    let watch = world.watch_query::<(Health, Player)>();
+    assert_eq!(watch.entities(), vec![a]);
+    assert_eq!(watch.drain_events(), vec![WatchEvent::Joined(a)]);
+    assert!(watch.drain_events().is_empty());
+}
+
+#[test]
+fn test_watch_updates_at_tick_end() {
+    let mut world = world();
+    let watch = world.watch_query::<(Health, Player)>();
+    let a = world.spawn();
+    let b = world.spawn();
+
+    world.set(a, &Health { hp: 1 });
+    world.set(a, &Player {});
+    world.set(b, &Player {});
+    assert!(watch.is_empty());
+
+    world.run();
+    assert_eq!(watch.entities(), vec![a]);
+    assert_eq!(watch.drain_events(), vec![WatchEvent::Joined(a)]);
+
+    world.set(b, &Health { hp: 2 });
+    world.destroy(a);
+    world.run();
+
+    assert_eq!(watch.entities(), vec![b]);
+    assert!(watch.contains(b) && !watch.contains(a));
+    assert_eq!(
+        watch.drain_events(),
+        vec![WatchEvent::Left(a), WatchEvent::Joined(b)]
+    );
+}
+
+#[test]
+fn test_watch_follows_rollback() {
+    let mut world = world();
+    let watch = world.watch_query::<(Health,)>();
+    let a = world.spawn();
+    world.run(); // tick 0
+
+    world.set(a, &Health { hp: 1 });
+    world.run(); // tick 1
+    assert_eq!(watch.len(), 1);
+
+    world.rollback(Tick::new(0));
+    assert!(watch.is_empty());
+    assert_eq!(
+        watch.drain_events(),
+        vec![WatchEvent::Joined(a), WatchEvent::Left(a)]
+    );
+}
+
+#[test]
+fn test_manual_refresh_between_ticks() {
+    let mut world = world();
+    let watch = world.watch_query::<(Player,)>();
+    let a = world.spawn();
+
+    world.edit_scope(|w| w.set(a, &Player {}));
+    assert!(watch.is_empty());
+
+    watch.refresh();
+    assert_eq!(watch.entities(), vec![a]);
+
+    // Nothing changed, so the tick end refresh reports nothing new
+    world.run();
+    assert_eq!(watch.drain_events(), vec![WatchEvent::Joined(a)]);
+}
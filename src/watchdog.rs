@@ -0,0 +1,402 @@
+//! Diagnostics for systems that take too long (requires the `watchdog` feature).
+//!
+//! A runaway system - an accidental infinite loop in user code - otherwise hangs the whole
+//! tick without a word. With a `WatchdogConfig` installed, the scheduler times every system
+//! and every wavefront:
+//!
+//! - A system that finishes but exceeds its per-system budget is recorded as an
+//!   `OverrunKind::Budget` overrun.
+//! - A wavefront still running past the deadline is reported by a background thread while
+//!   it is still stuck, naming every system that hasn't finished (`OverrunKind::Deadline`).
+//!
+//! Overruns are collected and can be read with `Scheduler::take_overruns`. The watchdog
+//! never prints on its own: to hear about a hang while it is happening, install a callback
+//! with `WatchdogConfig::on_overrun`, which is called with every overrun as it is recorded.
+//!
+//! With `OverrunPolicy::Panic`, debug builds panic with the offending system's name once
+//! the wavefront finishes. If it is still stuck at twice the deadline, the watchdog thread
+//! panics naming the system instead, through the usual panic hook; a hung thread can't be
+//! unwound from outside, so the tick itself panics once the system returns. Release builds
+//! only ever record: budgets are purely diagnostic there.
+//!
+//! # Example
+//! ```ignore
+//! world.set_watchdog(Some(
+//!     WatchdogConfig::new(Duration::from_millis(50))
+//!         .with_budget::<PathfindingSystem>(Duration::from_millis(4))
+//!         .with_policy(OverrunPolicy::Panic)
+//!         .on_overrun(|overrun| log::warn!("{overrun:?}")),
+//! ));
+//! ```
+
+use std::any::TypeId;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+/// What to do when a system overruns.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum OverrunPolicy {
+    /// Record the overrun and keep going.
+    Record,
+    /// Panic (debug builds only) naming the system. Release builds record instead.
+    Panic,
+}
+
+/// Callback called with every overrun as it is recorded.
+pub type OverrunCallback = Arc<dyn Fn(&Overrun) + Send + Sync>;
+
+/// Watchdog limits for a scheduler.
+#[derive(Clone)]
+pub struct WatchdogConfig {
+    /// Maximum wall-clock time for one wavefront.
+    pub wavefront_deadline: Duration,
+    /// Optional per-system budgets, keyed by the system's `TypeId`.
+    pub budgets: HashMap<TypeId, Duration>,
+    pub policy: OverrunPolicy,
+    /// How often the background thread checks the running wavefront.
+    pub poll_interval: Duration,
+    /// Called with every overrun as it is recorded, see `on_overrun`.
+    pub callback: Option<OverrunCallback>,
+}
+
+impl WatchdogConfig {
+    pub fn new(wavefront_deadline: Duration) -> Self {
+        WatchdogConfig {
+            wavefront_deadline,
+            budgets: HashMap::new(),
+            policy: OverrunPolicy::Record,
+            poll_interval: (wavefront_deadline / 4).max(Duration::from_millis(1)),
+            callback: None,
+        }
+    }
+
+    /// Sets the budget for system `S`.
+    pub fn with_budget<S: 'static>(mut self, budget: Duration) -> Self {
+        self.budgets.insert(TypeId::of::<S>(), budget);
+        self
+    }
+
+    pub fn with_policy(mut self, policy: OverrunPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// Calls `callback` with every overrun as it is recorded. Deadline overruns are
+    /// reported from the watchdog thread while the wavefront is still stuck, budget
+    /// overruns from the thread that ran the system.
+    pub fn on_overrun(mut self, callback: impl Fn(&Overrun) + Send + Sync + 'static) -> Self {
+        self.callback = Some(Arc::new(callback));
+        self
+    }
+
+    fn panics(&self) -> bool {
+        self.policy == OverrunPolicy::Panic && cfg!(debug_assertions)
+    }
+
+    fn report(&self, overrun: &Overrun) {
+        if let Some(callback) = &self.callback {
+            callback(overrun);
+        }
+    }
+}
+
+impl fmt::Debug for WatchdogConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WatchdogConfig")
+            .field("wavefront_deadline", &self.wavefront_deadline)
+            .field("budgets", &self.budgets)
+            .field("policy", &self.policy)
+            .field("poll_interval", &self.poll_interval)
+            .field("callback", &self.callback.is_some())
+            .finish()
+    }
+}
+
+/// Which limit was exceeded.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum OverrunKind {
+    /// The system finished but took longer than its budget.
+    Budget,
+    /// The system was still running (or was the slowest) when the wavefront deadline passed.
+    Deadline,
+}
+
+/// One recorded overrun.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Overrun {
+    pub system: &'static str,
+    pub wavefront: usize,
+    pub kind: OverrunKind,
+    /// Time the system had been running when the overrun was recorded.
+    pub elapsed: Duration,
+    /// The budget or deadline that was exceeded.
+    pub limit: Duration,
+}
+
+struct ActiveWavefront {
+    index: usize,
+    started: Instant,
+    /// Systems that started but haven't finished, with their start times.
+    running: Vec<(&'static str, Instant)>,
+    /// Systems that finished, with how long they took.
+    finished: Vec<(&'static str, Duration)>,
+    /// Number of overruns recorded before this wavefront started.
+    overruns_before: usize,
+    reported: bool,
+}
+
+struct WatchState {
+    active: Option<ActiveWavefront>,
+    overruns: Vec<Overrun>,
+    shutdown: bool,
+}
+
+struct Shared {
+    state: Mutex<WatchState>,
+    wake: Condvar,
+}
+
+/// Running watchdog owned by a scheduler. Dropping it stops the background thread.
+pub struct Watchdog {
+    config: WatchdogConfig,
+    shared: Arc<Shared>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Watchdog {
+    pub fn new(config: WatchdogConfig) -> Self {
+        let shared = Arc::new(Shared {
+            state: Mutex::new(WatchState {
+                active: None,
+                overruns: Vec::new(),
+                shutdown: false,
+            }),
+            wake: Condvar::new(),
+        });
+
+        let thread = {
+            let shared = shared.clone();
+            let config = config.clone();
+            std::thread::Builder::new()
+                .name("rollback_ecs-watchdog".to_string())
+                .spawn(move || Self::watch(&shared, &config))
+                .expect("Failed to spawn watchdog thread")
+        };
+
+        Watchdog {
+            config,
+            shared,
+            thread: Some(thread),
+        }
+    }
+
+    pub fn config(&self) -> &WatchdogConfig {
+        &self.config
+    }
+
+    fn watch(shared: &Shared, config: &WatchdogConfig) {
+        let mut state = shared.state.lock().expect("Watchdog state poisoned");
+
+        loop {
+            if state.shutdown {
+                return;
+            }
+
+            let now = Instant::now();
+            let mut hung = Vec::new();
+
+            if let Some(active) = state.active.as_mut()
+                && !active.reported
+                && now.duration_since(active.started) > config.wavefront_deadline
+            {
+                active.reported = true;
+                for &(system, started) in &active.running {
+                    hung.push(Overrun {
+                        system,
+                        wavefront: active.index,
+                        kind: OverrunKind::Deadline,
+                        elapsed: now.duration_since(started),
+                        limit: config.wavefront_deadline,
+                    });
+                }
+            }
+
+            state.overruns.extend(hung.iter().cloned());
+
+            // Still stuck at twice the deadline: treat it as hung. A thread can't be unwound
+            // from outside, so panic here, where the panic hook reports it; the deadline
+            // overrun recorded above makes `end_wavefront` panic once the system returns.
+            let hung_system = match state.active.as_ref() {
+                Some(active)
+                    if config.panics()
+                        && now.duration_since(active.started) > config.wavefront_deadline * 2 =>
+                {
+                    active
+                        .running
+                        .first()
+                        .map(|&(system, _)| (system, active.index))
+                }
+                _ => None,
+            };
+
+            if !hung.is_empty() || hung_system.is_some() {
+                // Never call back or panic holding the lock: that would block the scheduler
+                // or poison the state it still needs.
+                drop(state);
+                for overrun in &hung {
+                    config.report(overrun);
+                }
+                if let Some((system, wavefront)) = hung_system {
+                    panic!(
+                        "{system} appears to be hung: wavefront {wavefront} still running after \
+                         twice its deadline of {:?}",
+                        config.wavefront_deadline
+                    );
+                }
+                state = shared.state.lock().expect("Watchdog state poisoned");
+                continue;
+            }
+
+            state = shared
+                .wake
+                .wait_timeout(state, config.poll_interval)
+                .expect("Watchdog state poisoned")
+                .0;
+        }
+    }
+
+    /// Marks the start of a wavefront.
+    pub fn begin_wavefront(&self, index: usize) {
+        let mut state = self.shared.state.lock().expect("Watchdog state poisoned");
+
+        let overruns_before = state.overruns.len();
+        state.active = Some(ActiveWavefront {
+            index,
+            started: Instant::now(),
+            running: Vec::new(),
+            finished: Vec::new(),
+            overruns_before,
+            reported: false,
+        });
+    }
+
+    /// Runs one system of the active wavefront, timing it against its budget.
+    pub fn time(&self, system: &'static str, type_id: TypeId, run: impl FnOnce()) {
+        let started = Instant::now();
+
+        if let Some(active) = self
+            .shared
+            .state
+            .lock()
+            .expect("Watchdog state poisoned")
+            .active
+            .as_mut()
+        {
+            active.running.push((system, started));
+        }
+
+        run();
+
+        let elapsed = started.elapsed();
+        let mut state = self.shared.state.lock().expect("Watchdog state poisoned");
+
+        let wavefront = match state.active.as_mut() {
+            Some(active) => {
+                if let Some(i) = active.running.iter().position(|(s, _)| *s == system) {
+                    active.running.swap_remove(i);
+                }
+                active.finished.push((system, elapsed));
+                active.index
+            }
+            None => 0,
+        };
+
+        if let Some(&budget) = self.config.budgets.get(&type_id)
+            && elapsed > budget
+        {
+            let overrun = Overrun {
+                system,
+                wavefront,
+                kind: OverrunKind::Budget,
+                elapsed,
+                limit: budget,
+            };
+            state.overruns.push(overrun.clone());
+            drop(state);
+            self.config.report(&overrun);
+        }
+    }
+
+    /// Marks the end of the active wavefront. Records a deadline overrun for the slowest
+    /// system if the wavefront finished late before the background thread noticed, and
+    /// panics under `OverrunPolicy::Panic` in debug builds if anything overran.
+    pub fn end_wavefront(&self) {
+        let mut state = self.shared.state.lock().expect("Watchdog state poisoned");
+
+        let Some(active) = state.active.take() else {
+            return;
+        };
+
+        let elapsed = active.started.elapsed();
+        let mut late = None;
+        if !active.reported
+            && elapsed > self.config.wavefront_deadline
+            && let Some(&(system, system_elapsed)) =
+                active.finished.iter().max_by_key(|(_, elapsed)| *elapsed)
+        {
+            let overrun = Overrun {
+                system,
+                wavefront: active.index,
+                kind: OverrunKind::Deadline,
+                elapsed: system_elapsed,
+                limit: self.config.wavefront_deadline,
+            };
+            state.overruns.push(overrun.clone());
+            late = Some(overrun);
+        }
+
+        let first = if self.config.panics() {
+            state.overruns.get(active.overruns_before).cloned()
+        } else {
+            None
+        };
+        drop(state);
+
+        if let Some(overrun) = &late {
+            self.config.report(overrun);
+        }
+
+        if let Some(overrun) = first {
+            panic!(
+                "System `{}` overran: {:?} against a limit of {:?} ({:?})",
+                overrun.system, overrun.elapsed, overrun.limit, overrun.kind
+            );
+        }
+    }
+
+    /// Removes and returns all recorded overruns.
+    pub fn take_overruns(&self) -> Vec<Overrun> {
+        let mut state = self.shared.state.lock().expect("Watchdog state poisoned");
+        std::mem::take(&mut state.overruns)
+    }
+}
+
+impl Drop for Watchdog {
+    fn drop(&mut self) {
+        if let Ok(mut state) = self.shared.state.lock() {
+            state.shutdown = true;
+        }
+        self.shared.wake.notify_all();
+
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+#[cfg(test)]
+#[path = "watchdog.tests.rs"]
+mod tests;
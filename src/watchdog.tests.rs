@@ -0,0 +1,107 @@
+use super::*;
+use crate::scheduler::{PipelineStage, Scheduler};
+use std::any::TypeId;
+use std::time::Duration;
+
+struct SlowSystem;
+struct FastSystem;
+
+impl PipelineStage for SlowSystem {
+    fn run(&self) {
+        std::thread::sleep(Duration::from_millis(60));
+    }
+
+    fn type_id(&self) -> TypeId {
+        TypeId::of::<Self>()
+    }
+}
+
+impl PipelineStage for FastSystem {
+    fn run(&self) {}
+
+    fn type_id(&self) -> TypeId {
+        TypeId::of::<Self>()
+    }
+}
+
+fn scheduler(config: WatchdogConfig) -> Scheduler {
+    let mut scheduler = Scheduler::new(vec![Box::new(SlowSystem), Box::new(FastSystem)]);
+    scheduler.set_watchdog(Some(config));
+    scheduler
+}
+
+#[test]
+fn test_budget_overrun_recorded() {
+    let config = WatchdogConfig::new(Duration::from_secs(10))
+        .with_budget::<SlowSystem>(Duration::from_millis(5))
+        .with_budget::<FastSystem>(Duration::from_secs(1));
+    let scheduler = scheduler(config);
+
+    scheduler.run();
+    let overruns = scheduler.take_overruns();
+
+    assert_eq!(overruns.len(), 1);
+    assert!(overruns[0].system.ends_with("SlowSystem"));
+    assert_eq!(overruns[0].kind, OverrunKind::Budget);
+    assert!(overruns[0].elapsed >= Duration::from_millis(60));
+    assert_eq!(overruns[0].limit, Duration::from_millis(5));
+
+    // Overruns are drained by take_overruns
+    assert!(scheduler.take_overruns().is_empty());
+}
+
+#[test]
+fn test_deadline_reported_while_running() {
+    let config = WatchdogConfig::new(Duration::from_millis(10));
+    let scheduler = scheduler(config);
+
+    scheduler.run_sequential();
+    let overruns = scheduler.take_overruns();
+
+    let deadline: Vec<_> = overruns
+        .iter()
+        .filter(|o| o.kind == OverrunKind::Deadline)
+        .collect();
+    assert_eq!(deadline.len(), 1);
+    assert!(deadline[0].system.ends_with("SlowSystem"));
+    assert_eq!(deadline[0].limit, Duration::from_millis(10));
+}
+
+#[test]
+fn test_no_overruns_within_limits() {
+    let config = WatchdogConfig::new(Duration::from_secs(10))
+        .with_budget::<FastSystem>(Duration::from_secs(1));
+    let scheduler = scheduler(config);
+
+    scheduler.run();
+    assert!(scheduler.take_overruns().is_empty());
+}
+
+#[test]
+#[cfg(debug_assertions)]
+#[should_panic(expected = "SlowSystem` overran")]
+fn test_panic_policy_names_system() {
+    let config = WatchdogConfig::new(Duration::from_secs(10))
+        .with_budget::<SlowSystem>(Duration::from_millis(1))
+        .with_policy(OverrunPolicy::Panic);
+
+    scheduler(config).run_sequential();
+}
+
+#[test]
+fn test_callback_receives_deadline_overrun_while_running() {
+    let seen = Arc::new(Mutex::new(Vec::new()));
+    let config = WatchdogConfig::new(Duration::from_millis(10)).on_overrun({
+        let seen = seen.clone();
+        move |overrun: &Overrun| seen.lock().unwrap().push(overrun.clone())
+    });
+    let scheduler = scheduler(config);
+
+    scheduler.run_sequential();
+
+    let seen = seen.lock().unwrap();
+    assert_eq!(seen.len(), 1);
+    assert!(seen[0].system.ends_with("SlowSystem"));
+    assert_eq!(seen[0].kind, OverrunKind::Deadline);
+    assert_eq!(*seen, scheduler.take_overruns());
+}
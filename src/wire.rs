@@ -0,0 +1,1043 @@
+//! Stable wire format for component snapshots and deltas.
+//!
+//! Packets cross the network, so the decoder treats every byte as hostile: all lengths are
+//! checked against the remaining input before anything is allocated, block indices are
+//! bounds-checked against the storage hierarchy, and every malformed input is reported as a
+//! `DecodeError` instead of a panic. `apply_snapshot`/`apply_delta` decode a whole section
+//! before touching the storage, so a corrupt packet never leaves it half-updated.
+//!
+//! # Format (version 1)
+//!
+//! All integers are little-endian.
+//!
+//! ```text
+//! packet   := header section*
+//! header   := magic:[u8; 4] = "RBWF"
+//!             version:u16   = 1
+//!             kind:u8       (0 = snapshot, 1 = delta)
+//!             flags:u8      (reserved, must be 0)
+//!             tick:u32      (tick the packet describes)
+//!             base:u32      (delta: tick the delta applies to; snapshot: 0)
+//!             count:u16     (number of sections, at most MAX_SECTIONS)
+//! section  := component:u64 (component_id of the stored type, unique per packet)
+//!             length:u32    (payload length in bytes)
+//!             payload:[u8; length]
+//! snapshot payload := (ri:u8 mi:u8 present:u128 value*)*
+//! delta payload    := (ri:u8 mi:u8 set:u128 removed:u128 value*)*
+//! ```
+//!
+//! A payload is a list of inner blocks in strictly ascending `(ri, mi)` order, with
+//! `ri, mi < 128`. Each block is followed by one encoded value per set bit of `present`
+//! (or `set`), in ascending slot order. Masks must be non-zero and, for deltas, `set` and
+//! `removed` must be disjoint. Nothing may follow the last section.
+//!
+//! Values are encoded with the `Wire` trait, or for snapshots with the component's
+//! `SnapshotCodec` if one was registered on the world. Component ids are stable hashes of the
+//! component's type name, so both peers must run the same build.
+//!
+//! `World::save_snapshot` writes one snapshot section per storage whose component is a
+//! `SerializableComponent` (or has a registered codec), and `World::load_snapshot` replaces
+//! the world's state with such a packet, for save files, late-joining clients, or
+//! checkpoints older than the rollback window.
+
+use crate::component::Component;
+use crate::entity::Entity;
+use crate::sequence::stage_hash;
+use crate::statehash::Probe;
+use crate::storage::{ComponentStorage, Storage};
+use crate::tick::Tick;
+use std::fmt;
+
+/// Packet magic, `"RBWF"`.
+pub const MAGIC: [u8; 4] = *b"RBWF";
+
+/// Current wire format version. Decoders reject any other version.
+pub const VERSION: u16 = 1;
+
+/// Maximum number of sections in one packet (one per component storage).
+pub const MAX_SECTIONS: u16 = 128;
+
+const HEADER_LEN: usize = 4 + 2 + 1 + 1 + 4 + 4 + 2;
+
+/// Why a packet could not be decoded.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum DecodeError {
+    /// The input ended before a field of `needed` bytes could be read.
+    UnexpectedEof { needed: usize, remaining: usize },
+    /// The packet does not start with `MAGIC`.
+    BadMagic([u8; 4]),
+    /// The packet was written by an unsupported format version.
+    UnsupportedVersion(u16),
+    /// The packet kind byte is neither snapshot nor delta.
+    UnknownKind(u8),
+    /// Reserved header flags were set.
+    ReservedFlags(u8),
+    /// The packet declares more than `MAX_SECTIONS` sections.
+    TooManySections(u16),
+    /// Two sections describe the same component.
+    DuplicateSection(u64),
+    /// A block index lies outside the storage hierarchy.
+    BlockOutOfBounds { ri: u8, mi: u8 },
+    /// Blocks are not in strictly ascending `(ri, mi)` order.
+    UnorderedBlock { ri: u8, mi: u8 },
+    /// A block has an empty mask, or overlapping `set`/`removed` masks.
+    InvalidMask { ri: u8, mi: u8 },
+    /// A value could not be decoded.
+    InvalidValue(&'static str),
+    /// Bytes were left over after the last section or value.
+    TrailingBytes(usize),
+    /// A section was decoded as a snapshot but the packet is a delta, or vice versa.
+    KindMismatch {
+        expected: PacketKind,
+        found: PacketKind,
+    },
+    /// A whole-world snapshot has a section for a component this build doesn't know.
+    UnknownComponent(u64),
+    /// A packet was built for a different tick than the receiver expected, e.g. a delta on
+    /// another base than the receiver's state.
+    BaseMismatch { expected: Tick, found: Tick },
+    /// A snapshot is for a tick after the receiver's current one, so there is nothing to
+    /// rewind to, see `World::reconcile`.
+    TickAhead { tick: Tick, current: Tick },
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DecodeError::UnexpectedEof { needed, remaining } => write!(
+                f,
+                "unexpected end of input: needed {} bytes, {} remaining",
+                needed, remaining
+            ),
+            DecodeError::BadMagic(magic) => write!(f, "bad magic {:?}", magic),
+            DecodeError::UnsupportedVersion(v) => write!(f, "unsupported wire version {}", v),
+            DecodeError::UnknownKind(k) => write!(f, "unknown packet kind {}", k),
+            DecodeError::ReservedFlags(flags) => write!(f, "reserved flags set: {:#04x}", flags),
+            DecodeError::TooManySections(n) => {
+                write!(f, "{} sections exceeds the maximum of {}", n, MAX_SECTIONS)
+            }
+            DecodeError::DuplicateSection(id) => write!(f, "duplicate section {:#018x}", id),
+            DecodeError::BlockOutOfBounds { ri, mi } => {
+                write!(f, "block ({}, {}) is out of bounds", ri, mi)
+            }
+            DecodeError::UnorderedBlock { ri, mi } => {
+                write!(f, "block ({}, {}) is out of order", ri, mi)
+            }
+            DecodeError::InvalidMask { ri, mi } => {
+                write!(f, "block ({}, {}) has an invalid mask", ri, mi)
+            }
+            DecodeError::InvalidValue(what) => write!(f, "invalid value: {}", what),
+            DecodeError::TrailingBytes(n) => write!(f, "{} trailing bytes", n),
+            DecodeError::KindMismatch { expected, found } => {
+                write!(f, "expected a {:?} packet, found {:?}", expected, found)
+            }
+            DecodeError::UnknownComponent(id) => write!(f, "unknown component {:#018x}", id),
+            DecodeError::BaseMismatch { expected, found } => write!(
+                f,
+                "packet is built on tick {}, expected tick {}",
+                found.value(),
+                expected.value()
+            ),
+            DecodeError::TickAhead { tick, current } => write!(
+                f,
+                "packet is for tick {}, after the current tick {}",
+                tick.value(),
+                current.value()
+            ),
+        }
+    }
+}
+
+impl std::error::Error for DecodeError {}
+
+/// Bounds-checked cursor over untrusted input.
+pub struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    pub fn new(bytes: &'a [u8]) -> Self {
+        Reader { bytes, pos: 0 }
+    }
+
+    /// Number of unread bytes.
+    pub fn remaining(&self) -> usize {
+        self.bytes.len() - self.pos
+    }
+
+    /// Reads exactly `n` bytes.
+    pub fn bytes(&mut self, n: usize) -> Result<&'a [u8], DecodeError> {
+        if n > self.remaining() {
+            return Err(DecodeError::UnexpectedEof {
+                needed: n,
+                remaining: self.remaining(),
+            });
+        }
+
+        let out = &self.bytes[self.pos..self.pos + n];
+        self.pos += n;
+        Ok(out)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], DecodeError> {
+        let mut out = [0u8; N];
+        out.copy_from_slice(self.bytes(N)?);
+        Ok(out)
+    }
+
+    pub fn u8(&mut self) -> Result<u8, DecodeError> {
+        Ok(self.array::<1>()?[0])
+    }
+
+    pub fn u16(&mut self) -> Result<u16, DecodeError> {
+        Ok(u16::from_le_bytes(self.array()?))
+    }
+
+    pub fn u32(&mut self) -> Result<u32, DecodeError> {
+        Ok(u32::from_le_bytes(self.array()?))
+    }
+
+    pub fn u64(&mut self) -> Result<u64, DecodeError> {
+        Ok(u64::from_le_bytes(self.array()?))
+    }
+
+    pub fn u128(&mut self) -> Result<u128, DecodeError> {
+        Ok(u128::from_le_bytes(self.array()?))
+    }
+
+    /// Reads a `u32` length prefix that must fit in the remaining input, given that each
+    /// element takes at least `min_element_size` bytes. Use it before allocating.
+    pub fn len_prefix(&mut self, min_element_size: usize) -> Result<usize, DecodeError> {
+        let len = self.u32()? as usize;
+        let needed = len.saturating_mul(min_element_size.max(1));
+
+        if needed > self.remaining() {
+            return Err(DecodeError::UnexpectedEof {
+                needed,
+                remaining: self.remaining(),
+            });
+        }
+
+        Ok(len)
+    }
+}
+
+/// Binary encoding of a value on the wire.
+///
+/// `decode` must consume exactly the bytes written by `encode` and must never panic on
+/// malformed input.
+pub trait Wire: Sized {
+    fn encode(&self, out: &mut Vec<u8>);
+    fn decode(reader: &mut Reader<'_>) -> Result<Self, DecodeError>;
+}
+
+macro_rules! wire_int {
+    ($($ty:ty),*) => {
+        $(
+            impl Wire for $ty {
+                fn encode(&self, out: &mut Vec<u8>) {
+                    out.extend_from_slice(&self.to_le_bytes());
+                }
+
+                fn decode(reader: &mut Reader<'_>) -> Result<Self, DecodeError> {
+                    Ok(<$ty>::from_le_bytes(reader.array()?))
+                }
+            }
+        )*
+    };
+}
+
+wire_int!(u8, u16, u32, u64, u128, i8, i16, i32, i64, i128, f32, f64);
+
+impl Wire for bool {
+    fn encode(&self, out: &mut Vec<u8>) {
+        out.push(*self as u8);
+    }
+
+    fn decode(reader: &mut Reader<'_>) -> Result<Self, DecodeError> {
+        match reader.u8()? {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err(DecodeError::InvalidValue("bool")),
+        }
+    }
+}
+
+impl Wire for Tick {
+    fn encode(&self, out: &mut Vec<u8>) {
+        self.value().encode(out);
+    }
+
+    fn decode(reader: &mut Reader<'_>) -> Result<Self, DecodeError> {
+        Ok(Tick::new(reader.u32()?))
+    }
+}
+
+impl Wire for Entity {
+    fn encode(&self, out: &mut Vec<u8>) {
+        self.index().encode(out);
+        (self.generation() as u16).encode(out);
+    }
+
+    fn decode(reader: &mut Reader<'_>) -> Result<Self, DecodeError> {
+        let index = reader.u32()?;
+        let generation = reader.u16()? as u32;
+
+        let entity = Entity::new(index, generation);
+        if entity.index() != index || entity.generation() != generation {
+            return Err(DecodeError::InvalidValue("entity"));
+        }
+
+        Ok(entity)
+    }
+}
+
+impl<T: Wire> Wire for Option<T> {
+    fn encode(&self, out: &mut Vec<u8>) {
+        match self {
+            None => out.push(0),
+            Some(value) => {
+                out.push(1);
+                value.encode(out);
+            }
+        }
+    }
+
+    fn decode(reader: &mut Reader<'_>) -> Result<Self, DecodeError> {
+        match reader.u8()? {
+            0 => Ok(None),
+            1 => Ok(Some(T::decode(reader)?)),
+            _ => Err(DecodeError::InvalidValue("option tag")),
+        }
+    }
+}
+
+impl<T: Wire> Wire for Vec<T> {
+    fn encode(&self, out: &mut Vec<u8>) {
+        (self.len() as u32).encode(out);
+        for value in self {
+            value.encode(out);
+        }
+    }
+
+    fn decode(reader: &mut Reader<'_>) -> Result<Self, DecodeError> {
+        // Every element costs at least one byte, so a tiny packet can't request a huge allocation
+        let len = reader.len_prefix(1)?;
+        let mut out = Vec::with_capacity(len);
+
+        for _ in 0..len {
+            out.push(T::decode(reader)?);
+        }
+
+        Ok(out)
+    }
+}
+
+impl Wire for String {
+    fn encode(&self, out: &mut Vec<u8>) {
+        (self.len() as u32).encode(out);
+        out.extend_from_slice(self.as_bytes());
+    }
+
+    fn decode(reader: &mut Reader<'_>) -> Result<Self, DecodeError> {
+        let len = reader.len_prefix(1)?;
+        let bytes = reader.bytes(len)?;
+
+        String::from_utf8(bytes.to_vec()).map_err(|_| DecodeError::InvalidValue("utf-8 string"))
+    }
+}
+
+/// Custom encoding of a component's snapshot values, e.g. quantized positions or values
+/// packed relative to their neighbours. Register one with `World::set_snapshot_codec`.
+///
+/// Codecs encode whole inner blocks, so they can exploit similarity between neighbouring
+/// entities. The block layout around them (indices and presence masks) is unchanged, but
+/// the values are only readable with the same codec. Like `Wire::decode`, `decode_block`
+/// must consume exactly the bytes written by `encode_block` and must never panic on
+/// malformed input.
+pub trait SnapshotCodec<T> {
+    /// Encodes the values of one inner block in ascending slot order.
+    fn encode_block(&self, values: &[&T], out: &mut Vec<u8>);
+
+    /// Decodes the `count` values of one inner block.
+    fn decode_block(&self, count: usize, reader: &mut Reader<'_>) -> Result<Vec<T>, DecodeError>;
+}
+
+/// The default codec: every value encoded with its `Wire` implementation.
+#[derive(Clone, Copy, Default, Debug)]
+pub struct WireCodec;
+
+impl<T: Wire> SnapshotCodec<T> for WireCodec {
+    fn encode_block(&self, values: &[&T], out: &mut Vec<u8>) {
+        for value in values {
+            value.encode(out);
+        }
+    }
+
+    fn decode_block(&self, count: usize, reader: &mut Reader<'_>) -> Result<Vec<T>, DecodeError> {
+        let mut out = Vec::with_capacity(count);
+        for _ in 0..count {
+            out.push(T::decode(reader)?);
+        }
+        Ok(out)
+    }
+}
+
+/// A component `World::save_snapshot` includes. Implemented for every component type
+/// implementing `Wire`; `#[derive(Component)]` picks it up through `Component::wire_codec`.
+pub trait SerializableComponent: Component + Wire {}
+
+impl<T: Component + Wire> SerializableComponent for T {}
+
+/// Picked by method resolution when `T: SerializableComponent`.
+#[doc(hidden)]
+pub trait ViaSerializable<T> {
+    fn wire_codec(&self) -> Option<&'static dyn SnapshotCodec<T>>;
+}
+
+impl<T: SerializableComponent> ViaSerializable<T> for Probe<T> {
+    fn wire_codec(&self) -> Option<&'static dyn SnapshotCodec<T>> {
+        Some(&WireCodec)
+    }
+}
+
+/// The fallback for types without `Wire`, one autoref further away.
+#[doc(hidden)]
+pub trait ViaUnserializable<T> {
+    fn wire_codec(&self) -> Option<&'static dyn SnapshotCodec<T>>;
+}
+
+impl<T: 'static> ViaUnserializable<T> for &Probe<T> {
+    fn wire_codec(&self) -> Option<&'static dyn SnapshotCodec<T>> {
+        None
+    }
+}
+
+/// Stable identifier of a component type on the wire.
+pub fn component_id<T: ?Sized>() -> u64 {
+    stage_hash(std::any::type_name::<T>())
+}
+
+/// Whether a packet holds full snapshots or deltas against a base tick.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum PacketKind {
+    Snapshot,
+    Delta,
+}
+
+impl PacketKind {
+    fn to_byte(self) -> u8 {
+        match self {
+            PacketKind::Snapshot => 0,
+            PacketKind::Delta => 1,
+        }
+    }
+
+    fn from_byte(byte: u8) -> Result<Self, DecodeError> {
+        match byte {
+            0 => Ok(PacketKind::Snapshot),
+            1 => Ok(PacketKind::Delta),
+            _ => Err(DecodeError::UnknownKind(byte)),
+        }
+    }
+}
+
+/// A change to one entity in a delta section.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum DeltaOp<T> {
+    Set(T),
+    Remove,
+}
+
+/// Builds a packet section by section.
+pub struct PacketWriter {
+    kind: PacketKind,
+    tick: Tick,
+    base: Tick,
+    count: u16,
+    sections: Vec<u8>,
+}
+
+impl PacketWriter {
+    /// Starts a snapshot packet describing the state at `tick`.
+    pub fn snapshot(tick: Tick) -> Self {
+        PacketWriter {
+            kind: PacketKind::Snapshot,
+            tick,
+            base: Tick::new(0),
+            count: 0,
+            sections: Vec::new(),
+        }
+    }
+
+    /// Starts a delta packet turning the state at `base` into the state at `tick`.
+    pub fn delta(base: Tick, tick: Tick) -> Self {
+        PacketWriter {
+            kind: PacketKind::Delta,
+            tick,
+            base,
+            count: 0,
+            sections: Vec::new(),
+        }
+    }
+
+    fn begin_section<T>(&mut self) -> usize {
+        assert!(
+            self.count < MAX_SECTIONS,
+            "Packet already holds {} sections",
+            MAX_SECTIONS
+        );
+        self.count += 1;
+
+        component_id::<T>().encode(&mut self.sections);
+        let length_at = self.sections.len();
+        0u32.encode(&mut self.sections);
+        length_at
+    }
+
+    fn end_section(&mut self, length_at: usize) {
+        let length = (self.sections.len() - length_at - 4) as u32;
+        self.sections[length_at..length_at + 4].copy_from_slice(&length.to_le_bytes());
+    }
+
+    /// Writes every component of `storage` as a snapshot section.
+    ///
+    /// # Panics
+    /// Panics if this is a delta packet.
+    pub fn write_snapshot<T: Component + Wire>(&mut self, storage: &Storage<T>) {
+        self.write_snapshot_with(storage, &WireCodec);
+    }
+
+    /// Like `write_snapshot`, but encodes the values with `codec`.
+    ///
+    /// # Panics
+    /// Panics if this is a delta packet.
+    pub fn write_snapshot_with<T: Component>(
+        &mut self,
+        storage: &Storage<T>,
+        codec: &dyn SnapshotCodec<T>,
+    ) {
+        assert_eq!(
+            self.kind,
+            PacketKind::Snapshot,
+            "write_snapshot on a delta packet"
+        );
+        let length_at = self.begin_section::<T>();
+
+        let root = &storage.root;
+        let mut root_mask = root.presence_mask;
+        let mut values: Vec<&T> = Vec::with_capacity(128);
+
+        while root_mask != 0 {
+            let ri = root_mask.trailing_zeros();
+            root_mask &= !(1u128 << ri);
+            let middle = unsafe { root.data[ri as usize].assume_init_ref() };
+            let mut middle_mask = middle.presence_mask;
+
+            while middle_mask != 0 {
+                let mi = middle_mask.trailing_zeros();
+                middle_mask &= !(1u128 << mi);
+                let inner = unsafe { middle.data[mi as usize].assume_init_ref() };
+
+                if inner.presence_mask == 0 {
+                    continue;
+                }
+
+                let out = &mut self.sections;
+                out.push(ri as u8);
+                out.push(mi as u8);
+                inner.presence_mask.encode(out);
+
+                values.clear();
+                let mut mask = inner.presence_mask;
+                while mask != 0 {
+                    let ii = mask.trailing_zeros();
+                    mask &= !(1u128 << ii);
+                    values.push(unsafe { inner.data[ii as usize].assume_init_ref() });
+                }
+                codec.encode_block(&values, out);
+            }
+        }
+
+        self.end_section(length_at);
+    }
+
+    /// Like `write_snapshot_with`, for any storage type. Used by `World::save_snapshot`.
+    pub(crate) fn write_snapshot_of<S: ComponentStorage>(
+        &mut self,
+        storage: &S,
+        codec: &dyn SnapshotCodec<S::Item>,
+    ) {
+        assert_eq!(
+            self.kind,
+            PacketKind::Snapshot,
+            "write_snapshot on a delta packet"
+        );
+        let length_at = self.begin_section::<S::Item>();
+
+        let out = &mut self.sections;
+        let mut block: Option<(u32, u128, Vec<&S::Item>)> = None;
+        storage.visit(|index, value| {
+            let key = index >> 7;
+            if block.as_ref().is_some_and(|(k, ..)| *k != key) {
+                Self::flush_snapshot_block(out, block.take(), codec);
+            }
+
+            let (_, present, values) = block.get_or_insert_with(|| (key, 0, Vec::new()));
+            *present |= 1u128 << (index & 0x7F);
+            values.push(value);
+        });
+
+        Self::flush_snapshot_block(out, block, codec);
+        self.end_section(length_at);
+    }
+
+    fn flush_snapshot_block<T>(
+        out: &mut Vec<u8>,
+        block: Option<(u32, u128, Vec<&T>)>,
+        codec: &dyn SnapshotCodec<T>,
+    ) {
+        let Some((key, present, values)) = block else {
+            return;
+        };
+
+        out.push((key >> 7) as u8);
+        out.push((key & 0x7F) as u8);
+        present.encode(out);
+        codec.encode_block(&values, out);
+    }
+
+    /// Writes the changes turning `base` into `current` as a delta section.
+    ///
+    /// # Panics
+    /// Panics if this is a snapshot packet.
+    pub fn write_delta<T: Component + Wire + PartialEq>(
+        &mut self,
+        base: &Storage<T>,
+        current: &Storage<T>,
+    ) {
+        let mut ops = Vec::new();
+        let mut b = base.iter().peekable();
+        let mut c = current.iter().peekable();
+
+        loop {
+            let op = match (b.peek(), c.peek()) {
+                (None, None) => break,
+                (Some(&(bi, bv)), Some(&(ci, cv))) if bi == ci => {
+                    b.next();
+                    c.next();
+                    if bv == cv {
+                        continue;
+                    }
+                    (ci, Some(cv))
+                }
+                (Some(&(bi, _)), Some(&(ci, _))) if bi < ci => {
+                    b.next();
+                    (bi, None)
+                }
+                (Some(&(bi, _)), None) => {
+                    b.next();
+                    (bi, None)
+                }
+                (_, Some(&(ci, cv))) => {
+                    c.next();
+                    (ci, Some(cv))
+                }
+            };
+            ops.push(op);
+        }
+
+        self.write_delta_ops(ops);
+    }
+
+    /// Writes a delta section of `T` from `(index, value)` pairs in ascending index order,
+    /// where `None` removes the component. Used by `net::DeltaEncoder`, which knows what
+    /// changed from the change journal instead of comparing against a base storage.
+    ///
+    /// # Panics
+    /// Panics if this is a snapshot packet.
+    pub fn write_delta_ops<'v, T: Wire + 'v>(
+        &mut self,
+        ops: impl IntoIterator<Item = (u32, Option<&'v T>)>,
+    ) {
+        assert_eq!(
+            self.kind,
+            PacketKind::Delta,
+            "write_delta on a snapshot packet"
+        );
+        let length_at = self.begin_section::<T>();
+
+        let mut block: Option<(u32, u128, u128, Vec<&T>)> = None;
+        for (index, op) in ops {
+            let key = index >> 7;
+            if block.as_ref().is_some_and(|(k, ..)| *k != key) {
+                Self::flush_delta_block(&mut self.sections, block.take());
+            }
+
+            let (_, set, removed, values) = block.get_or_insert_with(|| (key, 0, 0, Vec::new()));
+            let bit = 1u128 << (index & 0x7F);
+            match op {
+                Some(value) => {
+                    *set |= bit;
+                    values.push(value);
+                }
+                None => *removed |= bit,
+            }
+        }
+
+        Self::flush_delta_block(&mut self.sections, block);
+        self.end_section(length_at);
+    }
+
+    fn flush_delta_block<T: Wire>(out: &mut Vec<u8>, block: Option<(u32, u128, u128, Vec<&T>)>) {
+        let Some((key, set, removed, values)) = block else {
+            return;
+        };
+
+        out.push((key >> 7) as u8);
+        out.push((key & 0x7F) as u8);
+        set.encode(out);
+        removed.encode(out);
+        for value in values {
+            value.encode(out);
+        }
+    }
+
+    /// Finishes the packet and returns its bytes.
+    pub fn finish(self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(HEADER_LEN + self.sections.len());
+        out.extend_from_slice(&MAGIC);
+        VERSION.encode(&mut out);
+        out.push(self.kind.to_byte());
+        out.push(0);
+        self.tick.encode(&mut out);
+        self.base.encode(&mut out);
+        self.count.encode(&mut out);
+        out.extend_from_slice(&self.sections);
+        out
+    }
+}
+
+/// One component's section of a decoded packet. The payload is validated lazily by
+/// `decode_snapshot`/`decode_delta`, since only the receiver knows the value type.
+#[derive(Clone, Copy, Debug)]
+pub struct Section<'a> {
+    pub component: u64,
+    kind: PacketKind,
+    payload: &'a [u8],
+}
+
+impl<'a> Section<'a> {
+    /// Raw payload bytes.
+    pub fn payload(&self) -> &'a [u8] {
+        self.payload
+    }
+
+    fn expect_kind(&self, expected: PacketKind) -> Result<(), DecodeError> {
+        if self.kind != expected {
+            return Err(DecodeError::KindMismatch {
+                expected,
+                found: self.kind,
+            });
+        }
+        Ok(())
+    }
+
+    /// Decodes a snapshot section into `(index, value)` pairs in ascending index order.
+    pub fn decode_snapshot<T: Wire>(&self) -> Result<Vec<(u32, T)>, DecodeError> {
+        self.decode_snapshot_with(&WireCodec)
+    }
+
+    /// Like `decode_snapshot`, for a section written with `codec`.
+    pub fn decode_snapshot_with<T>(
+        &self,
+        codec: &dyn SnapshotCodec<T>,
+    ) -> Result<Vec<(u32, T)>, DecodeError> {
+        self.expect_kind(PacketKind::Snapshot)?;
+
+        let mut reader = Reader::new(self.payload);
+        let mut blocks = BlockCursor::default();
+        let mut out = Vec::new();
+
+        while reader.remaining() > 0 {
+            let (ri, mi) = blocks.next(&mut reader)?;
+            let present = reader.u128()?;
+
+            if present == 0 {
+                return Err(DecodeError::InvalidMask { ri, mi });
+            }
+
+            let values = codec.decode_block(present.count_ones() as usize, &mut reader)?;
+            if values.len() != present.count_ones() as usize {
+                return Err(DecodeError::InvalidValue("snapshot codec value count"));
+            }
+
+            let base = block_base(ri, mi);
+            let mut mask = present;
+            for value in values {
+                let ii = mask.trailing_zeros();
+                mask &= !(1u128 << ii);
+                out.push((base + ii, value));
+            }
+        }
+
+        Ok(out)
+    }
+
+    /// Decodes a delta section into `(index, op)` pairs in ascending index order.
+    pub fn decode_delta<T: Wire>(&self) -> Result<Vec<(u32, DeltaOp<T>)>, DecodeError> {
+        self.expect_kind(PacketKind::Delta)?;
+
+        let mut reader = Reader::new(self.payload);
+        let mut blocks = BlockCursor::default();
+        let mut out = Vec::new();
+
+        while reader.remaining() > 0 {
+            let (ri, mi) = blocks.next(&mut reader)?;
+            let set = reader.u128()?;
+            let removed = reader.u128()?;
+
+            if set | removed == 0 || set & removed != 0 {
+                return Err(DecodeError::InvalidMask { ri, mi });
+            }
+
+            let start = out.len();
+            decode_values(&mut reader, ri, mi, set, |index, value| {
+                out.push((index, DeltaOp::Set(value)))
+            })?;
+
+            let base = block_base(ri, mi);
+            let mut mask = removed;
+            while mask != 0 {
+                let ii = mask.trailing_zeros();
+                mask &= !(1u128 << ii);
+                out.push((base + ii, DeltaOp::Remove));
+            }
+
+            out[start..].sort_by_key(|(index, _)| *index);
+        }
+
+        Ok(out)
+    }
+}
+
+fn block_base(ri: u8, mi: u8) -> u32 {
+    (ri as u32) * 16384 + (mi as u32) * 128
+}
+
+fn decode_values<T: Wire>(
+    reader: &mut Reader<'_>,
+    ri: u8,
+    mi: u8,
+    mask: u128,
+    mut push: impl FnMut(u32, T),
+) -> Result<(), DecodeError> {
+    let base = block_base(ri, mi);
+    let mut mask = mask;
+
+    while mask != 0 {
+        let ii = mask.trailing_zeros();
+        mask &= !(1u128 << ii);
+        push(base + ii, T::decode(reader)?);
+    }
+
+    Ok(())
+}
+
+/// Reads block indices and enforces bounds and strictly ascending order.
+#[derive(Default)]
+struct BlockCursor {
+    last: Option<(u8, u8)>,
+}
+
+impl BlockCursor {
+    fn next(&mut self, reader: &mut Reader<'_>) -> Result<(u8, u8), DecodeError> {
+        let ri = reader.u8()?;
+        let mi = reader.u8()?;
+
+        if ri >= 128 || mi >= 128 {
+            return Err(DecodeError::BlockOutOfBounds { ri, mi });
+        }
+
+        if self.last.is_some_and(|last| (ri, mi) <= last) {
+            return Err(DecodeError::UnorderedBlock { ri, mi });
+        }
+
+        self.last = Some((ri, mi));
+        Ok((ri, mi))
+    }
+}
+
+/// A decoded packet header with its sections split out.
+#[derive(Clone, Debug)]
+pub struct Packet<'a> {
+    pub kind: PacketKind,
+    pub tick: Tick,
+    pub base: Tick,
+    sections: Vec<Section<'a>>,
+}
+
+impl<'a> Packet<'a> {
+    /// Validates the header and section framing of `bytes`.
+    pub fn decode(bytes: &'a [u8]) -> Result<Self, DecodeError> {
+        let mut reader = Reader::new(bytes);
+
+        let magic: [u8; 4] = reader.array()?;
+        if magic != MAGIC {
+            return Err(DecodeError::BadMagic(magic));
+        }
+
+        let version = reader.u16()?;
+        if version != VERSION {
+            return Err(DecodeError::UnsupportedVersion(version));
+        }
+
+        let kind = PacketKind::from_byte(reader.u8()?)?;
+
+        let flags = reader.u8()?;
+        if flags != 0 {
+            return Err(DecodeError::ReservedFlags(flags));
+        }
+
+        let tick = Tick::new(reader.u32()?);
+        let base = Tick::new(reader.u32()?);
+
+        let count = reader.u16()?;
+        if count > MAX_SECTIONS {
+            return Err(DecodeError::TooManySections(count));
+        }
+
+        let mut sections: Vec<Section<'a>> = Vec::with_capacity(count as usize);
+        for _ in 0..count {
+            let component = reader.u64()?;
+            let length = reader.u32()? as usize;
+            let payload = reader.bytes(length)?;
+
+            if sections.iter().any(|s| s.component == component) {
+                return Err(DecodeError::DuplicateSection(component));
+            }
+
+            sections.push(Section {
+                component,
+                kind,
+                payload,
+            });
+        }
+
+        if reader.remaining() > 0 {
+            return Err(DecodeError::TrailingBytes(reader.remaining()));
+        }
+
+        Ok(Packet {
+            kind,
+            tick,
+            base,
+            sections,
+        })
+    }
+
+    /// All sections in packet order.
+    pub fn sections(&self) -> &[Section<'a>] {
+        &self.sections
+    }
+
+    /// The section for component `T`, if the packet has one.
+    pub fn section<T>(&self) -> Option<&Section<'a>> {
+        let id = component_id::<T>();
+        self.sections.iter().find(|s| s.component == id)
+    }
+}
+
+/// Replaces the contents of `storage` with the packet's snapshot of `T`.
+/// A packet without a `T` section means no entity has the component.
+///
+/// The section is fully decoded before `storage` is modified.
+pub fn apply_snapshot<T: Component + Wire>(
+    storage: &mut Storage<T>,
+    packet: &Packet<'_>,
+) -> Result<(), DecodeError> {
+    apply_snapshot_with(storage, packet, &WireCodec)
+}
+
+/// Like `apply_snapshot`, for a packet whose `T` section was written with `codec`.
+pub fn apply_snapshot_with<T: Component>(
+    storage: &mut Storage<T>,
+    packet: &Packet<'_>,
+    codec: &dyn SnapshotCodec<T>,
+) -> Result<(), DecodeError> {
+    apply_snapshot_where(storage, packet, codec, |_| true)
+}
+
+/// Like `apply_snapshot_with`, but only sets and removes components at indices for which
+/// `accept` returns true; the others keep their current state.
+pub(crate) fn apply_snapshot_where<T: Component>(
+    storage: &mut Storage<T>,
+    packet: &Packet<'_>,
+    codec: &dyn SnapshotCodec<T>,
+    accept: impl Fn(u32) -> bool,
+) -> Result<(), DecodeError> {
+    if packet.kind != PacketKind::Snapshot {
+        return Err(DecodeError::KindMismatch {
+            expected: PacketKind::Snapshot,
+            found: packet.kind,
+        });
+    }
+
+    let entries = match packet.section::<T>() {
+        Some(section) => section.decode_snapshot_with(codec)?,
+        None => Vec::new(),
+    };
+
+    let stale: Vec<u32> = storage
+        .iter()
+        .map(|(index, _)| index)
+        .filter(|index| entries.binary_search_by_key(index, |(i, _)| *i).is_err())
+        .filter(|&index| accept(index))
+        .collect();
+
+    for index in stale {
+        storage.remove(index);
+    }
+
+    for (index, value) in &entries {
+        if accept(*index) {
+            storage.set(*index, value);
+        }
+    }
+
+    Ok(())
+}
+
+/// Applies the packet's delta of `T` to `storage`. A packet without a `T` section means
+/// the component did not change.
+///
+/// The section is fully decoded before `storage` is modified.
+pub fn apply_delta<T: Component + Wire>(
+    storage: &mut Storage<T>,
+    packet: &Packet<'_>,
+) -> Result<(), DecodeError> {
+    if packet.kind != PacketKind::Delta {
+        return Err(DecodeError::KindMismatch {
+            expected: PacketKind::Delta,
+            found: packet.kind,
+        });
+    }
+
+    let Some(section) = packet.section::<T>() else {
+        return Ok(());
+    };
+
+    for (index, op) in section.decode_delta::<T>()? {
+        match op {
+            DeltaOp::Set(value) => storage.set(index, &value),
+            DeltaOp::Remove => storage.remove(index),
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+#[path = "wire.tests.rs"]
+mod tests;
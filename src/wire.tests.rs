@@ -0,0 +1,522 @@
+use super::*;
+use crate::component::Component;
+use crate::storage::Storage;
+
+#[derive(Component, Default, Clone, Debug, PartialEq)]
+struct Position {
+    x: i32,
+    y: i32,
+}
+
+impl Wire for Position {
+    fn encode(&self, out: &mut Vec<u8>) {
+        self.x.encode(out);
+        self.y.encode(out);
+    }
+
+    fn decode(reader: &mut Reader<'_>) -> Result<Self, DecodeError> {
+        Ok(Position {
+            x: i32::decode(reader)?,
+            y: i32::decode(reader)?,
+        })
+    }
+}
+
+#[derive(Component, Default, Clone, Debug, PartialEq)]
+struct Name {
+    value: String,
+}
+
+impl Wire for Name {
+    fn encode(&self, out: &mut Vec<u8>) {
+        self.value.encode(out);
+    }
+
+    fn decode(reader: &mut Reader<'_>) -> Result<Self, DecodeError> {
+        Ok(Name {
+            value: String::decode(reader)?,
+        })
+    }
+}
+
+fn positions(entries: &[(u32, i32)]) -> Storage<Position> {
+    let mut storage = Storage::new();
+    for &(index, x) in entries {
+        storage.set(index, &Position { x, y: -x });
+    }
+    storage
+}
+
+fn snapshot_packet() -> Vec<u8> {
+    let storage = positions(&[(0, 1), (5, 2), (200, 3), (40_000, 4)]);
+    let mut names = Storage::<Name>::new();
+    names.set(
+        5,
+        &Name {
+            value: "five".to_string(),
+        },
+    );
+
+    let mut writer = PacketWriter::snapshot(Tick::new(7));
+    writer.write_snapshot(&storage);
+    writer.write_snapshot(&names);
+    writer.finish()
+}
+
+#[test]
+fn test_snapshot_round_trip() {
+    let bytes = snapshot_packet();
+    let packet = Packet::decode(&bytes).unwrap();
+
+    assert_eq!(packet.kind, PacketKind::Snapshot);
+    assert_eq!(packet.tick, Tick::new(7));
+    assert_eq!(packet.sections().len(), 2);
+
+    let mut restored = positions(&[(1, 99)]);
+    apply_snapshot(&mut restored, &packet).unwrap();
+
+    crate::testing::assert_storage_eq(
+        &restored,
+        &positions(&[(0, 1), (5, 2), (200, 3), (40_000, 4)]),
+    );
+
+    let names = packet
+        .section::<Name>()
+        .unwrap()
+        .decode_snapshot::<Name>()
+        .unwrap();
+    assert_eq!(
+        names,
+        vec![(
+            5,
+            Name {
+                value: "five".to_string()
+            }
+        )]
+    );
+}
+
+#[test]
+fn test_delta_round_trip() {
+    let base = positions(&[(0, 1), (5, 2), (200, 3)]);
+    let current = positions(&[(0, 1), (5, 20), (300, 4), (20_000, 5)]);
+
+    let mut writer = PacketWriter::delta(Tick::new(3), Tick::new(4));
+    writer.write_delta(&base, &current);
+    let bytes = writer.finish();
+
+    let packet = Packet::decode(&bytes).unwrap();
+    assert_eq!(packet.base, Tick::new(3));
+
+    let ops = packet
+        .section::<Position>()
+        .unwrap()
+        .decode_delta::<Position>()
+        .unwrap();
+    assert_eq!(
+        ops,
+        vec![
+            (5, DeltaOp::Set(Position { x: 20, y: -20 })),
+            (200, DeltaOp::Remove),
+            (300, DeltaOp::Set(Position { x: 4, y: -4 })),
+            (20_000, DeltaOp::Set(Position { x: 5, y: -5 })),
+        ]
+    );
+
+    let mut patched = positions(&[(0, 1), (5, 2), (200, 3)]);
+    apply_delta(&mut patched, &packet).unwrap();
+    crate::testing::assert_storage_eq(&patched, &current);
+}
+
+#[test]
+fn test_header_errors() {
+    let bytes = snapshot_packet();
+
+    let mut bad = bytes.clone();
+    bad[0] = b'X';
+    assert!(matches!(
+        Packet::decode(&bad),
+        Err(DecodeError::BadMagic(_))
+    ));
+
+    let mut bad = bytes.clone();
+    bad[4] = 2;
+    assert_eq!(
+        Packet::decode(&bad).unwrap_err(),
+        DecodeError::UnsupportedVersion(2)
+    );
+
+    let mut bad = bytes.clone();
+    bad[6] = 9;
+    assert_eq!(
+        Packet::decode(&bad).unwrap_err(),
+        DecodeError::UnknownKind(9)
+    );
+
+    let mut bad = bytes.clone();
+    bad[7] = 1;
+    assert_eq!(
+        Packet::decode(&bad).unwrap_err(),
+        DecodeError::ReservedFlags(1)
+    );
+
+    let mut bad = bytes.clone();
+    bad[16..18].copy_from_slice(&500u16.to_le_bytes());
+    assert_eq!(
+        Packet::decode(&bad).unwrap_err(),
+        DecodeError::TooManySections(500)
+    );
+
+    let mut bad = bytes.clone();
+    bad.push(0);
+    assert_eq!(
+        Packet::decode(&bad).unwrap_err(),
+        DecodeError::TrailingBytes(1)
+    );
+
+    assert!(matches!(
+        Packet::decode(&bytes[..bytes.len() - 1]),
+        Err(DecodeError::UnexpectedEof { .. })
+    ));
+}
+
+#[test]
+fn test_payload_errors() {
+    let section = |payload: &[u8]| {
+        let mut bytes = PacketWriter::snapshot(Tick::new(1)).finish();
+        bytes[16..18].copy_from_slice(&1u16.to_le_bytes());
+        component_id::<Position>().encode(&mut bytes);
+        (payload.len() as u32).encode(&mut bytes);
+        bytes.extend_from_slice(payload);
+        bytes
+    };
+    let decode = |bytes: &[u8]| {
+        let packet = Packet::decode(bytes).unwrap();
+        packet.sections()[0]
+            .decode_snapshot::<Position>()
+            .map(|_| ())
+    };
+
+    // Block index out of the 128x128 hierarchy
+    let mut payload = vec![128, 0];
+    1u128.encode(&mut payload);
+    0i64.encode(&mut payload);
+    assert_eq!(
+        decode(&section(&payload)),
+        Err(DecodeError::BlockOutOfBounds { ri: 128, mi: 0 })
+    );
+
+    // Empty mask
+    let mut payload = vec![0, 0];
+    0u128.encode(&mut payload);
+    assert_eq!(
+        decode(&section(&payload)),
+        Err(DecodeError::InvalidMask { ri: 0, mi: 0 })
+    );
+
+    // Repeated block
+    let mut payload = Vec::new();
+    for _ in 0..2 {
+        payload.extend_from_slice(&[0, 1]);
+        1u128.encode(&mut payload);
+        0i64.encode(&mut payload);
+    }
+    assert_eq!(
+        decode(&section(&payload)),
+        Err(DecodeError::UnorderedBlock { ri: 0, mi: 1 })
+    );
+
+    // Mask claims more values than the payload holds
+    let mut payload = vec![0, 0];
+    u128::MAX.encode(&mut payload);
+    assert!(matches!(
+        decode(&section(&payload)),
+        Err(DecodeError::UnexpectedEof { .. })
+    ));
+
+    // Snapshot sections can't be read as deltas
+    let bytes = snapshot_packet();
+    let packet = Packet::decode(&bytes).unwrap();
+    assert!(matches!(
+        packet.sections()[0].decode_delta::<Position>(),
+        Err(DecodeError::KindMismatch { .. })
+    ));
+}
+
+#[test]
+fn test_length_prefix_cannot_force_allocation() {
+    let mut bytes = Vec::new();
+    u32::MAX.encode(&mut bytes);
+
+    let err = String::decode(&mut Reader::new(&bytes)).unwrap_err();
+    assert!(matches!(err, DecodeError::UnexpectedEof { .. }));
+
+    let err = Vec::<u64>::decode(&mut Reader::new(&bytes)).unwrap_err();
+    assert!(matches!(err, DecodeError::UnexpectedEof { .. }));
+}
+
+#[test]
+fn test_failed_apply_leaves_storage_untouched() {
+    let bytes = snapshot_packet();
+    let mut corrupt = bytes.clone();
+    // Corrupt the length prefix of the last string so it overruns the section
+    let last = corrupt.len() - 5;
+    corrupt[last] = 200;
+
+    let packet = Packet::decode(&corrupt).unwrap();
+    let mut names = Storage::<Name>::new();
+    names.set(
+        1,
+        &Name {
+            value: "one".to_string(),
+        },
+    );
+
+    assert!(apply_snapshot(&mut names, &packet).is_err());
+    assert_eq!(names.get(1).map(|n| n.value.as_str()), Some("one"));
+    assert!(names.get(5).is_none());
+}
+
+/// Deterministic mutation fuzzing: flips, truncates and splices bytes of valid packets.
+/// The decoder may reject them but must never panic.
+#[test]
+fn test_mutated_packets_never_panic() {
+    let mut delta = PacketWriter::delta(Tick::new(1), Tick::new(2));
+    delta.write_delta(
+        &positions(&[(3, 1), (900, 2)]),
+        &positions(&[(3, 5), (70_000, 6)]),
+    );
+    let seeds = [snapshot_packet(), delta.finish()];
+
+    let mut state = 0x9e37_79b9_7f4a_7c15u64;
+    let mut next = move || {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        state
+    };
+
+    for round in 0..5000 {
+        let mut bytes = seeds[round % seeds.len()].clone();
+
+        for _ in 0..=(next() % 4) {
+            let at = (next() as usize) % bytes.len().max(1);
+            match next() % 3 {
+                0 if !bytes.is_empty() => bytes[at] = next() as u8,
+                1 => bytes.truncate(at),
+                _ => bytes.insert(at.min(bytes.len()), next() as u8),
+            }
+        }
+
+        if let Ok(packet) = Packet::decode(&bytes) {
+            for section in packet.sections() {
+                let _ = section.decode_snapshot::<Position>();
+                let _ = section.decode_delta::<Position>();
+                let _ = section.decode_snapshot::<Name>();
+            }
+        }
+    }
+}
+
+/// Stores both coordinates as `i16`, half the size of the `Wire` encoding.
+struct QuantizedPosition;
+
+impl SnapshotCodec<Position> for QuantizedPosition {
+    fn encode_block(&self, values: &[&Position], out: &mut Vec<u8>) {
+        for value in values {
+            (value.x as i16).encode(out);
+            (value.y as i16).encode(out);
+        }
+    }
+
+    fn decode_block(
+        &self,
+        count: usize,
+        reader: &mut Reader<'_>,
+    ) -> Result<Vec<Position>, DecodeError> {
+        (0..count)
+            .map(|_| {
+                Ok(Position {
+                    x: i16::decode(reader)? as i32,
+                    y: i16::decode(reader)? as i32,
+                })
+            })
+            .collect()
+    }
+}
+
+/// Decodes one value fewer than asked for.
+struct ShortCodec;
+
+impl SnapshotCodec<Position> for ShortCodec {
+    fn encode_block(&self, values: &[&Position], out: &mut Vec<u8>) {
+        WireCodec.encode_block(values, out);
+    }
+
+    fn decode_block(
+        &self,
+        count: usize,
+        reader: &mut Reader<'_>,
+    ) -> Result<Vec<Position>, DecodeError> {
+        let mut values: Vec<Position> = WireCodec.decode_block(count, reader)?;
+        values.pop();
+        Ok(values)
+    }
+}
+
+fn world_with_positions(entries: &[(u32, i32)]) -> crate::world::World {
+    let mut world = crate::world::World::new();
+    for &(_, x) in entries {
+        let e = world.spawn();
+        world.set(e, &Position { x, y: -x });
+    }
+    world
+}
+
+#[test]
+fn test_world_snapshot_uses_registered_codec() {
+    let entries = [(0, 1), (1, 2), (2, 300)];
+    let mut source = world_with_positions(&entries);
+
+    let mut plain = PacketWriter::snapshot(Tick::new(1));
+    source.write_snapshot::<Position>(&mut plain);
+    let plain = plain.finish();
+
+    source.set_snapshot_codec::<Position>(QuantizedPosition);
+    let mut packed = PacketWriter::snapshot(Tick::new(1));
+    source.write_snapshot::<Position>(&mut packed);
+    let packed = packed.finish();
+    assert_eq!(plain.len() - packed.len(), entries.len() * 4);
+
+    let mut target = crate::world::World::new();
+    target.set_snapshot_codec::<Position>(QuantizedPosition);
+    target
+        .apply_snapshot::<Position>(&Packet::decode(&packed).unwrap())
+        .unwrap();
+    let restored = target.get_storage::<Position>();
+    crate::testing::assert_storage_eq(unsafe { &*restored.get() }, &positions(&entries));
+
+    // Without a codec the world falls back to `Wire`
+    let mut fallback = crate::world::World::new();
+    fallback
+        .apply_snapshot::<Position>(&Packet::decode(&plain).unwrap())
+        .unwrap();
+    let restored = fallback.get_storage::<Position>();
+    crate::testing::assert_storage_eq(unsafe { &*restored.get() }, &positions(&entries));
+}
+
+#[test]
+fn test_codec_value_count_is_checked() {
+    let mut world = world_with_positions(&[(0, 1), (1, 2)]);
+    let mut writer = PacketWriter::snapshot(Tick::new(1));
+    world.write_snapshot::<Position>(&mut writer);
+    let bytes = writer.finish();
+
+    world.set_snapshot_codec::<Position>(ShortCodec);
+    let err = world
+        .apply_snapshot::<Position>(&Packet::decode(&bytes).unwrap())
+        .unwrap_err();
+    assert_eq!(err, DecodeError::InvalidValue("snapshot codec value count"));
+
+    let storage = world.get_storage::<Position>();
+    assert_eq!(unsafe { (*storage.get()).len() }, 2);
+}
+
+#[derive(Component, Default, Clone, Debug, PartialEq)]
+struct Selected;
+
+fn saved_world() -> (crate::world::World, Vec<crate::entity::Entity>) {
+    let mut world = crate::world::World::new();
+    let entities: Vec<_> = (1..4)
+        .map(|x| {
+            let e = world.spawn();
+            world.set(e, &Position { x, y: -x });
+            e
+        })
+        .collect();
+    world.set(
+        entities[1],
+        &Name {
+            value: "one".to_string(),
+        },
+    );
+    world.set(entities[2], &Selected);
+    world.build_scheduler();
+    world.run();
+    (world, entities)
+}
+
+#[test]
+fn test_derive_detects_serializable() {
+    assert!(Position::wire_codec().is_some());
+    assert!(crate::entity::Entity::wire_codec().is_some());
+    assert!(Selected::wire_codec().is_none());
+}
+
+#[test]
+fn test_save_snapshot_loads_into_fresh_world() {
+    let (world, entities) = saved_world();
+    let bytes = world.save_snapshot();
+
+    let packet = Packet::decode(&bytes).unwrap();
+    assert_eq!(packet.tick, world.current_tick());
+    assert!(packet.section::<Selected>().is_none());
+
+    let mut joined = crate::world::World::new();
+    assert_eq!(joined.load_snapshot(&bytes), Ok(world.current_tick()));
+    for (i, &e) in entities.iter().enumerate() {
+        assert_eq!(joined.get::<Position>(e), world.get::<Position>(e), "{}", i);
+        assert_eq!(joined.get::<Name>(e), world.get::<Name>(e));
+    }
+    assert_eq!(joined.get::<Selected>(entities[2]), None);
+
+    // Loaded entities are allocated, so new ones get fresh indices
+    let spawned = joined.spawn();
+    assert!(entities.iter().all(|e| e.index() != spawned.index()));
+}
+
+#[test]
+fn test_load_snapshot_replaces_state() {
+    let (mut world, entities) = saved_world();
+    let bytes = world.save_snapshot();
+
+    world.set(entities[0], &Position { x: 50, y: 50 });
+    world.storage_mut::<Name>().remove(entities[1].index());
+    let spawned = world.spawn();
+    world.set(spawned, &Position { x: 9, y: 9 });
+    world.run();
+
+    world.load_snapshot(&bytes).unwrap();
+    assert_eq!(
+        world.get::<Position>(entities[0]),
+        Some(&Position { x: 1, y: -1 })
+    );
+    assert_eq!(
+        world.get::<Name>(entities[1]).map(|n| n.value.as_str()),
+        Some("one")
+    );
+    assert_eq!(world.get::<Position>(spawned), None);
+    // Components outside the snapshot keep their values
+    assert_eq!(world.get::<Selected>(entities[2]), Some(&Selected));
+}
+
+#[test]
+fn test_failed_load_snapshot_leaves_world_untouched() {
+    let (mut world, entities) = saved_world();
+    let mut corrupt = world.save_snapshot();
+    // Drop the last byte, so the final section overruns the input
+    corrupt.pop();
+    world.set(entities[0], &Position { x: 50, y: 50 });
+    assert!(world.load_snapshot(&corrupt).is_err());
+    assert_eq!(
+        world.get::<Position>(entities[0]),
+        Some(&Position { x: 50, y: 50 })
+    );
+
+    let mut delta = PacketWriter::delta(Tick::new(0), Tick::new(1));
+    delta.write_delta(&positions(&[]), &positions(&[(0, 1)]));
+    assert!(matches!(
+        world.load_snapshot(&delta.finish()),
+        Err(DecodeError::KindMismatch { .. })
+    ));
+}
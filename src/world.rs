@@ -1,20 +1,130 @@
-use crate::component::{Component, Destroyed};
+use crate::access::{StorageAccess, StorageMut, StorageRef};
+use crate::audit::{SystemAudit, TickAudit};
+use crate::bundle::{Bundle, BundleWriter};
+use crate::component::{
+    Component, ComponentMask, ComponentRegistration, ComponentSet, Destroyed, MAX_COMPONENTS,
+};
+use crate::cow::WorldFork;
+use crate::dirty::{DirtyBridge, DirtyMarker};
+use crate::dynamic::{DynValue, DynValueRef, DynVtable};
+use crate::effects::{EffectQueue, Effects, EffectsLike};
 use crate::entity::Entity;
-use crate::rollback::StorageLike;
-use crate::scheduler::{PipelineStage, Scheduler};
-use crate::storage::Storage;
-use crate::tick::Tick;
-use std::any::Any;
-use std::cell::UnsafeCell;
+use crate::expiry::{Expiry, ExpiryLike, ExpirySystem, ExpiryTable};
+#[cfg(feature = "serde")]
+use crate::export::{ComponentValues, Snapshot, ValueError};
+use crate::graph::{GraphDescription, ScheduleDescription, short_type_name};
+use crate::hashtree::{DesyncReport, DirtyBlocks, HashCache, HashReport, HashTree};
+use crate::journal::{Change, ChangeJournal, ChangeKind, ChangeLog};
+use crate::hierarchy::{Children, Parent};
+use crate::ingest::{IngestLike, IngestQueue, IngestSender, IngestStatus, IngestSystem, Ticket};
+use crate::input::{InputBuffer, InputError, InputLike};
+#[cfg(feature = "panic-isolation")]
+use crate::isolation::TickError;
+use crate::mailbox::{Inbox, Mailbox, MailboxLike, MailboxQueue};
+use crate::ownership::{Ownership, PeerId};
+use crate::pending::PendingTable;
+use crate::phase::{TickHook, WorldPhase};
+use crate::plugin::Plugin;
+use crate::query::{Query, QueryData};
+use crate::relation::Relation;
+use crate::removal::{RemovalLike, RemovalQueue};
+use crate::registry::TypeRegistry;
+use crate::rng::{RngClock, RngSource};
+use crate::savestate::{SaveSlot, SavedComponents, SavedStorage, SlotInfo};
+use crate::resource::{ResourceCell, ResourceLike};
+use crate::state::{State, StateDriver, StateLike};
+use crate::rollback::{
+    DeltaCompressible, OverflowAction, RollbackConfig, RollbackOverflow, RollbackOverflowHandler,
+    RollbackReport, RollbackWindow, StorageLike,
+};
+use crate::scheduler::{BoundLoop, LoopGroup, PipelineGroup, PipelineStage, Scheduler};
+use crate::sequence::{stage_hash, stage_key, EXTERNAL_STAGE};
+use crate::statehash::StateHash;
+use crate::storage::{
+    ComponentStorage, HistoryStats, MemoryStats, SnapshotGranularity, Storage,
+};
+use crate::tags::{TagId, TagRegistry, TagSet};
+use crate::tick::{Tick, TickDelta};
+use crate::tickrate::{self, TickRateChange, TickRateLog};
+use crate::warmup::WarmupPlan;
+use crate::watch::{QueryWatch, WatchState};
+use crate::wire::{
+    DecodeError, Packet, PacketKind, PacketWriter, SnapshotCodec, Wire, WireCodec,
+    apply_snapshot_where, apply_snapshot_with, component_id,
+};
+use crate::zone::{ZoneChannel, ZoneSender};
+use std::any::{Any, TypeId};
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::cell::{RefCell, UnsafeCell};
 use std::mem::MaybeUninit;
-use std::rc::Rc;
+use std::rc::{Rc, Weak};
+use std::time::Duration;
 
 pub struct World {
-    pub storages: [MaybeUninit<Box<dyn StorageLike>>; 128],
-    pub mask: u128,
+    pub storages: [MaybeUninit<Box<dyn StorageLike>>; MAX_COMPONENTS],
+    pub mask: ComponentMask,
     scheduler: Option<Scheduler>,
     pending_systems: Vec<Box<dyn PipelineStage>>,
+    pending_loops: Vec<BoundLoop>,
+    /// Whether systems were added or removed since the scheduler was built, so the next
+    /// tick rebuilds it from the pending systems
+    scheduler_stale: bool,
     current_tick: Tick,
+    phase: WorldPhase,
+    history_start: Tick,
+    max_rollback_depth: Option<u32>,
+    rollback_config: RollbackConfig,
+    /// Oldest tick the storages were last pruned to.
+    pruned_to: Tick,
+    rollback_overflow_handler: Option<RollbackOverflowHandler>,
+    tick_start_hooks: Vec<TickHook>,
+    tick_end_hooks: Vec<TickHook>,
+    /// Set when a system panicked, until a rollback
+    #[cfg(feature = "panic-isolation")]
+    tainted: Option<TickError>,
+    mailboxes: TypeRegistry<Rc<dyn MailboxLike>>,
+    removals: TypeRegistry<Rc<dyn RemovalLike>>,
+    ingests: TypeRegistry<Rc<dyn IngestLike>>,
+    expiries: TypeRegistry<Rc<dyn ExpiryLike>>,
+    resources: TypeRegistry<Rc<dyn ResourceLike>>,
+    states: TypeRegistry<Rc<dyn StateLike>>,
+    inputs: TypeRegistry<Rc<dyn InputLike>>,
+    effects: TypeRegistry<Rc<dyn EffectsLike>>,
+    zone_channels: TypeRegistry<Rc<dyn Any>>,
+    snapshot_codecs: TypeRegistry<Rc<dyn Any>>,
+    /// Erased operations and type index of every component with a storage, keyed by
+    /// `wire::component_id`, for `get_dyn`.
+    dyn_components: HashMap<u64, (usize, DynVtable)>,
+    local_peer: Option<PeerId>,
+    watches: Vec<Weak<RefCell<WatchState>>>,
+    hash_cache: HashCache,
+    journal: ChangeJournal,
+    rng_clock: Rc<RngClock>,
+    tick_rates: TickRateLog,
+    /// Time `advance` received but hasn't simulated yet.
+    accumulator: Duration,
+    /// Named savestates, outside the rollback history.
+    save_slots: BTreeMap<String, SaveSlot>,
+    /// Writes staged for networked entities that don't exist yet, see `stage_pending`.
+    pending: PendingTable,
+    /// Storages of dirty bridge markers, left out of `hash_tree`.
+    presentation_mask: ComponentMask,
+    /// Whether schedulers time their systems, see `set_profiling`.
+    profiling: bool,
+    /// Systems switched off with `set_system_enabled`.
+    disabled_systems: HashSet<TypeId>,
+    /// Plugin types added with `add_plugin`.
+    plugins: HashSet<TypeId>,
+    /// Tag names registered with `register_tag`.
+    tags: TagRegistry,
+    /// State hashes of the ticks in the rollback window, oldest first, while recording.
+    state_hashes: Option<VecDeque<StateHash>>,
+    /// Write-set hashes of the ticks simulated since the last `take_audits`, while auditing.
+    audits: Option<Vec<TickAudit>>,
+    /// Whether late registration panics, for worlds built by a strict `WorldBuilder`.
+    strict: bool,
+    #[cfg(feature = "watchdog")]
+    watchdog: Option<crate::watchdog::WatchdogConfig>,
 }
 
 impl World {
@@ -37,10 +147,52 @@ impl World {
     {
         let mut world = World {
             storages: std::array::from_fn(|_| MaybeUninit::uninit()),
-            mask: 0,
+            mask: ComponentMask::default(),
             scheduler: None,
             pending_systems: Vec::new(),
+            pending_loops: Vec::new(),
+            scheduler_stale: false,
             current_tick: Tick::new(0),
+            phase: WorldPhase::Idle,
+            history_start: Tick::new(0),
+            max_rollback_depth: None,
+            rollback_config: RollbackConfig::default(),
+            pruned_to: Tick::new(0),
+            rollback_overflow_handler: None,
+            tick_start_hooks: Vec::new(),
+            tick_end_hooks: Vec::new(),
+            #[cfg(feature = "panic-isolation")]
+            tainted: None,
+            mailboxes: TypeRegistry::new(),
+            removals: TypeRegistry::new(),
+            ingests: TypeRegistry::new(),
+            expiries: TypeRegistry::new(),
+            resources: TypeRegistry::new(),
+            states: TypeRegistry::new(),
+            inputs: TypeRegistry::new(),
+            effects: TypeRegistry::new(),
+            zone_channels: TypeRegistry::new(),
+            snapshot_codecs: TypeRegistry::new(),
+            dyn_components: HashMap::new(),
+            local_peer: None,
+            watches: Vec::new(),
+            hash_cache: HashCache::default(),
+            journal: ChangeJournal::default(),
+            rng_clock: Rc::new(RngClock::new(0)),
+            tick_rates: TickRateLog::default(),
+            accumulator: Duration::ZERO,
+            save_slots: BTreeMap::new(),
+            pending: PendingTable::new(),
+            presentation_mask: ComponentMask::default(),
+            profiling: false,
+            disabled_systems: HashSet::new(),
+            plugins: HashSet::new(),
+            tags: TagRegistry::default(),
+            state_hashes: None,
+            audits: None,
+            strict: false,
+            #[cfg(feature = "watchdog")]
+            watchdog: None,
         };
 
         // Create systems using the provided closure
@@ -61,28 +213,75 @@ impl World {
     pub fn new() -> Self {
         World {
             storages: std::array::from_fn(|_| MaybeUninit::uninit()),
-            mask: 0,
+            mask: ComponentMask::default(),
             scheduler: None,
             pending_systems: Vec::new(),
+            pending_loops: Vec::new(),
+            scheduler_stale: false,
             current_tick: Tick::new(0),
+            phase: WorldPhase::Idle,
+            history_start: Tick::new(0),
+            max_rollback_depth: None,
+            rollback_config: RollbackConfig::default(),
+            pruned_to: Tick::new(0),
+            rollback_overflow_handler: None,
+            tick_start_hooks: Vec::new(),
+            tick_end_hooks: Vec::new(),
+            #[cfg(feature = "panic-isolation")]
+            tainted: None,
+            mailboxes: TypeRegistry::new(),
+            removals: TypeRegistry::new(),
+            ingests: TypeRegistry::new(),
+            expiries: TypeRegistry::new(),
+            resources: TypeRegistry::new(),
+            states: TypeRegistry::new(),
+            inputs: TypeRegistry::new(),
+            effects: TypeRegistry::new(),
+            zone_channels: TypeRegistry::new(),
+            snapshot_codecs: TypeRegistry::new(),
+            dyn_components: HashMap::new(),
+            local_peer: None,
+            watches: Vec::new(),
+            hash_cache: HashCache::default(),
+            journal: ChangeJournal::default(),
+            rng_clock: Rc::new(RngClock::new(0)),
+            tick_rates: TickRateLog::default(),
+            accumulator: Duration::ZERO,
+            save_slots: BTreeMap::new(),
+            pending: PendingTable::new(),
+            presentation_mask: ComponentMask::default(),
+            profiling: false,
+            disabled_systems: HashSet::new(),
+            plugins: HashSet::new(),
+            tags: TagRegistry::default(),
+            state_hashes: None,
+            audits: None,
+            strict: false,
+            #[cfg(feature = "watchdog")]
+            watchdog: None,
         }
     }
 
-    pub fn get_storage<T: Component>(&mut self) -> Rc<UnsafeCell<Storage<T>>> {
+    pub fn get_storage<T: Component>(&mut self) -> Rc<UnsafeCell<T::Storage>> {
         let id = T::type_index();
 
-        if id >= 128 {
+        if id >= MAX_COMPONENTS {
             panic!("invalid component type index")
         }
 
-        let bit = 1u128 << id;
-
-        if (self.mask & bit) == 0 {
-            let rc = Rc::new(UnsafeCell::new(Storage::<T>::new()));
+        if !self.mask.contains(id) {
+            self.assert_registration_open("storage", std::any::type_name::<T>());
+            let rc = Rc::new(UnsafeCell::new(<T::Storage as ComponentStorage>::new()));
+            unsafe { (*rc.get()).set_tick(self.current_tick) };
             self.storages[id] = MaybeUninit::new(Box::new(rc.clone()) as Box<dyn StorageLike>);
-            self.mask |= bit;
+            self.mask.insert(id);
+            let vtable = DynVtable::of::<T>();
+            self.dyn_components.insert(vtable.component_id(), (id, vtable));
 
             if !T::IS_TEMPORARY {
+                // A built scheduler is rebuilt before the next tick, so the cleanup system
+                // clears the new storage's change masks from then on
+                self.invalidate_scheduler();
                 let cleanup_system = T::cleanup_system(self);
                 self.add_system_instance(cleanup_system);
             }
@@ -93,12 +292,170 @@ impl World {
         let storage_like = unsafe { self.storages[id].assume_init_ref() };
 
         unsafe {
-            let raw = storage_like.as_any() as *const dyn Any as *const Rc<UnsafeCell<Storage<T>>>;
+            let raw = storage_like.as_any() as *const dyn Any as *const Rc<UnsafeCell<T::Storage>>;
 
             (*raw).clone()
         }
     }
 
+    /// Returns the storage for `T` without creating it, e.g. to read the state in a loop
+    /// group's convergence test.
+    pub fn storage_ref<T: Component>(&self) -> Option<&T::Storage> {
+        self.storage_handle::<T>().map(|rc| unsafe { &*rc.get() })
+    }
+
+    pub(crate) fn storage_handle<T: Component>(&self) -> Option<&Rc<UnsafeCell<T::Storage>>> {
+        let id = T::type_index();
+        if !self.mask.contains(id) {
+            return None;
+        }
+
+        let storage_like = unsafe { self.storages[id].assume_init_ref() };
+        let raw = storage_like.as_any() as *const dyn Any as *const Rc<UnsafeCell<T::Storage>>;
+        Some(unsafe { &*raw })
+    }
+
+    /// Shared guard over the storage of `T`, if the world has one. See the `access` module.
+    pub fn storage<T: Component>(&self) -> Option<StorageRef<'_, T>> {
+        self.storage_ref::<T>().map(StorageRef::new)
+    }
+
+    /// Exclusive guard over the storage of `T`, created if needed. Writes through it are
+    /// change-tracked and rolled back.
+    ///
+    /// # Panics
+    /// Debug builds panic outside the idle phase, like `set`.
+    pub fn storage_mut<T: Component>(&mut self) -> StorageMut<'_, T> {
+        self.assert_phase("storage_mut");
+        self.get_storage::<T>();
+        let rc = self.storage_handle::<T>().unwrap();
+        // `&mut self` rules out every other reference into the world's storages
+        StorageMut::new(unsafe { &mut *rc.get() })
+    }
+
+    /// Locks the world and hands out runtime-checked guards over several storages at once.
+    ///
+    /// # Panics
+    /// Debug builds panic outside the idle phase, like `set`.
+    pub fn storages(&mut self) -> StorageAccess<'_> {
+        self.assert_phase("storages");
+        StorageAccess::new(self)
+    }
+
+    /// The `T` of `entity`, if the entity is alive and has one.
+    pub fn get<T: Component>(&self, entity: Entity) -> Option<&T> {
+        let current = self.storage_ref::<Entity>()?.get(entity.index())?;
+        if *current != entity {
+            return None;
+        }
+        self.storage_ref::<T>()?.get(entity.index())
+    }
+
+    /// The tick the `T` of `entity` last changed in, if the entity is alive and has one.
+    /// Changes made since the last tick report the current tick. Compare it with the last
+    /// tick you looked at, as `ChangedSince = tick` does.
+    pub fn changed_tick<T: Component>(&self, entity: Entity) -> Option<Tick> {
+        self.get::<T>(entity)?;
+        self.storage_ref::<T>()?.changed_tick(entity.index())
+    }
+
+    /// Keeps the change marks of `T` from the current tick on, for consumers that read
+    /// them less often than every tick, or stops keeping them. The cleanup stage still
+    /// clears the storage's own masks at the end of every tick, since rollback recording
+    /// relies on them, but merges them into a kept set first: `Changed = [T]` systems,
+    /// `query().changed::<T>()` and `kept_changes` see every mark until
+    /// `clear_kept_changes`. The journal and `ChangedSince` still see each change once.
+    ///
+    /// Kept marks survive rollback and are dropped by `clear_world` and
+    /// `reset_to_tick_zero`. Only entities that still have `T` are reported: an entity
+    /// whose `T` was removed or that was destroyed drops out of the kept set, so read
+    /// `removed::<T>()` every tick if removals matter.
+    ///
+    /// # Example
+    /// ```ignore
+    /// world.keep_changes::<Transform>(true);
+    /// world.run_for(3);
+    /// for entity in world.kept_changes::<Transform>() {
+    ///     scene.sync(entity, world.get::<Transform>(entity));
+    /// }
+    /// world.clear_kept_changes::<Transform>();
+    /// ```
+    pub fn keep_changes<T: Component>(&mut self, keep: bool) {
+        let storage = self.get_storage::<T>();
+        unsafe { (*storage.get()).set_keep_changes(keep) };
+    }
+
+    /// The living entities whose `T` changed since `keep_changes` or the last
+    /// `clear_kept_changes`, in ascending index order. Only the current tick's changes
+    /// unless `T` is kept.
+    pub fn kept_changes<T: Component>(&mut self) -> Vec<Entity> {
+        self.query::<(&Entity, &T)>()
+            .changed::<T>()
+            .map(|(entity, _)| *entity)
+            .collect()
+    }
+
+    /// Forgets the kept changes of `T`, keeping those from the current tick on.
+    pub fn clear_kept_changes<T: Component>(&mut self) {
+        let storage = self.get_storage::<T>();
+        unsafe { (*storage.get()).clear_kept_changes() };
+    }
+
+    /// Mutable access to the `T` of `entity`, if the entity is alive and has one. The
+    /// component is marked changed and rolled back like one written with `set`.
+    ///
+    /// # Panics
+    /// Debug builds panic outside the idle phase and when another peer owns the
+    /// component, like `set`.
+    pub fn get_mut<T: Component>(&mut self, entity: Entity) -> Option<&mut T> {
+        self.assert_phase("get_mut");
+        #[cfg(debug_assertions)]
+        if let Some(local) = self.local_peer {
+            let owner = self.owner_of::<T>(entity);
+            assert!(
+                owner == local,
+                "World::get_mut::<{}> on entity {} owned by {:?}, but the local peer is {:?}",
+                std::any::type_name::<T>(),
+                entity.index(),
+                owner,
+                local
+            );
+        }
+        self.get::<T>(entity)?;
+
+        let rc = self.storage_handle::<T>()?;
+        // `&mut self` rules out every other reference into the world's storages
+        Some(unsafe { (*rc.get()).get_mut(entity.index()) })
+    }
+
+    /// Removes the `T` of `entity` and returns it, if the entity is alive and has one.
+    /// The removal is change-tracked, matches next tick's `WasRemoved=[T]` filters and is
+    /// undone by rolling back past it, like removals made by systems.
+    ///
+    /// # Panics
+    /// Debug builds panic outside the idle phase and when another peer owns the
+    /// component, like `set`.
+    pub fn remove<T: Component>(&mut self, entity: Entity) -> Option<T> {
+        self.assert_phase("remove");
+        #[cfg(debug_assertions)]
+        if let Some(local) = self.local_peer {
+            let owner = self.owner_of::<T>(entity);
+            assert!(
+                owner == local,
+                "World::remove::<{}> on entity {} owned by {:?}, but the local peer is {:?}",
+                std::any::type_name::<T>(),
+                entity.index(),
+                owner,
+                local
+            );
+        }
+        let value = self.get::<T>(entity)?.clone();
+
+        let rc = self.storage_handle::<T>()?;
+        unsafe { (*rc.get()).remove(entity.index()) };
+        Some(value)
+    }
+
     pub fn run_system<T: PipelineStage>(&mut self) {
         T::create(self).run();
     }
@@ -118,6 +475,7 @@ impl World {
     /// world.run();
     /// ```
     pub fn add_system<T: PipelineStage>(&mut self) {
+        self.assert_registration_open("system", std::any::type_name::<T>());
         let system = T::create(self);
         self.pending_systems.push(Box::new(system));
     }
@@ -131,9 +489,123 @@ impl World {
     /// world.add_system_instance(Box::new(system));
     /// ```
     pub fn add_system_instance(&mut self, system: Box<dyn PipelineStage>) {
+        self.assert_registration_open("system", system.name());
         self.pending_systems.push(system);
     }
 
+    /// Adds the system `S` to a world whose scheduler may already be built, e.g. from a
+    /// plugin loaded after startup. The scheduler is rebuilt with it before the next tick;
+    /// until then `scheduler()` returns `None`.
+    ///
+    /// The system also joins the schedule of every peer only at the tick it is added at, so
+    /// add systems that change simulated state at the same tick everywhere.
+    ///
+    /// # Example
+    /// ```ignore
+    /// world.build_scheduler();
+    /// world.run();
+    /// world.add_system_dynamic::<PluginSystem>();
+    /// world.run(); // PluginSystem runs from this tick on
+    /// ```
+    pub fn add_system_dynamic<S: PipelineStage>(&mut self) {
+        self.invalidate_scheduler();
+        self.add_system::<S>();
+    }
+
+    /// Removes every instance of the system `S`, built or pending, and returns whether there
+    /// was one. The scheduler is rebuilt without it before the next tick, as with
+    /// `add_system_dynamic`.
+    pub fn remove_system<S: PipelineStage>(&mut self) -> bool {
+        self.assert_registration_open("system removal", std::any::type_name::<S>());
+        self.invalidate_scheduler();
+        let before = self.pending_systems.len();
+        self.pending_systems
+            .retain(|system| PipelineStage::type_id(system.as_ref()) != TypeId::of::<S>());
+        before != self.pending_systems.len()
+    }
+
+    /// Returns the systems and loop groups of a built scheduler to the pending lists, in
+    /// registration order, and marks the scheduler for a rebuild.
+    fn invalidate_scheduler(&mut self) {
+        let Some(scheduler) = self.scheduler.take() else {
+            return;
+        };
+
+        let (mut systems, mut loops) = scheduler.into_parts();
+        systems.append(&mut self.pending_systems);
+        self.pending_systems = systems;
+        // Loop groups added since the build replace the built ones
+        loops.retain(|bound| self.pending_loops.iter().all(|l| l.group() != bound.group()));
+        loops.append(&mut self.pending_loops);
+        self.pending_loops = loops;
+        self.scheduler_stale = true;
+    }
+
+    /// Whether the world was built by a strict `WorldBuilder`, so registering storages,
+    /// resources, systems, plugins or loop groups panics, see the `builder` module.
+    pub fn is_strict(&self) -> bool {
+        self.strict
+    }
+
+    pub(crate) fn set_strict(&mut self, strict: bool) {
+        self.strict = strict;
+    }
+
+    fn assert_registration_open(&self, kind: &str, name: &str) {
+        assert!(
+            !self.strict,
+            "late registration of {} `{}` in a strict world, declare it on the WorldBuilder",
+            kind, name
+        );
+    }
+
+    /// Rebuilds the scheduler if systems were added or removed since it was built.
+    fn rebuild_stale_scheduler(&mut self) {
+        if self.scheduler_stale {
+            self.build_scheduler();
+        }
+    }
+
+    /// Builds `plugin` into the world, see the `plugin` module. A plugin type that was already
+    /// added is skipped, so plugins can add the plugins they depend on. Added after
+    /// `build_scheduler()`, the plugin's systems are scheduled from the next tick on.
+    ///
+    /// # Example
+    /// ```ignore
+    /// world.add_plugin(PhysicsPlugin { gravity: -9.8 });
+    /// world.build_scheduler();
+    /// ```
+    pub fn add_plugin<P: Plugin>(&mut self, plugin: P) {
+        if self.plugins.contains(&TypeId::of::<P>()) {
+            return;
+        }
+        self.assert_registration_open("plugin", plugin.name());
+        self.plugins.insert(TypeId::of::<P>());
+        self.invalidate_scheduler();
+        plugin.build(self);
+    }
+
+    /// Whether a plugin of type `P` was added with `add_plugin`.
+    pub fn has_plugin<P: Plugin>(&self) -> bool {
+        self.plugins.contains(&TypeId::of::<P>())
+    }
+
+    /// Makes the pipeline group `G` a loop group: its direct children run repeatedly within
+    /// each tick as described by `group`, see `LoopGroup`. Call it before
+    /// `build_scheduler()`.
+    ///
+    /// # Example
+    /// ```ignore
+    /// world.add_loop_group::<SolverGroup>(LoopGroup::run_until(|world| converged(world), 8));
+    /// world.add_system::<RelaxConstraints>(); // Parent=[SolverGroup]
+    /// world.build_scheduler();
+    /// ```
+    pub fn add_loop_group<G: PipelineGroup>(&mut self, group: LoopGroup) {
+        self.assert_registration_open("loop group", std::any::type_name::<G>());
+        self.pending_loops.retain(|l| l.group() != TypeId::of::<G>());
+        self.pending_loops.push(group.bind::<G>());
+    }
+
     /// Builds the scheduler from all pending systems.
     /// This will panic if there are circular dependencies or non-deterministic ordering.
     /// After building, the scheduler is ready to run and pending systems are cleared.
@@ -157,7 +629,133 @@ impl World {
     /// automatically builds the scheduler.
     pub fn build_scheduler(&mut self) {
         let systems = std::mem::take(&mut self.pending_systems);
-        self.scheduler = Some(Scheduler::new(systems));
+        let loops = std::mem::take(&mut self.pending_loops);
+        self.scheduler = Some(Scheduler::with_loops(systems, loops));
+        self.scheduler_stale = false;
+
+        let audit = self.system_audit();
+        if let Some(scheduler) = self.scheduler.as_mut() {
+            scheduler.set_profiling(self.profiling);
+            scheduler.set_audit(audit);
+            for &system in &self.disabled_systems {
+                scheduler.set_enabled(system, false);
+            }
+        }
+
+        #[cfg(feature = "watchdog")]
+        if let Some(scheduler) = self.scheduler.as_mut() {
+            scheduler.set_watchdog(self.watchdog.clone());
+        }
+    }
+
+    /// Starts (or with `false`, stops) timing every system of the current scheduler and any
+    /// scheduler built later. See the `profile` module.
+    pub fn set_profiling(&mut self, enabled: bool) {
+        if let Some(scheduler) = self.scheduler.as_mut() {
+            scheduler.set_profiling(enabled);
+        }
+        self.profiling = enabled;
+    }
+
+    /// Starts (or with `false`, stops) hashing every system's write set after it ran, in
+    /// the current scheduler and any scheduler built later, see the `audit` module.
+    /// Stopping forgets the audits not taken yet.
+    pub fn set_determinism_audit(&mut self, enabled: bool) {
+        if enabled == self.audits.is_some() {
+            return;
+        }
+        self.audits = enabled.then(Vec::new);
+        let audit = self.system_audit();
+        if let Some(scheduler) = self.scheduler.as_mut() {
+            scheduler.set_audit(audit);
+        }
+    }
+
+    /// Removes and returns the audits of the ticks simulated since the last call, oldest
+    /// first. Empty while the audit is off.
+    pub fn take_audits(&mut self) -> Vec<TickAudit> {
+        self.audits.as_mut().map(std::mem::take).unwrap_or_default()
+    }
+
+    /// The write sets of the scheduler's systems, if auditing.
+    fn system_audit(&self) -> Option<SystemAudit> {
+        let scheduler = self.scheduler.as_ref().filter(|_| self.audits.is_some())?;
+        let mut storages: HashMap<TypeId, *const dyn StorageLike> = HashMap::new();
+        for id in self.mask.iter() {
+            let storage = unsafe { self.storages[id].assume_init_ref() }.as_ref();
+            storages.insert(storage.component_type_id(), storage);
+        }
+
+        let write_sets = scheduler
+            .systems()
+            .map(|system| {
+                let writes = system.writes().iter();
+                writes.filter_map(|id| storages.get(id).copied()).collect()
+            })
+            .collect();
+        Some(SystemAudit::new(write_sets))
+    }
+
+    fn record_audit(&mut self, tick: Tick, sequential: bool) {
+        let Some(audits) = self.audits.as_mut() else {
+            return;
+        };
+        let systems = self.scheduler.as_ref().and_then(|s| s.take_audit());
+        audits.push(TickAudit {
+            tick,
+            sequential,
+            systems: systems.unwrap_or_default(),
+        });
+    }
+
+    /// Switches the system `S` off (or back on) in the current scheduler and any scheduler
+    /// built later, e.g. for debug systems. A disabled system is skipped every tick until
+    /// it is enabled again; see `RunCondition` for systems that run depending on the world.
+    ///
+    /// The switch is local and not rolled back. A system that changes simulated state must
+    /// be switched at the same tick on every peer.
+    pub fn set_system_enabled<S: PipelineStage>(&mut self, enabled: bool) {
+        let system = TypeId::of::<S>();
+        if let Some(scheduler) = self.scheduler.as_mut() {
+            scheduler.set_enabled(system, enabled);
+        }
+        if enabled {
+            self.disabled_systems.remove(&system);
+        } else {
+            self.disabled_systems.insert(system);
+        }
+    }
+
+    /// Whether the system `S` wasn't switched off with `set_system_enabled`.
+    pub fn is_system_enabled<S: PipelineStage>(&self) -> bool {
+        !self.disabled_systems.contains(&TypeId::of::<S>())
+    }
+
+    /// Reorders the wavefronts of the current scheduler so the systems starting the longest
+    /// chains by profiled run time are spawned first, see `Scheduler::prioritize`.
+    pub fn prioritize_schedule(&mut self) {
+        if let Some(scheduler) = self.scheduler.as_mut() {
+            scheduler.prioritize();
+        }
+    }
+
+    /// Installs (or with `None`, removes) the overrun watchdog on the current scheduler and
+    /// any scheduler built later. See the `watchdog` module.
+    #[cfg(feature = "watchdog")]
+    pub fn set_watchdog(&mut self, config: Option<crate::watchdog::WatchdogConfig>) {
+        if let Some(scheduler) = self.scheduler.as_mut() {
+            scheduler.set_watchdog(config.clone());
+        }
+        self.watchdog = config;
+    }
+
+    /// Removes and returns the overruns recorded by the watchdog so far.
+    #[cfg(feature = "watchdog")]
+    pub fn take_overruns(&mut self) -> Vec<crate::watchdog::Overrun> {
+        self.scheduler
+            .as_ref()
+            .map(|s| s.take_overruns())
+            .unwrap_or_default()
     }
 
     /// Runs the scheduler and increments the world tick.
@@ -174,36 +772,42 @@ impl World {
     /// world.run(); // Tick 1 -> 2
     /// ```
     pub fn run(&mut self) {
+        self.assert_phase("run");
+        self.rebuild_stale_scheduler();
+        #[cfg(feature = "panic-isolation")]
+        if self.tainted.is_some() {
+            return;
+        }
+        self.begin_tick();
+        let tick = self.current_tick;
+        self.run_tick_hooks(tick, false);
+        self.phase = WorldPhase::Simulating;
+
         if let Some(ref scheduler) = self.scheduler {
-            scheduler.run();
+            scheduler.run_in(self);
         } else {
             panic!("Scheduler has not been built. Call build_scheduler() first.");
         }
 
+        #[cfg(feature = "panic-isolation")]
+        self.record_failure(tick);
+        self.end_effects();
+
+        // Mailboxes and removal events only live for a single tick
+        self.clear_mailboxes();
+        self.clear_removals();
+
         // Increment tick
         self.current_tick = Tick::new(self.current_tick.value().wrapping_add(1));
 
         // Update all storages with the new tick
-        let mut mask = self.mask;
-        while mask != 0 {
-            let start = mask.trailing_zeros();
-            let run = (mask >> start).trailing_ones();
-
-            for i in 0..run {
-                let idx = (start + i) as usize;
-                unsafe {
-                    let storage = self.storages[idx].assume_init_ref();
-                    storage.set_tick(self.current_tick);
-                }
-            }
-
-            let range_mask = if run == 128 {
-                u128::MAX
-            } else {
-                ((1u128 << run) - 1) << start
-            };
-            mask &= !range_mask;
-        }
+        self.sync_storage_ticks();
+        self.phase = WorldPhase::Idle;
+        self.journal_tick(tick);
+        self.record_state_hash(tick);
+        self.record_audit(tick, false);
+        crate::watch::refresh_all(&mut self.watches);
+        self.run_tick_hooks(tick, true);
     }
 
     /// Runs the scheduler sequentially and increments the world tick.
@@ -224,35 +828,217 @@ impl World {
     /// world.run_sequential(); // Tick 1 -> 2 (sequential execution)
     /// ```
     pub fn run_sequential(&mut self) {
+        self.assert_phase("run");
+        self.rebuild_stale_scheduler();
+        #[cfg(feature = "panic-isolation")]
+        if self.tainted.is_some() {
+            return;
+        }
+        self.begin_tick();
+        let tick = self.current_tick;
+        self.run_tick_hooks(tick, false);
+        self.phase = WorldPhase::Simulating;
+
         if let Some(ref scheduler) = self.scheduler {
-            scheduler.run_sequential();
+            scheduler.run_sequential_in(self);
         } else {
             panic!("Scheduler has not been built. Call build_scheduler() first.");
         }
 
+        #[cfg(feature = "panic-isolation")]
+        self.record_failure(tick);
+        self.end_effects();
+
+        // Mailboxes and removal events only live for a single tick
+        self.clear_mailboxes();
+        self.clear_removals();
+
         // Increment tick
         self.current_tick = Tick::new(self.current_tick.value().wrapping_add(1));
 
         // Update all storages with the new tick
-        let mut mask = self.mask;
-        while mask != 0 {
-            let start = mask.trailing_zeros();
-            let run = (mask >> start).trailing_ones();
-
-            for i in 0..run {
-                let idx = (start + i) as usize;
-                unsafe {
-                    let storage = self.storages[idx].assume_init_ref();
-                    storage.set_tick(self.current_tick);
-                }
-            }
+        self.sync_storage_ticks();
+        self.phase = WorldPhase::Idle;
+        self.journal_tick(tick);
+        self.record_state_hash(tick);
+        self.record_audit(tick, true);
+        crate::watch::refresh_all(&mut self.watches);
+        self.run_tick_hooks(tick, true);
+    }
 
-            let range_mask = if run == 128 {
-                u128::MAX
-            } else {
-                ((1u128 << run) - 1) << start
-            };
-            mask &= !range_mask;
+    /// Simulates one tick and returns it. With a scheduler this is `run`. Without one the
+    /// world still advances: storages move to the next tick and their change masks are
+    /// cleared, so tests driving storages by hand don't have to.
+    ///
+    /// # Example
+    /// ```ignore
+    /// world.set(e, &Health { hp: 10 }); // Written at tick 0
+    /// world.step();
+    /// world.set(e, &Health { hp: 5 }); // Written at tick 1
+    /// world.rollback(Tick::new(0));
+    /// ```
+    pub fn step(&mut self) -> Tick {
+        let tick = self.current_tick;
+        if self.scheduler.is_some() || self.scheduler_stale {
+            self.run();
+            return tick;
+        }
+
+        self.assert_phase("step");
+        self.begin_tick();
+        self.run_tick_hooks(tick, false);
+        self.end_effects();
+        self.clear_mailboxes();
+        self.clear_removals();
+
+        // No cleanup system reports the changes, so the cached hashes can't be trusted
+        self.hash_cache.invalidate();
+        self.clear_storage_changes();
+
+        self.current_tick = Tick::new(tick.value().wrapping_add(1));
+        self.sync_storage_ticks();
+        self.record_state_hash(tick);
+        crate::watch::refresh_all(&mut self.watches);
+        self.run_tick_hooks(tick, true);
+        tick
+    }
+
+    /// Simulates `ticks` ticks with `step`.
+    pub fn run_for(&mut self, ticks: u32) {
+        for _ in 0..ticks {
+            self.step();
+        }
+    }
+
+    /// Fixed-timestep driver: adds `dt` of real time and simulates as many ticks as fit,
+    /// each as long as `tick_duration()` at the time. The remainder carries over to the
+    /// next call. Returns the number of ticks simulated.
+    ///
+    /// # Example
+    /// ```ignore
+    /// let mut last = Instant::now();
+    /// loop {
+    ///     let now = Instant::now();
+    ///     world.advance(now - last);
+    ///     last = now;
+    ///     render(&world, world.step_alpha());
+    /// }
+    /// ```
+    pub fn advance(&mut self, dt: Duration) -> u32 {
+        self.accumulator += dt;
+        let mut ticks = 0;
+        while self.accumulator >= self.tick_duration() {
+            self.accumulator -= self.tick_duration();
+            self.step();
+            ticks += 1;
+        }
+        ticks
+    }
+
+    /// Time `advance` received that doesn't make a full tick yet.
+    pub fn accumulated(&self) -> Duration {
+        self.accumulator
+    }
+
+    /// How far into the next tick the accumulated time is, from 0 to 1, for interpolating
+    /// rendered state between the last two ticks.
+    pub fn step_alpha(&self) -> f32 {
+        self.accumulator.as_secs_f32() / self.tick_duration().as_secs_f32()
+    }
+
+    /// Registers `hook` to be called at the start of every tick `run` simulates, before
+    /// any system, with the tick about to be simulated. Hooks run in registration order,
+    /// also for resimulated ticks.
+    ///
+    /// Hooks are for engine glue such as advancing audio clocks or polling the network and
+    /// must not mutate storages. They run in the `RunningHooks` phase, where debug builds
+    /// reject out-of-band mutation, see the `phase` module.
+    ///
+    /// # Example
+    /// ```ignore
+    /// world.on_tick_start(|_, tick| network.poll(tick));
+    /// world.on_tick_end(|world, tick| audio.advance_to(tick, world.tick_duration()));
+    /// ```
+    pub fn on_tick_start<F>(&mut self, hook: F)
+    where
+        F: FnMut(&World, Tick) + 'static,
+    {
+        self.tick_start_hooks.push(Box::new(hook));
+    }
+
+    /// Registers `hook` to be called after every tick `run` simulates, once the world is
+    /// back at rest, with the tick that was simulated. Same rules as `on_tick_start`.
+    pub fn on_tick_end<F>(&mut self, hook: F)
+    where
+        F: FnMut(&World, Tick) + 'static,
+    {
+        self.tick_end_hooks.push(Box::new(hook));
+    }
+
+    /// Removes every tick hook.
+    pub fn clear_tick_hooks(&mut self) {
+        self.tick_start_hooks.clear();
+        self.tick_end_hooks.clear();
+    }
+
+    fn run_tick_hooks(&mut self, tick: Tick, end: bool) {
+        let hooks = if end {
+            &mut self.tick_end_hooks
+        } else {
+            &mut self.tick_start_hooks
+        };
+        if hooks.is_empty() {
+            return;
+        }
+
+        let mut hooks = std::mem::take(hooks);
+        let phase = std::mem::replace(&mut self.phase, WorldPhase::RunningHooks);
+        for hook in &mut hooks {
+            hook(self, tick);
+        }
+        self.phase = phase;
+
+        if end {
+            self.tick_end_hooks = hooks;
+        } else {
+            self.tick_start_hooks = hooks;
+        }
+    }
+
+    /// Like `run`, but returns the `TickError` of a tick cut short by a panicking system.
+    /// See the `isolation` module.
+    ///
+    /// # Errors
+    /// Fails if a system panicked, or without running anything if the world was already
+    /// tainted by an earlier failure.
+    #[cfg(feature = "panic-isolation")]
+    pub fn try_run(&mut self) -> Result<(), TickError> {
+        self.run();
+        self.tainted.clone().map_or(Ok(()), Err)
+    }
+
+    /// Like `run_sequential`, with the errors of `try_run`.
+    #[cfg(feature = "panic-isolation")]
+    pub fn try_run_sequential(&mut self) -> Result<(), TickError> {
+        self.run_sequential();
+        self.tainted.clone().map_or(Ok(()), Err)
+    }
+
+    /// The failure that tainted the world, if a system panicked since the last rollback.
+    #[cfg(feature = "panic-isolation")]
+    pub fn tainted(&self) -> Option<&TickError> {
+        self.tainted.as_ref()
+    }
+
+    #[cfg(feature = "panic-isolation")]
+    fn record_failure(&mut self, tick: Tick) {
+        let failure = self.scheduler.as_ref().and_then(|s| s.take_failure());
+        if let Some((system, message)) = failure {
+            self.tainted = Some(TickError {
+                system,
+                tick,
+                message,
+            });
         }
     }
 
@@ -261,7 +1047,116 @@ impl World {
         self.current_tick
     }
 
-    /// Returns a reference to the scheduler if it has been built.
+    /// Returns what the world is currently doing, see the `phase` module.
+    pub fn phase(&self) -> WorldPhase {
+        self.phase
+    }
+
+    /// Runs `f` as an out-of-band edit between ticks. Every storage and tick-dependent
+    /// table is stamped with the current tick first, so the changes are recorded at the
+    /// tick about to be simulated and roll back like changes made by its systems.
+    ///
+    /// # Panics
+    /// Panics in debug builds if the world is not idle.
+    pub fn edit_scope<R>(&mut self, f: impl FnOnce(&mut World) -> R) -> R {
+        self.assert_phase("edit_scope");
+        self.begin_tick();
+        self.sync_storage_ticks();
+
+        f(self)
+    }
+
+    /// Asserts, in debug builds, that out-of-band mutation is allowed right now.
+    #[inline]
+    fn assert_phase(&self, operation: &str) {
+        debug_assert!(
+            self.phase.allows_mutation(),
+            "World::{} called while the world is {:?}",
+            operation,
+            self.phase
+        );
+    }
+
+    /// Sets every storage's tick to the world tick.
+    fn sync_storage_ticks(&self) {
+        for id in self.mask.iter() {
+            let storage = unsafe { self.storages[id].assume_init_ref() };
+            storage.set_tick(self.current_tick);
+        }
+    }
+
+    /// Clears every storage's change masks.
+    fn clear_storage_changes(&self) {
+        for id in self.mask.iter() {
+            let storage = unsafe { self.storages[id].assume_init_ref() };
+            storage.clear_changes();
+        }
+    }
+
+    /// Describes which systems read and write which component types, from the read/write
+    /// sets of the built schedule and any systems still pending. Names have their module
+    /// paths stripped.
+    pub fn component_graph(&self) -> GraphDescription {
+        let names = self.type_names();
+        let type_name = |id: &TypeId| match names.get(id) {
+            Some(name) => short_type_name(name),
+            None => format!("{:?}", id),
+        };
+
+        let mut graph = GraphDescription::default();
+        let built = self.scheduler.iter().flat_map(|s| s.systems());
+        let pending = self.pending_systems.iter().map(|s| s.as_ref());
+
+        for system in built.chain(pending) {
+            let system_name = short_type_name(system.name());
+
+            for id in system.reads() {
+                graph.add_access(&type_name(id), &system_name, false);
+            }
+            for id in system.writes() {
+                graph.add_access(&type_name(id), &system_name, true);
+            }
+
+            graph.systems.push(system_name);
+        }
+
+        graph.sort();
+        graph
+    }
+
+    /// Describes the wavefronts of the built scheduler and why stages are ordered, see
+    /// `graph::ScheduleDescription`. Returns `None` until `build_scheduler()` has run.
+    pub fn describe_schedule(&self) -> Option<ScheduleDescription> {
+        let names = self.type_names();
+        let scheduler = self.scheduler.as_ref()?;
+        Some(scheduler.describe_with(&|id| match names.get(&id) {
+            Some(name) => short_type_name(name),
+            None => format!("{:?}", id),
+        }))
+    }
+
+    /// Names of the component, mailbox and resource types the world knows, by `TypeId`.
+    fn type_names(&self) -> HashMap<TypeId, &'static str> {
+        let mut names: HashMap<TypeId, &'static str> = HashMap::new();
+
+        for id in self.mask.iter() {
+            let storage = unsafe { self.storages[id].assume_init_ref() };
+            names.insert(storage.component_type_id(), storage.type_name());
+        }
+
+        for (id, queue) in self.mailboxes.iter() {
+            names.insert(*id, queue.name());
+        }
+
+        for (id, cell) in self.resources.iter() {
+            names.insert(*id, cell.type_name());
+        }
+
+        names
+    }
+
+    /// Returns a reference to the scheduler if it has been built and no system was added or
+    /// removed since.
     pub fn scheduler(&self) -> Option<&Scheduler> {
         self.scheduler.as_ref()
     }
@@ -278,11 +1173,8 @@ impl World {
     /// world.run();
     /// ```
     pub fn schedule_cleanup<T: Component>(&mut self) {
-        let id = T::type_index();
-        let bit = 1u128 << id;
-
         // If storage already exists, cleanup was auto-scheduled when storage was first accessed
-        if (self.mask & bit) != 0 {
+        if self.mask.contains(T::type_index()) {
             return;
         }
 
@@ -291,12 +1183,70 @@ impl World {
         self.add_system_instance(cleanup_system);
     }
 
+    /// Creates the storage of every `#[derive(Component)]` type linked into the program,
+    /// in `component_id` order, so snapshots and deltas cover the same set of components
+    /// on every peer even when some types were never touched locally. Call it before
+    /// `build_scheduler()` so their cleanup systems are scheduled.
+    ///
+    /// # Panics
+    /// Panics if the program has more component types than the world has storage slots.
+    pub fn ensure_all_registered(&mut self) {
+        for registration in crate::component::registered_components() {
+            (registration.register)(self);
+        }
+    }
+
     pub fn set<T: Component>(&mut self, entity: Entity, component: &T)
     where
         T: Clone,
     {
-        let ents = self.get_storage::<Entity>();
-        let current = unsafe { (*ents.get()).get(entity.index()) };
+        self.assert_phase("set");
+        self.assert_settable(entity);
+        self.write_component(entity, component);
+    }
+
+    /// Spawns an entity with every component of `bundle`, see the `bundle` module.
+    pub fn spawn_bundle<B: Bundle>(&mut self, bundle: B) -> Entity {
+        let entity = self.spawn();
+        self.insert_bundle(entity, bundle);
+        entity
+    }
+
+    /// Sets every component of `bundle` on `entity`, checking the entity once and touching
+    /// each storage once.
+    ///
+    /// # Panics
+    /// Same as `set`.
+    pub fn insert_bundle<B: Bundle>(&mut self, entity: Entity, bundle: B) {
+        self.assert_phase("insert_bundle");
+        self.assert_settable(entity);
+        bundle.write(&mut BundleWriter::new(self, entity));
+    }
+
+    /// Sets `component` on `entity`, which `assert_settable` accepted.
+    pub(crate) fn write_component<T: Component>(&mut self, entity: Entity, component: &T) {
+        #[cfg(debug_assertions)]
+        if let Some(local) = self.local_peer {
+            let owner = self.owner_of::<T>(entity);
+            assert!(
+                owner == local,
+                "World::set::<{}> on entity {} owned by {:?}, but the local peer is {:?}",
+                std::any::type_name::<T>(),
+                entity.index(),
+                owner,
+                local
+            );
+        }
+        let storage = self.get_storage::<T>();
+        unsafe {
+            (*storage.get()).set(entity.index(), component);
+        }
+    }
+
+    /// Panics unless `entity` is alive.
+    fn assert_settable(&mut self, entity: Entity) {
+        let ents = self.get_storage::<Entity>();
+        let current = unsafe { (*ents.get()).get(entity.index()) };
 
         if let Some(current_entity) = current {
             if current_entity.generation() != entity.generation() {
@@ -307,11 +1257,6 @@ impl World {
                     entity.generation()
                 );
             }
-
-            let storage = self.get_storage::<T>();
-            unsafe {
-                (*storage.get()).set(entity.index(), component);
-            }
         } else {
             panic!(
                 "Attempted to set component on entity {} which does not exist",
@@ -320,7 +1265,77 @@ impl World {
         }
     }
 
+    /// Sets a type-erased component on `entity` through `set`, so it is change-tracked and
+    /// rolled back like a native one. See the `dynamic` module.
+    ///
+    /// # Panics
+    /// Same as `set`.
+    pub fn set_dyn(&mut self, entity: Entity, value: &DynValue) {
+        value.set_on(self, entity);
+    }
+
+    /// Sets `value` on the entity mapped to the network id `net_id`, or keeps it until the
+    /// id is mapped if there is no such live entity yet. See the `pending` module.
+    pub fn stage_pending(&mut self, net_id: u64, value: DynValue) {
+        self.assert_phase("stage_pending");
+        match self.net_entity(net_id) {
+            Some(entity) => self.set_dyn(entity, &value),
+            None => self.pending.stage(self.current_tick, net_id, value),
+        }
+    }
+
+    /// Maps the network id `net_id` to the live `entity` and sets the writes staged for
+    /// it, in staging order.
+    ///
+    /// # Panics
+    /// Same as `set`, if `entity` is not alive.
+    pub fn map_net_id(&mut self, net_id: u64, entity: Entity) {
+        self.assert_phase("map_net_id");
+        for value in self.pending.map(self.current_tick, net_id, entity) {
+            self.set_dyn(entity, &value);
+        }
+    }
+
+    /// Spawns an entity for the network id `net_id`, see `map_net_id`.
+    pub fn spawn_networked(&mut self, net_id: u64) -> Entity {
+        let entity = self.spawn();
+        self.map_net_id(net_id, entity);
+        entity
+    }
+
+    /// The live entity mapped to the network id `net_id`, if any.
+    pub fn net_entity(&self, net_id: u64) -> Option<Entity> {
+        let entity = self.pending.entity(net_id)?;
+        let entities = self.storage_ref::<Entity>()?;
+        (entities.get(entity.index()) == Some(&entity)).then_some(entity)
+    }
+
+    /// Sets how many ticks staged writes wait for their entity before they are dropped.
+    pub fn set_pending_expiry(&mut self, ticks: u32) {
+        self.pending.set_expiry(ticks);
+    }
+
+    /// Number of staged writes still waiting for their entity.
+    pub fn pending_count(&self) -> usize {
+        self.pending.pending()
+    }
+
+    /// The component with `wire::component_id` `component` on `entity`, if the entity is
+    /// alive and has it.
+    pub fn get_dyn(&self, entity: Entity, component: u64) -> Option<DynValueRef<'_>> {
+        let current = self.storage_ref::<Entity>()?.get(entity.index())?;
+        if *current != entity {
+            return None;
+        }
+
+        let (id, vtable) = self.dyn_components.get(&component)?;
+        let storage = unsafe { self.storages[*id].assume_init_ref() };
+        let value = storage.get_any(entity.index())?;
+        Some(DynValueRef::new(*vtable, value))
+    }
+
     pub fn destroy(&mut self, entity: Entity) {
+        self.assert_phase("destroy");
         let ents = self.get_storage::<Entity>();
         let current = unsafe { (*ents.get()).get(entity.index()) };
 
@@ -334,8 +1349,8 @@ impl World {
                 );
             }
             let destroyed = self.get_storage::<Destroyed>();
-            unsafe {
-                (*destroyed.get()).set(entity.index(), &Destroyed {});
+            for entity in std::iter::once(entity).chain(self.descendants_of(entity)) {
+                unsafe { (*destroyed.get()).set(entity.index(), &Destroyed {}) };
             }
         } else {
             panic!(
@@ -345,63 +1360,1851 @@ impl World {
         }
     }
 
-    pub fn spawn(&mut self) -> Entity {
-        unsafe { (*self.get_storage::<Entity>().get()).spawn() }
+    /// Destroys every live entity that has all components in `Q` and passes `filter`, e.g.
+    /// all projectiles or one team's units. Candidates come from intersecting the storages'
+    /// presence masks block by block, in index order, and are marked `Destroyed` exactly as
+    /// `destroy` would mark them one by one, so the cleanup sweep removes them at the end of
+    /// the next tick and rollback treats them like any other destroy. Entities already
+    /// marked are skipped.
+    ///
+    /// Returns the number of entities destroyed.
+    ///
+    /// # Example
+    /// ```ignore
+    /// world.destroy_matching::<(Projectile,)>(|_, _| true);
+    /// world.destroy_matching::<(Unit, Team)>(|world, e| {
+    ///     world.storage_ref::<Team>().unwrap().get(e.index()) == Some(&Team::Red)
+    /// });
+    /// ```
+    pub fn destroy_matching<Q: ComponentSet>(
+        &mut self,
+        mut filter: impl FnMut(&World, Entity) -> bool,
+    ) -> usize {
+        self.assert_phase("destroy_matching");
+        let mut storages = Q::storages(self);
+        storages.push(Box::new(self.get_storage::<Entity>()));
+        let destroyed = self.get_storage::<Destroyed>();
+
+        let mut targets = Vec::new();
+        let entities = self.storage_ref::<Entity>().expect("entity storage exists");
+        for_each_match(&storages, |index| {
+            if unsafe { (*destroyed.get()).get(index) }.is_none() {
+                targets.push(*entities.get(index).expect("matched index is alive"));
+            }
+        });
+        targets.retain(|entity| filter(self, *entity));
+
+        for &entity in &targets {
+            for entity in std::iter::once(entity).chain(self.descendants_of(entity)) {
+                unsafe { (*destroyed.get()).set(entity.index(), &Destroyed {}) };
+            }
+        }
+        targets.len()
     }
 
-    pub fn rollback(&mut self, target_tick: Tick) {
-        let mut mask = self.mask;
+    /// Makes `parent` the parent of `child`, detaching it from its previous parent. The
+    /// child is appended to the parent's `Children`. See the `hierarchy` module.
+    ///
+    /// # Panics
+    /// Panics if either entity is not alive, or if `parent` is `child` or one of its
+    /// descendants.
+    pub fn set_parent(&mut self, child: Entity, parent: Entity) {
+        self.assert_phase("set_parent");
+        self.assert_settable(child);
+        self.assert_settable(parent);
+        assert!(
+            child != parent && !self.ancestors_of(parent).any(|e| e == child),
+            "World::set_parent would make entity {} its own ancestor",
+            child.index()
+        );
+
+        self.remove_parent(child);
+        let mut children = self.get::<Children>(parent).cloned().unwrap_or_default();
+        children.push(child);
+        self.write_component(parent, &children);
+        self.write_component(child, &Parent(parent));
+    }
 
-        while mask != 0 {
-            let start = mask.trailing_zeros();
-            let run = (mask >> start).trailing_ones();
+    /// Detaches `child` from its parent, making it a root. Does nothing if it has none.
+    pub fn remove_parent(&mut self, child: Entity) {
+        self.assert_phase("remove_parent");
+        let Some(Parent(parent)) = self.remove::<Parent>(child) else {
+            return;
+        };
+        if let Some(children) = self.get_mut::<Children>(parent) {
+            children.remove(child);
+        }
+    }
 
-            for i in 0..run {
-                let idx = (start + i) as usize;
-                unsafe {
-                    let storage = self.storages[idx].assume_init_ref();
-                    storage.rollback(target_tick);
-                }
+    /// The parent of `entity`, if it is alive and has one.
+    pub fn parent_of(&self, entity: Entity) -> Option<Entity> {
+        self.get::<Parent>(entity).map(|parent| parent.0)
+    }
+
+    /// The children of `entity` in the order they were attached.
+    pub fn children_of(&self, entity: Entity) -> &[Entity] {
+        self.get::<Children>(entity).map_or(&[], Children::as_slice)
+    }
+
+    /// The parent of `entity`, its parent and so on up to the root.
+    pub fn ancestors_of(&self, entity: Entity) -> impl Iterator<Item = Entity> + '_ {
+        std::iter::successors(self.parent_of(entity), |&e| self.parent_of(e))
+    }
+
+    /// Every descendant of `entity`, depth first with each entity before its children.
+    pub fn descendants_of(&self, entity: Entity) -> Vec<Entity> {
+        let mut descendants = Vec::new();
+        let mut stack: Vec<Entity> = self.children_of(entity).iter().rev().copied().collect();
+        while let Some(next) = stack.pop() {
+            descendants.push(next);
+            stack.extend(self.children_of(next).iter().rev());
+        }
+        descendants
+    }
+
+    /// Every live entity whose relation component `T` links to `target`, in ascending index
+    /// order. See the `relation` module.
+    pub fn relations_to<T>(&self, target: Entity) -> Vec<Entity>
+    where
+        T: Component<Storage = Relation<T>>,
+    {
+        let (Some(relations), Some(entities)) =
+            (self.storage_ref::<T>(), self.storage_ref::<Entity>())
+        else {
+            return Vec::new();
+        };
+        relations
+            .sources_of(target)
+            .into_iter()
+            .filter_map(|index| entities.get(index).copied())
+            .collect()
+    }
+
+    /// Iterates every entity that has the components in `D`, e.g. `(&A, &mut B)`, without
+    /// defining a system. Narrow it with `.with::<C>()`, `.without::<D>()` and
+    /// `.changed::<E>()`, see the `query` module.
+    ///
+    /// # Panics
+    /// Panics if `D` asks for a component mutably and more than once.
+    pub fn query<D: QueryData>(&mut self) -> Query<'_, D> {
+        self.assert_phase("query");
+        Query::new(self)
+    }
+
+    /// Returns how many entities have every component in `Q`, without visiting them: the
+    /// storages' presence masks are intersected block by block and the bits counted. Use
+    /// the generated `Stage::count()` for the exact filters of a `system!` query.
+    ///
+    /// # Example
+    /// ```ignore
+    /// if world.count_matching::<(Projectile, Velocity)>() > 10_000 {
+    ///     // ...
+    /// }
+    /// ```
+    pub fn count_matching<Q: ComponentSet>(&mut self) -> usize {
+        count_matches(&Q::storages(self))
+    }
+
+    /// Returns the shared queue for messages of type `M` addressed to the system `Target`,
+    /// creating it on first access.
+    fn mailbox_queue<M: 'static, Target: 'static>(&mut self) -> Rc<MailboxQueue<M>> {
+        let key = TypeId::of::<Mailbox<M, Target>>();
+
+        let queue = self
+            .mailboxes
+            .get_or_insert_with(key, || {
+                let name = std::any::type_name::<Mailbox<M, Target>>();
+                Rc::new(MailboxQueue::<M>::with_name(name)) as Rc<dyn MailboxLike>
+            })
+            .clone();
+
+        queue
+            .as_any_rc()
+            .downcast::<MailboxQueue<M>>()
+            .expect("Mailbox queue registered with a different message type")
+    }
+
+    /// Returns a sending half of the mailbox for messages `M` addressed to `Target`,
+    /// for use outside of systems. Its messages are delivered before those of any system.
+    pub fn mailbox<M: 'static, Target: 'static>(&mut self) -> Mailbox<M, Target> {
+        Mailbox::new(self.mailbox_queue::<M, Target>(), EXTERNAL_STAGE)
+    }
+
+    /// Returns a sending half of the mailbox for messages `M` addressed to `Target`, owned by
+    /// the system `Sender`. Messages are ordered by the sender's stable `SequenceKey`.
+    pub fn mailbox_from<M: 'static, Target: 'static, Sender: 'static>(
+        &mut self,
+    ) -> Mailbox<M, Target> {
+        Mailbox::new(self.mailbox_queue::<M, Target>(), stage_key::<Sender>())
+    }
+
+    /// Returns the receiving half of the mailbox for messages `M` addressed to `Target`.
+    pub fn inbox<M: 'static, Target: 'static>(&mut self) -> Inbox<M> {
+        Inbox::new(self.mailbox_queue::<M, Target>())
+    }
+
+    /// Drops all queued mailbox messages.
+    pub fn clear_mailboxes(&mut self) {
+        for queue in self.mailboxes.values() {
+            queue.clear();
+        }
+    }
+
+    /// Returns the queue of `Removed<T>` events filled by `Remove = [T]` queries, creating it
+    /// on first access. See the `removal` module.
+    pub fn removal_queue<T: Component>(&mut self) -> Rc<RemovalQueue<T>> {
+        self.removals
+            .get_or_insert_with(TypeId::of::<T>(), || {
+                Rc::new(RemovalQueue::<T>::new()) as Rc<dyn RemovalLike>
+            })
+            .clone()
+            .as_any_rc()
+            .downcast::<RemovalQueue<T>>()
+            .expect("Removal queue registered with a different component type")
+    }
+
+    fn clear_removals(&mut self) {
+        for queue in self.removals.values() {
+            queue.clear();
+        }
+    }
+
+    /// Returns the effect queue for effects of type `E`, creating it on first access. See
+    /// the `effects` module.
+    pub fn effects<E: 'static>(&mut self) -> Rc<EffectQueue<E>> {
+        self.effects
+            .get_or_insert_with(TypeId::of::<E>(), || {
+                Rc::new(EffectQueue::<E>::new()) as Rc<dyn EffectsLike>
+            })
+            .clone()
+            .as_any_rc()
+            .downcast::<EffectQueue<E>>()
+            .expect("Effect queue registered with a different effect type")
+    }
+
+    /// Returns an emitter for effects of type `E` owned by the system `S`.
+    pub fn effects_from<E: 'static, S: 'static>(&mut self) -> Effects<E> {
+        Effects::new(&self.effects::<E>(), stage_key::<S>())
+    }
+
+    /// Surfaces the effects emitted by the tick that just ran.
+    fn end_effects(&self) {
+        // Effects of a tick cut short by a panic are never surfaced
+        #[cfg(feature = "panic-isolation")]
+        if self.tainted.is_some() {
+            for queue in self.effects.values() {
+                queue.clear_pending();
             }
+            return;
+        }
 
-            let range_mask = if run == 128 {
-                u128::MAX
-            } else {
-                ((1u128 << run) - 1) << start
-            };
-            mask &= !range_mask;
+        let oldest = self.rollback_window().oldest;
+        for queue in self.effects.values() {
+            queue.end_tick(self.current_tick, oldest);
         }
+    }
 
-        // Update world's current tick to match the target tick after rollback
-        self.current_tick = target_tick;
+    /// Returns the shared ingest queue for component `T`, creating it on first access.
+    pub fn ingest_queue<T: Component>(&mut self) -> Rc<IngestQueue<T>> {
+        self.ingests
+            .get_or_insert_with(TypeId::of::<T>(), || {
+                Rc::new(IngestQueue::<T>::new()) as Rc<dyn IngestLike>
+            })
+            .clone()
+            .as_any_rc()
+            .downcast::<IngestQueue<T>>()
+            .expect("Ingest queue registered with a different component type")
     }
 
-}
+    /// Returns a thread-safe sender that streams `T` values into the world.
+    /// The first call schedules an `IngestSystem<T>`, so call it before `build_scheduler()`.
+    pub fn ingest<T: Component>(&mut self) -> IngestSender<T> {
+        if !self.ingests.contains_key(&TypeId::of::<T>()) {
+            self.add_system::<IngestSystem<T>>();
+        }
 
-impl Drop for World {
-    fn drop(&mut self) {
-        let mut mask = self.mask;
+        self.ingest_queue::<T>().sender()
+    }
 
-        unsafe {
-            let ptr = self.storages.as_mut_ptr();
+    /// The slot of resource `T`, created empty if needed. Stages hold it for `Res<T>` and
+    /// `ResMut<T>` parameters.
+    pub fn resource_cell<T: Clone + 'static>(&mut self) -> Rc<ResourceCell<T>> {
+        if self.resources.get(&TypeId::of::<T>()).is_none() {
+            self.assert_registration_open("resource", std::any::type_name::<T>());
+        }
+        self.resources
+            .get_or_insert_with(TypeId::of::<T>(), || {
+                Rc::new(ResourceCell::<T>::new()) as Rc<dyn ResourceLike>
+            })
+            .clone()
+            .as_any_rc()
+            .downcast::<ResourceCell<T>>()
+            .expect("Resource cell registered with a different type")
+    }
 
-            while mask != 0 {
-                let start = mask.trailing_zeros();
-                let run = (mask >> start).trailing_ones();
+    /// Sets the resource `T`, replacing any previous value. Rolled back like a write made
+    /// in the current tick, see the `resource` module.
+    pub fn insert_resource<T: Clone + 'static>(&mut self, value: T) {
+        self.assert_phase("insert_resource");
+        *self.resource_cell::<T>().slot_mut(self.current_tick) = Some(value);
+    }
 
-                for i in 0..run {
-                    let idx = (start + i) as usize;
-                    ptr.add(idx).read().assume_init_drop();
-                }
+    /// Removes the resource `T` and returns it.
+    pub fn remove_resource<T: Clone + 'static>(&mut self) -> Option<T> {
+        self.assert_phase("remove_resource");
+        let cell = self.resource_cell::<T>();
+        cell.get()?;
+        cell.slot_mut(self.current_tick).take()
+    }
+
+    /// The resource `T`, if it was inserted.
+    pub fn resource<T: Clone + 'static>(&self) -> Option<&T> {
+        let cell = self.resources.get(&TypeId::of::<T>())?.as_ref() as &dyn Any;
+        cell.downcast_ref::<ResourceCell<T>>()?.get()
+    }
+
+    /// Mutable access to the resource `T`, logged for rollback in the current tick.
+    pub fn resource_mut<T: Clone + 'static>(&mut self) -> Option<&mut T> {
+        self.assert_phase("resource_mut");
+        let cell = self.resources.get(&TypeId::of::<T>())?.as_ref() as &dyn Any;
+        let cell = cell.downcast_ref::<ResourceCell<T>>()?;
+        cell.get()?;
+        // `&mut self` rules out any other reference into the cell
+        cell.slot_mut(self.current_tick).as_mut()
+    }
+
+    /// Sets the `State<S>` resource to `initial`, entered in the current tick, see the
+    /// `state` module. Replaces any previous state of that type.
+    pub fn insert_state<S: Clone + PartialEq + 'static>(&mut self, initial: S) {
+        self.insert_resource(State::new(initial, self.current_tick));
+        let cell = self.resource_cell::<State<S>>();
+        self.states.get_or_insert_with(TypeId::of::<S>(), || {
+            Rc::new(StateDriver::new(cell)) as Rc<dyn StateLike>
+        });
+    }
+
+    /// The active state of type `S`, if one was inserted.
+    pub fn state<S: Clone + PartialEq + 'static>(&self) -> Option<&S> {
+        self.resource::<State<S>>().map(State::get)
+    }
+
+    /// Requests a transition to `next` at the start of the next tick, see `State::set`.
+    ///
+    /// # Panics
+    /// Panics if no state of type `S` was inserted.
+    pub fn set_state<S: Clone + PartialEq + 'static>(&mut self, next: S) {
+        self.resource_mut::<State<S>>()
+            .unwrap_or_else(|| {
+                panic!(
+                    "State {} was not inserted, call World::insert_state first",
+                    std::any::type_name::<S>()
+                )
+            })
+            .set(next);
+    }
+
+    /// The inputs of type `I`, created with no players if needed. Stages hold it for
+    /// `PlayerInput<I>` parameters, see the `input` module.
+    pub fn input_buffer<I: Clone + PartialEq + Default + 'static>(
+        &mut self,
+    ) -> Rc<InputBuffer<I>> {
+        self.inputs
+            .get_or_insert_with(TypeId::of::<I>(), || {
+                Rc::new(InputBuffer::<I>::new()) as Rc<dyn InputLike>
+            })
+            .clone()
+            .as_any_rc()
+            .downcast::<InputBuffer<I>>()
+            .expect("Input buffer registered with a different type")
+    }
+
+    /// Confirms the input of `player` for `tick`, see `InputBuffer::confirm`.
+    pub fn confirm_input<I: Clone + PartialEq + Default + 'static>(
+        &mut self,
+        player: usize,
+        tick: Tick,
+        input: I,
+    ) -> Result<(), InputError> {
+        self.input_buffer::<I>().confirm(player, tick, input)
+    }
+
+    /// Resimulates from the earliest tick an input buffer mispredicted up to the current
+    /// tick, with the corrected inputs. Returns the number of ticks simulated again, 0 if
+    /// every prediction held.
+    pub fn sync_inputs(&mut self) -> u32 {
+        let Some(tick) = self
+            .inputs
+            .values()
+            .filter_map(|buffer| buffer.take_mispredicted())
+            .min()
+        else {
+            return 0;
+        };
+
+        let end = self.current_tick;
+        self.resimulate_from(tick);
+        let mut ticks = 0;
+        while self.current_tick.is_before(end) {
+            self.run();
+            ticks += 1;
+        }
+        ticks
+    }
+
+    /// Corrects a predicting client with the server state: rewinds to the start of `tick`,
+    /// loads `snapshot` (a `save_snapshot` of the server's world at `tick`) in its place and
+    /// resimulates up to the current tick with the client's inputs, confirmed or predicted
+    /// as the input buffers have them. Returns the number of ticks simulated again.
+    ///
+    /// Pending mispredictions are dropped, since the resimulation covers every tick they
+    /// could correct. The tick is checked and the snapshot decoded before anything
+    /// changes, so the world is left untouched on error, including `TickAhead` for a
+    /// `tick` after the current one.
+    ///
+    /// # Panics
+    /// Panics if the start of `tick` left the rollback window and no overflow handler is
+    /// installed (see `resimulate_from`).
+    ///
+    /// # Example
+    /// ```ignore
+    /// let (tick, snapshot) = socket.receive_state();
+    /// world.reconcile(tick, &snapshot)?;
+    /// ```
+    pub fn reconcile(&mut self, tick: Tick, snapshot: &[u8]) -> Result<u32, DecodeError> {
+        self.assert_phase("reconcile");
+        let packet = Packet::decode(snapshot)?;
+        if packet.tick != tick {
+            return Err(DecodeError::BaseMismatch {
+                expected: tick,
+                found: packet.tick,
+            });
+        }
+        let end = self.current_tick;
+        if tick.is_after(end) {
+            return Err(DecodeError::TickAhead {
+                tick,
+                current: end,
+            });
+        }
+        let decoded = self.decode_world_snapshot(&packet)?;
+
+        for buffer in self.inputs.values() {
+            buffer.take_mispredicted();
+        }
+        if tick != end {
+            self.resimulate_from(tick);
+        }
+
+        self.load_decoded_snapshot(decoded);
+        let mut ticks = 0;
+        while self.current_tick.is_before(end) {
+            self.run();
+            ticks += 1;
+        }
+        Ok(ticks)
+    }
+
+    /// Returns the expiry table for component `T`, if ttls are enabled for it.
+    pub fn expiry_table<T: Component>(&self) -> Option<Rc<ExpiryTable<T>>> {
+        let table = self.expiries.get(&TypeId::of::<T>())?.clone();
+        Some(
+            table
+                .as_any_rc()
+                .downcast::<ExpiryTable<T>>()
+                .expect("Expiry table registered with a different component type"),
+        )
+    }
+
+    pub(crate) fn expiry_table_or_insert<T: Component>(&mut self) -> Rc<ExpiryTable<T>> {
+        self.expiries
+            .get_or_insert_with(TypeId::of::<T>(), || {
+                Rc::new(ExpiryTable::<T>::new()) as Rc<dyn ExpiryLike>
+            })
+            .clone()
+            .as_any_rc()
+            .downcast::<ExpiryTable<T>>()
+            .expect("Expiry table registered with a different component type")
+    }
+
+    /// Schedules the `ExpirySystem<T>` that removes `T` once its ttl runs out.
+    /// Call it before `build_scheduler()`; calling it again has no effect.
+    pub fn enable_ttl<T: Component>(&mut self) {
+        if !self.expiries.contains_key(&TypeId::of::<T>()) {
+            self.add_system::<ExpirySystem<T>>();
+        }
+    }
+
+    /// Sets `component` on `entity` and removes it again after `ttl_ticks` ticks: systems
+    /// see it during the current tick and the `ttl_ticks - 1` after it. See the `expiry`
+    /// module.
+    ///
+    /// # Panics
+    /// Panics if `ttl_ticks` is zero, if the entity doesn't exist, or if the scheduler is
+    /// already built and `enable_ttl::<T>()` wasn't called before building it.
+    pub fn set_with_ttl<T: Component>(&mut self, entity: Entity, component: &T, ttl_ticks: u32) {
+        self.assert_phase("set_with_ttl");
+        assert!(ttl_ticks > 0, "set_with_ttl requires a ttl of at least one tick");
+
+        if !self.expiries.contains_key(&TypeId::of::<T>()) {
+            if self.scheduler.is_some() {
+                panic!(
+                    "set_with_ttl::<{}> called after build_scheduler() without enable_ttl",
+                    std::any::type_name::<T>()
+                );
+            }
+            self.enable_ttl::<T>();
+        }
+
+        self.set(entity, component);
+
+        let expiry = Expiry {
+            generation: entity.generation(),
+            last_tick: Tick::new(self.current_tick.value().wrapping_add(ttl_ticks - 1)),
+        };
+        self.expiry_table_or_insert::<T>()
+            .put(self.current_tick, entity.index(), Some(expiry));
+    }
+
+    /// Cancels the pending expiry of `T` on `entity`, keeping the component.
+    pub fn clear_ttl<T: Component>(&mut self, entity: Entity) {
+        self.assert_phase("clear_ttl");
+        if let Some(table) = self.expiry_table::<T>()
+            && table
+                .get(entity.index())
+                .is_some_and(|e| e.generation == entity.generation())
+        {
+            table.put(self.current_tick, entity.index(), None);
+        }
+    }
+
+    /// Returns the last tick `T` is visible on `entity`, if it was set with a ttl.
+    pub fn expires_at<T: Component>(&self, entity: Entity) -> Option<Tick> {
+        self.expiry_table::<T>()?
+            .get(entity.index())
+            .filter(|e| e.generation == entity.generation())
+            .map(|e| e.last_tick)
+    }
+
+    /// Returns this world's channel for cross-zone messages of type `M`, see the `zone`
+    /// module.
+    pub fn zone_channel<M: 'static>(&mut self) -> Rc<ZoneChannel<M>> {
+        self.zone_channels
+            .get_or_insert_with(TypeId::of::<M>(), || {
+                Rc::new(ZoneChannel::<M>::new()) as Rc<dyn Any>
+            })
+            .clone()
+            .downcast::<ZoneChannel<M>>()
+            .expect("Zone channel registered with a different message type")
+    }
+
+    /// Returns the channel for `M` if one was created.
+    pub(crate) fn zone_channel_ref<M: 'static>(&self) -> Option<&ZoneChannel<M>> {
+        self.zone_channels.get(&TypeId::of::<M>())?.downcast_ref()
+    }
+
+    /// Encodes `T` with `codec` instead of its `Wire` implementation in snapshots written
+    /// by `write_snapshot` and applied by `apply_snapshot`. Both peers must register the
+    /// same codec. The rollback history is unaffected and keeps cloning changed values.
+    pub fn set_snapshot_codec<T: Component>(&mut self, codec: impl SnapshotCodec<T> + 'static) {
+        let codec: Box<dyn SnapshotCodec<T>> = Box::new(codec);
+        self.snapshot_codecs
+            .insert(TypeId::of::<T>(), Rc::new(codec) as Rc<dyn Any>);
+    }
+
+    /// Returns the codec registered for `T` with `set_snapshot_codec`.
+    pub fn snapshot_codec<T: Component>(&self) -> Option<&dyn SnapshotCodec<T>> {
+        self.snapshot_codecs
+            .get(&TypeId::of::<T>())?
+            .downcast_ref::<Box<dyn SnapshotCodec<T>>>()
+            .map(|codec| codec.as_ref())
+    }
+
+    /// Writes the current components of `T` as a snapshot section, using the codec
+    /// registered for `T` if there is one.
+    pub fn write_snapshot<T>(&self, writer: &mut PacketWriter)
+    where
+        T: Component<Storage = Storage<T>> + Wire,
+    {
+        let empty = Storage::new();
+        let storage = self.storage_ref::<T>().unwrap_or(&empty);
+        writer.write_snapshot_with(storage, self.snapshot_codec::<T>().unwrap_or(&WireCodec));
+    }
+
+    /// Replaces the components of `T` with the packet's snapshot, decoded with the codec
+    /// registered for `T` if there is one. The world is left untouched on error.
+    pub fn apply_snapshot<T>(&mut self, packet: &Packet<'_>) -> Result<(), DecodeError>
+    where
+        T: Component<Storage = Storage<T>> + Wire,
+    {
+        self.assert_phase("apply_snapshot");
+        let storage = self.get_storage::<T>();
+        let codec = self.snapshot_codec::<T>().unwrap_or(&WireCodec);
+        apply_snapshot_with(unsafe { &mut *storage.get() }, packet, codec)
+    }
+
+    /// Serializes the whole world into one snapshot packet of the current tick: a section
+    /// per storage whose component is a `SerializableComponent` or has a registered codec.
+    /// Other components and dirty bridge markers are left out. See the `wire` module.
+    pub fn save_snapshot(&self) -> Vec<u8> {
+        let mut writer = PacketWriter::snapshot(self.current_tick);
+        for id in self.mask.without(self.presentation_mask).iter() {
+            let storage = unsafe { self.storages[id].assume_init_ref() };
+            storage.write_snapshot(self, &mut writer);
+        }
+        writer.finish()
+    }
+
+    /// Replaces the world's serialized components with a `save_snapshot` packet and returns
+    /// the tick it was saved at. Storages the packet names but this world hasn't used yet
+    /// are created, so a fresh world can load it; components that aren't serialized keep
+    /// their values.
+    ///
+    /// Like `load_slot`, loading is an edit at the current tick that can be rolled back,
+    /// and entities the packet doesn't have are retired. Every section is decoded before
+    /// anything changes, so the world is left untouched on error.
+    pub fn load_snapshot(&mut self, bytes: &[u8]) -> Result<Tick, DecodeError> {
+        self.assert_phase("load_snapshot");
+        let packet = Packet::decode(bytes)?;
+        let decoded = self.decode_world_snapshot(&packet)?;
+        self.load_decoded_snapshot(decoded);
+        Ok(packet.tick)
+    }
+
+    /// Decodes a `save_snapshot` packet into the states `load_decoded_snapshot` loads.
+    /// Sections for storages the world doesn't have yet are decoded through the component
+    /// registry, so nothing is created until the whole packet is known to be valid; a
+    /// strict world rejects them like unknown components.
+    fn decode_world_snapshot(&self, packet: &Packet<'_>) -> Result<DecodedSnapshot, DecodeError> {
+        if packet.kind != PacketKind::Snapshot {
+            return Err(DecodeError::KindMismatch {
+                expected: PacketKind::Snapshot,
+                found: packet.kind,
+            });
+        }
+
+        let mut known = Vec::new();
+        let mut decoded = DecodedSnapshot {
+            existing: Vec::new(),
+            missing: Vec::new(),
+        };
+        for id in self.mask.without(self.presentation_mask).iter() {
+            let storage = unsafe { self.storages[id].assume_init_ref() };
+            if let Some(state) = storage.decode_snapshot(self, packet) {
+                known.push(stage_hash(storage.type_name()));
+                decoded.existing.push((id, state?));
+            }
+        }
+
+        // Entities aren't derived components, but a fresh world has no storage for them
+        static ENTITIES: ComponentRegistration = ComponentRegistration::of::<Entity>();
+        let mut registrations = crate::component::registered_components();
+        registrations.push(&ENTITIES);
+        for section in packet.sections() {
+            if known.contains(&section.component) {
+                continue;
+            }
+            let registration = registrations
+                .iter()
+                .find(|r| (r.component_id)() == section.component)
+                .filter(|r| !self.strict && self.storage_id_of((r.type_id)()).is_none());
+            let state = registration.and_then(|r| (r.decode_snapshot)(self, packet));
+            match (registration, state) {
+                (Some(registration), Some(state)) => decoded.missing.push((registration, state?)),
+                _ => return Err(DecodeError::UnknownComponent(section.component)),
+            }
+        }
+
+        Ok(decoded)
+    }
+
+    /// Id of the storage holding the component type `type_id`, if the world has one.
+    fn storage_id_of(&self, type_id: TypeId) -> Option<usize> {
+        self.mask.iter().find(|&id| {
+            let storage = unsafe { self.storages[id].assume_init_ref() };
+            storage.component_type_id() == type_id
+        })
+    }
+
+    fn load_decoded_snapshot(&mut self, decoded: DecodedSnapshot) {
+        let mut states = decoded.existing;
+        for (registration, state) in decoded.missing {
+            (registration.register)(self);
+            let id = self
+                .storage_id_of((registration.type_id)())
+                .expect("registration creates the storage");
+            states.push((id, state));
+        }
+
+        let entities = self.get_storage::<Entity>();
+        let saved = states
+            .iter()
+            .find_map(|(_, state)| state.as_any().downcast_ref::<SavedComponents<Entity>>())
+            .map_or(&[][..], |saved| &saved.values);
+        self.retire_missing(&entities, saved);
 
-                let range_mask = if run == 128 {
-                    u128::MAX
-                } else {
-                    ((1u128 << run) - 1) << start
+        for (id, state) in &states {
+            let storage = unsafe { self.storages[*id].assume_init_ref() };
+            storage.load_state(Some(state.as_ref()));
+        }
+        self.hash_cache.invalidate();
+    }
+
+    /// Captures every component whose type is serde-enabled into a full `Snapshot` of the
+    /// current tick, see the `export` module. Other components and dirty bridge markers are
+    /// left out.
+    #[cfg(feature = "serde")]
+    pub fn export_snapshot(&self) -> Result<Snapshot, ValueError> {
+        let mut components = BTreeMap::new();
+        for id in self.mask.without(self.presentation_mask).iter() {
+            let storage = unsafe { self.storages[id].assume_init_ref() };
+            if let Some(set) = storage.export_values() {
+                let values = ComponentValues {
+                    set: set?,
+                    removed: Vec::new(),
                 };
-                mask &= !range_mask;
+                components.insert(storage.type_name().to_string(), values);
+            }
+        }
+
+        Ok(Snapshot {
+            tick: self.current_tick,
+            base: None,
+            components,
+        })
+    }
+
+    /// Applies a `Snapshot` from `export_snapshot` or `Snapshot::delta`. A full snapshot
+    /// replaces the serde-enabled components like `load_snapshot`; a delta sets and removes
+    /// what it lists and leaves the rest, so it must be applied to the state of its base
+    /// tick. Storages the snapshot names are created if needed.
+    ///
+    /// Importing is an edit at the current tick that can be rolled back. Every component is
+    /// converted before anything changes, so the world is left untouched on error.
+    #[cfg(feature = "serde")]
+    pub fn import_snapshot(&mut self, snapshot: &Snapshot) -> Result<(), ValueError> {
+        self.assert_phase("import_snapshot");
+        let entities = self.get_storage::<Entity>();
+        for registration in crate::component::registered_components() {
+            if snapshot.components.contains_key((registration.type_name)()) {
+                (registration.register)(self);
+            }
+        }
+
+        let mut decoded = Vec::new();
+        for id in self.mask.without(self.presentation_mask).iter() {
+            let storage = unsafe { self.storages[id].assume_init_ref() };
+            let values = snapshot.components.get(storage.type_name());
+            let set = values.map_or(&[][..], |values| &values.set);
+            if let Some(state) = storage.decode_values(set) {
+                let removed = values.map_or(&[][..], |values| &values.removed);
+                decoded.push((id, storage.type_name(), state?, removed));
             }
         }
+        let unknown = snapshot
+            .components
+            .keys()
+            .find(|name| !decoded.iter().any(|(_, known, ..)| name == known));
+        if let Some(name) = unknown {
+            return Err(ValueError(format!("unknown component {}", name)));
+        }
+
+        let (_, _, saved, removed) = decoded
+            .iter()
+            .find(|(_, name, ..)| *name == std::any::type_name::<Entity>())
+            .expect("entity storage is serde-enabled");
+        let saved = saved
+            .as_any()
+            .downcast_ref::<SavedComponents<Entity>>()
+            .map_or(&[][..], |saved| &saved.values);
+        if !snapshot.is_delta() {
+            self.retire_missing(&entities, saved);
+            for (id, _, state, _) in &decoded {
+                let storage = unsafe { self.storages[*id].assume_init_ref() };
+                storage.load_state(Some(state.as_ref()));
+            }
+        } else {
+            // Slots the delta frees or hands to a new entity
+            let stale: Vec<Entity> = self
+                .iter_entities()
+                .filter(|entity| {
+                    removed.contains(&entity.index())
+                        || saved
+                            .iter()
+                            .any(|(index, e)| *index == entity.index() && e != entity)
+                })
+                .collect();
+            unsafe { (*entities.get()).retire(&stale) };
+            for (id, _, state, removed) in &decoded {
+                let storage = unsafe { self.storages[*id].assume_init_ref() };
+                storage.apply_values(state.as_ref(), removed);
+            }
+        }
+        self.hash_cache.invalidate();
+
+        Ok(())
+    }
+
+    /// Like `apply_snapshot`, but only takes the values and removals of `T` that `sender`
+    /// owns, leaving the others as they are. See the `ownership` module.
+    pub fn apply_snapshot_from<T>(
+        &mut self,
+        packet: &Packet<'_>,
+        sender: PeerId,
+    ) -> Result<(), DecodeError>
+    where
+        T: Component<Storage = Storage<T>> + Wire,
+    {
+        self.assert_phase("apply_snapshot_from");
+        let storage = self.get_storage::<T>();
+        let codec = self.snapshot_codec::<T>().unwrap_or(&WireCodec);
+        let ownership = self.storage_ref::<Ownership>();
+        let id = component_id::<T>();
+
+        apply_snapshot_where(unsafe { &mut *storage.get() }, packet, codec, |index| {
+            let owner = ownership.and_then(|o| o.get(index));
+            owner.map_or(PeerId::AUTHORITY, |o| o.owner(id)) == sender
+        })
+    }
+
+    /// Makes `owner` the owner of component `T` on `entity`, see the `ownership` module.
+    /// Ownership is rolled back like any component. Passing `PeerId::AUTHORITY` returns it
+    /// to the server.
+    ///
+    /// # Panics
+    /// Panics if the entity doesn't exist.
+    pub fn set_owned<T: Component>(&mut self, entity: Entity, owner: PeerId) {
+        self.assert_phase("set_owned");
+        let ents = self.get_storage::<Entity>();
+        let current = unsafe { (*ents.get()).get(entity.index()) };
+        assert!(
+            current.is_some_and(|e| e.generation() == entity.generation()),
+            "Attempted to set the owner of a component on entity {} which does not exist",
+            entity.index()
+        );
+
+        // Written directly, so the local peer's write guard doesn't apply to ownership
+        let storage = self.get_storage::<Ownership>();
+        let storage = unsafe { &mut *storage.get() };
+        let mut ownership = storage.get(entity.index()).cloned().unwrap_or_default();
+        ownership.set_owner(component_id::<T>(), owner);
+
+        if ownership.is_empty() {
+            storage.remove(entity.index());
+        } else {
+            storage.set(entity.index(), &ownership);
+        }
+    }
+
+    /// Returns the owner of component `T` on `entity`, `PeerId::AUTHORITY` unless
+    /// `set_owned` says otherwise.
+    pub fn owner_of<T: Component>(&self, entity: Entity) -> PeerId {
+        let alive = self
+            .storage_ref::<Entity>()
+            .and_then(|ents| ents.get(entity.index()))
+            .is_some_and(|e| e.generation() == entity.generation());
+
+        self.storage_ref::<Ownership>()
+            .and_then(|o| o.get(entity.index()))
+            .filter(|_| alive)
+            .map_or(PeerId::AUTHORITY, |o| o.owner(component_id::<T>()))
+    }
+
+    /// Sets which peer this world belongs to. Debug builds then reject `World::set` of
+    /// components owned by another peer. `None` (the default) disables the check.
+    pub fn set_local_peer(&mut self, peer: Option<PeerId>) {
+        self.local_peer = peer;
+    }
+
+    pub fn local_peer(&self) -> Option<PeerId> {
+        self.local_peer
+    }
+
+    /// Returns a sender of cross-zone messages for use outside of systems. Its messages
+    /// are delivered before those of any system.
+    pub fn zone_sender<M: 'static>(&mut self) -> ZoneSender<M> {
+        self.zone_channel::<M>().sender(EXTERNAL_STAGE)
+    }
+
+    /// Returns a sender of cross-zone messages owned by the system `S`. Messages are
+    /// ordered by the sender's stable `SequenceKey`.
+    pub fn zone_sender_from<M: 'static, S: 'static>(&mut self) -> ZoneSender<M> {
+        self.zone_channel::<M>().sender(stage_key::<S>())
+    }
+
+    /// Returns a retained query over the entities that have every component in `Q`, kept
+    /// up to date at the end of each tick. See the `watch` module.
+    pub fn watch_query<Q: ComponentSet>(&mut self) -> QueryWatch {
+        let watch = QueryWatch::new::<Q>(self);
+        self.watches.push(Rc::downgrade(watch.state()));
+        watch
+    }
+
+    /// Preallocates and pre-touches `T`'s storage for entity indices up to
+    /// `expected_max_index`, see the `warmup` module.
+    pub fn warmup<T: Component>(&mut self, expected_max_index: u32) {
+        let storage = self.get_storage::<T>();
+        unsafe { (*storage.get()).warmup(expected_max_index) };
+    }
+
+    /// Warms every storage used by the plan's prefabs for its whole population.
+    pub fn warmup_plan(&mut self, plan: &WarmupPlan) {
+        plan.apply(self);
+    }
+
+    /// Memory held by `T`'s storage.
+    pub fn component_memory<T: Component>(&mut self) -> MemoryStats {
+        let storage = self.get_storage::<T>();
+        unsafe { (*storage.get()).memory_stats() }
+    }
+
+    /// Memory held by the rollback history of `T`'s storage.
+    pub fn history_memory<T>(&mut self) -> HistoryStats
+    where
+        T: Component<Storage = Storage<T>>,
+    {
+        let storage = self.get_storage::<T>();
+        unsafe { (*storage.get()).history_stats() }
+    }
+
+    /// Sets how `T`'s storage records rollback snapshots, see `SnapshotGranularity`.
+    pub fn set_snapshot_granularity<T>(&mut self, granularity: SnapshotGranularity)
+    where
+        T: Component<Storage = Storage<T>>,
+    {
+        let storage = self.get_storage::<T>();
+        unsafe { (*storage.get()).set_snapshot_granularity(granularity) };
+    }
+
+    /// Stores `T`'s rollback snapshots as byte deltas from now on, see `DeltaCompressible`.
+    pub fn enable_delta_snapshots<T>(&mut self)
+    where
+        T: Component<Storage = Storage<T>> + DeltaCompressible,
+    {
+        let storage = self.get_storage::<T>();
+        unsafe { (*storage.get()).enable_delta_snapshots() };
+    }
+
+    /// Memory held by every storage, with the component type names, in type index order.
+    pub fn memory_stats(&self) -> Vec<(&'static str, MemoryStats)> {
+        let mut stats = Vec::new();
+        for id in self.mask.iter() {
+            let storage = unsafe { self.storages[id].assume_init_ref() };
+            stats.push((storage.type_name(), storage.memory_stats()));
+        }
+
+        stats
+    }
+
+    /// Frees the storage blocks mass despawns left empty, returning the bytes reclaimed.
+    /// Blocks the rollback history still records are kept until it is pruned past them,
+    /// see `Storage::shrink`.
+    pub fn compact(&mut self) -> usize {
+        self.assert_phase("compact");
+        let before: usize = self.memory_stats().iter().map(|(_, stats)| stats.bytes).sum();
+        for id in self.mask.iter() {
+            let storage = unsafe { self.storages[id].assume_init_ref() };
+            storage.shrink();
+        }
+        let after: usize = self.memory_stats().iter().map(|(_, stats)| stats.bytes).sum();
+        before.saturating_sub(after)
+    }
+
+    /// Saves every component of the world into the slot `name`, replacing any slot of that
+    /// name. Slots are kept outside the rollback history, see the `savestate` module.
+    pub fn save_slot(&mut self, name: &str) {
+        let mut storages = HashMap::new();
+        for id in self.mask.iter() {
+            let storage = unsafe { self.storages[id].assume_init_ref() };
+            storages.insert(id, storage.save_state());
+        }
+
+        let slot = SaveSlot {
+            tick: self.current_tick,
+            storages,
+        };
+        self.save_slots.insert(name.to_string(), slot);
+    }
+
+    /// Replaces every component of the world with the ones saved in the slot `name`. This
+    /// is an edit at the current tick and can be rolled back. Live entities the slot
+    /// doesn't have are retired like destroyed ones, so their handles go stale.
+    ///
+    /// Returns the tick the slot was saved at, or `None` if there is no such slot.
+    pub fn load_slot(&mut self, name: &str) -> Option<Tick> {
+        self.assert_phase("load_slot");
+        if !self.save_slots.contains_key(name) {
+            return None;
+        }
+        let entities = self.get_storage::<Entity>();
+        let slot = &self.save_slots[name];
+        self.retire_missing(&entities, slot.components::<Entity>());
+
+        for id in self.mask.iter() {
+            let storage = unsafe { self.storages[id].assume_init_ref() };
+            storage.load_state(slot.storages.get(&id).map(|saved| saved.as_ref()));
+        }
+        self.hash_cache.invalidate();
+
+        Some(slot.tick)
+    }
+
+    /// Deletes the slot `name`. Returns false if there was no such slot.
+    pub fn delete_slot(&mut self, name: &str) -> bool {
+        self.save_slots.remove(name).is_some()
+    }
+
+    /// Every save slot, by name.
+    pub fn slots(&self) -> Vec<SlotInfo> {
+        self.save_slots
+            .iter()
+            .map(|(name, slot)| slot.info(name))
+            .collect()
+    }
+
+    /// Approximate memory held by all save slots together.
+    pub fn slot_memory(&self) -> usize {
+        self.slots().iter().map(|slot| slot.bytes).sum()
+    }
+
+    /// Returns a copy-on-write view of the world for speculative changes, see the `cow`
+    /// module. Only the blocks the fork writes to are copied.
+    pub fn fork_cow(&self) -> WorldFork<'_> {
+        WorldFork::new(self)
+    }
+
+    /// Hashes the world along the block tree to locate desyncs, see the `hashtree` module.
+    /// Only blocks changed since the last call are rehashed.
+    pub fn hash_tree(&mut self) -> HashTree {
+        let storages: Vec<(usize, &str)> = self
+            .update_hashes()
+            .into_iter()
+            .map(|id| (id, unsafe { self.storages[id].assume_init_ref() }.type_name()))
+            .collect();
+        self.hash_cache.tree(&storages)
+    }
+
+    /// Per-storage block hashes of the world, to send to a peer for `diff_against`.
+    pub fn hash_report(&mut self) -> HashReport {
+        let storages: Vec<(usize, &dyn StorageLike)> = self
+            .update_hashes()
+            .into_iter()
+            .map(|id| (id, unsafe { self.storages[id].assume_init_ref() }.as_ref()))
+            .collect();
+        self.hash_cache.report(self.current_tick, &storages)
+    }
+
+    /// Compares the world with a peer's `hash_report()` taken at the same tick and lists the
+    /// storages, blocks and entities that diverged, see the `hashtree` module.
+    pub fn diff_against(&mut self, other: &HashReport) -> DesyncReport {
+        self.hash_report().diff(other)
+    }
+
+    /// Brings the cached block hashes up to date and returns the type indices of the hashed
+    /// storages.
+    fn update_hashes(&mut self) -> Vec<usize> {
+        let ids: Vec<usize> = self.mask.without(self.presentation_mask).iter().collect();
+        for &id in &ids {
+            let storage = unsafe { self.storages[id].assume_init_ref() };
+            self.hash_cache.update(id, storage.as_ref());
+        }
+        ids
+    }
+
+    /// Starts (or with `false`, stops) hashing the world state after every simulated tick,
+    /// see the `statehash` module. Stopping forgets the recorded hashes.
+    pub fn record_state_hashes(&mut self, enabled: bool) {
+        if enabled != self.state_hashes.is_some() {
+            self.state_hashes = enabled.then(VecDeque::new);
+        }
+    }
+
+    /// The state hash recorded at the end of `tick`, if hashes were being recorded then and
+    /// `tick` is still in the rollback window.
+    pub fn state_hash(&self, tick: Tick) -> Option<&StateHash> {
+        self.state_hashes.as_ref()?.iter().find(|hash| hash.tick == tick)
+    }
+
+    /// Hashes the current state, as `state_hash` records it for the last simulated tick.
+    pub fn compute_state_hash(&self) -> StateHash {
+        self.state_hash_at(Tick::new(self.current_tick.value().wrapping_sub(1)))
+    }
+
+    fn state_hash_at(&self, tick: Tick) -> StateHash {
+        let mut components = Vec::new();
+        for id in self.mask.without(self.presentation_mask).iter() {
+            let storage = unsafe { self.storages[id].assume_init_ref() };
+            // A peer may have created a storage it never filled; that isn't state
+            if storage.is_empty() {
+                continue;
+            }
+            components.push((storage.stable_name(), storage.state_hash()));
+        }
+
+        StateHash::new(tick, components)
+    }
+
+    fn record_state_hash(&mut self, tick: Tick) {
+        if self.state_hashes.is_none() {
+            return;
+        }
+        let hash = self.state_hash_at(tick);
+        let oldest = self.rollback_window().oldest;
+
+        let hashes = self.state_hashes.as_mut().expect("checked above");
+        while hashes.front().is_some_and(|h| h.tick.is_before(oldest)) {
+            hashes.pop_front();
+        }
+        // After `rollback(tick)` the tick runs again and replaces its hash
+        while hashes.back().is_some_and(|h| !h.tick.is_before(tick)) {
+            hashes.pop_back();
+        }
+        hashes.push_back(hash);
+    }
+
+    /// The change tracker the cleanup system of the storage with type index `id` feeds
+    /// the hash cache with.
+    pub(crate) fn dirty_blocks(&mut self, id: usize) -> Rc<DirtyBlocks> {
+        self.hash_cache.dirty_blocks(id)
+    }
+
+    /// The change log the cleanup system of the storage with type index `id` records the
+    /// tick's additions and updates in.
+    pub(crate) fn change_log(&mut self, id: usize) -> Rc<ChangeLog> {
+        self.journal.log(id)
+    }
+
+    /// Starts (or with `false`, stops) journaling component changes, see the `journal`
+    /// module. Stopping forgets the changes not drained yet.
+    pub fn record_changes(&mut self, enabled: bool) {
+        self.journal.set_enabled(enabled);
+    }
+
+    /// Removes and returns the journaled changes of the ticks simulated since the last
+    /// call, oldest first.
+    pub fn drain_changes(&mut self) -> impl Iterator<Item = Change> + '_ {
+        self.journal.drain()
+    }
+
+    /// Moves the changes recorded during `tick` into the journal, once the storages moved
+    /// on to the next tick and their removal logs show the removals of `tick`.
+    fn journal_tick(&mut self, tick: Tick) {
+        if !self.journal.is_enabled() {
+            return;
+        }
+
+        let entities = self.get_storage::<Entity>();
+        let entities = unsafe { &*entities.get() };
+        let mut changes = Vec::new();
+        for id in self.journal.ids() {
+            let updates = self.journal.take_log(id);
+            if !self.mask.contains(id) {
+                continue;
+            }
+            let storage = unsafe { self.storages[id].assume_init_ref() };
+            let (component, name) = (storage.component_type_id(), storage.type_name());
+
+            let updates = updates
+                .into_iter()
+                .filter_map(|(index, kind)| {
+                    let entity = entities.get(index).copied();
+                    entity.or_else(|| entities.retired_at(tick, index)).map(|e| (e, kind))
+                });
+            let removals = storage.removed_indices().into_iter().filter_map(|index| {
+                // Components of entities destroyed this tick name the retired entity
+                let entity = entities.retired_at(tick, index);
+                entity.or_else(|| entities.get(index).copied()).map(|e| (e, ChangeKind::Removed))
+            });
+
+            changes.extend(updates.chain(removals).map(|(entity, kind)| Change {
+                tick,
+                entity,
+                component,
+                name,
+                kind,
+            }));
+        }
+        self.journal.record(tick, changes);
+    }
+
+    /// Publishes the tick about to be simulated to tick-dependent system parameters.
+    fn begin_tick(&mut self) {
+        self.rng_clock.tick.set(self.current_tick);
+
+        let interval = self.rollback_config.prune_interval.max(1);
+        if self.current_tick.value().is_multiple_of(interval) {
+            self.prune_history();
+        }
+
+        let oldest = self.rollback_window().oldest;
+        self.pending.begin_tick(self.current_tick, oldest);
+
+        for queue in self.ingests.values() {
+            queue.begin_tick(self.current_tick);
+        }
+
+        for table in self.expiries.values() {
+            table.begin_tick(self.current_tick, oldest);
+        }
+
+        for cell in self.resources.values() {
+            cell.begin_tick(self.current_tick, oldest);
+        }
+
+        for state in self.states.values() {
+            state.begin_tick(self.current_tick);
+        }
+
+        for buffer in self.inputs.values() {
+            buffer.begin_tick(self.current_tick, oldest);
+        }
+    }
+
+    /// Sets the seed all `EntityRng` streams are derived from. Every peer must use the
+    /// same seed.
+    pub fn set_seed(&mut self, seed: u64) {
+        self.rng_clock.seed.set(seed);
+    }
+
+    /// Returns the world seed.
+    pub fn seed(&self) -> u64 {
+        self.rng_clock.seed.get()
+    }
+
+    /// Sets the rate the world starts with, in ticks per second. Change points recorded
+    /// with `rescale_tick_rate` still apply.
+    ///
+    /// # Panics
+    /// Panics if `rate` is zero.
+    pub fn set_tick_rate(&mut self, rate: u32) {
+        self.tick_rates.set_initial(rate);
+    }
+
+    /// Switches to `new_rate` ticks per second from `at_tick` on, see the `tickrate`
+    /// module. A change already recorded at `at_tick` is replaced.
+    ///
+    /// # Panics
+    /// Panics if `new_rate` is zero or `at_tick` has already been simulated.
+    pub fn rescale_tick_rate(&mut self, new_rate: u32, at_tick: Tick) -> TickRateChange {
+        assert!(
+            !at_tick.is_before(self.current_tick),
+            "Cannot change the tick rate of {:?}, it has already been simulated (current tick {:?})",
+            at_tick,
+            self.current_tick
+        );
+        let change = TickRateChange {
+            at: at_tick,
+            rate: new_rate,
+        };
+        self.tick_rates.record(change);
+        change
+    }
+
+    /// Returns the rate of the current tick in ticks per second.
+    pub fn tick_rate(&self) -> u32 {
+        self.tick_rates.rate_at(self.current_tick)
+    }
+
+    /// Returns the length of the current tick.
+    pub fn tick_duration(&self) -> Duration {
+        tickrate::tick_duration(self.tick_rate())
+    }
+
+    /// Returns the starting rate and every recorded rate change.
+    pub fn tick_rate_log(&self) -> &TickRateLog {
+        &self.tick_rates
+    }
+
+    /// Replaces the tick rate metadata with `log`, as received by a replay or late joiner.
+    pub fn load_tick_rate_log(&mut self, log: TickRateLog) {
+        self.tick_rates = log;
+    }
+
+    /// The clock tick-dependent system parameters read the simulated tick from.
+    pub(crate) fn tick_clock(&self) -> Rc<RngClock> {
+        self.rng_clock.clone()
+    }
+
+    /// Adds the system setting the dirty marker `M` on entities whose `M::Source` changed,
+    /// see the `dirty` module. The marker storage is left out of `hash_tree`.
+    pub fn add_dirty_bridge<M: DirtyMarker>(&mut self) {
+        self.get_storage::<M>();
+        self.presentation_mask.insert(<M as crate::component::Resource>::type_index());
+        self.add_system::<DirtyBridge<M>>();
+    }
+
+    /// Live entities flagged with the dirty marker `M`, in ascending index order, clearing
+    /// every marker. Markers left on destroyed entities are dropped.
+    pub fn take_dirty<M: DirtyMarker>(&mut self) -> Vec<Entity> {
+        let mut indices = Vec::new();
+        let mut markers = self.storage_mut::<M>();
+        markers.visit(|index, _| indices.push(index));
+        for &index in &indices {
+            markers.remove(index);
+        }
+        drop(markers);
+
+        let entities = self.get_storage::<Entity>();
+        let entities = unsafe { &*entities.get() };
+        indices
+            .into_iter()
+            .filter_map(|index| entities.get(index).copied())
+            .collect()
+    }
+
+    /// Returns a per-entity random stream source for the system `S`.
+    pub fn entity_rng<S: 'static>(&mut self) -> RngSource {
+        RngSource::new(self.rng_clock.clone(), stage_key::<S>())
+    }
+
+    /// Returns what happened to an ingested item, or `None` if it hasn't been drained yet.
+    pub fn ingest_status<T: Component>(&mut self, ticket: Ticket) -> Option<IngestStatus> {
+        self.ingest_queue::<T>().log().status(ticket)
+    }
+
+    /// Registers a tag by name and returns its bit, the lowest one not registered yet.
+    /// Registering the same name twice returns the same id. Register every tag before
+    /// adding the systems whose `Has=[...]` / `Not=[...]` clauses name it.
+    ///
+    /// # Panics
+    /// Panics if more than `MAX_TAGS` distinct tags are registered.
+    pub fn register_tag(&mut self, name: &'static str) -> TagId {
+        self.tags.register(name)
+    }
+
+    /// The id of a tag registered with `register_tag`. Systems call this when they are
+    /// created, for `tag("name")` in their clauses.
+    ///
+    /// # Panics
+    /// Panics if no tag was registered under `name`.
+    pub fn tag(&self, name: &str) -> TagId {
+        self.tags
+            .get(name)
+            .unwrap_or_else(|| panic!("Tag '{}' is not registered, see World::register_tag", name))
+    }
+
+    /// Returns the name a tag was registered with, if any.
+    pub fn tag_name(&self, id: TagId) -> Option<&'static str> {
+        self.tags.name(id)
+    }
+
+    /// Adds a tag to the entity's `TagSet`, inserting the component if needed.
+    pub fn add_tag(&mut self, entity: Entity, tag: TagId) {
+        self.assert_phase("add_tag");
+        let tags = self.get_storage::<TagSet>();
+        let mut set = unsafe { (*tags.get()).get(entity.index()).copied().unwrap_or_default() };
+
+        if !set.contains(tag) {
+            set.insert(tag);
+            self.set(entity, &set);
+        }
+    }
+
+    /// Removes a tag from the entity's `TagSet`. The component itself is kept.
+    pub fn remove_tag(&mut self, entity: Entity, tag: TagId) {
+        self.assert_phase("remove_tag");
+        let tags = self.get_storage::<TagSet>();
+        let current = unsafe { (*tags.get()).get(entity.index()).copied() };
+
+        if let Some(mut set) = current
+            && set.contains(tag)
+        {
+            set.remove(tag);
+            self.set(entity, &set);
+        }
+    }
+
+    /// Returns true if the entity's `TagSet` contains the tag.
+    pub fn has_tag(&mut self, entity: Entity, tag: TagId) -> bool {
+        let tags = self.get_storage::<TagSet>();
+        unsafe { (*tags.get()).get(entity.index()) }.is_some_and(|set| set.contains(tag))
+    }
+
+    pub fn spawn(&mut self) -> Entity {
+        self.assert_phase("spawn");
+        unsafe { (*self.get_storage::<Entity>().get()).spawn() }
+    }
+
+    /// Every live entity in ascending index order, walked through the entity storage's
+    /// presence masks. Entities marked `Destroyed` are listed until the destroy system
+    /// removes them.
+    pub fn iter_entities(&self) -> impl Iterator<Item = Entity> + '_ {
+        self.storage_ref::<Entity>()
+            .into_iter()
+            .flat_map(|entities| entities.iter().map(|(_, entity)| *entity))
+    }
+
+    /// Whether `entity` is alive: its slot is occupied by the same generation.
+    pub fn contains(&self, entity: Entity) -> bool {
+        self.storage_ref::<Entity>()
+            .and_then(|entities| entities.get(entity.index()))
+            == Some(&entity)
+    }
+
+    /// Drops every entity and component, e.g. to start a new match, while the storages,
+    /// systems and scheduler stay. The rollback history goes too, so the window starts
+    /// at the current tick. Handles of the dropped entities go stale.
+    ///
+    /// Resources, save slots and confirmed inputs are kept; pending mailbox messages,
+    /// removals, component expiries and staged network writes are dropped.
+    pub fn clear_entities(&mut self) {
+        self.assert_phase("clear_entities");
+        self.clear_world(true);
+    }
+
+    /// Clears the world like `clear_entities` and restarts it at tick zero, as if it were
+    /// new but with its systems, scheduler, resources and settings. Everything logged by
+    /// tick is dropped as well: inputs, surfaced effects and tick rate changes. Entity
+    /// generations start over, so the first spawns match those of a new world.
+    pub fn reset_to_tick_zero(&mut self) {
+        self.assert_phase("reset_to_tick_zero");
+        self.current_tick = Tick::new(0);
+        self.clear_world(false);
+
+        for buffer in self.inputs.values() {
+            buffer.clear();
+        }
+        for queue in self.effects.values() {
+            queue.clear();
+        }
+        self.tick_rates = TickRateLog::new(self.tick_rates.initial());
+        self.accumulator = Duration::ZERO;
+        self.rng_clock.tick.set(self.current_tick);
+    }
+
+    /// Shared part of `clear_entities` and `reset_to_tick_zero`.
+    fn clear_world(&mut self, keep_generations: bool) {
+        let entity_id = <Entity as crate::component::Resource>::type_index();
+        for id in self.mask.iter() {
+            let storage = unsafe { self.storages[id].assume_init_ref() };
+            if id == entity_id && keep_generations {
+                let entities = storage
+                    .as_any()
+                    .downcast_ref::<Rc<UnsafeCell<Storage<Entity>>>>()
+                    .expect("entity storage");
+                unsafe { (*entities.get()).clear_retiring(self.current_tick) };
+                storage.clear_kept_changes();
+            } else {
+                storage.clear();
+            }
+        }
+        self.sync_storage_ticks();
+        self.history_start = self.current_tick;
+        self.pruned_to = self.current_tick;
+
+        for mailbox in self.mailboxes.values() {
+            mailbox.clear();
+        }
+        for queue in self.removals.values() {
+            queue.clear();
+        }
+        for table in self.expiries.values() {
+            table.clear();
+        }
+        for cell in self.resources.values() {
+            cell.forget_history();
+        }
+        for queue in self.effects.values() {
+            queue.clear_pending();
+        }
+        self.pending.clear();
+        if let Some(hashes) = self.state_hashes.as_mut() {
+            hashes.clear();
+        }
+        #[cfg(feature = "panic-isolation")]
+        {
+            self.tainted = None;
+        }
+        self.hash_cache.invalidate();
+        crate::watch::refresh_all(&mut self.watches);
+    }
+
+    /// Returns the range of ticks that `rollback()` can currently restore.
+    pub fn rollback_window(&self) -> RollbackWindow {
+        let mut oldest = self.history_start;
+
+        let depths = [self.max_rollback_depth, self.rollback_config.history_len];
+        for depth in depths.into_iter().flatten() {
+            let clamped = self.current_tick - TickDelta::new(depth as i32);
+            if clamped.is_after(oldest) {
+                oldest = clamped;
+            }
+        }
+
+        RollbackWindow {
+            oldest,
+            newest: self.current_tick,
+        }
+    }
+
+    /// Limits how many ticks `rollback()` may resimulate. Requests reaching further back
+    /// are treated as overflows (see `on_rollback_overflow`). `None` removes the limit.
+    /// Unlike `set_history_len`, the history stays, so the limit can be lifted again.
+    pub fn set_max_rollback_depth(&mut self, depth: Option<u32>) {
+        self.max_rollback_depth = depth;
+    }
+
+    /// Sets how much rollback history the world keeps, see `RollbackConfig`.
+    pub fn set_rollback_config(&mut self, config: RollbackConfig) {
+        self.rollback_config = config;
+    }
+
+    pub fn rollback_config(&self) -> RollbackConfig {
+        self.rollback_config
+    }
+
+    /// Keeps `ticks` ticks of rollback history and prunes the rest as the world advances,
+    /// so memory stays bounded in long sessions.
+    ///
+    /// # Example
+    /// ```ignore
+    /// world.set_history_len(8);
+    /// world.run_for(1000);
+    /// assert_eq!(world.rollback_window().depth(), 8);
+    /// ```
+    pub fn set_history_len(&mut self, ticks: u32) {
+        self.rollback_config.history_len = Some(ticks);
+    }
+
+    /// Marks every tick up to `tick` as final: `rollback()` can restore the end of `tick`
+    /// but nothing before it, and the older history is pruned. Peers call it once every
+    /// input up to `tick` is known. Confirming a tick older than an earlier confirmation
+    /// does nothing.
+    ///
+    /// # Panics
+    /// Panics if `tick` is after the current tick.
+    pub fn confirm(&mut self, tick: Tick) {
+        assert!(
+            !tick.is_after(self.current_tick),
+            "Cannot confirm tick {}, the current tick is {}",
+            tick.value(),
+            self.current_tick.value()
+        );
+        if tick.is_after(self.history_start) {
+            self.history_start = tick;
+        }
+    }
+
+    /// Drops the history older than the configured length or the last confirmed tick now,
+    /// instead of at the next pruning tick.
+    pub fn prune_history(&mut self) {
+        if let Some(len) = self.rollback_config.history_len {
+            let floor = self.current_tick - TickDelta::new(len as i32);
+            if floor.is_after(self.history_start) {
+                self.history_start = floor;
+            }
+        }
+        let oldest = self.history_start;
+        if oldest == self.pruned_to {
+            return;
+        }
+        self.pruned_to = oldest;
+
+        for id in self.mask.iter() {
+            let storage = unsafe { self.storages[id].assume_init_ref() };
+            storage.prune_history(oldest);
+        }
+    }
+
+    /// Installs a handler invoked when `rollback()` is asked for a tick older than the
+    /// retained history. The handler receives the requested tick and the available window
+    /// and decides whether to clamp to the oldest tick or leave the world untouched, so
+    /// games can desync-disconnect, pause, or request a full resync instead of crashing.
+    ///
+    /// Without a handler, such a rollback panics.
+    ///
+    /// # Example
+    /// ```ignore
+    /// world.on_rollback_overflow(|overflow| {
+    ///     request_full_resync(overflow.requested);
+    ///     OverflowAction::Ignore
+    /// });
+    /// ```
+    pub fn on_rollback_overflow<F>(&mut self, handler: F)
+    where
+        F: FnMut(&RollbackOverflow) -> OverflowAction + 'static,
+    {
+        self.rollback_overflow_handler = Some(Box::new(handler));
+    }
+
+    /// Removes the rollback overflow handler, restoring the panicking default.
+    pub fn clear_rollback_overflow_handler(&mut self) {
+        self.rollback_overflow_handler = None;
+    }
+
+    /// Rolls all storages back to `target_tick`.
+    ///
+    /// Entities spawned after `target_tick` are removed from the entity storage and every
+    /// component storage, and their indices are freed. `spawn` takes the lowest free index,
+    /// so resimulating the same spawns hands out the same entities again. The returned
+    /// report lists those entities in `despawned`.
+    ///
+    /// If `target_tick` is older than `rollback_window().oldest`, the overflow handler
+    /// decides what happens (see `on_rollback_overflow`). A `target_tick` after every
+    /// recorded tick leaves the world untouched and reports `applied: false`.
+    ///
+    /// # Panics
+    /// Panics if the target is outside the window and no overflow handler is installed.
+    pub fn rollback(&mut self, target_tick: Tick) -> RollbackReport {
+        self.assert_phase("rollback");
+        self.phase = WorldPhase::RollingBack;
+        let report = self.rollback_inner(target_tick);
+        self.phase = WorldPhase::Idle;
+        report
+    }
+
+    /// Restores the state at the start of `tick` and makes it the current tick again, so
+    /// the next `run()` resimulates `tick` under the same tick number. Unlike
+    /// `rollback(tick)`, which keeps everything recorded at `tick`, this also undoes the
+    /// inputs set before `tick` ran and the tick itself.
+    ///
+    /// # Panics
+    /// Panics if `tick - 1` is outside the rollback window and no overflow handler is
+    /// installed. With a handler, the window is handled as in `rollback(tick - 1)`.
+    pub fn resimulate_from(&mut self, tick: Tick) -> RollbackReport {
+        self.assert_phase("resimulate_from");
+        self.phase = WorldPhase::RollingBack;
+
+        let target = Tick::new(tick.value().wrapping_sub(1));
+        let report = if self.rollback_window().contains(target) {
+            let report = self.rollback_storages(target);
+            self.current_tick = tick;
+            self.sync_storage_ticks();
+            report
+        } else {
+            self.rollback_inner(target)
+        };
+
+        self.phase = WorldPhase::Idle;
+        report
+    }
+
+    fn rollback_inner(&mut self, target_tick: Tick) -> RollbackReport {
+        // Nothing has been recorded after the newest tick, so there is nothing to undo
+        if target_tick.is_after(self.newest_recorded_tick()) {
+            return RollbackReport {
+                target: self.current_tick,
+                applied: false,
+                despawned: Vec::new(),
+                restored: Vec::new(),
+            };
+        }
+
+        let window = self.rollback_window();
+
+        if !window.contains(target_tick) {
+            let overflow = RollbackOverflow {
+                requested: target_tick,
+                window,
+            };
+
+            let action = match self.rollback_overflow_handler.as_mut() {
+                Some(handler) => handler(&overflow),
+                None => panic!(
+                    "Cannot rollback to tick {}: oldest retained tick is {} (current tick {})",
+                    target_tick.value(),
+                    window.oldest.value(),
+                    window.newest.value()
+                ),
+            };
+
+            return match action {
+                OverflowAction::Clamp => self.rollback_storages(window.oldest),
+                OverflowAction::Ignore => RollbackReport {
+                    target: window.newest,
+                    applied: false,
+                    despawned: Vec::new(),
+                    restored: Vec::new(),
+                },
+            };
+        }
+
+        self.rollback_storages(target_tick)
+    }
+
+    /// The newest tick the world or any storage records changes under. Storages driven
+    /// with `set_tick` can be ahead of `current_tick`.
+    fn newest_recorded_tick(&self) -> Tick {
+        self.mask
+            .iter()
+            .map(|id| unsafe { self.storages[id].assume_init_ref() }.tick())
+            .fold(self.current_tick, |newest, tick| {
+                if tick.is_after(newest) { tick } else { newest }
+            })
+    }
+
+    fn rollback_storages(&mut self, target_tick: Tick) -> RollbackReport {
+        let before: Vec<Entity> = self.iter_entities().collect();
+        #[cfg(feature = "panic-isolation")]
+        {
+            self.tainted = None;
+        }
+
+        // Messages queued during the abandoned timeline must not leak into resimulation
+        self.clear_mailboxes();
+        self.clear_removals();
+        for queue in self.effects.values() {
+            queue.clear_pending();
+        }
+        self.hash_cache.invalidate();
+        self.journal.rollback(target_tick);
+        if let Some(hashes) = self.state_hashes.as_mut() {
+            hashes.retain(|hash| !hash.tick.is_after(target_tick));
+        }
+
+        for table in self.expiries.values() {
+            table.rollback(target_tick);
+        }
+        for cell in self.resources.values() {
+            cell.rollback(target_tick);
+        }
+        for buffer in self.inputs.values() {
+            buffer.rollback(target_tick);
+        }
+        self.pending.rollback(target_tick);
+
+        for id in self.mask.iter() {
+            let storage = unsafe { self.storages[id].assume_init_ref() };
+            storage.rollback(target_tick);
+        }
+
+        let report = self.diff_alive_entities(target_tick, before);
+
+        // Components are rolled back per storage, so one recorded in a later tick than the
+        // entity it belongs to could survive it. Nothing may outlive an un-spawned entity.
+        let entities = self.storage_ref::<Entity>();
+        for entity in &report.despawned {
+            if entities.and_then(|e| e.get(entity.index())).is_none() {
+                self.discard_components(entity.index());
+            }
+        }
+
+        // Update world's current tick to match the target tick after rollback
+        self.current_tick = target_tick;
+        crate::watch::refresh_all(&mut self.watches);
+        report
+    }
+
+    /// Retires the live entities `saved` doesn't have, for `load_slot` and `load_snapshot`.
+    fn retire_missing(
+        &self,
+        entities: &Rc<UnsafeCell<Storage<Entity>>>,
+        saved: &[(u32, Entity)],
+    ) {
+        let stale: Vec<Entity> = self
+            .iter_entities()
+            .filter(|entity| {
+                saved
+                    .binary_search_by_key(&entity.index(), |(index, _)| *index)
+                    .map_or(true, |at| saved[at].1 != *entity)
+            })
+            .collect();
+        unsafe { (*entities.get()).retire(&stale) };
+    }
+
+    fn diff_alive_entities(&self, target: Tick, before: Vec<Entity>) -> RollbackReport {
+        let after: Vec<Entity> = self.iter_entities().collect();
+        let mut despawned = Vec::new();
+        let mut restored = Vec::new();
+
+        // Both lists are sorted by index, so one merge pass pairs up the slots
+        let (mut i, mut j) = (0, 0);
+        while i < before.len() || j < after.len() {
+            match (before.get(i), after.get(j)) {
+                (Some(b), Some(a)) if b.index() == a.index() => {
+                    if b != a {
+                        despawned.push(*b);
+                        restored.push(*a);
+                    }
+                    i += 1;
+                    j += 1;
+                }
+                (Some(b), a) if a.is_none_or(|a| b.index() < a.index()) => {
+                    despawned.push(*b);
+                    i += 1;
+                }
+                (_, Some(a)) => {
+                    restored.push(*a);
+                    j += 1;
+                }
+                (_, None) => unreachable!(),
+            }
+        }
+
+        RollbackReport {
+            target,
+            applied: true,
+            despawned,
+            restored,
+        }
+    }
+
+    /// Drops every component at `index` without recording it for rollback.
+    fn discard_components(&self, index: u32) {
+        let entity_id = <Entity as crate::component::Resource>::type_index();
+        for id in self.mask.iter() {
+            if id != entity_id {
+                unsafe { self.storages[id].assume_init_ref().discard_index(index) };
+            }
+        }
+    }
+
+}
+
+/// A `save_snapshot` packet decoded by `World::decode_world_snapshot`.
+struct DecodedSnapshot {
+    /// States for the world's storages, by storage id.
+    existing: Vec<(usize, Box<dyn SavedStorage>)>,
+    /// States for component types the world has no storage for yet.
+    missing: Vec<(&'static ComponentRegistration, Box<dyn SavedStorage>)>,
+}
+
+/// Calls `f` with every index present in all `storages`, in ascending order.
+fn for_each_match(storages: &[Box<dyn StorageLike>], mut f: impl FnMut(u32)) {
+    let mut root = storages.iter().fold(u128::MAX, |m, s| m & s.root_mask());
+    while root != 0 {
+        let ri = root.trailing_zeros();
+        root &= !(1u128 << ri);
+
+        let mut middle = storages.iter().fold(u128::MAX, |m, s| m & s.middle_mask(ri));
+        while middle != 0 {
+            let mi = middle.trailing_zeros();
+            middle &= !(1u128 << mi);
+
+            let mut inner = storages.iter().fold(u128::MAX, |m, s| m & s.inner_mask(ri, mi));
+            while inner != 0 {
+                let ii = inner.trailing_zeros();
+                inner &= !(1u128 << ii);
+                f(ri * 16384 + mi * 128 + ii);
+            }
+        }
+    }
+}
+
+/// Counts the indices present in all `storages`.
+fn count_matches(storages: &[Box<dyn StorageLike>]) -> usize {
+    let mut count = 0;
+    let mut root = storages.iter().fold(u128::MAX, |m, s| m & s.root_mask());
+    while root != 0 {
+        let ri = root.trailing_zeros();
+        root &= !(1u128 << ri);
+
+        let mut middle = storages.iter().fold(u128::MAX, |m, s| m & s.middle_mask(ri));
+        while middle != 0 {
+            let mi = middle.trailing_zeros();
+            middle &= !(1u128 << mi);
+
+            let inner = storages.iter().fold(u128::MAX, |m, s| m & s.inner_mask(ri, mi));
+            count += inner.count_ones() as usize;
+        }
+    }
+    count
+}
+
+impl Drop for World {
+    fn drop(&mut self) {
+        for id in self.mask.iter() {
+            unsafe { self.storages[id].assume_init_drop() };
+        }
     }
 }
 
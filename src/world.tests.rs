@@ -1,12 +1,15 @@
 use crate::component::{Component, Destroyed};
 use crate::entity::Entity;
 use crate::prelude::system;
+use crate::rollback::RollbackConfig;
 use crate::safety::verify_storage_invariants;
-use crate::storage::Storage;
+use crate::storage::{ComponentStorage, Storage};
 use crate::system::ComponentCleanupSystem;
 use crate::tick::Tick;
 use crate::world::World;
+use std::any::TypeId;
 use std::rc::Rc;
+use std::time::Duration;
 
 #[test]
 fn test_world_destroy() {
@@ -1918,11 +1921,11 @@ fn test_destroy_system_removes_entities_and_maintains_invariants() {
     world.run();
 
     // Verify entities are removed
-    assert_eq!(unsafe { (*world.get_storage::<Entity>().get()).len() }, 2);
-    assert!(unsafe { (*world.get_storage::<Entity>().get()).get(e1.index()) }.is_some());
-    assert!(unsafe { (*world.get_storage::<Entity>().get()).get(e2.index()) }.is_none());
-    assert!(unsafe { (*world.get_storage::<Entity>().get()).get(e3.index()) }.is_some());
-    assert!(unsafe { (*world.get_storage::<Entity>().get()).get(e4.index()) }.is_none());
+    assert_eq!(world.iter_entities().collect::<Vec<_>>(), vec![e1, e3]);
+    assert!(world.contains(e1));
+    assert!(!world.contains(e2));
+    assert!(world.contains(e3));
+    assert!(!world.contains(e4));
 
     // Verify Destroyed components are removed
     assert_eq!(
@@ -2034,12 +2037,12 @@ fn test_destroy_system_with_many_entities_maintains_invariants() {
     // Verify all remaining entities have their components
     for (i, &e) in entities.iter().enumerate() {
         if i % 3 != 0 {
-            assert!(unsafe { (*world.get_storage::<Entity>().get()).get(e.index()) }.is_some());
+            assert!(world.contains(e));
             assert!(
                 unsafe { (*world.get_storage::<TestComponent>().get()).get(e.index()) }.is_some()
             );
         } else {
-            assert!(unsafe { (*world.get_storage::<Entity>().get()).get(e.index()) }.is_none());
+            assert!(!world.contains(e));
             assert!(
                 unsafe { (*world.get_storage::<TestComponent>().get()).get(e.index()) }.is_none()
             );
@@ -2112,11 +2115,11 @@ fn test_component_cleanup_and_destroy_system_together() {
 
     // Verify entities were removed by DestroySystem
     assert_eq!(unsafe { (*world.get_storage::<Entity>().get()).len() }, 3);
-    assert!(unsafe { (*world.get_storage::<Entity>().get()).get(e1.index()) }.is_some());
-    assert!(unsafe { (*world.get_storage::<Entity>().get()).get(e2.index()) }.is_none());
-    assert!(unsafe { (*world.get_storage::<Entity>().get()).get(e3.index()) }.is_some());
-    assert!(unsafe { (*world.get_storage::<Entity>().get()).get(e4.index()) }.is_none());
-    assert!(unsafe { (*world.get_storage::<Entity>().get()).get(e5.index()) }.is_some());
+    assert!(world.contains(e1));
+    assert!(!world.contains(e2));
+    assert!(world.contains(e3));
+    assert!(!world.contains(e4));
+    assert!(world.contains(e5));
 
     // Destroyed components are removed by DestroySystem
     assert_eq!(
@@ -2220,7 +2223,7 @@ fn test_component_cleanup_system_with_multiple_component_types() {
 
     // Verify e2 is removed
     assert_eq!(unsafe { (*world.get_storage::<Entity>().get()).len() }, 2);
-    assert!(unsafe { (*world.get_storage::<Entity>().get()).get(e2.index()) }.is_none());
+    assert!(!world.contains(e2));
 
     // Verify final invariants
     verify_storage_invariants(unsafe { &*world.get_storage::<Entity>().get() }).unwrap();
@@ -2298,9 +2301,9 @@ fn test_destroy_system_removes_from_both_storages_when_has_both() {
 
     // Verify entities exist in Entity storage
     assert_eq!(unsafe { (*world.get_storage::<Entity>().get()).len() }, 3);
-    assert!(unsafe { (*world.get_storage::<Entity>().get()).get(e1.index()) }.is_some());
-    assert!(unsafe { (*world.get_storage::<Entity>().get()).get(e2.index()) }.is_some());
-    assert!(unsafe { (*world.get_storage::<Entity>().get()).get(e3.index()) }.is_some());
+    assert!(world.contains(e1));
+    assert!(world.contains(e2));
+    assert!(world.contains(e3));
 
     // Mark e2 and e3 as destroyed (adds Destroyed component)
     world.destroy(e2);
@@ -2312,8 +2315,8 @@ fn test_destroy_system_removes_from_both_storages_when_has_both() {
         unsafe { (*world.get_storage::<Destroyed>().get()).len() },
         2
     );
-    assert!(unsafe { (*world.get_storage::<Entity>().get()).get(e2.index()) }.is_some());
-    assert!(unsafe { (*world.get_storage::<Entity>().get()).get(e3.index()) }.is_some());
+    assert!(world.contains(e2));
+    assert!(world.contains(e3));
     assert!(unsafe { (*world.get_storage::<Destroyed>().get()).get(e2.index()) }.is_some());
     assert!(
         unsafe { &*world.get_storage::<Destroyed>().get() }
@@ -2342,11 +2345,11 @@ fn test_destroy_system_removes_from_both_storages_when_has_both() {
     );
 
     // e1 should still exist in Entity storage
-    assert!(unsafe { (*world.get_storage::<Entity>().get()).get(e1.index()) }.is_some());
+    assert!(world.contains(e1));
 
     // e2 and e3 should be removed from Entity storage
-    assert!(unsafe { (*world.get_storage::<Entity>().get()).get(e2.index()) }.is_none());
-    assert!(unsafe { (*world.get_storage::<Entity>().get()).get(e3.index()) }.is_none());
+    assert!(!world.contains(e2));
+    assert!(!world.contains(e3));
 
     // e2 and e3 should be removed from Destroyed storage
     assert!(
@@ -4904,3 +4907,1020 @@ fn test_rollback_10k_entities_50_ticks_minimal_clone_drop() {
         "World should be at tick 0 after rollback"
     );
 }
+
+#[test]
+fn test_rollback_window_clamped_by_max_depth() {
+    let mut world = World::new();
+    world.spawn();
+    world.build_scheduler();
+
+    world.set_max_rollback_depth(Some(2));
+    for _ in 0..5 {
+        world.run();
+    }
+
+    let window = world.rollback_window();
+    assert_eq!(window.oldest, Tick::new(3));
+    assert_eq!(window.newest, Tick::new(5));
+    assert_eq!(window.depth(), 2);
+    assert!(window.contains(Tick::new(3)));
+    assert!(!window.contains(Tick::new(2)));
+
+    world.set_max_rollback_depth(None);
+    assert_eq!(world.rollback_window().oldest, Tick::new(0));
+}
+
+#[test]
+#[should_panic(expected = "oldest retained tick is 3")]
+fn test_rollback_overflow_without_handler_panics() {
+    let mut world = World::new();
+    world.spawn();
+    world.build_scheduler();
+
+    world.set_max_rollback_depth(Some(2));
+    for _ in 0..5 {
+        world.run();
+    }
+
+    world.rollback(Tick::new(1));
+}
+
+#[test]
+fn test_rollback_to_future_tick_is_not_applied() {
+    let mut world = World::new();
+    let e = world.spawn();
+    world.build_scheduler();
+
+    for i in 0..3 {
+        world.set(e, &TestComponent { value: i });
+        world.run();
+    }
+
+    let report = world.rollback(Tick::new(9));
+    assert!(!report.applied);
+    assert_eq!(report.target, Tick::new(3));
+    assert_eq!(world.current_tick(), Tick::new(3));
+    assert_eq!(world.get::<TestComponent>(e), Some(&TestComponent { value: 2 }));
+}
+
+#[test]
+fn test_rollback_overflow_handler_ignore_and_clamp() {
+    use crate::rollback::{OverflowAction, RollbackOverflow};
+    use std::cell::RefCell;
+
+    let mut world = World::new();
+    let e = world.spawn();
+    let storage = world.get_storage::<TestComponent>();
+    world.build_scheduler();
+    world.set_max_rollback_depth(Some(2));
+
+    for i in 0..5 {
+        world.set(e, &TestComponent { value: i });
+        world.run();
+    }
+
+    let seen: Rc<RefCell<Vec<RollbackOverflow>>> = Rc::new(RefCell::new(Vec::new()));
+    let seen_clone = seen.clone();
+    world.on_rollback_overflow(move |overflow| {
+        seen_clone.borrow_mut().push(*overflow);
+        OverflowAction::Ignore
+    });
+
+    world.rollback(Tick::new(1));
+
+    assert_eq!(seen.borrow().len(), 1);
+    assert_eq!(seen.borrow()[0].requested, Tick::new(1));
+    assert_eq!(seen.borrow()[0].window.oldest, Tick::new(3));
+    // Ignore leaves the world untouched
+    assert_eq!(world.current_tick(), Tick::new(5));
+    assert_eq!(unsafe { (*storage.get()).get(e.index()).unwrap().value }, 4);
+
+    world.on_rollback_overflow(|_| OverflowAction::Clamp);
+    world.rollback(Tick::new(0));
+
+    // Clamp restores the oldest tick still inside the window
+    assert_eq!(world.current_tick(), Tick::new(3));
+    assert_eq!(unsafe { (*storage.get()).get(e.index()).unwrap().value }, 3);
+}
+
+#[derive(Component, Clone, Default, PartialEq, Debug)]
+struct LateComponent {
+    value: u32,
+}
+
+#[test]
+fn test_world_phase_idle_between_ticks() {
+    use crate::phase::WorldPhase;
+
+    let mut world = World::new();
+    world.build_scheduler();
+    assert_eq!(world.phase(), WorldPhase::Idle);
+
+    world.run();
+    assert_eq!(world.phase(), WorldPhase::Idle);
+
+    world.rollback(Tick::new(0));
+    assert_eq!(world.phase(), WorldPhase::Idle);
+}
+
+#[cfg(debug_assertions)]
+#[test]
+#[should_panic(expected = "World::set called while the world is Simulating")]
+fn test_world_mutation_outside_idle_panics() {
+    use crate::phase::WorldPhase;
+
+    let mut world = World::new();
+    let e = world.spawn();
+    world.phase = WorldPhase::Simulating;
+    world.set(e, &LateComponent { value: 1 });
+}
+
+#[test]
+fn test_edit_scope_changes_roll_back_at_current_tick() {
+    let mut world = World::new();
+    world.build_scheduler();
+    let e = world.spawn();
+
+    for _ in 0..5 {
+        world.run();
+    }
+
+    // First use of the component type happens mid-game, inside the scope
+    let tick = world.edit_scope(|w| {
+        w.set(e, &LateComponent { value: 7 });
+        w.current_tick()
+    });
+    assert_eq!(tick, Tick::new(5));
+    world.run();
+
+    let storage = world.get_storage::<LateComponent>();
+    world.rollback(Tick::new(5));
+    assert_eq!(
+        unsafe { (*storage.get()).get(e.index()) },
+        Some(&LateComponent { value: 7 })
+    );
+
+    // The component didn't exist before tick 5
+    world.rollback(Tick::new(4));
+    assert_eq!(unsafe { (*storage.get()).get(e.index()) }, None);
+}
+
+#[derive(Component, Clone, Default, PartialEq, Debug)]
+struct Steps {
+    count: u32,
+}
+
+system! {
+    StepSystem {
+        query! {
+            fn step(steps: &mut ViewMut<Steps>) {
+                steps.count += 1;
+            }
+        }
+    }
+}
+
+#[test]
+fn test_resimulate_from_replays_tick_once() {
+    let mut world = World::new();
+    world.add_system::<StepSystem>();
+    world.build_scheduler();
+    let e = world.spawn();
+    world.set(e, &Steps { count: 0 });
+    let storage = world.get_storage::<Steps>();
+    let count = || unsafe { (*storage.get()).get(e.index()).unwrap().count };
+
+    for _ in 0..5 {
+        world.run();
+    }
+    assert_eq!(count(), 5);
+
+    world.resimulate_from(Tick::new(2));
+    assert_eq!(world.current_tick(), Tick::new(2));
+    assert_eq!(count(), 2);
+
+    // Input changed for tick 2, then ticks 2..5 are simulated again
+    world.set(e, &Steps { count: 10 });
+    for _ in 0..3 {
+        world.run();
+    }
+    assert_eq!(count(), 13);
+
+    // Resimulating the same tick twice undoes the first resimulation completely
+    world.resimulate_from(Tick::new(2));
+    assert_eq!(count(), 2);
+}
+
+#[derive(Component, Clone, Default, PartialEq, Debug)]
+struct Fuse {
+    left: u32,
+}
+
+fn build_registered_world() -> World {
+    let mut world = World::new();
+    world.add_system::<StepSystem>();
+    world.enable_ttl::<Fuse>();
+    world.enable_ttl::<Steps>();
+    let _ = world.ingest::<Steps>();
+    let _ = world.mailbox::<u32, StepSystem>();
+    world.build_scheduler();
+    world
+}
+
+fn schedule_names(world: &World) -> Vec<Vec<String>> {
+    let scheduler = world.scheduler().unwrap();
+    let systems: Vec<_> = scheduler.systems().collect();
+    scheduler
+        .wavefronts()
+        .iter()
+        .map(|wave| wave.iter().map(|&i| systems[i].name().to_string()).collect())
+        .collect()
+}
+
+#[test]
+fn test_identical_registrations_build_identical_worlds() {
+    let a = build_registered_world();
+    let b = build_registered_world();
+
+    assert_eq!(schedule_names(&a), schedule_names(&b));
+
+    let storages = |world: &World| -> Vec<&'static str> {
+        world.memory_stats().into_iter().map(|(name, _)| name).collect()
+    };
+    assert_eq!(storages(&a), storages(&b));
+    assert_eq!(a.component_graph(), b.component_graph());
+
+    // Per-type tables iterate in registration order
+    let expiries: Vec<_> = a.expiries.iter().map(|(id, _)| *id).collect();
+    assert_eq!(
+        expiries,
+        [
+            std::any::TypeId::of::<Fuse>(),
+            std::any::TypeId::of::<Steps>()
+        ]
+    );
+}
+
+#[derive(Component, Clone, Default, PartialEq, Debug)]
+struct Residual {
+    value: i32,
+    iterations: u32,
+}
+
+#[derive(Component, Clone, Default, PartialEq, Debug)]
+struct Settled {
+    iterations: u32,
+}
+
+#[rollback_macros::pipeline_group]
+struct SolverGroup;
+
+system! {
+    RelaxSystem {
+        query! {
+            fn relax(residual: &mut ViewMut<Residual>) Parent=SolverGroup {
+                residual.value /= 2;
+                residual.iterations += 1;
+            }
+        }
+    }
+}
+
+system! {
+    SettleSystem {
+        query! {
+            fn settle(residual: View<Residual>, settled: &mut ViewMut<Settled>) {
+                settled.iterations = residual.iterations;
+            }
+        }
+    }
+}
+
+fn converged(world: &World) -> bool {
+    world
+        .storage_ref::<Residual>()
+        .is_some_and(|s| s.iter().all(|(_, r)| r.value == 0))
+}
+
+fn build_solver_world(max_iters: u32) -> (World, Entity) {
+    let mut world = World::new();
+    world.add_loop_group::<SolverGroup>(crate::scheduler::LoopGroup::run_until(
+        converged, max_iters,
+    ));
+    world.add_system::<SettleSystem>();
+    world.add_system::<RelaxSystem>();
+    world.build_scheduler();
+
+    let e = world.spawn();
+    world.set(e, &Residual { value: 100, iterations: 0 });
+    world.set(e, &Settled::default());
+    (world, e)
+}
+
+#[test]
+fn test_loop_group_runs_until_converged() {
+    let (mut world, e) = build_solver_world(16);
+    let scheduler = world.scheduler().unwrap();
+    let loop_index = scheduler.len();
+    assert!(scheduler.wavefronts().contains(&vec![loop_index]));
+
+    world.run();
+
+    // 100 -> 50 -> 25 -> 12 -> 6 -> 3 -> 1 -> 0
+    let residual = world.get_storage::<Residual>();
+    let settled = world.get_storage::<Settled>();
+    unsafe {
+        assert_eq!((*residual.get()).get(e.index()).unwrap().iterations, 7);
+        assert_eq!((*settled.get()).get(e.index()).unwrap().iterations, 7);
+    }
+
+    // Already converged: one iteration, then the test passes
+    world.run_sequential();
+    unsafe { assert_eq!((*settled.get()).get(e.index()).unwrap().iterations, 8) };
+}
+
+#[test]
+fn test_loop_group_stops_at_max_iters_and_resimulates() {
+    let (mut world, e) = build_solver_world(3);
+    let residual = world.get_storage::<Residual>();
+    let get = || unsafe { (*residual.get()).get(e.index()).cloned().unwrap() };
+
+    world.run();
+    assert_eq!(get(), Residual { value: 12, iterations: 3 });
+    world.run();
+    assert_eq!(get(), Residual { value: 1, iterations: 6 });
+
+    world.resimulate_from(Tick::new(1));
+    assert_eq!(get(), Residual { value: 12, iterations: 3 });
+    world.run();
+    assert_eq!(get(), Residual { value: 1, iterations: 6 });
+}
+
+#[test]
+fn test_rollback_despawns_entities_spawned_after_target() {
+    let mut world = World::new();
+    world.get_storage::<Score>();
+    let a = world.spawn();
+    world.set(a, &Health { value: 1 });
+    world.build_scheduler();
+    world.run();
+
+    let target = world.current_tick();
+    world.run();
+    let b = world.spawn();
+    world.set(b, &Health { value: 2 });
+    world.set(b, &Score { points: 3 });
+    world.run();
+    let c = world.spawn();
+    world.set(c, &Score { points: 4 });
+    world.run();
+
+    let report = world.rollback(target);
+    assert!(report.applied);
+    assert_eq!(report.target, target);
+    assert_eq!(report.despawned, vec![b, c]);
+    assert!(report.restored.is_empty());
+
+    let entities = world.storage_ref::<Entity>().unwrap();
+    let alive: Vec<Entity> = entities.iter().map(|(_, e)| *e).collect();
+    assert_eq!(alive, vec![a]);
+    let health = world.storage_ref::<Health>().unwrap();
+    let with_health: Vec<u32> = health.iter().map(|(i, _)| i).collect();
+    assert_eq!(with_health, vec![a.index()]);
+    assert_eq!(world.storage_ref::<Score>().unwrap().iter().count(), 0);
+    verify_storage_invariants(world.storage_ref::<Score>().unwrap()).unwrap();
+
+    // Resimulating the same spawns hands out the same entities again
+    world.run();
+    assert_eq!(world.spawn(), b);
+    world.run();
+    assert_eq!(world.spawn(), c);
+}
+
+#[test]
+fn test_rollback_report_when_overflow_is_ignored() {
+    use crate::rollback::OverflowAction;
+
+    let mut world = World::new();
+    world.set_max_rollback_depth(Some(1));
+    world.on_rollback_overflow(|_| OverflowAction::Ignore);
+    world.get_storage::<Entity>();
+    world.build_scheduler();
+    for _ in 0..3 {
+        world.run();
+    }
+    let e = world.spawn();
+    world.run();
+
+    let report = world.rollback(Tick::new(0));
+    assert!(!report.applied);
+    assert_eq!(report.target, world.current_tick());
+    assert!(report.despawned.is_empty());
+    let entities = world.storage_ref::<Entity>().unwrap();
+    assert!(entities.get(e.index()).is_some());
+}
+
+#[test]
+fn test_destroy_matching_marks_filtered_entities() {
+    let mut world = World::new();
+    world.get_storage::<Health>();
+    world.get_storage::<Score>();
+    world.get_storage::<Destroyed>();
+    world.build_scheduler();
+
+    let mut entities = Vec::new();
+    for i in 0..300 {
+        let e = world.spawn();
+        world.set(e, &Health { value: i % 3 });
+        if i % 2 == 0 {
+            world.set(e, &Score { points: 1 });
+        }
+        entities.push(e);
+    }
+    world.run();
+
+    let health = |world: &World, e: Entity| {
+        let storage = world.storage_ref::<Health>().unwrap();
+        storage.get(e.index()).unwrap().value
+    };
+    let marked = |world: &World| {
+        let destroyed = world.storage_ref::<Destroyed>().unwrap();
+        destroyed.iter().map(|(i, _)| i).collect::<Vec<_>>()
+    };
+    let expected: Vec<u32> = (0..300).filter(|i| i % 6 == 0).collect();
+
+    let count = world.destroy_matching::<(Health, Score)>(|w, e| health(w, e) == 0);
+    assert_eq!(count, 50);
+    assert_eq!(marked(&world), expected);
+
+    // Already marked entities aren't counted twice
+    assert_eq!(world.destroy_matching::<(Health, Score)>(|_, _| true), 100);
+    assert_eq!(marked(&world).len(), 150);
+
+    world.run();
+    let alive = world.storage_ref::<Entity>().unwrap();
+    assert_eq!(alive.iter().count(), 150);
+    assert!(alive.get(entities[6].index()).is_none());
+    assert_eq!(alive.get(entities[1].index()), Some(&entities[1]));
+    assert!(world.storage_ref::<Score>().unwrap().iter().next().is_none());
+}
+
+#[test]
+fn test_world_tick_rate_change_survives_rollback_and_late_join() {
+    use crate::tickrate::TickRateLog;
+
+    let mut world = World::new();
+    world.get_storage::<Health>();
+    world.build_scheduler();
+    world.set_tick_rate(30);
+    world.run();
+    world.run();
+
+    let change = world.rescale_tick_rate(60, Tick::new(4));
+    assert_eq!(change.at, Tick::new(4));
+    let mut rates = Vec::new();
+    for _ in 0..4 {
+        rates.push(world.tick_rate());
+        world.run();
+    }
+    assert_eq!(rates, vec![30, 30, 60, 60]);
+    assert_eq!(
+        world.tick_duration(),
+        std::time::Duration::from_secs(1) / 60
+    );
+
+    // Rollback keeps the change point, so resimulated ticks see the same rates
+    world.rollback(Tick::new(3));
+    assert_eq!(world.current_tick(), Tick::new(3));
+    assert_eq!(world.tick_rate(), 30);
+    world.run();
+    assert_eq!(world.tick_rate(), 60);
+
+    // A late joiner loading the log switches at the same tick
+    let bytes = world.tick_rate_log().to_bytes();
+    let mut joiner = World::new();
+    joiner.load_tick_rate_log(TickRateLog::from_bytes(&bytes).unwrap());
+    assert_eq!(joiner.tick_rate(), 30);
+    assert_eq!(joiner.tick_rate_log(), world.tick_rate_log());
+}
+
+#[test]
+#[should_panic(expected = "already been simulated")]
+fn test_world_tick_rate_change_in_the_past_panics() {
+    let mut world = World::new();
+    world.build_scheduler();
+    world.run();
+    world.run();
+    world.rescale_tick_rate(60, Tick::new(1));
+}
+
+#[test]
+fn test_ensure_all_registered_creates_every_derived_storage() {
+    let registered = crate::component::registered_components();
+    let ids: Vec<u64> = registered.iter().map(|r| (r.component_id)()).collect();
+    assert!(ids.windows(2).all(|w| w[0] <= w[1]));
+    assert!(
+        registered
+            .iter()
+            .any(|r| (r.type_name)() == std::any::type_name::<Health>())
+    );
+
+    let mut world = World::new();
+    assert!(world.storage::<Score>().is_none());
+    world.ensure_all_registered();
+    world.build_scheduler();
+    assert!(world.storage::<Health>().is_some());
+    assert!(world.storage::<Score>().is_some());
+    world.run();
+}
+
+#[test]
+fn test_respawn_bumps_generation_and_rolls_back() {
+    let mut world = World::new();
+    world.get_storage::<Health>();
+    world.build_scheduler();
+    let a = world.spawn();
+    world.set(a, &Health { value: 1 });
+    world.run();
+    let before_destroy = world.current_tick();
+
+    world.destroy(a);
+    world.run();
+    let b = world.spawn();
+    assert_eq!(b.index(), a.index());
+    assert_ne!(b.generation(), a.generation());
+
+    // The stale handle doesn't alias the new occupant
+    world.set(b, &Health { value: 2 });
+    assert!(world.get::<Health>(a).is_none());
+    assert!(world.get_mut::<Health>(a).is_none());
+    world.run();
+
+    // Rolling back past the destroy brings the old occupant back, and resimulating
+    // hands out the same generation again
+    let report = world.rollback(Tick::new(before_destroy.value() - 1));
+    assert_eq!(report.restored, vec![a]);
+    assert_eq!(report.despawned, vec![b]);
+    assert_eq!(world.get::<Entity>(a), Some(&a));
+    world.run();
+    world.destroy(a);
+    world.run();
+    assert_eq!(world.spawn(), b);
+}
+
+#[test]
+fn test_remove_returns_value_and_rolls_back() {
+    let mut world = World::new();
+    world.get_storage::<Health>();
+    world.build_scheduler();
+    let a = world.spawn();
+    world.set(a, &Health { value: 7 });
+    world.run();
+    let before_remove = world.current_tick();
+
+    assert_eq!(world.remove::<Health>(a), Some(Health { value: 7 }));
+    assert_eq!(world.get::<Health>(a), None);
+    assert_eq!(world.remove::<Health>(a), None);
+    world.run();
+    let removed = world.storage_ref::<Health>().unwrap();
+    assert_eq!(removed.removed_value(a.index()), Some(&Health { value: 7 }));
+
+    world.rollback(Tick::new(before_remove.value() - 1));
+    assert_eq!(world.get::<Health>(a), Some(&Health { value: 7 }));
+
+    // Stale handles remove nothing
+    world.destroy(a);
+    world.run();
+    let b = world.spawn();
+    world.set(b, &Health { value: 1 });
+    assert_eq!(world.remove::<Health>(a), None);
+    assert_eq!(world.get::<Health>(b), Some(&Health { value: 1 }));
+}
+
+#[test]
+fn test_tick_hooks_run_around_every_tick_in_registration_order() {
+    use crate::phase::WorldPhase;
+    use std::cell::RefCell;
+
+    let mut world = World::new();
+    world.get_storage::<Health>();
+    world.build_scheduler();
+    let e = world.spawn();
+    world.set(e, &Health { value: 7 });
+
+    let log = Rc::new(RefCell::new(Vec::new()));
+    for (name, end) in [("start a", false), ("end a", true), ("start b", false)] {
+        let log = log.clone();
+        let hook = move |world: &World, tick: Tick| {
+            assert_eq!(world.phase(), WorldPhase::RunningHooks);
+            log.borrow_mut().push((name, tick.value()));
+        };
+        if end {
+            world.on_tick_end(hook);
+        } else {
+            world.on_tick_start(hook);
+        }
+    }
+    let seen = log.clone();
+    world.on_tick_end(move |world, _| {
+        seen.borrow_mut()
+            .push(("health", world.get::<Health>(e).unwrap().value as u32));
+    });
+
+    world.run();
+    world.run_sequential();
+    assert_eq!(world.phase(), WorldPhase::Idle);
+    assert_eq!(
+        *log.borrow(),
+        vec![
+            ("start a", 0),
+            ("start b", 0),
+            ("end a", 0),
+            ("health", 7),
+            ("start a", 1),
+            ("start b", 1),
+            ("end a", 1),
+            ("health", 7),
+        ]
+    );
+
+    world.clear_tick_hooks();
+    world.run();
+    assert_eq!(log.borrow().len(), 8);
+}
+
+#[test]
+fn test_iter_entities_and_contains() {
+    let mut world = World::new();
+    assert_eq!(world.iter_entities().count(), 0);
+
+    let a = world.spawn();
+    let b = world.spawn();
+    let c = world.spawn();
+    world.build_scheduler();
+    world.destroy(b);
+
+    // Marked entities are listed until the destroy system runs
+    assert_eq!(world.iter_entities().collect::<Vec<_>>(), vec![a, b, c]);
+    world.run();
+    assert_eq!(world.iter_entities().collect::<Vec<_>>(), vec![a, c]);
+
+    assert!(world.contains(a));
+    assert!(!world.contains(b));
+    // A handle from another generation of the same slot isn't alive
+    assert!(!world.contains(Entity::new(a.index(), a.generation() + 1)));
+}
+
+#[test]
+fn test_step_without_scheduler_advances_storages() {
+    let mut world = World::new();
+    let e = world.spawn();
+    world.set(e, &TestComponent { value: 100 });
+
+    assert_eq!(world.step(), Tick::new(0));
+    assert_eq!(world.current_tick(), Tick::new(1));
+    let storage = world.get_storage::<TestComponent>();
+    assert_eq!(unsafe { (*storage.get()).root_changed_mask() }, 0);
+
+    world.set(e, &TestComponent { value: 200 });
+    world.step();
+    world.rollback(Tick::new(0));
+    assert_eq!(world.get::<TestComponent>(e).unwrap().value, 100);
+}
+
+system! {
+    StepCounterSystem {
+        query! {
+            fn count_steps(counter: &mut ViewMut<TestComponent>) {
+                counter.value += 1;
+            }
+        }
+    }
+}
+
+#[test]
+fn test_run_for_and_advance_drive_the_scheduler() {
+    let mut world = World::new();
+    world.add_system::<StepCounterSystem>();
+    world.build_scheduler();
+    let e = world.spawn();
+    world.set(e, &TestComponent { value: 0 });
+    world.set_tick_rate(10);
+
+    world.run_for(3);
+    assert_eq!(world.current_tick(), Tick::new(3));
+
+    // 100ms ticks: 250ms runs two and carries the rest over
+    assert_eq!(world.advance(Duration::from_millis(250)), 2);
+    assert_eq!(world.accumulated(), Duration::from_millis(50));
+    assert_eq!(world.step_alpha(), 0.5);
+    assert_eq!(world.advance(Duration::from_millis(40)), 0);
+    assert_eq!(world.advance(Duration::from_millis(10)), 1);
+    assert_eq!(world.get::<TestComponent>(e).unwrap().value, 6);
+
+    // Rate changes shorten the ticks that follow
+    let now = world.current_tick();
+    world.rescale_tick_rate(20, now);
+    assert_eq!(world.advance(Duration::from_millis(100)), 2);
+    assert_eq!(world.accumulated(), Duration::ZERO);
+}
+
+#[test]
+fn test_history_len_prunes_old_snapshots() {
+    let mut world = World::new();
+    let e = world.spawn();
+    world.get_storage::<TestComponent>();
+    world.build_scheduler();
+    world.set_history_len(4);
+
+    for i in 0..50 {
+        world.set(e, &TestComponent { value: i });
+        world.run();
+    }
+    assert_eq!(world.rollback_window().oldest, Tick::new(46));
+    assert_eq!(world.history_memory::<TestComponent>().blocks, 4);
+
+    world.rollback(Tick::new(46));
+    assert_eq!(world.get::<TestComponent>(e).unwrap().value, 46);
+
+    // Pruned ticks stay out of reach once the limit is lifted; tick 49 pruned up to 45
+    world.set_rollback_config(RollbackConfig::default());
+    assert_eq!(world.rollback_window().oldest, Tick::new(45));
+}
+
+#[test]
+fn test_confirm_marks_ticks_final() {
+    let mut world = World::new();
+    let e = world.spawn();
+    world.get_storage::<TestComponent>();
+    world.build_scheduler();
+    world.set_rollback_config(RollbackConfig {
+        history_len: None,
+        prune_interval: 4,
+    });
+
+    for i in 0..10 {
+        world.set(e, &TestComponent { value: i });
+        world.run();
+    }
+    world.confirm(Tick::new(6));
+    world.confirm(Tick::new(3));
+    assert_eq!(world.rollback_window().oldest, Tick::new(6));
+
+    // Tick 10 isn't a pruning tick
+    world.run();
+    assert_eq!(world.history_memory::<TestComponent>().blocks, 10);
+    world.prune_history();
+    assert_eq!(world.history_memory::<TestComponent>().blocks, 3);
+
+    world.rollback(Tick::new(6));
+    assert_eq!(world.get::<TestComponent>(e).unwrap().value, 6);
+}
+
+#[test]
+#[should_panic(expected = "Cannot confirm tick 3")]
+fn test_confirm_future_tick_panics() {
+    let mut world = World::new();
+    world.confirm(Tick::new(3));
+}
+
+system! {
+    ClearBumpSystem {
+        query! {
+            fn bump(value: &mut ViewMut<TestComponent>) {
+                value.value += 1;
+            }
+        }
+    }
+}
+
+#[derive(Clone, Default, PartialEq, Debug)]
+struct MatchesPlayed(u32);
+
+fn clearable_world() -> World {
+    let mut world = World::new();
+    world.get_storage::<TestComponent>();
+    world.add_system::<ClearBumpSystem>();
+    world.build_scheduler();
+    world.insert_resource(MatchesPlayed(0));
+    world
+}
+
+#[test]
+fn test_clear_entities_keeps_systems_and_resources() {
+    let mut world = clearable_world();
+    let old = world.spawn();
+    world.set(old, &TestComponent { value: 0 });
+    for _ in 0..5 {
+        world.run();
+    }
+    world.resource_mut::<MatchesPlayed>().unwrap().0 = 1;
+
+    world.clear_entities();
+    assert_eq!(world.iter_entities().count(), 0);
+    assert!(!world.contains(old));
+    assert_eq!(world.rollback_window().oldest, world.current_tick());
+    assert_eq!(world.resource::<MatchesPlayed>(), Some(&MatchesPlayed(1)));
+
+    // The scheduler still runs the same systems, and old handles stay stale
+    let e = world.spawn();
+    assert_eq!(e.index(), old.index());
+    assert_ne!(e, old);
+    assert!(!world.contains(old));
+    world.set(e, &TestComponent { value: 0 });
+    let t = world.current_tick();
+    world.run();
+    world.run();
+    assert_eq!(world.get::<TestComponent>(e), Some(&TestComponent { value: 2 }));
+
+    world.rollback(t);
+    assert_eq!(world.get::<TestComponent>(e), Some(&TestComponent { value: 1 }));
+}
+
+#[test]
+fn test_reset_to_tick_zero_matches_a_new_world() {
+    let mut world = clearable_world();
+    for _ in 0..3 {
+        let e = world.spawn();
+        world.set(e, &TestComponent { value: 7 });
+        world.destroy(e);
+        world.run();
+    }
+
+    world.reset_to_tick_zero();
+    assert_eq!(world.current_tick(), Tick::new(0));
+    let mut fresh = clearable_world();
+    for w in [&mut world, &mut fresh] {
+        let e = w.spawn();
+        w.set(e, &TestComponent { value: 0 });
+        w.run();
+        w.run();
+    }
+    assert_eq!(world.current_tick(), fresh.current_tick());
+    assert_eq!(
+        world.iter_entities().collect::<Vec<_>>(),
+        fresh.iter_entities().collect::<Vec<_>>()
+    );
+    assert_eq!(world.compute_state_hash(), fresh.compute_state_hash());
+}
+
+#[derive(Component, Clone, Default, PartialEq, Debug)]
+struct Strides(u32);
+
+#[derive(Clone, Default)]
+struct Paused(bool);
+
+struct Unpaused;
+
+impl crate::scheduler::RunCondition for Unpaused {
+    fn should_run(world: &World) -> bool {
+        world.resource::<Paused>().is_none_or(|paused| !paused.0)
+    }
+}
+
+system! {
+    StrideSystem {
+        query! {
+            fn stride(strides: &mut ViewMut<Strides>) RunIf = Unpaused {
+                strides.0 += 1;
+            }
+        }
+    }
+}
+
+system! {
+    DebugStrideSystem {
+        query! {
+            fn debug_stride(strides: &mut ViewMut<Strides>) After=[StrideSystem] {
+                strides.0 += 100;
+            }
+        }
+    }
+}
+
+#[test]
+fn test_run_if_skips_system_while_condition_fails() {
+    let mut world = World::new();
+    world.add_system::<StrideSystem>();
+    world.build_scheduler();
+    world.insert_resource(Paused(false));
+    let e = world.spawn();
+    world.set(e, &Strides(0));
+
+    world.run();
+    world.resource_mut::<Paused>().unwrap().0 = true;
+    let paused = world.current_tick();
+    world.run();
+    world.run();
+    assert_eq!(world.get::<Strides>(e), Some(&Strides(1)));
+
+    world.resource_mut::<Paused>().unwrap().0 = false;
+    world.run();
+    assert_eq!(world.get::<Strides>(e), Some(&Strides(2)));
+
+    // Resimulated ticks see the rolled back resource and skip the same runs
+    world.rollback(paused);
+    assert_eq!(world.get::<Strides>(e), Some(&Strides(1)));
+    assert!(world.resource::<Paused>().unwrap().0);
+}
+
+#[test]
+fn test_set_system_enabled_skips_system() {
+    let mut world = World::new();
+    world.add_system::<StrideSystem>();
+    world.add_system::<DebugStrideSystem>();
+    // Switched off before the build, so the new scheduler picks it up
+    world.set_system_enabled::<DebugStrideSystem>(false);
+    world.build_scheduler();
+    let e = world.spawn();
+    world.set(e, &Strides(0));
+
+    world.run();
+    assert!(!world.is_system_enabled::<DebugStrideSystem>());
+    assert_eq!(world.get::<Strides>(e), Some(&Strides(1)));
+
+    world.set_system_enabled::<DebugStrideSystem>(true);
+    world.set_system_enabled::<StrideSystem>(false);
+    world.run();
+    assert_eq!(world.get::<Strides>(e), Some(&Strides(101)));
+    assert!(world.scheduler().unwrap().is_enabled(TypeId::of::<DebugStrideSystem>()));
+    assert!(!world.scheduler().unwrap().is_enabled(TypeId::of::<StrideSystem>()));
+}
+
+#[test]
+fn test_add_system_dynamic_after_build() {
+    let mut world = World::new();
+    world.add_system::<DebugStrideSystem>();
+    world.build_scheduler();
+    let e = world.spawn();
+    world.set(e, &Strides(0));
+    world.run();
+    assert_eq!(world.get::<Strides>(e), Some(&Strides(100)));
+
+    world.add_system_dynamic::<StrideSystem>();
+    assert!(world.scheduler().is_none());
+    world.run();
+    // The rebuilt schedule orders the new system before `DebugStrideSystem`
+    assert_eq!(world.get::<Strides>(e), Some(&Strides(201)));
+    let scheduler = world.scheduler().unwrap();
+    let strides = [TypeId::of::<StrideSystem>(), TypeId::of::<DebugStrideSystem>()];
+    let order: Vec<_> = scheduler
+        .stage_wavefronts()
+        .concat()
+        .into_iter()
+        .map(|i| scheduler.systems().nth(i).unwrap().type_id())
+        .filter(|id| strides.contains(id))
+        .collect();
+    assert_eq!(order, strides);
+}
+
+#[test]
+fn test_remove_system_after_build() {
+    let mut world = World::new();
+    world.add_system::<StrideSystem>();
+    world.add_system::<DebugStrideSystem>();
+    world.build_scheduler();
+    let e = world.spawn();
+    world.set(e, &Strides(0));
+    world.run();
+    let systems = world.scheduler().unwrap().len();
+
+    assert!(world.remove_system::<DebugStrideSystem>());
+    assert!(!world.remove_system::<DebugStrideSystem>());
+    world.step();
+    assert_eq!(world.get::<Strides>(e), Some(&Strides(102)));
+    assert_eq!(world.scheduler().unwrap().len(), systems - 1);
+
+    // Cleanup still runs: a destroyed entity is gone at the end of the tick
+    world.destroy(e);
+    world.run();
+    assert_eq!(world.get::<Strides>(e), None);
+}
+
+#[test]
+fn test_compact_frees_blocks_after_mass_despawn() {
+    let mut world = World::new();
+    let entities: Vec<Entity> = (0..5_000).map(|_| world.spawn()).collect();
+    for &e in &entities {
+        world.set(e, &TestComponent { value: 1 });
+    }
+    world.build_scheduler();
+    world.set_history_len(1);
+    world.run();
+
+    for &e in &entities[1..] {
+        world.destroy(e);
+    }
+    world.run();
+    // The despawn is still in the history, so its blocks stay
+    assert_eq!(world.compact(), 0);
+
+    world.run();
+    world.run();
+    world.prune_history();
+    assert!(world.compact() > 0);
+    assert_eq!(world.get::<TestComponent>(entities[0]), Some(&TestComponent { value: 1 }));
+    let stats = world.get_storage::<TestComponent>();
+    let stats = unsafe { (*stats.get()).memory_stats() };
+    assert_eq!((stats.middle_blocks, stats.inner_blocks), (1, 1));
+}
@@ -0,0 +1,311 @@
+//! Several worlds (zones) advanced by one host, with cross-zone messaging and entity
+//! migration.
+//!
+//! Server-side zone architectures split the map into independent `World`s. A
+//! `MultiWorldHost` owns them, keyed by `ZoneId`, and advances them together. Zones share
+//! no state, so with the `parallel` feature `advance_parallel()` runs them concurrently.
+//!
+//! Zones talk through typed channels. A system (or the game loop) sends with a
+//! `ZoneSender<M>` obtained from `World::zone_sender_from` (or `World::zone_sender`). After every zone has finished its
+//! tick the host collects the messages, orders them by source zone and then by the
+//! sender's `SequenceKey`, and delivers them to the destination's `ZoneChannel<M>`, where
+//! systems read them during the next tick. The order is therefore the same whether zones
+//! ran in parallel or not.
+//!
+//! Entities move with the built-in `Migrate` message: the host spawns a new entity in the
+//! destination, copies every component over, destroys the original and records the
+//! mapping in `migrations()`.
+//!
+//! Zones are meant for authoritative servers and don't roll back; messages already
+//! delivered are not re-sent if a single zone is rolled back.
+//!
+//! # Example
+//! ```ignore
+//! let mut host = MultiWorldHost::new();
+//! host.insert(ZoneId(0), world_a);
+//! host.insert(ZoneId(1), world_b);
+//! host.register_message::<Chat>();
+//!
+//! let border = host.zone_mut(ZoneId(0)).unwrap().zone_sender::<Migrate>();
+//! border.send(ZoneId(1), Migrate { entity: player });
+//! host.advance();
+//! ```
+
+use crate::component::Destroyed;
+use crate::entity::Entity;
+use crate::sequence::{Lane, SequencedLanes};
+use crate::world::World;
+use std::any::TypeId;
+use std::cell::UnsafeCell;
+use std::collections::BTreeMap;
+use std::rc::Rc;
+
+/// Identifies a zone within a `MultiWorldHost`.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub struct ZoneId(pub u32);
+
+/// Asks the host to move `entity` from the sending zone to the destination zone.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Migrate {
+    pub entity: Entity,
+}
+
+/// One completed migration.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Migration {
+    pub from: ZoneId,
+    /// The entity in the source zone, destroyed by the migration.
+    pub old: Entity,
+    pub to: ZoneId,
+    /// The entity spawned in the destination zone.
+    pub new: Entity,
+}
+
+/// Per-world channel for messages of type `M`: outgoing lanes plus the messages delivered
+/// for the current tick.
+pub struct ZoneChannel<M> {
+    outgoing: SequencedLanes<(ZoneId, M)>,
+    received: UnsafeCell<Vec<(ZoneId, M)>>,
+}
+
+impl<M> ZoneChannel<M> {
+    pub fn new() -> Self {
+        ZoneChannel {
+            outgoing: SequencedLanes::new(),
+            received: UnsafeCell::new(Vec::new()),
+        }
+    }
+
+    /// Creates a sending lane for the given stage hash.
+    pub fn sender(&self, stage: u64) -> ZoneSender<M> {
+        ZoneSender {
+            lane: self.outgoing.lane(stage),
+        }
+    }
+
+    /// Messages delivered to this zone for the current tick, with their source zone, in
+    /// delivery order.
+    pub fn received(&self) -> impl Iterator<Item = (ZoneId, &M)> {
+        unsafe { &*self.received.get() }
+            .iter()
+            .map(|(from, message)| (*from, message))
+    }
+
+    /// Number of messages delivered for the current tick.
+    pub fn received_len(&self) -> usize {
+        unsafe { (*self.received.get()).len() }
+    }
+
+    fn take_outgoing(&self) -> Vec<(ZoneId, M)> {
+        self.outgoing
+            .drain()
+            .into_iter()
+            .map(|(_, item)| item)
+            .collect()
+    }
+
+    fn deliver(&self, messages: Vec<(ZoneId, M)>) {
+        unsafe { *self.received.get() = messages };
+    }
+}
+
+impl<M> Default for ZoneChannel<M> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Sends messages of type `M` to other zones. Each sending stage owns its own lane.
+pub struct ZoneSender<M> {
+    lane: Rc<Lane<(ZoneId, M)>>,
+}
+
+impl<M> ZoneSender<M> {
+    /// Queues `message` for zone `to`. It is delivered after the current tick.
+    pub fn send(&self, to: ZoneId, message: M) {
+        self.lane.push((to, message));
+    }
+}
+
+/// Routes one message type between zones. Messages to unknown zones are dropped.
+type Router = fn(&mut BTreeMap<ZoneId, World>);
+
+fn route<M: 'static>(zones: &mut BTreeMap<ZoneId, World>) {
+    let mut inboxes: BTreeMap<ZoneId, Vec<(ZoneId, M)>> = BTreeMap::new();
+
+    // Zones are visited in id order and each zone's lanes are merged by SequenceKey
+    for (&from, world) in zones.iter_mut() {
+        for (to, message) in world.zone_channel::<M>().take_outgoing() {
+            inboxes.entry(to).or_default().push((from, message));
+        }
+    }
+
+    for (&id, world) in zones.iter_mut() {
+        let messages = inboxes.remove(&id).unwrap_or_default();
+        world.zone_channel::<M>().deliver(messages);
+    }
+}
+
+/// Owns and advances a set of zones, see the module docs.
+pub struct MultiWorldHost {
+    zones: BTreeMap<ZoneId, World>,
+    routers: Vec<(TypeId, Router)>,
+    migrations: Vec<Migration>,
+}
+
+impl MultiWorldHost {
+    pub fn new() -> Self {
+        MultiWorldHost {
+            zones: BTreeMap::new(),
+            routers: vec![(TypeId::of::<Migrate>(), route::<Migrate> as Router)],
+            migrations: Vec::new(),
+        }
+    }
+
+    /// Adds a zone, returning the world previously registered under `id`.
+    pub fn insert(&mut self, id: ZoneId, world: World) -> Option<World> {
+        self.zones.insert(id, world)
+    }
+
+    /// Removes a zone. Messages still addressed to it are dropped.
+    pub fn remove(&mut self, id: ZoneId) -> Option<World> {
+        self.zones.remove(&id)
+    }
+
+    pub fn zone(&self, id: ZoneId) -> Option<&World> {
+        self.zones.get(&id)
+    }
+
+    pub fn zone_mut(&mut self, id: ZoneId) -> Option<&mut World> {
+        self.zones.get_mut(&id)
+    }
+
+    /// Zone ids in ascending order.
+    pub fn zone_ids(&self) -> impl Iterator<Item = ZoneId> + '_ {
+        self.zones.keys().copied()
+    }
+
+    /// Enables routing of messages of type `M` between zones.
+    pub fn register_message<M: 'static>(&mut self) {
+        let id = TypeId::of::<M>();
+        if !self.routers.iter().any(|(t, _)| *t == id) {
+            self.routers.push((id, route::<M>));
+        }
+    }
+
+    /// Migrations performed by the last `advance`, in the order they were applied.
+    pub fn migrations(&self) -> &[Migration] {
+        &self.migrations
+    }
+
+    /// Runs one tick in every zone, in ascending id order, then exchanges messages.
+    pub fn advance(&mut self) {
+        for world in self.zones.values_mut() {
+            world.run();
+        }
+
+        self.exchange();
+    }
+
+    /// Like `advance`, but runs the zones concurrently. The result is identical.
+    #[cfg(feature = "parallel")]
+    pub fn advance_parallel(&mut self) {
+        struct ZonePtr(*mut World);
+        // SAFETY: each world is only touched by one task, and worlds share no `Rc`s
+        unsafe impl Send for ZonePtr {}
+
+        let zones: Vec<ZonePtr> = self
+            .zones
+            .values_mut()
+            .map(|w| ZonePtr(w as *mut World))
+            .collect();
+
+        rayon::scope(|scope| {
+            for zone in zones {
+                scope.spawn(move |_| {
+                    let zone = zone;
+                    unsafe { (*zone.0).run() };
+                });
+            }
+        });
+
+        self.exchange();
+    }
+
+    /// Delivers all queued messages and applies migrations.
+    fn exchange(&mut self) {
+        for &(_, router) in &self.routers {
+            router(&mut self.zones);
+        }
+
+        self.migrations.clear();
+        let ids: Vec<ZoneId> = self.zones.keys().copied().collect();
+
+        for to in ids {
+            let requests: Vec<(ZoneId, Entity)> = self.zones[&to]
+                .zone_channel_ref::<Migrate>()
+                .map(|c| c.received().map(|(from, m)| (from, m.entity)).collect())
+                .unwrap_or_default();
+
+            for (from, entity) in requests {
+                if let Some(new) = self.transfer(from, entity, to) {
+                    self.migrations.push(Migration {
+                        from,
+                        old: entity,
+                        to,
+                        new,
+                    });
+                }
+            }
+        }
+    }
+
+    /// Moves `entity` from zone `from` to zone `to` immediately: spawns a new entity in
+    /// `to`, copies every component and destroys the original. Returns the new entity, or
+    /// `None` if either zone is unknown or the entity no longer exists.
+    pub fn transfer(&mut self, from: ZoneId, entity: Entity, to: ZoneId) -> Option<Entity> {
+        if from == to || !self.zones.contains_key(&to) {
+            return None;
+        }
+
+        let mut source = self.zones.remove(&from)?;
+        let moved = Self::move_entity(&mut source, entity, self.zones.get_mut(&to)?);
+        self.zones.insert(from, source);
+        moved
+    }
+
+    fn move_entity(source: &mut World, entity: Entity, dest: &mut World) -> Option<Entity> {
+        let entities = source.get_storage::<Entity>();
+        let destroyed = source.get_storage::<Destroyed>();
+        // An entity already marked destroyed (e.g. migrated twice in one tick) stays put
+        let alive = unsafe { (*entities.get()).get(entity.index()) }
+            .is_some_and(|e| e.generation() == entity.generation())
+            && unsafe { (*destroyed.get()).get(entity.index()) }.is_none();
+        if !alive {
+            return None;
+        }
+
+        let new = dest.spawn();
+        let skip = [TypeId::of::<Entity>(), TypeId::of::<Destroyed>()];
+
+        for id in source.mask.iter() {
+            let storage = unsafe { source.storages[id].assume_init_ref() };
+            if !skip.contains(&storage.component_type_id()) {
+                storage.copy_to(entity.index(), dest, new);
+            }
+        }
+
+        source.destroy(entity);
+        Some(new)
+    }
+}
+
+impl Default for MultiWorldHost {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+#[path = "zone.tests.rs"]
+mod tests;
@@ -0,0 +1,170 @@
+use super::*;
+use crate::component::Component;
+
+#[derive(Component, Default, Clone, Debug, PartialEq)]
+struct Position {
+    x: i32,
+}
+
+#[derive(Component, Default, Clone, Debug, PartialEq)]
+struct Health {
+    hp: u32,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct Chat(&'static str);
+
+struct BorderSystem;
+
+fn world() -> World {
+    let mut world = World::new();
+    // Register storages up front so their cleanup systems are scheduled
+    world.get_storage::<Position>();
+    world.get_storage::<Health>();
+    world.build_scheduler();
+    world
+}
+
+fn host(zones: u32) -> MultiWorldHost {
+    let mut host = MultiWorldHost::new();
+    for id in 0..zones {
+        host.insert(ZoneId(id), world());
+    }
+    host.register_message::<Chat>();
+    host
+}
+
+fn received(host: &mut MultiWorldHost, id: ZoneId) -> Vec<(ZoneId, Chat)> {
+    let channel = host.zone_mut(id).unwrap().zone_channel::<Chat>();
+    channel
+        .received()
+        .map(|(from, m)| (from, m.clone()))
+        .collect()
+}
+
+#[test]
+fn test_messages_delivered_after_advance_in_zone_order() {
+    let mut host = host(3);
+    let from_two = host.zone_mut(ZoneId(2)).unwrap().zone_sender::<Chat>();
+    let from_one = host.zone_mut(ZoneId(1)).unwrap().zone_sender::<Chat>();
+
+    from_two.send(ZoneId(0), Chat("b"));
+    from_one.send(ZoneId(0), Chat("a"));
+    from_one.send(ZoneId(7), Chat("lost"));
+    assert!(received(&mut host, ZoneId(0)).is_empty());
+
+    host.advance();
+    assert_eq!(
+        received(&mut host, ZoneId(0)),
+        vec![(ZoneId(1), Chat("a")), (ZoneId(2), Chat("b"))]
+    );
+    assert!(received(&mut host, ZoneId(1)).is_empty());
+
+    // Delivered messages only last for one tick
+    host.advance();
+    assert!(received(&mut host, ZoneId(0)).is_empty());
+}
+
+#[test]
+fn test_messages_ordered_by_sequence_key_within_zone() {
+    let mut host = host(2);
+    let zone = host.zone_mut(ZoneId(1)).unwrap();
+    let system = zone.zone_sender_from::<Chat, BorderSystem>();
+    let external = zone.zone_sender::<Chat>();
+
+    system.send(ZoneId(0), Chat("system"));
+    external.send(ZoneId(0), Chat("external"));
+    host.advance();
+
+    assert_eq!(
+        received(&mut host, ZoneId(0)),
+        vec![(ZoneId(1), Chat("external")), (ZoneId(1), Chat("system"))]
+    );
+}
+
+#[test]
+fn test_transfer_copies_components_and_destroys_source() {
+    let mut host = host(2);
+    let source = host.zone_mut(ZoneId(0)).unwrap();
+    let e = source.spawn();
+    source.set(e, &Position { x: 4 });
+    source.set(e, &Health { hp: 9 });
+
+    let moved = host.transfer(ZoneId(0), e, ZoneId(1)).unwrap();
+    // Already marked destroyed, so a second transfer is refused
+    assert_eq!(host.transfer(ZoneId(0), e, ZoneId(1)), None);
+    assert_eq!(host.transfer(ZoneId(0), e, ZoneId(5)), None);
+
+    let dest = host.zone_mut(ZoneId(1)).unwrap();
+    let pos = dest.get_storage::<Position>();
+    let hp = dest.get_storage::<Health>();
+    assert_eq!(
+        unsafe { (*pos.get()).get(moved.index()) },
+        Some(&Position { x: 4 })
+    );
+    assert_eq!(
+        unsafe { (*hp.get()).get(moved.index()) },
+        Some(&Health { hp: 9 })
+    );
+
+    host.advance();
+    let source = host.zone_mut(ZoneId(0)).unwrap();
+    let pos = source.get_storage::<Position>();
+    assert_eq!(unsafe { (*pos.get()).get(e.index()) }, None);
+}
+
+#[test]
+fn test_migrate_message_moves_entity() {
+    let mut host = host(2);
+    let source = host.zone_mut(ZoneId(1)).unwrap();
+    let e = source.spawn();
+    source.set(e, &Position { x: -3 });
+    source
+        .zone_sender::<Migrate>()
+        .send(ZoneId(0), Migrate { entity: e });
+
+    host.advance();
+
+    let migration = host.migrations()[0];
+    assert_eq!(host.migrations().len(), 1);
+    assert_eq!(
+        (migration.from, migration.old, migration.to),
+        (ZoneId(1), e, ZoneId(0))
+    );
+
+    let dest = host.zone_mut(ZoneId(0)).unwrap();
+    let pos = dest.get_storage::<Position>();
+    assert_eq!(
+        unsafe { (*pos.get()).get(migration.new.index()) },
+        Some(&Position { x: -3 })
+    );
+
+    host.advance();
+    assert!(host.migrations().is_empty());
+}
+
+#[cfg(feature = "parallel")]
+#[test]
+fn test_parallel_advance_matches_sequential() {
+    fn exchange(parallel: bool) -> Vec<(ZoneId, Chat)> {
+        let mut host = host(4);
+        for id in (1..4).rev() {
+            let zone = host.zone_mut(ZoneId(id)).unwrap();
+            zone.zone_sender_from::<Chat, BorderSystem>()
+                .send(ZoneId(0), Chat("system"));
+            zone.zone_sender::<Chat>().send(ZoneId(0), Chat("external"));
+        }
+
+        if parallel {
+            host.advance_parallel();
+        } else {
+            host.advance();
+        }
+        received(&mut host, ZoneId(0))
+    }
+
+    let sequential = exchange(false);
+    assert_eq!(sequential.len(), 6);
+    assert_eq!(sequential[0], (ZoneId(1), Chat("external")));
+    assert_eq!(exchange(true), sequential);
+}